@@ -0,0 +1,147 @@
+//! rpn2tex-macros - compile-time RPN conversion
+//!
+//! [`rpn!`] converts an RPN string literal to LaTeX while the program is
+//! compiled, and [`rpn_expr!`] expands to its syntax tree. Both read the
+//! input as [`Expr::from_str`](rpn2tex::ast::Expr) does, with variables
+//! allowed, and an expression that does not convert is reported as a
+//! compile error on the literal.
+//!
+//! The expansion of [`rpn_expr!`] names `::rpn2tex`, so crates using it
+//! must depend on `rpn2tex` as well.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use rpn2tex::ast::Expr;
+use rpn2tex::span::Span;
+use rpn2tex::Compiler;
+use syn::{parse_macro_input, LitStr};
+
+/// Converts an RPN expression to LaTeX, giving a `&'static str`.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex_macros::rpn;
+///
+/// const AREA: &str = rpn!("5 3 + 2 *");
+/// assert_eq!(AREA, r"$( 5 + 3 ) \times 2$");
+/// ```
+///
+/// An expression that does not convert fails to compile:
+///
+/// ```compile_fail
+/// use rpn2tex_macros::rpn;
+///
+/// const BROKEN: &str = rpn!("5 +");
+/// ```
+#[proc_macro]
+pub fn rpn(input: TokenStream) -> TokenStream {
+    let literal = parse_macro_input!(input as LitStr);
+    match compiler().compile(&literal.value()) {
+        Ok(latex) => quote!(#latex).into(),
+        Err(error) => syn::Error::new(literal.span(), error)
+            .to_compile_error()
+            .into(),
+    }
+}
+
+/// Parses an RPN expression, giving the [`Expr`] it builds.
+///
+/// An `Expr` owns its numbers, names and operands, so it cannot be a
+/// constant; the expansion builds the checked tree when it is evaluated,
+/// with the spans the parser gave it.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::ast::Expr;
+/// use rpn2tex_macros::rpn_expr;
+///
+/// let expr: Expr = rpn_expr!("x 3 +");
+/// assert!(expr.structurally_eq(&(Expr::var("x") + Expr::num(3))));
+/// assert_eq!(expr, "x 3 +".parse().unwrap());
+/// ```
+#[proc_macro]
+pub fn rpn_expr(input: TokenStream) -> TokenStream {
+    let literal = parse_macro_input!(input as LitStr);
+    match compiler().parse(&literal.value()) {
+        Ok(expr) => build(&expr).into(),
+        Err(error) => syn::Error::new(literal.span(), error)
+            .to_compile_error()
+            .into(),
+    }
+}
+
+/// Returns the compiler the macros read their input with.
+fn compiler() -> Compiler {
+    Compiler::new().variables(true)
+}
+
+/// Writes an expression building `expr`.
+fn build(expr: &Expr) -> TokenStream2 {
+    match expr {
+        Expr::Number { span, value } => {
+            let span = build_span(*span);
+            quote! {
+                ::rpn2tex::ast::Expr::Number {
+                    span: #span,
+                    value: ::std::string::String::from(#value),
+                }
+            }
+        }
+        Expr::Variable { span, name } => {
+            let span = build_span(*span);
+            quote! {
+                ::rpn2tex::ast::Expr::Variable {
+                    span: #span,
+                    name: ::std::string::String::from(#name),
+                }
+            }
+        }
+        Expr::BinaryOp {
+            span,
+            operator,
+            left,
+            right,
+        } => {
+            let span = build_span(*span);
+            let left = build(left);
+            let right = build(right);
+            quote! {
+                ::rpn2tex::ast::Expr::BinaryOp {
+                    span: #span,
+                    operator: ::std::string::String::from(#operator),
+                    left: ::std::boxed::Box::new(#left),
+                    right: ::std::boxed::Box::new(#right),
+                }
+            }
+        }
+        Expr::Apply {
+            span,
+            operator,
+            operands,
+        } => {
+            let span = build_span(*span);
+            let operands = operands.iter().map(build);
+            quote! {
+                ::rpn2tex::ast::Expr::Apply {
+                    span: #span,
+                    operator: ::std::string::String::from(#operator),
+                    operands: ::std::vec![#(#operands),*],
+                }
+            }
+        }
+    }
+}
+
+/// Writes an expression building `span`.
+fn build_span(span: Span) -> TokenStream2 {
+    let Span {
+        start,
+        end,
+        line,
+        column,
+    } = span;
+    quote!(::rpn2tex::span::Span::new(#start, #end, #line, #column))
+}
@@ -0,0 +1,26 @@
+//! Integration tests for the `rpn!` and `rpn_expr!` macros.
+//!
+//! These tests check that the expansions agree with converting the same
+//! input at run time.
+
+use rpn2tex::ast::Expr;
+use rpn2tex::Compiler;
+use rpn2tex_macros::{rpn, rpn_expr};
+
+const NESTED: &str = rpn!("2 3 4 * + 5 -");
+
+#[test]
+fn test_rpn_is_a_constant() {
+    assert_eq!(NESTED, r"$2 + 3 \times 4 - 5$");
+    assert_eq!(rpn!("x 2 /"), r"$x \div 2$");
+}
+
+#[test]
+fn test_rpn_expr_matches_parse() {
+    let parsed = Compiler::new()
+        .variables(true)
+        .parse("1.5 rate *\n2 -")
+        .unwrap();
+    assert_eq!(rpn_expr!("1.5 rate *\n2 -"), parsed);
+    assert!(matches!(rpn_expr!("42"), Expr::Number { value, .. } if value == "42"));
+}
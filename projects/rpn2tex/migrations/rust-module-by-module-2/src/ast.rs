@@ -5,65 +5,114 @@
 //! information for error reporting and uses recursive structures to represent
 //! arbitrarily complex expressions.
 
+use crate::decimal::Decimal;
+use crate::error::Rpn2TexError;
+#[cfg(feature = "exact")]
+use crate::eval::{evaluate_exact, rational_to_decimal};
+use crate::infix::InfixGenerator;
+use crate::pipeline::Compiler;
+use crate::span::Span;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::{Add, Div, Mul, Sub};
+use std::str::FromStr;
+
+/// The position of nodes built in code rather than parsed from input.
+const BUILT: Span = Span::new(0, 0, 1, 1);
+
 /// An expression node in the Abstract Syntax Tree.
 ///
-/// Each variant includes position information (line and column, both 1-based)
-/// for precise error reporting. Numbers are stored as strings to preserve
+/// Each variant includes the [`Span`] of the token it was built from, for
+/// precise error reporting. Numbers are stored as strings to preserve
 /// exact representation for LaTeX output (e.g., "3.14" remains "3.14").
 ///
 /// # Examples
 ///
 /// ```
 /// use rpn2tex::ast::Expr;
+/// use rpn2tex::span::Span;
 ///
 /// // A simple number
 /// let num = Expr::Number {
-///     line: 1,
-///     column: 1,
+///     span: Span::new(0, 2, 1, 1),
 ///     value: "42".to_string(),
 /// };
 ///
 /// // A binary operation: 3 + 5
 /// let add = Expr::BinaryOp {
-///     line: 1,
-///     column: 5,
+///     span: Span::new(4, 5, 1, 5),
 ///     operator: "+".to_string(),
 ///     left: Box::new(Expr::Number {
-///         line: 1,
-///         column: 1,
+///         span: Span::new(0, 1, 1, 1),
 ///         value: "3".to_string(),
 ///     }),
 ///     right: Box::new(Expr::Number {
-///         line: 1,
-///         column: 3,
+///         span: Span::new(2, 3, 1, 3),
 ///         value: "5".to_string(),
 ///     }),
 /// };
 /// ```
+///
+/// Trees can also be built without spelling out spans, using
+/// [`num`](Self::num), [`var`](Self::var) and the arithmetic operators:
+///
+/// ```
+/// use rpn2tex::ast::Expr;
+/// use rpn2tex::latex::LaTeXGenerator;
+///
+/// let ast = (Expr::num(5) + Expr::var("x")) * Expr::num(2);
+/// assert_eq!(LaTeXGenerator::new().generate(&ast), r"$( 5 + x ) \times 2$");
+/// ```
+///
+/// An expression can be parsed from RPN with [`str::parse`], and displays
+/// in infix notation:
+///
+/// ```
+/// use rpn2tex::ast::Expr;
+///
+/// let ast: Expr = "5 x + 2 *".parse().unwrap();
+/// assert_eq!(ast.to_string(), "(5 + x) * 2");
+/// ```
+///
+/// Expressions serialize to JSON with a `type` tag naming the variant and
+/// the span's fields inline, e.g.
+/// `{"type":"Number","start":0,"end":2,"line":1,"column":1,"value":"42"}`.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type"))]
 pub enum Expr {
     /// A numeric literal.
     ///
     /// The value is stored as a string to preserve exact representation
     /// for LaTeX output without floating-point precision issues.
     Number {
-        /// 1-based line number where this number appears
-        line: u32,
-        /// 1-based column number where this number starts
-        column: u32,
+        /// Where this number appears in the source
+        #[cfg_attr(feature = "serde", serde(flatten))]
+        span: Span,
         /// String representation of the number (e.g., "3.14", "42")
         value: String,
     },
 
+    /// A variable, named by an identifier in the input.
+    Variable {
+        /// Where this variable appears in the source
+        #[cfg_attr(feature = "serde", serde(flatten))]
+        span: Span,
+        /// The identifier naming the variable (e.g., "x", "rate2")
+        name: String,
+    },
+
     /// A binary operation with left and right operands.
     ///
     /// The operator is one of: "+", "-", "*", "/"
     /// Left and right operands are boxed to allow recursive expression trees.
     BinaryOp {
-        /// 1-based line number where this operator appears
-        line: u32,
-        /// 1-based column number where this operator is located
-        column: u32,
+        /// Where this operator appears in the source
+        #[cfg_attr(feature = "serde", serde(flatten))]
+        span: Span,
         /// The operator symbol ("+", "-", "*", "/")
         operator: String,
         /// Left operand (evaluated first in RPN)
@@ -71,28 +120,144 @@ pub enum Expr {
         /// Right operand (evaluated second in RPN)
         right: Box<Expr>,
     },
+
+    /// A custom operator from an
+    /// [`OperatorTable`](crate::plugin::OperatorTable) applied to its
+    /// operands.
+    Apply {
+        /// Where this operator appears in the source
+        #[cfg_attr(feature = "serde", serde(flatten))]
+        span: Span,
+        /// The operator's spelling (e.g., "mod")
+        operator: String,
+        /// The operands, in the order they were pushed in RPN
+        operands: Vec<Expr>,
+    },
 }
 
 impl Expr {
+    /// Creates a number node with `value` as written by its
+    /// [`Display`](std::fmt::Display) implementation, so `Expr::num("3.10")`
+    /// keeps its trailing zero.
+    ///
+    /// Nodes built this way, and by [`var`](Self::var),
+    /// [`apply`](Self::apply) and the arithmetic operators, are placed at
+    /// the start of line 1.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `value` is not a decimal literal such as `42` or `-0.5`;
+    /// exponents, infinities and NaN cannot be written in RPN.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::ast::Expr;
+    ///
+    /// let half = Expr::num(0.5);
+    /// assert!(matches!(half, Expr::Number { ref value, .. } if value == "0.5"));
+    /// ```
+    #[must_use]
+    pub fn num(value: impl ToString) -> Self {
+        let value = value.to_string();
+        assert!(
+            Decimal::parse(&value).is_some(),
+            "{:?} is not a decimal number",
+            value
+        );
+        Self::Number { span: BUILT, value }
+    }
+
+    /// Creates a variable node named `name`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::ast::Expr;
+    /// use rpn2tex::python::PythonGenerator;
+    ///
+    /// let ast = Expr::var("rate") * Expr::num(12);
+    /// assert_eq!(PythonGenerator::new().generate(&ast), "rate * 12");
+    /// ```
+    #[must_use]
+    pub fn var(name: impl Into<String>) -> Self {
+        Self::Variable {
+            span: BUILT,
+            name: name.into(),
+        }
+    }
+
+    /// Creates a node applying the custom operator spelled `operator` to
+    /// `operands`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::ast::Expr;
+    /// use rpn2tex::infix::InfixGenerator;
+    ///
+    /// let ast = Expr::apply("mod", [Expr::num(7), Expr::num(3)]);
+    /// assert_eq!(InfixGenerator::new().generate(&ast), "mod(7, 3)");
+    /// ```
+    #[must_use]
+    pub fn apply(operator: impl Into<String>, operands: impl IntoIterator<Item = Expr>) -> Self {
+        Self::Apply {
+            span: BUILT,
+            operator: operator.into(),
+            operands: operands.into_iter().collect(),
+        }
+    }
+
+    /// Creates a binary operation node, for the arithmetic operators.
+    fn binary(operator: &str, left: Self, right: Self) -> Self {
+        Self::BinaryOp {
+            span: BUILT,
+            operator: operator.to_string(),
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+
+    /// Returns where this expression node is located in the source: its
+    /// own token, which for an operation is the operator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::lexer::Lexer;
+    /// use rpn2tex::parser::Parser;
+    /// use rpn2tex::span::Span;
+    ///
+    /// let ast = Parser::new(Lexer::new("12 3 +").tokenize().unwrap()).parse().unwrap();
+    /// assert_eq!(ast.span(), Span::new(5, 6, 1, 6));
+    /// ```
+    #[must_use]
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Number { span, .. }
+            | Self::Variable { span, .. }
+            | Self::BinaryOp { span, .. }
+            | Self::Apply { span, .. } => *span,
+        }
+    }
+
     /// Returns the line number where this expression node is located.
     ///
     /// # Examples
     ///
     /// ```
     /// use rpn2tex::ast::Expr;
+    /// use rpn2tex::span::Span;
     ///
     /// let num = Expr::Number {
-    ///     line: 5,
-    ///     column: 10,
+    ///     span: Span::new(49, 51, 5, 10),
     ///     value: "42".to_string(),
     /// };
     /// assert_eq!(num.line(), 5);
     /// ```
     #[must_use]
     pub fn line(&self) -> u32 {
-        match self {
-            Self::Number { line, .. } | Self::BinaryOp { line, .. } => *line,
-        }
+        self.span().line
     }
 
     /// Returns the column number where this expression node is located.
@@ -101,31 +266,540 @@ impl Expr {
     ///
     /// ```
     /// use rpn2tex::ast::Expr;
+    /// use rpn2tex::span::Span;
     ///
     /// let num = Expr::Number {
-    ///     line: 5,
-    ///     column: 10,
+    ///     span: Span::new(49, 51, 5, 10),
     ///     value: "42".to_string(),
     /// };
     /// assert_eq!(num.column(), 10);
     /// ```
     #[must_use]
     pub fn column(&self) -> u32 {
+        self.span().column
+    }
+
+    /// Compares two trees by shape, operators, and values, ignoring the
+    /// span of every node.
+    ///
+    /// Number values are compared as written, so `2` and `2.0` differ.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::lexer::Lexer;
+    /// use rpn2tex::parser::Parser;
+    ///
+    /// let parse = |input: &str| Parser::new(Lexer::new(input).tokenize().unwrap()).parse().unwrap();
+    /// let a = parse("5 3 +");
+    /// let b = parse("5\n   3   +");
+    /// assert_ne!(a, b);
+    /// assert!(a.structurally_eq(&b));
+    /// assert!(!a.structurally_eq(&parse("3 5 +")));
+    /// ```
+    #[must_use]
+    pub fn structurally_eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Number { value: a, .. }, Self::Number { value: b, .. }) => a == b,
+            (Self::Variable { name: a, .. }, Self::Variable { name: b, .. }) => a == b,
+            (
+                Self::BinaryOp {
+                    operator: a_operator,
+                    left: a_left,
+                    right: a_right,
+                    ..
+                },
+                Self::BinaryOp {
+                    operator: b_operator,
+                    left: b_left,
+                    right: b_right,
+                    ..
+                },
+            ) => {
+                a_operator == b_operator
+                    && a_left.structurally_eq(b_left)
+                    && a_right.structurally_eq(b_right)
+            }
+            (
+                Self::Apply {
+                    operator: a_operator,
+                    operands: a_operands,
+                    ..
+                },
+                Self::Apply {
+                    operator: b_operator,
+                    operands: b_operands,
+                    ..
+                },
+            ) => {
+                a_operator == b_operator
+                    && a_operands.len() == b_operands.len()
+                    && a_operands
+                        .iter()
+                        .zip(b_operands)
+                        .all(|(a, b)| a.structurally_eq(b))
+            }
+            _ => false,
+        }
+    }
+
+    /// Feeds the tree into `state` ignoring positions, consistently with
+    /// [`structurally_eq`](Self::structurally_eq): structurally equal trees
+    /// hash identically.
+    ///
+    /// To key a `HashMap` or `HashSet` by structure, wrap the tree in
+    /// [`Structural`].
+    pub fn structural_hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            Self::Number { value, .. } => {
+                0_u8.hash(state);
+                value.hash(state);
+            }
+            Self::Variable { name, .. } => {
+                1_u8.hash(state);
+                name.hash(state);
+            }
+            Self::BinaryOp {
+                operator,
+                left,
+                right,
+                ..
+            } => {
+                2_u8.hash(state);
+                operator.hash(state);
+                left.structural_hash(state);
+                right.structural_hash(state);
+            }
+            Self::Apply {
+                operator, operands, ..
+            } => {
+                3_u8.hash(state);
+                operator.hash(state);
+                operands.len().hash(state);
+                for operand in operands {
+                    operand.structural_hash(state);
+                }
+            }
+        }
+    }
+
+    /// Returns a 64-bit hash of the tree's structure that is the same on
+    /// every platform, build, and run, so it can be stored or compared
+    /// across processes.
+    ///
+    /// Like [`structural_hash`](Self::structural_hash), positions are
+    /// ignored. Hash the [`canonicalize`](Self::canonicalize)d tree to make
+    /// expressions that differ only in operand order collide too.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::lexer::Lexer;
+    /// use rpn2tex::parser::Parser;
+    ///
+    /// let parse = |input: &str| Parser::new(Lexer::new(input).tokenize().unwrap()).parse().unwrap();
+    /// assert_eq!(parse("5 3 +").stable_hash(), parse(" 5  3 + ").stable_hash());
+    /// assert_ne!(parse("5 3 +").stable_hash(), parse("3 5 +").stable_hash());
+    /// assert_eq!(
+    ///     parse("5 3 +").canonicalize().stable_hash(),
+    ///     parse("3 5 +").canonicalize().stable_hash()
+    /// );
+    /// ```
+    #[must_use]
+    pub fn stable_hash(&self) -> u64 {
+        let mut hash = Fnv1a::new();
+        self.write_stable(&mut hash);
+        hash.finish()
+    }
+
+    /// Feeds the tree into `hash` in a fixed, self-delimiting encoding.
+    fn write_stable(&self, hash: &mut Fnv1a) {
+        match self {
+            Self::Number { value, .. } => {
+                hash.write(&[0]);
+                hash.write_text(value);
+            }
+            Self::Variable { name, .. } => {
+                hash.write(&[1]);
+                hash.write_text(name);
+            }
+            Self::BinaryOp {
+                operator,
+                left,
+                right,
+                ..
+            } => {
+                hash.write(&[2]);
+                hash.write_text(operator);
+                left.write_stable(hash);
+                right.write_stable(hash);
+            }
+            Self::Apply {
+                operator, operands, ..
+            } => {
+                hash.write(&[3]);
+                hash.write_text(operator);
+                hash.write(&(operands.len() as u64).to_le_bytes());
+                for operand in operands {
+                    operand.write_stable(hash);
+                }
+            }
+        }
+    }
+
+    /// Returns a copy of the tree in a canonical form, so expressions that
+    /// differ only in the order or grouping of `+` and `*` operands become
+    /// identical.
+    ///
+    /// Each chain of the same associative operator (`a + (b + c)`,
+    /// `(c * a) * b`) is flattened, its operands are sorted, and it is
+    /// rebuilt left to right. Operands sort as numbers by value, then
+    /// variables by name, then compound subexpressions. `-` and `/` are
+    /// neither reordered nor regrouped. Rebuilt operator nodes take the
+    /// position of the chain's outermost operator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::latex::LaTeXGenerator;
+    /// use rpn2tex::lexer::Lexer;
+    /// use rpn2tex::parser::Parser;
+    ///
+    /// let parse = |input: &str| {
+    ///     let tokens = Lexer::new(input).with_identifiers(true).tokenize().unwrap();
+    ///     Parser::new(tokens).parse().unwrap()
+    /// };
+    /// let generator = LaTeXGenerator::new();
+    /// let a = parse("y x 2 + +").canonicalize();
+    /// let b = parse("2 y + x +").canonicalize();
+    /// assert_eq!(generator.generate(&a), "$2 + x + y$");
+    /// assert_eq!(generator.generate(&a), generator.generate(&b));
+    /// ```
+    #[must_use]
+    pub fn canonicalize(&self) -> Self {
+        if let Self::Apply {
+            span,
+            operator,
+            operands,
+        } = self
+        {
+            return Self::Apply {
+                span: *span,
+                operator: operator.clone(),
+                operands: operands.iter().map(Self::canonicalize).collect(),
+            };
+        }
+        let Self::BinaryOp {
+            span,
+            operator,
+            left,
+            right,
+        } = self
+        else {
+            return self.clone();
+        };
+
+        if !matches!(operator.as_str(), "+" | "*") {
+            return Self::BinaryOp {
+                span: *span,
+                operator: operator.clone(),
+                left: Box::new(left.canonicalize()),
+                right: Box::new(right.canonicalize()),
+            };
+        }
+
+        let mut operands = Vec::new();
+        self.collect_chain(operator, &mut operands);
+        let mut operands: Vec<Self> = operands.into_iter().map(Self::canonicalize).collect();
+        operands.sort_by(Self::canonical_cmp);
+
+        let mut operands = operands.into_iter();
+        let first = operands.next().expect("a chain has at least two operands");
+        operands.fold(first, |chain, operand| Self::BinaryOp {
+            span: *span,
+            operator: operator.clone(),
+            left: Box::new(chain),
+            right: Box::new(operand),
+        })
+    }
+
+    /// Appends the operands of the chain of `operator` rooted at this node,
+    /// left to right.
+    fn collect_chain<'a>(&'a self, operator: &str, operands: &mut Vec<&'a Self>) {
         match self {
-            Self::Number { column, .. } | Self::BinaryOp { column, .. } => *column,
+            Self::BinaryOp {
+                operator: op,
+                left,
+                right,
+                ..
+            } if op == operator => {
+                left.collect_chain(operator, operands);
+                right.collect_chain(operator, operands);
+            }
+            _ => operands.push(self),
+        }
+    }
+
+    /// The total order used to sort the operands of a canonical chain.
+    fn canonical_cmp(&self, other: &Self) -> Ordering {
+        fn rank(expr: &Expr) -> u8 {
+            match expr {
+                Expr::Number { .. } => 0,
+                Expr::Variable { .. } => 1,
+                Expr::BinaryOp { .. } => 2,
+                Expr::Apply { .. } => 3,
+            }
+        }
+
+        match (self, other) {
+            (Self::Number { value: a, .. }, Self::Number { value: b, .. }) => {
+                match (Decimal::parse(a), Decimal::parse(b)) {
+                    (Some(x), Some(y)) => x.cmp(&y).then_with(|| a.cmp(b)),
+                    _ => a.cmp(b),
+                }
+            }
+            (Self::Variable { name: a, .. }, Self::Variable { name: b, .. }) => a.cmp(b),
+            (
+                Self::BinaryOp {
+                    operator: a_operator,
+                    left: a_left,
+                    right: a_right,
+                    ..
+                },
+                Self::BinaryOp {
+                    operator: b_operator,
+                    left: b_left,
+                    right: b_right,
+                    ..
+                },
+            ) => a_operator
+                .cmp(b_operator)
+                .then_with(|| a_left.canonical_cmp(b_left))
+                .then_with(|| a_right.canonical_cmp(b_right)),
+            (
+                Self::Apply {
+                    operator: a_operator,
+                    operands: a_operands,
+                    ..
+                },
+                Self::Apply {
+                    operator: b_operator,
+                    operands: b_operands,
+                    ..
+                },
+            ) => a_operator.cmp(b_operator).then_with(|| {
+                a_operands
+                    .iter()
+                    .zip(b_operands)
+                    .map(|(a, b)| a.canonical_cmp(b))
+                    .find(|ordering| ordering.is_ne())
+                    .unwrap_or_else(|| a_operands.len().cmp(&b_operands.len()))
+            }),
+            _ => rank(self).cmp(&rank(other)),
         }
     }
+
+    /// Returns a copy of the tree with every operation on two numbers
+    /// replaced by its result, so `2 3 * x +` becomes `6 x +`.
+    ///
+    /// Folding is exact: an operation is only replaced when its result can
+    /// be written as a terminating decimal, so `1 3 /` and divisions by
+    /// zero are left as written. A folded number takes the position of the
+    /// operator it replaces. This needs the `exact` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::lexer::Lexer;
+    /// use rpn2tex::parser::Parser;
+    /// use rpn2tex::notation::{Notation, NotationGenerator};
+    ///
+    /// let tokens = Lexer::new("x 2 3 * 1 4 / + -").with_identifiers(true).tokenize().unwrap();
+    /// let ast = Parser::new(tokens).parse().unwrap();
+    /// let folded = ast.fold_constants();
+    /// assert_eq!(NotationGenerator::new(Notation::Postfix).generate(&folded), "x 6.25 -");
+    /// ```
+    #[cfg(feature = "exact")]
+    #[must_use]
+    pub fn fold_constants(&self) -> Self {
+        match self {
+            Self::Number { .. } | Self::Variable { .. } => self.clone(),
+            Self::BinaryOp {
+                span,
+                operator,
+                left,
+                right,
+            } => {
+                let left = left.fold_constants();
+                let right = right.fold_constants();
+                let both_numbers =
+                    matches!(left, Self::Number { .. }) && matches!(right, Self::Number { .. });
+                let folded = Self::BinaryOp {
+                    span: *span,
+                    operator: operator.clone(),
+                    left: Box::new(left),
+                    right: Box::new(right),
+                };
+                if !both_numbers {
+                    return folded;
+                }
+
+                let value = evaluate_exact(&folded)
+                    .ok()
+                    .and_then(|value| rational_to_decimal(&value));
+                match value {
+                    Some(value) => Self::Number { span: *span, value },
+                    None => folded,
+                }
+            }
+            // A custom operator's value is unknown here, but its operands
+            // may still fold
+            Self::Apply {
+                span,
+                operator,
+                operands,
+            } => Self::Apply {
+                span: *span,
+                operator: operator.clone(),
+                operands: operands.iter().map(Self::fold_constants).collect(),
+            },
+        }
+    }
+}
+
+/// Parses one RPN expression, which may use variables, with the default
+/// settings of a [`Compiler`].
+impl FromStr for Expr {
+    type Err = Rpn2TexError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        Compiler::new().variables(true).parse(source)
+    }
+}
+
+impl TryFrom<&str> for Expr {
+    type Error = Rpn2TexError;
+
+    fn try_from(source: &str) -> Result<Self, Self::Error> {
+        source.parse()
+    }
+}
+
+/// Writes the expression in infix notation, as [`InfixGenerator`] does.
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&InfixGenerator::new().generate(self))
+    }
+}
+
+impl Add for Expr {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::binary("+", self, rhs)
+    }
+}
+
+impl Sub for Expr {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self::binary("-", self, rhs)
+    }
+}
+
+impl Mul for Expr {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self::binary("*", self, rhs)
+    }
+}
+
+impl Div for Expr {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self {
+        Self::binary("/", self, rhs)
+    }
+}
+
+/// The 64-bit FNV-1a hash, whose output is fixed by its definition rather
+/// than by the standard library's hasher, which may change between releases.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    const fn new() -> Self {
+        Self(Self::OFFSET_BASIS)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(Self::PRIME);
+        }
+    }
+
+    /// Writes `text` prefixed with its length, so adjacent strings cannot
+    /// run together.
+    fn write_text(&mut self, text: &str) {
+        self.write(&(text.len() as u64).to_le_bytes());
+        self.write(text.as_bytes());
+    }
+
+    const fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// A borrowed tree whose equality and hash ignore positions, for
+/// deduplicating expressions in hashed collections.
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::HashSet;
+/// use rpn2tex::ast::Structural;
+/// use rpn2tex::lexer::Lexer;
+/// use rpn2tex::parser::Parser;
+///
+/// let parse = |input: &str| Parser::new(Lexer::new(input).tokenize().unwrap()).parse().unwrap();
+/// let trees = [parse("1 2 +"), parse("1   2 +"), parse("2 1 +")];
+/// let distinct: HashSet<Structural> = trees.iter().map(Structural).collect();
+/// assert_eq!(distinct.len(), 2);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Structural<'a>(pub &'a Expr);
+
+impl PartialEq for Structural<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.structurally_eq(other.0)
+    }
+}
+
+impl Eq for Structural<'_> {}
+
+impl Hash for Structural<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.structural_hash(state);
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::notation::{Notation, NotationGenerator};
+    use crate::test_util::parse;
+    use std::collections::hash_map::DefaultHasher;
+    use std::collections::HashSet;
 
     #[test]
     fn test_number_creation() {
         let num = Expr::Number {
-            line: 1,
-            column: 5,
+            span: Span::new(4, 6, 1, 5),
             value: "42".to_string(),
         };
 
@@ -142,8 +816,7 @@ mod tests {
     #[test]
     fn test_number_with_decimal() {
         let num = Expr::Number {
-            line: 2,
-            column: 10,
+            span: Span::new(15, 22, 2, 10),
             value: "3.14159".to_string(),
         };
 
@@ -157,20 +830,17 @@ mod tests {
     #[test]
     fn test_binary_op_creation() {
         let left = Expr::Number {
-            line: 1,
-            column: 1,
+            span: Span::new(0, 1, 1, 1),
             value: "5".to_string(),
         };
 
         let right = Expr::Number {
-            line: 1,
-            column: 3,
+            span: Span::new(2, 3, 1, 3),
             value: "3".to_string(),
         };
 
         let add = Expr::BinaryOp {
-            line: 1,
-            column: 5,
+            span: Span::new(4, 5, 1, 5),
             operator: "+".to_string(),
             left: Box::new(left),
             right: Box::new(right),
@@ -202,29 +872,24 @@ mod tests {
     fn test_nested_binary_ops() {
         // Build: (5 + 3) * 2
         let add = Expr::BinaryOp {
-            line: 1,
-            column: 5,
+            span: Span::new(4, 5, 1, 5),
             operator: "+".to_string(),
             left: Box::new(Expr::Number {
-                line: 1,
-                column: 1,
+                span: Span::new(0, 1, 1, 1),
                 value: "5".to_string(),
             }),
             right: Box::new(Expr::Number {
-                line: 1,
-                column: 3,
+                span: Span::new(2, 3, 1, 3),
                 value: "3".to_string(),
             }),
         };
 
         let mult = Expr::BinaryOp {
-            line: 1,
-            column: 7,
+            span: Span::new(6, 7, 1, 7),
             operator: "*".to_string(),
             left: Box::new(add),
             right: Box::new(Expr::Number {
-                line: 1,
-                column: 9,
+                span: Span::new(8, 9, 1, 9),
                 value: "2".to_string(),
             }),
         };
@@ -245,17 +910,14 @@ mod tests {
 
         for op in operators {
             let expr = Expr::BinaryOp {
-                line: 1,
-                column: 1,
+                span: Span::new(0, 0, 1, 1),
                 operator: op.to_string(),
                 left: Box::new(Expr::Number {
-                    line: 1,
-                    column: 1,
+                    span: Span::new(0, 1, 1, 1),
                     value: "1".to_string(),
                 }),
                 right: Box::new(Expr::Number {
-                    line: 1,
-                    column: 1,
+                    span: Span::new(0, 1, 1, 1),
                     value: "2".to_string(),
                 }),
             };
@@ -271,14 +933,12 @@ mod tests {
     #[test]
     fn test_expr_equality() {
         let expr1 = Expr::Number {
-            line: 1,
-            column: 1,
+            span: Span::new(0, 2, 1, 1),
             value: "42".to_string(),
         };
 
         let expr2 = Expr::Number {
-            line: 1,
-            column: 1,
+            span: Span::new(0, 2, 1, 1),
             value: "42".to_string(),
         };
 
@@ -288,14 +948,12 @@ mod tests {
     #[test]
     fn test_expr_inequality() {
         let expr1 = Expr::Number {
-            line: 1,
-            column: 1,
+            span: Span::new(0, 2, 1, 1),
             value: "42".to_string(),
         };
 
         let expr2 = Expr::Number {
-            line: 1,
-            column: 1,
+            span: Span::new(0, 2, 1, 1),
             value: "43".to_string(),
         };
 
@@ -305,8 +963,7 @@ mod tests {
     #[test]
     fn test_clone() {
         let original = Expr::Number {
-            line: 1,
-            column: 5,
+            span: Span::new(4, 8, 1, 5),
             value: "3.14".to_string(),
         };
 
@@ -314,11 +971,188 @@ mod tests {
         assert_eq!(original, cloned);
     }
 
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json_round_trip() {
+        let expr = Expr::BinaryOp {
+            span: Span::new(4, 5, 1, 5),
+            operator: "+".to_string(),
+            left: Box::new(Expr::Number {
+                span: Span::new(0, 1, 1, 1),
+                value: "5".to_string(),
+            }),
+            right: Box::new(Expr::Number {
+                span: Span::new(2, 3, 1, 3),
+                value: "3".to_string(),
+            }),
+        };
+
+        let json = serde_json::to_string(&expr).expect("serialize failed");
+        assert!(json.starts_with(
+            r#"{"type":"BinaryOp","start":4,"end":5,"line":1,"column":5,"operator":"+""#
+        ));
+        assert!(
+            json.contains(r#"{"type":"Number","start":0,"end":1,"line":1,"column":1,"value":"5"}"#)
+        );
+
+        let back: Expr = serde_json::from_str(&json).expect("deserialize failed");
+        assert_eq!(back, expr);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_variable() {
+        let var = Expr::Variable {
+            span: Span::new(9, 10, 2, 4),
+            name: "x".to_string(),
+        };
+        assert_eq!(var.line(), 2);
+        assert_eq!(var.column(), 4);
+
+        let json = serde_json::to_string(&var).expect("serialize failed");
+        assert_eq!(
+            json,
+            r#"{"type":"Variable","start":9,"end":10,"line":2,"column":4,"name":"x"}"#
+        );
+    }
+
+    #[cfg(feature = "exact")]
+    fn fold(input: &str) -> String {
+        NotationGenerator::new(Notation::Postfix).generate(&parse(input).fold_constants())
+    }
+
+    #[cfg(feature = "exact")]
+    #[test]
+    fn test_fold_constants() {
+        assert_eq!(fold("5 3 + 2 *"), "16");
+        assert_eq!(fold("x 2 3 * +"), "x 6 +");
+        assert_eq!(fold("2 x * 3 4 * *"), "2 x * 12 *");
+        assert_eq!(fold("0.1 0.2 + y /"), "0.3 y /");
+        assert_eq!(fold("-1.5 2 *"), "-3");
+    }
+
+    #[cfg(feature = "exact")]
+    #[test]
+    fn test_fold_constants_keeps_inexact_and_undefined() {
+        assert_eq!(fold("1 3 / x +"), "1 3 / x +");
+        assert_eq!(fold("1 0 / 2 3 + *"), "1 0 / 5 *");
+    }
+
+    #[cfg(feature = "exact")]
+    #[test]
+    fn test_fold_constants_position() {
+        let folded = Expr::BinaryOp {
+            span: Span::new(10, 11, 2, 5),
+            operator: "*".to_string(),
+            left: Box::new(Expr::Number {
+                span: Span::new(6, 7, 2, 1),
+                value: "2".to_string(),
+            }),
+            right: Box::new(Expr::Number {
+                span: Span::new(8, 9, 2, 3),
+                value: "3".to_string(),
+            }),
+        }
+        .fold_constants();
+        assert_eq!(
+            folded,
+            Expr::Number {
+                span: Span::new(10, 11, 2, 5),
+                value: "6".to_string(),
+            }
+        );
+    }
+
+    fn structural_hash(expr: &Expr) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        expr.structural_hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_structurally_eq_ignores_positions() {
+        let a = parse("x 3 + 2 *");
+        let b = parse("\n\n  x   3 +\n 2 *");
+        assert_ne!(a, b);
+        assert!(a.structurally_eq(&b));
+        assert_eq!(structural_hash(&a), structural_hash(&b));
+    }
+
+    #[test]
+    fn test_structurally_eq_compares_content() {
+        let a = parse("x 3 +");
+        for other in ["3 x +", "x 3 -", "x 3.0 +", "y 3 +", "x", "3"] {
+            assert!(!a.structurally_eq(&parse(other)), "{}", other);
+        }
+        // A number and a variable never match, even with equal text
+        assert_ne!(structural_hash(&parse("x")), structural_hash(&parse("3")));
+    }
+
+    #[test]
+    fn test_stable_hash_is_fixed() {
+        // These values must never change: hashes may be persisted
+        let mut empty = Fnv1a::new();
+        empty.write(b"");
+        assert_eq!(empty.finish(), 0xcbf2_9ce4_8422_2325);
+        let mut a = Fnv1a::new();
+        a.write(b"a");
+        assert_eq!(a.finish(), 0xaf63_dc4c_8601_ec8c);
+
+        assert_eq!(parse("5 3 +").stable_hash(), parse("5\n3\n+").stable_hash());
+        assert_ne!(parse("5 3 +").stable_hash(), parse("5 3 -").stable_hash());
+        assert_ne!(parse("x").stable_hash(), parse("3").stable_hash());
+        // Length prefixes keep "12" "3" apart from "1" "23"
+        assert_ne!(parse("12 3 +").stable_hash(), parse("1 23 +").stable_hash());
+    }
+
+    #[test]
+    fn test_structural_dedup() {
+        let trees: Vec<Expr> = ["1 2 +", " 1 2 +", "1 2 *", "1\n2\n*"]
+            .iter()
+            .map(|input| parse(input))
+            .collect();
+        let distinct: HashSet<Structural> = trees.iter().map(Structural).collect();
+        assert_eq!(distinct.len(), 2);
+    }
+
+    fn canonical(input: &str) -> String {
+        NotationGenerator::new(Notation::Postfix).generate(&parse(input).canonicalize())
+    }
+
+    #[test]
+    fn test_canonicalize_sorts_and_flattens() {
+        assert_eq!(canonical("b a +"), "a b +");
+        assert_eq!(canonical("c b a + +"), "a b + c +");
+        assert_eq!(canonical("x 10 * 2 *"), "2 10 * x *");
+        assert_eq!(canonical("0.5 -1 3 + +"), "-1 0.5 + 3 +");
+    }
+
+    #[test]
+    fn test_canonicalize_equivalent_forms_match() {
+        let forms = ["a b c * +", "c b * a +", "b c * a +", "a c b * +"];
+        for form in forms {
+            assert_eq!(canonical(form), "a b c * +", "{}", form);
+        }
+    }
+
+    #[test]
+    fn test_canonicalize_keeps_non_commutative_order() {
+        assert_eq!(canonical("b a -"), "b a -");
+        assert_eq!(canonical("y x + b a * /"), "x y + a b * /");
+        // A difference inside a sum is one operand, not part of the chain
+        assert_eq!(canonical("z y x - +"), "z y x - +");
+    }
+
+    #[test]
+    fn test_canonicalize_orders_compound_operands() {
+        assert_eq!(canonical("b a * x 2 - +"), "a b * x 2 - +");
+        assert_eq!(canonical("x 2 - 3 +"), "3 x 2 - +");
+    }
+
     #[test]
     fn test_negative_numbers() {
         let num = Expr::Number {
-            line: 1,
-            column: 1,
+            span: Span::new(0, 3, 1, 1),
             value: "-42".to_string(),
         };
 
@@ -332,14 +1166,12 @@ mod tests {
     #[test]
     fn test_position_tracking() {
         let num1 = Expr::Number {
-            line: 3,
-            column: 7,
+            span: Span::new(20, 23, 3, 7),
             value: "100".to_string(),
         };
 
         let num2 = Expr::Number {
-            line: 5,
-            column: 12,
+            span: Span::new(41, 44, 5, 12),
             value: "200".to_string(),
         };
 
@@ -348,4 +1180,35 @@ mod tests {
         assert_eq!(num2.line(), 5);
         assert_eq!(num2.column(), 12);
     }
+
+    #[test]
+    fn test_built_expressions() {
+        let built = Expr::num(10) - (Expr::num("6.0") - Expr::var("y")) / Expr::num(-2.5);
+        assert!(built.structurally_eq(&parse("10 6.0 y - -2.5 / -")));
+        assert_eq!(built.span(), Span::new(0, 0, 1, 1));
+
+        let applied = Expr::apply("max", vec![Expr::num(1), Expr::num(2) + Expr::num(3)]);
+        assert_eq!(
+            NotationGenerator::new(Notation::Postfix).generate(&applied),
+            "1 2 3 + max"
+        );
+    }
+
+    #[test]
+    fn test_from_str_and_display() {
+        let ast: Expr = "10 6 y - -".parse().unwrap();
+        assert!(ast.structurally_eq(&parse("10 6 y - -")));
+        assert_eq!(ast.to_string(), "10 - (6 - y)");
+        assert_eq!(Expr::try_from("2.50 3 /").unwrap().to_string(), "2.50 / 3");
+
+        let error = Expr::try_from("5 +").unwrap_err();
+        assert!(matches!(error, Rpn2TexError::Parser(_)));
+        assert!("5 @".parse::<Expr>().is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "not a decimal number")]
+    fn test_num_rejects_non_decimal() {
+        let _ = Expr::num(f64::INFINITY);
+    }
 }
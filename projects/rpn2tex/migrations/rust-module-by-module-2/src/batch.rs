@@ -0,0 +1,250 @@
+//! Inputs holding several expressions, one per line or separated by `;`.
+//!
+//! [`split_expressions`] divides such an input into its expressions, keeping
+//! each one's position in the whole source. [`parse_all`] and [`compile_all`]
+//! process every expression independently, so one malformed expression does
+//! not hide the results of the others.
+
+use crate::ast::Expr;
+use crate::cache::CompilerCache;
+use crate::codegen::CodeGenerator;
+use crate::error::Rpn2TexError;
+use crate::latex::LaTeXGenerator;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+
+/// One expression of a multi-expression input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Segment<'a> {
+    /// The expression's text, without its separator
+    pub text: &'a str,
+    /// Byte offset where the text starts in the whole source
+    pub offset: usize,
+    /// 1-based line number where the text starts in the whole source
+    pub line: u32,
+    /// 1-based column number where the text starts in the whole source
+    pub column: u32,
+}
+
+impl<'a> Segment<'a> {
+    /// Returns the segment spanning all of `source`.
+    #[must_use]
+    pub const fn whole(source: &'a str) -> Self {
+        Self {
+            text: source,
+            offset: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    /// Returns a lexer for this expression whose positions refer to the
+    /// whole source.
+    #[must_use]
+    pub fn lexer(&self) -> Lexer<'a> {
+        Lexer::new(self.text).with_start(self.offset, self.line, self.column)
+    }
+}
+
+/// Error for one expression of a multi-expression input, now the crate's
+/// single error type.
+#[deprecated(note = "use `Rpn2TexError`")]
+pub type CompileError = Rpn2TexError;
+
+/// Splits an input into its expressions.
+///
+/// Expressions end at a newline or a `;`. A `;` inside a comment does not
+/// end one, and segments holding only whitespace and comments are dropped.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::batch::split_expressions;
+///
+/// let segments = split_expressions("5 3 +; 2 4 *\n\n# note\n7");
+/// let texts: Vec<&str> = segments.iter().map(|s| s.text).collect();
+/// assert_eq!(texts, ["5 3 +", " 2 4 *", "7"]);
+/// assert_eq!((segments[1].line, segments[1].column), (1, 7));
+/// assert_eq!((segments[2].line, segments[2].column), (4, 1));
+/// ```
+#[must_use]
+pub fn split_expressions(source: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let (mut start, mut start_line, mut start_column) = (0, 1, 1);
+    let (mut line, mut column) = (1, 1);
+    let mut in_comment = false;
+    let mut has_content = false;
+
+    for (index, ch) in source.char_indices() {
+        let ends_segment = ch == '\n' || (ch == ';' && !in_comment);
+        if ends_segment {
+            if has_content {
+                segments.push(Segment {
+                    text: &source[start..index],
+                    offset: start,
+                    line: start_line,
+                    column: start_column,
+                });
+            }
+            has_content = false;
+            in_comment = false;
+        } else if ch == '#' {
+            in_comment = true;
+        } else if !in_comment && !ch.is_whitespace() {
+            has_content = true;
+        }
+
+        if ch == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+        if ends_segment {
+            start = index + ch.len_utf8();
+            start_line = line;
+            start_column = column;
+        }
+    }
+
+    if has_content {
+        segments.push(Segment {
+            text: &source[start..],
+            offset: start,
+            line: start_line,
+            column: start_column,
+        });
+    }
+    segments
+}
+
+/// Parses every expression of an input, independently.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::batch::parse_all;
+///
+/// let results = parse_all("5 3 +\n4 *\n2");
+/// assert!(results[0].is_ok());
+/// assert_eq!(results[1].as_ref().unwrap_err().message(), "Too few operands for operator");
+/// assert!(results[2].is_ok());
+/// ```
+#[must_use]
+pub fn parse_all(source: &str) -> Vec<Result<Expr, Rpn2TexError>> {
+    split_expressions(source)
+        .iter()
+        .map(|segment| Parser::parse_stream(segment.lexer().iter()))
+        .collect()
+}
+
+/// Converts every expression of an input to LaTeX, independently.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::compile_all;
+///
+/// let results = compile_all("5 3 +; 2 @");
+/// assert_eq!(results[0], Ok("$5 + 3$".to_string()));
+/// assert_eq!(results[1].as_ref().unwrap_err().column(), 10);
+/// ```
+#[must_use]
+pub fn compile_all(source: &str) -> Vec<Result<String, Rpn2TexError>> {
+    compile_all_with(source, &LaTeXGenerator::new())
+}
+
+/// Converts every expression of an input with `generator`, independently.
+/// An expression repeated in the input is generated once, through a
+/// [`CompilerCache`].
+#[must_use]
+pub fn compile_all_with(
+    source: &str,
+    generator: &dyn CodeGenerator,
+) -> Vec<Result<String, Rpn2TexError>> {
+    let mut cache = CompilerCache::new(generator);
+    parse_all(source)
+        .into_iter()
+        .map(|result| result.map(|ast| cache.generate(&ast)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::{Notation, NotationGenerator};
+    use std::cell::Cell;
+
+    fn texts(source: &str) -> Vec<&str> {
+        split_expressions(source).iter().map(|s| s.text).collect()
+    }
+
+    #[test]
+    fn test_split_on_newlines_and_semicolons() {
+        assert_eq!(texts("1 2 +\n3 4 *"), ["1 2 +", "3 4 *"]);
+        assert_eq!(texts("1 2 +;3 4 *;"), ["1 2 +", "3 4 *"]);
+        assert_eq!(texts("1\r\n2"), ["1\r", "2"]);
+        assert!(texts("").is_empty());
+    }
+
+    #[test]
+    fn test_split_skips_blank_and_comment_segments() {
+        assert_eq!(texts("\n  \n;;\n# only a comment\n5"), ["5"]);
+        // A ';' inside a comment belongs to the comment
+        assert_eq!(texts("5 # a; b\n6"), ["5 # a; b", "6"]);
+    }
+
+    #[test]
+    fn test_split_positions() {
+        let segments = split_expressions("1 2 +\n\n  3 ; 4");
+        let positions: Vec<(u32, u32)> = segments.iter().map(|s| (s.line, s.column)).collect();
+        assert_eq!(positions, [(1, 1), (3, 1), (3, 6)]);
+    }
+
+    #[test]
+    fn test_errors_refer_to_whole_source() {
+        let results = compile_all("1 2 +\n3 @ +\n4 +");
+        assert_eq!(results[0], Ok("$1 + 2$".to_string()));
+
+        let lexer_error = results[1].as_ref().unwrap_err();
+        assert!(matches!(lexer_error, Rpn2TexError::Lexer(_)));
+        assert_eq!((lexer_error.line(), lexer_error.column()), (2, 3));
+
+        let parser_error = results[2].as_ref().unwrap_err();
+        assert!(matches!(parser_error, Rpn2TexError::Parser(_)));
+        assert_eq!((parser_error.line(), parser_error.column()), (3, 3));
+        assert_eq!(
+            parser_error.to_string(),
+            "Line 3, column 3: Too few operands for operator"
+        );
+    }
+
+    #[test]
+    fn test_compile_all_with_generator() {
+        let generator = NotationGenerator::new(Notation::Prefix);
+        let results = compile_all_with("1 2 +; 3 4 5 * -", &generator);
+        assert_eq!(
+            results,
+            [Ok("+ 1 2".to_string()), Ok("- 3 * 4 5".to_string())]
+        );
+    }
+
+    /// Counts how often it is asked to generate.
+    struct CountingGenerator(Cell<usize>);
+
+    impl CodeGenerator for CountingGenerator {
+        fn generate(&self, expr: &Expr) -> String {
+            self.0.set(self.0.get() + 1);
+            NotationGenerator::new(Notation::Prefix).generate(expr)
+        }
+    }
+
+    #[test]
+    fn test_compile_all_generates_repeats_once() {
+        let generator = CountingGenerator(Cell::new(0));
+        let results = compile_all_with("1 2 +\n3 4 *\n1  2 +; 1 2 +", &generator);
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[3], Ok("+ 1 2".to_string()));
+        assert_eq!(generator.0.get(), 2);
+    }
+}
@@ -0,0 +1,209 @@
+//! Memoized code generation for batches with repeated expressions.
+//!
+//! A [`CompilerCache`] wraps any [`CodeGenerator`] and remembers the output
+//! for every expression it has rendered, keyed by [`Expr::stable_hash`], so
+//! a batch job that sees the same expression many times generates it once.
+
+use crate::ast::Expr;
+use crate::codegen::CodeGenerator;
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+/// Counts of cache lookups since a [`CompilerCache`] was created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheStats {
+    /// Lookups answered from the cache
+    pub hits: u64,
+    /// Lookups that ran the generator
+    pub misses: u64,
+}
+
+impl CacheStats {
+    /// Returns the total number of lookups.
+    #[must_use]
+    pub const fn lookups(&self) -> u64 {
+        self.hits + self.misses
+    }
+}
+
+/// Memoizes a code generator's output by expression structure.
+///
+/// Expressions are matched ignoring positions, so `5 3 +` on line 1 and on
+/// line 40 share an entry. With [`with_canonicalize`](Self::with_canonicalize)
+/// expressions are canonicalized before lookup and generation, so `3 5 +`
+/// shares that entry too (and is rendered in canonical order).
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::cache::CompilerCache;
+/// use rpn2tex::latex::LaTeXGenerator;
+/// use rpn2tex::lexer::Lexer;
+/// use rpn2tex::parser::Parser;
+///
+/// let parse = |input: &str| Parser::new(Lexer::new(input).tokenize().unwrap()).parse().unwrap();
+/// let mut cache = CompilerCache::new(LaTeXGenerator::new());
+///
+/// assert_eq!(cache.generate(&parse("5 3 +")), "$5 + 3$");
+/// assert_eq!(cache.generate(&parse("5  3  +")), "$5 + 3$");
+/// assert_eq!(cache.stats().hits, 1);
+/// assert_eq!(cache.stats().misses, 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct CompilerCache<G> {
+    /// Backend producing the cached output
+    generator: G,
+    /// Whether expressions are canonicalized before lookup
+    canonicalize: bool,
+    /// Generated output by stable hash, with the expression it was
+    /// generated from to rule out hash collisions
+    entries: HashMap<u64, (Expr, String)>,
+    /// Lookup counts
+    stats: CacheStats,
+}
+
+impl<G: CodeGenerator> CompilerCache<G> {
+    /// Creates an empty cache around `generator`.
+    #[must_use]
+    pub fn new(generator: G) -> Self {
+        Self {
+            generator,
+            canonicalize: false,
+            entries: HashMap::new(),
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Sets whether expressions are [canonicalized](Expr::canonicalize)
+    /// before lookup, so operand order of `+` and `*` does not matter.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::cache::CompilerCache;
+    /// use rpn2tex::latex::LaTeXGenerator;
+    /// use rpn2tex::lexer::Lexer;
+    /// use rpn2tex::parser::Parser;
+    ///
+    /// let parse = |input: &str| Parser::new(Lexer::new(input).tokenize().unwrap()).parse().unwrap();
+    /// let mut cache = CompilerCache::new(LaTeXGenerator::new()).with_canonicalize(true);
+    ///
+    /// assert_eq!(cache.generate(&parse("5 3 +")), "$3 + 5$");
+    /// assert_eq!(cache.generate(&parse("3 5 +")), "$3 + 5$");
+    /// assert_eq!(cache.stats().hits, 1);
+    /// ```
+    #[must_use]
+    pub fn with_canonicalize(mut self, canonicalize: bool) -> Self {
+        self.canonicalize = canonicalize;
+        self
+    }
+
+    /// Returns the generator's output for `expr`, generating it only if no
+    /// structurally equal expression has been seen.
+    pub fn generate(&mut self, expr: &Expr) -> String {
+        let expr = if self.canonicalize {
+            Cow::Owned(expr.canonicalize())
+        } else {
+            Cow::Borrowed(expr)
+        };
+        let key = expr.stable_hash();
+
+        if let Some((cached, output)) = self.entries.get(&key) {
+            if cached.structurally_eq(&expr) {
+                self.stats.hits += 1;
+                return output.clone();
+            }
+        }
+
+        // On a hash collision the newer expression replaces the older one
+        self.stats.misses += 1;
+        let output = self.generator.generate(&expr);
+        self.entries
+            .insert(key, (expr.into_owned(), output.clone()));
+        output
+    }
+
+    /// Returns the lookup counts so far.
+    #[must_use]
+    pub const fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Returns the number of cached outputs.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether nothing has been cached.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Removes every cached output, keeping the lookup counts.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::{Notation, NotationGenerator};
+    use crate::test_util::parse;
+    use std::cell::Cell;
+
+    /// Counts how often the cache falls through to the generator.
+    struct CountingGenerator<'a>(&'a Cell<usize>);
+
+    impl CodeGenerator for CountingGenerator<'_> {
+        fn generate(&self, expr: &Expr) -> String {
+            self.0.set(self.0.get() + 1);
+            NotationGenerator::new(Notation::Postfix).generate(expr)
+        }
+    }
+
+    #[test]
+    fn test_repeated_expressions_generate_once() {
+        let calls = Cell::new(0);
+        let mut cache = CompilerCache::new(CountingGenerator(&calls));
+        for input in ["1 2 +", "3 4 *", "1 2 +", "1\n2\n+", "3 4 *"] {
+            cache.generate(&parse(input));
+        }
+
+        assert_eq!(calls.get(), 2);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.stats(), CacheStats { hits: 3, misses: 2 });
+        assert_eq!(cache.stats().lookups(), 5);
+    }
+
+    #[test]
+    fn test_operand_order_matters_without_canonicalize() {
+        let mut cache = CompilerCache::new(NotationGenerator::new(Notation::Postfix));
+        assert_eq!(cache.generate(&parse("1 2 +")), "1 2 +");
+        assert_eq!(cache.generate(&parse("2 1 +")), "2 1 +");
+        assert_eq!(cache.stats().hits, 0);
+    }
+
+    #[test]
+    fn test_canonicalize_shares_equivalent_expressions() {
+        let mut cache =
+            CompilerCache::new(NotationGenerator::new(Notation::Postfix)).with_canonicalize(true);
+        assert_eq!(cache.generate(&parse("3 2 1 + +")), "1 2 + 3 +");
+        assert_eq!(cache.generate(&parse("1 2 3 + +")), "1 2 + 3 +");
+        assert_eq!(cache.stats().hits, 1);
+    }
+
+    #[test]
+    fn test_clear_keeps_stats() {
+        let mut cache = CompilerCache::new(NotationGenerator::new(Notation::Postfix));
+        cache.generate(&parse("1 2 +"));
+        cache.generate(&parse("1 2 +"));
+        cache.clear();
+        assert!(cache.is_empty());
+
+        cache.generate(&parse("1 2 +"));
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 2 });
+    }
+}
@@ -0,0 +1,1484 @@
+//! Reading the command line into [`Options`], with the usage text and the
+//! checks that flags are combined sensibly.
+
+use crate::cli::batch::fill_pattern;
+use crate::cli::glob::is_glob;
+use crate::cli::options::{
+    ColorChoice, Command, Emit, Filter, Format, Leftovers, LetStyle, Options, PreviewProtocol,
+    StatsFormat,
+};
+use rpn2tex::latex::{FracStyle, MathEnvironment, MulStyle, ParenSizing, SpacingStyle};
+use rpn2tex::markdown::MarkdownFlavor;
+use rpn2tex::number_format::{DecimalSeparator, Precision, ThousandsSeparator};
+use rpn2tex::pipeline::InputNotation;
+use std::path::PathBuf;
+
+/// Parses the value of an `--environment` argument.
+fn parse_environment(value: &str) -> Option<MathEnvironment> {
+    match value {
+        "inline" => Some(MathEnvironment::Inline),
+        "equation" => Some(MathEnvironment::Equation),
+        "align*" => Some(MathEnvironment::AlignStar),
+        "gather" => Some(MathEnvironment::Gather),
+        "display" => Some(MathEnvironment::DisplayBrackets),
+        "display-dollars" => Some(MathEnvironment::DisplayDollars),
+        "bare" => Some(MathEnvironment::Bare),
+        _ => None,
+    }
+}
+
+/// Parses the value of a `--spacing` argument.
+pub(crate) fn parse_spacing(value: &str) -> Option<SpacingStyle> {
+    match value {
+        "tight" => Some(SpacingStyle::Tight),
+        "normal" => Some(SpacingStyle::Normal),
+        "spaced" => Some(SpacingStyle::Spaced),
+        _ => None,
+    }
+}
+
+/// Parses the value of a `--parens` argument.
+pub(crate) fn parse_paren_sizing(value: &str) -> Option<ParenSizing> {
+    match value {
+        "fixed" => Some(ParenSizing::Fixed),
+        "auto" => Some(ParenSizing::Auto),
+        "auto-tall" => Some(ParenSizing::AutoTall),
+        _ => None,
+    }
+}
+
+/// Parses the value of a `--thousands` argument.
+pub(crate) fn parse_thousands(value: &str) -> Option<ThousandsSeparator> {
+    match value {
+        "none" => Some(ThousandsSeparator::None),
+        "thin-space" => Some(ThousandsSeparator::ThinSpace),
+        "comma" => Some(ThousandsSeparator::Comma),
+        _ => None,
+    }
+}
+
+/// Parses the value of a `--decimal` argument.
+pub(crate) fn parse_decimal(value: &str) -> Option<DecimalSeparator> {
+    match value {
+        "point" => Some(DecimalSeparator::Point),
+        "comma" => Some(DecimalSeparator::Comma),
+        _ => None,
+    }
+}
+
+/// Parses the digit count given to `--decimal-places` or `--sig-figs`.
+///
+/// # Errors
+///
+/// Returns an error message if the value is not a number from 0 to 255.
+fn parse_digit_count(value: &str, flag: &str) -> Result<u8, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Error: {} expects a digit count, got '{}'", flag, value))
+}
+
+/// Parses the value given to `--max-input-bytes`, `--max-tokens` or
+/// `--max-depth`.
+///
+/// # Errors
+///
+/// Returns an error message if the value is not a non-negative integer.
+fn parse_limit(value: &str, flag: &str) -> Result<usize, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Error: {} expects a number, got '{}'", flag, value))
+}
+
+/// Parses the value of a `--mul-style` argument.
+pub(crate) fn parse_mul_style(value: &str) -> Option<MulStyle> {
+    match value {
+        "times" => Some(MulStyle::Times),
+        "cdot" => Some(MulStyle::Cdot),
+        _ => None,
+    }
+}
+
+/// Parses the value of a `--frac-style` argument.
+pub(crate) fn parse_frac_style(value: &str) -> Option<FracStyle> {
+    match value {
+        "frac" => Some(FracStyle::Frac),
+        "dfrac" => Some(FracStyle::Dfrac),
+        "tfrac" => Some(FracStyle::Tfrac),
+        _ => None,
+    }
+}
+
+/// Parses the value of a `--from` argument.
+fn parse_input_notation(value: &str) -> Option<InputNotation> {
+    match value {
+        "rpn" => Some(InputNotation::Rpn),
+        "prefix" => Some(InputNotation::Prefix),
+        "infix" => Some(InputNotation::Infix),
+        _ => None,
+    }
+}
+
+/// Parses the value of a `--markdown-flavor` argument.
+fn parse_markdown_flavor(value: &str) -> Option<MarkdownFlavor> {
+    match value {
+        "github" => Some(MarkdownFlavor::GitHub),
+        "pandoc" => Some(MarkdownFlavor::Pandoc),
+        "obsidian" => Some(MarkdownFlavor::Obsidian),
+        _ => None,
+    }
+}
+
+/// Parses the value of a `--let` argument, `name=value`.
+///
+/// # Errors
+///
+/// Returns an error message if the name is not an identifier or the value
+/// is not a finite number.
+fn parse_binding(value: &str) -> Result<(String, f64), String> {
+    let invalid = || format!("Error: --let expects name=value, got '{}'", value);
+    let (name, number) = value.split_once('=').ok_or_else(invalid)?;
+
+    let mut chars = name.chars();
+    let is_identifier = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric());
+    if !is_identifier {
+        return Err(format!("Error: --let name '{}' is not an identifier", name));
+    }
+
+    match number.parse::<f64>() {
+        Ok(number) if number.is_finite() => Ok((name.to_string(), number)),
+        _ => Err(invalid()),
+    }
+}
+
+/// Prints usage information to stderr.
+pub(crate) fn print_usage(program: &str) {
+    eprintln!(
+        "Usage: {} <input-file> [-o <output-file>] [--emit <kind>] [--format <fmt>]",
+        program
+    );
+    eprintln!("       {} <input>... -d <output-dir> [options]", program);
+    eprintln!("       {} <input>... --in-place [options]", program);
+    eprintln!(
+        "       {} convert <input> --from <fmt> --to <fmt> [options]",
+        program
+    );
+    eprintln!(
+        "       {} worksheet <input> [-o <output-file>] [options]",
+        program
+    );
+    eprintln!("       {} fmt <input>... [--check]", program);
+    eprintln!("       {} repl [options]", program);
+    eprintln!(
+        "       {} lint <input>... [--max-nesting <n>] [--format json]",
+        program
+    );
+    eprintln!();
+    eprintln!("Arguments:");
+    eprintln!("  <input-file>    Input RPN file (use '-' for stdin)");
+    eprintln!("  <input>...      With -d: files, directories (their .rpn files) or globs");
+    eprintln!("                  (src/**/*.rpn)");
+    eprintln!("  -o <output>     Output LaTeX file (default: stdout); with several inputs");
+    eprintln!("                  or a glob, the output directory, as with -d");
+    eprintln!("  -d <dir>        Convert each input file into <dir>, mirroring the input");
+    eprintln!("                  tree, and summarize the results");
+    eprintln!("  --in-place      Convert each input file beside it (foo.rpn -> foo.tex),");
+    eprintln!("                  and summarize the results");
+    eprintln!("  --output-pattern <pattern>");
+    eprintln!("                  Name the files of -d and --in-place from {{dir}} (the input's");
+    eprintln!("                  directory in the mirrored tree), {{stem}} and {{format}} (the");
+    eprintln!("                  output extension); default: {{dir}}/{{stem}}.{{format}}");
+    eprintln!("  --dry-run       With -d or --in-place, list each input and the file it would");
+    eprintln!("                  be written to, flagging existing files, without converting");
+    eprintln!("                  or writing anything");
+    eprintln!("  --stats <fmt>   After -d or --in-place, report files, expressions, failures");
+    eprintln!("                  by kind (io, decode, lex, parse, binding, eval), cache");
+    eprintln!("                  hits for repeated expressions and time by stage: text");
+    eprintln!("                  (stderr) or json (stdout)");
+    eprintln!("  --null          Convert NUL-separated expressions as they arrive, each");
+    eprintln!("                  output followed by NUL (empty for a failed expression)");
+    eprintln!("  --jupyter       Answer {{\"id\": ..., \"code\": \"5 3 +\"}} requests, one JSON");
+    eprintln!("                  object per line, with text/latex display data for notebooks;");
+    eprintln!("                  a failed request is answered with its error");
+    eprintln!("  -v, --verbose   Log progress and timings to stderr; -vv adds each stage");
+    eprintln!("  -q, --quiet     Print only errors to stderr");
+    eprintln!("  --copy          Also copy the output to the clipboard (requires the");
+    eprintln!("                  'clipboard' feature)");
+    eprintln!("  --pdf <file>    Compile the standalone document to a PDF with pdflatex;");
+    eprintln!("                  implies --standalone (requires the 'pdf' feature)");
+    eprintln!("  --preview       Also draw the rendered output in the terminal on stderr");
+    eprintln!("                  (requires the 'pdf' feature, pdflatex and pdftoppm)");
+    eprintln!("  --preview-protocol <protocol>");
+    eprintln!("                  Image protocol of --preview: kitty, iterm2, sixel (with");
+    eprintln!("                  img2sixel); detected from the terminal by default");
+    eprintln!("  --emit <kind>   Output kind: output (default), dot, ast (an indented tree),");
+    eprintln!("                  ast-json, packages, tokens (a table, or JSON with");
+    eprintln!("                  --format json)");
+    eprintln!("  --from <fmt>    Input notation: rpn (default), prefix, infix");
+    eprintln!("  --format <fmt>, --to <fmt>");
+    eprintln!("                  Output notation: latex (default), prefix, rpn, infix,");
+    eprintln!("                  content-mathml, wolfram, wolfram-infix, python, context,");
+    eprintln!("                  markdown, json");
+    eprintln!("                  (input, LaTeX, AST and diagnostics per expression), csv");
+    eprintln!("                  (the input CSV's rows with a latex column added)");
+    eprintln!("  --csv-column <name>");
+    eprintln!("                  The CSV column holding expressions (default: 'rpn' if");
+    eprintln!("                  present, else the first)");
+    eprintln!("  --csv-value     Also add a value column to --format csv output");
+    eprintln!("  --environment <env>");
+    eprintln!("                  LaTeX math environment: inline (default), equation,");
+    eprintln!("                  align*, gather, display (\\[...\\]), display-dollars");
+    eprintln!("                  ($$...$$), bare (no delimiters)");
+    eprintln!("  --standalone    Wrap LaTeX output in a compilable document");
+    eprintln!("  --template <file>");
+    eprintln!("                  Insert the output into a LaTeX file at its placeholder");
+    eprintln!("  --placeholder <marker>");
+    eprintln!("                  The template's placeholder (default: %%MATH%%)");
+    eprintln!("  --katex         Restrict LaTeX output to what KaTeX can render");
+    eprintln!("  --spacing <style>");
+    eprintln!("                  LaTeX whitespace: spaced (default), normal, tight");
+    eprintln!("  --parens <sizing>");
+    eprintln!("                  LaTeX parentheses: fixed (default), auto (\\left/\\right),");
+    eprintln!("                  auto-tall (\\left/\\right around tall content only)");
+    eprintln!("  --thousands <sep>");
+    eprintln!("                  Digit grouping in LaTeX numbers: none (default),");
+    eprintln!("                  thin-space (1\\,000), comma (1{{,}}000)");
+    eprintln!(
+        "  --decimal <sep>  Decimal mark in LaTeX numbers: point (default), comma (3{{,}}14)"
+    );
+    eprintln!("  --decimal-places <n>");
+    eprintln!("                  Round or pad LaTeX numbers to n decimal places");
+    eprintln!("  --sig-figs <n>  Round or pad LaTeX numbers to n significant figures");
+    eprintln!("  --leading-zero  Write leading-dot numbers such as .5 as 0.5 in LaTeX");
+    eprintln!("  --no-negative-parens");
+    eprintln!("                  Write '10 + -5' instead of '10 + (-5)' in LaTeX output");
+    eprintln!("  --siunitx       Wrap LaTeX numbers in siunitx's \\num{{...}}");
+    eprintln!("  --mul-style <sym>");
+    eprintln!("                  LaTeX multiplication symbol: times (default), cdot");
+    eprintln!("  --frac          Write LaTeX division as \\frac{{a}}{{b}}");
+    eprintln!("  --frac-style <cmd>");
+    eprintln!("                  Fraction command: frac (default), dfrac, tfrac; implies --frac");
+    eprintln!("  --eval          Also print the expression's numeric value to stderr");
+    eprintln!("  --evaluate      Print the expression's numeric value instead of converting it");
+    eprintln!("  --exact         With --eval, --evaluate or an answer key, compute an exact");
+    eprintln!("                  fraction (1/3, not 0.333...)");
+    eprintln!("  --precision <n> Round values of --eval, --evaluate and answer keys to n");
+    eprintln!("                  decimal places");
+    eprintln!("  --show-result   Append '= value' to LaTeX output, in the number format");
+    eprintln!("  --blank <n>     Replace the n-th number or operator, in RPN order, with a");
+    eprintln!("                  blank line and print what it hid to stderr");
+    eprintln!("  --allow-vars    Accept variable names (x, rate2) in the input");
+    eprintln!("  --unicode-digits");
+    eprintln!("                  Accept digits from other scripts (５, ٣) as 0-9");
+    eprintln!("  --comma-groups  Accept commas between digit groups (1,000) in the input;");
+    eprintln!("                  underscores (1_000) are always accepted");
+    eprintln!("  --lenient-numbers");
+    eprintln!("                  Split malformed numbers (5., 1.2.3) instead of rejecting them");
+    eprintln!("  --max-input-bytes <n>, --max-tokens <n>, --max-depth <n>");
+    eprintln!("                  Reject input longer than n bytes or tokens, or nested");
+    eprintln!("                  more than n levels deep");
+    eprintln!("  --let <name=value>");
+    eprintln!("                  Bind a variable for --eval and --show-result (repeatable);");
+    eprintln!("                  implies --allow-vars");
+    eprintln!("  --let-style <style>");
+    eprintln!("                  Output of names bound by 'let a = 5 3 + ; a 2 *' input:");
+    eprintln!("                  inline (default) substitutes them, symbolic keeps them");
+    eprintln!("  --prelude <file>");
+    eprintln!("                  Read 'macro sq = dup *' definitions for the input to use");
+    eprintln!("  --fold-constants");
+    eprintln!("                  Replace operations on two numbers by their exact result");
+    eprintln!("  --macros        Define repeated LaTeX subexpressions once with \\newcommand");
+    eprintln!("  --multi         Convert each line or ';'-separated expression, one output");
+    eprintln!("                  line each");
+    eprintln!("  --min-difficulty <n>, --max-difficulty <n>");
+    eprintln!("                  With --multi or worksheet, keep only the expressions scoring");
+    eprintln!("                  at least or at most n, by operators, nesting and the size");
+    eprintln!("                  of intermediate results");
+    eprintln!("  --all-errors    Report every syntax error instead of stopping at the first");
+    eprintln!("  --leftovers <mode>");
+    eprintln!("                  Values left on the stack: error (default), or convert each");
+    eprintln!("                  and join with comma or newline");
+    eprintln!("  --markdown-flavor <flavor>");
+    eprintln!("                  Markdown math syntax: github (default), pandoc, obsidian");
+    eprintln!("  --filter markdown");
+    eprintln!("                  Read a Markdown document and replace its ```rpn blocks and");
+    eprintln!("                  `rpn:...` spans with math, copying the rest unchanged");
+    eprintln!("  --color <when>  Color error messages: auto (default, when stderr is a");
+    eprintln!("                  terminal and NO_COLOR is unset), always, never");
+    eprintln!("  --fancy-errors  Draw error messages with labeled source snippets (requires");
+    eprintln!("                  the 'miette' feature)");
+    eprintln!("  --no-config     Ignore rpn2tex.toml files");
+    eprintln!();
+    eprintln!("Worksheet:");
+    eprintln!("  A LaTeX document with each line or ';'-separated expression of the input");
+    eprintln!("  as a numbered problem.");
+    eprintln!("  --columns <n>   Columns of problems, 1 to 10 (default: 2)");
+    eprintln!("  --answer-space <length>");
+    eprintln!("                  Space left for each answer (default: 2cm)");
+    eprintln!("  --title <text>  Heading above the problems, as plain text");
+    eprintln!("  --answer-key    Add a page listing each problem with its value");
+    eprintln!("  --answer-key-output <file>");
+    eprintln!("                  Write the answer key to its own document instead");
+    eprintln!();
+    eprintln!("Formatting:");
+    eprintln!("  fmt rewrites each input file in place, or stdin to stdout, with tokens");
+    eprintln!("  separated by single spaces, plain numbers and ASCII operators; comments");
+    eprintln!("  are kept as written.");
+    eprintln!("  --check         Only list the inputs that are not formatted, and fail if");
+    eprintln!("                  there are any");
+    eprintln!();
+    eprintln!("Linting:");
+    eprintln!("  lint reports operations that change nothing, constant parts that could be");
+    eprintln!("  folded, deep nesting and suspicious literals, and fails if it finds any.");
+    eprintln!("  --max-nesting <n>");
+    eprintln!("                  Deepest nesting of operations allowed (default: 6)");
+    eprintln!("  --format json   Report the findings as a JSON array");
+    eprintln!();
+    eprintln!("Interactive prompt:");
+    eprintln!("  repl (requires the 'readline' feature) converts each expression as it is");
+    eprintln!("  entered, with line editing, Ctrl-R history search and history kept in");
+    eprintln!("  $XDG_CONFIG_HOME/rpn2tex_history. An RPN expression leaving several values");
+    eprintln!("  on the stack continues on the next line; Ctrl-C discards it, Ctrl-D exits.");
+    eprintln!();
+    eprintln!("Configuration:");
+    eprintln!("  Defaults are read from $XDG_CONFIG_HOME/rpn2tex.toml, then from the nearest");
+    eprintln!("  rpn2tex.toml in the current directory or its parents; flags override both.");
+    eprintln!("  Settings are named like the flags above: format = \"latex\", frac = true,");
+    eprintln!("  max-depth = 100. An [operators] table maps \"*\" to times or cdot, and \"/\"");
+    eprintln!("  to div, frac, dfrac or tfrac.");
+    eprintln!("  RPN2TEX_* environment variables override the files: RPN2TEX_MUL_STYLE=cdot,");
+    eprintln!("  RPN2TEX_FRAC=1; a switch set to false or 0 turns off an earlier true. A");
+    eprintln!("  setting the command or the flags given rule out is skipped.");
+}
+
+/// Parses command-line arguments (excluding the program name).
+///
+/// # Errors
+///
+/// Returns a message describing the first invalid or incomplete argument.
+pub(crate) fn parse_args(args: &[String]) -> Result<Options, String> {
+    let command = args.first().and_then(|first| Command::parse(first));
+    let args = if command.is_some() { &args[1..] } else { args };
+    // The prompt is the input, so only flags follow `repl`
+    let (input_path, rest) = match command {
+        Some(Command::Repl) => ("-", args),
+        _ => args
+            .split_first()
+            .map(|(input, rest)| (input.as_str(), rest))
+            .ok_or_else(|| "Error: missing input file".to_string())?,
+    };
+    let mut options = Options::new(input_path);
+    options.command = command.unwrap_or(Command::Convert);
+
+    let mut i = 0;
+    while i < rest.len() {
+        let flag = rest[i].as_str();
+        match flag {
+            "-o" | "--output" => {
+                options.output_path = Some(PathBuf::from(flag_value(rest, i, "-o")?));
+                i += 2;
+            }
+            "-d" | "--output-dir" => {
+                options.output_dir = Some(PathBuf::from(flag_value(rest, i, "-d")?));
+                i += 2;
+            }
+            "--in-place" => {
+                options.in_place = true;
+                i += 1;
+            }
+            "--output-pattern" => {
+                let pattern = flag_value(rest, i, flag)?;
+                fill_pattern(pattern, &[("dir", ""), ("stem", ""), ("format", "")])?;
+                options.output_pattern = Some(pattern.to_string());
+                i += 2;
+            }
+            "--dry-run" => {
+                options.dry_run = true;
+                i += 1;
+            }
+            "--stats" => {
+                let value = flag_value(rest, i, flag)?;
+                options.stats = Some(
+                    StatsFormat::parse(value)
+                        .ok_or_else(|| format!("Error: Unknown --stats '{}'", value))?,
+                );
+                i += 2;
+            }
+            "--null" => {
+                options.null = true;
+                i += 1;
+            }
+            "--jupyter" => {
+                options.jupyter = true;
+                i += 1;
+            }
+            "--pdf" => {
+                options.pdf_path = Some(PathBuf::from(flag_value(rest, i, flag)?));
+                options.standalone = true;
+                i += 2;
+            }
+            "--preview" => {
+                options.preview = true;
+                i += 1;
+            }
+            "--preview-protocol" => {
+                let value = flag_value(rest, i, flag)?;
+                options.preview_protocol = Some(
+                    PreviewProtocol::parse(value)
+                        .ok_or_else(|| format!("Error: Unknown --preview-protocol '{}'", value))?,
+                );
+                options.preview = true;
+                i += 2;
+            }
+            "--copy" => {
+                options.copy = true;
+                i += 1;
+            }
+            "-v" | "--verbose" => {
+                options.verbose = options.verbose.saturating_add(1);
+                i += 1;
+            }
+            "-vv" => {
+                options.verbose = options.verbose.saturating_add(2);
+                i += 1;
+            }
+            "-q" | "--quiet" => {
+                options.quiet = true;
+                i += 1;
+            }
+            "--no-config" => {
+                // Handled by run, before the configuration files are read
+                i += 1;
+            }
+            "--emit" => {
+                let value = flag_value(rest, i, flag)?;
+                options.emit = Emit::parse(value)
+                    .ok_or_else(|| format!("Error: Unknown --emit kind '{}'", value))?;
+                i += 2;
+            }
+            "--from" => {
+                let value = flag_value(rest, i, flag)?;
+                options.from = parse_input_notation(value)
+                    .ok_or_else(|| format!("Error: Unknown --from '{}'", value))?;
+                i += 2;
+            }
+            "--format" | "--to" => {
+                let value = flag_value(rest, i, flag)?;
+                options.format = Format::parse(value)
+                    .ok_or_else(|| format!("Error: Unknown {} '{}'", flag, value))?;
+                i += 2;
+            }
+            "--environment" => {
+                let value = flag_value(rest, i, flag)?;
+                options.environment = parse_environment(value)
+                    .ok_or_else(|| format!("Error: Unknown --environment '{}'", value))?;
+                i += 2;
+            }
+            "--standalone" => {
+                options.standalone = true;
+                i += 1;
+            }
+            "--katex" => {
+                options.katex = true;
+                i += 1;
+            }
+            "--spacing" => {
+                let value = flag_value(rest, i, flag)?;
+                options.spacing = parse_spacing(value)
+                    .ok_or_else(|| format!("Error: Unknown --spacing '{}'", value))?;
+                i += 2;
+            }
+            "--parens" => {
+                let value = flag_value(rest, i, flag)?;
+                options.paren_sizing = parse_paren_sizing(value)
+                    .ok_or_else(|| format!("Error: Unknown --parens '{}'", value))?;
+                i += 2;
+            }
+            "--thousands" => {
+                let value = flag_value(rest, i, flag)?;
+                options.thousands = parse_thousands(value)
+                    .ok_or_else(|| format!("Error: Unknown --thousands '{}'", value))?;
+                i += 2;
+            }
+            "--decimal" => {
+                let value = flag_value(rest, i, flag)?;
+                options.decimal = parse_decimal(value)
+                    .ok_or_else(|| format!("Error: Unknown --decimal '{}'", value))?;
+                i += 2;
+            }
+            "--decimal-places" => {
+                let places = parse_digit_count(flag_value(rest, i, flag)?, flag)?;
+                options.precision = Precision::DecimalPlaces(places);
+                i += 2;
+            }
+            "--sig-figs" => {
+                let figures = parse_digit_count(flag_value(rest, i, flag)?, flag)?;
+                if figures == 0 {
+                    return Err("Error: --sig-figs must be at least 1".to_string());
+                }
+                options.precision = Precision::SignificantFigures(figures);
+                i += 2;
+            }
+            "--leading-zero" => {
+                options.leading_zero = true;
+                i += 1;
+            }
+            "--no-negative-parens" => {
+                options.negative_parens = false;
+                i += 1;
+            }
+            "--siunitx" => {
+                options.siunitx = true;
+                i += 1;
+            }
+            "--mul-style" => {
+                let value = flag_value(rest, i, flag)?;
+                options.mul_style = parse_mul_style(value)
+                    .ok_or_else(|| format!("Error: Unknown --mul-style '{}'", value))?;
+                i += 2;
+            }
+            "--frac" => {
+                options.frac = true;
+                i += 1;
+            }
+            "--frac-style" => {
+                let value = flag_value(rest, i, flag)?;
+                options.frac_style = parse_frac_style(value)
+                    .ok_or_else(|| format!("Error: Unknown --frac-style '{}'", value))?;
+                options.frac = true;
+                i += 2;
+            }
+            "--eval" => {
+                options.eval = true;
+                i += 1;
+            }
+            "--evaluate" => {
+                options.evaluate = true;
+                i += 1;
+            }
+            "--exact" => {
+                options.exact = true;
+                i += 1;
+            }
+            "--precision" => {
+                options.value_places = Some(parse_digit_count(flag_value(rest, i, flag)?, flag)?);
+                i += 2;
+            }
+            "--show-result" => {
+                options.show_result = true;
+                i += 1;
+            }
+            "--allow-vars" => {
+                options.allow_vars = true;
+                i += 1;
+            }
+            "--unicode-digits" => {
+                options.unicode_digits = true;
+                i += 1;
+            }
+            "--comma-groups" => {
+                options.comma_groups = true;
+                i += 1;
+            }
+            "--lenient-numbers" => {
+                options.lenient_numbers = true;
+                i += 1;
+            }
+            "--max-input-bytes" => {
+                let bytes = parse_limit(flag_value(rest, i, flag)?, flag)?;
+                options.limits = options.limits.with_max_input_bytes(bytes);
+                i += 2;
+            }
+            "--max-tokens" => {
+                let tokens = parse_limit(flag_value(rest, i, flag)?, flag)?;
+                options.limits = options.limits.with_max_tokens(tokens);
+                i += 2;
+            }
+            "--max-depth" => {
+                let depth = parse_limit(flag_value(rest, i, flag)?, flag)?;
+                options.limits = options.limits.with_max_depth(depth);
+                i += 2;
+            }
+            "--let" => {
+                options
+                    .bindings
+                    .push(parse_binding(flag_value(rest, i, flag)?)?);
+                options.allow_vars = true;
+                i += 2;
+            }
+            "--let-style" => {
+                let value = flag_value(rest, i, flag)?;
+                options.let_style = LetStyle::parse(value)
+                    .ok_or_else(|| format!("Error: Unknown --let-style '{}'", value))?;
+                i += 2;
+            }
+            "--prelude" => {
+                options.prelude_path = Some(PathBuf::from(flag_value(rest, i, flag)?));
+                i += 2;
+            }
+            "--template" => {
+                options.template_path = Some(PathBuf::from(flag_value(rest, i, flag)?));
+                i += 2;
+            }
+            "--placeholder" => {
+                options.placeholder = Some(flag_value(rest, i, flag)?.to_string());
+                i += 2;
+            }
+            "--blank" => {
+                options.blank = Some(parse_limit(flag_value(rest, i, flag)?, flag)?);
+                i += 2;
+            }
+            "--fold-constants" => {
+                options.fold_constants = true;
+                i += 1;
+            }
+            "--macros" => {
+                options.macros = true;
+                i += 1;
+            }
+            "--multi" => {
+                options.multi = true;
+                i += 1;
+            }
+            "--check" => {
+                options.check = true;
+                i += 1;
+            }
+            "--max-nesting" => {
+                options.max_nesting = Some(parse_limit(flag_value(rest, i, flag)?, flag)?);
+                i += 2;
+            }
+            "--min-difficulty" => {
+                options.min_difficulty = Some(parse_limit(flag_value(rest, i, flag)?, flag)?);
+                i += 2;
+            }
+            "--max-difficulty" => {
+                options.max_difficulty = Some(parse_limit(flag_value(rest, i, flag)?, flag)?);
+                i += 2;
+            }
+            "--all-errors" => {
+                options.all_errors = true;
+                i += 1;
+            }
+            "--leftovers" => {
+                let value = flag_value(rest, i, flag)?;
+                options.leftovers = Leftovers::parse(value)
+                    .ok_or_else(|| format!("Error: Unknown --leftovers '{}'", value))?;
+                i += 2;
+            }
+            "--color" => {
+                let value = flag_value(rest, i, flag)?;
+                options.color = ColorChoice::parse(value)
+                    .ok_or_else(|| format!("Error: Unknown --color '{}'", value))?;
+                i += 2;
+            }
+            "--fancy-errors" => {
+                options.fancy_errors = true;
+                i += 1;
+            }
+            "--csv-column" => {
+                options.csv_column = Some(flag_value(rest, i, flag)?.to_string());
+                i += 2;
+            }
+            "--csv-value" => {
+                options.csv_value = true;
+                i += 1;
+            }
+            "--columns" => {
+                let value = flag_value(rest, i, flag)?;
+                options.columns = Some(
+                    value
+                        .parse()
+                        .ok()
+                        .filter(|columns| (1..=10).contains(columns))
+                        .ok_or_else(|| {
+                            format!("Error: --columns expects 1 to 10, got '{}'", value)
+                        })?,
+                );
+                i += 2;
+            }
+            "--answer-space" => {
+                options.answer_space = Some(flag_value(rest, i, flag)?.to_string());
+                i += 2;
+            }
+            "--title" => {
+                options.title = Some(flag_value(rest, i, flag)?.to_string());
+                i += 2;
+            }
+            "--answer-key" => {
+                options.answer_key = true;
+                i += 1;
+            }
+            "--answer-key-output" => {
+                options.answer_key_path = Some(PathBuf::from(flag_value(rest, i, flag)?));
+                i += 2;
+            }
+            "--markdown-flavor" => {
+                let value = flag_value(rest, i, flag)?;
+                options.markdown_flavor = parse_markdown_flavor(value)
+                    .ok_or_else(|| format!("Error: Unknown --markdown-flavor '{}'", value))?;
+                i += 2;
+            }
+            "--filter" => {
+                let value = flag_value(rest, i, flag)?;
+                options.filter = Some(
+                    Filter::parse(value)
+                        .ok_or_else(|| format!("Error: Unknown --filter '{}'", value))?,
+                );
+                i += 2;
+            }
+            _ if !flag.starts_with('-') => {
+                options.extra_inputs.push(flag.to_string());
+                i += 1;
+            }
+            _ => return Err(format!("Error: Unknown argument '{}'", flag)),
+        }
+    }
+
+    let several = !options.extra_inputs.is_empty() || is_glob(&options.input_path);
+    if options.in_place && (options.output_dir.is_some() || options.output_path.is_some()) {
+        return Err("Error: --in-place cannot be combined with -o or -d".to_string());
+    }
+    if several
+        && options.output_dir.is_none()
+        && !options.in_place
+        && !matches!(options.command, Command::Fmt | Command::Lint)
+    {
+        // With several inputs, -o names the directory their outputs go to
+        options.output_dir = options.output_path.take();
+        if options.output_dir.is_none() {
+            return Err(
+                "Error: several inputs or a glob require -d <dir>, -o <dir> or --in-place"
+                    .to_string(),
+            );
+        }
+    }
+    if options.output_dir.is_some() || options.in_place {
+        let mode = if options.in_place { "--in-place" } else { "-d" };
+        if options.output_path.is_some() {
+            return Err("Error: -o cannot be combined with -d".to_string());
+        }
+        if options.input_path == "-" || options.extra_inputs.iter().any(|input| input == "-") {
+            return Err(format!("Error: {} cannot convert stdin", mode));
+        }
+        if options.eval {
+            return Err(format!("Error: --eval cannot be combined with {}", mode));
+        }
+        if options.copy {
+            return Err(format!("Error: --copy cannot be combined with {}", mode));
+        }
+    }
+    if options.output_pattern.is_some() && options.output_dir.is_none() && !options.in_place {
+        return Err("Error: --output-pattern requires -d or --in-place".to_string());
+    }
+    if options.stats.is_some() && options.output_dir.is_none() && !options.in_place {
+        return Err("Error: --stats requires -d or --in-place".to_string());
+    }
+    if options.dry_run && options.output_dir.is_none() && !options.in_place {
+        return Err("Error: --dry-run requires -d or --in-place".to_string());
+    }
+    if options.dry_run && options.stats.is_some() {
+        return Err("Error: --stats cannot be combined with --dry-run".to_string());
+    }
+    if options.null && (options.output_dir.is_some() || options.in_place) {
+        return Err("Error: --null cannot be combined with -d or --in-place".to_string());
+    }
+    if options.null && (options.eval || options.copy) {
+        return Err("Error: --null cannot be combined with --eval or --copy".to_string());
+    }
+    if options.jupyter
+        && (options.emit != Emit::Output
+            || options.format != Format::Latex
+            || options.standalone
+            || options.evaluate
+            || options.null
+            || options.copy
+            || options.output_dir.is_some()
+            || options.in_place
+            || options.filter.is_some()
+            || options.template_path.is_some())
+    {
+        return Err(
+            "Error: --jupyter answers with inline LaTeX; drop --emit, --format, --standalone, \
+             --evaluate, --null, --copy, -d, --in-place, --filter and --template"
+                .to_string(),
+        );
+    }
+    if options.command == Command::Worksheet
+        && (options.emit != Emit::Output
+            || options.format != Format::Latex
+            || options.standalone
+            || options.multi
+            || options.macros
+            || options.show_result
+            || options.evaluate
+            || options.null
+            || options.jupyter
+            || options.filter.is_some()
+            || options.template_path.is_some()
+            || options.leftovers != Leftovers::Error)
+    {
+        return Err(
+            "Error: worksheet generates a LaTeX document; drop --emit, --format, --standalone, \
+             --multi, --macros, --show-result, --evaluate, --null, --jupyter, --filter, \
+             --template and --leftovers"
+                .to_string(),
+        );
+    }
+    if options.command != Command::Worksheet
+        && (options.columns.is_some() || options.answer_space.is_some() || options.title.is_some())
+    {
+        return Err(
+            "Error: --columns, --answer-space and --title require the worksheet command"
+                .to_string(),
+        );
+    }
+    if options.command != Command::Worksheet
+        && (options.answer_key || options.answer_key_path.is_some())
+    {
+        return Err(
+            "Error: --answer-key and --answer-key-output require the worksheet command".to_string(),
+        );
+    }
+    if options.answer_key && options.answer_key_path.is_some() {
+        return Err("Error: --answer-key cannot be combined with --answer-key-output".to_string());
+    }
+    if options.answer_key_path.is_some() && (options.output_dir.is_some() || options.in_place) {
+        return Err(
+            "Error: --answer-key-output cannot be combined with -d or --in-place".to_string(),
+        );
+    }
+    if options.command == Command::Fmt
+        && (options.output_path.is_some()
+            || options.output_dir.is_some()
+            || options.in_place
+            || options.emit != Emit::Output
+            || options.format != Format::Latex
+            || options.standalone
+            || options.multi
+            || options.eval
+            || options.evaluate
+            || options.null
+            || options.jupyter
+            || options.filter.is_some()
+            || options.template_path.is_some())
+    {
+        return Err(
+            "Error: fmt rewrites its inputs in place; drop -o, -d, --in-place, --emit, \
+             --format, --standalone, --multi, --eval, --evaluate, --null, --jupyter, --filter \
+             and --template"
+                .to_string(),
+        );
+    }
+    if options.check && options.command != Command::Fmt {
+        return Err("Error: --check requires the fmt command".to_string());
+    }
+    if options.command == Command::Lint
+        && (options.output_path.is_some()
+            || options.output_dir.is_some()
+            || options.in_place
+            || options.emit != Emit::Output
+            || !matches!(options.format, Format::Latex | Format::Json)
+            || options.standalone
+            || options.multi
+            || options.eval
+            || options.evaluate
+            || options.fold_constants
+            || options.null
+            || options.jupyter
+            || options.filter.is_some()
+            || options.template_path.is_some())
+    {
+        return Err(
+            "Error: lint only reports on its inputs; drop -o, -d, --in-place, --emit, \
+             --standalone, --multi, --eval, --evaluate, --fold-constants, --null, --jupyter, \
+             --filter and --template, and use --format json or none"
+                .to_string(),
+        );
+    }
+    if options.max_nesting.is_some() && options.command != Command::Lint {
+        return Err("Error: --max-nesting requires the lint command".to_string());
+    }
+    if options.quiet && options.verbose > 0 {
+        return Err("Error: -q cannot be combined with -v".to_string());
+    }
+    if options.pdf_path.is_some() && !cfg!(feature = "pdf") {
+        return Err("Error: --pdf requires rpn2tex built with the 'pdf' feature".to_string());
+    }
+    if options.pdf_path.is_some()
+        && (options.emit != Emit::Output
+            || options.format != Format::Latex
+            || options.null
+            || options.jupyter
+            || options.output_dir.is_some()
+            || options.in_place)
+    {
+        return Err(
+            "Error: --pdf compiles one LaTeX document; drop --emit, --format, --null, \
+             --jupyter, -d and --in-place"
+                .to_string(),
+        );
+    }
+    if options.preview && !cfg!(feature = "pdf") {
+        return Err("Error: --preview requires rpn2tex built with the 'pdf' feature".to_string());
+    }
+    if options.preview
+        && (options.emit != Emit::Output
+            || options.format != Format::Latex
+            || options.evaluate
+            || options.null
+            || options.jupyter
+            || options.output_dir.is_some()
+            || options.in_place
+            || options.filter.is_some())
+    {
+        return Err(
+            "Error: --preview draws one LaTeX output; drop --emit, --format, --evaluate, \
+             --null, --jupyter, -d, --in-place and --filter"
+                .to_string(),
+        );
+    }
+    if options.command == Command::Repl {
+        if !cfg!(feature = "readline") {
+            return Err(
+                "Error: repl requires rpn2tex built with the 'readline' feature".to_string(),
+            );
+        }
+        if options.output_path.is_some()
+            || options.output_dir.is_some()
+            || options.in_place
+            || options.null
+            || options.jupyter
+            || options.copy
+            || options.pdf_path.is_some()
+            || options.preview
+            || options.template_path.is_some()
+            || options.answer_key_path.is_some()
+        {
+            return Err(
+                "Error: repl prints each expression's output as it is entered; drop -o, -d, \
+                 --in-place, --null, --jupyter, --copy, --pdf, --preview, --template and \
+                 --answer-key"
+                    .to_string(),
+            );
+        }
+    }
+    if (options.exact || options.fold_constants) && !cfg!(feature = "exact") {
+        let flag = if options.exact {
+            "--exact"
+        } else {
+            "--fold-constants"
+        };
+        return Err(format!(
+            "Error: {} requires rpn2tex built with the 'exact' feature",
+            flag
+        ));
+    }
+    if options.copy && !cfg!(feature = "clipboard") {
+        return Err(
+            "Error: --copy requires rpn2tex built with the 'clipboard' feature".to_string(),
+        );
+    }
+    if options.fancy_errors && !cfg!(feature = "miette") {
+        return Err(
+            "Error: --fancy-errors requires rpn2tex built with the 'miette' feature".to_string(),
+        );
+    }
+    if options.standalone && (options.emit != Emit::Output || options.format != Format::Latex) {
+        return Err("Error: --standalone requires LaTeX output".to_string());
+    }
+    if options.emit == Emit::Packages && options.format != Format::Latex {
+        return Err("Error: --emit packages requires LaTeX output".to_string());
+    }
+    if options.format == Format::Json && !matches!(options.emit, Emit::Output | Emit::Tokens) {
+        return Err(
+            "Error: --format json is only available with --emit output or tokens".to_string(),
+        );
+    }
+    if options.format == Format::Json
+        && (options.leftovers != Leftovers::Error || options.prelude_path.is_some())
+    {
+        return Err(
+            "Error: --format json cannot be combined with --leftovers or --prelude".to_string(),
+        );
+    }
+    if options.format == Format::Csv
+        && (options.emit != Emit::Output
+            || options.standalone
+            || options.macros
+            || options.multi
+            || options.eval
+            || options.evaluate
+            || options.null
+            || options.template_path.is_some())
+    {
+        return Err(
+            "Error: --format csv only applies to --emit output, without --standalone, \
+             --macros, --multi, --eval, --evaluate, --null or --template"
+                .to_string(),
+        );
+    }
+    if (options.csv_column.is_some() || options.csv_value) && options.format != Format::Csv {
+        return Err("Error: --csv-column and --csv-value require --format csv".to_string());
+    }
+    if options.emit == Emit::Tokens && !matches!(options.format, Format::Latex | Format::Json) {
+        return Err("Error: --emit tokens only supports --format json".to_string());
+    }
+    if options.standalone && options.environment == MathEnvironment::Bare {
+        return Err("Error: --standalone cannot wrap bare math".to_string());
+    }
+    if options.thousands == ThousandsSeparator::Comma && options.decimal == DecimalSeparator::Comma
+    {
+        return Err(
+            "Error: --thousands comma is ambiguous with --decimal comma; use thin-space"
+                .to_string(),
+        );
+    }
+    if options.siunitx
+        && (options.thousands != ThousandsSeparator::None
+            || options.decimal != DecimalSeparator::Point)
+    {
+        return Err(
+            "Error: --siunitx formats digits itself; drop --thousands and --decimal".to_string(),
+        );
+    }
+    if options.siunitx && options.katex {
+        return Err("Error: KaTeX cannot render --siunitx numbers".to_string());
+    }
+    if options.let_style == LetStyle::Symbolic
+        && (options.eval || options.evaluate || options.show_result)
+    {
+        return Err(
+            "Error: --let-style symbolic cannot be evaluated; drop --eval, --evaluate and \
+             --show-result"
+                .to_string(),
+        );
+    }
+    if options.placeholder.is_some() && options.template_path.is_none() {
+        return Err("Error: --placeholder requires --template".to_string());
+    }
+    if options.placeholder.as_deref() == Some("") {
+        return Err("Error: --placeholder cannot be empty".to_string());
+    }
+    if options.template_path.is_some()
+        && (options.emit != Emit::Output || options.standalone || options.null)
+    {
+        return Err(
+            "Error: --template only applies to --emit output, without --standalone or --null"
+                .to_string(),
+        );
+    }
+    if options.prelude_path.is_some() && options.from != InputNotation::Rpn {
+        return Err("Error: --prelude macros only apply to RPN input".to_string());
+    }
+    if options.evaluate
+        && (options.emit != Emit::Output
+            || options.eval
+            || options.show_result
+            || options.standalone
+            || options.macros
+            || options.multi
+            || options.template_path.is_some())
+    {
+        return Err(
+            "Error: --evaluate prints only the value; drop --emit, --eval, --show-result, \
+             --standalone, --macros, --multi and --template"
+                .to_string(),
+        );
+    }
+    if options.filter.is_some()
+        && (options.emit != Emit::Output
+            || !matches!(options.format, Format::Latex | Format::Markdown)
+            || options.standalone
+            || options.macros
+            || options.multi
+            || options.evaluate
+            || options.template_path.is_some())
+    {
+        return Err(
+            "Error: --filter only applies to --emit output as LaTeX or Markdown, without \
+             --standalone, --macros, --multi, --evaluate or --template"
+                .to_string(),
+        );
+    }
+    if options.exact && !options.evaluates() {
+        return Err("Error: --exact requires --eval, --evaluate or an answer key".to_string());
+    }
+    if options.value_places.is_some() {
+        if !options.evaluates() {
+            return Err(
+                "Error: --precision requires --eval, --evaluate or an answer key".to_string(),
+            );
+        }
+        if options.exact {
+            return Err("Error: --precision cannot be combined with --exact".to_string());
+        }
+    }
+    if options.show_result && (options.emit != Emit::Output || options.format != Format::Latex) {
+        return Err("Error: --show-result requires LaTeX output".to_string());
+    }
+    if options.show_result && options.standalone {
+        return Err("Error: --show-result cannot be combined with --standalone".to_string());
+    }
+    if options.macros && (options.emit != Emit::Output || options.format != Format::Latex) {
+        return Err("Error: --macros requires LaTeX output".to_string());
+    }
+    if options.macros && (options.standalone || options.show_result) {
+        return Err(
+            "Error: --macros cannot be combined with --standalone or --show-result".to_string(),
+        );
+    }
+    if options.blank.is_some()
+        && (options.command == Command::Worksheet
+            || options.emit != Emit::Output
+            || options.format != Format::Latex
+            || options.standalone
+            || options.macros
+            || options.show_result
+            || options.evaluate
+            || options.multi
+            || options.null
+            || options.jupyter
+            || options.output_dir.is_some()
+            || options.in_place
+            || options.filter.is_some()
+            || options.leftovers != Leftovers::Error)
+    {
+        return Err(
+            "Error: --blank hides a token of one LaTeX expression; drop the worksheet command, \
+             --emit, --format, --standalone, --macros, --show-result, --evaluate, --multi, \
+             --null, --jupyter, -d, --in-place, --filter and --leftovers"
+                .to_string(),
+        );
+    }
+    if options.blank == Some(0) {
+        return Err("Error: --blank counts tokens from 1".to_string());
+    }
+    if options.macros && options.katex {
+        return Err("Error: KaTeX cannot render --macros definitions".to_string());
+    }
+    if options.multi && (options.emit != Emit::Output || options.standalone || options.eval) {
+        return Err(
+            "Error: --multi only applies to --emit output, without --standalone or --eval"
+                .to_string(),
+        );
+    }
+    if (options.min_difficulty.is_some() || options.max_difficulty.is_some())
+        && (!(options.multi || options.command == Command::Worksheet)
+            || options.format == Format::Json)
+    {
+        return Err(
+            "Error: --min-difficulty and --max-difficulty require --multi or the worksheet \
+             command, without --format json"
+                .to_string(),
+        );
+    }
+    if let (Some(min), Some(max)) = (options.min_difficulty, options.max_difficulty) {
+        if min > max {
+            return Err("Error: --min-difficulty is above --max-difficulty".to_string());
+        }
+    }
+    if options.all_errors && options.emit == Emit::AstJson {
+        return Err("Error: --all-errors cannot be used with --emit ast-json".to_string());
+    }
+    if options.all_errors && options.from != InputNotation::Rpn {
+        return Err("Error: --all-errors cannot be used with --from prefix or infix".to_string());
+    }
+    if options.standalone && options.katex {
+        return Err("Error: KaTeX cannot render --standalone documents".to_string());
+    }
+    if options.leftovers != Leftovers::Error
+        && (options.emit != Emit::Output
+            || options.standalone
+            || options.eval
+            || options.all_errors
+            || options.from != InputNotation::Rpn)
+    {
+        return Err(
+            "Error: --leftovers only applies to --emit output, without --standalone, --eval, \
+             --all-errors or --from prefix or infix"
+                .to_string(),
+        );
+    }
+
+    Ok(options)
+}
+
+/// Returns the value following the flag at `args[i]`.
+///
+/// # Errors
+///
+/// Returns an error message if the flag is the last argument.
+fn flag_value<'a>(args: &'a [String], i: usize, flag: &str) -> Result<&'a str, String> {
+    args.get(i + 1)
+        .map(String::as_str)
+        .ok_or_else(|| format!("Error: {} flag requires an argument", flag))
+}
+
+/// Collects string literals into arguments, for tests.
+#[cfg(test)]
+pub(crate) fn args(values: &[&str]) -> Vec<String> {
+    values.iter().map(|v| v.to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::convert::convert;
+    use log::LevelFilter;
+
+    #[test]
+    fn test_parse_args_fancy_errors() {
+        let result = parse_args(&args(&["-", "--fancy-errors"]));
+        if cfg!(feature = "miette") {
+            let options = result.expect("should parse");
+            let error = convert("5 @", &options).unwrap_err();
+            assert!(error.contains("× Unexpected character '@'"));
+        } else {
+            assert!(result
+                .unwrap_err()
+                .contains("--fancy-errors requires rpn2tex built with the 'miette' feature"));
+        }
+    }
+
+    #[test]
+    fn test_parse_args_copy() {
+        let result = parse_args(&args(&["-", "--copy"]));
+        if cfg!(feature = "clipboard") {
+            assert!(result.expect("should parse").copy);
+        } else {
+            assert!(result
+                .unwrap_err()
+                .contains("--copy requires rpn2tex built with the 'clipboard' feature"));
+        }
+        let err = parse_args(&args(&["a.rpn", "-d", "out", "--copy"])).unwrap_err();
+        assert!(err.contains("--copy cannot be combined with -d"));
+    }
+
+    #[test]
+    fn test_parse_args_pdf() {
+        let result = parse_args(&args(&["-", "--pdf", "out.pdf"]));
+        if cfg!(feature = "pdf") {
+            let options = result.expect("should parse");
+            assert_eq!(options.pdf_path, Some(PathBuf::from("out.pdf")));
+            assert!(options.standalone);
+            let err = parse_args(&args(&["-", "--pdf", "out.pdf", "--format", "rpn"])).unwrap_err();
+            assert!(err.contains("--pdf compiles one LaTeX document"));
+        } else {
+            assert!(result
+                .unwrap_err()
+                .contains("--pdf requires rpn2tex built with the 'pdf' feature"));
+        }
+    }
+
+    #[test]
+    fn test_parse_args_preview() {
+        let result = parse_args(&args(&["-", "--preview-protocol", "kitty"]));
+        if cfg!(feature = "pdf") {
+            let options = result.expect("should parse");
+            assert!(options.preview);
+            assert_eq!(options.preview_protocol, Some(PreviewProtocol::Kitty));
+            let err = parse_args(&args(&["-", "--preview", "--emit", "dot"])).unwrap_err();
+            assert!(err.contains("--preview draws one LaTeX output"));
+            let err = parse_args(&args(&["-", "--preview-protocol", "png"])).unwrap_err();
+            assert!(err.contains("Unknown --preview-protocol 'png'"));
+        } else {
+            assert!(result
+                .unwrap_err()
+                .contains("--preview requires rpn2tex built with the 'pdf' feature"));
+        }
+    }
+
+    #[test]
+    fn test_parse_args_verbosity() {
+        let level = |flags: &[&str]| {
+            let mut values = vec!["-"];
+            values.extend(flags);
+            parse_args(&args(&values))
+                .expect("should parse")
+                .log_level()
+        };
+        assert_eq!(level(&[]), LevelFilter::Warn);
+        assert_eq!(level(&["-v"]), LevelFilter::Info);
+        assert_eq!(level(&["-vv"]), LevelFilter::Debug);
+        assert_eq!(level(&["-v", "--verbose", "-v"]), LevelFilter::Debug);
+        assert_eq!(level(&["--quiet"]), LevelFilter::Error);
+
+        let err = parse_args(&args(&["-", "-q", "-v"])).unwrap_err();
+        assert!(err.contains("-q cannot be combined with -v"));
+    }
+
+    #[test]
+    fn test_parse_args_output_dir() {
+        let options =
+            parse_args(&args(&["a.rpn", "b.rpn", "-d", "out", "src/*.rpn"])).expect("should parse");
+        assert_eq!(options.extra_inputs, ["b.rpn", "src/*.rpn"]);
+        assert_eq!(options.output_dir, Some(PathBuf::from("out")));
+
+        // With several inputs, -o names the output directory
+        let options = parse_args(&args(&["a.rpn", "b.rpn", "-o", "out"])).expect("should parse");
+        assert_eq!(options.output_dir, Some(PathBuf::from("out")));
+        assert_eq!(options.output_path, None);
+
+        let err = parse_args(&args(&["a.rpn", "b.rpn"])).unwrap_err();
+        assert!(err.contains("several inputs or a glob require -d"));
+        let err = parse_args(&args(&["src/*.rpn"])).unwrap_err();
+        assert!(err.contains("several inputs or a glob require -d"));
+        let err = parse_args(&args(&["a.rpn", "-d", "out", "-o", "x.tex"])).unwrap_err();
+        assert!(err.contains("-o cannot be combined with -d"));
+        let err = parse_args(&args(&["-", "-d", "out"])).unwrap_err();
+        assert!(err.contains("-d cannot convert stdin"));
+
+        let options = parse_args(&args(&["src", "--in-place"])).expect("should parse");
+        assert!(options.in_place);
+        let err = parse_args(&args(&["a.rpn", "--in-place", "-o", "a.tex"])).unwrap_err();
+        assert!(err.contains("--in-place cannot be combined with -o or -d"));
+        let err = parse_args(&args(&["-", "--in-place"])).unwrap_err();
+        assert!(err.contains("--in-place cannot convert stdin"));
+    }
+
+    #[test]
+    fn test_parse_args_defaults() {
+        let options = parse_args(&args(&["input.rpn"])).expect("should parse");
+        assert_eq!(options, Options::new("input.rpn"));
+    }
+
+    #[test]
+    fn test_parse_args_all_flags() {
+        let options = parse_args(&args(&[
+            "-", "-o", "out.tex", "--emit", "dot", "--format", "prefix",
+        ]))
+        .expect("should parse");
+        assert_eq!(options.input_path, "-");
+        assert_eq!(options.output_path, Some(PathBuf::from("out.tex")));
+        assert_eq!(options.emit, Emit::Dot);
+        assert_eq!(options.format, Format::Prefix);
+    }
+
+    #[test]
+    fn test_parse_args_errors() {
+        let err = parse_args(&args(&["-", "--emit"])).unwrap_err();
+        assert!(err.contains("--emit flag requires an argument"));
+        let err = parse_args(&args(&["-", "--format", "svg"])).unwrap_err();
+        assert!(err.contains("Unknown --format 'svg'"));
+        let err = parse_args(&args(&["-", "--bogus"])).unwrap_err();
+        assert!(err.contains("Unknown argument '--bogus'"));
+    }
+
+    #[test]
+    fn test_parse_environment() {
+        assert_eq!(parse_environment("inline"), Some(MathEnvironment::Inline));
+        assert_eq!(
+            parse_environment("equation"),
+            Some(MathEnvironment::Equation)
+        );
+        assert_eq!(
+            parse_environment("align*"),
+            Some(MathEnvironment::AlignStar)
+        );
+        assert_eq!(parse_environment("gather"), Some(MathEnvironment::Gather));
+        assert_eq!(
+            parse_environment("display"),
+            Some(MathEnvironment::DisplayBrackets)
+        );
+        assert_eq!(
+            parse_environment("display-dollars"),
+            Some(MathEnvironment::DisplayDollars)
+        );
+        assert_eq!(parse_environment("bare"), Some(MathEnvironment::Bare));
+        assert_eq!(parse_environment("align"), None);
+    }
+
+    #[test]
+    fn test_parse_args_eval() {
+        assert!(
+            parse_args(&args(&["-", "--eval"]))
+                .expect("should parse")
+                .eval
+        );
+        let result = parse_args(&args(&["-", "--eval", "--exact"]));
+        if cfg!(feature = "exact") {
+            assert!(result.expect("should parse").exact);
+            let err = parse_args(&args(&["-", "--exact"])).unwrap_err();
+            assert!(err.contains("--exact requires --eval, --evaluate or an answer key"));
+        } else {
+            assert!(result
+                .unwrap_err()
+                .contains("--exact requires rpn2tex built with the 'exact' feature"));
+        }
+    }
+
+    #[test]
+    fn test_parse_args_repl() {
+        let result = parse_args(&args(&["repl", "--spacing", "tight"]));
+        if cfg!(feature = "readline") {
+            let options = result.expect("should parse");
+            assert_eq!(options.command, Command::Repl);
+            assert_eq!(options.input_path, "-");
+            assert_eq!(options.spacing, SpacingStyle::Tight);
+            let err = parse_args(&args(&["repl", "-o", "out.tex"])).unwrap_err();
+            assert!(err.contains("repl prints each expression's output"));
+        } else {
+            assert!(result
+                .unwrap_err()
+                .contains("repl requires rpn2tex built with the 'readline' feature"));
+        }
+    }
+
+    #[test]
+    fn test_parse_args_fmt() {
+        let options =
+            parse_args(&args(&["fmt", "a.rpn", "src/*.rpn", "--check"])).expect("should parse");
+        assert_eq!(options.command, Command::Fmt);
+        assert_eq!(options.extra_inputs, ["src/*.rpn"]);
+        assert!(options.output_dir.is_none());
+
+        let err = parse_args(&args(&["fmt", "a.rpn", "-o", "b.rpn"])).unwrap_err();
+        assert!(err.contains("fmt rewrites its inputs in place"));
+        let err = parse_args(&args(&["a.rpn", "--check"])).unwrap_err();
+        assert!(err.contains("--check requires the fmt command"));
+    }
+
+    #[test]
+    fn test_parse_args_show_result_requires_latex() {
+        let err = parse_args(&args(&["-", "--show-result", "--format", "python"])).unwrap_err();
+        assert!(err.contains("--show-result requires LaTeX output"));
+        let err = parse_args(&args(&["-", "--show-result", "--standalone"])).unwrap_err();
+        assert!(err.contains("--standalone"));
+    }
+
+    #[test]
+    fn test_parse_args_let() {
+        let options =
+            parse_args(&args(&["-", "--let", "x=2", "--let", "rate=0.5"])).expect("should parse");
+        assert!(options.allow_vars);
+        assert_eq!(
+            options.bindings,
+            [("x".to_string(), 2.0), ("rate".to_string(), 0.5)]
+        );
+
+        let err = parse_args(&args(&["-", "--let", "x"])).unwrap_err();
+        assert!(err.contains("--let expects name=value"));
+        let err = parse_args(&args(&["-", "--let", "x=inf"])).unwrap_err();
+        assert!(err.contains("--let expects name=value"));
+        let err = parse_args(&args(&["-", "--let", "2x=1"])).unwrap_err();
+        assert!(err.contains("not an identifier"));
+    }
+
+    #[test]
+    fn test_parse_markdown_flavor() {
+        assert_eq!(
+            parse_markdown_flavor("github"),
+            Some(MarkdownFlavor::GitHub)
+        );
+        assert_eq!(
+            parse_markdown_flavor("pandoc"),
+            Some(MarkdownFlavor::Pandoc)
+        );
+        assert_eq!(
+            parse_markdown_flavor("obsidian"),
+            Some(MarkdownFlavor::Obsidian)
+        );
+        assert_eq!(parse_markdown_flavor("commonmark"), None);
+    }
+}
@@ -0,0 +1,723 @@
+//! Converting every file named by the inputs with `-d` or `--in-place`.
+
+use crate::cli::convert::{convert_rows, Failure};
+use crate::cli::files::{decode_input, fill_template, write_output};
+use crate::cli::glob::{expand_glob, glob_base, is_glob};
+use crate::cli::options::{Emit, Format, Options, StatsFormat};
+use log::{debug, info};
+use rpn2tex::ast::Expr;
+use rpn2tex::batch::split_expressions;
+use rpn2tex::cache::{CacheStats, CompilerCache};
+use rpn2tex::codegen::CodeGenerator;
+use rpn2tex::filter::find_snippets;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Output generated by a `-d` or `--in-place` run, shared by the clones of
+/// its options so an expression repeated across files is generated once.
+///
+/// The generator is made from the options of the first expression
+/// rendered, since a run renders every expression with the same options.
+#[derive(Clone, Default)]
+pub(crate) struct BatchCache(Rc<RefCell<Option<DynCache>>>);
+
+/// A cache in front of the generator chosen by the options.
+type DynCache = CompilerCache<Box<dyn CodeGenerator>>;
+
+impl BatchCache {
+    /// Returns the output for `ast`, making the generator with `generator`
+    /// on first use.
+    pub(crate) fn generate(
+        &self,
+        ast: &Expr,
+        generator: impl FnOnce() -> Box<dyn CodeGenerator>,
+    ) -> String {
+        self.0
+            .borrow_mut()
+            .get_or_insert_with(|| CompilerCache::new(generator()))
+            .generate(ast)
+    }
+
+    /// Returns the lookup counts so far.
+    fn stats(&self) -> CacheStats {
+        self.0
+            .borrow()
+            .as_ref()
+            .map_or_else(CacheStats::default, CompilerCache::stats)
+    }
+}
+
+impl fmt::Debug for BatchCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("BatchCache").field(&self.stats()).finish()
+    }
+}
+
+/// Clones share one cache, and a cache never changes what is generated.
+impl PartialEq for BatchCache {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Totals of a `-d` or `--in-place` run, reported with `--stats`.
+#[derive(Debug, Default)]
+struct BatchStats {
+    /// Files converted
+    converted: usize,
+    /// Expressions in the converted files
+    expressions: usize,
+    /// Files that failed, by the kind of failure: `io` for reading or
+    /// writing, `decode`, or the kind of a [`Failure`] to convert
+    failures: BTreeMap<&'static str, usize>,
+    /// Time spent reading and decoding inputs
+    read: Duration,
+    /// Time spent converting
+    convert: Duration,
+    /// Time spent writing outputs
+    write: Duration,
+    /// Expressions whose output was reused or generated
+    cache: CacheStats,
+}
+
+impl BatchStats {
+    /// Returns the number of files that failed.
+    fn failed(&self) -> usize {
+        self.failures.values().sum()
+    }
+
+    /// Counts a failure of `kind` and returns its error message.
+    fn fail(&mut self, kind: &'static str, error: String) -> String {
+        *self.failures.entry(kind).or_default() += 1;
+        error
+    }
+
+    /// Formats the totals as a table, `total` being the run's wall-clock
+    /// time.
+    fn text(&self, total: Duration) -> String {
+        let mut text = format!(
+            "Files:       {} processed, {} converted, {} failed\n\
+             Expressions: {} converted\n",
+            self.converted + self.failed(),
+            self.converted,
+            self.failed(),
+            self.expressions
+        );
+        if !self.failures.is_empty() {
+            let failures: Vec<String> = self
+                .failures
+                .iter()
+                .map(|(kind, count)| format!("{} {}", kind, count))
+                .collect();
+            text.push_str(&format!("Failures:    {}\n", failures.join(", ")));
+        }
+        text.push_str(&format!(
+            "Cache:       {} hits, {} misses\n",
+            self.cache.hits, self.cache.misses
+        ));
+        text.push_str(&format!(
+            "Time:        read {:.2?}, convert {:.2?}, write {:.2?}, total {:.2?}\n",
+            self.read, self.convert, self.write, total
+        ));
+        text
+    }
+
+    /// Formats the totals as pretty-printed JSON, times in seconds.
+    fn json(&self, total: Duration) -> String {
+        let json = serde_json::json!({
+            "files": self.converted + self.failed(),
+            "converted": self.converted,
+            "failed": self.failed(),
+            "expressions": self.expressions,
+            "failures": self.failures,
+            "cache": { "hits": self.cache.hits, "misses": self.cache.misses },
+            "seconds": {
+                "read": self.read.as_secs_f64(),
+                "convert": self.convert.as_secs_f64(),
+                "write": self.write.as_secs_f64(),
+                "total": total.as_secs_f64(),
+            },
+        });
+        format!("{:#}\n", json)
+    }
+}
+
+/// Converts every file named by the inputs into the `-d` directory or, with
+/// `--in-place`, beside each file, printing the failures and a summary to
+/// stderr, and with `-v` each converted file. `--stats` adds times by stage
+/// and failures by kind.
+///
+/// Returns the exit code: 1 if the inputs name no files or any file failed.
+pub(crate) fn convert_files(options: &Options) -> i32 {
+    let run_start = Instant::now();
+    let files = match collect_inputs(options) {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+
+    debug!("Found {} input files", files.len());
+
+    let mut outputs: Vec<PathBuf> = Vec::with_capacity(files.len());
+    for (input, relative) in &files {
+        let output = match batch_output(input, relative, options) {
+            Ok(output) => output,
+            Err(e) => {
+                eprintln!("{}", e);
+                return 1;
+            }
+        };
+        if let Some(other) = outputs.iter().position(|earlier| *earlier == output) {
+            eprintln!(
+                "Error: {} and {} would both be written to {}",
+                files[other].0.display(),
+                input.display(),
+                output.display()
+            );
+            return 1;
+        }
+        outputs.push(output);
+    }
+
+    if options.dry_run {
+        return plan_files(&files, &outputs, options);
+    }
+
+    let cache = BatchCache::default();
+    let options = &Options {
+        cache: Some(cache.clone()),
+        ..options.clone()
+    };
+    let mut stats = BatchStats::default();
+    for ((input, _), output) in files.iter().zip(&outputs) {
+        let start = Instant::now();
+        match convert_file(input, output, options, &mut stats) {
+            Ok(()) => info!(
+                "ok: {} -> {} in {:.2?}",
+                input.display(),
+                output.display(),
+                start.elapsed()
+            ),
+            Err(e) => eprintln!("failed: {}\n{}", input.display(), e),
+        }
+    }
+    stats.cache = cache.stats();
+
+    let failed = stats.failed();
+    match options.stats {
+        Some(StatsFormat::Text) => eprint!("{}", stats.text(run_start.elapsed())),
+        Some(StatsFormat::Json) => print!("{}", stats.json(run_start.elapsed())),
+        None => {}
+    }
+    if !options.quiet && options.stats != Some(StatsFormat::Text) {
+        eprintln!(
+            "{} of {} files converted, {} failed",
+            files.len() - failed,
+            files.len(),
+            failed
+        );
+    }
+    i32::from(failed > 0)
+}
+
+/// Lists, for `--dry-run`, each input of a `-d` or `--in-place` run and the
+/// output it would be written to, flagging outputs that already exist or
+/// would replace their input. Nothing is read past the file names, and
+/// nothing is written.
+///
+/// Returns the exit code: 1 if an output would replace its input, which the
+/// run itself refuses.
+fn plan_files(files: &[(PathBuf, PathBuf)], outputs: &[PathBuf], options: &Options) -> i32 {
+    let (mut overwrites, mut refused) = (0, 0);
+    for ((input, _), output) in files.iter().zip(outputs) {
+        let note = if output == input {
+            refused += 1;
+            " (would overwrite the input; refused)"
+        } else if output.exists() {
+            overwrites += 1;
+            " (overwrites an existing file)"
+        } else {
+            ""
+        };
+        println!("{} -> {}{}", input.display(), output.display(), note);
+    }
+    if !options.quiet {
+        eprintln!(
+            "{} files would be converted, {} existing files overwritten, {} refused",
+            files.len() - refused,
+            overwrites,
+            refused
+        );
+    }
+    i32::from(refused > 0)
+}
+
+/// Converts one input file into `output`, creating its directory, and adds
+/// its outcome and the time of each stage to `stats`.
+///
+/// # Errors
+///
+/// Returns an error message if the file cannot be read, decoded, converted
+/// or written, or if rows of a `--format csv` file fail, after writing the
+/// rows that converted.
+fn convert_file(
+    input: &Path,
+    output: &Path,
+    options: &Options,
+    stats: &mut BatchStats,
+) -> Result<(), String> {
+    if output == input {
+        return Err(stats.fail(
+            "io",
+            format!(
+                "Error: the output would overwrite the input {}",
+                input.display()
+            ),
+        ));
+    }
+
+    let start = Instant::now();
+    let bytes = fs::read(input);
+    stats.read += start.elapsed();
+    let bytes = bytes.map_err(|e| stats.fail("io", format!("Error reading input: {}", e)))?;
+    let start = Instant::now();
+    let source = decode_input(&bytes, options);
+    stats.read += start.elapsed();
+    let source = source.map_err(|e| stats.fail("decode", e))?;
+
+    let start = Instant::now();
+    let converted = convert_rows(&source, options);
+    stats.convert += start.elapsed();
+    let (content, failures) = converted.map_err(|e| stats.fail(e.kind, e.message))?;
+    let content = fill_template(content, options);
+
+    let start = Instant::now();
+    let written = output
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| write_output(&content, Some(&output.to_path_buf())));
+    stats.write += start.elapsed();
+    written.map_err(|e| stats.fail("io", format!("Error writing output: {}", e)))?;
+    // The rows that converted are written, but the file still failed
+    if !failures.is_empty() {
+        let failure = Failure::join(failures);
+        return Err(stats.fail(failure.kind, failure.message));
+    }
+
+    stats.converted += 1;
+    stats.expressions += expression_count(&source, options);
+    Ok(())
+}
+
+/// Returns the number of expressions `convert` finds in `source`: one per
+/// `--multi` segment or `--filter` snippet, otherwise one.
+fn expression_count(source: &str, options: &Options) -> usize {
+    if options.filter.is_some() {
+        find_snippets(source).len()
+    } else if options.multi {
+        split_expressions(source).len()
+    } else {
+        1
+    }
+}
+
+/// Lists the files named by the inputs, each with its path relative to
+/// the directory the output tree mirrors: a directory input or a glob's
+/// leading literal directories, or a file's own directory.
+///
+/// # Errors
+///
+/// Returns an error message if an input names no files or a directory
+/// cannot be read.
+pub(crate) fn collect_inputs(options: &Options) -> Result<Vec<(PathBuf, PathBuf)>, String> {
+    let mut files = Vec::new();
+    for input in std::iter::once(&options.input_path).chain(&options.extra_inputs) {
+        let (base, matches) = if is_glob(input) {
+            (glob_base(input), expand_glob(input))
+        } else if Path::new(input).is_dir() {
+            let pattern = Path::new(input).join("**").join("*.rpn");
+            (
+                PathBuf::from(input),
+                expand_glob(&pattern.to_string_lossy()),
+            )
+        } else {
+            let path = PathBuf::from(input);
+            let base = path.parent().map(Path::to_path_buf).unwrap_or_default();
+            (base, Ok(vec![path]))
+        };
+        let matches = matches.map_err(|e| format!("Error reading {}: {}", input, e))?;
+        if matches.is_empty() {
+            return Err(format!("Error: No files match '{}'", input));
+        }
+        for path in matches {
+            let relative = path.strip_prefix(&base).unwrap_or(&path).to_path_buf();
+            files.push((path, relative));
+        }
+    }
+    Ok(files)
+}
+
+/// Returns the path `convert_files` writes `input` to, whose path is
+/// `relative` in the mirrored tree: under the `-d` directory, or with
+/// `--in-place` under the directory `relative` starts from. Its name comes
+/// from `--output-pattern`, or by default replaces the input's extension.
+///
+/// # Errors
+///
+/// Returns an error message if the pattern is malformed.
+fn batch_output(input: &Path, relative: &Path, options: &Options) -> Result<PathBuf, String> {
+    let extension = output_extension(options);
+    let Some(pattern) = &options.output_pattern else {
+        return Ok(match &options.output_dir {
+            Some(output_dir) => output_dir.join(relative),
+            None => input.to_path_buf(),
+        }
+        .with_extension(extension));
+    };
+
+    let mut output = match &options.output_dir {
+        Some(output_dir) => output_dir.clone(),
+        None => input
+            .ancestors()
+            .nth(relative.components().count())
+            .map(Path::to_path_buf)
+            .unwrap_or_default(),
+    };
+    let dir = relative.parent().unwrap_or(Path::new("")).to_string_lossy();
+    let stem = relative.file_stem().unwrap_or_default().to_string_lossy();
+    let name = fill_pattern(
+        pattern,
+        &[("dir", &dir), ("stem", &stem), ("format", extension)],
+    )?;
+    // An empty {dir} leaves an empty component, as in "/a.tex"
+    for component in name.split('/') {
+        if !component.is_empty() && component != "." {
+            output.push(component);
+        }
+    }
+    Ok(output)
+}
+
+/// Replaces each `{name}` placeholder in an `--output-pattern` by its value.
+///
+/// # Errors
+///
+/// Returns an error message if a placeholder is unknown or unclosed.
+pub(crate) fn fill_pattern(pattern: &str, values: &[(&str, &str)]) -> Result<String, String> {
+    let mut filled = String::with_capacity(pattern.len());
+    let mut rest = pattern;
+    while let Some(open) = rest.find('{') {
+        filled.push_str(&rest[..open]);
+        let close = rest[open..]
+            .find('}')
+            .ok_or_else(|| "Error: Unclosed '{' in --output-pattern".to_string())?;
+        let name = &rest[open + 1..open + close];
+        let value = values
+            .iter()
+            .find(|(placeholder, _)| *placeholder == name)
+            .ok_or_else(|| {
+                format!(
+                    "Error: Unknown placeholder '{{{}}}' in --output-pattern",
+                    name
+                )
+            })?;
+        filled.push_str(value.1);
+        rest = &rest[open + close + 1..];
+    }
+    filled.push_str(rest);
+    Ok(filled)
+}
+
+/// Returns the extension of the files `-d` writes, by artifact and format.
+fn output_extension(options: &Options) -> &'static str {
+    match options.emit {
+        Emit::Dot => "dot",
+        Emit::Ast => "txt",
+        Emit::AstJson => "json",
+        Emit::Packages => "txt",
+        Emit::Tokens if options.format == Format::Json => "json",
+        Emit::Tokens => "txt",
+        Emit::Output if options.evaluate => "txt",
+        Emit::Output if options.filter.is_some() => "md",
+        Emit::Output => match options.format {
+            Format::Latex | Format::Context => "tex",
+            Format::Markdown => "md",
+            Format::Python => "py",
+            Format::Wolfram | Format::WolframInfix => "wl",
+            Format::ContentMathml => "mml",
+            Format::Json => "json",
+            Format::Csv => "csv",
+            Format::Prefix | Format::Rpn | Format::Infix => "txt",
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::args::{args, parse_args};
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn test_convert_files_mirrors_tree() {
+        let root = env::temp_dir().join(format!("rpn2tex-batch-{}", process::id()));
+        let src = root.join("src");
+        fs::create_dir_all(src.join("nested")).unwrap();
+        fs::write(src.join("a.rpn"), "5 3 +").unwrap();
+        fs::write(src.join("nested").join("b.rpn"), "2 4 *").unwrap();
+        fs::write(src.join("nested").join("bad.rpn"), "2 +").unwrap();
+        fs::write(src.join("notes.txt"), "not rpn").unwrap();
+
+        let pattern = format!("{}/**/*.rpn", src.display());
+        let files = collect_inputs(&Options::new(pattern)).unwrap();
+        let relative: Vec<&Path> = files.iter().map(|(_, r)| r.as_path()).collect();
+        assert_eq!(
+            relative,
+            [
+                Path::new("a.rpn"),
+                Path::new("nested/b.rpn"),
+                Path::new("nested/bad.rpn")
+            ]
+        );
+        // A directory stands for the .rpn files under it
+        let from_dir = collect_inputs(&Options::new(src.to_string_lossy())).unwrap();
+        assert_eq!(from_dir, files);
+
+        let out = root.join("out");
+        let options = Options {
+            output_dir: Some(out.clone()),
+            ..Options::new(src.to_string_lossy())
+        };
+        assert_eq!(convert_files(&options), 1);
+        assert_eq!(fs::read_to_string(out.join("a.tex")).unwrap(), "$5 + 3$");
+        assert_eq!(
+            fs::read_to_string(out.join("nested").join("b.tex")).unwrap(),
+            "$2 \\times 4$"
+        );
+        assert!(!out.join("nested").join("bad.tex").exists());
+
+        let err = collect_inputs(&Options::new(format!("{}/*.tex", src.display()))).unwrap_err();
+        assert!(err.contains("No files match"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_convert_files_output_pattern() {
+        let root = env::temp_dir().join(format!("rpn2tex-pattern-{}", process::id()));
+        let src = root.join("src");
+        fs::create_dir_all(src.join("nested")).unwrap();
+        fs::write(src.join("a.rpn"), "5 3 +").unwrap();
+        fs::write(src.join("nested").join("b.rpn"), "2 4 *").unwrap();
+
+        let out = root.join("out");
+        let options = parse_args(&args(&[
+            &src.to_string_lossy(),
+            "-d",
+            &out.to_string_lossy(),
+            "--output-pattern",
+            "{format}/{dir}/{stem}-math.{format}",
+        ]))
+        .expect("should parse");
+        assert_eq!(convert_files(&options), 0);
+        assert_eq!(
+            fs::read_to_string(out.join("tex").join("a-math.tex")).unwrap(),
+            "$5 + 3$"
+        );
+        assert!(out.join("tex/nested/b-math.tex").exists());
+
+        // With --in-place the pattern starts from the input directory
+        let options = parse_args(&args(&[
+            &src.to_string_lossy(),
+            "--in-place",
+            "--output-pattern",
+            "build/{stem}.{format}",
+        ]))
+        .expect("should parse");
+        assert_eq!(convert_files(&options), 0);
+        assert!(src.join("build/a.tex").exists());
+        assert!(src.join("build/b.tex").exists());
+
+        // Outputs that would overwrite each other are refused up front
+        let options = Options {
+            output_pattern: Some("{format}.out".to_string()),
+            ..options
+        };
+        assert_eq!(convert_files(&options), 1);
+        assert!(!src.join("tex.out").exists());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_batch_stats() {
+        let root = env::temp_dir().join(format!("rpn2tex-stats-{}", process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("a.rpn"), "5 3 +\n2 4 *\n5 3 +").unwrap();
+        fs::write(root.join("b.rpn"), "1 +").unwrap();
+        fs::write(root.join("c.rpn"), b"1 \xff").unwrap();
+        fs::write(root.join("d.rpn"), "5 @").unwrap();
+        fs::write(root.join("e.rpn"), "let a = 2\nb 1 +").unwrap();
+
+        let options = parse_args(&args(&[
+            &root.to_string_lossy(),
+            "--in-place",
+            "--multi",
+            "--stats",
+            "json",
+        ]))
+        .expect("should parse");
+        let cache = BatchCache::default();
+        let options = Options {
+            cache: Some(cache.clone()),
+            ..options
+        };
+        let mut stats = BatchStats::default();
+        // f.rpn does not exist, and e.rpn is read as one program
+        let program = Options {
+            multi: false,
+            ..options.clone()
+        };
+        for name in ["a", "b", "c", "d", "e", "f"] {
+            let input = root.join(name).with_extension("rpn");
+            let options = if name == "e" { &program } else { &options };
+            let _ = convert_file(&input, &input.with_extension("tex"), options, &mut stats);
+        }
+        stats.cache = cache.stats();
+        assert_eq!(
+            (stats.converted, stats.expressions, stats.failed()),
+            (1, 3, 5)
+        );
+        assert_eq!(
+            fs::read_to_string(root.join("a.tex")).unwrap(),
+            "$5 + 3$\n$2 \\times 4$\n$5 + 3$"
+        );
+
+        let json: serde_json::Value =
+            serde_json::from_str(&stats.json(Duration::from_millis(3))).expect("valid JSON");
+        assert_eq!(json["files"], 6);
+        assert_eq!(json["failures"]["parse"], 1);
+        assert_eq!(json["failures"]["decode"], 1);
+        assert_eq!(json["failures"]["lex"], 1);
+        assert_eq!(json["failures"]["binding"], 1);
+        assert_eq!(json["failures"]["io"], 1);
+        assert_eq!(json["cache"]["hits"], 1);
+        assert_eq!(json["cache"]["misses"], 2);
+        assert_eq!(json["seconds"]["total"], 0.003);
+
+        let text = stats.text(Duration::from_millis(3));
+        assert!(text.starts_with("Files:       6 processed, 1 converted, 5 failed\n"));
+        assert!(text.contains("Failures:    binding 1, decode 1, io 1, lex 1, parse 1\n"));
+        assert!(text.contains("Cache:       1 hits, 2 misses\n"));
+        assert!(text.contains("total 3.00ms"));
+
+        let err = parse_args(&args(&["a.rpn", "--stats", "text"])).unwrap_err();
+        assert!(err.contains("--stats requires -d or --in-place"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_fill_pattern() {
+        let values = [("dir", "x/y"), ("stem", "a"), ("format", "md")];
+        assert_eq!(
+            fill_pattern("{dir}/{stem}.{format}", &values).unwrap(),
+            "x/y/a.md"
+        );
+        assert_eq!(fill_pattern("plain", &values).unwrap(), "plain");
+        let err = fill_pattern("{stem}.{ext}", &values).unwrap_err();
+        assert_eq!(
+            err,
+            "Error: Unknown placeholder '{ext}' in --output-pattern"
+        );
+        let err = fill_pattern("{stem", &values).unwrap_err();
+        assert!(err.contains("Unclosed '{'"));
+
+        let err = parse_args(&args(&["a.rpn", "--output-pattern", "{stem}.tex"])).unwrap_err();
+        assert!(err.contains("--output-pattern requires -d or --in-place"));
+        let err =
+            parse_args(&args(&["a.rpn", "-d", "out", "--output-pattern", "{name}"])).unwrap_err();
+        assert!(err.contains("Unknown placeholder '{name}'"));
+    }
+
+    #[test]
+    fn test_convert_files_in_place() {
+        let root = env::temp_dir().join(format!("rpn2tex-in-place-{}", process::id()));
+        fs::create_dir_all(root.join("nested")).unwrap();
+        fs::write(root.join("a.rpn"), "5 3 +").unwrap();
+        fs::write(root.join("nested").join("b.rpn"), "2 4 *").unwrap();
+
+        let options = Options {
+            in_place: true,
+            ..Options::new(root.to_string_lossy())
+        };
+        assert_eq!(convert_files(&options), 0);
+        assert_eq!(fs::read_to_string(root.join("a.tex")).unwrap(), "$5 + 3$");
+        assert_eq!(
+            fs::read_to_string(root.join("nested").join("b.tex")).unwrap(),
+            "$2 \\times 4$"
+        );
+
+        // An input is never replaced by its own output
+        let input = root.join("c.txt");
+        fs::write(&input, "1 2 +").unwrap();
+        let options = Options {
+            format: Format::Rpn,
+            ..options
+        };
+        let mut stats = BatchStats::default();
+        let err =
+            convert_file(&input, &input.with_extension("txt"), &options, &mut stats).unwrap_err();
+        assert!(err.contains("would overwrite the input"));
+        assert_eq!(stats.failures["io"], 1);
+        assert_eq!(fs::read_to_string(&input).unwrap(), "1 2 +");
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let root = env::temp_dir().join(format!("rpn2tex-dry-run-{}", process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("a.rpn"), "5 3 +").unwrap();
+        fs::write(root.join("b.rpn"), "1 +").unwrap();
+        fs::write(root.join("b.tex"), "kept").unwrap();
+
+        let out = root.join("out");
+        let src = root.to_string_lossy();
+        let options = parse_args(&args(&[&src, "-d", &out.to_string_lossy(), "--dry-run"]))
+            .expect("should parse");
+        assert_eq!(convert_files(&options), 0);
+        assert!(!out.exists());
+
+        let options = parse_args(&args(&[&src, "--in-place", "--dry-run"])).expect("should parse");
+        assert_eq!(convert_files(&options), 0);
+        assert!(!root.join("a.tex").exists());
+        assert_eq!(fs::read_to_string(root.join("b.tex")).unwrap(), "kept");
+
+        // An output that would replace its input fails the run it plans
+        let options = Options {
+            output_pattern: Some("{stem}.rpn".to_string()),
+            ..options
+        };
+        assert_eq!(convert_files(&options), 1);
+        assert_eq!(fs::read_to_string(root.join("a.rpn")).unwrap(), "5 3 +");
+
+        let err = parse_args(&args(&["a.rpn", "--dry-run"])).unwrap_err();
+        assert!(err.contains("--dry-run requires -d or --in-place"));
+        let err = parse_args(&args(&[
+            "a.rpn",
+            "--in-place",
+            "--dry-run",
+            "--stats",
+            "text",
+        ]))
+        .unwrap_err();
+        assert!(err.contains("--stats cannot be combined with --dry-run"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
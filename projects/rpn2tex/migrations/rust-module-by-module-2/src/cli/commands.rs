@@ -0,0 +1,271 @@
+//! The `fmt` and `lint` commands.
+
+use crate::cli::batch::collect_inputs;
+use crate::cli::convert::{format_compile_error, is_program, parse_program, parse_segment};
+use crate::cli::files::{decode_input, read_input, write_output};
+use crate::cli::options::{Format, LetStyle, Options};
+use log::info;
+use rpn2tex::batch::split_expressions;
+use rpn2tex::format::format_source;
+use rpn2tex::lint::{Lint, Linter};
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// Rewrites the inputs of the `fmt` command in canonical form: each file
+/// in place if it changes, or stdin to stdout. With `--check` nothing is
+/// written, and the inputs that would change are listed instead.
+///
+/// Returns the exit code: 1 if an input fails to read or format or, with
+/// `--check`, is not formatted.
+pub(crate) fn format_files(options: &Options) -> i32 {
+    let files = if options.input_path == "-" {
+        vec![PathBuf::from("-")]
+    } else {
+        match collect_inputs(options) {
+            Ok(files) => files.into_iter().map(|(input, _)| input).collect(),
+            Err(e) => {
+                eprintln!("{}", e);
+                return 1;
+            }
+        }
+    };
+
+    let (mut changed, mut failed) = (0, 0);
+    for path in &files {
+        let name = path.to_string_lossy();
+        let result = read_input(&name)
+            .map_err(|e| format!("Error reading input: {}", e))
+            .and_then(|bytes| decode_input(&bytes, options))
+            .and_then(|source| {
+                let formatted = format_source(&source, |segment| options.compiler().lexer(segment))
+                    .map_err(|e| format_compile_error(&options.error_formatter(&source), &e))?;
+                Ok((source, formatted))
+            });
+        let written = match result {
+            Ok((_, formatted)) if name == "-" && !options.check => {
+                write_output(&formatted, None).map_err(|e| format!("Error writing output: {}", e))
+            }
+            Ok((source, formatted)) if source != formatted => {
+                changed += 1;
+                if options.check {
+                    eprintln!("Not formatted: {}", name);
+                    Ok(())
+                } else {
+                    info!("Formatted {}", name);
+                    write_output(&formatted, Some(path))
+                        .map_err(|e| format!("Error writing output: {}", e))
+                }
+            }
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = written {
+            eprintln!("failed: {}\n{}", name, e);
+            failed += 1;
+        }
+    }
+
+    if !options.quiet && options.input_path != "-" {
+        let verb = if options.check {
+            "need formatting"
+        } else {
+            "reformatted"
+        };
+        eprintln!("{} of {} files {}", changed, files.len(), verb);
+    }
+    i32::from(failed > 0 || (options.check && changed > 0))
+}
+
+/// A finding of the `lint` command, with the input it is in.
+#[derive(Serialize)]
+struct FileLint<'a> {
+    /// The input's path, or `-` for stdin
+    file: &'a str,
+    #[serde(flatten)]
+    lint: &'a Lint,
+}
+
+/// Lints the inputs of the `lint` command, printing what it finds with
+/// source context or, with `--format json`, as one JSON array.
+///
+/// Returns the exit code: 1 if an input fails to read or parse, or has
+/// anything to report.
+pub(crate) fn lint_files(options: &Options) -> i32 {
+    let files = if options.input_path == "-" {
+        vec![PathBuf::from("-")]
+    } else {
+        match collect_inputs(options) {
+            Ok(files) => files.into_iter().map(|(input, _)| input).collect(),
+            Err(e) => {
+                eprintln!("{}", e);
+                return 1;
+            }
+        }
+    };
+
+    let mut found = Vec::new();
+    let mut failed = 0;
+    for path in &files {
+        let name = path.to_string_lossy();
+        let result = read_input(&name)
+            .map_err(|e| format!("Error reading input: {}", e))
+            .and_then(|bytes| decode_input(&bytes, options))
+            .and_then(|source| Ok((lint_source(&source, options)?, source)));
+        match result {
+            Ok((lints, source)) => {
+                if options.format != Format::Json {
+                    let formatter = options.error_formatter(&source);
+                    for lint in &lints {
+                        let message = format!("{} [{}]", lint.diagnostic.message(), lint.rule);
+                        let report =
+                            formatter.format_span_error(message, lint.diagnostic.span(), 1);
+                        println!(
+                            "{}: {}",
+                            name,
+                            formatter.with_help(report, lint.diagnostic.help())
+                        );
+                    }
+                }
+                found.extend(lints.into_iter().map(|lint| (name.to_string(), lint)));
+            }
+            Err(e) => {
+                eprintln!("failed: {}\n{}", name, e);
+                failed += 1;
+            }
+        }
+    }
+
+    if options.format == Format::Json {
+        let report: Vec<_> = found
+            .iter()
+            .map(|(file, lint)| FileLint { file, lint })
+            .collect();
+        match serde_json::to_string_pretty(&report) {
+            Ok(json) => println!("{}", json),
+            Err(e) => {
+                eprintln!("Error writing output: {}", e);
+                return 1;
+            }
+        }
+    } else if !options.quiet {
+        eprintln!("{} problems in {} files", found.len(), files.len());
+    }
+    i32::from(failed > 0 || !found.is_empty())
+}
+
+/// Lints each expression of `source` or, for a program, its final
+/// expression with the bound names kept.
+///
+/// # Errors
+///
+/// Returns the formatted errors of every expression that fails to parse.
+fn lint_source(source: &str, options: &Options) -> Result<Vec<Lint>, String> {
+    let options = Options {
+        allow_vars: true,
+        let_style: LetStyle::Symbolic,
+        ..options.clone()
+    };
+    let mut linter = Linter::new();
+    if let Some(depth) = options.max_nesting {
+        linter = linter.with_max_nesting(u32::try_from(depth).unwrap_or(u32::MAX));
+    }
+    if is_program(source, &options) {
+        return Ok(linter.lint(&parse_program(source, &options)?));
+    }
+
+    let mut lints = Vec::new();
+    let mut errors = Vec::new();
+    for segment in split_expressions(source) {
+        match parse_segment(source, &segment, &options) {
+            Ok(ast) => lints.extend(linter.lint(&ast)),
+            Err(e) => errors.push(String::from(e)),
+        }
+    }
+    if errors.is_empty() {
+        Ok(lints)
+    } else {
+        Err(errors.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::args::{args, parse_args};
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    #[test]
+    fn test_format_files() {
+        let root = env::temp_dir().join(format!("rpn2tex-fmt-{}", process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let messy = root.join("messy.rpn");
+        let tidy = root.join("tidy.rpn");
+        let bad = root.join("bad.rpn");
+        fs::write(&messy, "5   1_000 +   # total\n\n\n").unwrap();
+        fs::write(&tidy, "2 3 *\n").unwrap();
+        fs::write(&bad, "2 @\n").unwrap();
+        let dir = root.to_string_lossy();
+
+        let check = parse_args(&args(&["fmt", &dir, "--check", "-q"])).expect("should parse");
+        assert_eq!(format_files(&check), 1);
+        assert_eq!(
+            fs::read_to_string(&messy).unwrap(),
+            "5   1_000 +   # total\n\n\n"
+        );
+
+        // The malformed file fails and is left alone; the others are written
+        let options = parse_args(&args(&["fmt", &dir, "-q"])).expect("should parse");
+        assert_eq!(format_files(&options), 1);
+        assert_eq!(fs::read_to_string(&messy).unwrap(), "5 1000 + # total\n");
+        assert_eq!(fs::read_to_string(&bad).unwrap(), "2 @\n");
+        fs::remove_file(&bad).unwrap();
+        assert_eq!(format_files(&check), 0);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_lint() {
+        let options = parse_args(&args(&["lint", "-"])).expect("should parse");
+        let rules = |source| -> Vec<_> {
+            lint_source(source, &options)
+                .unwrap()
+                .iter()
+                .map(|lint| (lint.rule.name(), lint.diagnostic.line()))
+                .collect()
+        };
+        assert_eq!(
+            rules("5 3 +\nx 1 *; 2 y +\n007 1 +"),
+            [("redundant-operation", 2), ("suspicious-literal", 3)]
+        );
+        if cfg!(feature = "exact") {
+            assert_eq!(rules("x 2 3 * +"), [("constant-expression", 1)]);
+        }
+        // Bound names are kept, so their values are not folded in
+        assert!(rules("let a = 5\na 1 +").is_empty());
+        let err = lint_source("1 2 +\n3 @", &options).unwrap_err();
+        assert!(err.contains("Line 2, column 3"));
+
+        let options =
+            parse_args(&args(&["lint", "-", "--max-nesting", "1"])).expect("should parse");
+        assert_eq!(lint_source("1 2 + 3 *", &options).unwrap().len(), 1);
+
+        let root = env::temp_dir().join(format!("rpn2tex-lint-{}", process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("clean.rpn"), "2 3 *\n").unwrap();
+        let dir = root.to_string_lossy();
+        let options =
+            parse_args(&args(&["lint", &dir, "--format", "json", "-q"])).expect("should parse");
+        assert_eq!(lint_files(&options), 0);
+        fs::write(root.join("messy.rpn"), "x 0 +\n").unwrap();
+        assert_eq!(lint_files(&options), 1);
+        fs::remove_dir_all(&root).unwrap();
+
+        let err = parse_args(&args(&["lint", "a.rpn", "--format", "rpn"])).unwrap_err();
+        assert!(err.contains("lint only reports on its inputs"));
+        let err = parse_args(&args(&["a.rpn", "--max-nesting", "3"])).unwrap_err();
+        assert!(err.contains("--max-nesting requires the lint command"));
+    }
+}
@@ -0,0 +1,519 @@
+//! Settings from `rpn2tex.toml` files and `RPN2TEX_` environment variables,
+//! applied as defaults under the arguments of the command line.
+
+use crate::cli::args::parse_args;
+use crate::cli::options::{Command, Options};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the configuration file read from the user's configuration
+/// directory and from the project.
+const CONFIG_FILE: &str = "rpn2tex.toml";
+
+/// Switches a configuration file may turn on with `true` or off with
+/// `false`, named like their flags.
+const CONFIG_SWITCHES: [&str; 12] = [
+    "standalone",
+    "katex",
+    "leading-zero",
+    "no-negative-parens",
+    "siunitx",
+    "frac",
+    "allow-vars",
+    "unicode-digits",
+    "comma-groups",
+    "lenient-numbers",
+    "fold-constants",
+    "all-errors",
+];
+
+/// Settings a configuration file may give a value, named like their flags.
+const CONFIG_VALUES: [&str; 19] = [
+    "format",
+    "from",
+    "environment",
+    "spacing",
+    "parens",
+    "thousands",
+    "decimal",
+    "decimal-places",
+    "sig-figs",
+    "mul-style",
+    "frac-style",
+    "max-input-bytes",
+    "max-tokens",
+    "max-depth",
+    "let-style",
+    "prelude",
+    "leftovers",
+    "markdown-flavor",
+    "color",
+];
+
+/// Prefix of the environment variables holding settings.
+const ENV_PREFIX: &str = "RPN2TEX_";
+
+/// One setting from a configuration file or the environment, as the flags
+/// that give it its value. A switch turned off has no flags, so it still
+/// replaces an earlier layer's `true`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Setting {
+    /// The setting's name, shared by every layer that may set it.
+    pub(crate) key: String,
+    /// The flags that set it, empty for a switch turned off.
+    pub(crate) args: Vec<String>,
+}
+
+impl Setting {
+    fn new(key: &str, args: Vec<String>) -> Self {
+        Self {
+            key: key.to_string(),
+            args,
+        }
+    }
+}
+
+/// Adds a layer of settings over `settings`, each replacing any earlier
+/// value of the same setting. `settings` stays ordered from the lowest
+/// layer to the highest.
+pub(crate) fn merge_settings(settings: &mut Vec<Setting>, layer: Vec<Setting>) {
+    for setting in layer {
+        settings.retain(|earlier| earlier.key != setting.key);
+        settings.push(setting);
+    }
+}
+
+/// Returns the settings of the configuration files: the user's, in
+/// `$XDG_CONFIG_HOME` (or `~/.config`), then the project's, the nearest in
+/// the current directory or its ancestors, so the project's settings
+/// override the user's.
+///
+/// # Errors
+///
+/// Returns an error message naming the file if one cannot be read or holds
+/// an invalid setting.
+pub(crate) fn config_settings() -> Result<Vec<Setting>, String> {
+    let user = user_config_dir()
+        .map(|dir| dir.join(CONFIG_FILE))
+        .filter(|path| path.is_file());
+    let project = env::current_dir()
+        .ok()
+        .and_then(|dir| find_project_config(&dir))
+        .filter(|path| Some(path) != user.as_ref());
+
+    let mut settings = Vec::new();
+    for path in user.into_iter().chain(project) {
+        merge_settings(&mut settings, load_config(&path)?);
+    }
+    Ok(settings)
+}
+
+/// Parses the command line over `settings`, which act as defaults: the
+/// command line is checked on its own, then each setting, from the highest
+/// layer down, is applied unless it conflicts with the subcommand, the flags
+/// given or a setting already applied. A skipped setting is not an error,
+/// since the user did not ask for it on this run.
+///
+/// # Errors
+///
+/// Returns `parse_args`'s message for the command line alone.
+pub(crate) fn parse_args_with_settings(
+    args: &[String],
+    settings: &[Setting],
+) -> Result<Options, String> {
+    let mut options = parse_args(args)?;
+    // Settings go after the input, which follows any subcommand; `repl`
+    // takes no input
+    let input = match args.first().and_then(|first| Command::parse(first)) {
+        Some(Command::Repl) | None => 1,
+        Some(_) => 2,
+    }
+    .min(args.len());
+
+    let mut applied: Vec<&Setting> = Vec::new();
+    for setting in settings
+        .iter()
+        .rev()
+        .filter(|setting| !setting.args.is_empty())
+    {
+        // Before the flags, which override them
+        let mut values = args[..input].to_vec();
+        values.extend(
+            std::iter::once(setting)
+                .chain(applied.iter().copied())
+                .flat_map(|setting| setting.args.iter().cloned()),
+        );
+        values.extend_from_slice(&args[input..]);
+        if let Ok(parsed) = parse_args(&values) {
+            options = parsed;
+            applied.push(setting);
+        }
+    }
+    Ok(options)
+}
+
+/// Returns the user's configuration directory: `$XDG_CONFIG_HOME`, or
+/// `~/.config` if that is unset.
+pub(crate) fn user_config_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+}
+
+/// Returns the configuration file nearest to `dir`: in `dir` itself or the
+/// closest of its ancestors.
+fn find_project_config(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(CONFIG_FILE))
+        .find(|path| path.is_file())
+}
+
+/// Reads a configuration file into its settings.
+///
+/// # Errors
+///
+/// Returns an error message naming the file if it cannot be read or holds
+/// an invalid setting.
+fn load_config(path: &Path) -> Result<Vec<Setting>, String> {
+    let source =
+        fs::read_to_string(path).map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+    parse_settings(&source, path.parent().unwrap_or(Path::new("")))
+        .map_err(|e| format!("Error in {}: {}", path.display(), e))
+}
+
+/// Converts the settings of a configuration file to the flags that set
+/// them. A `prelude` path is taken relative to `dir`, the file's directory.
+///
+/// # Errors
+///
+/// Returns an error message if the file is not valid TOML, names an unknown
+/// setting or operator, or gives a setting a value its flag rejects.
+fn parse_settings(source: &str, dir: &Path) -> Result<Vec<Setting>, String> {
+    let table: toml::Table = source
+        .parse()
+        .map_err(|e: toml::de::Error| e.to_string().trim_end().to_string())?;
+
+    let mut settings = Vec::new();
+    for (key, value) in &table {
+        let flag = format!("--{}", key);
+        if key == "operators" {
+            merge_settings(&mut settings, operator_settings(value)?);
+        } else if CONFIG_SWITCHES.contains(&key.as_str()) {
+            match value {
+                toml::Value::Boolean(true) => settings.push(Setting::new(key, vec![flag])),
+                toml::Value::Boolean(false) => settings.push(Setting::new(key, Vec::new())),
+                _ => return Err(format!("'{}' must be true or false", key)),
+            }
+        } else if CONFIG_VALUES.contains(&key.as_str()) {
+            let value = match value {
+                toml::Value::String(value) if key == "prelude" => {
+                    dir.join(value).display().to_string()
+                }
+                toml::Value::String(value) => value.clone(),
+                toml::Value::Integer(value) => value.to_string(),
+                _ => return Err(format!("'{}' must be a string or an integer", key)),
+            };
+            merge_settings(&mut settings, vec![Setting::new(key, vec![flag, value])]);
+        } else {
+            return Err(format!("Unknown setting '{}'", key));
+        }
+    }
+
+    // Check the values as their flags would, so errors name this file
+    check_settings(&settings)?;
+    Ok(settings)
+}
+
+/// Converts `RPN2TEX_*` environment variables to the settings they hold, in
+/// name order. Each is named like a configuration setting, in upper case
+/// with `_` for `-`: `RPN2TEX_MUL_STYLE=cdot`. Switches are turned on by `1`
+/// or `true` and off by `0`, `false` or an empty value, overriding the
+/// configuration files either way.
+///
+/// Variables naming no setting are ignored: the environment is shared with
+/// wrappers, CI and other versions of the tool, which may set their own.
+///
+/// # Errors
+///
+/// Returns an error message naming the variable if it holds a value its
+/// flag rejects.
+pub(crate) fn env_settings(
+    vars: impl IntoIterator<Item = (String, String)>,
+) -> Result<Vec<Setting>, String> {
+    let mut vars: Vec<(String, String)> = vars
+        .into_iter()
+        .filter(|(name, _)| name.starts_with(ENV_PREFIX))
+        .collect();
+    vars.sort();
+
+    let mut settings = Vec::new();
+    for (name, value) in vars {
+        let key = name[ENV_PREFIX.len()..].to_lowercase().replace('_', "-");
+        let flag = format!("--{}", key);
+        let args = if CONFIG_SWITCHES.contains(&key.as_str()) {
+            match value.as_str() {
+                "1" | "true" => vec![flag],
+                "" | "0" | "false" => Vec::new(),
+                _ => {
+                    return Err(format!(
+                        "Error in {}: expected 1, true, 0 or false, got '{}'",
+                        name, value
+                    ))
+                }
+            }
+        } else if CONFIG_VALUES.contains(&key.as_str()) {
+            vec![flag, value]
+        } else {
+            continue;
+        };
+        let setting = Setting::new(&key, args);
+        check_settings(std::slice::from_ref(&setting))
+            .map_err(|e| format!("Error in {}: {}", name, e))?;
+        settings.push(setting);
+    }
+    Ok(settings)
+}
+
+/// Checks settings as `parse_args` would check their flags.
+///
+/// # Errors
+///
+/// Returns `parse_args`'s message without its `Error: ` prefix, for the
+/// caller to name where the settings came from.
+fn check_settings(settings: &[Setting]) -> Result<(), String> {
+    let mut args = vec!["-".to_string()];
+    args.extend(
+        settings
+            .iter()
+            .flat_map(|setting| setting.args.iter().cloned()),
+    );
+    parse_args(&args)
+        .map(drop)
+        .map_err(|e| e.trim_start_matches("Error: ").to_string())
+}
+
+/// Converts the `[operators]` table of a configuration file, which maps
+/// `*` and `/` to their LaTeX symbols, to the settings that select them.
+///
+/// # Errors
+///
+/// Returns an error message if an operator or symbol is unknown.
+fn operator_settings(value: &toml::Value) -> Result<Vec<Setting>, String> {
+    let table = value
+        .as_table()
+        .ok_or_else(|| "'operators' must be a table".to_string())?;
+
+    let mut settings = Vec::new();
+    for (operator, symbol) in table {
+        let symbol = symbol
+            .as_str()
+            .ok_or_else(|| format!("The symbol for '{}' must be a string", operator))?;
+        match (operator.as_str(), symbol) {
+            ("*", "times" | "cdot") => settings.push(Setting::new(
+                "mul-style",
+                vec!["--mul-style".to_string(), symbol.into()],
+            )),
+            ("/", "div") => {}
+            ("/", "frac" | "dfrac" | "tfrac") => settings.push(Setting::new(
+                "frac-style",
+                vec!["--frac-style".to_string(), symbol.into()],
+            )),
+            ("*" | "/", _) => {
+                return Err(format!(
+                    "Unknown symbol '{}' for operator '{}'",
+                    symbol, operator
+                ))
+            }
+            _ => return Err(format!("Unknown operator '{}'", operator)),
+        }
+    }
+    Ok(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::args::args;
+    use rpn2tex::latex::{FracStyle, MulStyle};
+    use std::process;
+
+    fn flags(settings: &[Setting]) -> Vec<String> {
+        settings
+            .iter()
+            .flat_map(|setting| setting.args.iter().cloned())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_settings() {
+        let source = "format = \"latex\"\n\
+                      max-depth = 10\n\
+                      frac = true\n\
+                      katex = false\n\
+                      prelude = \"defs.rpn\"\n\
+                      [operators]\n\
+                      \"*\" = \"cdot\"\n\
+                      \"/\" = \"dfrac\"\n";
+        let settings = parse_settings(source, Path::new("/project")).unwrap();
+        assert_eq!(
+            flags(&settings),
+            [
+                "--format",
+                "latex",
+                "--frac",
+                "--max-depth",
+                "10",
+                "--mul-style",
+                "cdot",
+                "--frac-style",
+                "dfrac",
+                "--prelude",
+                "/project/defs.rpn",
+            ]
+        );
+
+        // Flags given on the command line override them
+        let options = parse_args_with_settings(&args(&["-", "--mul-style", "times"]), &settings)
+            .expect("should parse");
+        assert_eq!(options.mul_style, MulStyle::Times);
+        assert_eq!(options.frac_style, FracStyle::Dfrac);
+        assert!(options.frac);
+    }
+
+    #[test]
+    fn test_settings_apply_to_each_command() {
+        let source = "standalone = true\n\
+                      frac = true\n\
+                      spacing = \"tight\"\n\
+                      mul-style = \"cdot\"\n\
+                      color = \"never\"\n";
+        let settings = parse_settings(source, Path::new("")).unwrap();
+        let parse = |values: &[&str]| parse_args_with_settings(&args(values), &settings);
+
+        for input in [&["a.rpn"][..], &["convert", "a.rpn"]] {
+            let options = parse(input).expect("should parse");
+            assert!(options.standalone);
+            assert!(options.frac);
+        }
+        // Settings a subcommand rejects are skipped rather than reported
+        // as flags the user never gave
+        for command in ["fmt", "lint", "worksheet"] {
+            let options =
+                parse(&[command, "a.rpn"]).unwrap_or_else(|e| panic!("{}: {}", command, e));
+            assert_eq!(options.command, Command::parse(command).unwrap());
+            assert!(!options.standalone);
+            assert_eq!(options.mul_style, MulStyle::Cdot);
+        }
+        if cfg!(feature = "readline") {
+            let options = parse(&["repl"]).expect("should parse");
+            assert!(options.standalone);
+        }
+
+        // A setting that conflicts with a flag given gives way to it
+        let options = parse(&["a.rpn", "--format", "json"]).expect("should parse");
+        assert_eq!(options.format, crate::cli::options::Format::Json);
+        assert!(!options.standalone);
+        // The flags given are still checked
+        let err = parse(&["fmt", "a.rpn", "--standalone"]).unwrap_err();
+        assert!(err.contains("fmt rewrites its inputs in place"));
+    }
+
+    #[test]
+    fn test_false_overrides_earlier_layers() {
+        let user = parse_settings("frac = true\nkatex = true\n", Path::new("")).unwrap();
+        let project = parse_settings("frac = false\n", Path::new("")).unwrap();
+        let mut settings = user;
+        merge_settings(&mut settings, project);
+        let options = parse_args_with_settings(&args(&["-"]), &settings).unwrap();
+        assert!(!options.frac);
+        assert!(options.katex);
+
+        // The environment turns off what either file turned on
+        for value in ["0", "false", ""] {
+            let mut layered = settings.clone();
+            let env = env_settings([("RPN2TEX_KATEX".to_string(), value.to_string())]).unwrap();
+            merge_settings(&mut layered, env);
+            let options = parse_args_with_settings(&args(&["-"]), &layered).unwrap();
+            assert!(!options.katex, "RPN2TEX_KATEX={:?}", value);
+        }
+
+        // A later true turns an earlier false back on
+        merge_settings(
+            &mut settings,
+            env_settings([("RPN2TEX_FRAC".to_string(), "1".to_string())]).unwrap(),
+        );
+        let options = parse_args_with_settings(&args(&["-"]), &settings).unwrap();
+        assert!(options.frac);
+    }
+
+    #[test]
+    fn test_settings_errors() {
+        let error = |source: &str| parse_settings(source, Path::new("")).unwrap_err();
+        assert_eq!(error("output = \"a.tex\""), "Unknown setting 'output'");
+        assert_eq!(error("frac = \"yes\""), "'frac' must be true or false");
+        assert_eq!(
+            error("spacing = true"),
+            "'spacing' must be a string or an integer"
+        );
+        assert_eq!(error("spacing = \"wide\""), "Unknown --spacing 'wide'");
+        assert_eq!(
+            error("[operators]\n\"^\" = \"wedge\""),
+            "Unknown operator '^'"
+        );
+        assert_eq!(
+            error("[operators]\n\"*\" = \"ast\""),
+            "Unknown symbol 'ast' for operator '*'"
+        );
+        assert!(error("format = latex").starts_with("TOML parse error at line 1, column 10"));
+    }
+
+    #[test]
+    fn test_env_settings() {
+        let vars = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect::<Vec<_>>()
+        };
+        let settings = env_settings(vars(&[
+            ("RPN2TEX_MUL_STYLE", "cdot"),
+            ("HOME", "/root"),
+            ("RPN2TEX_COLOR", "never"),
+            ("RPN2TEX_FRAC", "1"),
+            ("RPN2TEX_KATEX", "false"),
+        ]))
+        .unwrap();
+        assert_eq!(
+            flags(&settings),
+            ["--color", "never", "--frac", "--mul-style", "cdot"]
+        );
+        assert_eq!(settings[2], Setting::new("katex", Vec::new()));
+
+        // Unknown names are left to whoever set them
+        let settings = env_settings(vars(&[("RPN2TEX_OUTPUT", "a.tex"), ("RPN2TEX_FOO", "1")]));
+        assert_eq!(settings.unwrap(), Vec::new());
+        let err = env_settings(vars(&[("RPN2TEX_FRAC", "yes")])).unwrap_err();
+        assert_eq!(
+            err,
+            "Error in RPN2TEX_FRAC: expected 1, true, 0 or false, got 'yes'"
+        );
+        let err = env_settings(vars(&[("RPN2TEX_COLOR", "sometimes")])).unwrap_err();
+        assert_eq!(err, "Error in RPN2TEX_COLOR: Unknown --color 'sometimes'");
+    }
+
+    #[test]
+    fn test_find_project_config() {
+        let root = env::temp_dir().join(format!("rpn2tex-config-{}", process::id()));
+        let nested = root.join("docs").join("math");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(root.join(CONFIG_FILE), "spacing = \"tight\"\n").unwrap();
+        assert_eq!(find_project_config(&nested), Some(root.join(CONFIG_FILE)));
+        assert_eq!(
+            flags(&load_config(&root.join(CONFIG_FILE)).unwrap()),
+            ["--spacing", "tight"]
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,2195 @@
+//! Converting one input to the artifact selected by the options, and the
+//! formatted failures it reports.
+
+use crate::cli::options::{Command, Emit, Format, Leftovers, LetStyle, Options};
+use log::debug;
+use rpn2tex::ast::Expr;
+use rpn2tex::batch::{split_expressions, Segment};
+use rpn2tex::cloze::{Cloze, ClozeGenerator};
+use rpn2tex::diagnostic::{parse_recovering, Diagnostic};
+use rpn2tex::dot::DotGenerator;
+use rpn2tex::error::{ErrorFormatter, Rpn2TexError};
+use rpn2tex::eval::EvalError;
+use rpn2tex::filter::filter_markdown;
+use rpn2tex::latex::MathEnvironment;
+use rpn2tex::lexer::LexerError;
+use rpn2tex::macros::has_macros;
+use rpn2tex::number_format::{NumberFormat, Precision};
+use rpn2tex::parser::{Parser, ParserError};
+use rpn2tex::pipeline::InputNotation;
+use rpn2tex::program::{has_bindings, Program};
+use rpn2tex::tree::TreeGenerator;
+use serde::Serialize;
+use std::fmt;
+use std::ops::Deref;
+use std::time::Instant;
+
+/// A formatted error message for input that failed to convert, with the
+/// kind of failure, which `--stats` counts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Failure {
+    /// `lex`, `parse`, `binding` for a name with no value or a malformed
+    /// `let`, `eval`, or `convert` for any other failure
+    pub(crate) kind: &'static str,
+    /// The message shown to the user
+    pub(crate) message: String,
+}
+
+impl Failure {
+    /// Creates a failure of `kind` with its formatted `message`.
+    pub(crate) fn new(kind: &'static str, message: String) -> Self {
+        Self { kind, message }
+    }
+
+    /// Joins the messages of `failures`, one per line, under the kind of the
+    /// first.
+    pub(crate) fn join(failures: Vec<Self>) -> Self {
+        let kind = failures.first().map_or("convert", |failure| failure.kind);
+        let messages: Vec<String> = failures
+            .into_iter()
+            .map(|failure| failure.message)
+            .collect();
+        Self::new(kind, messages.join("\n"))
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Deref for Failure {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.message
+    }
+}
+
+impl From<String> for Failure {
+    fn from(message: String) -> Self {
+        Self::new("convert", message)
+    }
+}
+
+impl From<Failure> for String {
+    fn from(failure: Failure) -> Self {
+        failure.message
+    }
+}
+
+/// Converts RPN input to the artifact selected by `options`.
+///
+/// # Errors
+///
+/// Returns a formatted error if lexing, parsing or evaluation fails.
+pub(crate) fn convert(source: &str, options: &Options) -> Result<String, Failure> {
+    if converts_expression(source, options) {
+        return emit_expression(source, &parse_rpn(source, options)?, options);
+    }
+    match options.emit {
+        Emit::Output if options.evaluate => evaluate_rpn(source, options),
+        Emit::Output if options.command == Command::Worksheet => convert_worksheet(source, options),
+        Emit::Output if options.filter.is_some() => filter_document(source, options),
+        Emit::Output if options.format == Format::Json => convert_to_json(source, options),
+        Emit::Output if options.format == Format::Csv => {
+            let (output, failures) = convert_csv(source, options)?;
+            if failures.is_empty() {
+                Ok(output)
+            } else {
+                Err(Failure::join(failures))
+            }
+        }
+        Emit::Output if options.multi => convert_each(source, options),
+        Emit::Output => convert_segment(source, &Segment::whole(source), options),
+        Emit::Dot | Emit::Ast => unreachable!("--emit dot and ast are built by emit_expression"),
+        Emit::AstJson => convert_rpn_to_ast_json(source, options),
+        Emit::Tokens => dump_tokens(source, options),
+        Emit::Packages => Ok(options
+            .latex_generator()
+            .required_packages()
+            .iter()
+            .map(|package| format!("{}\n", package))
+            .collect()),
+    }
+}
+
+/// Returns whether [`convert`] builds its output from the one expression
+/// [`parse_rpn`] reads from `source`, so a caller that parsed it already
+/// can pass it to [`emit_expression`] instead.
+pub(crate) fn converts_expression(source: &str, options: &Options) -> bool {
+    match options.emit {
+        Emit::Dot | Emit::Ast => true,
+        Emit::Output => {
+            !options.evaluate
+                && options.command != Command::Worksheet
+                && options.filter.is_none()
+                && !matches!(options.format, Format::Json | Format::Csv)
+                && !options.multi
+                && (options.leftovers == Leftovers::Error || is_program(source, options))
+        }
+        Emit::AstJson | Emit::Tokens | Emit::Packages => false,
+    }
+}
+
+/// Builds the artifact selected by `options` from the expression parsed
+/// from `source`, for the cases [`converts_expression`] accepts.
+///
+/// # Errors
+///
+/// Returns the errors of [`render`].
+pub(crate) fn emit_expression(
+    source: &str,
+    ast: &Expr,
+    options: &Options,
+) -> Result<String, Failure> {
+    match options.emit {
+        Emit::Dot => Ok(DotGenerator::new().generate(ast)),
+        Emit::Ast => Ok(TreeGenerator::new().generate(ast)),
+        _ => render(source, ast, options),
+    }
+}
+
+/// Converts `source` like [`convert`], except that the rows of `--format
+/// csv` input convert one by one: a failing row is kept with empty output
+/// cells, and its failure returned beside it.
+///
+/// # Errors
+///
+/// Returns a formatted error if the whole input fails to convert.
+pub(crate) fn convert_rows(
+    source: &str,
+    options: &Options,
+) -> Result<(String, Vec<Failure>), Failure> {
+    if options.format == Format::Csv {
+        convert_csv(source, options)
+    } else {
+        convert(source, options).map(|output| (output, Vec::new()))
+    }
+}
+
+/// Renders a parsed expression of `source` in the selected `--format`.
+///
+/// # Errors
+///
+/// Returns a formatted error string if `--show-result` cannot evaluate the
+/// expression.
+fn render(source: &str, ast: &Expr, options: &Options) -> Result<String, Failure> {
+    let start = Instant::now();
+    let output = if options.show_result {
+        let value = options
+            .evaluator()
+            .evaluate(ast)
+            .map_err(|e| format_eval_error(&options.error_formatter(source), &e))?;
+        options.latex_generator().generate_with_result(ast, value)
+    } else if let Some(token) = options.blank {
+        blank_out(ast, token, options)?.latex
+    } else if options.macros {
+        options.latex_generator().generate_with_macros(ast)
+    } else if options.standalone {
+        options.latex_generator().generate_document(ast)
+    } else if let Some(cache) = &options.cache {
+        cache.generate(ast, || options.code_generator())
+    } else {
+        options.code_generator().generate(ast)
+    };
+    debug!("Generated in {:.2?}", start.elapsed());
+    Ok(output)
+}
+
+/// Replaces the `token`-th token of `ast` with a blank for `--blank`.
+///
+/// # Errors
+///
+/// Returns an error message if the expression has fewer tokens.
+pub(crate) fn blank_out(ast: &Expr, token: usize, options: &Options) -> Result<Cloze, Failure> {
+    ClozeGenerator::new()
+        .with_generator(options.latex_generator())
+        .generate(ast, token)
+        .ok_or_else(|| {
+            Failure::from(format!(
+                "Error: --blank {} is past the expression's last token",
+                token
+            ))
+        })
+}
+
+/// Converts one expression of `source` in the selected `--format`. With
+/// `--leftovers comma` or `newline`, each value left on the stack is converted
+/// separately and the outputs joined.
+///
+/// # Errors
+///
+/// Returns a formatted error string if lexing, parsing or rendering fails.
+fn convert_segment(source: &str, segment: &Segment, options: &Options) -> Result<String, Failure> {
+    let Some(separator) = options.leftovers.separator() else {
+        return parse_segment(source, segment, options)
+            .and_then(|ast| render(source, &ast, options));
+    };
+
+    let formatter = options.error_formatter(source);
+    let tokens = options
+        .compiler()
+        .lexer(segment)
+        .tokenize()
+        .map_err(|e| format_lexer_error(&formatter, &e))?;
+    let exprs = Parser::new(tokens)
+        .with_limits(options.limits)
+        .parse_all()
+        .map_err(|e| format_parser_error(&formatter, &e))?;
+
+    let outputs = exprs
+        .into_iter()
+        .map(|ast| {
+            let ast = options.fold(ast);
+            render(source, &ast, options)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(outputs.join(separator))
+}
+
+/// Converts the RPN embedded in a `--filter markdown` document to math
+/// delimited for `--markdown-flavor`, copying the rest of it unchanged.
+///
+/// # Errors
+///
+/// Returns the formatted errors of every failing block or span, positioned
+/// in the whole document.
+fn filter_document(source: &str, options: &Options) -> Result<String, Failure> {
+    // The filter adds the delimiters itself
+    let math = Options {
+        environment: MathEnvironment::Bare,
+        format: Format::Latex,
+        ..options.clone()
+    };
+    filter_markdown(source, options.markdown_flavor, |segment| {
+        convert_segment(source, segment, &math)
+    })
+    .map_err(Failure::join)
+}
+
+/// Generates a `worksheet` document from the expressions of `source`,
+/// with its answer key appended for `--answer-key`.
+///
+/// # Errors
+///
+/// Returns the formatted errors of every failing expression.
+fn convert_worksheet(source: &str, options: &Options) -> Result<String, Failure> {
+    generate_worksheet(source, &parse_problems(source, options)?, options)
+}
+
+/// Generates a `worksheet` document from the problems parsed from
+/// `source`, with its answer key appended for `--answer-key`.
+///
+/// # Errors
+///
+/// Returns the formatted errors of every problem that fails to evaluate.
+pub(crate) fn generate_worksheet(
+    source: &str,
+    problems: &[Expr],
+    options: &Options,
+) -> Result<String, Failure> {
+    let generator = options.worksheet_generator();
+    if options.answer_key {
+        let answers = evaluate_problems(source, problems, options)?;
+        Ok(generator.generate_with_answers(problems, &answers))
+    } else {
+        Ok(generator.generate(problems))
+    }
+}
+
+/// Generates the answer key document of a `worksheet` for
+/// `--answer-key-output` from the problems parsed from `source`.
+///
+/// # Errors
+///
+/// Returns the formatted errors of every problem that fails to evaluate.
+pub(crate) fn answer_key(
+    source: &str,
+    problems: &[Expr],
+    options: &Options,
+) -> Result<String, Failure> {
+    let answers = evaluate_problems(source, problems, options)?;
+    Ok(options
+        .worksheet_generator()
+        .generate_answer_key(problems, &answers))
+}
+
+/// Evaluates each `worksheet` problem for its answer key.
+///
+/// # Errors
+///
+/// Returns the formatted errors of every problem that fails to evaluate.
+fn evaluate_problems(
+    source: &str,
+    problems: &[Expr],
+    options: &Options,
+) -> Result<Vec<String>, Failure> {
+    let (answers, errors): (Vec<_>, Vec<_>) = problems
+        .iter()
+        .map(|problem| evaluate_ast(source, problem, options))
+        .partition(Result::is_ok);
+    if errors.is_empty() {
+        Ok(answers.into_iter().flatten().collect())
+    } else {
+        Err(Failure::join(
+            errors.into_iter().filter_map(Result::err).collect(),
+        ))
+    }
+}
+
+/// Parses each line or `;`-separated expression of `source` as a
+/// `worksheet` problem.
+///
+/// # Errors
+///
+/// Returns the formatted errors of every failing expression.
+pub(crate) fn parse_problems(source: &str, options: &Options) -> Result<Vec<Expr>, Failure> {
+    let mut problems = Vec::new();
+    let mut errors = Vec::new();
+    for segment in split_expressions(source) {
+        match parse_segment(source, &segment, options) {
+            Ok(problem) if options.within_difficulty(&problem) => problems.push(problem),
+            Ok(_) => {}
+            Err(e) => errors.push(e),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(problems)
+    } else {
+        Err(Failure::join(errors))
+    }
+}
+
+/// Converts every expression of a `--multi` input, one output line each.
+///
+/// # Errors
+///
+/// Returns the formatted errors of every failing expression, so all of them
+/// can be fixed in one pass.
+fn convert_each(source: &str, options: &Options) -> Result<String, Failure> {
+    let mut outputs = Vec::new();
+    let mut errors = Vec::new();
+    for segment in split_expressions(source) {
+        let converted = match parse_segment(source, &segment, options) {
+            Ok(ast) if !options.within_difficulty(&ast) => continue,
+            Ok(ast) if options.leftovers == Leftovers::Error => render(source, &ast, options),
+            // Leftover values, and errors, are found by converting it whole
+            _ => convert_segment(source, &segment, options),
+        };
+        match converted {
+            Ok(output) => outputs.push(output),
+            Err(e) => errors.push(e),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(outputs.join("\n"))
+    } else {
+        Err(Failure::join(errors))
+    }
+}
+
+/// Header of the column `--format csv` reads expressions from by default.
+const DEFAULT_CSV_COLUMN: &str = "rpn";
+
+/// Converts the expressions in a column of a `--format csv` input, writing
+/// its rows with a `latex` column added and, with `--csv-value`, a `value`
+/// column. Blank cells get blank outputs.
+///
+/// Each row converts on its own: a row that fails keeps its place with empty
+/// output cells, and its formatted errors, naming its line, are returned
+/// beside it.
+///
+/// # Errors
+///
+/// Returns an error message if the input is not well-formed CSV or lacks the
+/// `--csv-column`.
+fn convert_csv(source: &str, options: &Options) -> Result<(String, Vec<Failure>), Failure> {
+    let csv_error = |e: csv::Error| format!("Error in CSV input: {}", e);
+    let mut reader = csv::Reader::from_reader(source.as_bytes());
+    let mut headers = reader.headers().map_err(csv_error)?.clone();
+    let column = match &options.csv_column {
+        Some(name) => headers
+            .iter()
+            .position(|header| header == name)
+            .ok_or_else(|| format!("Error: The CSV input has no column '{}'", name))?,
+        None => headers
+            .iter()
+            .position(|header| header == DEFAULT_CSV_COLUMN)
+            .unwrap_or(0),
+    };
+    headers.push_field("latex");
+    if options.csv_value {
+        headers.push_field("value");
+    }
+
+    // Each cell is converted as LaTeX, like a file of its own
+    let latex = Options {
+        format: Format::Latex,
+        ..options.clone()
+    };
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(&headers).map_err(csv_error)?;
+    let mut errors = Vec::new();
+    for record in reader.records() {
+        let mut record = record.map_err(csv_error)?;
+        let line = record.position().map_or(0, csv::Position::line);
+        let expression = record.get(column).unwrap_or_default().to_string();
+        let blank = expression.trim().is_empty();
+
+        let outputs = if blank {
+            Ok(vec![String::new(); usize::from(options.csv_value) + 1])
+        } else {
+            convert(&expression, &latex).and_then(|output| {
+                let mut outputs = vec![output];
+                if options.csv_value {
+                    outputs.push(evaluate_rpn(&expression, options)?);
+                }
+                Ok(outputs)
+            })
+        };
+        let outputs = outputs.unwrap_or_else(|e| {
+            errors.push(Failure::new(
+                e.kind,
+                format!("Error in CSV line {}:\n{}", line, e),
+            ));
+            vec![String::new(); usize::from(options.csv_value) + 1]
+        });
+        outputs.iter().for_each(|output| record.push_field(output));
+        writer.write_record(&record).map_err(csv_error)?;
+    }
+
+    let bytes = writer.into_inner().map_err(|e| e.to_string())?;
+    let output = String::from_utf8(bytes).map_err(|e| e.to_string())?;
+    Ok((output, errors))
+}
+
+/// One expression's entry in `--format json` output.
+#[derive(Debug, Serialize)]
+struct JsonResult<'a> {
+    /// The expression's source text
+    input: &'a str,
+    /// The LaTeX output, or `None` if the expression has errors
+    latex: Option<String>,
+    /// The parsed expression, or `None` if it has errors
+    ast: Option<Expr>,
+    /// Every problem found in the expression
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Converts `source` to pretty-printed JSON holding the input, LaTeX, AST
+/// and diagnostics of its expression or, with `--multi`, an array of them,
+/// one per expression.
+///
+/// Syntax errors are reported in the diagnostics rather than as an error, so
+/// scripts can read every outcome from stdout.
+///
+/// # Errors
+///
+/// Returns an error message only if serialization fails.
+fn convert_to_json(source: &str, options: &Options) -> Result<String, Failure> {
+    let mut json = if options.multi {
+        let results: Vec<JsonResult> = split_expressions(source)
+            .iter()
+            .map(|segment| json_result(segment, options))
+            .collect();
+        serde_json::to_string_pretty(&results)
+    } else {
+        serde_json::to_string_pretty(&json_result(&Segment::whole(source), options))
+    }
+    .map_err(|e| e.to_string())?;
+    json.push('\n');
+    Ok(json)
+}
+
+/// Converts one expression for `--format json`. RPN input reports every
+/// syntax error, as with `--all-errors`; prefix and infix input report the
+/// first.
+fn json_result<'a>(segment: &Segment<'a>, options: &Options) -> JsonResult<'a> {
+    let compiler = options.compiler();
+    let parsed = match options.from {
+        InputNotation::Rpn => parse_recovering(compiler.lexer(segment)),
+        InputNotation::Prefix | InputNotation::Infix => compiler
+            .parse_segment(segment)
+            .map_err(|error| vec![error.into()]),
+    };
+
+    let input = segment.text.trim();
+    match parsed {
+        Ok(ast) => {
+            let ast = options.fold(ast);
+            JsonResult {
+                input,
+                latex: Some(options.latex_generator().generate(&ast)),
+                ast: Some(ast),
+                diagnostics: Vec::new(),
+            }
+        }
+        Err(diagnostics) => JsonResult {
+            input,
+            latex: None,
+            ast: None,
+            diagnostics,
+        },
+    }
+}
+
+/// Converts RPN input to a pretty-printed JSON serialization of its AST.
+///
+/// Unlike the other outputs, failures are also reported as JSON, in the form
+/// `{"stage": "lexer" | "parser", "error": {...}, "diagnostic": {...}}`,
+/// so scripts can consume both outcomes without parsing free-form text.
+/// The diagnostic has the same form for either stage.
+///
+/// # Errors
+///
+/// Returns the JSON-encoded error if lexing or parsing fails.
+fn convert_rpn_to_ast_json(source: &str, options: &Options) -> Result<String, Failure> {
+    let compiler = options.compiler();
+    let tokens = compiler
+        .lexer(&Segment::whole(source))
+        .tokenize()
+        .map_err(|e| json_error("lexer", &e))?;
+    let ast = compiler
+        .parse_tokens(tokens)
+        .map_err(|e| json_error("parser", &e))?;
+    let ast = options.fold(ast);
+
+    let mut json = serde_json::to_string_pretty(&ast).map_err(|e| e.to_string())?;
+    json.push('\n');
+    Ok(json)
+}
+
+/// Lists the tokens of `source` with their lexemes and positions, as an
+/// aligned table or, with `--format json`, as a JSON array.
+///
+/// The lexeme is the source text a token was read from and the value what
+/// later stages see, so the two differ for numbers such as `1_000`.
+///
+/// # Errors
+///
+/// Returns the formatted lexer error, JSON-encoded with `--format json`, if
+/// tokenizing fails.
+fn dump_tokens(source: &str, options: &Options) -> Result<String, Failure> {
+    let tokens = options.compiler().lexer(&Segment::whole(source)).tokenize();
+
+    if options.format == Format::Json {
+        let tokens = tokens.map_err(|e| json_error("lexer", &e))?;
+        let mut json = serde_json::to_string_pretty(&tokens).map_err(|e| e.to_string())?;
+        json.push('\n');
+        return Ok(json);
+    }
+
+    let tokens = tokens.map_err(|e| format_lexer_error(&options.error_formatter(source), &e))?;
+    let header = ["TYPE", "LEXEME", "VALUE", "POSITION", "BYTES"].map(String::from);
+    let rows: Vec<[String; 5]> = std::iter::once(header)
+        .chain(tokens.iter().map(|token| {
+            [
+                format!("{:?}", token.token_type),
+                token.span.text(source).to_string(),
+                token.value.to_string(),
+                format!("{}:{}", token.span.line, token.span.column),
+                format!("{}..{}", token.span.start, token.span.end),
+            ]
+        }))
+        .collect();
+
+    let mut widths = [0; 5];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut table = String::new();
+    for row in &rows {
+        let line: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        table.push_str(line.join("  ").trim_end());
+        table.push('\n');
+    }
+    Ok(table)
+}
+
+/// Encodes a pipeline error as a JSON object tagged with its stage,
+/// `decoder`, `lexer` or `parser`, holding both the error and its
+/// diagnostic.
+pub(crate) fn json_error<E>(stage: &str, error: &E) -> Failure
+where
+    E: Serialize + Clone + Into<Diagnostic>,
+{
+    let kind = match stage {
+        "decoder" => "decode",
+        "lexer" => "lex",
+        _ => "parse",
+    };
+    let diagnostic: Diagnostic = error.clone().into();
+    let json = serde_json::json!({ "stage": stage, "error": error, "diagnostic": diagnostic });
+    Failure::new(kind, json.to_string())
+}
+
+/// Tokenizes and parses RPN input into an AST, folding constants if
+/// `--fold-constants` is set.
+///
+/// # Errors
+///
+/// Returns a formatted error string (with source context) if lexing or
+/// parsing fails.
+pub(crate) fn parse_rpn(source: &str, options: &Options) -> Result<Expr, Failure> {
+    if is_program(source, options) {
+        return parse_program(source, options);
+    }
+    parse_segment(source, &Segment::whole(source), options)
+}
+
+/// Returns `true` if `source` is parsed as a program: it holds `let` or
+/// `macro` statements, or may use `--prelude` macros.
+pub(crate) fn is_program(source: &str, options: &Options) -> bool {
+    has_bindings(source) || has_macros(source) || options.prelude_path.is_some()
+}
+
+/// Parses input with `let` or `macro` statements into the final expression,
+/// with the macros expanded and the bound names substituted or, with
+/// `--let-style symbolic`, kept.
+///
+/// # Errors
+///
+/// Returns a formatted error string (with source context) if a statement
+/// fails to lex or parse, or uses a name that is neither bound nor, with
+/// `--allow-vars`, a variable.
+pub(crate) fn parse_program(source: &str, options: &Options) -> Result<Expr, Failure> {
+    let formatter = options.error_formatter(source);
+    let program = options
+        .prelude
+        .clone()
+        .with_definitions(source)
+        .and_then(|macros| {
+            let compiler = options.compiler().variables(true).macros(macros);
+            Program::parse_with(source, |segment| compiler.parse_segment(segment))
+        })
+        .map_err(|e| format_compile_error(&formatter, &e))?;
+
+    if !options.allow_vars {
+        if let Some((name, span)) = program.unbound_variable() {
+            let error = formatter.format_span_error(format!("Undefined name '{}'", name), span, 1);
+            return Err(Failure::new(
+                "binding",
+                formatter.with_help(
+                    error,
+                    Some("bind it with 'let' first, or pass --allow-vars"),
+                ),
+            ));
+        }
+    }
+
+    let ast = match options.let_style {
+        LetStyle::Inline => program.inline(),
+        LetStyle::Symbolic => program.body().clone(),
+    };
+    Ok(options.fold(ast))
+}
+
+/// Tokenizes and parses one expression of `source`, folding constants if
+/// `--fold-constants` is set.
+///
+/// # Errors
+///
+/// Returns a formatted error string (with context from the whole source) if
+/// lexing or parsing fails. With `--all-errors` it holds every error found.
+pub(crate) fn parse_segment(
+    source: &str,
+    segment: &Segment,
+    options: &Options,
+) -> Result<Expr, Failure> {
+    let formatter = options.error_formatter(source);
+
+    if options.all_errors {
+        let ast = parse_recovering(options.compiler().lexer(segment)).map_err(|diagnostics| {
+            // Lexer error codes start with E0
+            let kind = match diagnostics.first().and_then(Diagnostic::code) {
+                Some(code) if code.as_str().starts_with("E0") => "lex",
+                _ => "parse",
+            };
+            let messages: Vec<String> = diagnostics
+                .iter()
+                .map(|d| formatter.format_diagnostic(d, 1))
+                .collect();
+            Failure::new(kind, messages.join("\n"))
+        })?;
+        return Ok(options.fold(ast));
+    }
+
+    // The stages are run one by one, to log each one's time
+    let compiler = options.compiler();
+    let start = Instant::now();
+    let tokens = compiler
+        .lexer(segment)
+        .tokenize()
+        .map_err(|e| format_lexer_error(&formatter, &e))?;
+    debug!("Lexed {} tokens in {:.2?}", tokens.len(), start.elapsed());
+
+    let start = Instant::now();
+    let ast = compiler
+        .parse_tokens(tokens)
+        .map_err(|e| format_parser_error(&formatter, &e))?;
+    debug!("Parsed in {:.2?}", start.elapsed());
+
+    Ok(options.fold(ast))
+}
+
+/// Parses RPN input and computes its value with the `--let` bindings, as a
+/// decimal or, with `--exact`, as an exact fraction such as `1/3`.
+///
+/// # Errors
+///
+/// Returns a formatted error string (with source context) if lexing,
+/// parsing or evaluation fails.
+fn evaluate_rpn(source: &str, options: &Options) -> Result<String, Failure> {
+    evaluate_ast(source, &parse_rpn(source, options)?, options)
+}
+
+/// Computes the value of an expression parsed from `source`, formatted as
+/// for `--evaluate`.
+///
+/// # Errors
+///
+/// Returns a formatted error string (with source context) if evaluation
+/// fails.
+pub(crate) fn evaluate_ast(source: &str, ast: &Expr, options: &Options) -> Result<String, Failure> {
+    let evaluator = options.evaluator();
+    #[cfg(feature = "exact")]
+    if options.exact {
+        return evaluator
+            .evaluate_exact(ast)
+            .map(|value| value.to_string())
+            .map_err(|e| format_eval_error(&options.error_formatter(source), &e));
+    }
+    let value = evaluator.evaluate(ast).map(|value| {
+        // Negative zero would otherwise be written as "-0"
+        let value = if value == 0.0 { 0.0 } else { value };
+        match options.value_places {
+            Some(places) => NumberFormat::new()
+                .with_precision(Precision::DecimalPlaces(places))
+                .format(&value.to_string()),
+            None => value.to_string(),
+        }
+    });
+    value.map_err(|e| format_eval_error(&options.error_formatter(source), &e))
+}
+
+/// Formats an evaluation error with source context.
+fn format_eval_error(formatter: &ErrorFormatter, error: &EvalError) -> Failure {
+    Failure::new(
+        "eval",
+        formatter.format_diagnostic(&error.clone().into(), 1),
+    )
+}
+
+/// Formats a lexer error with source context.
+///
+/// # Arguments
+///
+/// * `formatter` - The error formatter with source context
+/// * `error` - The lexer error to format
+///
+/// # Returns
+///
+/// A formatted error message with source context, ready to display to the user.
+fn format_lexer_error(formatter: &ErrorFormatter, error: &LexerError) -> Failure {
+    Failure::new("lex", formatter.format_diagnostic(&error.clone().into(), 1))
+}
+
+/// Formats a parser error with source context.
+///
+/// # Arguments
+///
+/// * `formatter` - The error formatter with source context
+/// * `error` - The parser error to format
+///
+/// # Returns
+///
+/// A formatted error message with source context, ready to display to the user.
+fn format_parser_error(formatter: &ErrorFormatter, error: &ParserError) -> Failure {
+    Failure::new(
+        "parse",
+        formatter.format_diagnostic(&error.clone().into(), 1),
+    )
+}
+
+/// Formats an error of any stage with source context.
+pub(crate) fn format_compile_error(formatter: &ErrorFormatter, error: &Rpn2TexError) -> Failure {
+    let kind = match error {
+        Rpn2TexError::Io { .. } => "io",
+        Rpn2TexError::Encoding(_) => "decode",
+        Rpn2TexError::Lexer(_) => "lex",
+        Rpn2TexError::Parser(_) => "parse",
+        Rpn2TexError::Binding(_) => "binding",
+        Rpn2TexError::Eval(_) => "eval",
+        Rpn2TexError::Generation(_) => "generate",
+    };
+    Failure::new(kind, formatter.format_diagnostic(&error.clone().into(), 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::args::{
+        args, parse_args, parse_decimal, parse_frac_style, parse_mul_style, parse_paren_sizing,
+        parse_spacing, parse_thousands,
+    };
+    use crate::cli::options::ColorChoice;
+    use rpn2tex::latex::{FracStyle, MulStyle, ParenSizing, SpacingStyle};
+    use rpn2tex::macros::MacroTable;
+    use rpn2tex::number_format::DecimalSeparator;
+    use std::path::PathBuf;
+
+    /// Converts RPN input to LaTeX with the default CLI options.
+    fn convert_rpn_to_latex(source: &str) -> Result<String, String> {
+        convert(source, &Options::new("-")).map_err(String::from)
+    }
+
+    #[test]
+    fn test_convert_simple_addition() {
+        let result = convert_rpn_to_latex("5 3 +").expect("should succeed");
+        assert_eq!(result, "$5 + 3$");
+    }
+
+    #[test]
+    fn test_convert_simple_multiplication() {
+        let result = convert_rpn_to_latex("4 7 *").expect("should succeed");
+        assert_eq!(result, r"$4 \times 7$");
+    }
+
+    #[test]
+    fn test_convert_with_precedence() {
+        let result = convert_rpn_to_latex("5 3 + 2 *").expect("should succeed");
+        assert_eq!(result, r"$( 5 + 3 ) \times 2$");
+    }
+
+    #[test]
+    fn test_convert_lexer_error() {
+        let result = convert_rpn_to_latex("2 3 ^");
+        assert!(result.is_err());
+        let error = result.unwrap_err();
+        assert!(error.contains("Line 1, column 5"));
+        assert!(error.contains("Unexpected character"));
+    }
+
+    #[test]
+    fn test_convert_parser_error_too_few_operands() {
+        let result = convert_rpn_to_latex("5 +");
+        assert!(result.is_err());
+        let error = result.unwrap_err();
+        assert!(error.contains("Too few operands"));
+    }
+
+    #[test]
+    fn test_convert_parser_error_empty() {
+        let result = convert_rpn_to_latex("");
+        assert!(result.is_err());
+        let error = result.unwrap_err();
+        assert!(error.contains("Empty expression"));
+    }
+
+    #[test]
+    fn test_convert_parser_error_missing_operator() {
+        let result = convert_rpn_to_latex("5 3");
+        assert!(result.is_err());
+        let error = result.unwrap_err();
+        assert!(error.contains("Too many values"));
+    }
+
+    #[test]
+    fn test_convert_floating_point() {
+        let result = convert_rpn_to_latex("3.14 2 *").expect("should succeed");
+        assert_eq!(result, r"$3.14 \times 2$");
+    }
+
+    #[test]
+    fn test_convert_negative_number() {
+        let result = convert_rpn_to_latex("-5 3 +").expect("should succeed");
+        assert_eq!(result, "$-5 + 3$");
+    }
+
+    #[test]
+    fn test_convert_complex_expression() {
+        let result = convert_rpn_to_latex("1 2 + 3 4 + *").expect("should succeed");
+        assert_eq!(result, r"$( 1 + 2 ) \times ( 3 + 4 )$");
+    }
+
+    #[test]
+    fn test_format_lexer_error() {
+        use rpn2tex::span::Span;
+
+        let source = "2 3 ^";
+        let formatter = ErrorFormatter::new(source);
+        let error = LexerError::new("Unexpected character '^'", Span::new(4, 5, 1, 5));
+
+        let formatted = format_lexer_error(&formatter, &error);
+        assert!(formatted.contains("Line 1, column 5"));
+        assert!(formatted.contains("Unexpected character"));
+        assert!(formatted.contains("2 3 ^"));
+        assert!(formatted.contains("^")); // Should have caret pointer
+    }
+
+    #[test]
+    fn test_lexer_error_suggestions() {
+        let err = convert_rpn_to_latex("2 3 ^").unwrap_err();
+        assert!(err.ends_with(
+            "help: exponentiation is not supported; multiply the base by itself, as in `2 2 *`\n"
+        ));
+
+        let options = parse_args(&args(&["-", "--all-errors"])).expect("should parse");
+        let err = convert("2 y + 3 :", &options).unwrap_err();
+        assert!(err.contains("help: variable names require --allow-vars\n"));
+        assert!(err.contains("help: division is written `/`\n"));
+    }
+
+    #[test]
+    fn test_format_parser_error() {
+        use rpn2tex::span::Span;
+        use rpn2tex::tokens::{Token, TokenType};
+
+        let source = "5 +";
+        let formatter = ErrorFormatter::new(source);
+        let token = Token::new(TokenType::Plus, "+", Span::new(2, 3, 1, 3));
+        let error = ParserError::new("Too few operands", token);
+
+        let formatted = format_parser_error(&formatter, &error);
+        assert!(formatted.contains("Line 1, column 3"));
+        assert!(formatted.contains("Too few operands"));
+        assert!(!formatted.contains('['));
+    }
+
+    #[test]
+    fn test_error_codes() {
+        let err = convert_rpn_to_latex("5 @").unwrap_err();
+        assert!(err.contains("column 3: Unexpected character '@' [E001]\n"));
+        let err = convert_rpn_to_latex("5 3").unwrap_err();
+        assert!(err.contains("[E102]"));
+
+        let options = parse_args(&args(&["-", "--all-errors"])).expect("should parse");
+        let err = convert("2 ^ 3 + +", &options).unwrap_err();
+        assert!(err.contains("[E001]") && err.contains("[E101]"));
+    }
+
+    #[test]
+    fn test_all_errors() {
+        let options = parse_args(&args(&["-", "--all-errors"])).expect("should parse");
+        let err = convert("5 @ +\n$ *", &options).unwrap_err();
+        assert!(err.contains("Line 1, column 3: Unexpected character '@'"));
+        assert!(err.contains("Line 2, column 1: Unexpected character '$'"));
+        assert!(err.contains("Line 1, column 5: Too few operands for operator"));
+        assert!(err.contains("Line 2, column 3: Too few operands for operator"));
+
+        assert_eq!(convert("5 3 +", &options).unwrap(), "$5 + 3$");
+
+        let err = parse_args(&args(&["-", "--all-errors", "--emit", "ast-json"])).unwrap_err();
+        assert!(err.contains("--all-errors cannot be used with --emit ast-json"));
+    }
+
+    #[test]
+    fn test_errors_underline_whole_token() {
+        let options = parse_args(&args(&["-", "--eval", "--allow-vars"])).expect("should parse");
+        let err = evaluate_rpn("rate 12 *", &options).unwrap_err();
+        assert!(err.contains("1 | rate 12 *\n    ^^^^\n"), "{}", err);
+
+        let err = convert_rpn_to_latex("5 3.14").unwrap_err();
+        assert!(err.contains("1 | 5 3.14\n          ^\n"), "{}", err);
+    }
+
+    #[test]
+    fn test_convert_unicode_operators() {
+        let result = convert_rpn_to_latex("6 2 × 3 ÷ 1 −").expect("should succeed");
+        assert_eq!(result, r"$6 \times 2 \div 3 - 1$");
+
+        let err = convert_rpn_to_latex("6 ÷ 2 @").unwrap_err();
+        assert!(err.contains("1 | 6 ÷ 2 @\n          ^\n"), "{}", err);
+    }
+
+    #[test]
+    fn test_convert_unicode_digits() {
+        let options = parse_args(&args(&["-", "--unicode-digits"])).expect("should parse");
+        assert!(options.unicode_digits);
+        assert_eq!(convert("１２ ٣ ×", &options).unwrap(), r"$12 \times 3$");
+
+        let err = convert_rpn_to_latex("１２ 3 *").unwrap_err();
+        assert!(err.contains("help: digits other than 0-9 require --unicode-digits"));
+    }
+
+    #[test]
+    fn test_convert_digit_groups() {
+        assert_eq!(
+            convert_rpn_to_latex("1_000 2 *").unwrap(),
+            r"$1000 \times 2$"
+        );
+
+        let options = parse_args(&args(&["-", "--comma-groups"])).expect("should parse");
+        assert!(options.comma_groups);
+        assert_eq!(convert("1,000 2 *", &options).unwrap(), r"$1000 \times 2$");
+    }
+
+    #[test]
+    fn test_malformed_number_error() {
+        let err = convert_rpn_to_latex("1.2.3 2 +").unwrap_err();
+        assert!(err.starts_with("Line 1, column 1: Malformed number literal '1.2.3' [E002]\n"));
+        assert!(err.contains("1 | 1.2.3 2 +\n    ^^^^^\n"), "{}", err);
+
+        let options = parse_args(&args(&["-", "--lenient-numbers"])).expect("should parse");
+        assert_eq!(convert("1.2.3 +", &options).unwrap(), "$1.2 + .3$");
+    }
+
+    #[test]
+    fn test_convert_from_prefix() {
+        let options = parse_args(&args(&["-", "--from", "prefix"])).expect("should parse");
+        assert_eq!(options.from, InputNotation::Prefix);
+        assert_eq!(
+            convert("* + 5 3 2", &options).unwrap(),
+            r"$( 5 + 3 ) \times 2$"
+        );
+
+        let options =
+            parse_args(&args(&["-", "--from", "prefix", "--format", "rpn"])).expect("should parse");
+        assert_eq!(convert("- 10 / 6 2", &options).unwrap(), "10 6 2 / -");
+
+        let err = convert(
+            "5 3 +",
+            &parse_args(&args(&["-", "--from", "prefix"])).unwrap(),
+        )
+        .unwrap_err();
+        assert!(err.contains("Line 1, column 5: Too few operands for operator"));
+
+        let err = parse_args(&args(&["-", "--from", "postfix"])).unwrap_err();
+        assert!(err.contains("Unknown --from 'postfix'"));
+        let err = parse_args(&args(&["-", "--from", "prefix", "--all-errors"])).unwrap_err();
+        assert!(err.contains("--all-errors cannot be used with --from prefix"));
+    }
+
+    #[test]
+    fn test_convert_between_notations() {
+        let options = parse_args(&args(&["convert", "-", "--from", "infix", "--to", "rpn"]))
+            .expect("should parse");
+        assert_eq!(options.command, Command::Convert);
+        assert_eq!(options.format, Format::Rpn);
+        assert_eq!(convert("(5 + 3) * 2-1", &options).unwrap(), "5 3 + 2 * 1 -");
+
+        let options =
+            parse_args(&args(&["-", "--from", "prefix", "--to", "infix"])).expect("should parse");
+        assert_eq!(
+            convert("/ - 10 4 + 1 2", &options).unwrap(),
+            "(10 - 4) / (1 + 2)"
+        );
+
+        let options = parse_args(&args(&["convert", "-", "--from", "infix", "--multi"]))
+            .expect("should parse");
+        assert_eq!(
+            convert("1 + 2\n3 * (4 - 5)", &options).unwrap(),
+            "$1 + 2$\n$3 \\times ( 4 - 5 )$"
+        );
+
+        let err = convert(
+            "(1 + 2",
+            &parse_args(&args(&["-", "--from", "infix"])).unwrap(),
+        )
+        .unwrap_err();
+        assert!(err.contains("Line 1, column 7: Expected ')'"));
+        let err = parse_args(&args(&["-", "--to", "svg"])).unwrap_err();
+        assert!(err.contains("Unknown --to 'svg'"));
+    }
+
+    #[test]
+    fn test_convert_with_limits() {
+        let options = parse_args(&args(&["-", "--max-tokens", "3", "--max-depth", "2"]))
+            .expect("should parse");
+        assert_eq!(convert("5 3 +", &options).unwrap(), "$5 + 3$");
+
+        let err = convert("5 3 + 2 *", &options).unwrap_err();
+        assert!(err.starts_with("Line 1, column 7: Input exceeds the limit of 3 tokens"));
+
+        let options =
+            parse_args(&args(&["-", "--max-depth", "2", "--all-errors"])).expect("should parse");
+        let err = convert("1 2 + 3 + 4 +", &options).unwrap_err();
+        assert_eq!(err.matches("Line ").count(), 1);
+        assert!(
+            err.starts_with("Line 1, column 9: Expression exceeds the nesting limit of 2 levels")
+        );
+
+        let options = parse_args(&args(&["-", "--max-input-bytes", "4"])).expect("should parse");
+        let err = convert("5 3 +", &options).unwrap_err();
+        assert!(err.starts_with("Line 1, column 5: Input exceeds the limit of 4 bytes"));
+
+        let err = parse_args(&args(&["-", "--max-depth", "deep"])).unwrap_err();
+        assert!(err.contains("--max-depth expects a number, got 'deep'"));
+    }
+
+    #[test]
+    fn test_color() {
+        let options = parse_args(&args(&["-", "--color", "always"])).expect("should parse");
+        assert_eq!(options.color, ColorChoice::Always);
+        let err = convert("5 @", &options).unwrap_err();
+        assert!(err.starts_with("\x1b[1;31mLine 1, column 3:\x1b[0m"));
+
+        // Without --color, tests see the unresolved default, which is plain
+        let err = convert_rpn_to_latex("5 @").unwrap_err();
+        assert!(err.starts_with("Line 1, column 3:"));
+        assert_eq!(ColorChoice::Never.resolve(), ColorChoice::Never);
+
+        let err = parse_args(&args(&["-", "--color", "sometimes"])).unwrap_err();
+        assert!(err.contains("Unknown --color 'sometimes'"));
+    }
+
+    #[test]
+    fn test_crlf_error_context() {
+        let err = convert_rpn_to_latex("5 3 +\r\n2 @ # note\r\n").unwrap_err();
+        assert_eq!(
+            err,
+            "Line 2, column 3: Unexpected character '@' [E001]\n1 | 5 3 +\n2 | 2 @ # note\n      ^\n"
+        );
+    }
+
+    // Test all I/O contract cases through the convert function
+    #[test]
+    fn test_io_contract_case_01() {
+        assert_eq!(convert_rpn_to_latex("5 3 +").unwrap(), "$5 + 3$");
+    }
+
+    #[test]
+    fn test_io_contract_case_02() {
+        assert_eq!(convert_rpn_to_latex("5 3 -").unwrap(), "$5 - 3$");
+    }
+
+    #[test]
+    fn test_io_contract_case_03() {
+        assert_eq!(convert_rpn_to_latex("4 7 *").unwrap(), r"$4 \times 7$");
+    }
+
+    #[test]
+    fn test_io_contract_case_04() {
+        assert_eq!(convert_rpn_to_latex("10 2 /").unwrap(), r"$10 \div 2$");
+    }
+
+    #[test]
+    fn test_io_contract_case_05() {
+        assert_eq!(
+            convert_rpn_to_latex("5 3 + 2 *").unwrap(),
+            r"$( 5 + 3 ) \times 2$"
+        );
+    }
+
+    #[test]
+    fn test_io_contract_case_06() {
+        assert_eq!(
+            convert_rpn_to_latex("5 3 * 2 +").unwrap(),
+            r"$5 \times 3 + 2$"
+        );
+    }
+
+    #[test]
+    fn test_io_contract_case_07() {
+        assert_eq!(
+            convert_rpn_to_latex("10 2 / 5 *").unwrap(),
+            r"$10 \div 2 \times 5$"
+        );
+    }
+
+    #[test]
+    fn test_io_contract_case_08() {
+        assert_eq!(convert_rpn_to_latex("5 3 - 2 -").unwrap(), "$5 - 3 - 2$");
+    }
+
+    #[test]
+    fn test_io_contract_case_09() {
+        assert_eq!(
+            convert_rpn_to_latex("100 10 / 5 / 2 /").unwrap(),
+            r"$100 \div 10 \div 5 \div 2$"
+        );
+    }
+
+    #[test]
+    fn test_io_contract_case_10() {
+        assert_eq!(
+            convert_rpn_to_latex("1 2 + 3 + 4 +").unwrap(),
+            "$1 + 2 + 3 + 4$"
+        );
+    }
+
+    #[test]
+    fn test_io_contract_case_11() {
+        assert_eq!(
+            convert_rpn_to_latex("2 3 4 * +").unwrap(),
+            r"$2 + 3 \times 4$"
+        );
+    }
+
+    #[test]
+    fn test_io_contract_case_12() {
+        assert_eq!(
+            convert_rpn_to_latex("2 3 + 4 *").unwrap(),
+            r"$( 2 + 3 ) \times 4$"
+        );
+    }
+
+    #[test]
+    fn test_io_contract_case_13() {
+        assert_eq!(
+            convert_rpn_to_latex("2 3 4 + *").unwrap(),
+            r"$2 \times ( 3 + 4 )$"
+        );
+    }
+
+    #[test]
+    fn test_io_contract_case_14() {
+        assert_eq!(
+            convert_rpn_to_latex("2 3 * 4 +").unwrap(),
+            r"$2 \times 3 + 4$"
+        );
+    }
+
+    #[test]
+    fn test_io_contract_case_15() {
+        assert_eq!(
+            convert_rpn_to_latex("3.14 2 *").unwrap(),
+            r"$3.14 \times 2$"
+        );
+    }
+
+    #[test]
+    fn test_io_contract_case_16() {
+        assert_eq!(convert_rpn_to_latex("1.5 0.5 +").unwrap(), "$1.5 + 0.5$");
+    }
+
+    #[test]
+    fn test_io_contract_case_17() {
+        assert_eq!(
+            convert_rpn_to_latex("1 2 + 3 4 + *").unwrap(),
+            r"$( 1 + 2 ) \times ( 3 + 4 )$"
+        );
+    }
+
+    #[test]
+    fn test_io_contract_case_18() {
+        assert_eq!(
+            convert_rpn_to_latex("10 2 / 3 + 4 *").unwrap(),
+            r"$( 10 \div 2 + 3 ) \times 4$"
+        );
+    }
+
+    /// Builds options for the given `--emit` kind with default settings.
+    fn emit_options(emit: Emit) -> Options {
+        Options {
+            emit,
+            ..Options::new("-")
+        }
+    }
+
+    /// Builds options for the given `--format` with default settings.
+    fn format_options(format: Format) -> Options {
+        Options {
+            format,
+            ..Options::new("-")
+        }
+    }
+
+    #[test]
+    fn test_convert_with_environment() {
+        let options = Options {
+            environment: MathEnvironment::Gather,
+            ..Options::new("-")
+        };
+        let result = convert("5 3 +", &options).unwrap();
+        assert_eq!(result, "\\begin{gather}\n5 + 3\n\\end{gather}");
+    }
+
+    #[test]
+    fn test_convert_standalone() {
+        let options = parse_args(&args(&["-", "--standalone"])).expect("should parse");
+        let result = convert("5 3 +", &options).unwrap();
+        assert!(result.starts_with("\\documentclass{standalone}\n"));
+        assert!(result.contains("\\begin{document}\n$5 + 3$\n\\end{document}"));
+    }
+
+    #[test]
+    fn test_standalone_requires_latex() {
+        let err = parse_args(&args(&["-", "--standalone", "--format", "python"])).unwrap_err();
+        assert!(err.contains("--standalone requires LaTeX output"));
+    }
+
+    #[test]
+    fn test_standalone_rejects_bare() {
+        let err = parse_args(&args(&["-", "--standalone", "--environment", "bare"])).unwrap_err();
+        assert!(err.contains("--standalone cannot wrap bare math"));
+    }
+
+    #[test]
+    fn test_convert_with_spacing() {
+        let options = parse_args(&args(&["-", "--spacing", "normal"])).expect("should parse");
+        assert_eq!(
+            convert("5 3 + 2 *", &options).unwrap(),
+            r"$(5 + 3) \times 2$"
+        );
+        assert_eq!(parse_spacing("tight"), Some(SpacingStyle::Tight));
+        assert_eq!(parse_spacing("loose"), None);
+    }
+
+    #[test]
+    fn test_convert_with_paren_sizing() {
+        let options = parse_args(&args(&["-", "--parens", "auto"])).expect("should parse");
+        assert_eq!(
+            convert("5 3 + 2 *", &options).unwrap(),
+            r"$\left( 5 + 3 \right) \times 2$"
+        );
+        assert_eq!(parse_paren_sizing("auto-tall"), Some(ParenSizing::AutoTall));
+        assert_eq!(parse_paren_sizing("big"), None);
+    }
+
+    #[test]
+    fn test_convert_with_thousands() {
+        let options = parse_args(&args(&["-", "--thousands", "thin-space"])).expect("should parse");
+        assert_eq!(
+            convert("1000000 2 *", &options).unwrap(),
+            r"$1\,000\,000 \times 2$"
+        );
+
+        let options = parse_args(&args(&["-", "--thousands", "comma"])).expect("should parse");
+        assert_eq!(
+            convert("1000000 2 *", &options).unwrap(),
+            r"$1{,}000{,}000 \times 2$"
+        );
+        assert_eq!(parse_thousands("dot"), None);
+    }
+
+    #[test]
+    fn test_convert_with_decimal_comma() {
+        let options = parse_args(&args(&["-", "--decimal", "comma"])).expect("should parse");
+        assert_eq!(convert("3.14 2 *", &options).unwrap(), r"$3{,}14 \times 2$");
+        assert_eq!(parse_decimal("point"), Some(DecimalSeparator::Point));
+        assert_eq!(parse_decimal("dot"), None);
+    }
+
+    #[test]
+    fn test_decimal_comma_rejects_comma_grouping() {
+        let err =
+            parse_args(&args(&["-", "--decimal", "comma", "--thousands", "comma"])).unwrap_err();
+        assert!(err.contains("ambiguous"));
+    }
+
+    #[test]
+    fn test_convert_leading_dot_decimals() {
+        assert_eq!(convert_rpn_to_latex(".5 2 *").unwrap(), r"$.5 \times 2$");
+
+        let options = parse_args(&args(&["-", "--leading-zero"])).expect("should parse");
+        assert_eq!(convert(".5 -.25 -", &options).unwrap(), "$0.5 - (-0.25)$");
+    }
+
+    #[test]
+    fn test_convert_with_precision() {
+        let options = parse_args(&args(&["-", "--decimal-places", "2"])).expect("should parse");
+        assert_eq!(
+            convert("3.14159 2 *", &options).unwrap(),
+            r"$3.14 \times 2.00$"
+        );
+
+        let options = parse_args(&args(&["-", "--sig-figs", "2"])).expect("should parse");
+        assert_eq!(convert("3.14159 1234 +", &options).unwrap(), "$3.1 + 1200$");
+    }
+
+    #[test]
+    fn test_precision_errors() {
+        let err = parse_args(&args(&["-", "--decimal-places", "two"])).unwrap_err();
+        assert!(err.contains("--decimal-places expects a digit count, got 'two'"));
+        let err = parse_args(&args(&["-", "--sig-figs", "0"])).unwrap_err();
+        assert!(err.contains("--sig-figs must be at least 1"));
+    }
+
+    #[test]
+    fn test_convert_negative_operand() {
+        assert_eq!(convert_rpn_to_latex("10 -5 +").unwrap(), "$10 + (-5)$");
+
+        let options = parse_args(&args(&["-", "--no-negative-parens"])).expect("should parse");
+        assert_eq!(convert("10 -5 +", &options).unwrap(), "$10 + -5$");
+    }
+
+    #[test]
+    fn test_convert_siunitx() {
+        let options = parse_args(&args(&["-", "--siunitx"])).expect("should parse");
+        assert_eq!(
+            convert("1000000 2 *", &options).unwrap(),
+            r"$\num{1000000} \times \num{2}$"
+        );
+
+        let options = parse_args(&args(&["-", "--siunitx", "--standalone"])).expect("should parse");
+        assert!(convert("1", &options)
+            .unwrap()
+            .contains("\\usepackage{siunitx}\n"));
+    }
+
+    #[test]
+    fn test_siunitx_conflicts() {
+        let err = parse_args(&args(&["-", "--siunitx", "--thousands", "comma"])).unwrap_err();
+        assert!(err.contains("--siunitx formats digits itself"));
+        let err = parse_args(&args(&["-", "--siunitx", "--katex"])).unwrap_err();
+        assert!(err.contains("KaTeX cannot render --siunitx numbers"));
+    }
+
+    #[test]
+    fn test_convert_mul_style_and_frac() {
+        let options =
+            parse_args(&args(&["-", "--mul-style", "cdot", "--frac"])).expect("should parse");
+        assert_eq!(
+            convert("1 2 / 3 *", &options).unwrap(),
+            r"$\frac{1}{2} \cdot 3$"
+        );
+        assert_eq!(parse_mul_style("times"), Some(MulStyle::Times));
+        assert_eq!(parse_mul_style("dot"), None);
+    }
+
+    #[test]
+    fn test_convert_frac_style() {
+        let options = parse_args(&args(&["-", "--frac-style", "tfrac"])).expect("should parse");
+        assert!(options.frac);
+        assert_eq!(convert("1 2 /", &options).unwrap(), r"$\tfrac{1}{2}$");
+        assert_eq!(parse_frac_style("dfrac"), Some(FracStyle::Dfrac));
+        assert_eq!(parse_frac_style("sfrac"), None);
+    }
+
+    #[test]
+    fn test_convert_emit_packages() {
+        let options = parse_args(&args(&["-", "--emit", "packages"])).expect("should parse");
+        assert_eq!(convert("5 3 +", &options).unwrap(), "");
+
+        let options = parse_args(&args(&[
+            "-",
+            "--emit",
+            "packages",
+            "--environment",
+            "gather",
+            "--siunitx",
+        ]))
+        .expect("should parse");
+        assert_eq!(convert("5 3 +", &options).unwrap(), "amsmath\nsiunitx\n");
+
+        let err =
+            parse_args(&args(&["-", "--emit", "packages", "--format", "python"])).unwrap_err();
+        assert!(err.contains("--emit packages requires LaTeX output"));
+    }
+
+    #[test]
+    fn test_evaluate_rpn() {
+        let options = Options::new("-");
+        assert_eq!(evaluate_rpn("5 3 + 2 *", &options), Ok("16".to_string()));
+        assert_eq!(evaluate_rpn("1 4 /", &options), Ok("0.25".to_string()));
+
+        let err = evaluate_rpn("5 0 /", &options).unwrap_err();
+        assert!(err.contains("Division by zero"));
+        assert!(err.contains("^"));
+
+        let err = evaluate_rpn("5 @", &options).unwrap_err();
+        assert!(err.contains("Unexpected character '@'"));
+    }
+
+    #[test]
+    fn test_convert_csv() {
+        let source = "name,rpn\nsum,5 3 +\n\"a, b\",\"1 2 /\"\nblank,\n";
+        let options = parse_args(&args(&["-", "--format", "csv"])).expect("should parse");
+        assert_eq!(
+            convert(source, &options).unwrap(),
+            "name,rpn,latex\nsum,5 3 +,$5 + 3$\n\"a, b\",1 2 /,$1 \\div 2$\nblank,,\n"
+        );
+
+        let options = parse_args(&args(&[
+            "-",
+            "--format",
+            "csv",
+            "--csv-column",
+            "expr",
+            "--csv-value",
+            "--environment",
+            "bare",
+        ]))
+        .expect("should parse");
+        assert_eq!(
+            convert("expr\n2 3 *\n", &options).unwrap(),
+            "expr,latex,value\n2 3 *,2 \\times 3,6\n"
+        );
+
+        // Failing rows keep empty cells, and each is reported by its line
+        let source = "expr\n1 +\n2 3 *\n4 @\n";
+        let (output, failures) = convert_rows(source, &options).unwrap();
+        assert_eq!(
+            output,
+            "expr,latex,value\n1 +,,\n2 3 *,2 \\times 3,6\n4 @,,\n"
+        );
+        assert_eq!(output.lines().count(), source.lines().count());
+        assert_eq!(failures.len(), 2);
+        assert!(failures[0].starts_with("Error in CSV line 2:\n"));
+        assert_eq!((failures[0].kind, failures[1].kind), ("parse", "lex"));
+        assert!(failures[1].starts_with("Error in CSV line 4:\n"));
+        let err = convert(source, &options).unwrap_err();
+        assert!(err.contains("Error in CSV line 4:\n"));
+        let err = convert("rpn\n1 +\n", &options).unwrap_err();
+        assert!(err.contains("no column 'expr'"));
+        let err = convert("expr\n1,2\n", &options).unwrap_err();
+        assert!(err.starts_with("Error in CSV input"));
+
+        let err = parse_args(&args(&["-", "--format", "csv", "--multi"])).unwrap_err();
+        assert!(err.contains("--format csv only applies to --emit output"));
+        let err = parse_args(&args(&["-", "--csv-value"])).unwrap_err();
+        assert!(err.contains("--csv-value require --format csv"));
+    }
+
+    #[test]
+    fn test_convert_worksheet() {
+        let options = parse_args(&args(&[
+            "worksheet",
+            "-",
+            "--columns",
+            "1",
+            "--answer-space",
+            "3em",
+            "--title",
+            "Sums",
+        ]))
+        .expect("should parse");
+        assert_eq!(options.command, Command::Worksheet);
+        assert_eq!(options.input_path, "-");
+        assert_eq!(
+            convert("5 3 +\n2 4 *; 7", &options).unwrap(),
+            "\\documentclass{article}\n\\begin{document}\n\\section*{Sums}\n\
+             \\begin{enumerate}\n\
+             \\item $5 + 3 = $\n\\vspace{3em}\n\
+             \\item $2 \\times 4 = $\n\\vspace{3em}\n\
+             \\item $7 = $\n\\vspace{3em}\n\
+             \\end{enumerate}\n\\end{document}\n"
+        );
+
+        // The title is text, not markup
+        let titled =
+            parse_args(&args(&["worksheet", "-", "--title", "50% & up_1"])).expect("should parse");
+        assert!(convert("1", &titled)
+            .unwrap()
+            .contains("\\section*{50\\% \\& up\\_1}\n"));
+
+        // Every failing problem is reported
+        let err = convert("5 +\n2 4 *\n@", &options).unwrap_err();
+        assert!(err.contains("Line 1, column 3"));
+        assert!(err.contains("Line 3, column 1"));
+
+        let err = parse_args(&args(&["worksheet", "-", "--columns", "11"])).unwrap_err();
+        assert!(err.contains("--columns expects 1 to 10"));
+        let err = parse_args(&args(&["worksheet", "-", "--format", "rpn"])).unwrap_err();
+        assert!(err.contains("worksheet generates a LaTeX document"));
+        let err = parse_args(&args(&["-", "--title", "Sums"])).unwrap_err();
+        assert!(err.contains("require the worksheet command"));
+    }
+
+    #[cfg(feature = "exact")]
+    #[test]
+    fn test_worksheet_answer_key() {
+        let options = parse_args(&args(&[
+            "worksheet",
+            "-",
+            "--columns",
+            "1",
+            "--answer-key",
+            "--exact",
+        ]))
+        .expect("should parse");
+        let worksheet = convert("5 3 +\n1 3 /", &options).unwrap();
+        assert!(worksheet.ends_with(
+            "\\newpage\n\\section*{Answers}\n\\begin{enumerate}\n\
+             \\item $5 + 3 = 8$\n\\item $1 \\div 3 = 1/3$\n\
+             \\end{enumerate}\n\\end{document}\n"
+        ));
+
+        let options = parse_args(&args(&[
+            "worksheet",
+            "-",
+            "--title",
+            "Quiz",
+            "--answer-key-output",
+            "key.tex",
+            "--precision",
+            "2",
+        ]))
+        .expect("should parse");
+        let key = |source| {
+            parse_problems(source, &options)
+                .and_then(|problems| answer_key(source, &problems, &options))
+        };
+        let text = key("1 3 /").unwrap();
+        assert!(text.contains("\\section*{Quiz: Answers}\n"));
+        assert!(text.contains("\\item $1 \\div 3 = 0.33$\n"));
+        // Problems that cannot be evaluated are all reported
+        let err = key("1 0 /\n2 2 -\n5 0 /").unwrap_err();
+        assert_eq!(err.matches("Division by zero").count(), 2);
+
+        let err = parse_args(&args(&["-", "--answer-key"])).unwrap_err();
+        assert!(err.contains("require the worksheet command"));
+        let err = parse_args(&args(&[
+            "worksheet",
+            "a.rpn",
+            "-d",
+            "out",
+            "--answer-key-output",
+            "k.tex",
+        ]))
+        .unwrap_err();
+        assert!(err.contains("--answer-key-output cannot be combined with -d"));
+    }
+
+    #[test]
+    fn test_convert_filter_markdown() {
+        let document = "# Sums\n\nInline `rpn:1 2 +` and `code`.\n\n```rpn\n3 4 *\n```\n";
+        let options = parse_args(&args(&["-", "--filter", "markdown"])).expect("should parse");
+        assert_eq!(
+            convert(document, &options).unwrap(),
+            "# Sums\n\nInline $1 + 2$ and `code`.\n\n```math\n3 \\times 4\n```\n"
+        );
+
+        let options = parse_args(&args(&[
+            "-",
+            "--filter",
+            "markdown",
+            "--markdown-flavor",
+            "pandoc",
+            "--mul-style",
+            "cdot",
+        ]))
+        .expect("should parse");
+        let output = convert(document, &options).unwrap();
+        assert!(output.ends_with("\n$$\n3 \\cdot 4\n$$\n"));
+
+        // Every failing snippet is reported, positioned in the document
+        let err = convert("Text\n\n`rpn:1 +` and `rpn:2 @`\n", &options).unwrap_err();
+        assert!(err.contains("Line 3, column 8: Too few operands"));
+        assert!(err.contains("Line 3, column 22: Unexpected character '@'"));
+
+        let err = parse_args(&args(&["-", "--filter", "html"])).unwrap_err();
+        assert!(err.contains("Unknown --filter 'html'"));
+        let err = parse_args(&args(&["-", "--filter", "markdown", "--multi"])).unwrap_err();
+        assert!(err.contains("--filter only applies to --emit output"));
+    }
+
+    #[test]
+    fn test_convert_evaluate() {
+        let options = parse_args(&args(&["-", "--evaluate"])).expect("should parse");
+        assert_eq!(convert("5 3 + 2 *", &options).unwrap(), "16");
+        assert_eq!(convert("0 -1 *", &options).unwrap(), "0");
+        assert!(convert("1 0 /", &options)
+            .unwrap_err()
+            .contains("Division by zero"));
+
+        let options =
+            parse_args(&args(&["-", "--evaluate", "--precision", "3"])).expect("should parse");
+        assert_eq!(convert("2 3 /", &options).unwrap(), "0.667");
+        assert_eq!(convert("5 2 /", &options).unwrap(), "2.500");
+        if cfg!(feature = "exact") {
+            let options = parse_args(&args(&["-", "--evaluate", "--exact"])).expect("should parse");
+            assert_eq!(convert("2 3 /", &options).unwrap(), "2/3");
+            let err =
+                parse_args(&args(&["-", "--eval", "--exact", "--precision", "2"])).unwrap_err();
+            assert!(err.contains("--precision cannot be combined with --exact"));
+        }
+
+        let err = parse_args(&args(&["-", "--evaluate", "--show-result"])).unwrap_err();
+        assert!(err.contains("--evaluate prints only the value"));
+        let err = parse_args(&args(&["-", "--precision", "2"])).unwrap_err();
+        assert!(err.contains("--precision requires --eval, --evaluate or an answer key"));
+    }
+
+    #[test]
+    fn test_convert_show_result() {
+        let options = parse_args(&args(&["-", "--show-result"])).expect("should parse");
+        assert_eq!(
+            convert("5 3 + 2 *", &options).unwrap(),
+            r"$( 5 + 3 ) \times 2 = 16$"
+        );
+
+        let options = parse_args(&args(&["-", "--show-result", "--decimal-places", "2"]))
+            .expect("should parse");
+        assert_eq!(
+            convert("1 3 /", &options).unwrap(),
+            r"$1.00 \div 3.00 = 0.33$"
+        );
+
+        let err = convert(
+            "5 0 /",
+            &parse_args(&args(&["-", "--show-result"])).unwrap(),
+        )
+        .unwrap_err();
+        assert!(err.contains("Division by zero"));
+    }
+
+    #[test]
+    fn test_convert_blank() {
+        let options =
+            parse_args(&args(&["-", "--blank", "4", "--mul-style", "cdot"])).expect("should parse");
+        assert_eq!(
+            convert("5 3 + 2 *", &options).unwrap(),
+            r"$( 5 + 3 ) \cdot \underline{\hspace{1cm}}$"
+        );
+        let ast = parse_rpn("5 3 + 2 *", &options).unwrap();
+        assert_eq!(blank_out(&ast, 5, &options).unwrap().hidden, r"\cdot");
+        let err = convert("5 3 +", &options).unwrap_err();
+        assert!(err.contains("--blank 4 is past the expression's last token"));
+
+        let err = parse_args(&args(&["-", "--blank", "0"])).unwrap_err();
+        assert!(err.contains("--blank counts tokens from 1"));
+        let err = parse_args(&args(&["-", "--blank", "1", "--format", "python"])).unwrap_err();
+        assert!(err.contains("--blank hides a token of one LaTeX expression"));
+    }
+
+    #[test]
+    fn test_variables() {
+        let options = parse_args(&args(&["-", "--allow-vars"])).expect("should parse");
+        assert_eq!(
+            convert("x 3 + 2 *", &options).unwrap(),
+            r"$( x + 3 ) \times 2$"
+        );
+        assert!(convert("x 3 +", &Options::new("-"))
+            .unwrap_err()
+            .contains("Unexpected character 'x'"));
+
+        let err = evaluate_rpn("x 3 +", &options).unwrap_err();
+        assert!(err.contains("Unbound variable 'x'"));
+
+        let options =
+            parse_args(&args(&["-", "--let", "x=2", "--show-result"])).expect("should parse");
+        assert_eq!(
+            convert("x 3 + 2 *", &options).unwrap(),
+            r"$( x + 3 ) \times 2 = 10$"
+        );
+        assert_eq!(
+            evaluate_rpn("x 3 /", &options),
+            Ok((2.0_f64 / 3.0).to_string())
+        );
+    }
+
+    #[cfg(feature = "exact")]
+    #[test]
+    fn test_convert_fold_constants() {
+        let options =
+            parse_args(&args(&["-", "--fold-constants", "--allow-vars"])).expect("should parse");
+        assert_eq!(convert("x 2 3 * +", &options).unwrap(), "$x + 6$");
+        assert_eq!(
+            convert("1 3 / 2 2 + *", &options).unwrap(),
+            r"$1 \div 3 \times 4$"
+        );
+
+        let options = parse_args(&args(&["-", "--fold-constants", "--emit", "ast-json"]))
+            .expect("should parse");
+        let json = convert("2 3 *", &options).unwrap();
+        assert!(json.contains(r#""value": "6""#));
+    }
+
+    #[test]
+    fn test_convert_macros() {
+        let options = parse_args(&args(&["-", "--macros"])).expect("should parse");
+        assert_eq!(
+            convert("1 2 + 1 2 + *", &options).unwrap(),
+            "\\newcommand{\\rpnA}{1 + 2}\n$( \\rpnA ) \\times ( \\rpnA )$"
+        );
+
+        for extra in ["--standalone", "--katex", "--show-result"] {
+            assert!(
+                parse_args(&args(&["-", "--macros", extra])).is_err(),
+                "{}",
+                extra
+            );
+        }
+        let err = parse_args(&args(&["-", "--macros", "--format", "rpn"])).unwrap_err();
+        assert!(err.contains("--macros requires LaTeX output"));
+    }
+
+    #[test]
+    fn test_convert_multi() {
+        let options = parse_args(&args(&["-", "--multi"])).expect("should parse");
+        assert_eq!(
+            convert("5 3 +\n2 4 *; 1 2 3 + -\n", &options).unwrap(),
+            "$5 + 3$\n$2 \\times 4$\n$1 - ( 2 + 3 )$"
+        );
+
+        let err = convert("5 3 +\n4 *\n2 @", &options).unwrap_err();
+        assert!(err.contains("Line 2, column 3: Too few operands"));
+        assert!(err.contains("Line 3, column 3: Unexpected character '@'"));
+
+        let err = parse_args(&args(&["-", "--multi", "--emit", "dot"])).unwrap_err();
+        assert!(err.contains("--multi only applies to --emit output"));
+    }
+
+    #[test]
+    fn test_difficulty_filter() {
+        // Scores 2, 12 and 13
+        let source = "5 3 +\n47 12 * 7 /\n9 8 * 7 * 6 -\n";
+        let options =
+            parse_args(&args(&["-", "--multi", "--min-difficulty", "5"])).expect("should parse");
+        assert_eq!(
+            convert(source, &options).unwrap(),
+            "$47 \\times 12 \\div 7$\n$9 \\times 8 \\times 7 - 6$"
+        );
+        // Errors are still reported
+        assert!(convert("5 3 +\n+", &options).is_err());
+
+        let options = parse_args(&args(&[
+            "worksheet",
+            "-",
+            "--max-difficulty",
+            "12",
+            "--min-difficulty",
+            "3",
+        ]))
+        .expect("should parse");
+        let worksheet = convert(source, &options).unwrap();
+        assert_eq!(worksheet.matches("\\item").count(), 1);
+        assert!(worksheet.contains("\\item $47 \\times 12 \\div 7 = $"));
+
+        let err = parse_args(&args(&["-", "--max-difficulty", "3"])).unwrap_err();
+        assert!(err.contains("require --multi or the worksheet command"));
+        let err = parse_args(&args(&[
+            "-",
+            "--multi",
+            "--min-difficulty",
+            "4",
+            "--max-difficulty",
+            "3",
+        ]))
+        .unwrap_err();
+        assert!(err.contains("--min-difficulty is above --max-difficulty"));
+    }
+
+    #[test]
+    fn test_convert_leftovers() {
+        let options = parse_args(&args(&["-", "--leftovers", "comma"])).expect("should parse");
+        assert_eq!(
+            convert("5 3 + 2 4 *", &options).unwrap(),
+            "$5 + 3$, $2 \\times 4$"
+        );
+        assert_eq!(convert("5", &options).unwrap(), "$5$");
+        let err = convert("5 +", &options).unwrap_err();
+        assert!(err.contains("Too few operands"));
+
+        let options = parse_args(&args(&["-", "--leftovers", "newline", "--format", "rpn"]))
+            .expect("should parse");
+        assert_eq!(convert("1 2 3", &options).unwrap(), "1\n2\n3");
+
+        let err = convert("5 3", &parse_args(&args(&["-"])).unwrap()).unwrap_err();
+        assert!(err.contains("Too many values"));
+        let err = parse_args(&args(&["-", "--leftovers", "space"])).unwrap_err();
+        assert!(err.contains("Unknown --leftovers 'space'"));
+        let err = parse_args(&args(&["-", "--leftovers", "comma", "--eval"])).unwrap_err();
+        assert!(err.contains("--leftovers only applies to --emit output"));
+    }
+
+    #[test]
+    fn test_convert_let_bindings() {
+        let source = "let a = 5 3 + ; a 2 *";
+        assert_eq!(
+            convert_rpn_to_latex(source).unwrap(),
+            "$( 5 + 3 ) \\times 2$"
+        );
+        let options = parse_args(&args(&["-", "--let-style", "symbolic"])).expect("should parse");
+        assert_eq!(convert(source, &options).unwrap(), "$a \\times 2$");
+
+        let options = parse_args(&args(&["-", "--eval"])).expect("should parse");
+        assert_eq!(evaluate_rpn(source, &options), Ok("16".to_string()));
+
+        let err = convert_rpn_to_latex("let a = 1\na b +").unwrap_err();
+        assert!(err.contains("Line 2, column 3: Undefined name 'b'"));
+        assert!(err.contains("help: bind it with 'let' first, or pass --allow-vars"));
+        let options = parse_args(&args(&["-", "--allow-vars"])).expect("should parse");
+        assert_eq!(convert("let a = 1\na b +", &options).unwrap(), "$1 + b$");
+
+        let err = convert_rpn_to_latex("let a 1; a").unwrap_err();
+        assert!(err.contains("Line 1, column 7: Expected '=' after 'let a'"));
+        let err = convert_rpn_to_latex("let a = 1 @; a").unwrap_err();
+        assert!(err.contains("Unexpected character '@'"));
+
+        let err = parse_args(&args(&["-", "--let-style", "named"])).unwrap_err();
+        assert!(err.contains("Unknown --let-style 'named'"));
+        let err =
+            parse_args(&args(&["-", "--let-style", "symbolic", "--show-result"])).unwrap_err();
+        assert!(err.contains("--let-style symbolic cannot be evaluated"));
+    }
+
+    #[test]
+    fn test_convert_rpn_macros() {
+        assert_eq!(
+            convert_rpn_to_latex("macro sq = dup *\n3 sq").unwrap(),
+            "$3 \\times 3$"
+        );
+        let err = convert_rpn_to_latex("macro sq = dup *\nsq").unwrap_err();
+        assert!(err.contains("Line 2, column 1: 'dup' needs a value"));
+
+        let mut options = parse_args(&args(&["-", "--prelude", "defs.rpn"])).expect("should parse");
+        assert_eq!(options.prelude_path, Some(PathBuf::from("defs.rpn")));
+        options.prelude = MacroTable::parse_prelude("macro half = 2 /").unwrap();
+        assert_eq!(convert("5 half", &options).unwrap(), "$5 \\div 2$");
+        assert_eq!(
+            convert("let a = 4 half; a 1 +", &options).unwrap(),
+            "$4 \\div 2 + 1$"
+        );
+
+        let err =
+            parse_args(&args(&["-", "--prelude", "defs.rpn", "--from", "prefix"])).unwrap_err();
+        assert!(err.contains("--prelude macros only apply to RPN input"));
+    }
+
+    #[test]
+    fn test_program_errors_underline_whole_names() {
+        let err = convert_rpn_to_latex("let a = 1; a rate +").unwrap_err();
+        assert!(
+            err.contains("1 | let a = 1; a rate +\n                 ^^^^\n"),
+            "{}",
+            err
+        );
+        // Tokens expanded from a macro carry the span of the macro's name
+        let err = convert_rpn_to_latex("macro sq = dup *; sq").unwrap_err();
+        assert!(
+            err.contains("1 | macro sq = dup *; sq\n                      ^^\n"),
+            "{}",
+            err
+        );
+    }
+
+    #[cfg(feature = "exact")]
+    #[test]
+    fn test_evaluate_rpn_exact() {
+        let options = parse_args(&args(&["-", "--eval", "--exact"])).expect("should parse");
+        assert_eq!(evaluate_rpn("1 3 /", &options), Ok("1/3".to_string()));
+        assert_eq!(evaluate_rpn("0.1 0.2 +", &options), Ok("3/10".to_string()));
+        assert!(evaluate_rpn("1 0 /", &options)
+            .unwrap_err()
+            .contains("Division by zero"));
+    }
+
+    #[test]
+    fn test_convert_katex() {
+        let options = parse_args(&args(&["-", "--katex"])).expect("should parse");
+        assert_eq!(convert("5 3 +", &options).unwrap(), r"\(5 + 3\)");
+    }
+
+    #[test]
+    fn test_katex_rejects_standalone() {
+        let err = parse_args(&args(&["-", "--katex", "--standalone"])).unwrap_err();
+        assert!(err.contains("KaTeX cannot render --standalone documents"));
+    }
+
+    #[test]
+    fn test_convert_format_prefix() {
+        let result = convert("5 3 2 * +", &format_options(Format::Prefix)).unwrap();
+        assert_eq!(result, "+ 5 * 3 2");
+    }
+
+    #[test]
+    fn test_convert_format_rpn() {
+        let result = convert("5  3\n+", &format_options(Format::Rpn)).unwrap();
+        assert_eq!(result, "5 3 +");
+    }
+
+    #[test]
+    fn test_convert_format_content_mathml() {
+        let result = convert("1 3 /", &format_options(Format::ContentMathml)).unwrap();
+        assert!(result.contains("<apply><divide/><cn type=\"integer\">1</cn>"));
+    }
+
+    #[test]
+    fn test_convert_format_wolfram() {
+        let result = convert("5 3 + 2 *", &format_options(Format::Wolfram)).unwrap();
+        assert_eq!(result, "Times[Plus[5, 3], 2]");
+
+        let result = convert("5 3 + 2 *", &format_options(Format::WolframInfix)).unwrap();
+        assert_eq!(result, "(5 + 3)*2");
+    }
+
+    #[test]
+    fn test_convert_format_python() {
+        let result = convert("5 3 + 2 *", &format_options(Format::Python)).unwrap();
+        assert_eq!(result, "(5 + 3) * 2");
+    }
+
+    #[test]
+    fn test_convert_format_context() {
+        let result = convert("5 3 + 2 *", &format_options(Format::Context)).unwrap();
+        assert_eq!(result, r"\m{( 5 + 3 ) \times 2}");
+    }
+
+    #[test]
+    fn test_convert_format_markdown() {
+        let options = parse_args(&args(&["-", "--format", "markdown"])).expect("should parse");
+        assert_eq!(convert("5 3 +", &options).unwrap(), "```math\n5 + 3\n```");
+
+        let options = parse_args(&args(&[
+            "-",
+            "--format",
+            "markdown",
+            "--markdown-flavor",
+            "pandoc",
+        ]))
+        .expect("should parse");
+        assert_eq!(convert("5 3 +", &options).unwrap(), "$5 + 3$");
+    }
+
+    #[test]
+    fn test_convert_emit_dot() {
+        let result = convert("5 3 +", &emit_options(Emit::Dot)).expect("should succeed");
+        assert!(result.starts_with("digraph AST {"));
+        assert!(result.contains("n0 -> n2;"));
+    }
+
+    #[test]
+    fn test_convert_emit_ast() {
+        if cfg!(feature = "exact") {
+            let options = parse_args(&args(&["-", "--emit", "ast", "--fold-constants"]))
+                .expect("should parse");
+            assert_eq!(
+                convert("5 3 4 *\n-", &options).unwrap(),
+                "Number -7 at 2:1\n"
+            );
+        }
+        let result = convert("5 3 4 * -", &emit_options(Emit::Ast)).unwrap();
+        assert!(result.starts_with("BinaryOp - at 1:9\n├── Number 5 at 1:1\n"));
+
+        let err = convert("5 3 4 *", &emit_options(Emit::Ast)).unwrap_err();
+        assert!(err.contains("Too many values"));
+    }
+
+    #[test]
+    fn test_convert_emit_ast_json() {
+        let result = convert("5 3 +", &emit_options(Emit::AstJson)).expect("should succeed");
+        let value: serde_json::Value = serde_json::from_str(&result).expect("valid JSON");
+        assert_eq!(value["type"], "BinaryOp");
+        assert_eq!(value["operator"], "+");
+        assert_eq!(value["left"]["value"], "5");
+        assert_eq!(value["right"]["column"], 3);
+    }
+
+    #[test]
+    fn test_convert_emit_ast_json_errors() {
+        let lexer_error = convert("2 3 ^", &emit_options(Emit::AstJson)).unwrap_err();
+        let value: serde_json::Value = serde_json::from_str(&lexer_error).expect("valid JSON");
+        assert_eq!(value["stage"], "lexer");
+        assert_eq!(value["error"]["column"], 5);
+
+        let parser_error = convert("5 +", &emit_options(Emit::AstJson)).unwrap_err();
+        let value: serde_json::Value = serde_json::from_str(&parser_error).expect("valid JSON");
+        assert_eq!(value["stage"], "parser");
+        assert_eq!(value["error"]["token"]["token_type"], "Plus");
+        assert_eq!(value["diagnostic"]["code"], "E101");
+        assert_eq!(value["diagnostic"]["severity"], "error");
+        assert_eq!(value["diagnostic"]["column"], 3);
+    }
+
+    #[test]
+    fn test_convert_emit_tokens() {
+        let options =
+            parse_args(&args(&["-", "--emit", "tokens", "--allow-vars"])).expect("should parse");
+        assert_eq!(options.emit, Emit::Tokens);
+        assert_eq!(
+            convert("1_000 x\n  *", &options).unwrap(),
+            "TYPE        LEXEME  VALUE  POSITION  BYTES\n\
+             Number      1_000   1000   1:1       0..5\n\
+             Identifier  x       x      1:7       6..7\n\
+             Mult        *       *      2:3       10..11\n\
+             Eof                        2:4       11..11\n"
+        );
+
+        let err = convert("5 @", &options).unwrap_err();
+        assert!(err.starts_with("Line 1, column 3: Unexpected character '@'"));
+    }
+
+    #[test]
+    fn test_convert_emit_tokens_json() {
+        let options = parse_args(&args(&["-", "--emit", "tokens", "--format", "json"]))
+            .expect("should parse");
+        let result = convert("5 3 +", &options).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&result).expect("valid JSON");
+        assert_eq!(value.as_array().unwrap().len(), 4);
+        assert_eq!(value[2]["token_type"], "Plus");
+        assert_eq!(value[2]["column"], 5);
+
+        let err = convert("5 @", &options).unwrap_err();
+        let value: serde_json::Value = serde_json::from_str(&err).expect("valid JSON");
+        assert_eq!(value["stage"], "lexer");
+
+        let err = parse_args(&args(&["-", "--emit", "dot", "--format", "json"])).unwrap_err();
+        assert!(err.contains("--format json is only available with --emit output or tokens"));
+        let err = parse_args(&args(&["-", "--emit", "tokens", "--format", "python"])).unwrap_err();
+        assert!(err.contains("--emit tokens only supports --format json"));
+    }
+
+    #[test]
+    fn test_convert_format_json() {
+        let options = parse_args(&args(&["-", "--format", "json"])).expect("should parse");
+        let result = convert("5 3 +", &options).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&result).expect("valid JSON");
+        assert_eq!(value["input"], "5 3 +");
+        assert_eq!(value["latex"], "$5 + 3$");
+        assert_eq!(value["ast"]["type"], "BinaryOp");
+        assert_eq!(value["ast"]["operator"], "+");
+        assert_eq!(value["diagnostics"], serde_json::json!([]));
+
+        // Errors are part of the result, not a failure of the conversion
+        let result = convert("2 ^ 3 @", &options).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&result).expect("valid JSON");
+        assert!(value["latex"].is_null());
+        assert!(value["ast"].is_null());
+        let diagnostics = value["diagnostics"].as_array().unwrap();
+        assert_eq!(diagnostics.len(), 3);
+        assert_eq!(diagnostics[0]["message"], "Unexpected character '^'");
+        assert_eq!(diagnostics[0]["column"], 3);
+        assert_eq!(diagnostics[0]["code"], "E001");
+    }
+
+    #[test]
+    fn test_convert_format_json_multi() {
+        let options =
+            parse_args(&args(&["-", "--format", "json", "--multi"])).expect("should parse");
+        let result = convert("5 3 +\n2 +\n  x 1 -", &options).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&result).expect("valid JSON");
+        let results = value.as_array().unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0]["latex"], "$5 + 3$");
+        assert_eq!(results[1]["diagnostics"][0]["line"], 2);
+        // Positions refer to the whole input
+        assert_eq!(results[2]["input"], "x 1 -");
+        assert_eq!(results[2]["diagnostics"][0]["column"], 3);
+
+        let options = parse_args(&args(&["-", "--format", "json", "--from", "prefix"]))
+            .expect("should parse");
+        let result = convert("+ 5", &options).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&result).expect("valid JSON");
+        assert_eq!(value["diagnostics"].as_array().unwrap().len(), 1);
+
+        let err =
+            parse_args(&args(&["-", "--format", "json", "--leftovers", "comma"])).unwrap_err();
+        assert!(err.contains("--format json cannot be combined with --leftovers"));
+    }
+
+    #[test]
+    fn test_convert_emit_dot_error() {
+        let result = convert("5 +", &emit_options(Emit::Dot));
+        assert!(result.unwrap_err().contains("Too few operands"));
+    }
+
+    // Error test cases
+    #[test]
+    fn test_io_contract_error_01() {
+        assert!(convert_rpn_to_latex("2 3 ^").is_err());
+    }
+
+    #[test]
+    fn test_io_contract_error_02() {
+        assert!(convert_rpn_to_latex("2 3 ^ 4 *").is_err());
+    }
+
+    #[test]
+    fn test_io_contract_error_03() {
+        assert!(convert_rpn_to_latex("2 3 4 ^ ^").is_err());
+    }
+}
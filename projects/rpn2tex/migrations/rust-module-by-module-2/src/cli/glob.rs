@@ -0,0 +1,148 @@
+//! Expanding input patterns such as `src/**/*.rpn` into the files they
+//! match.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Returns `true` if `path` holds a wildcard, `*` or `?`.
+pub(crate) fn is_glob(path: &str) -> bool {
+    path.contains(['*', '?'])
+}
+
+/// Returns the leading directories of a glob that hold no wildcard.
+pub(crate) fn glob_base(pattern: &str) -> PathBuf {
+    let components: Vec<&str> = pattern.split('/').collect();
+    let literal = components
+        .iter()
+        .take(components.len() - 1)
+        .take_while(|component| !is_glob(component))
+        .count();
+    let base = components[..literal].join("/");
+    if base.is_empty() && pattern.starts_with('/') {
+        PathBuf::from("/")
+    } else {
+        PathBuf::from(base)
+    }
+}
+
+/// Lists the files matching a glob, in sorted order. `*` and `?` match
+/// within one path component, and a `**` component matches any number of
+/// directories.
+///
+/// # Errors
+///
+/// Returns an error if a directory on the way cannot be read.
+pub(crate) fn expand_glob(pattern: &str) -> io::Result<Vec<PathBuf>> {
+    let root = if pattern.starts_with('/') {
+        PathBuf::from("/")
+    } else {
+        PathBuf::new()
+    };
+    let components: Vec<&str> = pattern
+        .split('/')
+        .filter(|component| !component.is_empty() && *component != ".")
+        .collect();
+    let mut files = Vec::new();
+    walk_glob(&root, &components, &mut files)?;
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// Adds the files under `path` matching the remaining glob `components`.
+fn walk_glob(path: &Path, components: &[&str], files: &mut Vec<PathBuf>) -> io::Result<()> {
+    let Some((&first, rest)) = components.split_first() else {
+        if path.is_file() {
+            files.push(path.to_path_buf());
+        }
+        return Ok(());
+    };
+
+    if first == "**" {
+        walk_glob(path, rest, files)?;
+        for entry in directory_entries(path)? {
+            if entry.is_dir() {
+                walk_glob(&entry, components, files)?;
+            }
+        }
+    } else if !is_glob(first) {
+        let next = path.join(first);
+        if next.exists() {
+            walk_glob(&next, rest, files)?;
+        }
+    } else {
+        for entry in directory_entries(path)? {
+            let name = entry.file_name().map(|name| name.to_string_lossy());
+            if name.is_some_and(|name| glob_matches(first, &name)) {
+                walk_glob(&entry, rest, files)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the paths of the entries of directory `path` (the current
+/// directory if empty), or none if it is not a directory.
+fn directory_entries(path: &Path) -> io::Result<Vec<PathBuf>> {
+    let dir = if path.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        path
+    };
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| path.join(entry.file_name())))
+        .collect()
+}
+
+/// Returns `true` if `name` matches a one-component glob `pattern`, where
+/// `*` matches any run of characters and `?` any one character.
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    // Backtrack to the most recent `*` on a mismatch
+    let (mut p, mut n) = (0, 0);
+    let mut star = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&ch| ch == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("*.rpn", "area.rpn"));
+        assert!(glob_matches("a?ea*", "area.rpn"));
+        assert!(glob_matches("*a*a*", "banana"));
+        assert!(!glob_matches("*.rpn", "area.tex"));
+        assert!(!glob_matches("?", ""));
+        assert!(glob_matches("*", ""));
+    }
+
+    #[test]
+    fn test_glob_base() {
+        assert_eq!(glob_base("src/**/*.rpn"), PathBuf::from("src"));
+        assert_eq!(glob_base("src/a*/b/*.rpn"), PathBuf::from("src"));
+        assert_eq!(glob_base("*.rpn"), PathBuf::from(""));
+        assert_eq!(glob_base("/data/*.rpn"), PathBuf::from("/data"));
+    }
+}
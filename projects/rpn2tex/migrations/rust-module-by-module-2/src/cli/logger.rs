@@ -0,0 +1,30 @@
+//! The logger printing records to stderr, filtered by `-v` and `-q`.
+
+use log::{Level, Log, Metadata, Record};
+
+/// Prints log records to stderr, each prefixed with its level.
+pub(crate) struct StderrLogger;
+
+/// The logger installed by `run`, filtered by `-v` and `-q`.
+pub(crate) static STDERR_LOGGER: StderrLogger = StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if self.enabled(record.metadata()) {
+            let label = match record.level() {
+                Level::Error => "Error",
+                Level::Warn => "Warning",
+                Level::Info => "Info",
+                Level::Debug => "Debug",
+                Level::Trace => "Trace",
+            };
+            eprintln!("{}: {}", label, record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
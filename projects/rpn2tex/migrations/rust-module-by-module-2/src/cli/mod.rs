@@ -0,0 +1,18 @@
+//! The modules of the command-line tool, each one part of what `run` does.
+
+pub(crate) mod args;
+pub(crate) mod batch;
+pub(crate) mod commands;
+pub(crate) mod config;
+pub(crate) mod convert;
+pub(crate) mod files;
+pub(crate) mod glob;
+pub(crate) mod logger;
+pub(crate) mod options;
+#[cfg(feature = "pdf")]
+pub(crate) mod pdf;
+#[cfg(feature = "pdf")]
+pub(crate) mod preview;
+pub(crate) mod records;
+#[cfg(feature = "readline")]
+pub(crate) mod repl;
@@ -0,0 +1,702 @@
+//! The settings of one run of the command-line tool, and the choices its
+//! flags select from.
+
+use crate::cli::batch::BatchCache;
+use log::LevelFilter;
+use rpn2tex::ast::Expr;
+use rpn2tex::codegen::CodeGenerator;
+use rpn2tex::context::ConTeXtGenerator;
+use rpn2tex::difficulty::difficulty;
+use rpn2tex::error::ErrorFormatter;
+use rpn2tex::eval::Evaluator;
+use rpn2tex::infix::InfixGenerator;
+use rpn2tex::latex::{
+    escape, FracStyle, LaTeXGenerator, MathEnvironment, MulStyle, ParenSizing, SpacingStyle,
+};
+use rpn2tex::limits::Limits;
+use rpn2tex::macros::MacroTable;
+use rpn2tex::markdown::{MarkdownFlavor, MarkdownGenerator};
+use rpn2tex::mathml::ContentMathMLGenerator;
+use rpn2tex::notation::{Notation, NotationGenerator};
+use rpn2tex::number_format::{DecimalSeparator, NumberFormat, Precision, ThousandsSeparator};
+use rpn2tex::pipeline::{Compiler, InputNotation};
+use rpn2tex::python::PythonGenerator;
+use rpn2tex::wolfram::{WolframGenerator, WolframStyle};
+use rpn2tex::worksheet::WorksheetGenerator;
+use std::env;
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
+
+/// The artifact produced by the CLI, selected with `--emit`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Emit {
+    /// The converted expression, rendered in the selected `--format` (the default)
+    Output,
+    /// Graphviz DOT rendering of the AST
+    Dot,
+    /// Indented tree listing of the AST, with positions
+    Ast,
+    /// JSON serialization of the AST
+    AstJson,
+    /// The LaTeX packages the output needs, one per line
+    Packages,
+    /// The lexer's tokens, as a table or, with `--format json`, as JSON
+    Tokens,
+}
+
+impl Emit {
+    /// Parses the value of an `--emit` argument.
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        match value {
+            "output" => Some(Self::Output),
+            "dot" => Some(Self::Dot),
+            "ast" => Some(Self::Ast),
+            "ast-json" => Some(Self::AstJson),
+            "packages" => Some(Self::Packages),
+            "tokens" => Some(Self::Tokens),
+            _ => None,
+        }
+    }
+}
+
+/// The notation the expression is rendered in, selected with `--format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Format {
+    /// LaTeX math (the default)
+    Latex,
+    /// Polish (prefix) notation
+    Prefix,
+    /// Reverse Polish (postfix) notation, normalized
+    Rpn,
+    /// Infix notation with minimal parentheses
+    Infix,
+    /// Content MathML markup
+    ContentMathml,
+    /// Wolfram Language `FullForm` expression
+    Wolfram,
+    /// Wolfram Language infix expression
+    WolframInfix,
+    /// Evaluable Python expression
+    Python,
+    /// ConTeXt inline math
+    Context,
+    /// Markdown math snippet, delimited for `--markdown-flavor`
+    Markdown,
+    /// JSON holding each expression's input, LaTeX, AST and diagnostics, or
+    /// with `--emit tokens` the token list
+    Json,
+    /// The input's CSV rows with a LaTeX column added
+    Csv,
+}
+
+impl Format {
+    /// Parses the value of a `--format` argument.
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        match value {
+            "latex" => Some(Self::Latex),
+            "prefix" => Some(Self::Prefix),
+            "rpn" => Some(Self::Rpn),
+            "infix" => Some(Self::Infix),
+            "content-mathml" => Some(Self::ContentMathml),
+            "wolfram" => Some(Self::Wolfram),
+            "wolfram-infix" => Some(Self::WolframInfix),
+            "python" => Some(Self::Python),
+            "context" => Some(Self::Context),
+            "markdown" => Some(Self::Markdown),
+            "json" => Some(Self::Json),
+            "csv" => Some(Self::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// What to do with values left over on the stack, selected with `--leftovers`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Leftovers {
+    /// Report them as a missing-operator error (the default)
+    Error,
+    /// Convert each separately, outputs separated by ", "
+    Comma,
+    /// Convert each separately, one output per line
+    Newline,
+}
+
+impl Leftovers {
+    /// Parses the value of a `--leftovers` argument.
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        match value {
+            "error" => Some(Self::Error),
+            "comma" => Some(Self::Comma),
+            "newline" => Some(Self::Newline),
+            _ => None,
+        }
+    }
+
+    /// Returns the text placed between outputs, or `None` if leftover values
+    /// are an error.
+    pub(crate) const fn separator(self) -> Option<&'static str> {
+        match self {
+            Self::Error => None,
+            Self::Comma => Some(", "),
+            Self::Newline => Some("\n"),
+        }
+    }
+}
+
+/// How names bound by `let` statements in the input are output, selected
+/// with `--let-style`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LetStyle {
+    /// Replace each name by its definition (the default)
+    Inline,
+    /// Keep the names, as variables
+    Symbolic,
+}
+
+impl LetStyle {
+    /// Parses the value of a `--let-style` argument.
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        match value {
+            "inline" => Some(Self::Inline),
+            "symbolic" => Some(Self::Symbolic),
+            _ => None,
+        }
+    }
+}
+
+/// Whether error messages are colored, selected with `--color`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ColorChoice {
+    /// Color when stderr is a terminal and `NO_COLOR` is unset (the default)
+    Auto,
+    /// Always color
+    Always,
+    /// Never color
+    Never,
+}
+
+impl ColorChoice {
+    /// Parses the value of a `--color` argument.
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        match value {
+            "auto" => Some(Self::Auto),
+            "always" => Some(Self::Always),
+            "never" => Some(Self::Never),
+            _ => None,
+        }
+    }
+
+    /// Replaces `Auto` by `Always` or `Never`, by whether stderr is a
+    /// terminal and `NO_COLOR` is unset.
+    pub(crate) fn resolve(self) -> Self {
+        match self {
+            Self::Auto if io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none() => {
+                Self::Always
+            }
+            Self::Auto => Self::Never,
+            choice => choice,
+        }
+    }
+}
+
+/// Where the `--stats` summary of a `-d` or `--in-place` run is printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum StatsFormat {
+    /// A table on stderr
+    Text,
+    /// A JSON object on stdout
+    Json,
+}
+
+impl StatsFormat {
+    /// Parses the value of a `--stats` argument.
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        match value {
+            "text" => Some(Self::Text),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Terminal image protocol `--preview` draws with, selected with
+/// `--preview-protocol` or detected from the environment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PreviewProtocol {
+    /// The kitty graphics protocol, also spoken by Ghostty
+    Kitty,
+    /// iTerm2 inline images, also shown by WezTerm
+    Iterm2,
+    /// Sixel graphics, encoded by `img2sixel`
+    Sixel,
+}
+
+impl PreviewProtocol {
+    /// Parses the value of a `--preview-protocol` argument.
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        match value {
+            "kitty" => Some(Self::Kitty),
+            "iterm2" => Some(Self::Iterm2),
+            "sixel" => Some(Self::Sixel),
+            _ => None,
+        }
+    }
+
+    /// Returns the protocol of the terminal named by `TERM`,
+    /// `TERM_PROGRAM` and `KITTY_WINDOW_ID`, if it is known to show images.
+    #[cfg(feature = "pdf")]
+    pub(crate) fn detect() -> Option<Self> {
+        let term = env::var("TERM").unwrap_or_default();
+        let program = env::var("TERM_PROGRAM").unwrap_or_default();
+        if env::var_os("KITTY_WINDOW_ID").is_some()
+            || term.contains("kitty")
+            || term.contains("ghostty")
+        {
+            Some(Self::Kitty)
+        } else if matches!(program.as_str(), "iTerm.app" | "WezTerm") {
+            Some(Self::Iterm2)
+        } else if term.starts_with("foot") || term.starts_with("mlterm") {
+            Some(Self::Sixel)
+        } else {
+            None
+        }
+    }
+}
+
+/// Document kind whose embedded RPN is converted, selected with `--filter`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Filter {
+    /// Markdown with ```` ```rpn ```` blocks and `` `rpn:...` `` spans
+    Markdown,
+}
+
+impl Filter {
+    /// Parses the value of a `--filter` argument.
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        match value {
+            "markdown" => Some(Self::Markdown),
+            _ => None,
+        }
+    }
+}
+
+/// What the CLI generates, selected by a subcommand before the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Command {
+    /// Convert the input (no subcommand, or `convert`)
+    Convert,
+    /// `worksheet`: a document of numbered problems, one per expression
+    Worksheet,
+    /// `fmt`: rewrite the input files in canonical form
+    Fmt,
+    /// `lint`: report input that is redundant or probably a mistake
+    Lint,
+    /// `repl`: convert each expression entered at an interactive prompt
+    Repl,
+}
+
+impl Command {
+    /// Parses a subcommand name.
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        match value {
+            "worksheet" => Some(Self::Worksheet),
+            "convert" => Some(Self::Convert),
+            "fmt" => Some(Self::Fmt),
+            "lint" => Some(Self::Lint),
+            "repl" => Some(Self::Repl),
+            _ => None,
+        }
+    }
+}
+
+/// Command-line options controlling a single conversion.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Options {
+    /// What to generate from the input
+    pub(crate) command: Command,
+    /// Input file path, or "-" for stdin
+    pub(crate) input_path: String,
+    /// Further input paths, directories or globs, converted with `-d`
+    pub(crate) extra_inputs: Vec<String>,
+    /// Directory receiving one output file per input file
+    pub(crate) output_dir: Option<PathBuf>,
+    /// Write each input file's output beside it
+    pub(crate) in_place: bool,
+    /// Names of the files `-d` and `--in-place` write, with placeholders
+    pub(crate) output_pattern: Option<String>,
+    /// List what `-d` and `--in-place` would read and write, doing neither
+    pub(crate) dry_run: bool,
+    /// Summary printed after `-d` and `--in-place`, beyond the one-line count
+    pub(crate) stats: Option<StatsFormat>,
+    /// Convert NUL-separated input records one by one
+    pub(crate) null: bool,
+    /// Answer JSON requests, one per line, with `text/latex` display data
+    pub(crate) jupyter: bool,
+    /// Also place the output on the system clipboard
+    pub(crate) copy: bool,
+    /// PDF file the standalone document is compiled to
+    pub(crate) pdf_path: Option<PathBuf>,
+    /// Draw the rendered output in the terminal
+    pub(crate) preview: bool,
+    /// Image protocol of `--preview`, or `None` to detect it
+    pub(crate) preview_protocol: Option<PreviewProtocol>,
+    /// How many times `-v` was given, raising the log level
+    pub(crate) verbose: u8,
+    /// Print only errors to stderr
+    pub(crate) quiet: bool,
+    /// Output file path; stdout when absent
+    pub(crate) output_path: Option<PathBuf>,
+    /// Which artifact to produce
+    pub(crate) emit: Emit,
+    /// Notation the input is written in
+    pub(crate) from: InputNotation,
+    /// Notation used when emitting the converted expression
+    pub(crate) format: Format,
+    /// Math environment wrapping LaTeX output
+    pub(crate) environment: MathEnvironment,
+    /// Wrap LaTeX output in a complete standalone document
+    pub(crate) standalone: bool,
+    /// Restrict LaTeX output to what KaTeX can render
+    pub(crate) katex: bool,
+    /// Whitespace style of LaTeX output
+    pub(crate) spacing: SpacingStyle,
+    /// Sizing of grouping parentheses in LaTeX output
+    pub(crate) paren_sizing: ParenSizing,
+    /// Separator between groups of integer digits in LaTeX output
+    pub(crate) thousands: ThousandsSeparator,
+    /// Separator between integer and fractional digits in LaTeX output
+    pub(crate) decimal: DecimalSeparator,
+    /// Rounding of number literals in LaTeX output
+    pub(crate) precision: Precision,
+    /// Write `.5` as `0.5` in LaTeX output
+    pub(crate) leading_zero: bool,
+    /// Parenthesize negative right operands in LaTeX output
+    pub(crate) negative_parens: bool,
+    /// Wrap LaTeX number literals in siunitx's `\num{...}`
+    pub(crate) siunitx: bool,
+    /// Multiplication symbol in LaTeX output
+    pub(crate) mul_style: MulStyle,
+    /// Write LaTeX division as `\frac{...}{...}`
+    pub(crate) frac: bool,
+    /// Fraction command used with `--frac`
+    pub(crate) frac_style: FracStyle,
+    /// Report the numeric value of the expression on stderr
+    pub(crate) eval: bool,
+    /// Print the numeric value of the expression instead of converting it
+    pub(crate) evaluate: bool,
+    /// Evaluate with exact rationals instead of floating point
+    pub(crate) exact: bool,
+    /// Decimal places of values printed by `--eval` and `--evaluate`
+    pub(crate) value_places: Option<u8>,
+    /// Append `= value` to LaTeX output
+    pub(crate) show_result: bool,
+    /// Token replaced by a blank, counted from 1 in RPN order
+    pub(crate) blank: Option<usize>,
+    /// Accept variable names in the input
+    pub(crate) allow_vars: bool,
+    /// Read decimal digits from other scripts as 0-9
+    pub(crate) unicode_digits: bool,
+    /// Accept commas as thousands separators in input numbers
+    pub(crate) comma_groups: bool,
+    /// Split malformed numbers such as `5.` instead of rejecting them
+    pub(crate) lenient_numbers: bool,
+    /// Caps on input size, token count and expression depth
+    pub(crate) limits: Limits,
+    /// Values bound to variables for evaluation, in command-line order
+    pub(crate) bindings: Vec<(String, f64)>,
+    /// Output of names bound by `let` statements in the input
+    pub(crate) let_style: LetStyle,
+    /// File of macro definitions available to the input
+    pub(crate) prelude_path: Option<PathBuf>,
+    /// Macros read from the prelude, once loaded
+    pub(crate) prelude: MacroTable,
+    /// LaTeX file the output is inserted into
+    pub(crate) template_path: Option<PathBuf>,
+    /// The template's text, once loaded
+    pub(crate) template: Option<String>,
+    /// Marker in the template replaced by the output
+    pub(crate) placeholder: Option<String>,
+    /// Fold numeric subexpressions before generating output
+    pub(crate) fold_constants: bool,
+    /// Define repeated LaTeX subexpressions once as macros
+    pub(crate) macros: bool,
+    /// Convert each line or `;`-separated expression separately
+    pub(crate) multi: bool,
+    /// Lowest difficulty score of the expressions kept
+    pub(crate) min_difficulty: Option<usize>,
+    /// Highest difficulty score of the expressions kept
+    pub(crate) max_difficulty: Option<usize>,
+    /// List the `fmt` inputs that are not formatted instead of rewriting them
+    pub(crate) check: bool,
+    /// Deepest nesting of operations `lint` allows
+    pub(crate) max_nesting: Option<usize>,
+    /// Report every lexer and parser error rather than only the first
+    pub(crate) all_errors: bool,
+    /// Handling of values left over on the stack
+    pub(crate) leftovers: Leftovers,
+    /// Header of the `--format csv` column holding the expressions
+    pub(crate) csv_column: Option<String>,
+    /// Add a value column to `--format csv` output
+    pub(crate) csv_value: bool,
+    /// Markdown dialect used by `--format markdown` and `--filter markdown`
+    pub(crate) markdown_flavor: MarkdownFlavor,
+    /// Document kind the input is filtered as, converting only its RPN
+    pub(crate) filter: Option<Filter>,
+    /// Columns of `worksheet` problems, if set
+    pub(crate) columns: Option<u8>,
+    /// Space for the answer after each `worksheet` problem, if set
+    pub(crate) answer_space: Option<String>,
+    /// Heading of the `worksheet`
+    pub(crate) title: Option<String>,
+    /// Append the `worksheet`'s answer key on a new page
+    pub(crate) answer_key: bool,
+    /// File the `worksheet`'s answer key is written to as its own document
+    pub(crate) answer_key_path: Option<PathBuf>,
+    /// Whether error messages are colored; `Auto` is resolved by `run`
+    pub(crate) color: ColorChoice,
+    /// Render error messages with miette's graphical report handler
+    pub(crate) fancy_errors: bool,
+    /// Output already generated by a `-d` or `--in-place` run, shared by its
+    /// files
+    pub(crate) cache: Option<BatchCache>,
+}
+
+impl Options {
+    /// Creates options with default settings for the given input path.
+    pub(crate) fn new(input_path: impl Into<String>) -> Self {
+        Self {
+            command: Command::Convert,
+            input_path: input_path.into(),
+            extra_inputs: Vec::new(),
+            output_dir: None,
+            in_place: false,
+            output_pattern: None,
+            dry_run: false,
+            stats: None,
+            null: false,
+            jupyter: false,
+            copy: false,
+            pdf_path: None,
+            preview: false,
+            preview_protocol: None,
+            verbose: 0,
+            quiet: false,
+            output_path: None,
+            emit: Emit::Output,
+            from: InputNotation::Rpn,
+            format: Format::Latex,
+            environment: MathEnvironment::Inline,
+            standalone: false,
+            katex: false,
+            spacing: SpacingStyle::Spaced,
+            paren_sizing: ParenSizing::Fixed,
+            thousands: ThousandsSeparator::None,
+            decimal: DecimalSeparator::Point,
+            precision: Precision::Exact,
+            leading_zero: false,
+            negative_parens: true,
+            siunitx: false,
+            mul_style: MulStyle::Times,
+            frac: false,
+            frac_style: FracStyle::Frac,
+            eval: false,
+            evaluate: false,
+            exact: false,
+            value_places: None,
+            show_result: false,
+            blank: None,
+            allow_vars: false,
+            unicode_digits: false,
+            comma_groups: false,
+            lenient_numbers: false,
+            limits: Limits::new(),
+            bindings: Vec::new(),
+            let_style: LetStyle::Inline,
+            prelude_path: None,
+            prelude: MacroTable::new(),
+            template_path: None,
+            template: None,
+            placeholder: None,
+            fold_constants: false,
+            macros: false,
+            multi: false,
+            min_difficulty: None,
+            max_difficulty: None,
+            check: false,
+            max_nesting: None,
+            all_errors: false,
+            leftovers: Leftovers::Error,
+            csv_column: None,
+            csv_value: false,
+            markdown_flavor: MarkdownFlavor::GitHub,
+            filter: None,
+            columns: None,
+            answer_space: None,
+            title: None,
+            answer_key: false,
+            answer_key_path: None,
+            color: ColorChoice::Auto,
+            fancy_errors: false,
+            cache: None,
+        }
+    }
+
+    /// Builds an error formatter for `source`, colored with `--color always`.
+    pub(crate) fn error_formatter(&self, source: &str) -> ErrorFormatter {
+        let formatter = ErrorFormatter::new(source).with_color(self.color == ColorChoice::Always);
+        #[cfg(feature = "miette")]
+        let formatter = formatter.with_fancy(self.fancy_errors);
+        formatter
+    }
+
+    /// Builds the `worksheet` generator configured by these options.
+    pub(crate) fn worksheet_generator(&self) -> WorksheetGenerator {
+        let mut generator = WorksheetGenerator::new().with_generator(self.latex_generator());
+        if let Some(columns) = self.columns {
+            generator = generator.with_columns(columns);
+        }
+        if let Some(space) = &self.answer_space {
+            generator = generator.with_answer_space(space.as_str());
+        }
+        if let Some(title) = &self.title {
+            generator = generator.with_title(escape(title));
+        }
+        generator
+    }
+
+    /// Returns whether output is JSON, for `--format json` or `--emit
+    /// ast-json`, so errors are reported as JSON too.
+    pub(crate) fn json_output(&self) -> bool {
+        self.format == Format::Json || self.emit == Emit::AstJson
+    }
+
+    /// Returns whether expressions are evaluated, for `--eval`, `--evaluate`
+    /// or a `worksheet` answer key.
+    pub(crate) fn evaluates(&self) -> bool {
+        self.eval || self.evaluate || self.answer_key || self.answer_key_path.is_some()
+    }
+
+    /// Returns the most detailed log level printed, from `-v` and `-q`.
+    pub(crate) fn log_level(&self) -> LevelFilter {
+        if self.quiet {
+            return LevelFilter::Error;
+        }
+        match self.verbose {
+            0 => LevelFilter::Warn,
+            1 => LevelFilter::Info,
+            _ => LevelFilter::Debug,
+        }
+    }
+
+    /// Builds the backend for the selected `--format`.
+    pub(crate) fn code_generator(&self) -> Box<dyn CodeGenerator> {
+        match self.format {
+            Format::Latex => Box::new(self.latex_generator()),
+            Format::Prefix => Box::new(NotationGenerator::new(Notation::Prefix)),
+            Format::Rpn => Box::new(NotationGenerator::new(Notation::Postfix)),
+            Format::Infix => Box::new(InfixGenerator::new()),
+            Format::ContentMathml => Box::new(ContentMathMLGenerator::new()),
+            Format::Wolfram => Box::new(WolframGenerator::new(WolframStyle::FullForm)),
+            Format::WolframInfix => Box::new(WolframGenerator::new(WolframStyle::Infix)),
+            Format::Python => Box::new(PythonGenerator::new()),
+            Format::Context => Box::new(ConTeXtGenerator::new()),
+            Format::Markdown => Box::new(MarkdownGenerator::new(self.markdown_flavor)),
+            Format::Json => unreachable!("--format json output is built by convert_to_json"),
+            Format::Csv => unreachable!("--format csv output is built by convert_csv"),
+        }
+    }
+
+    /// Builds the number format configured by these options.
+    fn number_format(&self) -> NumberFormat {
+        NumberFormat::new()
+            .with_thousands(self.thousands)
+            .with_decimal(self.decimal)
+            .with_precision(self.precision)
+            .with_leading_zero(self.leading_zero)
+    }
+
+    /// Builds the compiler reading input in the `--from` notation with the
+    /// enabled input extensions, within the limits. Constants are folded
+    /// with [`fold`](Self::fold), after a program's names are substituted.
+    pub(crate) fn compiler(&self) -> Compiler {
+        Compiler::new()
+            .input(self.from)
+            .variables(self.allow_vars)
+            .unicode_digits(self.unicode_digits)
+            .comma_groups(self.comma_groups)
+            .lenient_numbers(self.lenient_numbers)
+            .limits(self.limits)
+    }
+
+    /// Folds the constants of `ast` if `--fold-constants` is set.
+    pub(crate) fn fold(&self, ast: Expr) -> Expr {
+        #[cfg(feature = "exact")]
+        if self.fold_constants {
+            return ast.fold_constants();
+        }
+        ast
+    }
+
+    /// Builds an evaluator with the `--let` bindings.
+    pub(crate) fn evaluator(&self) -> Evaluator {
+        self.bindings
+            .iter()
+            .fold(Evaluator::new(), |evaluator, (name, value)| {
+                evaluator.bind(name.as_str(), *value)
+            })
+    }
+
+    /// Returns whether `ast` scores within `--min-difficulty` and
+    /// `--max-difficulty`.
+    pub(crate) fn within_difficulty(&self, ast: &Expr) -> bool {
+        if self.min_difficulty.is_none() && self.max_difficulty.is_none() {
+            return true;
+        }
+        let score = difficulty(ast, &self.evaluator()).score() as usize;
+        self.min_difficulty.is_none_or(|min| score >= min)
+            && self.max_difficulty.is_none_or(|max| score <= max)
+    }
+
+    /// Builds the LaTeX generator configured by these options.
+    pub(crate) fn latex_generator(&self) -> LaTeXGenerator {
+        LaTeXGenerator::builder()
+            .environment(self.environment)
+            .katex(self.katex)
+            .spacing(self.spacing)
+            .paren_sizing(self.paren_sizing)
+            .number_format(self.number_format())
+            .negative_parens(self.negative_parens)
+            .siunitx(self.siunitx)
+            .mul_style(self.mul_style)
+            .frac(self.frac)
+            .frac_style(self.frac_style)
+            .build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_emit_parse() {
+        assert_eq!(Emit::parse("output"), Some(Emit::Output));
+        assert_eq!(Emit::parse("dot"), Some(Emit::Dot));
+        assert_eq!(Emit::parse("ast"), Some(Emit::Ast));
+        assert_eq!(Emit::parse("svg"), None);
+    }
+
+    #[test]
+    fn test_format_parse() {
+        assert_eq!(Format::parse("latex"), Some(Format::Latex));
+        assert_eq!(Format::parse("prefix"), Some(Format::Prefix));
+        assert_eq!(Format::parse("rpn"), Some(Format::Rpn));
+        assert_eq!(Format::parse("content-mathml"), Some(Format::ContentMathml));
+        assert_eq!(Format::parse("wolfram"), Some(Format::Wolfram));
+        assert_eq!(Format::parse("python"), Some(Format::Python));
+        assert_eq!(Format::parse("context"), Some(Format::Context));
+        assert_eq!(Format::parse("markdown"), Some(Format::Markdown));
+        assert_eq!(Format::parse("infix"), Some(Format::Infix));
+        assert_eq!(Format::parse("postfix"), None);
+    }
+}
@@ -0,0 +1,104 @@
+//! Compiling `--pdf` documents with pdflatex.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// Command that compiles `--pdf` documents.
+const PDFLATEX: &str = "pdflatex";
+
+/// Compiles a standalone LaTeX document into a PDF at `path`, running
+/// pdflatex in a private scratch directory that is removed afterwards.
+///
+/// # Errors
+///
+/// Returns an error message if pdflatex cannot be run or reports an error,
+/// or the PDF cannot be written.
+pub(crate) fn compile_pdf(document: &str, path: &Path) -> Result<(), String> {
+    let dir = scratch_dir("rpn2tex-pdf-")?;
+    let compiled = run_pdflatex(document, dir.path())
+        .and_then(|pdf| fs::copy(pdf, path).map_err(|e| format!("Error writing output: {}", e)));
+    // Leftover auxiliary files are harmless, so a failed cleanup is ignored
+    let _ = dir.close();
+    compiled.map(|_| ())
+}
+
+/// Creates a directory named `prefix` and a random suffix in the system's
+/// temporary directory, readable only by the current user. It is created
+/// atomically, so a file or link planted under a predictable name cannot
+/// take its place.
+///
+/// # Errors
+///
+/// Returns an error message if the directory cannot be created.
+pub(crate) fn scratch_dir(prefix: &str) -> Result<tempfile::TempDir, String> {
+    let mut builder = tempfile::Builder::new();
+    builder.prefix(prefix);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        builder.permissions(fs::Permissions::from_mode(0o700));
+    }
+    builder
+        .tempdir()
+        .map_err(|e| format!("Error writing output: {}", e))
+}
+
+/// Runs pdflatex on `document` in `dir` and returns the path of the PDF.
+///
+/// # Errors
+///
+/// Returns an error message if pdflatex cannot be run or fails, with the
+/// errors from its log.
+pub(crate) fn run_pdflatex(document: &str, dir: &Path) -> Result<PathBuf, String> {
+    fs::write(dir.join("expression.tex"), document)
+        .map_err(|e| format!("Error writing output: {}", e))?;
+    let output = process::Command::new(PDFLATEX)
+        .args([
+            "-interaction=nonstopmode",
+            "-halt-on-error",
+            "expression.tex",
+        ])
+        .current_dir(dir)
+        .output()
+        .map_err(|e| format!("Error running {}: {}", PDFLATEX, e))?;
+    if !output.status.success() {
+        // TeX reports errors on stdout, on lines starting with '!'
+        let log = String::from_utf8_lossy(&output.stdout);
+        let errors: Vec<&str> = log.lines().filter(|line| line.starts_with('!')).collect();
+        return Err(format!("Error: {} failed\n{}", PDFLATEX, errors.join("\n")));
+    }
+    Ok(dir.join("expression.pdf"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_compile_pdf_reports_failure() {
+        // Fails whether or not pdflatex is installed, and leaves no PDF
+        let path = env::temp_dir().join(format!("rpn2tex-broken-{}.pdf", process::id()));
+        let document = "\\documentclass{article}\\begin{document}\\undefined\\end{document}";
+        let err = compile_pdf(document, &path).unwrap_err();
+        assert!(err.starts_with("Error"));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_scratch_dirs_are_private() {
+        let first = scratch_dir("rpn2tex-pdf-").unwrap();
+        let second = scratch_dir("rpn2tex-pdf-").unwrap();
+        assert_ne!(first.path(), second.path());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(first.path()).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+        let path = first.path().to_path_buf();
+        first.close().unwrap();
+        assert!(!path.exists());
+    }
+}
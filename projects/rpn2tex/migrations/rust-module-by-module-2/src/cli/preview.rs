@@ -0,0 +1,202 @@
+//! Showing `--preview` images in the terminal with the kitty or iTerm2
+//! image protocol.
+
+use crate::cli::options::{Options, PreviewProtocol};
+use crate::cli::pdf::{run_pdflatex, scratch_dir};
+use log::warn;
+use rpn2tex::latex::MathEnvironment;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// Resolution, in dots per inch, of `--preview` images.
+const PREVIEW_DPI: &str = "200";
+
+/// Size of the base64 chunks the kitty graphics protocol sends an image in.
+const KITTY_CHUNK: usize = 4096;
+
+/// Draws `output` on stderr for `--preview`: compiles it into a document,
+/// renders the page as a PNG with pdftoppm and sends it in the terminal's
+/// image protocol. Without a known protocol it only logs a warning.
+///
+/// # Errors
+///
+/// Returns an error message if compiling, rendering or encoding the image
+/// fails.
+pub(crate) fn show_preview(output: &str, options: &Options) -> Result<(), String> {
+    let Some(protocol) = options.preview_protocol.or_else(PreviewProtocol::detect) else {
+        warn!("The terminal's image protocol is unknown; choose one with --preview-protocol");
+        return Ok(());
+    };
+
+    let dir = scratch_dir("rpn2tex-preview-")?;
+    let image = run_pdflatex(&preview_document(output, options), dir.path())
+        .and_then(|pdf| render_png(&pdf, dir.path()))
+        .and_then(|png| encode_image(protocol, &png));
+    // Leftover auxiliary files are harmless, so a failed cleanup is ignored
+    let _ = dir.close();
+
+    let mut stderr = io::stderr().lock();
+    stderr
+        .write_all(&image?)
+        .and_then(|()| stderr.flush())
+        .map_err(|e| format!("Error writing preview: {}", e))
+}
+
+/// Returns a standalone document showing `output`, which already is one
+/// with `--standalone`.
+fn preview_document(output: &str, options: &Options) -> String {
+    if options.standalone {
+        return output.to_string();
+    }
+    let generator = options.latex_generator();
+    let mut document = if options.environment == MathEnvironment::Inline {
+        String::from("\\documentclass[border=2pt]{standalone}\n")
+    } else {
+        String::from("\\documentclass[border=2pt,varwidth]{standalone}\n")
+    };
+    for package in generator.required_packages() {
+        document.push_str(&format!("\\usepackage{{{}}}\n", package));
+    }
+    document.push_str(&format!(
+        "\\begin{{document}}\n{}\n\\end{{document}}\n",
+        output
+    ));
+    document
+}
+
+/// Renders the first page of `pdf` as a PNG in `dir` and returns its path.
+///
+/// # Errors
+///
+/// Returns an error message if pdftoppm cannot be run or fails.
+fn render_png(pdf: &Path, dir: &Path) -> Result<PathBuf, String> {
+    let status = process::Command::new("pdftoppm")
+        .args(["-png", "-singlefile", "-r", PREVIEW_DPI])
+        .arg(pdf)
+        .arg(dir.join("preview"))
+        .status()
+        .map_err(|e| format!("Error running pdftoppm: {}", e))?;
+    if !status.success() {
+        return Err(format!("Error: pdftoppm failed ({})", status));
+    }
+    Ok(dir.join("preview.png"))
+}
+
+/// Encodes the PNG at `png` as the escape sequences that draw it with
+/// `protocol`.
+///
+/// # Errors
+///
+/// Returns an error message if the image cannot be read or `img2sixel`
+/// fails.
+fn encode_image(protocol: PreviewProtocol, png: &Path) -> Result<Vec<u8>, String> {
+    if protocol == PreviewProtocol::Sixel {
+        let output = process::Command::new("img2sixel")
+            .arg(png)
+            .output()
+            .map_err(|e| format!("Error running img2sixel: {}", e))?;
+        if !output.status.success() {
+            return Err(format!("Error: img2sixel failed ({})", output.status));
+        }
+        return Ok(output.stdout);
+    }
+
+    let bytes = fs::read(png).map_err(|e| format!("Error reading preview: {}", e))?;
+    Ok(match protocol {
+        PreviewProtocol::Kitty => kitty_image(&bytes),
+        _ => iterm2_image(&bytes),
+    }
+    .into_bytes())
+}
+
+/// Returns the kitty graphics protocol commands that draw a PNG at the
+/// cursor, its base64 data split into chunks.
+fn kitty_image(png: &[u8]) -> String {
+    let data = base64(png);
+    let chunks: Vec<&str> = data
+        .as_bytes()
+        .chunks(KITTY_CHUNK)
+        .map(|chunk| std::str::from_utf8(chunk).unwrap_or_default())
+        .collect();
+    let mut commands = String::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        // m=1 announces more chunks; the first also says what to do
+        let more = u8::from(i + 1 < chunks.len());
+        let control = if i == 0 {
+            format!("f=100,a=T,m={}", more)
+        } else {
+            format!("m={}", more)
+        };
+        commands.push_str(&format!("\x1b_G{};{}\x1b\\", control, chunk));
+    }
+    commands.push('\n');
+    commands
+}
+
+/// Returns the iTerm2 escape sequence that draws a PNG inline.
+fn iterm2_image(png: &[u8]) -> String {
+    format!(
+        "\x1b]1337;File=inline=1;size={}:{}\x07\n",
+        png.len(),
+        base64(png)
+    )
+}
+
+/// Encodes `bytes` in standard base64, with padding.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+            group | u32::from(byte) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(char::from(ALPHABET[(group >> (18 - 6 * i)) as usize & 63]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::args::{args, parse_args};
+
+    #[test]
+    fn test_preview_encoding() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64(&[0xfb, 0xff]), "+/8=");
+
+        assert_eq!(
+            iterm2_image(b"fo"),
+            "\x1b]1337;File=inline=1;size=2:Zm8=\x07\n"
+        );
+        assert_eq!(kitty_image(b"fo"), "\x1b_Gf=100,a=T,m=0;Zm8=\x1b\\\n");
+        // Large images are sent in chunks, all but the last marked m=1
+        let commands = kitty_image(&[0; KITTY_CHUNK]);
+        assert_eq!(commands.matches("\x1b_G").count(), 2);
+        assert!(commands.starts_with("\x1b_Gf=100,a=T,m=1;"));
+        assert!(commands.contains("\x1b\\\x1b_Gm=0;"));
+    }
+
+    #[test]
+    fn test_preview_document() {
+        let options = parse_args(&args(&["-", "--siunitx"])).expect("should parse");
+        assert_eq!(
+            preview_document("$\\num{5}$", &options),
+            "\\documentclass[border=2pt]{standalone}\n\\usepackage{siunitx}\n\
+             \\begin{document}\n$\\num{5}$\n\\end{document}\n"
+        );
+        let options = parse_args(&args(&["-", "--standalone"])).expect("should parse");
+        assert_eq!(preview_document("document", &options), "document");
+    }
+}
@@ -0,0 +1,214 @@
+//! Converting `--null` records and `--jupyter` requests as they arrive.
+
+use crate::cli::convert::{convert, Failure};
+use crate::cli::files::decode_input;
+use crate::cli::options::{Format, Options};
+use serde::Deserialize;
+use std::fs;
+use std::io::{self, BufRead, Write};
+
+/// Converts the NUL-separated records of the input for `--null`, or answers
+/// its `--jupyter` requests, writing to the output file or stdout.
+///
+/// Returns the exit code: 1 if reading or writing fails or any `--null`
+/// record failed to convert. A failed `--jupyter` request is answered with
+/// its error, which does not fail the run.
+pub(crate) fn convert_records(options: &Options) -> i32 {
+    let input: Box<dyn BufRead> = if options.input_path == "-" {
+        Box::new(io::stdin().lock())
+    } else {
+        match fs::File::open(&options.input_path) {
+            Ok(file) => Box::new(io::BufReader::new(file)),
+            Err(e) => {
+                eprintln!("Error reading input: {}", e);
+                return 1;
+            }
+        }
+    };
+    let output: Box<dyn Write> = match &options.output_path {
+        Some(path) => match fs::File::create(path) {
+            Ok(file) => Box::new(io::BufWriter::new(file)),
+            Err(e) => {
+                eprintln!("Error writing output: {}", e);
+                return 1;
+            }
+        },
+        None => Box::new(io::stdout().lock()),
+    };
+
+    let streamed = if options.jupyter {
+        serve_jupyter(input, output, options).map(|()| 0)
+    } else {
+        stream_records(input, output, options)
+    };
+    match streamed {
+        Ok(failed) => i32::from(failed > 0),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
+/// Converts each NUL-terminated record of `input` as it arrives, writing its
+/// output followed by a NUL, so the outputs line up with the records. A
+/// record that fails has an empty output and its error printed to stderr.
+///
+/// Returns the number of records that failed.
+///
+/// # Errors
+///
+/// Returns an error if reading the input or writing the output fails.
+fn stream_records(
+    input: impl BufRead,
+    mut output: impl Write,
+    options: &Options,
+) -> io::Result<usize> {
+    let mut failed = 0;
+    for (index, record) in input.split(b'\0').enumerate() {
+        let converted = decode_input(&record?, options)
+            .map_err(|e| Failure::new("decode", e))
+            .and_then(|source| convert(&source, options));
+        match converted {
+            Ok(converted) => output.write_all(converted.as_bytes())?,
+            Err(e) => {
+                failed += 1;
+                eprintln!("failed: record {}\n{}", index + 1, e);
+            }
+        }
+        output.write_all(b"\0")?;
+        output.flush()?;
+    }
+    Ok(failed)
+}
+
+/// A `--jupyter` request: the RPN input of a notebook cell.
+#[derive(Debug, Deserialize)]
+struct JupyterRequest {
+    /// Identifier copied into the response, so requests can be pipelined
+    #[serde(default)]
+    id: serde_json::Value,
+    /// The RPN input to convert
+    code: String,
+}
+
+/// Answers each JSON request line of `input` as it arrives with one JSON
+/// response line: `{"id": ..., "data": {"text/latex": ..., "text/plain":
+/// ...}}`, a display data bundle a notebook can show as is, with the
+/// expression in infix notation as its plain text, or `{"id": ...,
+/// "error": ...}`. Blank lines are skipped.
+///
+/// # Errors
+///
+/// Returns an error if reading the input or writing the output fails.
+fn serve_jupyter(input: impl BufRead, mut output: impl Write, options: &Options) -> io::Result<()> {
+    let plain = Options {
+        format: Format::Infix,
+        ..options.clone()
+    };
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<JupyterRequest>(&line) {
+            Ok(request) => {
+                let converted = convert(&request.code, options)
+                    .and_then(|latex| Ok((latex, convert(&request.code, &plain)?)));
+                match converted {
+                    Ok((latex, text)) => serde_json::json!({
+                        "id": request.id,
+                        "data": { "text/latex": latex, "text/plain": text },
+                    }),
+                    Err(e) => serde_json::json!({ "id": request.id, "error": e.to_string() }),
+                }
+            }
+            Err(e) => {
+                let error = format!("Error: Malformed request: {}", e);
+                serde_json::json!({ "id": null, "error": error })
+            }
+        };
+        writeln!(output, "{}", response)?;
+        output.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::args::{args, parse_args};
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn test_serve_jupyter() {
+        let options =
+            parse_args(&args(&["-", "--jupyter", "--color", "never"])).expect("should parse");
+        let requests =
+            "{\"id\": 1, \"code\": \"5 3 + 2 *\\n\"}\n\n{\"id\": \"b\", \"code\": \"+\"}\n\
+                        not json\n";
+        let mut output = Vec::new();
+        serve_jupyter(requests.as_bytes(), &mut output, &options).unwrap();
+
+        let responses: Vec<serde_json::Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("valid JSON"))
+            .collect();
+        assert_eq!(responses.len(), 3);
+        assert_eq!(
+            responses[0],
+            serde_json::json!({
+                "id": 1,
+                "data": { "text/latex": r"$( 5 + 3 ) \times 2$", "text/plain": "(5 + 3) * 2" },
+            })
+        );
+        assert_eq!(responses[1]["id"], "b");
+        assert!(responses[1]["error"]
+            .as_str()
+            .unwrap()
+            .contains("Too few operands"));
+        assert_eq!(responses[2]["id"], serde_json::Value::Null);
+        assert!(responses[2]["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("Error: Malformed request"));
+
+        // Failed requests are answered, and do not fail the run
+        let root = env::temp_dir().join(format!("rpn2tex-jupyter-{}", process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("requests.jsonl"), requests).unwrap();
+        let options = Options {
+            input_path: root.join("requests.jsonl").to_string_lossy().into_owned(),
+            output_path: Some(root.join("responses.jsonl")),
+            ..options
+        };
+        assert_eq!(convert_records(&options), 0);
+        let responses = fs::read_to_string(root.join("responses.jsonl")).unwrap();
+        assert_eq!(responses.lines().count(), 3);
+        fs::remove_dir_all(&root).unwrap();
+
+        let err = parse_args(&args(&["-", "--jupyter", "--format", "python"])).unwrap_err();
+        assert!(err.contains("--jupyter answers with inline LaTeX"));
+    }
+
+    #[test]
+    fn test_stream_records() {
+        let options = parse_args(&args(&["-", "--null"])).expect("should parse");
+        assert!(options.null);
+        let mut output = Vec::new();
+        let failed =
+            stream_records(&b"5 3 +\x002 +\x00x\ny *\x00"[..], &mut output, &options).unwrap();
+        assert_eq!(failed, 2);
+        assert_eq!(output, b"$5 + 3$\x00\x00\x00");
+
+        // A last record without a terminator is still converted
+        let mut output = Vec::new();
+        stream_records(&b"1 2 *\x004 2 /"[..], &mut output, &options).unwrap();
+        assert_eq!(output, b"$1 \\times 2$\x00$4 \\div 2$\x00");
+
+        let err = parse_args(&args(&["a.rpn", "--null", "--in-place"])).unwrap_err();
+        assert!(err.contains("--null cannot be combined with -d or --in-place"));
+    }
+}
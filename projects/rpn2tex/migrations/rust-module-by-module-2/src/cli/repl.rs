@@ -0,0 +1,120 @@
+//! The editing prompt of the `repl` command.
+
+use crate::cli::config::user_config_dir;
+use crate::cli::convert::{convert, is_program};
+use crate::cli::options::{Leftovers, Options};
+use log::warn;
+use rpn2tex::pipeline::InputNotation;
+use std::fs;
+
+/// Name of the `repl` history file in the user's configuration directory.
+const HISTORY_FILE: &str = "rpn2tex_history";
+
+/// Runs the `repl` prompt: reads expressions with line editing, history
+/// (kept between sessions) and Ctrl-R search, and prints each one's output
+/// or errors. An RPN expression leaving several values on the stack
+/// continues on the next line, at a `...` prompt, until its operators are
+/// entered; Ctrl-C discards it.
+///
+/// Returns the exit code: 1 if the terminal cannot be read.
+pub(crate) fn run_repl(options: &Options) -> i32 {
+    use rustyline::error::ReadlineError;
+
+    let mut editor = match rustyline::DefaultEditor::new() {
+        Ok(editor) => editor,
+        Err(e) => {
+            eprintln!("Error starting the prompt: {}", e);
+            return 1;
+        }
+    };
+    let history = user_config_dir().map(|dir| dir.join(HISTORY_FILE));
+    if let Some(path) = &history {
+        // A first session has no history yet
+        let _ = editor.load_history(path);
+    }
+
+    let mut pending = String::new();
+    let status = loop {
+        let prompt = if pending.is_empty() { "rpn> " } else { "...> " };
+        match editor.readline(prompt) {
+            Ok(line) => {
+                if !pending.is_empty() {
+                    pending.push('\n');
+                }
+                pending.push_str(&line);
+                if pending.trim().is_empty() {
+                    pending.clear();
+                    continue;
+                }
+                if awaits_operators(&pending, options) {
+                    continue;
+                }
+                let _ = editor.add_history_entry(pending.as_str());
+                match convert(&pending, options) {
+                    Ok(output) if output.ends_with('\n') => print!("{}", output),
+                    Ok(output) => println!("{}", output),
+                    Err(e) => eprint!("{}", e),
+                }
+                pending.clear();
+            }
+            Err(ReadlineError::Interrupted) => pending.clear(),
+            Err(ReadlineError::Eof) => break 0,
+            Err(e) => {
+                eprintln!("Error reading input: {}", e);
+                break 1;
+            }
+        }
+    };
+
+    if let Some(path) = &history {
+        let saved = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .map_err(|e| e.to_string())
+            .and_then(|()| editor.save_history(path).map_err(|e| e.to_string()));
+        if let Err(e) = saved {
+            warn!("Could not save the history to {}: {}", path.display(), e);
+        }
+    }
+    status
+}
+
+/// Returns whether `source` is an RPN expression that leaves more than one
+/// value on the stack, so the `repl` prompt waits for the operators that
+/// combine them rather than reporting it.
+fn awaits_operators(source: &str, options: &Options) -> bool {
+    use rpn2tex::error_code::ErrorCode;
+
+    if options.from != InputNotation::Rpn
+        || options.multi
+        || options.leftovers != Leftovers::Error
+        || is_program(source, options)
+    {
+        return false;
+    }
+    matches!(
+        options.compiler().parse(source),
+        Err(error) if error.code() == Some(ErrorCode::MissingOperator)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repl_continuation() {
+        let options = Options::new("-");
+        assert!(awaits_operators("5 3", &options));
+        assert!(awaits_operators("5 3\n2 +", &options));
+        assert!(!awaits_operators("5 3\n2 + *", &options));
+        // Errors are reported at once rather than continued
+        assert!(!awaits_operators("5 +", &options));
+        assert!(!awaits_operators("5 @ 3", &options));
+        let options = Options {
+            from: InputNotation::Infix,
+            ..options
+        };
+        assert!(!awaits_operators("5 3", &options));
+    }
+}
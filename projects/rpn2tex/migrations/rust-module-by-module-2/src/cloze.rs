@@ -0,0 +1,179 @@
+//! Fill-in-the-blank exercises.
+//!
+//! A cloze hides one token of an expression, a number, variable or
+//! operator, behind an underlined blank in its LaTeX, and keeps the hidden
+//! token apart so it can serve as the answer. Tokens are counted from 1 in
+//! RPN order, the order they are written in the input.
+
+use crate::ast::Expr;
+use crate::latex::LaTeXGenerator;
+
+/// An expression with one token blanked out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cloze {
+    /// The expression's LaTeX, with the blank in place of the token
+    pub latex: String,
+    /// The hidden token, as LaTeX
+    pub hidden: String,
+}
+
+/// Generates fill-in-the-blank LaTeX from AST expressions.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::cloze::ClozeGenerator;
+/// use rpn2tex::lexer::Lexer;
+/// use rpn2tex::parser::Parser;
+///
+/// let ast = Parser::new(Lexer::new("5 3 + 2 *").tokenize().unwrap()).parse().unwrap();
+/// let generator = ClozeGenerator::new();
+///
+/// let cloze = generator.generate(&ast, 2).unwrap();
+/// assert_eq!(cloze.latex, "$( 5 + \\underline{\\hspace{1cm}} ) \\times 2$");
+/// assert_eq!(cloze.hidden, "3");
+///
+/// let cloze = generator.generate(&ast, 5).unwrap();
+/// assert_eq!(cloze.latex, "$( 5 + 3 ) \\underline{\\hspace{1cm}} 2$");
+/// assert_eq!(cloze.hidden, "\\times");
+///
+/// assert!(generator.generate(&ast, 6).is_none());
+/// ```
+#[derive(Debug, Clone)]
+pub struct ClozeGenerator {
+    /// Renders the expression around the blank
+    math: LaTeXGenerator,
+    /// Length of the blank, as a LaTeX length
+    width: String,
+}
+
+impl ClozeGenerator {
+    /// Creates a generator writing 1cm blanks in the default LaTeX output.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            math: LaTeXGenerator::new(),
+            width: "1cm".to_string(),
+        }
+    }
+
+    /// Returns this generator rendering expressions with `generator`.
+    #[must_use]
+    pub fn with_generator(mut self, generator: LaTeXGenerator) -> Self {
+        self.math = generator;
+        self
+    }
+
+    /// Returns this generator writing blanks `width` long, a LaTeX length
+    /// such as `2em`.
+    #[must_use]
+    pub fn with_width(mut self, width: impl Into<String>) -> Self {
+        self.width = width.into();
+        self
+    }
+
+    /// Returns the length of the blank.
+    #[must_use]
+    pub fn width(&self) -> &str {
+        &self.width
+    }
+
+    /// Generates the expression with its `token`-th token, counted from 1
+    /// in RPN order, replaced by a blank, in the generator's math
+    /// environment.
+    ///
+    /// A hidden division is written inline even when divisions are
+    /// fractions, so the blank has a place on the line.
+    ///
+    /// Returns `None` if the expression has fewer than `token` tokens, or
+    /// `token` is 0.
+    #[must_use]
+    pub fn generate(&self, ast: &Expr, token: usize) -> Option<Cloze> {
+        let hidden = nth_token(ast, token.checked_sub(1)?)?;
+        let blank = format!("\\underline{{\\hspace{{{}}}}}", self.width);
+        let latex = self.math.render_blanked(ast, hidden, &blank);
+        Some(Cloze {
+            latex: self.math.wrap_math(&latex),
+            hidden: match hidden {
+                Expr::BinaryOp { operator, .. } => self.math.operator_tex(operator),
+                Expr::Apply { operator, .. } => LaTeXGenerator::operator_name(operator),
+                _ => self.math.render_math(hidden),
+            },
+        })
+    }
+}
+
+impl Default for ClozeGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the node of the `index`-th token of `ast`, counted from 0 in RPN
+/// order: each operation after its operands.
+fn nth_token(ast: &Expr, index: usize) -> Option<&Expr> {
+    fn walk<'a>(node: &'a Expr, index: usize, seen: &mut usize) -> Option<&'a Expr> {
+        let operands: Vec<&Expr> = match node {
+            Expr::Number { .. } | Expr::Variable { .. } => Vec::new(),
+            Expr::BinaryOp { left, right, .. } => vec![left, right],
+            Expr::Apply { operands, .. } => operands.iter().collect(),
+        };
+        for operand in operands {
+            if let Some(found) = walk(operand, index, seen) {
+                return Some(found);
+            }
+        }
+        *seen += 1;
+        (*seen == index + 1).then_some(node)
+    }
+
+    walk(ast, index, &mut 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::latex::{MathEnvironment, MulStyle};
+    use crate::test_util::parse;
+
+    #[test]
+    fn test_every_token_in_rpn_order() {
+        let ast = parse("x 2 3 * -");
+        let generator = ClozeGenerator::new().with_width("2em");
+        let hidden: Vec<_> = (1..=5)
+            .map(|token| generator.generate(&ast, token).unwrap().hidden)
+            .collect();
+        assert_eq!(hidden, ["x", "2", "3", "\\times", "-"]);
+        assert_eq!(
+            generator.generate(&ast, 1).unwrap().latex,
+            "$\\underline{\\hspace{2em}} - 2 \\times 3$"
+        );
+        assert!(generator.generate(&ast, 0).is_none());
+    }
+
+    #[test]
+    fn test_only_the_chosen_copy_is_hidden() {
+        let cloze = ClozeGenerator::new().generate(&parse("2 2 +"), 2).unwrap();
+        assert_eq!(cloze.latex, "$2 + \\underline{\\hspace{1cm}}$");
+    }
+
+    #[test]
+    fn test_generator_settings() {
+        let generator = LaTeXGenerator::builder()
+            .environment(MathEnvironment::Bare)
+            .mul_style(MulStyle::Cdot)
+            .frac(true)
+            .build();
+        let generator = ClozeGenerator::new().with_generator(generator);
+        let ast = parse("6 2 / 3 *");
+        assert_eq!(
+            generator.generate(&ast, 2).unwrap().latex,
+            "\\frac{6}{\\underline{\\hspace{1cm}}} \\cdot 3"
+        );
+        // A hidden division is written inline
+        let cloze = generator.generate(&ast, 3).unwrap();
+        assert_eq!(cloze.latex, "6 \\underline{\\hspace{1cm}} 2 \\cdot 3");
+        assert_eq!(cloze.hidden, "\\div");
+        assert_eq!(generator.generate(&ast, 5).unwrap().hidden, "\\cdot");
+    }
+}
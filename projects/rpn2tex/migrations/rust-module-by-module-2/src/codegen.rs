@@ -0,0 +1,173 @@
+//! The extension point shared by every output backend.
+//!
+//! Each generator in this crate implements [`CodeGenerator`], so callers can
+//! pick a backend at runtime and downstream crates can add their own without
+//! re-implementing the lexer or parser.
+
+use crate::ast::Expr;
+use crate::span::Span;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+
+/// Error type for expressions a backend cannot write.
+///
+/// Raised by [`CodeGenerator::try_generate`] when the target notation has
+/// no way to express part of the tree, such as a custom operator whose name
+/// is not a Python identifier. The span is that of the offending node.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::codegen::GenerationError;
+/// use rpn2tex::span::Span;
+///
+/// let error = GenerationError::new("Cannot call 'a&b'", Span::new(4, 7, 1, 5));
+/// assert_eq!(error.to_string(), "Line 1, column 5: Cannot call 'a&b'");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GenerationError {
+    /// Error description
+    message: String,
+    /// Where in the source the error occurred
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    span: Span,
+}
+
+impl GenerationError {
+    /// Creates a new generation error.
+    #[must_use]
+    pub fn new(message: impl Into<String>, span: Span) -> Self {
+        Self {
+            message: message.into(),
+            span,
+        }
+    }
+
+    /// Returns the error message.
+    #[must_use]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns where in the source the error occurred.
+    #[must_use]
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl fmt::Display for GenerationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Line {}, column {}: {}",
+            self.span.line, self.span.column, self.message
+        )
+    }
+}
+
+impl Error for GenerationError {}
+
+/// Renders an AST expression as text in some target notation.
+///
+/// # Examples
+///
+/// A downstream backend only needs to walk the [`Expr`] tree:
+///
+/// ```
+/// use rpn2tex::ast::Expr;
+/// use rpn2tex::codegen::CodeGenerator;
+/// use rpn2tex::latex::LaTeXGenerator;
+/// use rpn2tex::span::Span;
+///
+/// /// Writes every operation as a Lisp-style list.
+/// struct SExprGenerator;
+///
+/// impl CodeGenerator for SExprGenerator {
+///     fn generate(&self, expr: &Expr) -> String {
+///         match expr {
+///             Expr::Number { value, .. } => value.clone(),
+///             Expr::Variable { name, .. } => name.clone(),
+///             Expr::BinaryOp {
+///                 operator,
+///                 left,
+///                 right,
+///                 ..
+///             } => format!(
+///                 "({} {} {})",
+///                 operator,
+///                 self.generate(left),
+///                 self.generate(right)
+///             ),
+///             Expr::Apply {
+///                 operator, operands, ..
+///             } => {
+///                 let operands: Vec<String> = operands.iter().map(|o| self.generate(o)).collect();
+///                 format!("({} {})", operator, operands.join(" "))
+///             }
+///         }
+///     }
+/// }
+///
+/// let expr = Expr::BinaryOp {
+///     span: Span::new(4, 5, 1, 5),
+///     operator: "+".to_string(),
+///     left: Box::new(Expr::Number {
+///         span: Span::new(0, 1, 1, 1),
+///         value: "5".to_string(),
+///     }),
+///     right: Box::new(Expr::Number {
+///         span: Span::new(2, 3, 1, 3),
+///         value: "3".to_string(),
+///     }),
+/// };
+///
+/// let backends: Vec<Box<dyn CodeGenerator>> =
+///     vec![Box::new(LaTeXGenerator::new()), Box::new(SExprGenerator)];
+/// let outputs: Vec<String> = backends.iter().map(|b| b.generate(&expr)).collect();
+/// assert_eq!(outputs, ["$5 + 3$", "(+ 5 3)"]);
+/// ```
+pub trait CodeGenerator {
+    /// Generates output for the expression rooted at `expr`.
+    fn generate(&self, expr: &Expr) -> String;
+
+    /// Generates output for `expr` like [`generate`](Self::generate), or
+    /// reports the part of it the target notation cannot express.
+    /// [`Compiler::compile`](crate::pipeline::Compiler::compile) generates
+    /// through this method.
+    ///
+    /// The default accepts every expression.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`GenerationError`] if the backend cannot write `expr`.
+    fn try_generate(&self, expr: &Expr) -> Result<String, GenerationError> {
+        Ok(self.generate(expr))
+    }
+}
+
+/// A borrowed generator, such as a `&dyn CodeGenerator` chosen at runtime,
+/// can be wrapped like an owned one, as by a
+/// [`CompilerCache`](crate::cache::CompilerCache).
+impl<G: CodeGenerator + ?Sized> CodeGenerator for &G {
+    fn generate(&self, expr: &Expr) -> String {
+        (**self).generate(expr)
+    }
+
+    fn try_generate(&self, expr: &Expr) -> Result<String, GenerationError> {
+        (**self).try_generate(expr)
+    }
+}
+
+impl<G: CodeGenerator + ?Sized> CodeGenerator for Box<G> {
+    fn generate(&self, expr: &Expr) -> String {
+        (**self).generate(expr)
+    }
+
+    fn try_generate(&self, expr: &Expr) -> Result<String, GenerationError> {
+        (**self).try_generate(expr)
+    }
+}
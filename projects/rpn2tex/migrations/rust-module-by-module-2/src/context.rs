@@ -0,0 +1,134 @@
+//! ConTeXt math generation from Abstract Syntax Trees.
+//!
+//! ConTeXt shares TeX's math syntax, so the expression body is rendered by
+//! the LaTeX backend; only the surrounding math-mode commands differ.
+
+use crate::ast::Expr;
+use crate::codegen::CodeGenerator;
+use crate::latex::LaTeXGenerator;
+
+/// Converts AST expressions to ConTeXt math.
+///
+/// Inline output uses ConTeXt's `\m{...}` command; display output uses a
+/// `\startformula ... \stopformula` block.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::ast::Expr;
+/// use rpn2tex::context::ConTeXtGenerator;
+/// use rpn2tex::span::Span;
+///
+/// let expr = Expr::BinaryOp {
+///     span: Span::new(4, 5, 1, 5),
+///     operator: "*".to_string(),
+///     left: Box::new(Expr::Number {
+///         span: Span::new(0, 1, 1, 1),
+///         value: "4".to_string(),
+///     }),
+///     right: Box::new(Expr::Number {
+///         span: Span::new(2, 3, 1, 3),
+///         value: "7".to_string(),
+///     }),
+/// };
+///
+/// let generator = ConTeXtGenerator::new();
+/// assert_eq!(generator.generate(&expr), r"\m{4 \times 7}");
+///
+/// let display = ConTeXtGenerator::new().with_display(true);
+/// assert_eq!(
+///     display.generate(&expr),
+///     "\\startformula\n4 \\times 7\n\\stopformula"
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConTeXtGenerator {
+    /// Renders the math body shared with LaTeX
+    math: LaTeXGenerator,
+    /// Emit a display formula instead of inline math
+    display: bool,
+}
+
+impl ConTeXtGenerator {
+    /// Creates a new ConTeXt generator producing inline math.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::context::ConTeXtGenerator;
+    ///
+    /// let generator = ConTeXtGenerator::new();
+    /// assert!(!generator.display());
+    /// ```
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            math: LaTeXGenerator::new(),
+            display: false,
+        }
+    }
+
+    /// Returns this generator configured for display (`true`) or inline math.
+    #[must_use]
+    pub const fn with_display(mut self, display: bool) -> Self {
+        self.display = display;
+        self
+    }
+
+    /// Returns whether this generator produces display formulas.
+    #[must_use]
+    pub const fn display(&self) -> bool {
+        self.display
+    }
+
+    /// Generates ConTeXt source code from an AST expression.
+    ///
+    /// # Arguments
+    ///
+    /// * `ast` - The root expression node to convert
+    #[must_use]
+    pub fn generate(&self, ast: &Expr) -> String {
+        let inner = self.math.render_math(ast);
+        if self.display {
+            format!("\\startformula\n{}\n\\stopformula", inner)
+        } else {
+            format!("\\m{{{}}}", inner)
+        }
+    }
+}
+
+impl CodeGenerator for ConTeXtGenerator {
+    fn generate(&self, expr: &Expr) -> String {
+        ConTeXtGenerator::generate(self, expr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::parse;
+
+    #[test]
+    fn test_inline_number() {
+        assert_eq!(ConTeXtGenerator::new().generate(&parse("42")), r"\m{42}");
+    }
+
+    #[test]
+    fn test_inline_precedence_matches_latex() {
+        let ast = parse("10 2 / 3 + 4 *");
+        assert_eq!(
+            ConTeXtGenerator::new().generate(&ast),
+            r"\m{( 10 \div 2 + 3 ) \times 4}"
+        );
+    }
+
+    #[test]
+    fn test_display_formula() {
+        let generator = ConTeXtGenerator::new().with_display(true);
+        assert!(generator.display());
+        assert_eq!(
+            generator.generate(&parse("5 3 -")),
+            "\\startformula\n5 - 3\n\\stopformula"
+        );
+    }
+}
@@ -0,0 +1,118 @@
+//! Decimal literals compared by value.
+//!
+//! Number nodes keep their literal text. This module reads that text as
+//! digits, so literals can be checked, ordered and recognized as zero or
+//! one without the arbitrary-precision numbers of the `exact` feature.
+
+use std::cmp::Ordering;
+
+/// A decimal literal such as `-3.14`, read as its sign and digits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Decimal<'a> {
+    /// Whether the value is below zero; false for every spelling of zero
+    negative: bool,
+    /// Digits before the point, without leading zeros
+    integer: &'a str,
+    /// Digits after the point, without trailing zeros
+    fraction: &'a str,
+}
+
+impl<'a> Decimal<'a> {
+    /// Reads digits with an optional leading `-` and decimal point, such
+    /// as `-3.14`, `.5` or `2.`, or returns `None` for any other text.
+    pub(crate) fn parse(value: &'a str) -> Option<Self> {
+        let (negative, unsigned) = match value.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, value),
+        };
+        let (integer, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+        let digits = |text: &str| text.bytes().all(|byte| byte.is_ascii_digit());
+        if integer.is_empty() && fraction.is_empty() || !digits(integer) || !digits(fraction) {
+            return None;
+        }
+
+        let integer = integer.trim_start_matches('0');
+        let fraction = fraction.trim_end_matches('0');
+        let zero = integer.is_empty() && fraction.is_empty();
+        Some(Self {
+            negative: negative && !zero,
+            integer,
+            fraction,
+        })
+    }
+
+    /// Returns whether the value is zero, however it is written.
+    pub(crate) fn is_zero(&self) -> bool {
+        self.integer.is_empty() && self.fraction.is_empty()
+    }
+
+    /// Returns whether the value is one, however it is written.
+    pub(crate) fn is_one(&self) -> bool {
+        !self.negative && self.integer == "1" && self.fraction.is_empty()
+    }
+}
+
+impl Ord for Decimal<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Without leading zeros, a longer integer part is a larger magnitude
+        let magnitude = self
+            .integer
+            .len()
+            .cmp(&other.integer.len())
+            .then_with(|| self.integer.cmp(other.integer))
+            .then_with(|| self.fraction.cmp(other.fraction));
+        match (self.negative, other.negative) {
+            (false, false) => magnitude,
+            (true, true) => magnitude.reverse(),
+            (true, false) => Ordering::Less,
+            (false, true) => Ordering::Greater,
+        }
+    }
+}
+
+impl PartialOrd for Decimal<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimal(value: &str) -> Decimal<'_> {
+        Decimal::parse(value).expect("not a decimal")
+    }
+
+    #[test]
+    fn test_parse() {
+        for value in ["0", "-3.14", ".5", "2.", "007", "-0.0"] {
+            assert!(Decimal::parse(value).is_some(), "{}", value);
+        }
+        for value in ["", "-", ".", "1e3", "--1", "1.2.3", "+1", "x"] {
+            assert!(Decimal::parse(value).is_none(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn test_compare_by_value() {
+        assert_eq!(decimal("1.50"), decimal("01.5"));
+        assert_eq!(decimal("-0.0"), decimal("0"));
+        assert!(decimal("0.5") > decimal("0.45"));
+        assert!(decimal("0.4") < decimal("0.45"));
+        assert!(decimal("10") > decimal("9.99"));
+        assert!(decimal("-10") < decimal("-9.99"));
+        assert!(decimal("-0.1") < decimal("0"));
+    }
+
+    #[test]
+    fn test_zero_and_one() {
+        assert!(decimal("-0.00").is_zero());
+        assert!(decimal(".0").is_zero());
+        assert!(!decimal("0.01").is_zero());
+        assert!(decimal("1.0").is_one());
+        assert!(decimal("001").is_one());
+        assert!(!decimal("-1").is_one());
+        assert!(!decimal("10").is_one());
+    }
+}
@@ -0,0 +1,508 @@
+//! Problems found in the input, in one structured form.
+//!
+//! A [`Diagnostic`] describes a problem from any stage: lexer, parser and
+//! evaluation errors convert into one, and lints are warnings. It carries
+//! a code, a [`Severity`], a message and span, and any notes and
+//! suggestions, which [`ErrorFormatter::format_diagnostic`] renders and the
+//! JSON output embeds as they are.
+//!
+//! The regular pipeline stops at the first problem. In recovery mode the
+//! lexer skips characters it cannot read and the parser skips operators it
+//! cannot apply, recording a diagnostic for each, so every error in the
+//! input can be reported at once. [`parse_recovering`] runs both stages.
+//!
+//! With the `miette` feature, [`Diagnostic::to_report`] makes a diagnostic
+//! a `miette::Report`, which renders with its source snippet, labels and
+//! help.
+//!
+//! [`ErrorFormatter::format_diagnostic`]: crate::error::ErrorFormatter::format_diagnostic
+
+use crate::ast::Expr;
+use crate::codegen::GenerationError;
+use crate::encoding::EncodingError;
+use crate::error::Rpn2TexError;
+use crate::error_code::ErrorCode;
+use crate::eval::EvalError;
+use crate::lexer::{Lexer, LexerError};
+use crate::parser::{Parser, ParserError};
+use crate::program::BindingError;
+use crate::span::Span;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+
+/// How serious a [`Diagnostic`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Severity {
+    /// The input cannot be converted
+    #[default]
+    Error,
+    /// The input converts, but is probably not what was meant
+    Warning,
+}
+
+impl Severity {
+    /// Returns the severity's name, as written in the output.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// One problem found in the input, located by the span it refers to.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::diagnostic::{Diagnostic, Severity};
+/// use rpn2tex::span::Span;
+///
+/// let diagnostic = Diagnostic::new("Unexpected character '@'", Span::new(4, 5, 1, 5));
+/// assert_eq!(diagnostic.severity(), Severity::Error);
+/// assert_eq!(diagnostic.to_string(), "Line 1, column 5: Unexpected character '@'");
+///
+/// let diagnostic = Diagnostic::new("'x 1 *' is just x", Span::new(4, 5, 1, 5))
+///     .with_severity(Severity::Warning)
+///     .with_help("write x");
+/// assert_eq!(diagnostic.suggestions(), ["write x"]);
+/// assert_eq!(diagnostic.to_string(), "Line 1, column 5: warning: 'x 1 *' is just x");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Diagnostic {
+    /// Stable code identifying the kind of problem
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    code: Option<ErrorCode>,
+    /// How serious the problem is
+    #[cfg_attr(feature = "serde", serde(default))]
+    severity: Severity,
+    /// Error description
+    message: String,
+    /// Where in the source the problem is
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    span: Span,
+    /// Further explanation of the problem
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    notes: Vec<String>,
+    /// Suggestions for what the input may have meant
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    suggestions: Vec<String>,
+}
+
+impl Diagnostic {
+    /// Creates a new error diagnostic.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - Description of the problem
+    /// * `span` - Where in the source the problem is
+    #[must_use]
+    pub fn new(message: impl Into<String>, span: Span) -> Self {
+        Self {
+            code: None,
+            severity: Severity::Error,
+            message: message.into(),
+            span,
+            notes: Vec::new(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    /// Adds a suggestion for what the input may have meant, shown on a
+    /// `help:` line.
+    #[must_use]
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.suggestions.push(help.into());
+        self
+    }
+
+    /// Adds a note further explaining the problem.
+    #[must_use]
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// Attaches the stable code identifying the kind of problem.
+    #[must_use]
+    pub fn with_code(mut self, code: ErrorCode) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    /// Sets how serious the problem is.
+    #[must_use]
+    pub fn with_severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    /// Returns the error message.
+    #[must_use]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns where in the source the problem is.
+    #[must_use]
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// Returns the line number of the problem (1-based).
+    #[must_use]
+    pub fn line(&self) -> u32 {
+        self.span.line
+    }
+
+    /// Returns the column number of the problem (1-based).
+    #[must_use]
+    pub fn column(&self) -> u32 {
+        self.span.column
+    }
+
+    /// Returns the first suggestion for what the input may have meant, if
+    /// any.
+    #[must_use]
+    pub fn help(&self) -> Option<&str> {
+        self.suggestions.first().map(String::as_str)
+    }
+
+    /// Returns every suggestion for what the input may have meant.
+    #[must_use]
+    pub fn suggestions(&self) -> &[String] {
+        &self.suggestions
+    }
+
+    /// Returns the notes further explaining the problem.
+    #[must_use]
+    pub fn notes(&self) -> &[String] {
+        &self.notes
+    }
+
+    /// Returns the stable code identifying the kind of problem, if it has
+    /// one.
+    #[must_use]
+    pub fn code(&self) -> Option<ErrorCode> {
+        self.code
+    }
+
+    /// Returns how serious the problem is.
+    #[must_use]
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    /// Makes a `miette` report of the diagnostic, pointing into `source`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::diagnostic::Diagnostic;
+    /// use rpn2tex::lexer::Lexer;
+    ///
+    /// let error = Lexer::new("2 3 ^").tokenize().unwrap_err();
+    /// let report = Diagnostic::from(error).to_report("2 3 ^");
+    /// assert_eq!(report.code().unwrap().to_string(), "E001");
+    /// assert!(report.help().is_some());
+    /// ```
+    #[cfg(feature = "miette")]
+    #[must_use]
+    pub fn to_report(&self, source: impl Into<String>) -> miette::Report {
+        miette::Report::new(MietteDiagnostic(self.clone())).with_source_code(source.into())
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Line {}, column {}: ", self.span.line, self.span.column)?;
+        if self.severity != Severity::Error {
+            write!(f, "{}: ", self.severity)?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for Diagnostic {}
+
+/// A diagnostic as miette shows it: the message alone, since miette
+/// locates it in the snippet, and the notes before the suggestions in the
+/// help text, as `note:` lines.
+#[cfg(feature = "miette")]
+#[derive(Debug)]
+struct MietteDiagnostic(Diagnostic);
+
+#[cfg(feature = "miette")]
+impl fmt::Display for MietteDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0.message)
+    }
+}
+
+#[cfg(feature = "miette")]
+impl Error for MietteDiagnostic {}
+
+#[cfg(feature = "miette")]
+impl miette::Diagnostic for MietteDiagnostic {
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        self.0
+            .code
+            .map(|code| Box::new(code) as Box<dyn fmt::Display + 'a>)
+    }
+
+    fn severity(&self) -> Option<miette::Severity> {
+        Some(match self.0.severity {
+            Severity::Error => miette::Severity::Error,
+            Severity::Warning => miette::Severity::Warning,
+        })
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        let lines: Vec<String> = self
+            .0
+            .notes
+            .iter()
+            .map(|note| format!("note: {}", note))
+            .chain(self.0.suggestions.iter().cloned())
+            .collect();
+        if lines.is_empty() {
+            return None;
+        }
+        Some(Box::new(lines.join("\n")))
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = miette::LabeledSpan> + '_>> {
+        let span = (self.0.span.start, self.0.span.len());
+        Some(Box::new(std::iter::once(
+            miette::LabeledSpan::new_primary_with_span(None, span),
+        )))
+    }
+}
+
+/// The note on a limit error, whose input is not checked past the limit.
+const LIMIT_NOTE: &str = "the rest of the input is not checked";
+
+impl From<LexerError> for Diagnostic {
+    fn from(error: LexerError) -> Self {
+        let mut diagnostic = Self {
+            code: error.code(),
+            ..Self::new(error.message(), error.span())
+        };
+        if let Some(help) = error.help() {
+            diagnostic = diagnostic.with_help(help);
+        }
+        if error.limit_exceeded().is_some() {
+            diagnostic = diagnostic.with_note(LIMIT_NOTE);
+        }
+        diagnostic
+    }
+}
+
+impl From<ParserError> for Diagnostic {
+    fn from(error: ParserError) -> Self {
+        let diagnostic = Self {
+            code: error.code(),
+            ..Self::new(error.message(), error.token().span)
+        };
+        if error.limit_exceeded().is_some() {
+            diagnostic.with_note(LIMIT_NOTE)
+        } else {
+            diagnostic
+        }
+    }
+}
+
+impl From<EncodingError> for Diagnostic {
+    fn from(error: EncodingError) -> Self {
+        Self::new(error.message(), error.span()).with_code(error.code())
+    }
+}
+
+impl From<BindingError> for Diagnostic {
+    fn from(error: BindingError) -> Self {
+        Self::new(error.message(), error.span())
+    }
+}
+
+/// An I/O error, which has no position, is placed at the start of the
+/// input.
+impl From<Rpn2TexError> for Diagnostic {
+    fn from(error: Rpn2TexError) -> Self {
+        match error {
+            Rpn2TexError::Io { .. } => Self::new(error.to_string(), Span::new(0, 0, 1, 1)),
+            Rpn2TexError::Encoding(error) => error.into(),
+            Rpn2TexError::Lexer(error) => error.into(),
+            Rpn2TexError::Parser(error) => error.into(),
+            Rpn2TexError::Binding(error) => error.into(),
+            Rpn2TexError::Eval(error) => error.into(),
+            Rpn2TexError::Generation(error) => error.into(),
+        }
+    }
+}
+
+/// Generation errors, such as an operator the output format cannot write.
+impl From<GenerationError> for Diagnostic {
+    fn from(error: GenerationError) -> Self {
+        Self::new(error.message(), error.span())
+    }
+}
+
+/// Evaluation errors, such as a value that cannot be computed.
+impl From<EvalError> for Diagnostic {
+    fn from(error: EvalError) -> Self {
+        Self {
+            code: error.code(),
+            ..Self::new(error.message(), error.span())
+        }
+    }
+}
+
+/// Tokenizes and parses an input, collecting every lexer and parser error
+/// instead of stopping at the first.
+///
+/// The lexer's [`Limits`](crate::limits::Limits) also bound the parser.
+/// Diagnostics are ordered lexer errors first, then parser errors. Skipping
+/// an unreadable character can leave operands unused, so a lexer error may
+/// be followed by a parser error it caused.
+///
+/// # Errors
+///
+/// Returns all diagnostics, in source order within each stage, if any
+/// stage found a problem.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::diagnostic::parse_recovering;
+/// use rpn2tex::lexer::Lexer;
+///
+/// assert!(parse_recovering(Lexer::new("5 3 +")).is_ok());
+///
+/// let diagnostics = parse_recovering(Lexer::new("5 @ + 3 $ *")).unwrap_err();
+/// let messages: Vec<&str> = diagnostics.iter().map(|d| d.message()).collect();
+/// assert_eq!(
+///     messages,
+///     [
+///         "Unexpected character '@'",
+///         "Unexpected character '$'",
+///         "Too few operands for operator",
+///     ]
+/// );
+/// ```
+pub fn parse_recovering(lexer: Lexer<'_>) -> Result<Expr, Vec<Diagnostic>> {
+    let limits = lexer.limits();
+    let (tokens, mut diagnostics) = lexer.tokenize_recovering();
+    match Parser::new(tokens).with_limits(limits).parse_recovering() {
+        Ok(ast) if diagnostics.is_empty() => Ok(ast),
+        Ok(_) => Err(diagnostics),
+        Err(parser_diagnostics) => {
+            diagnostics.extend(parser_diagnostics);
+            Err(diagnostics)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::limits::Limits;
+
+    fn positions(source: &str) -> Vec<(u32, u32)> {
+        parse_recovering(Lexer::new(source))
+            .unwrap_err()
+            .iter()
+            .map(|d| (d.line(), d.column()))
+            .collect()
+    }
+
+    #[test]
+    fn test_collects_every_lexer_error() {
+        // Skipping the operators leaves two values on the stack at the end
+        assert_eq!(
+            positions("2 ^ 3 ^ 4 +\n1 ! +"),
+            [(1, 3), (1, 7), (2, 3), (2, 6)]
+        );
+    }
+
+    #[test]
+    fn test_collects_every_parser_error() {
+        // Both operators lack an operand; the final stack is then too deep
+        assert_eq!(positions("+ 5 * 3 4"), [(1, 1), (1, 5), (1, 10)]);
+    }
+
+    #[test]
+    fn test_valid_input_has_no_diagnostics() {
+        let ast = parse_recovering(Lexer::new("5 3 + 2 *")).unwrap();
+        assert_eq!(ast.column(), 9);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_diagnostic_json() {
+        let diagnostic = Diagnostic::new("Empty expression", Span::new(0, 0, 1, 1));
+        assert_eq!(
+            serde_json::to_string(&diagnostic).unwrap(),
+            r#"{"severity":"error","message":"Empty expression","start":0,"end":0,"line":1,"column":1}"#
+        );
+        let diagnostic = diagnostic
+            .with_code(ErrorCode::EmptyExpression)
+            .with_note("the input has no tokens")
+            .with_help("write an expression");
+        let json = serde_json::to_string(&diagnostic).unwrap();
+        assert!(json.starts_with(r#"{"code":"E103","severity":"error","#));
+        assert!(json.ends_with(
+            r#""column":1,"notes":["the input has no tokens"],"suggestions":["write an expression"]}"#
+        ));
+        assert_eq!(
+            serde_json::from_str::<Diagnostic>(&json).unwrap(),
+            diagnostic
+        );
+    }
+
+    #[test]
+    fn test_limit_errors_have_a_note() {
+        let lexer = Lexer::new("1 2 + 3 +").with_limits(Limits::new().with_max_depth(2));
+        let diagnostics = parse_recovering(lexer).unwrap_err();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].notes(), [LIMIT_NOTE]);
+        assert_eq!(diagnostics[0].code(), Some(ErrorCode::DepthLimit));
+    }
+
+    #[test]
+    fn test_codes_are_kept() {
+        let codes: Vec<_> = parse_recovering(Lexer::new("2 ^ 3 1.2.3 + +"))
+            .unwrap_err()
+            .iter()
+            .map(Diagnostic::code)
+            .collect();
+        assert_eq!(
+            codes,
+            [
+                Some(ErrorCode::UnexpectedCharacter),
+                Some(ErrorCode::MalformedNumber),
+                Some(ErrorCode::TooFewOperands),
+            ]
+        );
+    }
+}
@@ -0,0 +1,174 @@
+//! Difficulty scores for arithmetic exercises.
+//!
+//! [`difficulty`] rates how hard an expression is to work out by hand, from
+//! the operators it uses, how deeply its operations nest and how large the
+//! intermediate results get. The score is a heuristic for sorting and
+//! filtering generated problems; only its order is meaningful.
+
+use crate::ast::Expr;
+use crate::eval::Evaluator;
+
+/// The parts of an expression's difficulty score.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Difficulty {
+    /// Points for the operators: 1 for each `+` or `-`, 2 for each `*` and
+    /// 3 for each `/` or custom operator
+    pub operators: u32,
+    /// Levels of nested operations, 0 for a lone number or variable
+    pub depth: u32,
+    /// Points for the result of each operation: 1 per digit of its whole
+    /// part past the first, 2 if it is not a whole number and 1 if it is
+    /// negative
+    pub magnitude: u32,
+}
+
+impl Difficulty {
+    /// Returns the total score, the sum of the parts.
+    #[must_use]
+    pub fn score(&self) -> u32 {
+        self.operators + self.depth + self.magnitude
+    }
+}
+
+/// Scores how hard `expr` is to work out by hand.
+///
+/// Intermediate results are computed with `evaluator`'s variable bindings;
+/// operations on unbound variables, and divisions by zero, add no
+/// magnitude points.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::difficulty::difficulty;
+/// use rpn2tex::eval::Evaluator;
+/// use rpn2tex::lexer::Lexer;
+/// use rpn2tex::parser::Parser;
+///
+/// let parse = |input: &str| Parser::new(Lexer::new(input).tokenize().unwrap()).parse().unwrap();
+/// let easy = difficulty(&parse("5 3 +"), &Evaluator::new());
+/// let hard = difficulty(&parse("47 12 * 7 /"), &Evaluator::new());
+/// assert_eq!(easy.score(), 2);
+/// assert_eq!((hard.operators, hard.depth, hard.magnitude), (5, 2, 5));
+/// assert!(hard.score() > easy.score());
+/// ```
+#[must_use]
+pub fn difficulty(expr: &Expr, evaluator: &Evaluator) -> Difficulty {
+    let mut difficulty = Difficulty::default();
+    let (depth, _) = visit(expr, evaluator, &mut difficulty);
+    difficulty.depth = depth;
+    difficulty
+}
+
+/// Adds the operator and magnitude points of `node` and its operands to
+/// `difficulty`, and returns its depth and, if it can be computed, value.
+fn visit(node: &Expr, evaluator: &Evaluator, difficulty: &mut Difficulty) -> (u32, Option<f64>) {
+    if let Expr::Apply { operands, .. } = node {
+        let depth = operands
+            .iter()
+            .map(|operand| visit(operand, evaluator, difficulty).0)
+            .max()
+            .unwrap_or(0);
+        difficulty.operators += 3;
+        // Only the evaluator's plugins can compute the value
+        let value = evaluator.evaluate(node).ok();
+        if let Some(value) = value {
+            difficulty.magnitude += magnitude(value);
+        }
+        return (depth + 1, value);
+    }
+    let Expr::BinaryOp {
+        operator,
+        left,
+        right,
+        ..
+    } = node
+    else {
+        return (0, evaluator.evaluate(node).ok());
+    };
+
+    let (left_depth, left) = visit(left, evaluator, difficulty);
+    let (right_depth, right) = visit(right, evaluator, difficulty);
+    difficulty.operators += match operator.as_str() {
+        "+" | "-" => 1,
+        "*" => 2,
+        _ => 3,
+    };
+    let value = left
+        .zip(right)
+        .and_then(|(left, right)| match operator.as_str() {
+            "+" => Some(left + right),
+            "-" => Some(left - right),
+            "*" => Some(left * right),
+            "/" if right != 0.0 => Some(left / right),
+            _ => None,
+        });
+    if let Some(value) = value {
+        difficulty.magnitude += magnitude(value);
+    }
+    (left_depth.max(right_depth) + 1, value)
+}
+
+/// Returns the magnitude points of one intermediate result.
+fn magnitude(value: f64) -> u32 {
+    if !value.is_finite() {
+        return 0;
+    }
+    let whole = value.abs().trunc();
+    let digits = if whole < 10.0 {
+        1
+    } else {
+        whole.log10().floor() as u32 + 1
+    };
+    let fraction = if value.fract() == 0.0 { 0 } else { 2 };
+    let sign = u32::from(value < 0.0);
+    digits - 1 + fraction + sign
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn score(source: &str, evaluator: &Evaluator) -> Difficulty {
+        let tokens = Lexer::new(source)
+            .with_identifiers(true)
+            .tokenize()
+            .unwrap();
+        difficulty(&Parser::new(tokens).parse().unwrap(), evaluator)
+    }
+
+    #[test]
+    fn test_lone_operand() {
+        assert_eq!(score("12345", &Evaluator::new()), Difficulty::default());
+    }
+
+    #[test]
+    fn test_operator_mix_and_depth() {
+        let difficulty = score("1 2 + 3 - 4 *", &Evaluator::new());
+        assert_eq!(difficulty.operators, 4);
+        assert_eq!(difficulty.depth, 3);
+        // 3, 0 and 0 are all single digits
+        assert_eq!(difficulty.magnitude, 0);
+        assert_eq!(difficulty.score(), 7);
+    }
+
+    #[test]
+    fn test_magnitude() {
+        assert_eq!(magnitude(9.0), 0);
+        assert_eq!(magnitude(10.0), 1);
+        assert_eq!(magnitude(-1234.0), 4);
+        assert_eq!(magnitude(0.5), 2);
+        assert_eq!(magnitude(f64::INFINITY), 0);
+    }
+
+    #[test]
+    fn test_unknown_values_add_no_magnitude() {
+        assert_eq!(score("x 100 *", &Evaluator::new()).magnitude, 0);
+        assert_eq!(
+            score("x 100 *", &Evaluator::new().bind("x", 5.0)).magnitude,
+            2
+        );
+        assert_eq!(score("1 0 / 100 *", &Evaluator::new()).magnitude, 0);
+    }
+}
@@ -0,0 +1,191 @@
+//! Graphviz DOT output for Abstract Syntax Trees.
+//!
+//! This module renders an AST as a Graphviz `digraph` so the structure of a
+//! parsed expression can be visualized with tools such as `dot -Tpng`.
+
+use crate::ast::Expr;
+use crate::codegen::CodeGenerator;
+
+/// Converts AST expressions to Graphviz DOT source.
+///
+/// Nodes are numbered in pre-order (`n0` is the root) and labelled with the
+/// operator symbol or number literal they represent. Each operator node has
+/// an edge to its left operand followed by an edge to its right operand.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::ast::Expr;
+/// use rpn2tex::dot::DotGenerator;
+/// use rpn2tex::span::Span;
+///
+/// let expr = Expr::BinaryOp {
+///     span: Span::new(4, 5, 1, 5),
+///     operator: "+".to_string(),
+///     left: Box::new(Expr::Number {
+///         span: Span::new(0, 1, 1, 1),
+///         value: "5".to_string(),
+///     }),
+///     right: Box::new(Expr::Number {
+///         span: Span::new(2, 3, 1, 3),
+///         value: "3".to_string(),
+///     }),
+/// };
+///
+/// let dot = DotGenerator::new().generate(&expr);
+/// assert!(dot.starts_with("digraph AST {"));
+/// assert!(dot.contains("n0 [label=\"+\"];"));
+/// assert!(dot.contains("n0 -> n1;"));
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DotGenerator;
+
+impl DotGenerator {
+    /// Creates a new DOT generator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::dot::DotGenerator;
+    ///
+    /// let generator = DotGenerator::new();
+    /// ```
+    #[must_use]
+    pub const fn new() -> Self {
+        Self
+    }
+
+    /// Generates a Graphviz digraph from an AST expression.
+    ///
+    /// # Arguments
+    ///
+    /// * `ast` - The root expression node to render
+    ///
+    /// # Returns
+    ///
+    /// A complete `digraph AST { ... }` document terminated by a newline.
+    #[must_use]
+    pub fn generate(&self, ast: &Expr) -> String {
+        let mut body = String::new();
+        let mut next_id = 0;
+        Self::visit(ast, &mut next_id, &mut body);
+        format!("digraph AST {{\n{}}}\n", body)
+    }
+
+    /// Emits the node for `node` and its subtree, returning the node's id.
+    ///
+    /// Ids are allocated from `next_id` in pre-order so the numbering is
+    /// stable for a given tree shape.
+    fn visit(node: &Expr, next_id: &mut usize, out: &mut String) -> usize {
+        let id = *next_id;
+        *next_id += 1;
+
+        match node {
+            Expr::Number { value, .. } => {
+                out.push_str(&format!("    n{} [label=\"{}\"];\n", id, escape(value)));
+            }
+            Expr::Variable { name, .. } => {
+                out.push_str(&format!("    n{} [label=\"{}\"];\n", id, escape(name)));
+            }
+            Expr::BinaryOp {
+                operator,
+                left,
+                right,
+                ..
+            } => {
+                out.push_str(&format!("    n{} [label=\"{}\"];\n", id, escape(operator)));
+                let left_id = Self::visit(left, next_id, out);
+                out.push_str(&format!("    n{} -> n{};\n", id, left_id));
+                let right_id = Self::visit(right, next_id, out);
+                out.push_str(&format!("    n{} -> n{};\n", id, right_id));
+            }
+            Expr::Apply {
+                operator, operands, ..
+            } => {
+                out.push_str(&format!("    n{} [label=\"{}\"];\n", id, escape(operator)));
+                for operand in operands {
+                    let operand_id = Self::visit(operand, next_id, out);
+                    out.push_str(&format!("    n{} -> n{};\n", id, operand_id));
+                }
+            }
+        }
+
+        id
+    }
+}
+
+/// Escapes a label for use inside a double-quoted DOT string.
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl CodeGenerator for DotGenerator {
+    fn generate(&self, expr: &Expr) -> String {
+        DotGenerator::generate(self, expr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::span::Span;
+
+    fn make_number(value: &str) -> Expr {
+        Expr::Number {
+            span: Span::new(0, 0, 1, 1),
+            value: value.to_string(),
+        }
+    }
+
+    fn make_binop(operator: &str, left: Expr, right: Expr) -> Expr {
+        Expr::BinaryOp {
+            span: Span::new(0, 0, 1, 1),
+            operator: operator.to_string(),
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+
+    #[test]
+    fn test_single_number() {
+        let dot = DotGenerator::new().generate(&make_number("42"));
+        assert_eq!(dot, "digraph AST {\n    n0 [label=\"42\"];\n}\n");
+    }
+
+    #[test]
+    fn test_nested_expression_numbering() {
+        // (5 + 3) * 2
+        let expr = make_binop(
+            "*",
+            make_binop("+", make_number("5"), make_number("3")),
+            make_number("2"),
+        );
+        let dot = DotGenerator::new().generate(&expr);
+        let expected = "digraph AST {\n\
+                        \x20   n0 [label=\"*\"];\n\
+                        \x20   n1 [label=\"+\"];\n\
+                        \x20   n2 [label=\"5\"];\n\
+                        \x20   n1 -> n2;\n\
+                        \x20   n3 [label=\"3\"];\n\
+                        \x20   n1 -> n3;\n\
+                        \x20   n0 -> n1;\n\
+                        \x20   n4 [label=\"2\"];\n\
+                        \x20   n0 -> n4;\n\
+                        }\n";
+        assert_eq!(dot, expected);
+    }
+
+    #[test]
+    fn test_negative_and_decimal_labels() {
+        let expr = make_binop("-", make_number("-1.5"), make_number("2"));
+        let dot = DotGenerator::new().generate(&expr);
+        assert!(dot.contains("n1 [label=\"-1.5\"];"));
+        assert!(dot.contains("n0 [label=\"-\"];"));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("a\"b"), "a\\\"b");
+        assert_eq!(escape("a\\b"), "a\\\\b");
+    }
+}
@@ -0,0 +1,180 @@
+//! Decoding raw input bytes into source text.
+//!
+//! Input files are expected to be UTF-8. [`decode`] strips the byte order
+//! mark some editors write at the start of UTF-8 files, and reports invalid
+//! UTF-8 as an [`EncodingError`] at the offending byte rather than failing
+//! with a generic I/O error.
+
+use crate::error_code::ErrorCode;
+use crate::span::Span;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+
+/// The UTF-8 encoding of U+FEFF, the byte order mark.
+const BOM: &str = "\u{feff}";
+
+/// Error type for input that is not valid UTF-8.
+///
+/// The span covers the invalid bytes, with offsets into the input as given
+/// (including any byte order mark) and the line and column of the text
+/// before them.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::encoding::decode;
+///
+/// let error = decode(b"5 3\n+ \xff").unwrap_err();
+/// assert_eq!(error.offset(), 6);
+/// assert_eq!((error.line(), error.column()), (2, 3));
+/// assert_eq!(error.to_string(), "Line 2, column 3: Invalid UTF-8 at byte 6");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EncodingError {
+    /// Error description
+    message: String,
+    /// The invalid bytes
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    span: Span,
+}
+
+impl EncodingError {
+    /// Returns the error message.
+    #[must_use]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the span of the invalid bytes.
+    #[must_use]
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// Returns the byte offset of the first invalid byte.
+    #[must_use]
+    pub fn offset(&self) -> usize {
+        self.span.start
+    }
+
+    /// Returns the line number where the invalid bytes start (1-based).
+    #[must_use]
+    pub fn line(&self) -> u32 {
+        self.span.line
+    }
+
+    /// Returns the column number where the invalid bytes start (1-based).
+    #[must_use]
+    pub fn column(&self) -> u32 {
+        self.span.column
+    }
+
+    /// Returns the stable code of the error, [`ErrorCode::InvalidUtf8`].
+    #[must_use]
+    pub fn code(&self) -> ErrorCode {
+        ErrorCode::InvalidUtf8
+    }
+}
+
+impl fmt::Display for EncodingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Line {}, column {}: {}",
+            self.span.line, self.span.column, self.message
+        )
+    }
+}
+
+impl Error for EncodingError {}
+
+/// Decodes UTF-8 input, without its byte order mark if it has one.
+///
+/// # Errors
+///
+/// Returns an [`EncodingError`] at the first byte that is not valid UTF-8.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::encoding::decode;
+///
+/// assert_eq!(decode(b"\xef\xbb\xbf5 3 +").unwrap(), "5 3 +");
+/// ```
+pub fn decode(bytes: &[u8]) -> Result<&str, EncodingError> {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Ok(strip_bom(text)),
+        Err(error) => {
+            let start = error.valid_up_to();
+            let end = error.error_len().map_or(bytes.len(), |len| start + len);
+            // The bytes before the error are valid by definition
+            let before = strip_bom(std::str::from_utf8(&bytes[..start]).unwrap_or(""));
+            let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+            let line = before.matches('\n').count() as u32 + 1;
+            let column = before[line_start..].chars().count() as u32 + 1;
+            Err(EncodingError {
+                message: format!("Invalid UTF-8 at byte {}", start),
+                span: Span::new(start, end, line, column),
+            })
+        }
+    }
+}
+
+/// Returns `text` without a leading byte order mark.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::encoding::strip_bom;
+///
+/// assert_eq!(strip_bom("\u{feff}5 3 +"), "5 3 +");
+/// assert_eq!(strip_bom("5 3 +"), "5 3 +");
+/// ```
+#[must_use]
+pub fn strip_bom(text: &str) -> &str {
+    text.strip_prefix(BOM).unwrap_or(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_valid() {
+        assert_eq!(decode(b"5 3 +").unwrap(), "5 3 +");
+        assert_eq!(decode("５ ÷ 2".as_bytes()).unwrap(), "５ ÷ 2");
+        assert_eq!(decode(b"").unwrap(), "");
+    }
+
+    #[test]
+    fn test_only_leading_bom_is_stripped() {
+        assert_eq!(decode(b"\xef\xbb\xbf").unwrap(), "");
+        assert_eq!(decode(b"5\xef\xbb\xbf").unwrap(), "5\u{feff}");
+    }
+
+    #[test]
+    fn test_invalid_byte_span() {
+        let error = decode(b"\xef\xbb\xbf1 \xc3(").unwrap_err();
+        // Offsets count the byte order mark; columns do not
+        assert_eq!(error.span(), Span::new(5, 6, 1, 3));
+        assert_eq!(error.message(), "Invalid UTF-8 at byte 5");
+    }
+
+    #[test]
+    fn test_truncated_sequence_at_end() {
+        // The first two bytes of a three-byte sequence
+        let error = decode(b"1 2 \xe2\x88").unwrap_err();
+        assert_eq!(error.span(), Span::new(4, 6, 1, 5));
+    }
+
+    #[test]
+    fn test_columns_count_characters() {
+        let mut bytes = "×\r\n÷ \u{80}".as_bytes().to_vec();
+        bytes.push(0xff);
+        let error = decode(&bytes).unwrap_err();
+        assert_eq!((error.line(), error.column()), (2, 4));
+    }
+}
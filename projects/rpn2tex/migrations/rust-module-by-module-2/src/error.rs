@@ -2,12 +2,51 @@
 //!
 //! This module provides the [`ErrorFormatter`] type for formatting parse errors
 //! with visual source context, similar to compiler error output.
+//!
+//! Output is plain text unless color is enabled with
+//! [`ErrorFormatter::with_color`], which highlights it with ANSI escape codes
+//! for display in a terminal. With the `miette` feature,
+//! [`ErrorFormatter::with_fancy`] renders diagnostics with miette's
+//! graphical report handler instead.
+//!
+//! [`Rpn2TexError`] gathers the error types of every stage of a conversion,
+//! from reading the input to generating the output, into one type returned
+//! by [`convert`](crate::convert) and [`compile`](crate::compile).
+
+use crate::codegen::GenerationError;
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::encoding::EncodingError;
+use crate::error_code::ErrorCode;
+use crate::eval::EvalError;
+use crate::lexer::LexerError;
+use crate::parser::ParserError;
+use crate::program::BindingError;
+use crate::span::Span;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// ANSI style of the error location and its underline.
+const ERROR_STYLE: &str = "\x1b[1;31m";
+/// ANSI style of the error message.
+const MESSAGE_STYLE: &str = "\x1b[1m";
+/// ANSI style of the line number gutter.
+const GUTTER_STYLE: &str = "\x1b[1;34m";
+/// ANSI style of the `help:` label.
+const HELP_STYLE: &str = "\x1b[1;36m";
+/// ANSI style of the `note:` label.
+const NOTE_STYLE: &str = "\x1b[1;32m";
+/// ANSI code ending a style.
+const RESET: &str = "\x1b[0m";
 
 /// Formats parse errors with source context.
 ///
 /// The `ErrorFormatter` produces user-friendly error messages with visual context,
 /// showing the error line with configurable context lines before/after, and a caret
-/// (`^`) pointing to the exact column where the error occurred.
+/// (`^`) pointing to the exact column where the error occurred, or a row of carets
+/// underlining the whole token.
 ///
 /// # Examples
 ///
@@ -26,6 +65,11 @@ pub struct ErrorFormatter {
     source: String,
     /// Source split into lines for efficient access
     lines: Vec<String>,
+    /// Whether output is highlighted with ANSI escape codes
+    color: bool,
+    /// Whether diagnostics are rendered by miette
+    #[cfg(feature = "miette")]
+    fancy: bool,
 }
 
 impl ErrorFormatter {
@@ -45,7 +89,66 @@ impl ErrorFormatter {
     pub fn new(source: impl Into<String>) -> Self {
         let source = source.into();
         let lines = source.lines().map(String::from).collect();
-        Self { source, lines }
+        Self {
+            source,
+            lines,
+            color: false,
+            #[cfg(feature = "miette")]
+            fancy: false,
+        }
+    }
+
+    /// Returns this formatter highlighting its output with ANSI escape
+    /// codes: the location and underline in red, the message in bold, the
+    /// line numbers in blue and `help:` in cyan.
+    ///
+    /// The text is the same as without color, apart from the escape codes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::error::ErrorFormatter;
+    ///
+    /// let formatter = ErrorFormatter::new("5 @").with_color(true);
+    /// let error = formatter.format_error("Unexpected character '@'", 1, 3, 0);
+    /// assert!(error.starts_with("\x1b[1;31mLine 1, column 3:\x1b[0m "));
+    /// assert!(error.ends_with("\x1b[1;31m^\x1b[0m\n"));
+    /// ```
+    #[must_use]
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Returns `true` if output is highlighted with ANSI escape codes.
+    #[must_use]
+    pub fn color(&self) -> bool {
+        self.color
+    }
+
+    /// Returns this formatter rendering diagnostics with miette's graphical
+    /// report handler, which draws the source snippet with box-drawing
+    /// characters and labels the span. Only
+    /// [`format_diagnostic`](Self::format_diagnostic) is affected.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::diagnostic::Diagnostic;
+    /// use rpn2tex::error::ErrorFormatter;
+    /// use rpn2tex::span::Span;
+    ///
+    /// let formatter = ErrorFormatter::new("5 @").with_fancy(true);
+    /// let diagnostic = Diagnostic::new("Unexpected character '@'", Span::new(2, 3, 1, 3));
+    /// let error = formatter.format_diagnostic(&diagnostic, 1);
+    /// assert!(error.contains("× Unexpected character '@'"));
+    /// assert!(error.contains(" 1 │ 5 @\n"));
+    /// ```
+    #[cfg(feature = "miette")]
+    #[must_use]
+    pub fn with_fancy(mut self, fancy: bool) -> Self {
+        self.fancy = fancy;
+        self
     }
 
     /// Formats an error message with source context.
@@ -82,9 +185,164 @@ impl ErrorFormatter {
         column: u32,
         context_lines: u32,
     ) -> String {
-        let message = message.as_ref();
-        let context = self.get_context(line, column, context_lines);
-        format!("Line {}, column {}: {}\n{}", line, column, message, context)
+        let context = self.get_context(line, column, 1, context_lines);
+        format!(
+            "{}\n{}",
+            self.header(line, column, message.as_ref()),
+            context
+        )
+    }
+
+    /// Formats an error message with source context, underlining all of
+    /// `span` rather than pointing at its first column.
+    ///
+    /// The underline stops at the end of the span's first line and is at
+    /// least one caret wide, so empty spans such as EOF are still marked.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - The error message to display
+    /// * `span` - The source text the error refers to
+    /// * `context_lines` - Number of lines to show before and after the error
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::error::ErrorFormatter;
+    /// use rpn2tex::span::Span;
+    ///
+    /// let formatter = ErrorFormatter::new("5 3.14 + +");
+    /// let error = formatter.format_span_error("Bad token", Span::new(2, 6, 1, 3), 0);
+    /// assert!(error.contains("\n      ^^^^\n"));
+    /// ```
+    #[must_use]
+    pub fn format_span_error(
+        &self,
+        message: impl AsRef<str>,
+        span: Span,
+        context_lines: u32,
+    ) -> String {
+        let text = span.text(&self.source);
+        let first_line = text.lines().next().unwrap_or("");
+        let width = first_line.chars().count().max(1);
+        let context = self.get_context(span.line, span.column, width, context_lines);
+        format!(
+            "{}\n{}",
+            self.header(span.line, span.column, message.as_ref()),
+            context
+        )
+    }
+
+    /// Appends a `help:` note to a formatted error, if there is one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::error::ErrorFormatter;
+    ///
+    /// let formatter = ErrorFormatter::new("2 3 ^");
+    /// let error = formatter.format_error("Unexpected character '^'", 1, 5, 0);
+    /// let error = formatter.with_help(error, Some("exponentiation is not supported"));
+    /// assert!(error.ends_with("^\nhelp: exponentiation is not supported\n"));
+    /// ```
+    #[must_use]
+    pub fn with_help(&self, mut error: String, help: Option<&str>) -> String {
+        if let Some(help) = help {
+            error.push_str(&format!("{} {}\n", self.paint(HELP_STYLE, "help:"), help));
+        }
+        error
+    }
+
+    /// Formats a diagnostic with source context, underlining its span.
+    ///
+    /// The message is followed by the diagnostic's code, if it has one, and
+    /// preceded by its severity unless it is an error. Each note follows
+    /// the context on a `note:` line, then each suggestion on a `help:`
+    /// line.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::diagnostic::{Diagnostic, Severity};
+    /// use rpn2tex::error::ErrorFormatter;
+    /// use rpn2tex::error_code::ErrorCode;
+    /// use rpn2tex::span::Span;
+    ///
+    /// let formatter = ErrorFormatter::new("2 3 ^");
+    /// let diagnostic = Diagnostic::new("Unexpected character '^'", Span::new(4, 5, 1, 5))
+    ///     .with_code(ErrorCode::UnexpectedCharacter)
+    ///     .with_note("only + - * / are operators")
+    ///     .with_help("exponentiation is not supported");
+    /// let error = formatter.format_diagnostic(&diagnostic, 0);
+    /// assert!(error.starts_with("Line 1, column 5: Unexpected character '^' [E001]\n"));
+    /// assert!(error.ends_with(
+    ///     "^\nnote: only + - * / are operators\nhelp: exponentiation is not supported\n"
+    /// ));
+    ///
+    /// let warning = Diagnostic::new("Division by zero", Span::new(4, 5, 1, 5))
+    ///     .with_severity(Severity::Warning);
+    /// assert!(formatter
+    ///     .format_diagnostic(&warning, 0)
+    ///     .starts_with("Line 1, column 5: warning: Division by zero\n"));
+    /// ```
+    #[must_use]
+    pub fn format_diagnostic(&self, diagnostic: &Diagnostic, context_lines: u32) -> String {
+        #[cfg(feature = "miette")]
+        if self.fancy {
+            return self.format_fancy(diagnostic, context_lines);
+        }
+
+        let mut message = match diagnostic.severity() {
+            Severity::Error => diagnostic.message().to_string(),
+            severity => format!("{}: {}", severity, diagnostic.message()),
+        };
+        if let Some(code) = diagnostic.code() {
+            message.push_str(&format!(" [{}]", code));
+        }
+
+        let mut report = self.format_span_error(message, diagnostic.span(), context_lines);
+        for note in diagnostic.notes() {
+            report.push_str(&format!("{} {}\n", self.paint(NOTE_STYLE, "note:"), note));
+        }
+        for suggestion in diagnostic.suggestions() {
+            report = self.with_help(report, Some(suggestion));
+        }
+        report
+    }
+
+    /// Renders a diagnostic with miette's graphical report handler.
+    #[cfg(feature = "miette")]
+    fn format_fancy(&self, diagnostic: &Diagnostic, context_lines: u32) -> String {
+        let theme = if self.color {
+            miette::GraphicalTheme::unicode()
+        } else {
+            miette::GraphicalTheme::unicode_nocolor()
+        };
+        let handler = miette::GraphicalReportHandler::new_themed(theme)
+            .with_context_lines(context_lines as usize);
+        let report = diagnostic.to_report(self.source.as_str());
+        let mut output = String::new();
+        // Writing to a String cannot fail
+        let _ = handler.render_report(&mut output, report.as_ref());
+        output
+    }
+
+    /// Formats the first line of an error: its location and message.
+    fn header(&self, line: u32, column: u32, message: &str) -> String {
+        format!(
+            "{} {}",
+            self.paint(ERROR_STYLE, &format!("Line {}, column {}:", line, column)),
+            self.paint(MESSAGE_STYLE, message)
+        )
+    }
+
+    /// Wraps `text` in an ANSI `style` if color is enabled.
+    fn paint(&self, style: &str, text: &str) -> String {
+        if self.color {
+            format!("{}{}{}", style, text, RESET)
+        } else {
+            text.to_string()
+        }
     }
 
     /// Extracts context around an error location.
@@ -92,11 +350,11 @@ impl ErrorFormatter {
     /// Returns a formatted string containing:
     /// - Context lines before the error
     /// - The error line
-    /// - A caret line pointing to the error column
+    /// - A caret line underlining `width` columns from the error column
     /// - Context lines after the error
     ///
     /// Line numbers are formatted with proper alignment.
-    fn get_context(&self, line: u32, column: u32, context_lines: u32) -> String {
+    fn get_context(&self, line: u32, column: u32, width: usize, context_lines: u32) -> String {
         // Convert from 1-based to 0-based indexing
         let line_idx = (line.saturating_sub(1)) as usize;
 
@@ -117,11 +375,11 @@ impl ErrorFormatter {
         // Add context lines
         for (idx, line_content) in self.lines[start..end].iter().enumerate() {
             let current_line = start + idx + 1; // Convert back to 1-based
+            let gutter = format!("{:>width$} |", current_line, width = line_num_width);
             result.push_str(&format!(
-                "{:>width$} | {}\n",
-                current_line,
-                line_content,
-                width = line_num_width
+                "{} {}\n",
+                self.paint(GUTTER_STYLE, &gutter),
+                line_content
             ));
 
             // Add caret line if this is the error line
@@ -129,7 +387,8 @@ impl ErrorFormatter {
                 // Create caret pointer
                 let caret_padding =
                     " ".repeat(line_num_width + 3 + (column.saturating_sub(1)) as usize);
-                result.push_str(&format!("{}^\n", caret_padding));
+                let carets = self.paint(ERROR_STYLE, &"^".repeat(width));
+                result.push_str(&format!("{}{}\n", caret_padding, carets));
             }
         }
 
@@ -137,6 +396,198 @@ impl ErrorFormatter {
     }
 }
 
+/// An error from any stage of a conversion.
+///
+/// Every variant but [`Io`](Self::Io) points into the source, so it can be
+/// shown with an [`ErrorFormatter`].
+///
+/// With the `serde` feature the error can be stored or sent between
+/// services; an I/O error is kept as its message.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::error::Rpn2TexError;
+///
+/// let error = rpn2tex::compile("5 3 + *").unwrap_err();
+/// assert!(matches!(error, Rpn2TexError::Parser(_)));
+/// assert_eq!(error.span().map(|span| span.column), Some(7));
+/// ```
+#[derive(Debug, Clone, thiserror::Error)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Rpn2TexError {
+    /// The input could not be read
+    #[error("Error reading {}: {}", path.display(), error)]
+    Io {
+        /// The file being read
+        path: PathBuf,
+        /// Why reading it failed
+        #[source]
+        #[cfg_attr(feature = "serde", serde(with = "io_message"))]
+        error: Arc<io::Error>,
+    },
+    /// The input is not valid UTF-8
+    #[error("{0}")]
+    Encoding(#[from] EncodingError),
+    /// The input could not be tokenized
+    #[error("{0}")]
+    Lexer(#[from] LexerError),
+    /// The input's tokens do not form a single expression
+    #[error("{0}")]
+    Parser(#[from] ParserError),
+    /// A `let` statement is malformed, or a program does not end with one
+    /// expression
+    #[error("{0}")]
+    Binding(#[from] BindingError),
+    /// A value could not be computed, as for `--eval` or a result shown in
+    /// the output
+    #[error("{0}")]
+    Eval(#[from] EvalError),
+    /// The output format cannot express the expression, as reported by
+    /// [`CodeGenerator::try_generate`](crate::codegen::CodeGenerator::try_generate)
+    #[error("{0}")]
+    Generation(#[from] GenerationError),
+}
+
+impl Rpn2TexError {
+    /// Makes an [`Io`](Self::Io) error for reading `path`.
+    #[must_use]
+    pub fn io(path: impl Into<PathBuf>, error: io::Error) -> Self {
+        Self::Io {
+            path: path.into(),
+            error: Arc::new(error),
+        }
+    }
+
+    /// Returns where in the source the error occurred, or `None` for an
+    /// I/O error.
+    #[must_use]
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Self::Io { .. } => None,
+            Self::Encoding(error) => Some(error.span()),
+            Self::Lexer(error) => Some(error.span()),
+            Self::Parser(error) => Some(error.token().span),
+            Self::Binding(error) => Some(error.span()),
+            Self::Eval(error) => Some(error.span()),
+            Self::Generation(error) => Some(error.span()),
+        }
+    }
+
+    /// Returns the error message, without its position.
+    #[must_use]
+    pub fn message(&self) -> String {
+        match self {
+            Self::Io { .. } => self.to_string(),
+            Self::Encoding(error) => error.message().to_string(),
+            Self::Lexer(error) => error.message().to_string(),
+            Self::Parser(error) => error.message().to_string(),
+            Self::Binding(error) => error.message().to_string(),
+            Self::Eval(error) => error.message().to_string(),
+            Self::Generation(error) => error.message().to_string(),
+        }
+    }
+
+    /// Returns the line number where the error occurred (1-based), or 0 for
+    /// an I/O error.
+    #[must_use]
+    pub fn line(&self) -> u32 {
+        self.span().map_or(0, |span| span.line)
+    }
+
+    /// Returns the column number where the error occurred (1-based), or 0
+    /// for an I/O error.
+    #[must_use]
+    pub fn column(&self) -> u32 {
+        self.span().map_or(0, |span| span.column)
+    }
+
+    /// Returns the stable code identifying the kind of error, if it has
+    /// one.
+    #[must_use]
+    pub fn code(&self) -> Option<ErrorCode> {
+        match self {
+            Self::Encoding(error) => Some(error.code()),
+            Self::Lexer(error) => error.code(),
+            Self::Parser(error) => error.code(),
+            Self::Eval(error) => error.code(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "miette")]
+impl Rpn2TexError {
+    /// Makes a `miette` report of the error, pointing into `source` unless
+    /// it is an I/O error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let error = rpn2tex::compile("5 +").unwrap_err();
+    /// let report = error.to_report("5 +");
+    /// assert_eq!(report.code().unwrap().to_string(), "E101");
+    /// ```
+    #[must_use]
+    pub fn to_report(&self, source: impl Into<String>) -> miette::Report {
+        if let Self::Io { .. } = self {
+            return miette::Report::msg(self.to_string());
+        }
+        Diagnostic::from(self.clone()).to_report(source)
+    }
+}
+
+/// I/O errors compare by path, kind and message.
+impl PartialEq for Rpn2TexError {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (
+                Self::Io { path, error },
+                Self::Io {
+                    path: other_path,
+                    error: other_error,
+                },
+            ) => {
+                path == other_path
+                    && error.kind() == other_error.kind()
+                    && error.to_string() == other_error.to_string()
+            }
+            (Self::Encoding(error), Self::Encoding(other)) => error == other,
+            (Self::Lexer(error), Self::Lexer(other)) => error == other,
+            (Self::Parser(error), Self::Parser(other)) => error == other,
+            (Self::Binding(error), Self::Binding(other)) => error == other,
+            (Self::Eval(error), Self::Eval(other)) => error == other,
+            (Self::Generation(error), Self::Generation(other)) => error == other,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Rpn2TexError {}
+
+/// Writes an I/O error as its message, and reads it back as an error of
+/// kind [`Other`](io::ErrorKind::Other).
+#[cfg(feature = "serde")]
+mod io_message {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::io;
+    use std::sync::Arc;
+
+    pub(super) fn serialize<S: Serializer>(
+        error: &Arc<io::Error>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(error)
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Arc<io::Error>, D::Error> {
+        let message = String::deserialize(deserializer)?;
+        Ok(Arc::new(io::Error::other(message)))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -203,6 +654,66 @@ mod tests {
         assert!(caret_pos >= 6); // At least after "1 | " and some source chars
     }
 
+    #[test]
+    fn test_span_underlines_whole_token() {
+        let source = "5 3 +\n10 rate *";
+        let formatter = ErrorFormatter::new(source);
+        let error =
+            formatter.format_span_error("Unbound variable 'rate'", Span::new(9, 13, 2, 4), 0);
+        assert_eq!(
+            error,
+            "Line 2, column 4: Unbound variable 'rate'\n2 | 10 rate *\n       ^^^^\n"
+        );
+    }
+
+    #[test]
+    fn test_crlf_line_endings() {
+        let source = "5 3 +\r\n10 rate *\r\n";
+        let formatter = ErrorFormatter::new(source);
+        let error = formatter.format_span_error("Unbound", Span::new(10, 14, 2, 4), 1);
+        assert_eq!(
+            error,
+            "Line 2, column 4: Unbound\n1 | 5 3 +\n2 | 10 rate *\n       ^^^^\n"
+        );
+        // A span reaching the line break underlines up to it
+        let error = formatter.format_span_error("Trailing", Span::new(15, 18, 2, 9), 0);
+        assert!(error.ends_with("\n            ^\n"), "{:?}", error);
+    }
+
+    #[test]
+    fn test_empty_span_gets_one_caret() {
+        let formatter = ErrorFormatter::new("5 3");
+        let error = formatter.format_span_error("Missing operator", Span::new(3, 3, 1, 4), 0);
+        assert!(error.ends_with("1 | 5 3\n       ^\n"));
+    }
+
+    #[test]
+    fn test_help_follows_context() {
+        let formatter = ErrorFormatter::new("2 3 x\n4 *");
+        let error = formatter.format_error("Unexpected character 'x'", 1, 5, 1);
+        assert_eq!(
+            formatter.with_help(error, Some("multiplication is written `*`")),
+            "Line 1, column 5: Unexpected character 'x'\n1 | 2 3 x\n        ^\n2 | 4 *\nhelp: multiplication is written `*`\n"
+        );
+        assert_eq!(formatter.with_help(String::from("x\n"), None), "x\n");
+    }
+
+    #[test]
+    fn test_color() {
+        let formatter = ErrorFormatter::new("2 3 ^").with_color(true);
+        assert!(formatter.color());
+        let error = formatter.format_span_error("Unexpected '^'", Span::new(4, 5, 1, 5), 0);
+        let error = formatter.with_help(error, Some("use `*`"));
+        assert_eq!(
+            error,
+            "\x1b[1;31mLine 1, column 5:\x1b[0m \x1b[1mUnexpected '^'\x1b[0m\n\
+             \x1b[1;34m1 |\x1b[0m 2 3 ^\n\
+             \x20       \x1b[1;31m^\x1b[0m\n\
+             \x1b[1;36mhelp:\x1b[0m use `*`\n"
+        );
+        assert!(!ErrorFormatter::new("").color());
+    }
+
     #[test]
     fn test_context_lines() {
         let source = "line1\nline2\nline3\nline4\nline5";
@@ -252,6 +763,31 @@ mod tests {
         assert!(error.contains("11 | "));
     }
 
+    #[test]
+    fn test_unified_error_conversions() {
+        let span = Span::new(4, 5, 1, 5);
+        let error = Rpn2TexError::from(
+            EvalError::new("Division by zero", span).with_code(ErrorCode::DivisionByZero),
+        );
+        assert!(matches!(error, Rpn2TexError::Eval(_)));
+        assert_eq!(error.span(), Some(span));
+        assert_eq!((error.line(), error.column()), (1, 5));
+        assert_eq!(error.message(), "Division by zero");
+        assert_eq!(error.code(), Some(ErrorCode::DivisionByZero));
+        assert!(std::error::Error::source(&error).is_some());
+
+        let error = crate::compile_all("5 @").remove(0).unwrap_err();
+        assert!(matches!(error, Rpn2TexError::Lexer(_)));
+        assert_eq!(
+            error.to_string(),
+            "Line 1, column 3: Unexpected character '@'"
+        );
+
+        let error = Rpn2TexError::io("in.rpn", io::Error::from(io::ErrorKind::NotFound));
+        assert_eq!(error, error.clone());
+        assert_eq!((error.line(), error.column()), (0, 0));
+    }
+
     #[test]
     fn test_empty_source() {
         let formatter = ErrorFormatter::new("");
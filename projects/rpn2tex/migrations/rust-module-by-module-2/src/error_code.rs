@@ -0,0 +1,152 @@
+//! Stable codes identifying each kind of error in the input.
+//!
+//! Messages may be reworded between releases; codes are not. Tools reading
+//! the JSON output, or an editor integration, can match on a code to filter
+//! or suppress one kind of error. Codes starting `E0` are decoding and lexer
+//! errors, `E1` parser errors, `E2` exceeded limits and `E3` evaluation
+//! errors.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The kind of an error in the input.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::error_code::ErrorCode;
+/// use rpn2tex::lexer::Lexer;
+///
+/// let error = Lexer::new("5 @").tokenize().unwrap_err();
+/// assert_eq!(error.code(), Some(ErrorCode::UnexpectedCharacter));
+/// assert_eq!(ErrorCode::UnexpectedCharacter.as_str(), "E001");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ErrorCode {
+    /// A character that starts no token
+    #[cfg_attr(feature = "serde", serde(rename = "E001"))]
+    UnexpectedCharacter,
+    /// A number literal with a misplaced `.`
+    #[cfg_attr(feature = "serde", serde(rename = "E002"))]
+    MalformedNumber,
+    /// Input bytes that are not valid UTF-8
+    #[cfg_attr(feature = "serde", serde(rename = "E003"))]
+    InvalidUtf8,
+    /// An operator without enough operands
+    #[cfg_attr(feature = "serde", serde(rename = "E101"))]
+    TooFewOperands,
+    /// Values left over with no operator to combine them
+    #[cfg_attr(feature = "serde", serde(rename = "E102"))]
+    MissingOperator,
+    /// Input holding no expression
+    #[cfg_attr(feature = "serde", serde(rename = "E103"))]
+    EmptyExpression,
+    /// Infix input where an operand should be
+    #[cfg_attr(feature = "serde", serde(rename = "E104"))]
+    ExpectedOperand,
+    /// Infix operands not joined by an operator
+    #[cfg_attr(feature = "serde", serde(rename = "E105"))]
+    ExpectedOperator,
+    /// A parenthesis that is unmatched, or outside infix input
+    #[cfg_attr(feature = "serde", serde(rename = "E106"))]
+    Parenthesis,
+    /// A stack word or macro that cannot be applied
+    #[cfg_attr(feature = "serde", serde(rename = "E107"))]
+    Macro,
+    /// A custom operator the parser was not given
+    #[cfg_attr(feature = "serde", serde(rename = "E108"))]
+    UnknownOperator,
+    /// Input longer than the byte limit
+    #[cfg_attr(feature = "serde", serde(rename = "E201"))]
+    InputBytesLimit,
+    /// Input with more tokens than the token limit
+    #[cfg_attr(feature = "serde", serde(rename = "E202"))]
+    TokenLimit,
+    /// An expression nested deeper than the depth limit
+    #[cfg_attr(feature = "serde", serde(rename = "E203"))]
+    DepthLimit,
+    /// A division whose divisor evaluates to zero
+    #[cfg_attr(feature = "serde", serde(rename = "E301"))]
+    DivisionByZero,
+}
+
+impl ErrorCode {
+    /// Returns the code as written in the output, such as `E001`.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::UnexpectedCharacter => "E001",
+            Self::MalformedNumber => "E002",
+            Self::InvalidUtf8 => "E003",
+            Self::TooFewOperands => "E101",
+            Self::MissingOperator => "E102",
+            Self::EmptyExpression => "E103",
+            Self::ExpectedOperand => "E104",
+            Self::ExpectedOperator => "E105",
+            Self::Parenthesis => "E106",
+            Self::Macro => "E107",
+            Self::UnknownOperator => "E108",
+            Self::InputBytesLimit => "E201",
+            Self::TokenLimit => "E202",
+            Self::DepthLimit => "E203",
+            Self::DivisionByZero => "E301",
+        }
+    }
+
+    /// Returns the error the code is written as, such as `E001`, if it is a
+    /// known code.
+    #[must_use]
+    pub fn parse(code: &str) -> Option<Self> {
+        ALL.into_iter().find(|known| known.as_str() == code)
+    }
+}
+
+/// Every error code, in order.
+const ALL: [ErrorCode; 15] = [
+    ErrorCode::UnexpectedCharacter,
+    ErrorCode::MalformedNumber,
+    ErrorCode::InvalidUtf8,
+    ErrorCode::TooFewOperands,
+    ErrorCode::MissingOperator,
+    ErrorCode::EmptyExpression,
+    ErrorCode::ExpectedOperand,
+    ErrorCode::ExpectedOperator,
+    ErrorCode::Parenthesis,
+    ErrorCode::Macro,
+    ErrorCode::UnknownOperator,
+    ErrorCode::InputBytesLimit,
+    ErrorCode::TokenLimit,
+    ErrorCode::DepthLimit,
+    ErrorCode::DivisionByZero,
+];
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes_round_trip() {
+        for code in ALL {
+            assert_eq!(ErrorCode::parse(code.as_str()), Some(code));
+        }
+        assert_eq!(ErrorCode::parse("E999"), None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_codes_serialize_as_written() {
+        for code in ALL {
+            let json = serde_json::to_string(&code).unwrap();
+            assert_eq!(json, format!("\"{}\"", code));
+            assert_eq!(serde_json::from_str::<ErrorCode>(&json).unwrap(), code);
+        }
+    }
+}
@@ -0,0 +1,581 @@
+//! Numeric evaluation of Abstract Syntax Trees.
+//!
+//! This module computes the value of a parsed expression, so converted output
+//! can be checked against the number it is supposed to represent. Values are
+//! computed either in double-precision floating point ([`evaluate`]) or as
+//! exact rationals (`evaluate_exact`, with the `exact` feature), where `1 3 /`
+//! is exactly one third.
+
+use crate::ast::Expr;
+use crate::error_code::ErrorCode;
+use crate::plugin::OperatorTable;
+use crate::span::Span;
+#[cfg(feature = "exact")]
+use num_bigint::BigInt;
+#[cfg(feature = "exact")]
+use num_rational::BigRational;
+#[cfg(feature = "exact")]
+use num_traits::{One, Signed, Zero};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+/// Error type for evaluation failures.
+///
+/// Raised when an expression has no finite value, such as a division by
+/// zero. The span is that of the offending operator.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::eval::EvalError;
+/// use rpn2tex::span::Span;
+///
+/// let error = EvalError::new("Division by zero", Span::new(4, 5, 1, 5));
+/// assert_eq!(error.line(), 1);
+/// assert_eq!(error.column(), 5);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EvalError {
+    /// Error description
+    message: String,
+    /// Where in the source the error occurred
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    span: Span,
+    /// Stable code identifying the kind of error
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    code: Option<ErrorCode>,
+}
+
+impl EvalError {
+    /// Creates a new evaluation error.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - Description of the error
+    /// * `span` - Where in the source the error occurred
+    #[must_use]
+    pub fn new(message: impl Into<String>, span: Span) -> Self {
+        Self {
+            message: message.into(),
+            span,
+            code: None,
+        }
+    }
+
+    /// Attaches the stable code identifying the kind of error.
+    #[must_use]
+    pub fn with_code(mut self, code: ErrorCode) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    /// Returns the stable code identifying the kind of error, if it has
+    /// one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::error_code::ErrorCode;
+    /// use rpn2tex::eval::evaluate;
+    ///
+    /// let ast = rpn2tex::pipeline::Compiler::new().parse("1 0 /").unwrap();
+    /// assert_eq!(evaluate(&ast).unwrap_err().code(), Some(ErrorCode::DivisionByZero));
+    /// ```
+    #[must_use]
+    pub fn code(&self) -> Option<ErrorCode> {
+        self.code
+    }
+
+    /// Returns the error message.
+    #[must_use]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns where in the source the error occurred.
+    #[must_use]
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// Returns the line number where the error occurred (1-based).
+    #[must_use]
+    pub fn line(&self) -> u32 {
+        self.span.line
+    }
+
+    /// Returns the column number where the error occurred (1-based).
+    #[must_use]
+    pub fn column(&self) -> u32 {
+        self.span.column
+    }
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Line {}, column {}: {}",
+            self.span.line, self.span.column, self.message
+        )
+    }
+}
+
+impl Error for EvalError {}
+
+/// Evaluates expressions, substituting bound values for variables.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::eval::Evaluator;
+/// use rpn2tex::lexer::Lexer;
+/// use rpn2tex::parser::Parser;
+///
+/// let tokens = Lexer::new("x 3 + y *").with_identifiers(true).tokenize().unwrap();
+/// let ast = Parser::new(tokens).parse().unwrap();
+///
+/// let evaluator = Evaluator::new().bind("x", 2.0).bind("y", 4.0);
+/// assert_eq!(evaluator.evaluate(&ast), Ok(20.0));
+///
+/// let error = Evaluator::new().bind("x", 2.0).evaluate(&ast).unwrap_err();
+/// assert_eq!(error.message(), "Unbound variable 'y'");
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Evaluator {
+    /// Values substituted for variables, by name
+    bindings: HashMap<String, f64>,
+    /// Custom operators computing [`Expr::Apply`] nodes
+    operators: OperatorTable,
+}
+
+impl Evaluator {
+    /// Creates an evaluator with no variables bound.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Binds `name` to `value`, replacing any earlier binding of the name.
+    #[must_use]
+    pub fn bind(mut self, name: impl Into<String>, value: f64) -> Self {
+        self.bindings.insert(name.into(), value);
+        self
+    }
+
+    /// Sets the custom operators whose plugins compute [`Expr::Apply`]
+    /// nodes.
+    #[must_use]
+    pub fn with_operators(mut self, operators: OperatorTable) -> Self {
+        self.operators = operators;
+        self
+    }
+
+    /// Returns the value bound to `name`, if any.
+    #[must_use]
+    pub fn value(&self, name: &str) -> Option<f64> {
+        self.bindings.get(name).copied()
+    }
+
+    /// Computes the value of an expression.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`EvalError`] located at the operator if a division by
+    /// zero occurs or a custom operator has no plugin or no value, at the
+    /// literal if a number cannot be read, or at the variable if it is
+    /// unbound.
+    pub fn evaluate(&self, expr: &Expr) -> Result<f64, EvalError> {
+        match expr {
+            Expr::Number { span, value } => value
+                .parse()
+                .map_err(|_| EvalError::new(format!("Invalid number '{}'", value), *span)),
+            Expr::Variable { span, name } => self
+                .value(name)
+                .ok_or_else(|| unbound_variable(name, *span)),
+            Expr::BinaryOp {
+                span,
+                operator,
+                left,
+                right,
+            } => {
+                let left = self.evaluate(left)?;
+                let right = self.evaluate(right)?;
+                match operator.as_str() {
+                    "+" => Ok(left + right),
+                    "-" => Ok(left - right),
+                    "*" => Ok(left * right),
+                    "/" if right == 0.0 => Err(division_by_zero(*span)),
+                    "/" => Ok(left / right),
+                    _ => Err(unknown_operator(operator, *span)),
+                }
+            }
+            Expr::Apply {
+                span,
+                operator,
+                operands,
+            } => {
+                let values = operands
+                    .iter()
+                    .map(|operand| self.evaluate(operand))
+                    .collect::<Result<Vec<f64>, EvalError>>()?;
+                let plugin = self
+                    .operators
+                    .get(operator)
+                    .ok_or_else(|| unknown_operator(operator, *span))?;
+                plugin.evaluate(&values).ok_or_else(|| {
+                    EvalError::new(format!("Operator '{}' has no value here", operator), *span)
+                })
+            }
+        }
+    }
+
+    /// Computes the exact rational value of an expression.
+    ///
+    /// Decimal literals are read exactly (`0.1` is one tenth), so no
+    /// rounding occurs at any step. A bound value is read as the shortest
+    /// decimal that round-trips to it, so binding `0.1` also means one tenth.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`EvalError`] located at the operator if a division by
+    /// zero occurs, at the literal if a number cannot be read, or at the
+    /// variable if it is unbound or bound to a non-finite value. A custom
+    /// operator is computed in floating point, as by
+    /// [`evaluate`](Self::evaluate), and read back like a bound value.
+    #[cfg(feature = "exact")]
+    pub fn evaluate_exact(&self, expr: &Expr) -> Result<BigRational, EvalError> {
+        match expr {
+            Expr::Number { span, value } => parse_rational(value)
+                .ok_or_else(|| EvalError::new(format!("Invalid number '{}'", value), *span)),
+            Expr::Variable { span, name } => {
+                let value = self
+                    .value(name)
+                    .ok_or_else(|| unbound_variable(name, *span))?;
+                parse_rational(&value.to_string()).ok_or_else(|| {
+                    EvalError::new(format!("Variable '{}' has no exact value", name), *span)
+                })
+            }
+            Expr::BinaryOp {
+                span,
+                operator,
+                left,
+                right,
+            } => {
+                let left = self.evaluate_exact(left)?;
+                let right = self.evaluate_exact(right)?;
+                match operator.as_str() {
+                    "+" => Ok(left + right),
+                    "-" => Ok(left - right),
+                    "*" => Ok(left * right),
+                    "/" if right.is_zero() => Err(division_by_zero(*span)),
+                    "/" => Ok(left / right),
+                    _ => Err(unknown_operator(operator, *span)),
+                }
+            }
+            Expr::Apply { span, operator, .. } => {
+                let value = self.evaluate(expr)?;
+                parse_rational(&value.to_string()).ok_or_else(|| {
+                    EvalError::new(format!("Operator '{}' has no exact value", operator), *span)
+                })
+            }
+        }
+    }
+}
+
+/// Computes the value of an expression that has no variables.
+///
+/// # Errors
+///
+/// Returns an [`EvalError`] located at the operator if a division by zero
+/// occurs, at the literal if a number cannot be read, or at the first
+/// variable, since none are bound.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::eval::evaluate;
+/// use rpn2tex::lexer::Lexer;
+/// use rpn2tex::parser::Parser;
+///
+/// let tokens = Lexer::new("5 3 + 2 *").tokenize().unwrap();
+/// let ast = Parser::new(tokens).parse().unwrap();
+/// assert_eq!(evaluate(&ast), Ok(16.0));
+/// ```
+pub fn evaluate(expr: &Expr) -> Result<f64, EvalError> {
+    Evaluator::new().evaluate(expr)
+}
+
+/// Computes the exact rational value of an expression that has no variables.
+///
+/// Decimal literals are read exactly (`0.1` is one tenth), so no rounding
+/// occurs at any step.
+///
+/// # Errors
+///
+/// Returns an [`EvalError`] located at the operator if a division by zero
+/// occurs, at the literal if a number cannot be read, or at the first
+/// variable, since none are bound.
+///
+/// # Examples
+///
+/// ```
+/// use num_rational::BigRational;
+/// use rpn2tex::eval::evaluate_exact;
+/// use rpn2tex::lexer::Lexer;
+/// use rpn2tex::parser::Parser;
+///
+/// let tokens = Lexer::new("1 3 /").tokenize().unwrap();
+/// let ast = Parser::new(tokens).parse().unwrap();
+/// let third = evaluate_exact(&ast).unwrap();
+/// assert_eq!(third.to_string(), "1/3");
+/// ```
+#[cfg(feature = "exact")]
+pub fn evaluate_exact(expr: &Expr) -> Result<BigRational, EvalError> {
+    Evaluator::new().evaluate_exact(expr)
+}
+
+/// Builds the error for a variable with no bound value.
+fn unbound_variable(name: &str, span: Span) -> EvalError {
+    EvalError::new(format!("Unbound variable '{}'", name), span)
+}
+
+/// Builds the error for a division by zero, at the `/` operator.
+fn division_by_zero(span: Span) -> EvalError {
+    EvalError::new("Division by zero", span).with_code(ErrorCode::DivisionByZero)
+}
+
+/// Builds the error for an operator the evaluator does not know.
+fn unknown_operator(operator: &str, span: Span) -> EvalError {
+    EvalError::new(format!("Unknown operator '{}'", operator), span)
+}
+
+/// Writes a rational value as LaTeX: integers plainly, other values as a
+/// `\frac` with the sign in front.
+///
+/// # Examples
+///
+/// ```
+/// use num_rational::BigRational;
+/// use rpn2tex::eval::rational_to_latex;
+///
+/// let value = BigRational::new((-2).into(), 6.into());
+/// assert_eq!(rational_to_latex(&value), r"-\frac{1}{3}");
+/// assert_eq!(rational_to_latex(&BigRational::from_integer(4.into())), "4");
+/// ```
+#[cfg(feature = "exact")]
+#[must_use]
+pub fn rational_to_latex(value: &BigRational) -> String {
+    if value.is_integer() {
+        return value.numer().to_string();
+    }
+
+    let sign = if value.is_negative() { "-" } else { "" };
+    format!(
+        "{}\\frac{{{}}}{{{}}}",
+        sign,
+        value.numer().abs(),
+        value.denom()
+    )
+}
+
+/// Writes a rational value as a decimal literal such as `-2.5`, or returns
+/// `None` if its decimal expansion does not terminate (one third).
+#[cfg(feature = "exact")]
+pub(crate) fn rational_to_decimal(value: &BigRational) -> Option<String> {
+    // The expansion terminates iff the denominator has no prime factors
+    // other than 2 and 5; it then needs as many places as the larger power.
+    let mut denominator = value.denom().clone();
+    let mut places = [0_u32; 2];
+    for (count, prime) in places.iter_mut().zip([2, 5]) {
+        let prime = BigInt::from(prime);
+        while (&denominator % &prime).is_zero() {
+            denominator /= &prime;
+            *count += 1;
+        }
+    }
+    if !denominator.is_one() {
+        return None;
+    }
+
+    let places = places[0].max(places[1]);
+    let scaled = value.numer().abs() * BigInt::from(10).pow(places) / value.denom();
+    let mut digits = scaled.to_string();
+    let places = places as usize;
+    if places > 0 {
+        if digits.len() <= places {
+            digits.insert_str(0, &"0".repeat(places + 1 - digits.len()));
+        }
+        digits.insert(digits.len() - places, '.');
+    }
+
+    let sign = if value.is_negative() { "-" } else { "" };
+    Some(format!("{}{}", sign, digits))
+}
+
+/// Reads a decimal literal such as `-3.14` as an exact rational.
+#[cfg(feature = "exact")]
+pub(crate) fn parse_rational(value: &str) -> Option<BigRational> {
+    let (negative, unsigned) = match value.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, value),
+    };
+    let (integer, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+
+    let digits: BigInt = format!("{}{}", integer, fraction).parse().ok()?;
+    let scale = BigInt::from(10).pow(u32::try_from(fraction.len()).ok()?);
+    let magnitude = BigRational::new(digits, scale);
+    Some(if negative { -magnitude } else { magnitude })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn eval(input: &str) -> Result<f64, EvalError> {
+        let tokens = Lexer::new(input).tokenize().expect("tokenize failed");
+        let ast = Parser::new(tokens).parse().expect("parse failed");
+        evaluate(&ast)
+    }
+
+    #[test]
+    fn test_basic_operators() {
+        assert_eq!(eval("5 3 +"), Ok(8.0));
+        assert_eq!(eval("5 3 -"), Ok(2.0));
+        assert_eq!(eval("4 7 *"), Ok(28.0));
+        assert_eq!(eval("10 4 /"), Ok(2.5));
+    }
+
+    #[test]
+    fn test_nested_expression() {
+        assert_eq!(eval("10 2 / 3 + 4 *"), Ok(32.0));
+        assert_eq!(eval("5 3 2 - -"), Ok(4.0));
+        assert_eq!(eval("-3.5 2 *"), Ok(-7.0));
+    }
+
+    #[test]
+    fn test_division_by_zero_reports_operator_position() {
+        let error = eval("1\n3 3 - /").unwrap_err();
+        assert_eq!(error.message(), "Division by zero");
+        assert_eq!((error.line(), error.column()), (2, 7));
+        assert_eq!(error.code(), Some(ErrorCode::DivisionByZero));
+        #[cfg(feature = "serde")]
+        assert_eq!(serde_json::to_value(&error).unwrap()["code"], "E301");
+    }
+
+    #[cfg(feature = "exact")]
+    fn eval_exact(input: &str) -> Result<BigRational, EvalError> {
+        let tokens = Lexer::new(input).tokenize().expect("tokenize failed");
+        let ast = Parser::new(tokens).parse().expect("parse failed");
+        evaluate_exact(&ast)
+    }
+
+    #[cfg(feature = "exact")]
+    #[test]
+    fn test_exact_keeps_fractions() {
+        assert_eq!(eval_exact("1 3 /").unwrap().to_string(), "1/3");
+        assert_eq!(eval_exact("1 3 / 3 *").unwrap().to_string(), "1");
+        assert_eq!(eval_exact("2 -6 /").unwrap().to_string(), "-1/3");
+    }
+
+    #[cfg(feature = "exact")]
+    #[test]
+    fn test_exact_decimal_literals() {
+        // 0.1 + 0.2 is exactly 0.3, unlike in floating point
+        assert_eq!(eval_exact("0.1 0.2 +").unwrap().to_string(), "3/10");
+        assert_eq!(eval_exact("-3.14").unwrap().to_string(), "-157/50");
+    }
+
+    #[cfg(feature = "exact")]
+    #[test]
+    fn test_exact_division_by_zero() {
+        let error = eval_exact("1 0.0 /").unwrap_err();
+        assert_eq!(error.message(), "Division by zero");
+        assert_eq!((error.line(), error.column()), (1, 7));
+        assert_eq!(error.code(), Some(ErrorCode::DivisionByZero));
+    }
+
+    #[cfg(feature = "exact")]
+    #[test]
+    fn test_rational_to_latex() {
+        assert_eq!(
+            rational_to_latex(&eval_exact("1 3 /").unwrap()),
+            r"\frac{1}{3}"
+        );
+        assert_eq!(
+            rational_to_latex(&eval_exact("-7 2 /").unwrap()),
+            r"-\frac{7}{2}"
+        );
+        assert_eq!(rational_to_latex(&eval_exact("6 -2 /").unwrap()), "-3");
+        assert_eq!(rational_to_latex(&eval_exact("0 5 /").unwrap()), "0");
+    }
+
+    fn parse_with_variables(input: &str) -> Expr {
+        let tokens = Lexer::new(input)
+            .with_identifiers(true)
+            .tokenize()
+            .expect("tokenize failed");
+        Parser::new(tokens).parse().expect("parse failed")
+    }
+
+    #[test]
+    fn test_bound_variables() {
+        let ast = parse_with_variables("x x * y -");
+        let evaluator = Evaluator::new().bind("x", 3.0).bind("y", 1.5);
+        assert_eq!(evaluator.evaluate(&ast), Ok(7.5));
+
+        // Rebinding replaces the earlier value
+        let evaluator = evaluator.bind("x", 2.0);
+        assert_eq!(evaluator.value("x"), Some(2.0));
+        assert_eq!(evaluator.evaluate(&ast), Ok(2.5));
+    }
+
+    #[test]
+    fn test_unbound_variable_reports_position() {
+        let ast = parse_with_variables("2 rate *");
+        let error = evaluate(&ast).unwrap_err();
+        assert_eq!(error.message(), "Unbound variable 'rate'");
+        assert_eq!((error.line(), error.column()), (1, 3));
+    }
+
+    #[cfg(feature = "exact")]
+    #[test]
+    fn test_exact_bound_variables() {
+        let ast = parse_with_variables("x 3 /");
+        let evaluator = Evaluator::new().bind("x", 0.1);
+        assert_eq!(evaluator.evaluate_exact(&ast).unwrap().to_string(), "1/30");
+
+        let error = Evaluator::new()
+            .bind("x", f64::INFINITY)
+            .evaluate_exact(&ast)
+            .unwrap_err();
+        assert_eq!(error.message(), "Variable 'x' has no exact value");
+    }
+
+    #[cfg(feature = "exact")]
+    #[test]
+    fn test_rational_to_decimal() {
+        let decimal = |input| rational_to_decimal(&eval_exact(input).unwrap());
+        assert_eq!(decimal("6 3 /"), Some("2".to_string()));
+        assert_eq!(decimal("-5 2 /"), Some("-2.5".to_string()));
+        assert_eq!(decimal("1 40 /"), Some("0.025".to_string()));
+        assert_eq!(decimal("0.1 0.2 +"), Some("0.3".to_string()));
+        assert_eq!(decimal("1 3 /"), None);
+        assert_eq!(decimal("1 6 /"), None);
+    }
+
+    #[test]
+    fn test_display() {
+        let error = EvalError::new("Division by zero", Span::new(13, 14, 3, 4));
+        assert_eq!(error.to_string(), "Line 3, column 4: Division by zero");
+    }
+}
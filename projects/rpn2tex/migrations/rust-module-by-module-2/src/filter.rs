@@ -0,0 +1,356 @@
+//! Markdown documents with RPN embedded in them.
+//!
+//! A document can hold expressions in fenced blocks whose info string is
+//! `rpn`, or in inline code spans starting with `rpn:`. [`find_snippets`]
+//! locates them, and [`filter_markdown`] replaces each with rendered math,
+//! delimited for a [`MarkdownFlavor`], leaving the rest of the document as it
+//! was. Code in other fenced blocks is never touched.
+
+use crate::batch::Segment;
+use crate::markdown::MarkdownFlavor;
+use std::ops::Range;
+
+/// Info string that marks a fenced block as RPN.
+const FENCE_INFO: &str = "rpn";
+
+/// Prefix that marks an inline code span as RPN.
+const SPAN_PREFIX: &str = "rpn:";
+
+/// One RPN expression embedded in a Markdown document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet<'a> {
+    /// The expression, positioned in the whole document
+    pub segment: Segment<'a>,
+    /// Byte range of the block or span the rendered math replaces
+    pub range: Range<usize>,
+    /// Whether the expression came from a fenced block, and is displayed
+    pub display: bool,
+}
+
+/// An open fenced code block.
+struct Fence {
+    /// The fence character, `` ` `` or `~`
+    marker: char,
+    /// Number of fence characters in the opening fence
+    len: usize,
+    /// Whether the info string is `rpn`
+    rpn: bool,
+    /// Byte offset of the opening fence's first fence character
+    start: usize,
+    /// Byte offset of the block's first content line
+    content: usize,
+    /// 1-based line number of the block's first content line
+    content_line: u32,
+}
+
+impl Fence {
+    /// Returns the fence opened by `text`, the line at `start` followed by
+    /// `content`, if it is an opening code fence.
+    fn open(text: &str, start: usize, content: usize, line: u32) -> Option<Self> {
+        let (indent, rest) = fence_indent(text)?;
+        let marker = rest.chars().next().filter(|c| matches!(c, '`' | '~'))?;
+        let len = rest.chars().take_while(|&c| c == marker).count();
+        let info = rest[len..].trim();
+        // A backtick fence's info string cannot hold backticks
+        if len < 3 || (marker == '`' && info.contains('`')) {
+            return None;
+        }
+        Some(Self {
+            marker,
+            len,
+            rpn: info.split_whitespace().next() == Some(FENCE_INFO),
+            start: start + indent,
+            content,
+            content_line: line + 1,
+        })
+    }
+
+    /// Returns whether `text` is a line closing this fence.
+    fn closes(&self, text: &str) -> bool {
+        let Some((_, rest)) = fence_indent(text) else {
+            return false;
+        };
+        let len = rest.chars().take_while(|&c| c == self.marker).count();
+        len >= self.len && rest[len..].trim().is_empty()
+    }
+
+    /// Returns the snippet of this block when it is RPN, with its content
+    /// ending at `end` and the block at `close_end`.
+    fn snippet<'a>(&self, document: &'a str, end: usize, close_end: usize) -> Option<Snippet<'a>> {
+        self.rpn.then(|| Snippet {
+            segment: Segment {
+                text: &document[self.content.min(end)..end],
+                offset: self.content.min(end),
+                line: self.content_line,
+                column: 1,
+            },
+            range: self.start..close_end,
+            display: true,
+        })
+    }
+}
+
+/// Splits a line into the size of its indentation and the rest, if it is
+/// indented little enough to hold a code fence.
+fn fence_indent(text: &str) -> Option<(usize, &str)> {
+    let rest = text.trim_start_matches(' ');
+    let indent = text.len() - rest.len();
+    (indent <= 3).then_some((indent, rest))
+}
+
+/// Finds the RPN blocks and spans of a Markdown document, in order.
+///
+/// Inline spans are looked for outside fenced blocks and end on the line
+/// they start on. A block left open runs to the end of the document.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::filter::find_snippets;
+///
+/// let snippets = find_snippets("Sum: `rpn:1 2 +`\n\n```rpn\n3 4 *\n```\n");
+/// assert_eq!(snippets.len(), 2);
+/// assert_eq!(snippets[0].segment.text, "1 2 +");
+/// assert_eq!(snippets[0].segment.column, 11);
+/// assert!(!snippets[0].display);
+/// assert_eq!(snippets[1].segment.text, "3 4 *\n");
+/// assert_eq!(snippets[1].segment.line, 4);
+/// assert_eq!(snippets[1].range, 18..34);
+/// ```
+#[must_use]
+pub fn find_snippets(document: &str) -> Vec<Snippet<'_>> {
+    let mut snippets = Vec::new();
+    let mut fence: Option<Fence> = None;
+    let mut start = 0;
+    for (index, line) in document.split_inclusive('\n').enumerate() {
+        let number = index as u32 + 1;
+        let text = line.trim_end_matches(['\n', '\r']);
+        let end = start + line.len();
+        match &fence {
+            Some(open) if open.closes(text) => {
+                snippets.extend(open.snippet(document, start, start + text.len()));
+                fence = None;
+            }
+            Some(_) => {}
+            None => {
+                fence = Fence::open(text, start, end, number);
+                if fence.is_none() {
+                    find_spans(document, start..start + text.len(), number, &mut snippets);
+                }
+            }
+        }
+        start = end;
+    }
+    if let Some(open) = fence {
+        snippets.extend(open.snippet(document, document.len(), document.len()));
+    }
+    snippets
+}
+
+/// Appends the `rpn:` code spans of the line at `range` of `document`.
+fn find_spans<'a>(document: &'a str, range: Range<usize>, line: u32, out: &mut Vec<Snippet<'a>>) {
+    let text = &document[range.clone()];
+    let mut i = 0;
+    while let Some(found) = text[i..].find('`') {
+        let open = i + found;
+        let ticks = backticks(&text[open..]);
+        let content = open + ticks;
+        // The span closes at the next run of exactly as many backticks
+        let mut search = content;
+        let close = loop {
+            let Some(found) = text[search..].find('`') else {
+                break None;
+            };
+            let run = search + found;
+            let len = backticks(&text[run..]);
+            if len == ticks {
+                break Some(run);
+            }
+            search = run + len;
+        };
+        let Some(close) = close else {
+            i = content;
+            continue;
+        };
+        if text[content..close].starts_with(SPAN_PREFIX) {
+            let expression = content + SPAN_PREFIX.len();
+            out.push(Snippet {
+                segment: Segment {
+                    text: &text[expression..close],
+                    offset: range.start + expression,
+                    line,
+                    column: text[..expression].chars().count() as u32 + 1,
+                },
+                range: range.start + open..range.start + close + ticks,
+                display: false,
+            });
+        }
+        i = close + ticks;
+    }
+}
+
+/// Returns the number of backticks `text` starts with.
+fn backticks(text: &str) -> usize {
+    text.bytes().take_while(|&b| b == b'`').count()
+}
+
+/// Replaces the RPN blocks and spans of a Markdown document with math.
+///
+/// `render` produces the math body of each snippet, without delimiters.
+/// Inline spans become `$...$`; blocks become a ```` ```math ```` fence for
+/// flavors that use one, and `$$...$$` otherwise, indented like the RPN
+/// block's opening fence so blocks inside list items stay there.
+/// Everything else is copied unchanged.
+///
+/// # Errors
+///
+/// Returns the errors of every snippet `render` fails on, in document order.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::filter::filter_markdown;
+/// use rpn2tex::latex::{LaTeXGenerator, MathEnvironment};
+/// use rpn2tex::markdown::MarkdownFlavor;
+/// use rpn2tex::parser::Parser;
+///
+/// let generator = LaTeXGenerator::builder()
+///     .environment(MathEnvironment::Bare)
+///     .build();
+/// let output = filter_markdown("Sum: `rpn:1 2 +`.\n", MarkdownFlavor::Pandoc, |segment| {
+///     let tokens = segment.lexer().tokenize().map_err(|e| e.to_string())?;
+///     let ast = Parser::new(tokens).parse().map_err(|e| e.to_string())?;
+///     Ok::<_, String>(generator.generate(&ast))
+/// });
+/// assert_eq!(output.unwrap(), "Sum: $1 + 2$.\n");
+/// ```
+pub fn filter_markdown<'a, E>(
+    document: &'a str,
+    flavor: MarkdownFlavor,
+    mut render: impl FnMut(&Segment<'a>) -> Result<String, E>,
+) -> Result<String, Vec<E>> {
+    let mut output = String::with_capacity(document.len());
+    let mut errors = Vec::new();
+    let mut copied = 0;
+    for snippet in find_snippets(document) {
+        output.push_str(&document[copied..snippet.range.start]);
+        copied = snippet.range.end;
+        let block = |open: &str, math: &str, close: &str| {
+            // The opening fence's indentation was copied before it
+            let line_start = document[..snippet.range.start]
+                .rfind('\n')
+                .map_or(0, |newline| newline + 1);
+            let indent = &document[line_start..snippet.range.start];
+            format!("{}\n{}\n{}", open, math, close).replace('\n', &format!("\n{}", indent))
+        };
+        match render(&snippet.segment) {
+            Ok(math) if !snippet.display => output.push_str(&format!("${}$", math)),
+            Ok(math) if flavor.uses_fence() => output.push_str(&block("```math", &math, "```")),
+            Ok(math) => output.push_str(&block("$$", &math, "$$")),
+            Err(error) => errors.push(error),
+        }
+    }
+    output.push_str(&document[copied..]);
+
+    if errors.is_empty() {
+        Ok(output)
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Replaces each snippet with its trimmed text, or fails on `!`.
+    fn echo(document: &str, flavor: MarkdownFlavor) -> Result<String, Vec<u32>> {
+        filter_markdown(document, flavor, |segment| {
+            if segment.text.contains('!') {
+                Err(segment.line)
+            } else {
+                Ok(segment.text.trim().to_string())
+            }
+        })
+    }
+
+    #[test]
+    fn test_blocks_by_flavor() {
+        let document = "# Title\n\n```rpn\n1 2 +\n```\n\nEnd\n";
+        assert_eq!(
+            echo(document, MarkdownFlavor::GitHub).unwrap(),
+            "# Title\n\n```math\n1 2 +\n```\n\nEnd\n"
+        );
+        assert_eq!(
+            echo(document, MarkdownFlavor::Obsidian).unwrap(),
+            "# Title\n\n$$\n1 2 +\n$$\n\nEnd\n"
+        );
+    }
+
+    #[test]
+    fn test_indented_blocks_keep_their_indentation() {
+        let document = "- Sum:\n\n  ```rpn\n  1 2 +\n  ```\n- Next\n";
+        assert_eq!(
+            echo(document, MarkdownFlavor::GitHub).unwrap(),
+            "- Sum:\n\n  ```math\n  1 2 +\n  ```\n- Next\n"
+        );
+        assert_eq!(
+            echo(document, MarkdownFlavor::Obsidian).unwrap(),
+            "- Sum:\n\n  $$\n  1 2 +\n  $$\n- Next\n"
+        );
+    }
+
+    #[test]
+    fn test_other_code_is_untouched() {
+        let document = "```python\nx = `rpn:1`\n```\n~~~\n```rpn\n~~~\n``rpn:2``, `` `rpn:3` ``\n";
+        let snippets = find_snippets(document);
+        assert_eq!(snippets.len(), 1);
+        assert_eq!(snippets[0].segment.text, "2");
+        assert_eq!(
+            echo(document, MarkdownFlavor::GitHub).unwrap(),
+            "```python\nx = `rpn:1`\n```\n~~~\n```rpn\n~~~\n$2$, `` `rpn:3` ``\n"
+        );
+    }
+
+    #[test]
+    fn test_fence_rules() {
+        // A longer closing fence closes; a shorter one is content
+        let snippets = find_snippets("````rpn extra\n1\n```\n2 +\n`````\n");
+        assert_eq!(snippets[0].segment.text, "1\n```\n2 +\n");
+        // Four spaces of indentation make an indented code block instead
+        assert!(find_snippets("    ```rpn\n    1\n    ```\n").is_empty());
+        // An unclosed block runs to the end of the document
+        let snippets = find_snippets("  ```rpn\n1 2 +");
+        assert_eq!(snippets[0].range, 2..14);
+        assert_eq!(snippets[0].segment.text, "1 2 +");
+    }
+
+    #[test]
+    fn test_positions_refer_to_document() {
+        let snippets = find_snippets("é `x` `rpn:1 +`\r\n```rpn\r\n5\r\n```\r\n");
+        assert_eq!(
+            snippets[0].segment,
+            Segment {
+                text: "1 +",
+                offset: 12,
+                line: 1,
+                column: 12,
+            }
+        );
+        assert_eq!(snippets[1].segment.line, 3);
+        assert_eq!(snippets[1].segment.text, "5\r\n");
+        assert_eq!(snippets[1].range, 18..32);
+    }
+
+    #[test]
+    fn test_unmatched_backticks() {
+        assert!(find_snippets("a ` b `rpn:1").is_empty());
+        assert_eq!(find_snippets("`` a `rpn:1`")[0].segment.text, "1");
+    }
+
+    #[test]
+    fn test_errors_are_collected() {
+        let document = "`rpn:!` ok `rpn:1`\n```rpn\n!\n```\n";
+        assert_eq!(echo(document, MarkdownFlavor::Pandoc).unwrap_err(), [1, 3]);
+    }
+}
@@ -0,0 +1,207 @@
+//! Canonical formatting of RPN sources.
+//!
+//! [`format_source`] rewrites an input so equivalent sources read the same:
+//! tokens separated by single spaces, numbers without group separators or
+//! redundant zeros, ASCII operators, `; ` between statements and at most one
+//! blank line in a row. Comments are kept as written, on their own lines or
+//! after the code, so wrapped comment blocks stay wrapped. Formatting is
+//! idempotent: formatting its output changes nothing.
+
+use crate::batch::Segment;
+use crate::error::Rpn2TexError;
+use crate::lexer::Lexer;
+use crate::program::definition;
+use crate::tokens::TokenType;
+
+/// Keywords introducing `name = value` statements.
+const KEYWORDS: [&str; 2] = ["let", "macro"];
+
+/// Rewrites `source` in canonical form.
+///
+/// Each statement is tokenized with the lexer `lexer` returns for it, with
+/// identifiers enabled so names and macros are kept; the statement need not
+/// parse.
+///
+/// # Errors
+///
+/// Returns a [`Rpn2TexError`] if a statement fails to lex, or a `let` or
+/// `macro` statement is malformed. Positions refer to the whole source.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::format::format_source;
+///
+/// let source = "# Totals\n\n\n  5   1_000 +;2 ×  .50 *   # scaled\n";
+/// assert_eq!(
+///     format_source(source, |segment| segment.lexer()).unwrap(),
+///     "# Totals\n\n5 1000 +; 2 * 0.50 * # scaled\n"
+/// );
+/// ```
+pub fn format_source<'a>(
+    source: &'a str,
+    lexer: impl Fn(&Segment<'a>) -> Lexer<'a>,
+) -> Result<String, Rpn2TexError> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut offset = 0;
+    for (index, line) in source.split_inclusive('\n').enumerate() {
+        let text = line.trim_end_matches(['\n', '\r']);
+        let (code, comment) = text.split_at(text.find('#').unwrap_or(text.len()));
+
+        let mut statements = Vec::new();
+        let mut start = 0;
+        for statement in code.split(';') {
+            let segment = Segment {
+                text: statement,
+                offset: offset + start,
+                line: index as u32 + 1,
+                column: code[..start].chars().count() as u32 + 1,
+            };
+            start += statement.len() + 1;
+            if !statement.trim().is_empty() {
+                statements.push(format_statement(&segment, &lexer)?);
+            }
+        }
+
+        let mut formatted = statements.join("; ");
+        let comment = comment.trim_end();
+        if !comment.is_empty() {
+            if !formatted.is_empty() {
+                formatted.push(' ');
+            }
+            formatted.push_str(comment);
+        }
+        // Blank lines are kept singly, and only between other lines
+        let blank = formatted.is_empty();
+        if !blank || lines.last().is_some_and(|last| !last.is_empty()) {
+            lines.push(formatted);
+        }
+        offset += line.len();
+    }
+    while lines.last().is_some_and(String::is_empty) {
+        lines.pop();
+    }
+
+    let mut output = lines.join("\n");
+    if !output.is_empty() {
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+/// Formats one statement: an expression, or a `let` or `macro` definition
+/// of one.
+fn format_statement<'a>(
+    segment: &Segment<'a>,
+    lexer: &impl Fn(&Segment<'a>) -> Lexer<'a>,
+) -> Result<String, Rpn2TexError> {
+    for keyword in KEYWORDS {
+        if let Some((name, _, value)) = definition(segment, keyword)? {
+            let value = format_tokens(&value, lexer)?;
+            return Ok(format!("{} {} = {}", keyword, name, value)
+                .trim_end()
+                .to_string());
+        }
+    }
+    format_tokens(segment, lexer)
+}
+
+/// Returns the tokens of `segment` in canonical form, separated by single
+/// spaces.
+fn format_tokens<'a>(
+    segment: &Segment<'a>,
+    lexer: &impl Fn(&Segment<'a>) -> Lexer<'a>,
+) -> Result<String, Rpn2TexError> {
+    let tokens = lexer(segment).with_identifiers(true).tokenize()?;
+    let words: Vec<String> = tokens
+        .iter()
+        .filter_map(|token| match token.token_type {
+            TokenType::Number => Some(canonical_number(&token.value)),
+            TokenType::Plus => Some("+".to_string()),
+            TokenType::Minus => Some("-".to_string()),
+            TokenType::Mult => Some("*".to_string()),
+            TokenType::Div => Some("/".to_string()),
+            TokenType::Identifier | TokenType::Operator => Some(token.value.to_string()),
+            TokenType::LeftParen => Some("(".to_string()),
+            TokenType::RightParen => Some(")".to_string()),
+            TokenType::Whitespace | TokenType::Comment | TokenType::Eof => None,
+        })
+        .collect();
+    Ok(words.join(" "))
+}
+
+/// Rewrites a number literal, already in ASCII without group separators,
+/// without leading zeros but with one before a bare decimal point. Trailing
+/// zeros are kept, since they state a precision.
+pub(crate) fn canonical_number(value: &str) -> String {
+    let (sign, digits) = match value.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", value),
+    };
+    let (whole, fraction) = match digits.split_once('.') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (digits, None),
+    };
+    let whole = match whole.trim_start_matches('0') {
+        "" => "0",
+        whole => whole,
+    };
+    match fraction {
+        Some(fraction) => format!("{}{}.{}", sign, whole, fraction),
+        None => format!("{}{}", sign, whole),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(source: &str) -> String {
+        format_source(source, |segment| segment.lexer()).unwrap()
+    }
+
+    #[test]
+    fn test_numbers() {
+        assert_eq!(canonical_number("007"), "7");
+        assert_eq!(canonical_number("-.5"), "-0.5");
+        assert_eq!(canonical_number("00.250"), "0.250");
+        assert_eq!(canonical_number("0"), "0");
+        let unicode = format_source("−３ 1_000 −\n", |segment| {
+            segment.lexer().with_unicode_digits(true)
+        });
+        assert_eq!(unicode.unwrap(), "-3 1000 -\n");
+    }
+
+    #[test]
+    fn test_statements_and_names() {
+        assert_eq!(
+            format("macro   sq=dup *\r\nlet a =5 sq ;a   1 +;\n"),
+            "macro sq = dup *\nlet a = 5 sq; a 1 +\n"
+        );
+    }
+
+    #[test]
+    fn test_comments_and_blank_lines() {
+        let source = "\n\n# A comment\n#   wrapped onto two lines;\n\n\n\n1 2 +  #; end  \n\n";
+        assert_eq!(
+            format(source),
+            "# A comment\n#   wrapped onto two lines;\n\n1 2 + #; end\n"
+        );
+        assert_eq!(format(""), "");
+        assert_eq!(format("\n  \n"), "");
+    }
+
+    #[test]
+    fn test_idempotent() {
+        let once = format("let  x = 1 2+ # note\n\n\n x  x*\n");
+        assert_eq!(format(&once), once);
+    }
+
+    #[test]
+    fn test_errors_point_into_source() {
+        let error = format_source("1 2 +\n3; 4 @", |segment| segment.lexer()).unwrap_err();
+        assert_eq!((error.line(), error.column()), (2, 6));
+        let error = format_source("let = 5", |segment| segment.lexer()).unwrap_err();
+        assert!(matches!(error, Rpn2TexError::Binding(_)));
+    }
+}
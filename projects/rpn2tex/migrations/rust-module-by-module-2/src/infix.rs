@@ -0,0 +1,126 @@
+//! Plain infix notation generated from ASTs.
+//!
+//! The output is the everyday way of writing arithmetic, `(5 + 3) * 2`, with
+//! ASCII operators separated by spaces and parentheses only where the
+//! precedence rules shared with the LaTeX backend require them. It reads
+//! back with [`Parser::parse_infix`](crate::parser::Parser::parse_infix)
+//! into an expression of the same value.
+
+use crate::ast::Expr;
+use crate::codegen::CodeGenerator;
+use crate::precedence;
+
+/// Converts AST expressions to infix notation.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::infix::InfixGenerator;
+/// use rpn2tex::lexer::Lexer;
+/// use rpn2tex::parser::Parser;
+///
+/// let parse = |input: &str| Parser::new(Lexer::new(input).tokenize().unwrap()).parse().unwrap();
+/// let generator = InfixGenerator::new();
+/// assert_eq!(generator.generate(&parse("5 3 + 2 *")), "(5 + 3) * 2");
+/// assert_eq!(generator.generate(&parse("10 6 2 - -")), "10 - (6 - 2)");
+/// assert_eq!(generator.generate(&parse("2 -3 *")), "2 * -3");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct InfixGenerator;
+
+impl InfixGenerator {
+    /// Creates an infix generator.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self
+    }
+
+    /// Generates the expression in infix notation.
+    ///
+    /// # Arguments
+    ///
+    /// * `ast` - The root expression node to convert
+    #[must_use]
+    pub fn generate(&self, ast: &Expr) -> String {
+        Self::visit(ast)
+    }
+
+    /// Writes a node, grouping its operands only where required.
+    fn visit(node: &Expr) -> String {
+        match node {
+            Expr::Number { value, .. } => value.clone(),
+            Expr::Variable { name, .. } => name.clone(),
+            Expr::BinaryOp {
+                operator,
+                left,
+                right,
+                ..
+            } => {
+                let mut left_text = Self::visit(left);
+                if precedence::needs_parens(left, operator, false) {
+                    left_text = format!("({})", left_text);
+                }
+
+                let mut right_text = Self::visit(right);
+                if precedence::needs_parens(right, operator, true) {
+                    right_text = format!("({})", right_text);
+                }
+
+                format!("{} {} {}", left_text, operator, right_text)
+            }
+            Expr::Apply {
+                operator, operands, ..
+            } => {
+                let operands: Vec<String> = operands.iter().map(Self::visit).collect();
+                format!("{}({})", operator, operands.join(", "))
+            }
+        }
+    }
+}
+
+impl CodeGenerator for InfixGenerator {
+    fn generate(&self, expr: &Expr) -> String {
+        InfixGenerator::generate(self, expr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::Evaluator;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::test_util::parse;
+
+    fn parse_infix(input: &str) -> Expr {
+        let tokens = Lexer::new(input)
+            .with_identifiers(true)
+            .with_infix(true)
+            .tokenize()
+            .expect("tokenize failed");
+        Parser::new(tokens).parse_infix().expect("parse failed")
+    }
+
+    #[test]
+    fn test_minimal_parentheses() {
+        let generator = InfixGenerator::new();
+        assert_eq!(generator.generate(&parse("42")), "42");
+        assert_eq!(generator.generate(&parse("x 3 4 * +")), "x + 3 * 4");
+        assert_eq!(generator.generate(&parse("8 4 2 * /")), "8 / (4 * 2)");
+        assert_eq!(
+            generator.generate(&parse("1 2 + 3 4 - *")),
+            "(1 + 2) * (3 - 4)"
+        );
+    }
+
+    #[test]
+    fn test_reads_back_to_the_same_value() {
+        let generator = InfixGenerator::new();
+        let evaluator = Evaluator::new();
+        for input in ["5 3 2 - +", "10 2 / 5 *", "7 -2 - 3 /", "1 2 3 4 - - -"] {
+            let ast = parse(input);
+            let round_trip = parse_infix(&generator.generate(&ast));
+            assert_eq!(evaluator.evaluate(&round_trip), evaluator.evaluate(&ast));
+        }
+    }
+}
@@ -3,8 +3,521 @@
 //! This module converts AST nodes to LaTeX source code with proper operator
 //! precedence handling and parenthesization. The generator ensures that
 //! expressions are rendered correctly based on mathematical precedence rules.
+//!
+//! Text from users placed in the output goes through [`escape`], or
+//! [`escape_math`] for names set in math mode such as variables and
+//! operators, so it cannot break the document.
+
+use crate::ast::{Expr, Structural};
+use crate::codegen::CodeGenerator;
+use crate::number_format::NumberFormat;
+use crate::plugin::OperatorTable;
+use crate::precedence;
+use std::collections::HashMap;
+
+/// Text written in place of parts of the tree while visiting it.
+#[derive(Default)]
+struct Substitutions<'a> {
+    /// Macro names standing in for repeated subexpressions, keyed by structure
+    macros: HashMap<Structural<'a>, String>,
+    /// A node whose own token is hidden, and the blank written instead
+    blank: Option<(&'a Expr, &'a str)>,
+    /// Custom operators writing their own [`Expr::Apply`] nodes
+    operators: Option<&'a OperatorTable>,
+}
+
+/// The math-mode wrapper placed around generated expressions.
+///
+/// `AlignStar` and `Gather` require the `amsmath` package.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum MathEnvironment {
+    /// Inline math: `$...$` (the default)
+    #[default]
+    Inline,
+    /// Numbered display equation: `\begin{equation}...\end{equation}`
+    Equation,
+    /// Unnumbered aligned display: `\begin{align*}...\end{align*}`
+    AlignStar,
+    /// Numbered gathered display: `\begin{gather}...\end{gather}`
+    Gather,
+    /// Unnumbered display math: `\[...\]`
+    DisplayBrackets,
+    /// Plain TeX display math: `$$...$$`
+    DisplayDollars,
+    /// No delimiters at all, for embedding in templates that supply their own
+    Bare,
+}
+
+impl MathEnvironment {
+    /// Returns the LaTeX environment name, or `None` for math that is
+    /// delimited without a `\begin`/`\end` pair.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::latex::MathEnvironment;
+    ///
+    /// assert_eq!(MathEnvironment::AlignStar.name(), Some("align*"));
+    /// assert_eq!(MathEnvironment::Inline.name(), None);
+    /// assert_eq!(MathEnvironment::DisplayBrackets.name(), None);
+    /// ```
+    #[must_use]
+    pub const fn name(self) -> Option<&'static str> {
+        match self {
+            Self::Inline | Self::DisplayBrackets | Self::DisplayDollars | Self::Bare => None,
+            Self::Equation => Some("equation"),
+            Self::AlignStar => Some("align*"),
+            Self::Gather => Some("gather"),
+        }
+    }
+}
+
+/// Whitespace placed around operators and inside parentheses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum SpacingStyle {
+    /// No spaces at all: `(5+3)\times2`
+    Tight,
+    /// Spaces around operators only: `(5 + 3) \times 2`
+    Normal,
+    /// Spaces around operators and inside parentheses: `( 5 + 3 ) \times 2`
+    /// (the default)
+    #[default]
+    Spaced,
+}
+
+/// How parentheses around grouped subexpressions are sized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ParenSizing {
+    /// Plain `( ... )` at text height (the default)
+    #[default]
+    Fixed,
+    /// `\left( ... \right)` around every group
+    Auto,
+    /// `\left( ... \right)` only around groups containing a construct
+    /// taller than a text line; other groups use plain parentheses
+    AutoTall,
+}
+
+/// Symbol used for multiplication.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum MulStyle {
+    /// A cross: `4 \times 7` (the default)
+    #[default]
+    Times,
+    /// A centered dot: `4 \cdot 7`
+    Cdot,
+}
+
+impl MulStyle {
+    /// Returns the LaTeX command for this multiplication symbol.
+    #[must_use]
+    pub const fn tex(self) -> &'static str {
+        match self {
+            Self::Times => r"\times",
+            Self::Cdot => r"\cdot",
+        }
+    }
+}
+
+/// Fraction command used when division is written as a fraction.
+///
+/// `Dfrac` and `Tfrac` require the `amsmath` package.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum FracStyle {
+    /// `\frac`, sized by the surrounding math style (the default)
+    #[default]
+    Frac,
+    /// `\dfrac`, always at display size
+    Dfrac,
+    /// `\tfrac`, always at text size
+    Tfrac,
+}
+
+impl FracStyle {
+    /// Returns the LaTeX command for this fraction style.
+    #[must_use]
+    pub const fn tex(self) -> &'static str {
+        match self {
+            Self::Frac => r"\frac",
+            Self::Dfrac => r"\dfrac",
+            Self::Tfrac => r"\tfrac",
+        }
+    }
+}
+
+/// Options for a [`LaTeXGenerator`], assembled with builder methods.
+///
+/// Obtain one from [`LaTeXGenerator::builder`] (or from an existing
+/// generator's [`config`](LaTeXGenerator::config)), chain the options to
+/// change, and finish with [`build`](Self::build). Options not mentioned keep
+/// the defaults of [`LaTeXGenerator::new`].
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::ast::Expr;
+/// use rpn2tex::latex::{LaTeXGenerator, MulStyle};
+/// use rpn2tex::span::Span;
+///
+/// let number = |value: &str| Expr::Number {
+///     span: Span::new(0, 0, 1, 1),
+///     value: value.to_string(),
+/// };
+/// // 1 / 2 * 3
+/// let expr = Expr::BinaryOp {
+///     span: Span::new(6, 7, 1, 7),
+///     operator: "*".to_string(),
+///     left: Box::new(Expr::BinaryOp {
+///         span: Span::new(4, 5, 1, 5),
+///         operator: "/".to_string(),
+///         left: Box::new(number("1")),
+///         right: Box::new(number("2")),
+///     }),
+///     right: Box::new(number("3")),
+/// };
+///
+/// let generator = LaTeXGenerator::builder()
+///     .mul_style(MulStyle::Cdot)
+///     .frac(true)
+///     .display_math(true)
+///     .build();
+/// assert_eq!(generator.generate(&expr), r"\[\frac{1}{2} \cdot 3\]");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GeneratorConfig {
+    /// Math-mode wrapper placed around the expression
+    environment: MathEnvironment,
+    /// Restrict output to constructs KaTeX can render
+    katex: bool,
+    /// Whitespace around operators and inside parentheses
+    spacing: SpacingStyle,
+    /// Whether grouping parentheses stretch to fit their content
+    paren_sizing: ParenSizing,
+    /// How number literals are written
+    number_format: NumberFormat,
+    /// Wrap negative literals that appear as right operands: `10 + (-5)`
+    negative_parens: bool,
+    /// Wrap every number literal in siunitx's `\num{...}`
+    siunitx: bool,
+    /// Symbol used for multiplication
+    mul_style: MulStyle,
+    /// Write division as `\frac{...}{...}` instead of `\div`
+    frac: bool,
+    /// Fraction command used when `frac` is on
+    frac_style: FracStyle,
+}
+
+impl GeneratorConfig {
+    /// Creates a configuration with the default options.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            environment: MathEnvironment::Inline,
+            katex: false,
+            spacing: SpacingStyle::Spaced,
+            paren_sizing: ParenSizing::Fixed,
+            number_format: NumberFormat::new(),
+            negative_parens: true,
+            siunitx: false,
+            mul_style: MulStyle::Times,
+            frac: false,
+            frac_style: FracStyle::Frac,
+        }
+    }
+
+    /// Wraps output in `environment`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::ast::Expr;
+    /// use rpn2tex::latex::{LaTeXGenerator, MathEnvironment};
+    /// use rpn2tex::span::Span;
+    ///
+    /// let generator = LaTeXGenerator::builder()
+    ///     .environment(MathEnvironment::Equation)
+    ///     .build();
+    /// let num = Expr::Number {
+    ///     span: Span::new(0, 2, 1, 1),
+    ///     value: "42".to_string(),
+    /// };
+    /// assert_eq!(
+    ///     generator.generate(&num),
+    ///     "\\begin{equation}\n42\n\\end{equation}"
+    /// );
+    /// ```
+    #[must_use]
+    pub const fn environment(mut self, environment: MathEnvironment) -> Self {
+        self.environment = environment;
+        self
+    }
+
+    /// Chooses between display math (`\[...\]`) and inline math.
+    ///
+    /// Shorthand for [`environment`](Self::environment) with
+    /// [`MathEnvironment::DisplayBrackets`] or [`MathEnvironment::Inline`].
+    #[must_use]
+    pub const fn display_math(self, display_math: bool) -> Self {
+        if display_math {
+            self.environment(MathEnvironment::DisplayBrackets)
+        } else {
+            self.environment(MathEnvironment::Inline)
+        }
+    }
+
+    /// Restricts output to what KaTeX can render.
+    ///
+    /// In KaTeX mode, inline math is delimited with `\(...\)` instead of
+    /// `$...$`, because KaTeX's auto-render extension does not recognize
+    /// single dollar signs by default. Constructs that KaTeX renders
+    /// differently from LaTeX are reported by
+    /// [`katex_warnings`](LaTeXGenerator::katex_warnings).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::ast::Expr;
+    /// use rpn2tex::latex::LaTeXGenerator;
+    /// use rpn2tex::span::Span;
+    ///
+    /// let generator = LaTeXGenerator::builder().katex(true).build();
+    /// let num = Expr::Number {
+    ///     span: Span::new(0, 2, 1, 1),
+    ///     value: "42".to_string(),
+    /// };
+    /// assert_eq!(generator.generate(&num), r"\(42\)");
+    /// ```
+    #[must_use]
+    pub const fn katex(mut self, katex: bool) -> Self {
+        self.katex = katex;
+        self
+    }
+
+    /// Lays out whitespace in `spacing`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::ast::Expr;
+    /// use rpn2tex::latex::{LaTeXGenerator, SpacingStyle};
+    /// use rpn2tex::span::Span;
+    ///
+    /// let number = |value: &str| Expr::Number {
+    ///     span: Span::new(0, 0, 1, 1),
+    ///     value: value.to_string(),
+    /// };
+    /// let sum = Expr::BinaryOp {
+    ///     span: Span::new(4, 5, 1, 5),
+    ///     operator: "+".to_string(),
+    ///     left: Box::new(number("5")),
+    ///     right: Box::new(number("3")),
+    /// };
+    /// let product = Expr::BinaryOp {
+    ///     span: Span::new(8, 9, 1, 9),
+    ///     operator: "*".to_string(),
+    ///     left: Box::new(sum),
+    ///     right: Box::new(number("2")),
+    /// };
+    ///
+    /// let tight = LaTeXGenerator::builder().spacing(SpacingStyle::Tight).build();
+    /// assert_eq!(tight.generate(&product), r"$(5+3)\times2$");
+    ///
+    /// let normal = LaTeXGenerator::builder().spacing(SpacingStyle::Normal).build();
+    /// assert_eq!(normal.generate(&product), r"$(5 + 3) \times 2$");
+    /// ```
+    #[must_use]
+    pub const fn spacing(mut self, spacing: SpacingStyle) -> Self {
+        self.spacing = spacing;
+        self
+    }
+
+    /// Sizes grouping parentheses with `paren_sizing`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::ast::Expr;
+    /// use rpn2tex::latex::{LaTeXGenerator, ParenSizing};
+    /// use rpn2tex::span::Span;
+    ///
+    /// let number = |value: &str| Expr::Number {
+    ///     span: Span::new(0, 0, 1, 1),
+    ///     value: value.to_string(),
+    /// };
+    /// let product = Expr::BinaryOp {
+    ///     span: Span::new(8, 9, 1, 9),
+    ///     operator: "*".to_string(),
+    ///     left: Box::new(Expr::BinaryOp {
+    ///         span: Span::new(4, 5, 1, 5),
+    ///         operator: "+".to_string(),
+    ///         left: Box::new(number("5")),
+    ///         right: Box::new(number("3")),
+    ///     }),
+    ///     right: Box::new(number("2")),
+    /// };
+    ///
+    /// let generator = LaTeXGenerator::builder()
+    ///     .paren_sizing(ParenSizing::Auto)
+    ///     .build();
+    /// assert_eq!(
+    ///     generator.generate(&product),
+    ///     r"$\left( 5 + 3 \right) \times 2$"
+    /// );
+    /// ```
+    #[must_use]
+    pub const fn paren_sizing(mut self, paren_sizing: ParenSizing) -> Self {
+        self.paren_sizing = paren_sizing;
+        self
+    }
+
+    /// Writes number literals with `number_format`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::ast::Expr;
+    /// use rpn2tex::latex::LaTeXGenerator;
+    /// use rpn2tex::number_format::{NumberFormat, ThousandsSeparator};
+    /// use rpn2tex::span::Span;
+    ///
+    /// let format = NumberFormat::new().with_thousands(ThousandsSeparator::Comma);
+    /// let generator = LaTeXGenerator::builder().number_format(format).build();
+    /// let num = Expr::Number {
+    ///     span: Span::new(0, 7, 1, 1),
+    ///     value: "1000000".to_string(),
+    /// };
+    /// assert_eq!(generator.generate(&num), "$1{,}000{,}000$");
+    /// ```
+    #[must_use]
+    pub const fn number_format(mut self, number_format: NumberFormat) -> Self {
+        self.number_format = number_format;
+        self
+    }
+
+    /// Parenthesizes (`true`, the default) or leaves bare negative literals
+    /// on the right of an operator.
+    ///
+    /// `10 + -5` reads as a typo when typeset; `10 + (-5)` does not.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::ast::Expr;
+    /// use rpn2tex::latex::LaTeXGenerator;
+    /// use rpn2tex::span::Span;
+    ///
+    /// let sum = Expr::BinaryOp {
+    ///     span: Span::new(6, 7, 1, 7),
+    ///     operator: "+".to_string(),
+    ///     left: Box::new(Expr::Number {
+    ///         span: Span::new(0, 2, 1, 1),
+    ///         value: "10".to_string(),
+    ///     }),
+    ///     right: Box::new(Expr::Number {
+    ///         span: Span::new(3, 5, 1, 4),
+    ///         value: "-5".to_string(),
+    ///     }),
+    /// };
+    /// assert_eq!(LaTeXGenerator::new().generate(&sum), "$10 + (-5)$");
+    ///
+    /// let bare = LaTeXGenerator::builder().negative_parens(false).build();
+    /// assert_eq!(bare.generate(&sum), "$10 + -5$");
+    /// ```
+    #[must_use]
+    pub const fn negative_parens(mut self, negative_parens: bool) -> Self {
+        self.negative_parens = negative_parens;
+        self
+    }
+
+    /// Wraps every number literal in siunitx's `\num{...}`.
+    ///
+    /// siunitx then applies the document's own digit grouping and decimal
+    /// marker, so only the [`Precision`](crate::number_format::Precision) of
+    /// the number format is applied; its separators are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::ast::Expr;
+    /// use rpn2tex::latex::LaTeXGenerator;
+    /// use rpn2tex::span::Span;
+    ///
+    /// let generator = LaTeXGenerator::builder().siunitx(true).build();
+    /// let num = Expr::Number {
+    ///     span: Span::new(0, 6, 1, 1),
+    ///     value: "1234.5".to_string(),
+    /// };
+    /// assert_eq!(generator.generate(&num), r"$\num{1234.5}$");
+    /// ```
+    #[must_use]
+    pub const fn siunitx(mut self, siunitx: bool) -> Self {
+        self.siunitx = siunitx;
+        self
+    }
+
+    /// Writes multiplication with the `mul_style` symbol.
+    #[must_use]
+    pub const fn mul_style(mut self, mul_style: MulStyle) -> Self {
+        self.mul_style = mul_style;
+        self
+    }
+
+    /// Writes division as a stacked `\frac{...}{...}` (`true`) or with the
+    /// inline `\div` operator (the default).
+    ///
+    /// Numerator and denominator are grouped by the fraction itself, so
+    /// neither is parenthesized.
+    #[must_use]
+    pub const fn frac(mut self, frac: bool) -> Self {
+        self.frac = frac;
+        self
+    }
+
+    /// Chooses the fraction command used when [`frac`](Self::frac) is on.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::ast::Expr;
+    /// use rpn2tex::latex::{FracStyle, LaTeXGenerator};
+    /// use rpn2tex::span::Span;
+    ///
+    /// let half = Expr::BinaryOp {
+    ///     span: Span::new(4, 5, 1, 5),
+    ///     operator: "/".to_string(),
+    ///     left: Box::new(Expr::Number {
+    ///         span: Span::new(0, 1, 1, 1),
+    ///         value: "1".to_string(),
+    ///     }),
+    ///     right: Box::new(Expr::Number {
+    ///         span: Span::new(2, 3, 1, 3),
+    ///         value: "2".to_string(),
+    ///     }),
+    /// };
+    /// let generator = LaTeXGenerator::builder()
+    ///     .frac(true)
+    ///     .frac_style(FracStyle::Dfrac)
+    ///     .build();
+    /// assert_eq!(generator.generate(&half), r"$\dfrac{1}{2}$");
+    /// ```
+    #[must_use]
+    pub const fn frac_style(mut self, frac_style: FracStyle) -> Self {
+        self.frac_style = frac_style;
+        self
+    }
+
+    /// Creates a generator with this configuration.
+    #[must_use]
+    pub const fn build(self) -> LaTeXGenerator {
+        LaTeXGenerator { config: self }
+    }
+}
 
-use crate::ast::Expr;
+impl Default for GeneratorConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 /// Converts AST expressions to LaTeX source code.
 ///
@@ -12,27 +525,28 @@ use crate::ast::Expr;
 /// necessary to preserve the correct evaluation order. The output is formatted
 /// in LaTeX math mode with proper spacing.
 ///
+/// [`LaTeXGenerator::new`] uses sensible defaults; use
+/// [`LaTeXGenerator::builder`] to change them.
+///
 /// # Examples
 ///
 /// ```
 /// use rpn2tex::ast::Expr;
 /// use rpn2tex::latex::LaTeXGenerator;
+/// use rpn2tex::span::Span;
 ///
 /// let generator = LaTeXGenerator::new();
 ///
 /// // Simple addition: 5 + 3
 /// let expr = Expr::BinaryOp {
-///     line: 1,
-///     column: 5,
+///     span: Span::new(4, 5, 1, 5),
 ///     operator: "+".to_string(),
 ///     left: Box::new(Expr::Number {
-///         line: 1,
-///         column: 1,
+///         span: Span::new(0, 1, 1, 1),
 ///         value: "5".to_string(),
 ///     }),
 ///     right: Box::new(Expr::Number {
-///         line: 1,
-///         column: 3,
+///         span: Span::new(2, 3, 1, 3),
 ///         value: "3".to_string(),
 ///     }),
 /// };
@@ -40,161 +554,758 @@ use crate::ast::Expr;
 /// let latex = generator.generate(&expr);
 /// assert_eq!(latex, "$5 + 3$");
 /// ```
-#[derive(Debug, Clone, Copy, Default)]
-pub struct LaTeXGenerator;
+#[derive(Debug, Clone, Copy)]
+pub struct LaTeXGenerator {
+    /// Options controlling the output
+    config: GeneratorConfig,
+}
+
+impl LaTeXGenerator {
+    /// Creates a new LaTeX generator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::latex::LaTeXGenerator;
+    ///
+    /// let generator = LaTeXGenerator::new();
+    /// ```
+    #[must_use]
+    pub const fn new() -> Self {
+        GeneratorConfig::new().build()
+    }
+
+    /// Starts configuring a generator from the default options.
+    ///
+    /// See [`GeneratorConfig`] for the available options.
+    #[must_use]
+    pub const fn builder() -> GeneratorConfig {
+        GeneratorConfig::new()
+    }
+
+    /// Returns this generator's configuration, which can be adjusted and
+    /// rebuilt into a new generator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::latex::{LaTeXGenerator, SpacingStyle};
+    ///
+    /// let katex = LaTeXGenerator::builder().katex(true).build();
+    /// let tight_katex = katex.config().spacing(SpacingStyle::Tight).build();
+    /// assert!(tight_katex.katex());
+    /// ```
+    #[must_use]
+    pub const fn config(&self) -> GeneratorConfig {
+        self.config
+    }
+
+    /// Returns the math-mode wrapper this generator uses.
+    #[must_use]
+    pub const fn environment(&self) -> MathEnvironment {
+        self.config.environment
+    }
+
+    /// Returns whether output is restricted to what KaTeX can render.
+    #[must_use]
+    pub const fn katex(&self) -> bool {
+        self.config.katex
+    }
+
+    /// Returns the whitespace style this generator uses.
+    #[must_use]
+    pub const fn spacing(&self) -> SpacingStyle {
+        self.config.spacing
+    }
+
+    /// Returns how this generator sizes grouping parentheses.
+    #[must_use]
+    pub const fn paren_sizing(&self) -> ParenSizing {
+        self.config.paren_sizing
+    }
+
+    /// Returns the format used for number literals.
+    #[must_use]
+    pub const fn number_format(&self) -> NumberFormat {
+        self.config.number_format
+    }
+
+    /// Returns whether negative right operands are parenthesized.
+    #[must_use]
+    pub const fn negative_parens(&self) -> bool {
+        self.config.negative_parens
+    }
+
+    /// Returns whether number literals are wrapped in `\num{...}`.
+    #[must_use]
+    pub const fn siunitx(&self) -> bool {
+        self.config.siunitx
+    }
+
+    /// Returns the symbol used for multiplication.
+    #[must_use]
+    pub const fn mul_style(&self) -> MulStyle {
+        self.config.mul_style
+    }
+
+    /// Returns whether division is written as `\frac{...}{...}`.
+    #[must_use]
+    pub const fn frac(&self) -> bool {
+        self.config.frac
+    }
+
+    /// Returns the fraction command used when division is written as a
+    /// fraction.
+    #[must_use]
+    pub const fn frac_style(&self) -> FracStyle {
+        self.config.frac_style
+    }
+
+    /// Describes configured features that have no exact KaTeX equivalent.
+    ///
+    /// Returns an empty list when KaTeX mode is off or when the output will
+    /// render in KaTeX exactly as it would in LaTeX.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::latex::{LaTeXGenerator, MathEnvironment};
+    ///
+    /// let generator = LaTeXGenerator::builder()
+    ///     .katex(true)
+    ///     .environment(MathEnvironment::Equation)
+    ///     .build();
+    /// assert_eq!(generator.katex_warnings().len(), 1);
+    /// ```
+    #[must_use]
+    pub fn katex_warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if !self.config.katex {
+            return warnings;
+        }
+
+        if matches!(
+            self.config.environment,
+            MathEnvironment::Equation | MathEnvironment::Gather
+        ) {
+            warnings.push(format!(
+                "KaTeX does not number equations; the {} environment will render unnumbered",
+                self.config.environment.name().unwrap_or_default()
+            ));
+        }
+        if self.config.siunitx {
+            warnings.push("KaTeX does not support siunitx; \\num{...} will not render".to_string());
+        }
+
+        warnings
+    }
+
+    /// Generates LaTeX source code from an AST expression.
+    ///
+    /// The output is wrapped in the configured math environment (inline
+    /// `$...$` by default) and includes proper operator symbols and
+    /// parenthesization. Display environments place the expression on its
+    /// own line between `\begin{...}` and `\end{...}`; the `\[...\]` and
+    /// `$$...$$` forms keep it on one line, and bare output has no delimiters.
+    ///
+    /// # Arguments
+    ///
+    /// * `ast` - The root expression node to convert
+    ///
+    /// # Returns
+    ///
+    /// A string containing the LaTeX representation, wrapped in the math
+    /// environment
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::ast::Expr;
+    /// use rpn2tex::latex::LaTeXGenerator;
+    /// use rpn2tex::span::Span;
+    ///
+    /// let generator = LaTeXGenerator::new();
+    ///
+    /// // Number literal
+    /// let num = Expr::Number {
+    ///     span: Span::new(0, 2, 1, 1),
+    ///     value: "42".to_string(),
+    /// };
+    /// assert_eq!(generator.generate(&num), "$42$");
+    ///
+    /// // Multiplication
+    /// let mult = Expr::BinaryOp {
+    ///     span: Span::new(4, 5, 1, 5),
+    ///     operator: "*".to_string(),
+    ///     left: Box::new(Expr::Number {
+    ///         span: Span::new(0, 1, 1, 1),
+    ///         value: "4".to_string(),
+    ///     }),
+    ///     right: Box::new(Expr::Number {
+    ///         span: Span::new(2, 3, 1, 3),
+    ///         value: "7".to_string(),
+    ///     }),
+    /// };
+    /// assert_eq!(generator.generate(&mult), r"$4 \times 7$");
+    /// ```
+    #[must_use]
+    pub fn generate(&self, ast: &Expr) -> String {
+        self.wrap_math(&self.render_math(ast))
+    }
+
+    /// Generates LaTeX like [`generate`](Self::generate), writing each
+    /// custom operator with its plugin from `operators`.
+    ///
+    /// Without a plugin, as in `generate`, a custom operator is written as
+    /// a function: `\operatorname{mod}(7, 3)`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::latex::LaTeXGenerator;
+    /// use rpn2tex::lexer::Lexer;
+    /// use rpn2tex::parser::Parser;
+    /// use rpn2tex::plugin::{OperatorPlugin, OperatorTable};
+    ///
+    /// struct Modulo;
+    ///
+    /// impl OperatorPlugin for Modulo {
+    ///     fn spelling(&self) -> &str {
+    ///         "mod"
+    ///     }
+    ///
+    ///     fn precedence(&self) -> Option<i32> {
+    ///         Some(2)
+    ///     }
+    ///
+    ///     fn latex(&self, operands: &[String]) -> String {
+    ///         format!(r"{} \bmod {}", operands[0], operands[1])
+    ///     }
+    /// }
+    ///
+    /// let operators = OperatorTable::new().with_operator(Modulo);
+    /// let tokens = Lexer::new("7 1 + 3 mod").with_operators(operators.clone()).tokenize().unwrap();
+    /// let ast = Parser::new(tokens).with_operators(operators.clone()).parse().unwrap();
+    /// let generator = LaTeXGenerator::new();
+    /// assert_eq!(generator.generate_with_operators(&ast, &operators), r"$( 7 + 1 ) \bmod 3$");
+    /// assert_eq!(generator.generate(&ast), r"$\operatorname{mod}(7 + 1, 3)$");
+    /// ```
+    #[must_use]
+    pub fn generate_with_operators(&self, ast: &Expr, operators: &OperatorTable) -> String {
+        let substitutions = Substitutions {
+            operators: Some(operators),
+            ..Substitutions::default()
+        };
+        self.wrap_math(&self.visit(ast, &substitutions))
+    }
+
+    /// Generates LaTeX for an expression followed by `= result`, inside the
+    /// same math environment.
+    ///
+    /// The result is written like a number literal, so the configured number
+    /// format (separators, rounding, siunitx) applies to it as well.
+    ///
+    /// # Arguments
+    ///
+    /// * `ast` - The root expression node to convert
+    /// * `result` - The value of the expression
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::eval::evaluate;
+    /// use rpn2tex::latex::LaTeXGenerator;
+    /// use rpn2tex::lexer::Lexer;
+    /// use rpn2tex::number_format::{NumberFormat, ThousandsSeparator};
+    /// use rpn2tex::parser::Parser;
+    ///
+    /// let tokens = Lexer::new("5 3 + 2 *").tokenize().unwrap();
+    /// let ast = Parser::new(tokens).parse().unwrap();
+    /// let value = evaluate(&ast).unwrap();
+    /// assert_eq!(
+    ///     LaTeXGenerator::new().generate_with_result(&ast, value),
+    ///     r"$( 5 + 3 ) \times 2 = 16$"
+    /// );
+    ///
+    /// let grouped = LaTeXGenerator::builder()
+    ///     .number_format(NumberFormat::new().with_thousands(ThousandsSeparator::ThinSpace))
+    ///     .build();
+    /// let tokens = Lexer::new("1200 1 +").tokenize().unwrap();
+    /// let ast = Parser::new(tokens).parse().unwrap();
+    /// assert_eq!(grouped.generate_with_result(&ast, 1201.0), r"$1\,200 + 1 = 1\,201$");
+    /// ```
+    #[must_use]
+    pub fn generate_with_result(&self, ast: &Expr, result: f64) -> String {
+        // Negative zero would otherwise be written as "-0"
+        let result = if result == 0.0 { 0.0 } else { result };
+        let equals = match self.config.spacing {
+            SpacingStyle::Tight => "=",
+            SpacingStyle::Normal | SpacingStyle::Spaced => " = ",
+        };
+        let inner = format!(
+            "{}{}{}",
+            self.render_math(ast),
+            equals,
+            self.visit_number(&result.to_string())
+        );
+        self.wrap_math(&inner)
+    }
+
+    /// Wraps rendered math in the configured environment's delimiters.
+    pub(crate) fn wrap_math(&self, inner: &str) -> String {
+        match self.config.environment {
+            MathEnvironment::Inline if self.config.katex => format!("\\({}\\)", inner),
+            MathEnvironment::Inline => format!("${}$", inner),
+            MathEnvironment::DisplayBrackets => format!("\\[{}\\]", inner),
+            MathEnvironment::DisplayDollars => format!("$${}$$", inner),
+            MathEnvironment::Bare => inner.to_string(),
+            MathEnvironment::Equation | MathEnvironment::AlignStar | MathEnvironment::Gather => {
+                let name = self.config.environment.name().unwrap_or_default();
+                format!("\\begin{{{}}}\n{}\n\\end{{{}}}", name, inner, name)
+            }
+        }
+    }
+
+    /// Generates a complete, compilable LaTeX document for an AST expression.
+    ///
+    /// The document uses the `standalone` class so the compiled page is
+    /// cropped to the expression, loads the packages the output depends on,
+    /// and contains the result of [`generate`](Self::generate) as its body.
+    /// Display environments add the `varwidth` class option, which
+    /// `standalone` needs to typeset display math.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::ast::Expr;
+    /// use rpn2tex::latex::LaTeXGenerator;
+    /// use rpn2tex::span::Span;
+    ///
+    /// let num = Expr::Number {
+    ///     span: Span::new(0, 2, 1, 1),
+    ///     value: "42".to_string(),
+    /// };
+    /// let document = LaTeXGenerator::new().generate_document(&num);
+    /// assert_eq!(
+    ///     document,
+    ///     "\\documentclass{standalone}\n\\begin{document}\n$42$\n\\end{document}\n"
+    /// );
+    /// ```
+    #[must_use]
+    pub fn generate_document(&self, ast: &Expr) -> String {
+        let mut document = String::new();
+        if self.config.environment == MathEnvironment::Inline {
+            document.push_str("\\documentclass{standalone}\n");
+        } else {
+            document.push_str("\\documentclass[varwidth]{standalone}\n");
+        }
+        for package in self.required_packages() {
+            document.push_str(&format!("\\usepackage{{{}}}\n", package));
+        }
+        document.push_str("\\begin{document}\n");
+        document.push_str(&self.generate(ast));
+        document.push_str("\n\\end{document}\n");
+        document
+    }
+
+    /// Renders the math-mode body of an expression without any delimiters.
+    ///
+    /// Used by backends that share TeX math syntax but wrap it differently.
+    pub(crate) fn render_math(&self, ast: &Expr) -> String {
+        self.visit(ast, &Substitutions::default())
+    }
+
+    /// Renders the math-mode body of an expression with the token of
+    /// `hidden`, a node of `ast`, replaced by `blank`: a number or variable
+    /// entirely, an operation only its operator.
+    pub(crate) fn render_blanked(&self, ast: &Expr, hidden: &Expr, blank: &str) -> String {
+        let substitutions = Substitutions {
+            blank: Some((hidden, blank)),
+            ..Substitutions::default()
+        };
+        self.visit(ast, &substitutions)
+    }
 
-impl LaTeXGenerator {
-    /// Creates a new LaTeX generator.
+    /// Generates LaTeX in which every repeated subexpression is defined once
+    /// with `\newcommand` and referenced by name.
+    ///
+    /// The definitions come first, one per line, followed by the result of
+    /// [`generate`](Self::generate) with the repeated parts replaced by
+    /// macros `\rpnA`, `\rpnB`, .... An operation is repeated if it occurs
+    /// more than once, ignoring positions; occurrences inside another
+    /// repeated subexpression count once. A definition may use earlier
+    /// macros. Without repetition the output equals `generate`.
     ///
     /// # Examples
     ///
     /// ```
     /// use rpn2tex::latex::LaTeXGenerator;
+    /// use rpn2tex::lexer::Lexer;
+    /// use rpn2tex::parser::Parser;
     ///
-    /// let generator = LaTeXGenerator::new();
+    /// let tokens = Lexer::new("x 1 + x 1 + * 2 /").with_identifiers(true).tokenize().unwrap();
+    /// let ast = Parser::new(tokens).parse().unwrap();
+    /// assert_eq!(
+    ///     LaTeXGenerator::new().generate_with_macros(&ast),
+    ///     "\\newcommand{\\rpnA}{x + 1}\n$( \\rpnA ) \\times ( \\rpnA ) \\div 2$"
+    /// );
     /// ```
     #[must_use]
-    pub const fn new() -> Self {
-        Self
+    pub fn generate_with_macros(&self, ast: &Expr) -> String {
+        let mut substitutions = Substitutions::default();
+        let mut output = String::new();
+        for (index, node) in Self::repeated_subexpressions(ast).into_iter().enumerate() {
+            let Expr::BinaryOp {
+                operator,
+                left,
+                right,
+                ..
+            } = node
+            else {
+                continue;
+            };
+            let name = format!("\\rpn{}", Self::macro_suffix(index));
+            let body = self.visit_binary_op(operator, None, left, right, &substitutions);
+            output.push_str(&format!("\\newcommand{{{}}}{{{}}}\n", name, body));
+            substitutions.macros.insert(Structural(node), name);
+        }
+        output.push_str(&self.wrap_math(&self.visit(ast, &substitutions)));
+        output
     }
 
-    /// Generates LaTeX source code from an AST expression.
-    ///
-    /// The output is wrapped in LaTeX inline math mode delimiters (`$...$`)
-    /// and includes proper operator symbols and parenthesization.
-    ///
-    /// # Arguments
-    ///
-    /// * `ast` - The root expression node to convert
+    /// Returns the operations worth naming in `ast`, each after the ones it
+    /// contains.
     ///
-    /// # Returns
+    /// Only the first copy of a repeated subtree is searched, so parts that
+    /// repeat merely because their parent does are not named separately.
+    fn repeated_subexpressions(ast: &Expr) -> Vec<&Expr> {
+        fn walk<'a>(
+            node: &'a Expr,
+            seen: &mut HashMap<Structural<'a>, usize>,
+            order: &mut Vec<&'a Expr>,
+        ) {
+            let (left, right) = match node {
+                Expr::BinaryOp { left, right, .. } => (left, right),
+                // Only the operands of a custom operator are named
+                Expr::Apply { operands, .. } => {
+                    for operand in operands {
+                        walk(operand, seen, order);
+                    }
+                    return;
+                }
+                Expr::Number { .. } | Expr::Variable { .. } => return,
+            };
+            let count = seen.entry(Structural(node)).or_insert(0);
+            *count += 1;
+            if *count > 1 {
+                return;
+            }
+            walk(left, seen, order);
+            walk(right, seen, order);
+            order.push(node);
+        }
+
+        let mut seen = HashMap::new();
+        let mut order = Vec::new();
+        walk(ast, &mut seen, &mut order);
+        order.retain(|node| seen[&Structural(node)] > 1);
+        order
+    }
+
+    /// Returns the letters naming the macro at `index`: `A` to `Z`, then
+    /// `AA`, `AB`, ... (TeX control words cannot contain digits).
+    fn macro_suffix(index: usize) -> String {
+        let mut suffix = Vec::new();
+        let mut rest = index + 1;
+        while rest > 0 {
+            rest -= 1;
+            suffix.push(char::from(b'A' + (rest % 26) as u8));
+            rest /= 26;
+        }
+        suffix.iter().rev().collect()
+    }
+
+    /// Returns the LaTeX packages the generated output depends on, in the
+    /// order they should be loaded.
     ///
-    /// A string containing the LaTeX representation, wrapped in `$...$`
+    /// The list depends only on the configuration, so it can be used to
+    /// assemble a preamble before any expression is converted.
     ///
     /// # Examples
     ///
     /// ```
-    /// use rpn2tex::ast::Expr;
-    /// use rpn2tex::latex::LaTeXGenerator;
+    /// use rpn2tex::latex::{LaTeXGenerator, MathEnvironment};
     ///
-    /// let generator = LaTeXGenerator::new();
-    ///
-    /// // Number literal
-    /// let num = Expr::Number {
-    ///     line: 1,
-    ///     column: 1,
-    ///     value: "42".to_string(),
-    /// };
-    /// assert_eq!(generator.generate(&num), "$42$");
+    /// assert!(LaTeXGenerator::new().required_packages().is_empty());
     ///
-    /// // Multiplication
-    /// let mult = Expr::BinaryOp {
-    ///     line: 1,
-    ///     column: 5,
-    ///     operator: "*".to_string(),
-    ///     left: Box::new(Expr::Number {
-    ///         line: 1,
-    ///         column: 1,
-    ///         value: "4".to_string(),
-    ///     }),
-    ///     right: Box::new(Expr::Number {
-    ///         line: 1,
-    ///         column: 3,
-    ///         value: "7".to_string(),
-    ///     }),
-    /// };
-    /// assert_eq!(generator.generate(&mult), r"$4 \times 7$");
+    /// let generator = LaTeXGenerator::builder()
+    ///     .environment(MathEnvironment::AlignStar)
+    ///     .siunitx(true)
+    ///     .build();
+    /// assert_eq!(generator.required_packages(), ["amsmath", "siunitx"]);
     /// ```
     #[must_use]
-    pub fn generate(&self, ast: &Expr) -> String {
-        let inner = self.visit(ast);
-        format!("${}$", inner)
+    pub fn required_packages(&self) -> Vec<&'static str> {
+        let mut packages = Vec::new();
+        let ams_fraction = self.config.frac && self.config.frac_style != FracStyle::Frac;
+        if ams_fraction
+            || matches!(
+                self.config.environment,
+                MathEnvironment::AlignStar | MathEnvironment::Gather
+            )
+        {
+            packages.push("amsmath");
+        }
+        if self.config.siunitx {
+            packages.push("siunitx");
+        }
+        packages
     }
 
     /// Visits an expression node and generates its LaTeX representation.
     ///
     /// This is the main recursive traversal function that handles both
     /// number literals and binary operations.
-    fn visit(&self, node: &Expr) -> String {
+    fn visit(&self, node: &Expr, substitutions: &Substitutions) -> String {
+        if let Some(name) = substitutions.macros.get(&Structural(node)) {
+            return name.clone();
+        }
+        let blank = substitutions
+            .blank
+            .filter(|(hidden, _)| std::ptr::eq(*hidden, node))
+            .map(|(_, blank)| blank);
+        match (node, blank) {
+            (
+                Expr::BinaryOp {
+                    operator,
+                    left,
+                    right,
+                    ..
+                },
+                _,
+            ) => self.visit_binary_op(operator, blank, left, right, substitutions),
+            (
+                Expr::Apply {
+                    operator, operands, ..
+                },
+                _,
+            ) => self.visit_apply(operator, blank, operands, substitutions),
+            (_, Some(blank)) => blank.to_string(),
+            (Expr::Number { value, .. }, None) => self.visit_number(value),
+            (Expr::Variable { name, .. }, None) => Self::visit_variable(name),
+        }
+    }
+
+    /// Writes a custom operator with its plugin, parenthesizing operands
+    /// that bind more loosely, or as a function if it has no plugin or is
+    /// replaced by `blank`.
+    fn visit_apply(
+        &self,
+        operator: &str,
+        blank: Option<&str>,
+        operands: &[Expr],
+        substitutions: &Substitutions,
+    ) -> String {
+        let plugin = substitutions
+            .operators
+            .and_then(|operators| operators.get(operator))
+            .filter(|_| blank.is_none());
+        let Some(plugin) = plugin else {
+            let operands: Vec<String> = operands
+                .iter()
+                .map(|operand| self.visit(operand, substitutions))
+                .collect();
+            let name = blank.map_or_else(|| Self::operator_name(operator), str::to_string);
+            return format!("{}({})", name, operands.join(", "));
+        };
+
+        let operands: Vec<String> = operands
+            .iter()
+            .enumerate()
+            .map(|(index, operand)| {
+                let text = self.visit(operand, substitutions);
+                let grouped = plugin
+                    .precedence()
+                    .zip(Self::binding(operand, substitutions))
+                    .is_some_and(|(parent, child)| {
+                        child < parent || (child == parent && index > 0)
+                    });
+                if grouped {
+                    self.parenthesize(operand, &text)
+                } else {
+                    text
+                }
+            })
+            .collect();
+        plugin.latex(&operands)
+    }
+
+    /// Returns how a custom operator without a plugin is named, as a
+    /// function.
+    pub(crate) fn operator_name(operator: &str) -> String {
+        format!("\\operatorname{{{}}}", escape_math(operator))
+    }
+
+    /// Returns how tightly the rendering of `node` binds, if it can need
+    /// parentheses as an operand.
+    fn binding(node: &Expr, substitutions: &Substitutions) -> Option<i32> {
         match node {
-            Expr::Number { value, .. } => value.clone(),
-            Expr::BinaryOp {
-                operator,
-                left,
-                right,
-                ..
-            } => self.visit_binary_op(operator, left, right),
+            Expr::Number { .. } | Expr::Variable { .. } => None,
+            Expr::BinaryOp { operator, .. } => Some(precedence::precedence(operator)),
+            Expr::Apply { operator, .. } => substitutions
+                .operators
+                .and_then(|operators| operators.get(operator))
+                .and_then(|plugin| plugin.precedence()),
+        }
+    }
+
+    /// Writes a number literal, formatted or wrapped for siunitx.
+    fn visit_number(&self, value: &str) -> String {
+        if self.config.siunitx {
+            let rounding = NumberFormat::new()
+                .with_precision(self.config.number_format.precision())
+                .with_leading_zero(self.config.number_format.leading_zero());
+            format!("\\num{{{}}}", rounding.format(value))
+        } else {
+            self.config.number_format.format(value)
+        }
+    }
+
+    /// Writes a variable name: single letters as math italics, longer names
+    /// in `\mathit` so they are kerned as one word rather than a product.
+    fn visit_variable(name: &str) -> String {
+        if name.chars().count() == 1 {
+            escape_math(name)
+        } else {
+            format!("\\mathit{{{}}}", escape_math(name))
         }
     }
 
     /// Visits a binary operation node and generates its LaTeX representation.
     ///
-    /// Handles operator precedence and adds parentheses when necessary.
-    fn visit_binary_op(&self, operator: &str, left: &Expr, right: &Expr) -> String {
-        let parent_precedence = Self::precedence(operator);
-        let latex_op = Self::operator_to_latex(operator);
+    /// Handles operator precedence and adds parentheses when necessary. A
+    /// `blank` is written in place of the operator, so a hidden division
+    /// stays on the line rather than becoming a fraction.
+    fn visit_binary_op(
+        &self,
+        operator: &str,
+        blank: Option<&str>,
+        left: &Expr,
+        right: &Expr,
+        substitutions: &Substitutions,
+    ) -> String {
+        if operator == "/" && self.config.frac && blank.is_none() {
+            return format!(
+                "{}{{{}}}{{{}}}",
+                self.config.frac_style.tex(),
+                self.visit(left, substitutions),
+                self.visit(right, substitutions)
+            );
+        }
+
+        let latex_op = blank.map_or_else(|| self.operator_tex(operator), str::to_string);
 
-        let mut left_text = self.visit(left);
-        if self.needs_parens(left, parent_precedence, false) {
-            left_text = format!("( {} )", left_text);
+        let mut left_text = self.visit(left, substitutions);
+        if self.needs_parens(left, operator, false)
+            || Self::plugin_needs_parens(left, operator, false, substitutions)
+        {
+            left_text = self.parenthesize(left, &left_text);
         }
 
-        let mut right_text = self.visit(right);
-        if self.needs_parens(right, parent_precedence, true) {
-            right_text = format!("( {} )", right_text);
+        let mut right_text = self.visit(right, substitutions);
+        if self.needs_parens(right, operator, true)
+            || Self::plugin_needs_parens(right, operator, true, substitutions)
+        {
+            right_text = self.parenthesize(right, &right_text);
+        } else if self.config.negative_parens && Self::is_negative_literal(right) {
+            right_text = format!("({})", right_text);
         }
 
-        format!("{} {} {}", left_text, latex_op, right_text)
+        match self.config.spacing {
+            // A control word such as \times would absorb a following letter
+            SpacingStyle::Tight
+                if latex_op.ends_with(|c: char| c.is_ascii_alphabetic())
+                    && right_text.starts_with(|c: char| c.is_ascii_alphabetic()) =>
+            {
+                format!("{}{} {}", left_text, latex_op, right_text)
+            }
+            SpacingStyle::Tight => format!("{}{}{}", left_text, latex_op, right_text),
+            SpacingStyle::Normal | SpacingStyle::Spaced => {
+                format!("{} {} {}", left_text, latex_op, right_text)
+            }
+        }
     }
 
-    /// Determines if a child expression needs parentheses.
-    ///
-    /// Parentheses are needed when:
-    /// 1. The child has lower precedence than the parent
-    /// 2. The child has equal precedence, is on the right, and the operator
-    ///    is left-associative (- or /)
-    ///
-    /// # Arguments
-    ///
-    /// * `child` - The child expression to check
-    /// * `parent_precedence` - The precedence level of the parent operator
-    /// * `is_right` - Whether the child is the right operand
-    fn needs_parens(&self, child: &Expr, parent_precedence: i32, is_right: bool) -> bool {
-        match child {
-            Expr::Number { .. } => false,
-            Expr::BinaryOp { operator, .. } => {
-                let child_precedence = Self::precedence(operator);
-
-                if child_precedence < parent_precedence {
-                    // Lower precedence always needs parens
-                    true
-                } else if child_precedence == parent_precedence && is_right {
-                    // Equal precedence on right side needs parens for left-associative ops
-                    // Subtraction and division are left-associative
-                    matches!(operator.as_str(), "-" | "/")
-                } else {
-                    false
-                }
-            }
+    /// Returns the infix symbol written for `operator`, with the configured
+    /// multiplication symbol.
+    pub(crate) fn operator_tex(&self, operator: &str) -> String {
+        match operator {
+            "*" => self.config.mul_style.tex().to_string(),
+            _ => Self::operator_to_latex(operator),
         }
     }
 
-    /// Returns the precedence level for an operator.
-    ///
-    /// Higher numbers indicate higher precedence (tighter binding).
-    ///
-    /// # Precedence levels
+    /// Wraps the rendered text of `group` in parentheses, sized according
+    /// to the paren sizing and padded according to the spacing style.
+    fn parenthesize(&self, group: &Expr, inner: &str) -> String {
+        let stretch = match self.config.paren_sizing {
+            ParenSizing::Fixed => false,
+            ParenSizing::Auto => true,
+            ParenSizing::AutoTall => self.is_tall(group),
+        };
+        let (open, close) = if stretch {
+            (r"\left(", r"\right)")
+        } else {
+            ("(", ")")
+        };
+
+        match self.config.spacing {
+            SpacingStyle::Tight | SpacingStyle::Normal => format!("{}{}{}", open, inner, close),
+            SpacingStyle::Spaced => format!("{} {} {}", open, inner, close),
+        }
+    }
+
+    /// Returns whether `node` is a number literal with a leading minus sign.
+    fn is_negative_literal(node: &Expr) -> bool {
+        matches!(node, Expr::Number { value, .. } if value.starts_with('-'))
+    }
+
+    /// Returns whether the rendering of `node` extends above or below a
+    /// single text line.
     ///
-    /// * Level 1: Addition (+), Subtraction (-)
-    /// * Level 2: Multiplication (*), Division (/)
-    fn precedence(operator: &str) -> i32 {
-        match operator {
-            "+" | "-" => 1,
-            "*" | "/" => 2,
-            _ => 0,
+    /// Fractions are tall; numbers and the infix operators all render at
+    /// text height.
+    fn is_tall(&self, node: &Expr) -> bool {
+        match node {
+            Expr::Number { .. } | Expr::Variable { .. } => false,
+            Expr::BinaryOp { operator, .. } if operator == "/" && self.config.frac => true,
+            Expr::BinaryOp { left, right, .. } => self.is_tall(left) || self.is_tall(right),
+            Expr::Apply { operands, .. } => operands.iter().any(|operand| self.is_tall(operand)),
         }
     }
 
+    /// Determines if a child expression needs parentheses.
+    ///
+    /// Delegates to the precedence rules shared by all infix generators.
+    fn needs_parens(&self, child: &Expr, parent_operator: &str, is_right: bool) -> bool {
+        precedence::needs_parens(child, parent_operator, is_right)
+    }
+
+    /// Determines if a custom operator written by its plugin needs
+    /// parentheses as an operand of `parent_operator`: it does if it binds
+    /// more loosely, or equally on the right.
+    fn plugin_needs_parens(
+        child: &Expr,
+        parent_operator: &str,
+        is_right: bool,
+        substitutions: &Substitutions,
+    ) -> bool {
+        let parent = precedence::precedence(parent_operator);
+        matches!(child, Expr::Apply { .. })
+            && Self::binding(child, substitutions)
+                .is_some_and(|child| child < parent || (child == parent && is_right))
+    }
+
     /// Converts an operator symbol to its LaTeX representation.
     ///
     /// # Mappings
@@ -214,22 +1325,97 @@ impl LaTeXGenerator {
     }
 }
 
+impl Default for LaTeXGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CodeGenerator for LaTeXGenerator {
+    fn generate(&self, expr: &Expr) -> String {
+        LaTeXGenerator::generate(self, expr)
+    }
+}
+
+/// Escapes `text` so LaTeX typesets it as written.
+///
+/// The characters LaTeX reserves, `% $ & # _ { } ~ ^ \`, are replaced by
+/// the commands printing them; anything else is kept.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::latex::escape;
+///
+/// assert_eq!(escape("50% of $x_1"), r"50\% of \$x\_1");
+/// assert_eq!(escape("{a^b}"), r"\{a\textasciicircum{}b\}");
+/// assert_eq!(escape(r"~\"), r"\textasciitilde{}\textbackslash{}");
+/// assert_eq!(escape("rate"), "rate");
+/// ```
+#[must_use]
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '%' | '$' | '&' | '#' | '_' | '{' | '}' => {
+                escaped.push('\\');
+                escaped.push(ch);
+            }
+            '~' => escaped.push_str(r"\textasciitilde{}"),
+            '^' => escaped.push_str(r"\textasciicircum{}"),
+            '\\' => escaped.push_str(r"\textbackslash{}"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// Escapes `text` so LaTeX typesets it as written in math mode.
+///
+/// Like [`escape`], except that `~ ^ \` become the math-mode commands
+/// printing them, since the text-mode commands `escape` uses are invalid
+/// in math.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::latex::escape_math;
+///
+/// assert_eq!(escape_math("x_1"), r"x\_1");
+/// assert_eq!(escape_math(r"~^\"), r"\sim{}\hat{}\backslash{}");
+/// ```
+#[must_use]
+pub fn escape_math(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '~' => escaped.push_str(r"\sim{}"),
+            '^' => escaped.push_str(r"\hat{}"),
+            '\\' => escaped.push_str(r"\backslash{}"),
+            _ => escaped.push_str(&escape(ch.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    escaped
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::lexer::Lexer;
+    use crate::number_format::{Precision, ThousandsSeparator};
+    use crate::parser::Parser;
+    use crate::span::Span;
 
     fn make_number(value: &str) -> Expr {
         Expr::Number {
-            line: 1,
-            column: 1,
+            span: Span::new(0, 0, 1, 1),
             value: value.to_string(),
         }
     }
 
     fn make_binop(operator: &str, left: Expr, right: Expr) -> Expr {
         Expr::BinaryOp {
-            line: 1,
-            column: 1,
+            span: Span::new(0, 0, 1, 1),
             operator: operator.to_string(),
             left: Box::new(left),
             right: Box::new(right),
@@ -416,12 +1602,382 @@ mod tests {
         assert_eq!(generator.generate(&expr), r"$( 10 \div 2 + 3 ) \times 4$");
     }
 
+    #[test]
+    fn test_default_environment_is_inline() {
+        assert_eq!(LaTeXGenerator::new().environment(), MathEnvironment::Inline);
+        assert_eq!(
+            LaTeXGenerator::default().environment(),
+            MathEnvironment::Inline
+        );
+    }
+
+    #[test]
+    fn test_display_environments() {
+        let expr = make_binop(
+            "*",
+            make_binop("+", make_number("5"), make_number("3")),
+            make_number("2"),
+        );
+        let cases = [
+            (MathEnvironment::Equation, "equation"),
+            (MathEnvironment::AlignStar, "align*"),
+            (MathEnvironment::Gather, "gather"),
+        ];
+        for (environment, name) in cases {
+            let generator = LaTeXGenerator::builder().environment(environment).build();
+            assert_eq!(
+                generator.generate(&expr),
+                format!(
+                    "\\begin{{{}}}\n( 5 + 3 ) \\times 2\n\\end{{{}}}",
+                    name, name
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn test_display_delimiters() {
+        let expr = make_binop("-", make_number("5"), make_number("3"));
+        let cases = [
+            (MathEnvironment::DisplayBrackets, r"\[5 - 3\]"),
+            (MathEnvironment::DisplayDollars, "$$5 - 3$$"),
+            (MathEnvironment::Bare, "5 - 3"),
+        ];
+        for (environment, expected) in cases {
+            let generator = LaTeXGenerator::builder().environment(environment).build();
+            assert_eq!(generator.generate(&expr), expected);
+            assert!(generator.required_packages().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_default_spacing_is_spaced() {
+        assert_eq!(LaTeXGenerator::new().spacing(), SpacingStyle::Spaced);
+        assert_eq!(LaTeXGenerator::default().spacing(), SpacingStyle::Spaced);
+    }
+
+    #[test]
+    fn test_spacing_styles() {
+        // (10 / 2 + 3) * (4 - 1)
+        let expr = make_binop(
+            "*",
+            make_binop(
+                "+",
+                make_binop("/", make_number("10"), make_number("2")),
+                make_number("3"),
+            ),
+            make_binop("-", make_number("4"), make_number("1")),
+        );
+        let cases = [
+            (SpacingStyle::Tight, r"$(10\div2+3)\times(4-1)$"),
+            (SpacingStyle::Normal, r"$(10 \div 2 + 3) \times (4 - 1)$"),
+            (
+                SpacingStyle::Spaced,
+                r"$( 10 \div 2 + 3 ) \times ( 4 - 1 )$",
+            ),
+        ];
+        for (spacing, expected) in cases {
+            let generator = LaTeXGenerator::builder().spacing(spacing).build();
+            assert_eq!(generator.generate(&expr), expected);
+        }
+    }
+
+    #[test]
+    fn test_paren_sizing() {
+        // (1 + 2) * (3 - 4)
+        let expr = make_binop(
+            "*",
+            make_binop("+", make_number("1"), make_number("2")),
+            make_binop("-", make_number("3"), make_number("4")),
+        );
+        let auto = LaTeXGenerator::builder()
+            .paren_sizing(ParenSizing::Auto)
+            .build();
+        assert_eq!(
+            auto.generate(&expr),
+            r"$\left( 1 + 2 \right) \times \left( 3 - 4 \right)$"
+        );
+
+        let tight = auto.config().spacing(SpacingStyle::Tight).build();
+        assert_eq!(
+            tight.generate(&expr),
+            r"$\left(1+2\right)\times\left(3-4\right)$"
+        );
+
+        // Nothing here is taller than a line, so plain parentheses remain
+        let auto_tall = LaTeXGenerator::builder()
+            .paren_sizing(ParenSizing::AutoTall)
+            .build();
+        assert_eq!(auto_tall.generate(&expr), r"$( 1 + 2 ) \times ( 3 - 4 )$");
+    }
+
+    #[test]
+    fn test_number_format_applies_to_operands() {
+        let format = NumberFormat::new().with_thousands(ThousandsSeparator::ThinSpace);
+        let generator = LaTeXGenerator::builder().number_format(format).build();
+        let expr = make_binop("*", make_number("1000000"), make_number("2"));
+        assert_eq!(generator.generate(&expr), r"$1\,000\,000 \times 2$");
+        assert_eq!(generator.number_format(), format);
+    }
+
+    #[test]
+    fn test_negative_right_operands() {
+        let generator = LaTeXGenerator::new();
+        assert!(generator.negative_parens());
+        let cases = [
+            (
+                make_binop("+", make_number("10"), make_number("-5")),
+                "$10 + (-5)$",
+            ),
+            (
+                make_binop("*", make_number("3"), make_number("-2.5")),
+                r"$3 \times (-2.5)$",
+            ),
+            // Left operands read naturally and stay bare
+            (
+                make_binop("-", make_number("-5"), make_number("3")),
+                "$-5 - 3$",
+            ),
+        ];
+        for (expr, expected) in cases {
+            assert_eq!(generator.generate(&expr), expected);
+        }
+
+        let bare = generator.config().negative_parens(false).build();
+        let expr = make_binop("+", make_number("10"), make_number("-5"));
+        assert_eq!(bare.generate(&expr), "$10 + -5$");
+    }
+
+    #[test]
+    fn test_siunitx_wraps_numbers() {
+        let generator = LaTeXGenerator::builder().siunitx(true).build();
+        let expr = make_binop("+", make_number("1000000"), make_number("-2.5"));
+        assert_eq!(generator.generate(&expr), r"$\num{1000000} + (\num{-2.5})$");
+        assert_eq!(generator.required_packages(), ["siunitx"]);
+    }
+
+    #[test]
+    fn test_siunitx_keeps_precision_only() {
+        let format = NumberFormat::new()
+            .with_thousands(ThousandsSeparator::ThinSpace)
+            .with_precision(Precision::DecimalPlaces(1));
+        let generator = LaTeXGenerator::builder()
+            .siunitx(true)
+            .number_format(format)
+            .build();
+        assert_eq!(
+            generator.generate(&make_number("12345.67")),
+            r"$\num{12345.7}$"
+        );
+    }
+
+    #[test]
+    fn test_minimal_parens_right_operands() {
+        let generator = LaTeXGenerator::new();
+        // 5 - (3 + 2): the grouping changes the value and must be kept
+        let expr = make_binop(
+            "-",
+            make_number("5"),
+            make_binop("+", make_number("3"), make_number("2")),
+        );
+        assert_eq!(generator.generate(&expr), "$5 - ( 3 + 2 )$");
+
+        // 5 + (3 - 2) reads the same without parentheses
+        let expr = make_binop(
+            "+",
+            make_number("5"),
+            make_binop("-", make_number("3"), make_number("2")),
+        );
+        assert_eq!(generator.generate(&expr), "$5 + 3 - 2$");
+
+        // 8 / (4 * 2) must keep its grouping
+        let expr = make_binop(
+            "/",
+            make_number("8"),
+            make_binop("*", make_number("4"), make_number("2")),
+        );
+        assert_eq!(generator.generate(&expr), r"$8 \div ( 4 \times 2 )$");
+    }
+
+    #[test]
+    fn test_builder_defaults_match_new() {
+        assert_eq!(LaTeXGenerator::builder(), LaTeXGenerator::new().config());
+        assert_eq!(GeneratorConfig::default(), GeneratorConfig::new());
+    }
+
+    #[test]
+    fn test_display_math_shorthand() {
+        let generator = LaTeXGenerator::builder().display_math(true).build();
+        assert_eq!(generator.environment(), MathEnvironment::DisplayBrackets);
+        let generator = generator.config().display_math(false).build();
+        assert_eq!(generator.environment(), MathEnvironment::Inline);
+    }
+
+    #[test]
+    fn test_mul_style_cdot() {
+        let generator = LaTeXGenerator::builder().mul_style(MulStyle::Cdot).build();
+        let expr = make_binop("*", make_number("4"), make_number("7"));
+        assert_eq!(generator.generate(&expr), r"$4 \cdot 7$");
+        assert_eq!(generator.mul_style(), MulStyle::Cdot);
+    }
+
+    #[test]
+    fn test_frac_division() {
+        let generator = LaTeXGenerator::builder().frac(true).build();
+        // (10 + 2) / (3 - -1): the fraction groups both operands itself
+        let expr = make_binop(
+            "/",
+            make_binop("+", make_number("10"), make_number("2")),
+            make_binop("-", make_number("3"), make_number("-1")),
+        );
+        assert_eq!(generator.generate(&expr), r"$\frac{10 + 2}{3 - (-1)}$");
+
+        // Nested fractions and fractions as operands
+        let expr = make_binop(
+            "*",
+            make_binop(
+                "/",
+                make_number("1"),
+                make_binop("/", make_number("2"), make_number("3")),
+            ),
+            make_number("4"),
+        );
+        assert_eq!(
+            generator.generate(&expr),
+            r"$\frac{1}{\frac{2}{3}} \times 4$"
+        );
+    }
+
+    #[test]
+    fn test_frac_styles() {
+        let expr = make_binop("/", make_number("1"), make_number("2"));
+        let cases = [
+            (FracStyle::Frac, r"$\frac{1}{2}$", false),
+            (FracStyle::Dfrac, r"$\dfrac{1}{2}$", true),
+            (FracStyle::Tfrac, r"$\tfrac{1}{2}$", true),
+        ];
+        for (style, expected, needs_amsmath) in cases {
+            let generator = LaTeXGenerator::builder()
+                .frac(true)
+                .frac_style(style)
+                .build();
+            assert_eq!(generator.generate(&expr), expected);
+            assert_eq!(
+                generator.required_packages().contains(&"amsmath"),
+                needs_amsmath
+            );
+        }
+    }
+
+    #[test]
+    fn test_frac_style_ignored_without_frac() {
+        let generator = LaTeXGenerator::builder()
+            .frac_style(FracStyle::Dfrac)
+            .build();
+        let expr = make_binop("/", make_number("1"), make_number("2"));
+        assert_eq!(generator.generate(&expr), r"$1 \div 2$");
+        assert!(generator.required_packages().is_empty());
+    }
+
+    #[test]
+    fn test_auto_tall_parens_around_fractions() {
+        let generator = LaTeXGenerator::builder()
+            .frac(true)
+            .paren_sizing(ParenSizing::AutoTall)
+            .build();
+        // (1 / 2 + 3) * (4 - 5)
+        let expr = make_binop(
+            "*",
+            make_binop(
+                "+",
+                make_binop("/", make_number("1"), make_number("2")),
+                make_number("3"),
+            ),
+            make_binop("-", make_number("4"), make_number("5")),
+        );
+        assert_eq!(
+            generator.generate(&expr),
+            r"$\left( \frac{1}{2} + 3 \right) \times ( 4 - 5 )$"
+        );
+    }
+
+    #[test]
+    fn test_generate_document_inline() {
+        let generator = LaTeXGenerator::new();
+        let expr = make_binop("+", make_number("5"), make_number("3"));
+        assert_eq!(
+            generator.generate_document(&expr),
+            "\\documentclass{standalone}\n\\begin{document}\n$5 + 3$\n\\end{document}\n"
+        );
+    }
+
+    #[test]
+    fn test_generate_document_display_environment() {
+        let generator = LaTeXGenerator::builder()
+            .environment(MathEnvironment::AlignStar)
+            .build();
+        let document = generator.generate_document(&make_number("1"));
+        assert!(document.starts_with("\\documentclass[varwidth]{standalone}\n"));
+        assert!(document.contains("\\usepackage{amsmath}\n\\begin{document}\n"));
+        assert!(document.contains("\\begin{align*}\n1\n\\end{align*}"));
+        assert!(document.ends_with("\\end{document}\n"));
+    }
+
+    #[test]
+    fn test_equation_needs_no_packages() {
+        let generator = LaTeXGenerator::builder()
+            .environment(MathEnvironment::Equation)
+            .build();
+        assert!(generator.required_packages().is_empty());
+        assert!(!generator
+            .generate_document(&make_number("1"))
+            .contains("usepackage"));
+    }
+
+    #[test]
+    fn test_katex_inline_delimiters() {
+        let generator = LaTeXGenerator::builder().katex(true).build();
+        let expr = make_binop("*", make_number("4"), make_number("7"));
+        assert_eq!(generator.generate(&expr), r"\(4 \times 7\)");
+    }
+
+    #[test]
+    fn test_katex_keeps_environments() {
+        let generator = LaTeXGenerator::builder()
+            .katex(true)
+            .environment(MathEnvironment::AlignStar)
+            .build();
+        assert_eq!(
+            generator.generate(&make_number("1")),
+            "\\begin{align*}\n1\n\\end{align*}"
+        );
+        assert!(generator.katex_warnings().is_empty());
+    }
+
+    #[test]
+    fn test_katex_warnings() {
+        assert!(LaTeXGenerator::new().katex_warnings().is_empty());
+        assert!(LaTeXGenerator::builder()
+            .environment(MathEnvironment::Gather)
+            .build()
+            .katex_warnings()
+            .is_empty());
+
+        let warnings = LaTeXGenerator::builder()
+            .katex(true)
+            .environment(MathEnvironment::Gather)
+            .build()
+            .katex_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("gather"));
+    }
+
     #[test]
     fn test_precedence_function() {
-        assert_eq!(LaTeXGenerator::precedence("+"), 1);
-        assert_eq!(LaTeXGenerator::precedence("-"), 1);
-        assert_eq!(LaTeXGenerator::precedence("*"), 2);
-        assert_eq!(LaTeXGenerator::precedence("/"), 2);
+        assert_eq!(precedence::precedence("+"), 1);
+        assert_eq!(precedence::precedence("-"), 1);
+        assert_eq!(precedence::precedence("*"), 2);
+        assert_eq!(precedence::precedence("/"), 2);
     }
 
     #[test]
@@ -436,8 +1992,8 @@ mod tests {
     fn test_needs_parens_number() {
         let generator = LaTeXGenerator::new();
         let num = make_number("5");
-        assert!(!generator.needs_parens(&num, 2, false));
-        assert!(!generator.needs_parens(&num, 2, true));
+        assert!(!generator.needs_parens(&num, "*", false));
+        assert!(!generator.needs_parens(&num, "*", true));
     }
 
     #[test]
@@ -445,8 +2001,8 @@ mod tests {
         let generator = LaTeXGenerator::new();
         let add = make_binop("+", make_number("2"), make_number("3"));
         // Addition (precedence 1) as child of multiplication (precedence 2)
-        assert!(generator.needs_parens(&add, 2, false));
-        assert!(generator.needs_parens(&add, 2, true));
+        assert!(generator.needs_parens(&add, "*", false));
+        assert!(generator.needs_parens(&add, "*", true));
     }
 
     #[test]
@@ -454,7 +2010,7 @@ mod tests {
         let generator = LaTeXGenerator::new();
         let sub = make_binop("-", make_number("5"), make_number("3"));
         // Subtraction as left child of another subtraction - no parens
-        assert!(!generator.needs_parens(&sub, 1, false));
+        assert!(!generator.needs_parens(&sub, "-", false));
     }
 
     #[test]
@@ -462,7 +2018,7 @@ mod tests {
         let generator = LaTeXGenerator::new();
         let sub = make_binop("-", make_number("3"), make_number("2"));
         // Subtraction as right child of subtraction - needs parens
-        assert!(generator.needs_parens(&sub, 1, true));
+        assert!(generator.needs_parens(&sub, "-", true));
     }
 
     #[test]
@@ -470,20 +2026,155 @@ mod tests {
         let generator = LaTeXGenerator::new();
         let div = make_binop("/", make_number("10"), make_number("2"));
         // Division as right child of division - needs parens
-        assert!(generator.needs_parens(&div, 2, true));
+        assert!(generator.needs_parens(&div, "/", true));
     }
 
     #[test]
     fn test_needs_parens_equal_precedence_right_addition() {
         let generator = LaTeXGenerator::new();
         let add = make_binop("+", make_number("2"), make_number("3"));
-        // Addition as right child of addition - no parens (commutative)
-        assert!(!generator.needs_parens(&add, 1, true));
+        // Addition as right child of addition - no parens (associative)
+        assert!(!generator.needs_parens(&add, "+", true));
+    }
+
+    #[test]
+    fn test_generate_with_result() {
+        let expr = make_binop("/", make_number("1"), make_number("4"));
+        assert_eq!(
+            LaTeXGenerator::new().generate_with_result(&expr, 0.25),
+            r"$1 \div 4 = 0.25$"
+        );
+
+        let tight = LaTeXGenerator::builder()
+            .spacing(SpacingStyle::Tight)
+            .environment(MathEnvironment::Equation)
+            .build();
+        assert_eq!(
+            tight.generate_with_result(&expr, 0.25),
+            "\\begin{equation}\n1\\div4=0.25\n\\end{equation}"
+        );
+    }
+
+    #[test]
+    fn test_generate_with_result_formats_value() {
+        let expr = make_binop("-", make_number("0"), make_number("0"));
+        assert_eq!(
+            LaTeXGenerator::new().generate_with_result(&expr, -0.0),
+            "$0 - 0 = 0$"
+        );
+
+        let rounded = LaTeXGenerator::builder()
+            .number_format(NumberFormat::new().with_precision(Precision::DecimalPlaces(3)))
+            .siunitx(true)
+            .build();
+        let third = make_binop("/", make_number("1"), make_number("3"));
+        assert_eq!(
+            rounded.generate_with_result(&third, 1.0 / 3.0),
+            r"$\num{1.000} \div \num{3.000} = \num{0.333}$"
+        );
+    }
+
+    #[test]
+    fn test_variables() {
+        let generator = LaTeXGenerator::new();
+        let variable = |name: &str| Expr::Variable {
+            span: Span::new(0, 0, 1, 1),
+            name: name.to_string(),
+        };
+        let expr = make_binop("*", variable("x"), variable("rate"));
+        assert_eq!(generator.generate(&expr), r"$x \times \mathit{rate}$");
+
+        let grouped = make_binop(
+            "-",
+            make_number("1"),
+            make_binop("-", variable("a"), variable("b")),
+        );
+        assert_eq!(generator.generate(&grouped), "$1 - ( a - b )$");
+
+        let tight = LaTeXGenerator::builder()
+            .spacing(SpacingStyle::Tight)
+            .build();
+        let product = make_binop("*", make_number("2"), variable("y"));
+        assert_eq!(tight.generate(&product), r"$2\times y$");
+        let quotient = make_binop("/", variable("y"), variable("rate"));
+        assert_eq!(tight.generate(&quotient), r"$y\div\mathit{rate}$");
+    }
+
+    #[test]
+    fn test_names_are_escaped() {
+        let generator = LaTeXGenerator::new();
+        let expr = make_binop("+", Expr::var("rate_2"), Expr::var("%"));
+        assert_eq!(generator.generate(&expr), r"$\mathit{rate\_2} + \%$");
+        let apply = Expr::apply("a&b", [make_number("1")]);
+        assert_eq!(generator.generate(&apply), r"$\operatorname{a\&b}(1)$");
+
+        // In math mode, so without text-mode commands
+        let expr = make_binop("*", Expr::var("a~b"), Expr::var("^"));
+        assert_eq!(
+            generator.generate(&expr),
+            r"$\mathit{a\sim{}b} \times \hat{}$"
+        );
+        let apply = Expr::apply(r"f\g", [make_number("1")]);
+        assert_eq!(
+            generator.generate(&apply),
+            r"$\operatorname{f\backslash{}g}(1)$"
+        );
+    }
+
+    fn generate_with_macros(input: &str) -> String {
+        let tokens = Lexer::new(input)
+            .with_identifiers(true)
+            .tokenize()
+            .expect("tokenize failed");
+        let ast = Parser::new(tokens).parse().expect("parse failed");
+        LaTeXGenerator::new().generate_with_macros(&ast)
+    }
+
+    #[test]
+    fn test_macros_without_repetition() {
+        assert_eq!(generate_with_macros("5 3 + 2 *"), r"$( 5 + 3 ) \times 2$");
+        // Repeated leaves are not worth a macro
+        assert_eq!(generate_with_macros("x x *"), r"$x \times x$");
+    }
+
+    #[test]
+    fn test_macros_nested_repetition() {
+        // (a + b) * (a + b) occurs twice and contains a + b twice
+        assert_eq!(
+            generate_with_macros("a b + a b + * a b + a b + * -"),
+            "\\newcommand{\\rpnA}{a + b}\n\\newcommand{\\rpnB}{( \\rpnA ) \\times ( \\rpnA )}\n$\\rpnB - \\rpnB$"
+        );
+    }
+
+    #[test]
+    fn test_macros_skip_parts_of_repeated_subtrees() {
+        // x - 1 only repeats because (x - 1) * y does
+        assert_eq!(
+            generate_with_macros("x 1 - y * x 1 - y * +"),
+            "\\newcommand{\\rpnA}{( x - 1 ) \\times y}\n$\\rpnA + \\rpnA$"
+        );
+    }
+
+    #[test]
+    fn test_macros_ignore_positions() {
+        assert_eq!(
+            generate_with_macros("2 3 +\n2   3 + /"),
+            "\\newcommand{\\rpnA}{2 + 3}\n$( \\rpnA ) \\div ( \\rpnA )$"
+        );
+    }
+
+    #[test]
+    fn test_macro_suffix() {
+        assert_eq!(LaTeXGenerator::macro_suffix(0), "A");
+        assert_eq!(LaTeXGenerator::macro_suffix(25), "Z");
+        assert_eq!(LaTeXGenerator::macro_suffix(26), "AA");
+        assert_eq!(LaTeXGenerator::macro_suffix(27), "AB");
+        assert_eq!(LaTeXGenerator::macro_suffix(26 * 27), "AAA");
     }
 
     #[test]
     fn test_default_constructor() {
-        let generator = LaTeXGenerator;
+        let generator = LaTeXGenerator::default();
         let expr = make_number("42");
         assert_eq!(generator.generate(&expr), "$42$");
     }
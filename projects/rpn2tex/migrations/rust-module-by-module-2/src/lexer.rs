@@ -3,9 +3,18 @@
 //! This module provides lexical analysis for RPN expressions, converting
 //! raw text input into a sequence of tokens that can be parsed into an AST.
 
+use crate::diagnostic::Diagnostic;
+use crate::error_code::ErrorCode;
+use crate::limits::{LimitExceeded, Limits};
+use crate::plugin::OperatorTable;
+use crate::span::Span;
 use crate::tokens::{Token, TokenType};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::error::Error;
 use std::fmt;
+use std::iter::FusedIterator;
 
 /// Error type for lexical analysis failures.
 ///
@@ -16,19 +25,38 @@ use std::fmt;
 ///
 /// ```
 /// use rpn2tex::lexer::LexerError;
+/// use rpn2tex::span::Span;
 ///
-/// let error = LexerError::new("Unexpected character '@'", 1, 5);
+/// let error = LexerError::new("Unexpected character '@'", Span::new(4, 5, 1, 5));
 /// assert_eq!(error.line(), 1);
 /// assert_eq!(error.column(), 5);
 /// ```
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct LexerError {
     /// Error description
     message: String,
-    /// 1-based line number where the error occurred
-    line: u32,
-    /// 1-based column number where the error occurred
-    column: u32,
+    /// The source text the error refers to
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    span: Span,
+    /// Suggestion for what the input may have meant
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    help: Option<String>,
+    /// The limit the input exceeded, if that is the error
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    limit_exceeded: Option<LimitExceeded>,
+    /// Stable code identifying the kind of error
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    code: Option<ErrorCode>,
 }
 
 impl LexerError {
@@ -37,42 +65,105 @@ impl LexerError {
     /// # Arguments
     ///
     /// * `message` - Description of the error
-    /// * `line` - 1-based line number where the error occurred
-    /// * `column` - 1-based column number where the error occurred
+    /// * `span` - The source text the error refers to
     ///
     /// # Examples
     ///
     /// ```
     /// use rpn2tex::lexer::LexerError;
+    /// use rpn2tex::span::Span;
     ///
-    /// let error = LexerError::new("Invalid token", 2, 10);
+    /// let error = LexerError::new("Invalid token", Span::new(12, 13, 2, 10));
     /// assert_eq!(error.message(), "Invalid token");
     /// ```
     #[must_use]
-    pub fn new(message: impl Into<String>, line: u32, column: u32) -> Self {
+    pub fn new(message: impl Into<String>, span: Span) -> Self {
         Self {
             message: message.into(),
-            line,
-            column,
+            span,
+            help: None,
+            limit_exceeded: None,
+            code: None,
+        }
+    }
+
+    /// Creates an error for input that exceeded one of the lexer's
+    /// [`Limits`], at the token where it was detected.
+    #[must_use]
+    pub fn from_limit(limit: LimitExceeded, span: Span) -> Self {
+        Self {
+            limit_exceeded: Some(limit),
+            code: Some(limit.code()),
+            ..Self::new(limit.to_string(), span)
         }
     }
 
+    /// Attaches a suggestion for what the input may have meant.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::lexer::LexerError;
+    /// use rpn2tex::span::Span;
+    ///
+    /// let error = LexerError::new("Unexpected character '^'", Span::new(4, 5, 1, 5))
+    ///     .with_help("exponentiation is not supported");
+    /// assert_eq!(error.help(), Some("exponentiation is not supported"));
+    /// ```
+    #[must_use]
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    /// Attaches the stable code identifying the kind of error.
+    #[must_use]
+    pub fn with_code(mut self, code: ErrorCode) -> Self {
+        self.code = Some(code);
+        self
+    }
+
     /// Returns the error message.
     #[must_use]
     pub fn message(&self) -> &str {
         &self.message
     }
 
+    /// Returns the source text the error refers to.
+    #[must_use]
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
     /// Returns the line number where the error occurred (1-based).
     #[must_use]
     pub fn line(&self) -> u32 {
-        self.line
+        self.span.line
     }
 
     /// Returns the column number where the error occurred (1-based).
     #[must_use]
     pub fn column(&self) -> u32 {
-        self.column
+        self.span.column
+    }
+
+    /// Returns the suggestion for what the input may have meant, if any.
+    #[must_use]
+    pub fn help(&self) -> Option<&str> {
+        self.help.as_deref()
+    }
+
+    /// Returns the limit the input exceeded, or `None` for a syntax error.
+    #[must_use]
+    pub fn limit_exceeded(&self) -> Option<LimitExceeded> {
+        self.limit_exceeded
+    }
+
+    /// Returns the stable code identifying the kind of error, if it has
+    /// one.
+    #[must_use]
+    pub fn code(&self) -> Option<ErrorCode> {
+        self.code
     }
 }
 
@@ -81,7 +172,7 @@ impl fmt::Display for LexerError {
         write!(
             f,
             "Line {}, column {}: {}",
-            self.line, self.column, self.message
+            self.span.line, self.span.column, self.message
         )
     }
 }
@@ -92,7 +183,11 @@ impl Error for LexerError {}
 ///
 /// The `Lexer` performs character-by-character scanning of RPN input,
 /// producing a token stream. It tracks position information (line and column)
-/// for error reporting.
+/// for error reporting. Comments, from `#` to the end of the line, are skipped
+/// like whitespace. The typeset operators `×`, `·`, `÷` and `−` are read
+/// as `*`, `*`, `/` and `-`.
+///
+/// The lexer borrows its input, and token values are slices of it.
 ///
 /// # Examples
 ///
@@ -107,18 +202,42 @@ impl Error for LexerError {}
 /// assert_eq!(tokens[0].value, "5");
 /// ```
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Lexer {
+pub struct Lexer<'a> {
     /// Input text to tokenize
-    text: String,
+    text: &'a str,
     /// Current position in text (0-based)
     pos: usize,
+    /// Byte offset of the text in the whole source
+    offset: usize,
     /// Current line number (1-based)
     line: u32,
     /// Current column number (1-based)
     column: u32,
+    /// Whether letters start identifier tokens instead of being rejected
+    identifiers: bool,
+    /// Whether non-ASCII decimal digits such as `５` are read as 0-9
+    unicode_digits: bool,
+    /// Whether commas may group digits in threes, as in `1,000`
+    comma_groups: bool,
+    /// Whether a number may be directly followed by a `.` that does not
+    /// continue it, as in `5.` or `1.2.3`
+    lenient_numbers: bool,
+    /// Whether whitespace and comments are returned as tokens
+    trivia: bool,
+    /// Whether the input is infix: parentheses are tokens, and a `-` after
+    /// an operand is always subtraction
+    infix: bool,
+    /// Whether the last token scanned ends an operand
+    after_operand: bool,
+    /// Caps on input size and token count
+    limits: Limits,
+    /// Custom operators, read before any built-in token
+    operators: OperatorTable,
+    /// Number of tokens scanned so far, not counting EOF
+    scanned: usize,
 }
 
-impl Lexer {
+impl<'a> Lexer<'a> {
     /// Creates a new lexer for the given input text.
     ///
     /// Position tracking starts at line 1, column 1 (1-based indexing).
@@ -135,15 +254,286 @@ impl Lexer {
     /// let lexer = Lexer::new("5 3 +");
     /// ```
     #[must_use]
-    pub fn new(text: impl Into<String>) -> Self {
+    pub fn new(text: &'a str) -> Self {
         Self {
-            text: text.into(),
+            text,
             pos: 0,
+            offset: 0,
             line: 1,
             column: 1,
+            identifiers: false,
+            unicode_digits: false,
+            comma_groups: false,
+            lenient_numbers: false,
+            trivia: false,
+            infix: false,
+            after_operand: false,
+            limits: Limits::new(),
+            operators: OperatorTable::new(),
+            scanned: 0,
         }
     }
 
+    /// Sets the position of the first character of the text, for lexing a
+    /// fragment of a larger source so spans refer to the whole source.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - Byte offset of the text in the whole source
+    /// * `line` - 1-based line number where the text starts
+    /// * `column` - 1-based column number where the text starts
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::lexer::Lexer;
+    /// use rpn2tex::span::Span;
+    ///
+    /// // "3 +" taken from "5\n  3 +", at column 3 of line 2
+    /// let tokens = Lexer::new("3 +").with_start(4, 2, 3).tokenize().unwrap();
+    /// assert_eq!(tokens[1].span, Span::new(6, 7, 2, 5));
+    /// ```
+    #[must_use]
+    pub fn with_start(mut self, offset: usize, line: u32, column: u32) -> Self {
+        self.offset = offset;
+        self.line = line;
+        self.column = column;
+        self
+    }
+
+    /// Sets whether variable names such as `x` or `rate2` are accepted.
+    ///
+    /// Identifiers are off by default, so a stray letter is reported as an
+    /// unexpected character rather than silently becoming a variable.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::lexer::Lexer;
+    /// use rpn2tex::tokens::TokenType;
+    ///
+    /// assert!(Lexer::new("x 2 *").tokenize().is_err());
+    ///
+    /// let tokens = Lexer::new("x 2 *").with_identifiers(true).tokenize().unwrap();
+    /// assert_eq!(tokens[0].token_type, TokenType::Identifier);
+    /// assert_eq!(tokens[0].value, "x");
+    /// ```
+    #[must_use]
+    pub fn with_identifiers(mut self, identifiers: bool) -> Self {
+        self.identifiers = identifiers;
+        self
+    }
+
+    /// Sets whether decimal digits from other scripts, such as fullwidth
+    /// `５` or Arabic-Indic `٥`, are accepted in numbers.
+    ///
+    /// Number token values are normalized to ASCII digits; spans still
+    /// cover the original text.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::lexer::Lexer;
+    ///
+    /// assert!(Lexer::new("１２ ٣ +").tokenize().is_err());
+    ///
+    /// let tokens = Lexer::new("１２ ٣ +").with_unicode_digits(true).tokenize().unwrap();
+    /// assert_eq!(tokens[0].value, "12");
+    /// assert_eq!(tokens[1].value, "3");
+    /// ```
+    #[must_use]
+    pub fn with_unicode_digits(mut self, unicode_digits: bool) -> Self {
+        self.unicode_digits = unicode_digits;
+        self
+    }
+
+    /// Sets whether commas are accepted as thousands separators in the
+    /// integer part of a number, as in `1,000,000`.
+    ///
+    /// Underscores between digits (`1_000_000`) are always accepted. Each
+    /// comma must be followed by exactly three digits, so `1,5` is still
+    /// rejected. Separators are removed from number token values; spans
+    /// still cover the original text.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::lexer::Lexer;
+    ///
+    /// let tokens = Lexer::new("1_000 2").tokenize().unwrap();
+    /// assert_eq!(tokens[0].value, "1000");
+    ///
+    /// assert!(Lexer::new("1,000 2 +").tokenize().is_err());
+    /// let tokens = Lexer::new("1,000 2 +").with_comma_groups(true).tokenize().unwrap();
+    /// assert_eq!(tokens[0].value, "1000");
+    /// ```
+    #[must_use]
+    pub fn with_comma_groups(mut self, comma_groups: bool) -> Self {
+        self.comma_groups = comma_groups;
+        self
+    }
+
+    /// Sets whether malformed number literals such as `5.` or `1.2.3` are
+    /// split into a number and whatever follows instead of rejected.
+    ///
+    /// By default such a literal is a "Malformed number literal" error
+    /// spanning all of it. Leniently, `5.` is the number `5` followed by an
+    /// unexpected `.`, and `1.2.3` is the two numbers `1.2` and `.3`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::lexer::Lexer;
+    ///
+    /// let error = Lexer::new("1.2.3 2 +").tokenize().unwrap_err();
+    /// assert_eq!(error.message(), "Malformed number literal '1.2.3'");
+    /// assert_eq!(error.span().len(), 5);
+    ///
+    /// let tokens = Lexer::new("1.2.3").with_lenient_numbers(true).tokenize().unwrap();
+    /// assert_eq!(tokens[0].value, "1.2");
+    /// assert_eq!(tokens[1].value, ".3");
+    /// ```
+    #[must_use]
+    pub fn with_lenient_numbers(mut self, lenient_numbers: bool) -> Self {
+        self.lenient_numbers = lenient_numbers;
+        self
+    }
+
+    /// Sets whether whitespace and comments are returned as
+    /// [`Whitespace`](TokenType::Whitespace) and
+    /// [`Comment`](TokenType::Comment) tokens instead of skipped.
+    ///
+    /// With trivia the token spans cover the whole input without gaps, so
+    /// a formatter or syntax highlighter can rebuild the source exactly.
+    /// The parser skips trivia tokens, and they do not count toward
+    /// [`Limits::with_max_tokens`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::lexer::Lexer;
+    /// use rpn2tex::tokens::TokenType;
+    ///
+    /// let source = "5 3 +  # sum\n";
+    /// let tokens = Lexer::new(source).with_trivia(true).tokenize().unwrap();
+    /// let rebuilt: String = tokens.iter().map(|t| t.span.text(source)).collect();
+    /// assert_eq!(rebuilt, source);
+    /// assert_eq!(tokens[6].token_type, TokenType::Comment);
+    /// assert_eq!(tokens[6].value, "# sum");
+    /// ```
+    #[must_use]
+    pub fn with_trivia(mut self, trivia: bool) -> Self {
+        self.trivia = trivia;
+        self
+    }
+
+    /// Sets whether the input is infix, as read by
+    /// [`Parser::parse_infix`](crate::parser::Parser::parse_infix).
+    ///
+    /// Parentheses are then [`LeftParen`](TokenType::LeftParen) and
+    /// [`RightParen`](TokenType::RightParen) tokens, and a `-` straight
+    /// after a number, variable or `)` is subtraction even when a digit
+    /// follows it, so `3-2` is a difference rather than two numbers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::lexer::Lexer;
+    /// use rpn2tex::tokens::TokenType;
+    ///
+    /// assert!(Lexer::new("(3-2)").tokenize().is_err());
+    ///
+    /// let tokens = Lexer::new("(3-2) * -1").with_infix(true).tokenize().unwrap();
+    /// let types: Vec<_> = tokens.iter().map(|t| t.token_type).collect();
+    /// assert_eq!(
+    ///     types,
+    ///     [
+    ///         TokenType::LeftParen,
+    ///         TokenType::Number,
+    ///         TokenType::Minus,
+    ///         TokenType::Number,
+    ///         TokenType::RightParen,
+    ///         TokenType::Mult,
+    ///         TokenType::Number,
+    ///         TokenType::Eof,
+    ///     ]
+    /// );
+    /// assert_eq!(tokens[6].value, "-1");
+    /// ```
+    #[must_use]
+    pub fn with_infix(mut self, infix: bool) -> Self {
+        self.infix = infix;
+        self
+    }
+
+    /// Sets the input size and token count limits. The depth limit is
+    /// enforced by the parser.
+    ///
+    /// The byte limit counts from the start of the whole source, so it
+    /// applies across fragments lexed with [`with_start`](Self::with_start).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::lexer::Lexer;
+    /// use rpn2tex::limits::{LimitExceeded, Limits};
+    ///
+    /// let limits = Limits::new().with_max_input_bytes(8);
+    /// let error = Lexer::new("5 3 + 2 * 7 -").with_limits(limits).tokenize().unwrap_err();
+    /// assert_eq!(error.limit_exceeded(), Some(LimitExceeded::InputBytes(8)));
+    /// assert_eq!(error.column(), 9);
+    /// ```
+    #[must_use]
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Sets the custom operators to read as
+    /// [`Operator`](TokenType::Operator) tokens.
+    ///
+    /// Where an operator's spelling starts the next token it takes
+    /// precedence over the built-in tokens, the longest spelling winning,
+    /// so an operator `**` is not read as two `*`. A spelling ending in a
+    /// letter must end the word: `mod` is not read from `modulus`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::lexer::Lexer;
+    /// use rpn2tex::plugin::{OperatorPlugin, OperatorTable};
+    /// use rpn2tex::tokens::TokenType;
+    ///
+    /// struct Power;
+    ///
+    /// impl OperatorPlugin for Power {
+    ///     fn spelling(&self) -> &str {
+    ///         "**"
+    ///     }
+    ///
+    ///     fn latex(&self, operands: &[String]) -> String {
+    ///         format!("{}^{{{}}}", operands[0], operands[1])
+    ///     }
+    /// }
+    ///
+    /// let operators = OperatorTable::new().with_operator(Power);
+    /// let tokens = Lexer::new("2 3 ** 4 *").with_operators(operators).tokenize().unwrap();
+    /// assert_eq!(tokens[2].token_type, TokenType::Operator);
+    /// assert_eq!(tokens[2].value, "**");
+    /// assert_eq!(tokens[4].token_type, TokenType::Mult);
+    /// ```
+    #[must_use]
+    pub fn with_operators(mut self, operators: OperatorTable) -> Self {
+        self.operators = operators;
+        self
+    }
+
+    /// Returns the limits this lexer enforces.
+    #[must_use]
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
     /// Tokenizes the entire input text.
     ///
     /// Returns a vector of tokens, including a final EOF token.
@@ -154,7 +544,7 @@ impl Lexer {
     ///
     /// Returns `LexerError` if the input contains:
     /// - Unexpected characters that are not valid operators or number components
-    /// - Malformed tokens
+    /// - Malformed number literals such as `5.` or `1.2.3`, unless lenient
     ///
     /// # Examples
     ///
@@ -171,26 +561,83 @@ impl Lexer {
     /// assert_eq!(tokens[2].token_type, TokenType::Div);
     /// assert_eq!(tokens[3].token_type, TokenType::Eof);
     /// ```
-    pub fn tokenize(mut self) -> Result<Vec<Token>, LexerError> {
+    pub fn tokenize(self) -> Result<Vec<Token<'a>>, LexerError> {
+        self.iter().collect()
+    }
+
+    /// Tokenizes the entire input text, skipping characters that cannot
+    /// start a token instead of stopping at the first one.
+    ///
+    /// Returns the tokens that could be read, ending with EOF, and a
+    /// diagnostic for every skipped character.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::lexer::Lexer;
+    ///
+    /// let (tokens, diagnostics) = Lexer::new("2 ^ 3 @ +").tokenize_recovering();
+    /// let values: Vec<&str> = tokens.iter().map(|t| t.value.as_ref()).collect();
+    /// assert_eq!(values, ["2", "3", "+", ""]);
+    /// assert_eq!(diagnostics.len(), 2);
+    /// assert_eq!(diagnostics[1].message(), "Unexpected character '@'");
+    /// ```
+    #[must_use]
+    pub fn tokenize_recovering(mut self) -> (Vec<Token<'a>>, Vec<Diagnostic>) {
         let mut tokens = Vec::new();
+        let mut diagnostics = Vec::new();
 
-        while !self.at_end() {
-            self.skip_whitespace();
-            if self.at_end() {
-                break;
+        loop {
+            match self.next_token() {
+                Ok(token) => {
+                    let eof = token.token_type == TokenType::Eof;
+                    tokens.push(token);
+                    if eof {
+                        break;
+                    }
+                }
+                Err(error) => {
+                    // Past a limit the rest of the input is not read at all
+                    let limit_exceeded = error.limit_exceeded().is_some();
+                    diagnostics.push(Diagnostic::from(error));
+                    if limit_exceeded {
+                        let span = self.span_from(self.pos, self.line, self.column);
+                        tokens.push(Token::new(TokenType::Eof, "", span));
+                        break;
+                    }
+                }
             }
-            tokens.push(self.scan_token()?);
         }
 
-        // Always append EOF token
-        tokens.push(Token::new(
-            TokenType::Eof,
-            String::new(),
-            self.line,
-            self.column,
-        ));
+        (tokens, diagnostics)
+    }
 
-        Ok(tokens)
+    /// Returns an iterator that scans tokens on demand.
+    ///
+    /// The iterator yields the same tokens as [`tokenize`](Self::tokenize),
+    /// ending with the EOF token, without holding them all in memory. It
+    /// stops after the first error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::lexer::Lexer;
+    /// use rpn2tex::tokens::TokenType;
+    ///
+    /// let mut tokens = Lexer::new("5 @").iter();
+    /// assert_eq!(tokens.next().unwrap().unwrap().value, "5");
+    /// assert!(tokens.next().unwrap().is_err());
+    /// assert!(tokens.next().is_none());
+    ///
+    /// let last = Lexer::new("5 3 +").iter().last().unwrap().unwrap();
+    /// assert_eq!(last.token_type, TokenType::Eof);
+    /// ```
+    #[must_use]
+    pub fn iter(self) -> Tokens<'a> {
+        Tokens {
+            lexer: self,
+            finished: false,
+        }
     }
 
     /// Checks if we've reached the end of input.
@@ -200,7 +647,12 @@ impl Lexer {
 
     /// Returns the current character without advancing position.
     fn peek(&self) -> char {
-        self.text[self.pos..].chars().next().unwrap_or('\0')
+        self.peek_at(0)
+    }
+
+    /// Returns the character `n` characters ahead without advancing.
+    fn peek_at(&self, n: usize) -> char {
+        self.text[self.pos..].chars().nth(n).unwrap_or('\0')
     }
 
     /// Consumes and returns the current character, updating position tracking.
@@ -224,114 +676,356 @@ impl Lexer {
         ch
     }
 
-    /// Skips whitespace characters (space, tab, newline, carriage return).
+    /// Skips whitespace characters (space, tab, newline, carriage return)
+    /// and comments, which run from `#` to the end of the line.
     fn skip_whitespace(&mut self) {
-        while !self.at_end() {
-            let ch = self.peek();
-            if matches!(ch, ' ' | '\t' | '\n' | '\r') {
+        while self.scan_trivia().is_some() {}
+    }
+
+    /// Consumes one run of whitespace or one comment, returning which it
+    /// was, or `None` if the input does not continue with either.
+    fn scan_trivia(&mut self) -> Option<TokenType> {
+        let blank = |ch| matches!(ch, ' ' | '\t' | '\n' | '\r');
+        if blank(self.peek()) {
+            while blank(self.peek()) {
                 self.advance();
-            } else {
-                break;
             }
+            Some(TokenType::Whitespace)
+        } else if self.peek() == '#' {
+            // A comment ends before the line break, `\n` or `\r\n`
+            while !self.at_end()
+                && self.peek() != '\n'
+                && !(self.peek() == '\r' && self.peek_at(1) == '\n')
+            {
+                self.advance();
+            }
+            Some(TokenType::Comment)
+        } else {
+            None
+        }
+    }
+
+    /// Skips whitespace and scans the next token, or EOF at the end of the
+    /// input, checking it against the limits. In trivia mode whitespace and
+    /// comments are returned as tokens instead.
+    fn next_token(&mut self) -> Result<Token<'a>, LexerError> {
+        let over_input_limit = |lexer: &Self| {
+            lexer
+                .limits
+                .max_input_bytes()
+                .filter(|&max| lexer.offset + lexer.pos > max)
+                .map(LimitExceeded::InputBytes)
+        };
+
+        if self.trivia {
+            let (start, line, column) = (self.pos, self.line, self.column);
+            if let Some(token_type) = self.scan_trivia() {
+                let span = self.span_from(start, line, column);
+                if let Some(limit) = over_input_limit(self) {
+                    return Err(LexerError::from_limit(limit, span));
+                }
+                return Ok(Token::new(token_type, &self.text[start..self.pos], span));
+            }
+        } else {
+            self.skip_whitespace();
+        }
+
+        if self.at_end() {
+            let span = self.span_from(self.pos, self.line, self.column);
+            return match over_input_limit(self) {
+                Some(limit) => Err(LexerError::from_limit(limit, span)),
+                None => Ok(Token::new(TokenType::Eof, "", span)),
+            };
+        }
+
+        let token = self.scan_token()?;
+        self.after_operand = matches!(
+            token.token_type,
+            TokenType::Number | TokenType::Identifier | TokenType::RightParen
+        );
+        if let Some(limit) = over_input_limit(self) {
+            return Err(LexerError::from_limit(limit, token.span));
         }
+        self.scanned += 1;
+        if let Some(max) = self.limits.max_tokens().filter(|&max| self.scanned > max) {
+            return Err(LexerError::from_limit(
+                LimitExceeded::Tokens(max),
+                token.span,
+            ));
+        }
+        Ok(token)
     }
 
     /// Scans the next token from the input.
-    fn scan_token(&mut self) -> Result<Token, LexerError> {
+    fn scan_token(&mut self) -> Result<Token<'a>, LexerError> {
+        let start = self.pos;
         let start_line = self.line;
         let start_column = self.column;
+        let operator = self.operators.longest_prefix(&self.text[start..]);
+        if let Some(length) = operator.map(|spelling| spelling.chars().count()) {
+            for _ in 0..length {
+                self.advance();
+            }
+            return Ok(Token::new(
+                TokenType::Operator,
+                &self.text[start..self.pos],
+                self.span_from(start, start_line, start_column),
+            ));
+        }
         let ch = self.advance();
 
-        match ch {
-            '+' => Ok(Token::new(
-                TokenType::Plus,
-                "+".to_string(),
-                start_line,
-                start_column,
-            )),
-            '*' => Ok(Token::new(
-                TokenType::Mult,
-                "*".to_string(),
-                start_line,
-                start_column,
-            )),
-            '/' => Ok(Token::new(
-                TokenType::Div,
-                "/".to_string(),
-                start_line,
-                start_column,
-            )),
-            '-' => {
+        let token_type = match ch {
+            '+' => TokenType::Plus,
+            // `×` and `·` are typeset multiplication, `÷` typeset division
+            '*' | '×' | '·' => TokenType::Mult,
+            '/' | '÷' => TokenType::Div,
+            // `−` is the Unicode minus sign
+            '-' | '−' => {
                 // Minus can be subtraction operator OR negative number prefix
-                // If followed immediately by a digit (no whitespace), it's a negative number
-                if !self.at_end() && self.peek().is_ascii_digit() {
-                    self.scan_number("-".to_string(), start_line, start_column)
+                // If followed immediately by a digit (no whitespace), it's a negative number,
+                // unless infix input has an operand for it to subtract from
+                if !(self.infix && self.after_operand)
+                    && (self.is_digit(self.peek())
+                        || (self.peek() == '.' && self.is_digit(self.peek_at(1))))
+                {
+                    self.scan_number();
+                    TokenType::Number
                 } else {
-                    Ok(Token::new(
-                        TokenType::Minus,
-                        "-".to_string(),
-                        start_line,
-                        start_column,
-                    ))
+                    TokenType::Minus
                 }
             }
-            _ if ch.is_ascii_digit() => self.scan_number(ch.to_string(), start_line, start_column),
-            _ => Err(LexerError::new(
-                format!("Unexpected character '{}'", ch),
-                start_line,
-                start_column,
-            )),
+            _ if self.is_digit(ch) => {
+                self.scan_number();
+                TokenType::Number
+            }
+            // A leading-dot decimal such as `.5`
+            '.' if self.is_digit(self.peek()) => {
+                self.scan_digits(false);
+                TokenType::Number
+            }
+            _ if ch.is_ascii_alphabetic() && self.identifiers => {
+                self.scan_identifier();
+                TokenType::Identifier
+            }
+            '(' if self.infix => TokenType::LeftParen,
+            ')' if self.infix => TokenType::RightParen,
+            _ => {
+                let error = LexerError::new(
+                    format!("Unexpected character '{}'", ch),
+                    self.span_from(start, start_line, start_column),
+                )
+                .with_code(ErrorCode::UnexpectedCharacter);
+                return Err(match suggestion(ch) {
+                    Some(help) => error.with_help(help),
+                    None => error,
+                });
+            }
+        };
+
+        // A dot straight after a complete number cannot start a new token
+        // sensibly, so the whole literal is malformed
+        if token_type == TokenType::Number && !self.lenient_numbers && self.peek() == '.' {
+            while self.peek() == '.' || self.peek() == '_' || self.is_digit(self.peek()) {
+                self.advance();
+            }
+            return Err(LexerError::new(
+                format!("Malformed number literal '{}'", &self.text[start..self.pos]),
+                self.span_from(start, start_line, start_column),
+            )
+            .with_code(ErrorCode::MalformedNumber));
         }
+
+        // Numbers are normalized to ASCII so later stages can read them; the
+        // span still covers the original text
+        let lexeme = &self.text[start..self.pos];
+        let value = if token_type == TokenType::Number {
+            normalize_number(lexeme)
+        } else {
+            Cow::Borrowed(lexeme)
+        };
+
+        Ok(Token::new(
+            token_type,
+            value,
+            self.span_from(start, start_line, start_column),
+        ))
     }
 
-    /// Scans a number token (integer or decimal).
-    ///
-    /// Numbers consist of digits with an optional decimal point.
-    /// The prefix parameter contains any characters already consumed (e.g., "-" or first digit).
-    fn scan_number(
-        &mut self,
-        mut prefix: String,
-        start_line: u32,
-        start_column: u32,
-    ) -> Result<Token, LexerError> {
+    /// Returns the span from byte `start` of the text, at `line` and
+    /// `column`, to the current position.
+    fn span_from(&self, start: usize, line: u32, column: u32) -> Span {
+        Span::new(self.offset + start, self.offset + self.pos, line, column)
+    }
+
+    /// Consumes the rest of a number (integer or decimal) whose first
+    /// character, a digit or `-`, has already been consumed. The integer
+    /// part may be empty, as in `-.5`.
+    fn scan_number(&mut self) {
         // Scan integer part
-        while !self.at_end() && self.peek().is_ascii_digit() {
-            prefix.push(self.advance());
-        }
+        self.scan_digits(self.comma_groups);
 
         // Scan optional decimal part
         if !self.at_end() && self.peek() == '.' {
             // Look ahead to ensure there's a digit after the decimal point
-            let pos_after_dot = self.pos + 1;
-            if pos_after_dot < self.text.len()
-                && self.text[pos_after_dot..]
-                    .chars()
-                    .next()
-                    .is_some_and(|c| c.is_ascii_digit())
-            {
-                prefix.push(self.advance()); // consume '.'
-                while !self.at_end() && self.peek().is_ascii_digit() {
-                    prefix.push(self.advance());
+            if self.is_digit(self.peek_at(1)) {
+                self.advance(); // consume '.'
+                self.scan_digits(false);
+            }
+        }
+    }
+
+    /// Consumes a run of digits and the group separators between them: `_`
+    /// before another digit and, if `commas` is set, `,` before exactly
+    /// three digits.
+    fn scan_digits(&mut self, commas: bool) {
+        loop {
+            while !self.at_end() && self.is_digit(self.peek()) {
+                self.advance();
+            }
+            let separator = match self.peek() {
+                '_' => self.is_digit(self.peek_at(1)),
+                ',' if commas => {
+                    (1..=3).all(|n| self.is_digit(self.peek_at(n)))
+                        && !self.is_digit(self.peek_at(4))
                 }
+                _ => false,
+            };
+            if !separator {
+                break;
             }
+            self.advance();
         }
+    }
 
-        Ok(Token::new(
-            TokenType::Number,
-            prefix,
-            start_line,
-            start_column,
-        ))
+    /// Returns `true` if `ch` is a digit this lexer accepts in numbers.
+    fn is_digit(&self, ch: char) -> bool {
+        ch.is_ascii_digit() || (self.unicode_digits && ascii_digit(ch).is_some())
+    }
+
+    /// Consumes the rest of an identifier, whose first letter has already
+    /// been consumed: any ASCII letters and digits.
+    fn scan_identifier(&mut self) {
+        while !self.at_end() && self.peek().is_ascii_alphanumeric() {
+            self.advance();
+        }
+    }
+}
+
+/// Iterator over the tokens of a [`Lexer`], created by [`Lexer::iter`].
+#[derive(Debug, Clone)]
+pub struct Tokens<'a> {
+    /// Lexer scanning the remaining input
+    lexer: Lexer<'a>,
+    /// Whether the EOF token or an error has been yielded
+    finished: bool,
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Result<Token<'a>, LexerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let token = self.lexer.next_token();
+        self.finished = token
+            .as_ref()
+            .map_or(true, |token| token.token_type == TokenType::Eof);
+        Some(token)
+    }
+}
+
+impl FusedIterator for Tokens<'_> {}
+
+/// First code points of the Unicode decimal digit ranges accepted with
+/// [`Lexer::with_unicode_digits`]; each range holds 0-9 in order.
+const DIGIT_ZEROS: [u32; 22] = [
+    0x0660,  // Arabic-Indic
+    0x06F0,  // Extended Arabic-Indic
+    0x07C0,  // NKo
+    0x0966,  // Devanagari
+    0x09E6,  // Bengali
+    0x0A66,  // Gurmukhi
+    0x0AE6,  // Gujarati
+    0x0B66,  // Oriya
+    0x0BE6,  // Tamil
+    0x0C66,  // Telugu
+    0x0CE6,  // Kannada
+    0x0D66,  // Malayalam
+    0x0E50,  // Thai
+    0x0ED0,  // Lao
+    0x0F20,  // Tibetan
+    0x1040,  // Myanmar
+    0x17E0,  // Khmer
+    0x1810,  // Mongolian
+    0xFF10,  // Fullwidth
+    0x1D7CE, // Mathematical bold
+    0x1D7D8, // Mathematical double-struck
+    0x1D7E2, // Mathematical sans-serif
+];
+
+/// Returns the ASCII digit with the same value as a decimal digit from
+/// another script, or `ch` itself if it is already ASCII.
+fn ascii_digit(ch: char) -> Option<char> {
+    if ch.is_ascii_digit() {
+        return Some(ch);
+    }
+    let code = u32::from(ch);
+    DIGIT_ZEROS
+        .iter()
+        .find(|&&zero| (zero..zero + 10).contains(&code))
+        .and_then(|&zero| char::from_digit(code - zero, 10))
+}
+
+/// Rewrites a number lexeme with an ASCII sign and digits and without
+/// group separators, borrowing it when it is already in that form.
+fn normalize_number(lexeme: &str) -> Cow<'_, str> {
+    if lexeme.is_ascii() && !lexeme.contains(['_', ',']) {
+        return Cow::Borrowed(lexeme);
+    }
+    Cow::Owned(
+        lexeme
+            .chars()
+            .filter(|&ch| ch != '_' && ch != ',')
+            .map(|ch| match ch {
+                '−' => '-',
+                _ => ascii_digit(ch).unwrap_or(ch),
+            })
+            .collect(),
+    )
+}
+
+/// Returns a hint for a character that cannot start a token but looks like
+/// something the user may have meant.
+///
+/// Letters only reach here when identifiers are disabled.
+fn suggestion(ch: char) -> Option<&'static str> {
+    match ch {
+        '^' => Some("exponentiation is not supported; multiply the base by itself, as in `2 2 *`"),
+        'x' | 'X' => Some("multiplication is written `*`; variable names require --allow-vars"),
+        '∙' | '⋅' => Some("multiplication is written `*`"),
+        ':' => Some("division is written `/`"),
+        '–' | '—' => Some("subtraction is written `-`"),
+        '(' | ')' | '[' | ']' => {
+            Some("RPN needs no parentheses; write operands before their operator")
+        }
+        ',' => Some("decimal points are written `.`; digits are grouped with `_`, or with `,` under --comma-groups"),
+        _ if ascii_digit(ch).is_some() => Some("digits other than 0-9 require --unicode-digits"),
+        _ if ch.is_alphabetic() => Some("variable names require --allow-vars"),
+        _ => None,
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::parser::Parser;
+    use std::borrow::Cow;
 
     #[test]
     fn test_lexer_error_creation() {
-        let error = LexerError::new("Test error", 1, 5);
+        let error = LexerError::new("Test error", Span::new(4, 5, 1, 5));
         assert_eq!(error.message(), "Test error");
         assert_eq!(error.line(), 1);
         assert_eq!(error.column(), 5);
@@ -339,12 +1033,25 @@ mod tests {
 
     #[test]
     fn test_lexer_error_display() {
-        let error = LexerError::new("Unexpected character", 2, 10);
+        let error = LexerError::new("Unexpected character", Span::new(20, 21, 2, 10));
         let display = format!("{}", error);
         assert!(display.contains("Line 2, column 10"));
         assert!(display.contains("Unexpected character"));
     }
 
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_lexer_error_json() {
+        let error = LexerError::new("Unexpected character '^'", Span::new(4, 5, 1, 5));
+        let json = serde_json::to_string(&error).expect("serialize failed");
+        assert_eq!(
+            json,
+            r#"{"message":"Unexpected character '^'","start":4,"end":5,"line":1,"column":5}"#
+        );
+        let back: LexerError = serde_json::from_str(&json).expect("deserialize failed");
+        assert_eq!(back, error);
+    }
+
     #[test]
     fn test_tokenize_empty_string() {
         let lexer = Lexer::new("");
@@ -360,8 +1067,8 @@ mod tests {
         assert_eq!(tokens.len(), 2); // number + EOF
         assert_eq!(tokens[0].token_type, TokenType::Number);
         assert_eq!(tokens[0].value, "42");
-        assert_eq!(tokens[0].line, 1);
-        assert_eq!(tokens[0].column, 1);
+        assert_eq!(tokens[0].span.line, 1);
+        assert_eq!(tokens[0].span.column, 1);
         assert_eq!(tokens[1].token_type, TokenType::Eof);
     }
 
@@ -501,23 +1208,23 @@ mod tests {
         let lexer = Lexer::new("5 3 +");
         let tokens = lexer.tokenize().expect("tokenize failed");
         // 5 at column 1, 3 at column 3, + at column 5
-        assert_eq!(tokens[0].column, 1);
-        assert_eq!(tokens[1].column, 3);
-        assert_eq!(tokens[2].column, 5);
+        assert_eq!(tokens[0].span.column, 1);
+        assert_eq!(tokens[1].span.column, 3);
+        assert_eq!(tokens[2].span.column, 5);
     }
 
     #[test]
     fn test_multiline_position_tracking() {
         let lexer = Lexer::new("5 3\n+ 2");
         let tokens = lexer.tokenize().expect("tokenize failed");
-        assert_eq!(tokens[0].line, 1);
-        assert_eq!(tokens[0].column, 1); // 5
-        assert_eq!(tokens[1].line, 1);
-        assert_eq!(tokens[1].column, 3); // 3
-        assert_eq!(tokens[2].line, 2);
-        assert_eq!(tokens[2].column, 1); // +
-        assert_eq!(tokens[3].line, 2);
-        assert_eq!(tokens[3].column, 3); // 2
+        assert_eq!(tokens[0].span.line, 1);
+        assert_eq!(tokens[0].span.column, 1); // 5
+        assert_eq!(tokens[1].span.line, 1);
+        assert_eq!(tokens[1].span.column, 3); // 3
+        assert_eq!(tokens[2].span.line, 2);
+        assert_eq!(tokens[2].span.column, 1); // +
+        assert_eq!(tokens[3].span.line, 2);
+        assert_eq!(tokens[3].span.column, 3); // 2
     }
 
     #[test]
@@ -594,7 +1301,7 @@ mod tests {
         let eof_token = tokens.last().unwrap();
         assert_eq!(eof_token.token_type, TokenType::Eof);
         // EOF should be at the position after the last character
-        assert_eq!(eof_token.line, 1);
+        assert_eq!(eof_token.span.line, 1);
     }
 
     #[test]
@@ -610,14 +1317,437 @@ mod tests {
 
     #[test]
     fn test_error_equality() {
-        let err1 = LexerError::new("Test", 1, 5);
-        let err2 = LexerError::new("Test", 1, 5);
-        let err3 = LexerError::new("Other", 1, 5);
+        let span = Span::new(4, 5, 1, 5);
+        let err1 = LexerError::new("Test", span);
+        let err2 = LexerError::new("Test", span);
+        let err3 = LexerError::new("Other", span);
 
         assert_eq!(err1, err2);
         assert_ne!(err1, err3);
     }
 
+    #[test]
+    fn test_identifiers_disabled_by_default() {
+        let error = Lexer::new("5 x +").tokenize().unwrap_err();
+        assert_eq!(error.message(), "Unexpected character 'x'");
+        assert_eq!(error.column(), 3);
+    }
+
+    #[test]
+    fn test_near_miss_suggestions() {
+        let help = |source: &str| {
+            Lexer::new(source)
+                .tokenize()
+                .unwrap_err()
+                .help()
+                .map(String::from)
+        };
+        assert_eq!(
+            help("2 3 ^").as_deref(),
+            Some("exponentiation is not supported; multiply the base by itself, as in `2 2 *`")
+        );
+        assert_eq!(
+            help("2 3 x").as_deref(),
+            Some("multiplication is written `*`; variable names require --allow-vars")
+        );
+        assert_eq!(
+            help("2 rate +").as_deref(),
+            Some("variable names require --allow-vars")
+        );
+        assert_eq!(help("6 2 :").as_deref(), Some("division is written `/`"));
+        assert_eq!(
+            help("( 2 3 + )").as_deref(),
+            Some("RPN needs no parentheses; write operands before their operator")
+        );
+        assert_eq!(help("2 @"), None);
+    }
+
+    #[test]
+    fn test_recovering_keeps_suggestions() {
+        let (_, diagnostics) = Lexer::new("2 3 ∙ @").tokenize_recovering();
+        assert_eq!(diagnostics[0].help(), Some("multiplication is written `*`"));
+        assert_eq!(diagnostics[1].help(), None);
+    }
+
+    #[test]
+    fn test_input_byte_limit() {
+        let limits = Limits::new().with_max_input_bytes(5);
+        assert!(Lexer::new("5 3 +").with_limits(limits).tokenize().is_ok());
+
+        // A token ending past the limit is reported
+        let error = Lexer::new("5 3 10 +")
+            .with_limits(limits)
+            .tokenize()
+            .unwrap_err();
+        assert_eq!(error.limit_exceeded(), Some(LimitExceeded::InputBytes(5)));
+        assert_eq!(error.span(), Span::new(4, 6, 1, 5));
+
+        // So is trailing whitespace, at the end of input
+        let error = Lexer::new("5 3 +   ")
+            .with_limits(limits)
+            .tokenize()
+            .unwrap_err();
+        assert_eq!(error.span(), Span::new(8, 8, 1, 9));
+
+        // The limit counts from the start of the whole source
+        let error = Lexer::new("3 +")
+            .with_start(4, 1, 5)
+            .with_limits(limits)
+            .tokenize()
+            .unwrap_err();
+        assert_eq!(error.column(), 7);
+    }
+
+    #[test]
+    fn test_token_limit() {
+        let limits = Limits::new().with_max_tokens(2);
+        let mut tokens = Lexer::new("5 3 + 2").with_limits(limits).iter();
+        assert!(tokens.next().unwrap().is_ok());
+        assert!(tokens.next().unwrap().is_ok());
+        let error = tokens.next().unwrap().unwrap_err();
+        assert_eq!(error.message(), "Input exceeds the limit of 2 tokens");
+        assert_eq!(error.column(), 5);
+        assert!(tokens.next().is_none());
+
+        // Syntax errors are not limit errors
+        let error = Lexer::new("@").with_limits(limits).tokenize().unwrap_err();
+        assert_eq!(error.limit_exceeded(), None);
+    }
+
+    #[test]
+    fn test_recovering_stops_at_limit() {
+        let limits = Limits::new().with_max_tokens(2);
+        let (tokens, diagnostics) = Lexer::new("5 @ 3 + $ 2 *")
+            .with_limits(limits)
+            .tokenize_recovering();
+        let values: Vec<&str> = tokens.iter().map(|t| t.value.as_ref()).collect();
+        assert_eq!(values, ["5", "3", ""]);
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Unexpected character '@'",
+                "Input exceeds the limit of 2 tokens"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_trivia_tokens() {
+        let source = "# area\n5\t3 *  # product\r\n";
+        let tokens = Lexer::new(source)
+            .with_trivia(true)
+            .tokenize()
+            .expect("tokenize failed");
+        let types: Vec<TokenType> = tokens.iter().map(|t| t.token_type).collect();
+        assert_eq!(
+            types,
+            [
+                TokenType::Comment,
+                TokenType::Whitespace,
+                TokenType::Number,
+                TokenType::Whitespace,
+                TokenType::Number,
+                TokenType::Whitespace,
+                TokenType::Mult,
+                TokenType::Whitespace,
+                TokenType::Comment,
+                TokenType::Whitespace,
+                TokenType::Eof,
+            ]
+        );
+        assert_eq!(tokens[8].value, "# product");
+        assert_eq!(tokens[9].span, Span::new(23, 25, 2, 17));
+
+        let rebuilt: String = tokens.iter().map(|t| t.span.text(source)).collect();
+        assert_eq!(rebuilt, source);
+    }
+
+    #[test]
+    fn test_trivia_does_not_count_as_tokens() {
+        let tokens = Lexer::new("5   3 +")
+            .with_trivia(true)
+            .with_limits(Limits::new().with_max_tokens(3))
+            .tokenize()
+            .expect("tokenize failed");
+        assert_eq!(tokens.len(), 6);
+
+        let ast = Parser::new(tokens).parse().expect("parse failed");
+        assert_eq!(ast.column(), 7);
+    }
+
+    #[test]
+    fn test_unicode_operators() {
+        let tokens = Lexer::new("6 2 × 3 ÷ 1 − 4 · 2 −5 +")
+            .tokenize()
+            .expect("tokenize failed");
+        let types: Vec<TokenType> = tokens.iter().map(|t| t.token_type).collect();
+        assert_eq!(
+            types,
+            [
+                TokenType::Number,
+                TokenType::Number,
+                TokenType::Mult,
+                TokenType::Number,
+                TokenType::Div,
+                TokenType::Number,
+                TokenType::Minus,
+                TokenType::Number,
+                TokenType::Mult,
+                TokenType::Number,
+                TokenType::Number,
+                TokenType::Plus,
+                TokenType::Eof,
+            ]
+        );
+        assert_eq!(tokens[2].value, "×");
+
+        // A Unicode minus before digits gives an ASCII negative number
+        assert_eq!(tokens[10].value, "-5");
+        assert_eq!(tokens[10].span.text("6 2 × 3 ÷ 1 − 4 · 2 −5 +"), "−5");
+    }
+
+    #[test]
+    fn test_unicode_digits() {
+        let source = "５.２５ −٣ ۴ १० ๙ 𝟕 +";
+        let tokens = Lexer::new(source)
+            .with_unicode_digits(true)
+            .tokenize()
+            .expect("tokenize failed");
+        let values: Vec<&str> = tokens.iter().map(|t| t.value.as_ref()).collect();
+        assert_eq!(values, ["5.25", "-3", "4", "10", "9", "7", "+", ""]);
+        assert_eq!(tokens[0].span.text(source), "５.２５");
+        assert_eq!(tokens[1].span.column, 6);
+
+        // ASCII numbers are still borrowed from the source
+        let tokens = Lexer::new("42")
+            .with_unicode_digits(true)
+            .tokenize()
+            .unwrap();
+        assert!(matches!(tokens[0].value, Cow::Borrowed("42")));
+    }
+
+    #[test]
+    fn test_unicode_digits_disabled_by_default() {
+        let error = Lexer::new("5 ５ +").tokenize().unwrap_err();
+        assert_eq!(error.message(), "Unexpected character '５'");
+        assert_eq!(
+            error.help(),
+            Some("digits other than 0-9 require --unicode-digits")
+        );
+    }
+
+    #[test]
+    fn test_leading_dot_decimals() {
+        let source = ".5 -.25 + 2 .";
+        let (tokens, diagnostics) = Lexer::new(source).tokenize_recovering();
+        let values: Vec<&str> = tokens.iter().map(|t| t.value.as_ref()).collect();
+        assert_eq!(values, [".5", "-.25", "+", "2", ""]);
+        assert_eq!(tokens[1].span, Span::new(3, 7, 1, 4));
+
+        // A dot not followed by a digit is still rejected
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].column(), 13);
+    }
+
+    #[test]
+    fn test_malformed_numbers() {
+        for (source, literal) in [
+            ("5. 2 +", "5."),
+            ("1.2.3 2 +", "1.2.3"),
+            ("2 -.5. +", "-.5."),
+            ("3..4 2 +", "3..4"),
+        ] {
+            let error = Lexer::new(source).tokenize().unwrap_err();
+            assert_eq!(
+                error.message(),
+                format!("Malformed number literal '{}'", literal)
+            );
+            assert_eq!(error.span().text(source), literal);
+        }
+    }
+
+    #[test]
+    fn test_malformed_numbers_recovering() {
+        let (tokens, diagnostics) = Lexer::new("5. 1.2.3 +").tokenize_recovering();
+        assert_eq!(tokens.len(), 2); // +, EOF
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[1].span(), Span::new(3, 8, 1, 4));
+    }
+
+    #[test]
+    fn test_lenient_numbers() {
+        let lexer = |source| Lexer::new(source).with_lenient_numbers(true).tokenize();
+        let error = lexer("5. 2 +").unwrap_err();
+        assert_eq!(error.message(), "Unexpected character '.'");
+        assert_eq!(error.column(), 2);
+
+        let tokens = lexer("1.2.3 +").expect("tokenize failed");
+        let values: Vec<&str> = tokens.iter().map(|t| t.value.as_ref()).collect();
+        assert_eq!(values, ["1.2", ".3", "+", ""]);
+    }
+
+    #[test]
+    fn test_underscore_groups() {
+        let source = "1_000_000 -2_5.000_1 +";
+        let tokens = Lexer::new(source).tokenize().expect("tokenize failed");
+        assert_eq!(tokens[0].value, "1000000");
+        assert_eq!(tokens[0].span.text(source), "1_000_000");
+        assert_eq!(tokens[1].value, "-25.0001");
+        assert_eq!(tokens[2].span.column, 22);
+
+        // An underscore must sit between two digits
+        for source in ["1_ 2 +", "1__0 2 +", "1_.5 2 +"] {
+            let error = Lexer::new(source).tokenize().unwrap_err();
+            assert!(error.message().contains('_'), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_comma_groups() {
+        let lexer = |source| Lexer::new(source).with_comma_groups(true).tokenize();
+        let tokens = lexer("1,000,000.5 12,345 +").expect("tokenize failed");
+        assert_eq!(tokens[0].value, "1000000.5");
+        assert_eq!(tokens[1].value, "12345");
+
+        // Each comma must be followed by exactly three digits
+        assert_eq!(lexer("1,5 2 +").unwrap_err().column(), 2);
+        assert_eq!(lexer("1,0000 2 +").unwrap_err().column(), 2);
+        assert_eq!(lexer("1.000,000 2 +").unwrap_err().column(), 6);
+    }
+
+    #[test]
+    fn test_ascii_digit() {
+        assert_eq!(ascii_digit('7'), Some('7'));
+        assert_eq!(ascii_digit('٠'), Some('0'));
+        assert_eq!(ascii_digit('９'), Some('9'));
+        assert_eq!(ascii_digit('²'), None);
+        assert_eq!(ascii_digit('a'), None);
+    }
+
+    #[test]
+    fn test_columns_count_characters_not_bytes() {
+        let tokens = Lexer::new("6 × 2\n8 ÷ @").tokenize_recovering().0;
+        // `×` is two bytes but one column
+        assert_eq!(tokens[2].span, Span::new(5, 6, 1, 5));
+        assert_eq!(tokens[4].span, Span::new(9, 11, 2, 3));
+
+        let error = Lexer::new("8 ÷ @").tokenize().unwrap_err();
+        assert_eq!(error.column(), 5);
+    }
+
+    #[test]
+    fn test_tokenize_identifiers() {
+        let tokens = Lexer::new("rate2 x -")
+            .with_identifiers(true)
+            .tokenize()
+            .expect("tokenize failed");
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(tokens[0].token_type, TokenType::Identifier);
+        assert_eq!(tokens[0].value, "rate2");
+        assert_eq!(tokens[1].token_type, TokenType::Identifier);
+        assert_eq!(tokens[1].value, "x");
+        assert_eq!(tokens[1].span.column, 7);
+        assert_eq!(tokens[2].token_type, TokenType::Minus);
+    }
+
+    #[test]
+    fn test_identifier_stops_at_non_alphanumeric() {
+        let error = Lexer::new("x_1")
+            .with_identifiers(true)
+            .tokenize()
+            .unwrap_err();
+        assert_eq!(error.message(), "Unexpected character '_'");
+        assert_eq!(error.column(), 2);
+    }
+
+    #[test]
+    fn test_comments_are_skipped() {
+        let tokens = Lexer::new("# perimeter of a rectangle\n5 3 +  # sides\n2 * # double\n")
+            .tokenize()
+            .expect("tokenize failed");
+        let values: Vec<&str> = tokens.iter().map(|t| t.value.as_ref()).collect();
+        assert_eq!(values, ["5", "3", "+", "2", "*", ""]);
+
+        // Positions after a comment are unaffected
+        assert_eq!((tokens[0].span.line, tokens[0].span.column), (2, 1));
+        assert_eq!((tokens[3].span.line, tokens[3].span.column), (3, 1));
+        assert_eq!((tokens[5].span.line, tokens[5].span.column), (4, 1));
+    }
+
+    #[test]
+    fn test_comment_ends_token() {
+        let tokens = Lexer::new("5#five\n3# three\r\n+")
+            .tokenize()
+            .expect("tokenize failed");
+        let values: Vec<&str> = tokens.iter().map(|t| t.value.as_ref()).collect();
+        assert_eq!(values, ["5", "3", "+", ""]);
+    }
+
+    #[test]
+    fn test_comment_may_contain_anything() {
+        let tokens = Lexer::new("1 # ^ @ × ünïcode")
+            .tokenize()
+            .expect("tokenize failed");
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[1].token_type, TokenType::Eof);
+        assert_eq!(tokens[1].span.column, 18);
+    }
+
+    #[test]
+    fn test_spans_cover_whole_tokens() {
+        let input = "12.5 -3 *\n  # note\n x +";
+        let tokens = Lexer::new(input).with_identifiers(true).tokenize().unwrap();
+        let spans: Vec<Span> = tokens.iter().map(|t| t.span).collect();
+        assert_eq!(
+            spans,
+            [
+                Span::new(0, 4, 1, 1),
+                Span::new(5, 7, 1, 6),
+                Span::new(8, 9, 1, 9),
+                Span::new(20, 21, 3, 2),
+                Span::new(22, 23, 3, 4),
+                Span::new(23, 23, 3, 5),
+            ]
+        );
+        for token in &tokens {
+            assert_eq!(token.span.text(input), token.value);
+        }
+    }
+
+    #[test]
+    fn test_token_values_borrow_from_source() {
+        let tokens = Lexer::new("-3.5 rate2 *")
+            .with_identifiers(true)
+            .tokenize()
+            .unwrap();
+        for token in &tokens {
+            assert!(matches!(token.value, Cow::Borrowed(_)), "{}", token);
+        }
+        assert_eq!(tokens[0].value, "-3.5");
+        assert_eq!(tokens[1].value, "rate2");
+    }
+
+    #[test]
+    fn test_iter_matches_tokenize() {
+        let input = "3.14 2 * # area\n  r 2 / -";
+        let expected = Lexer::new(input).with_identifiers(true).tokenize();
+        let streamed: Result<Vec<Token>, LexerError> =
+            Lexer::new(input).with_identifiers(true).iter().collect();
+        assert_eq!(streamed, expected);
+    }
+
+    #[test]
+    fn test_iter_is_lazy_and_fused() {
+        let mut tokens = Lexer::new("1 2 @ 3").iter();
+        assert_eq!(tokens.next().unwrap().unwrap().value, "1");
+        assert_eq!(tokens.next().unwrap().unwrap().value, "2");
+        let error = tokens.next().unwrap().unwrap_err();
+        assert_eq!((error.line(), error.column()), (1, 5));
+        assert!(tokens.next().is_none());
+        assert!(tokens.next().is_none());
+    }
+
     #[test]
     fn test_carriage_return_handling() {
         let lexer = Lexer::new("5\r\n3\r\n+");
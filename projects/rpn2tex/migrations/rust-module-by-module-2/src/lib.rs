@@ -2,10 +2,85 @@
 //!
 //! This library converts Reverse Polish Notation (RPN) mathematical expressions
 //! to LaTeX format with proper operator precedence and parenthesization.
+//!
+//! The default `serde` feature derives `Serialize` and `Deserialize` for the
+//! intermediate artifacts, tokens, ASTs, spans, programs and macro tables,
+//! and for the errors and diagnostics, so they can be stored or sent between
+//! services.
+//!
+//! The default `cli` feature builds the command-line tool, along with the
+//! crates only it uses for JSON, CSV, TOML and logging; the `clipboard`,
+//! `readline` and `pdf` features extend the tool and imply it. Embedders
+//! can leave it out with `default-features = false`, and the library then
+//! depends on nothing beyond thiserror and, with `serde`, serde.
+//!
+//! The default `exact` feature adds exact rational evaluation
+//! (`eval::evaluate_exact`), constant folding and the linter's
+//! constant-expression check, along with the arbitrary-precision number
+//! crates they use.
+//!
+//! The `testing` feature adds the [`testing`] module, which generates random
+//! expressions for property tests and fuzzing.
+//!
+//! The `miette` feature renders diagnostics as miette reports, with labeled
+//! source snippets, for embedders that opt in and for the command line's
+//! `--fancy-errors`; plain formatting stays the default.
+//!
+//! The `tracing` feature runs the lexer, parser and generator of
+//! [`convert`] and [`Compiler`] in `tracing` spans named `lex`, `parse` and
+//! `generate`, each recording its duration in microseconds as `elapsed_us`,
+//! and emits a `WARN` event when a stage fails, so services embedding the
+//! library can observe its performance and failures.
+//!
+//! The companion `rpn2tex-macros` crate converts expressions at compile
+//! time: `rpn!("5 3 +")` expands to the LaTeX as a `&'static str`, and
+//! `rpn_expr!` to the [`Expr`](ast::Expr), with a compile error for input
+//! that does not convert.
 
 pub mod ast;
+pub mod batch;
+pub mod cache;
+pub mod cloze;
+pub mod codegen;
+pub mod context;
+mod decimal;
+pub mod diagnostic;
+pub mod difficulty;
+pub mod dot;
+pub mod encoding;
 pub mod error;
+pub mod error_code;
+pub mod eval;
+pub mod filter;
+pub mod format;
+pub mod infix;
 pub mod latex;
 pub mod lexer;
+pub mod limits;
+pub mod lint;
+pub mod macros;
+pub mod markdown;
+pub mod mathml;
+pub mod notation;
+pub mod number_format;
 pub mod parser;
+pub mod pipeline;
+pub mod plugin;
+mod precedence;
+pub mod program;
+pub mod python;
+pub mod session;
+pub mod span;
+#[cfg(test)]
+mod test_util;
+#[cfg(feature = "testing")]
+pub mod testing;
 pub mod tokens;
+mod trace;
+pub mod tree;
+pub mod wolfram;
+pub mod worksheet;
+
+pub use batch::compile_all;
+pub use error::Rpn2TexError;
+pub use pipeline::{compile, convert, convert_file, Compiler};
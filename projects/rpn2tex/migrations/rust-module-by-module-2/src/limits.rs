@@ -0,0 +1,198 @@
+//! Bounds on input size and expression complexity.
+//!
+//! Input from untrusted sources can be arbitrarily long or nest arbitrarily
+//! deep, and every later stage walks the tree recursively. [`Limits`] caps
+//! what the lexer and parser accept; exceeding a cap is reported as a
+//! [`LimitExceeded`] error rather than a syntax error, so callers can tell
+//! oversized input from malformed input.
+
+use crate::error_code::ErrorCode;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Caps on the input the lexer and parser accept. Every limit is off by
+/// default.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::lexer::Lexer;
+/// use rpn2tex::limits::{LimitExceeded, Limits};
+///
+/// let limits = Limits::new().with_max_tokens(3);
+/// assert!(Lexer::new("5 3 +").with_limits(limits).tokenize().is_ok());
+///
+/// let error = Lexer::new("5 3 + 2 *").with_limits(limits).tokenize().unwrap_err();
+/// assert_eq!(error.limit_exceeded(), Some(LimitExceeded::Tokens(3)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Limits {
+    /// Largest accepted input, in bytes
+    max_input_bytes: Option<usize>,
+    /// Most tokens accepted, not counting EOF
+    max_tokens: Option<usize>,
+    /// Deepest accepted expression tree, counting a lone number as 1
+    max_depth: Option<usize>,
+}
+
+impl Limits {
+    /// Creates limits that accept any input.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            max_input_bytes: None,
+            max_tokens: None,
+            max_depth: None,
+        }
+    }
+
+    /// Returns these limits rejecting input longer than `bytes`.
+    ///
+    /// The lexer reports the first token that ends past the limit, or the
+    /// end of input if only trailing whitespace does.
+    #[must_use]
+    pub const fn with_max_input_bytes(mut self, bytes: usize) -> Self {
+        self.max_input_bytes = Some(bytes);
+        self
+    }
+
+    /// Returns these limits rejecting input with more than `tokens` tokens,
+    /// not counting the EOF token.
+    #[must_use]
+    pub const fn with_max_tokens(mut self, tokens: usize) -> Self {
+        self.max_tokens = Some(tokens);
+        self
+    }
+
+    /// Returns these limits rejecting expressions whose tree is more than
+    /// `depth` levels deep; a lone number is one level.
+    #[must_use]
+    pub const fn with_max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Returns the largest accepted input in bytes, if limited.
+    #[must_use]
+    pub const fn max_input_bytes(&self) -> Option<usize> {
+        self.max_input_bytes
+    }
+
+    /// Returns the most tokens accepted, if limited.
+    #[must_use]
+    pub const fn max_tokens(&self) -> Option<usize> {
+        self.max_tokens
+    }
+
+    /// Returns the deepest accepted expression tree, if limited.
+    #[must_use]
+    pub const fn max_depth(&self) -> Option<usize> {
+        self.max_depth
+    }
+}
+
+/// Which limit an input exceeded, and its value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum LimitExceeded {
+    /// The input is longer than this many bytes
+    InputBytes(usize),
+    /// The input has more than this many tokens
+    Tokens(usize),
+    /// The expression nests deeper than this many levels
+    Depth(usize),
+}
+
+impl LimitExceeded {
+    /// Returns the stable code of an error for this limit.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::error_code::ErrorCode;
+    /// use rpn2tex::limits::LimitExceeded;
+    ///
+    /// assert_eq!(LimitExceeded::Depth(10).code(), ErrorCode::DepthLimit);
+    /// ```
+    #[must_use]
+    pub const fn code(self) -> ErrorCode {
+        match self {
+            Self::InputBytes(_) => ErrorCode::InputBytesLimit,
+            Self::Tokens(_) => ErrorCode::TokenLimit,
+            Self::Depth(_) => ErrorCode::DepthLimit,
+        }
+    }
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let plural = |limit: usize| if limit == 1 { "" } else { "s" };
+        match *self {
+            Self::InputBytes(limit) => write!(
+                f,
+                "Input exceeds the limit of {} byte{}",
+                limit,
+                plural(limit)
+            ),
+            Self::Tokens(limit) => write!(
+                f,
+                "Input exceeds the limit of {} token{}",
+                limit,
+                plural(limit)
+            ),
+            Self::Depth(limit) => write!(
+                f,
+                "Expression exceeds the nesting limit of {} level{}",
+                limit,
+                plural(limit)
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_is_unlimited() {
+        let limits = Limits::default();
+        assert_eq!(limits, Limits::new());
+        assert_eq!(limits.max_input_bytes(), None);
+        assert_eq!(limits.max_tokens(), None);
+        assert_eq!(limits.max_depth(), None);
+    }
+
+    #[test]
+    fn test_limit_exceeded_display() {
+        assert_eq!(
+            LimitExceeded::InputBytes(1024).to_string(),
+            "Input exceeds the limit of 1024 bytes"
+        );
+        assert_eq!(
+            LimitExceeded::Depth(3).to_string(),
+            "Expression exceeds the nesting limit of 3 levels"
+        );
+        assert_eq!(
+            LimitExceeded::Depth(1).to_string(),
+            "Expression exceeds the nesting limit of 1 level"
+        );
+        assert_eq!(
+            LimitExceeded::Tokens(1).to_string(),
+            "Input exceeds the limit of 1 token"
+        );
+        assert_eq!(
+            LimitExceeded::InputBytes(1).to_string(),
+            "Input exceeds the limit of 1 byte"
+        );
+    }
+
+    #[test]
+    fn test_each_limit_has_its_own_code() {
+        assert_eq!(LimitExceeded::InputBytes(1).code().as_str(), "E201");
+        assert_eq!(LimitExceeded::Tokens(1).code().as_str(), "E202");
+        assert_eq!(LimitExceeded::Depth(1).code().as_str(), "E203");
+    }
+}
@@ -0,0 +1,449 @@
+//! Style checks for RPN expressions.
+//!
+//! [`Linter`] looks for input that parses but is probably not what was
+//! meant, or could be written more simply: operations that change nothing,
+//! constant parts of an expression that could be worked out in advance
+//! (with the `exact` feature, which works them out),
+//! nesting too deep to read, and literals that look like typos. Each finding
+//! is a [`Lint`], a [`Diagnostic`] tagged with the [`Rule`] it breaks.
+
+use crate::ast::Expr;
+use crate::decimal::Decimal;
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::format::canonical_number;
+use crate::notation::{Notation, NotationGenerator};
+use crate::span::Span;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Significant digits a floating-point value keeps, past which a literal
+/// loses precision when evaluated.
+const SIGNIFICANT_DIGITS: usize = 15;
+
+/// The kinds of problem [`Linter`] looks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum Rule {
+    /// An operation that leaves its other operand unchanged, such as
+    /// `x 1 *`, or always gives 0, such as `x 0 *`
+    RedundantOperation,
+    /// Part of an expression made only of numbers, which could be written
+    /// as its value
+    ConstantExpression,
+    /// Operations nested deeper than the linter allows
+    DeepNesting,
+    /// A literal that looks like a mistake: leading zeros, a negative zero,
+    /// more digits than evaluation keeps, or a zero divisor
+    SuspiciousLiteral,
+}
+
+impl Rule {
+    /// Returns the rule's name, as written in the output.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::RedundantOperation => "redundant-operation",
+            Self::ConstantExpression => "constant-expression",
+            Self::DeepNesting => "deep-nesting",
+            Self::SuspiciousLiteral => "suspicious-literal",
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// One finding of the linter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Lint {
+    /// The rule the input breaks
+    pub rule: Rule,
+    /// What is wrong, where, and how to fix it
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub diagnostic: Diagnostic,
+}
+
+impl Lint {
+    /// Creates a lint breaking `rule`, as a warning.
+    fn new(rule: Rule, diagnostic: Diagnostic) -> Self {
+        Self {
+            rule,
+            diagnostic: diagnostic.with_severity(Severity::Warning),
+        }
+    }
+}
+
+/// Checks AST expressions for redundant, foldable, deeply nested and
+/// suspicious input.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::lexer::Lexer;
+/// use rpn2tex::lint::{Linter, Rule};
+/// use rpn2tex::parser::Parser;
+///
+/// let tokens = Lexer::new("x 1 * 0 /").with_identifiers(true).tokenize().unwrap();
+/// let ast = Parser::new(tokens).parse().unwrap();
+/// let lints = Linter::new().lint(&ast);
+/// let rules: Vec<_> = lints.iter().map(|lint| lint.rule).collect();
+/// assert_eq!(rules, [Rule::RedundantOperation, Rule::SuspiciousLiteral]);
+/// assert_eq!(lints[1].diagnostic.message(), "Division by zero");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Linter {
+    /// Deepest nesting of operations allowed
+    max_nesting: u32,
+}
+
+impl Linter {
+    /// Creates a linter allowing operations nested 6 deep.
+    #[must_use]
+    pub fn new() -> Self {
+        Self { max_nesting: 6 }
+    }
+
+    /// Returns this linter allowing operations nested `depth` deep, where
+    /// `1 2 +` is 1 deep.
+    #[must_use]
+    pub fn with_max_nesting(mut self, depth: u32) -> Self {
+        self.max_nesting = depth;
+        self
+    }
+
+    /// Returns the deepest nesting allowed.
+    #[must_use]
+    pub fn max_nesting(&self) -> u32 {
+        self.max_nesting
+    }
+
+    /// Checks `ast` and returns what it finds, in source order.
+    ///
+    /// An expression made only of numbers is not reported as constant as a
+    /// whole, since working it out is usually the point; constant parts of
+    /// an expression with variables are.
+    #[must_use]
+    pub fn lint(&self, ast: &Expr) -> Vec<Lint> {
+        let mut lints = Vec::new();
+        let (depth, _) = visit(ast, &mut lints);
+        if depth > self.max_nesting {
+            let message = format!(
+                "Operations are nested {} deep, more than {}",
+                depth, self.max_nesting
+            );
+            lints.push(Lint::new(
+                Rule::DeepNesting,
+                Diagnostic::new(message, ast.span())
+                    .with_help("split the expression with 'let' bindings"),
+            ));
+        }
+        lints.sort_by_key(|lint| lint.diagnostic.span().start);
+        lints
+    }
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Adds the lints of `node` and its operands to `lints`, and returns its
+/// depth and, if it is made only of numbers and folds to one, its value.
+///
+/// A constant operand is reported by the operation using it, so only the
+/// largest constant part of an expression is.
+fn visit(node: &Expr, lints: &mut Vec<Lint>) -> (u32, Option<String>) {
+    let (span, operator, left, right) = match node {
+        Expr::Number { span, value } => {
+            if let Some((message, help)) = literal(value) {
+                lints.push(Lint::new(
+                    Rule::SuspiciousLiteral,
+                    Diagnostic::new(message, *span).with_help(help),
+                ));
+            }
+            return (0, Some(value.clone()));
+        }
+        Expr::Variable { .. } => return (0, None),
+        // A custom operator is never folded, so its constant operands are
+        // reported here
+        Expr::Apply { operands, .. } => {
+            let mut depth = 0;
+            for operand in operands {
+                let (operand_depth, value) = visit(operand, lints);
+                depth = depth.max(operand_depth);
+                constant_operand(operand, &value, lints);
+            }
+            return (depth + 1, None);
+        }
+        Expr::BinaryOp {
+            span,
+            operator,
+            left,
+            right,
+        } => (span, operator, left, right),
+    };
+
+    let (left_depth, left_value) = visit(left, lints);
+    let (right_depth, right_value) = visit(right, lints);
+    if let Some((message, help)) = redundancy(operator, left, right) {
+        lints.push(Lint::new(
+            Rule::RedundantOperation,
+            Diagnostic::new(message, *span).with_help(help),
+        ));
+    }
+    if operator == "/" && is_value(right, |value| value.is_zero()) {
+        lints.push(Lint::new(
+            Rule::SuspiciousLiteral,
+            Diagnostic::new("Division by zero", right.span()),
+        ));
+    }
+
+    let value = left_value
+        .as_deref()
+        .zip(right_value.as_deref())
+        .and_then(|(left, right)| fold(*span, operator, left, right));
+    if value.is_none() {
+        constant_operand(left, &left_value, lints);
+        constant_operand(right, &right_value, lints);
+    }
+    (left_depth.max(right_depth) + 1, value)
+}
+
+/// Reports `operand` if it is an operation folding to `value`.
+fn constant_operand(operand: &Expr, value: &Option<String>, lints: &mut Vec<Lint>) {
+    if let (Expr::BinaryOp { .. }, Some(value)) = (operand, value) {
+        let message = format!(
+            "'{}' could be folded to {}",
+            NotationGenerator::new(Notation::Postfix).generate(operand),
+            value
+        );
+        lints.push(Lint::new(
+            Rule::ConstantExpression,
+            Diagnostic::new(message, operand.span()).with_help(format!("write {}", value)),
+        ));
+    }
+}
+
+/// Returns the value of `left operator right` as a literal, if it is exact
+/// and its decimal expansion ends.
+#[cfg(feature = "exact")]
+fn fold(span: Span, operator: &str, left: &str, right: &str) -> Option<String> {
+    let number = |value: &str| {
+        Box::new(Expr::Number {
+            span,
+            value: value.to_string(),
+        })
+    };
+    let operation = Expr::BinaryOp {
+        span,
+        operator: operator.to_string(),
+        left: number(left),
+        right: number(right),
+    };
+    match operation.fold_constants() {
+        Expr::Number { value, .. } => Some(value),
+        _ => None,
+    }
+}
+
+/// Returns nothing: without the `exact` feature no operation is folded.
+#[cfg(not(feature = "exact"))]
+fn fold(_span: Span, _operator: &str, _left: &str, _right: &str) -> Option<String> {
+    None
+}
+
+/// Returns the message and help for an operation that changes nothing or
+/// always gives 0.
+fn redundancy(operator: &str, left: &Expr, right: &Expr) -> Option<(String, &'static str)> {
+    let one = |operand| is_value(operand, |value| value.is_one());
+    let zero = |operand| is_value(operand, |value| value.is_zero());
+    let (message, help) = match operator {
+        "*" if zero(left) || zero(right) => (
+            "Multiplying by 0 always gives 0",
+            "write 0, or check the operands",
+        ),
+        "*" if one(left) || one(right) => (
+            "Multiplying by 1 changes nothing",
+            "remove the 1 and the operator",
+        ),
+        "/" if one(right) => (
+            "Dividing by 1 changes nothing",
+            "remove the 1 and the operator",
+        ),
+        "+" if zero(left) || zero(right) => {
+            ("Adding 0 changes nothing", "remove the 0 and the operator")
+        }
+        "-" if zero(right) => (
+            "Subtracting 0 changes nothing",
+            "remove the 0 and the operator",
+        ),
+        _ => return None,
+    };
+    Some((message.to_string(), help))
+}
+
+/// Returns `true` if `operand` is a number literal whose value passes
+/// `test`.
+fn is_value(operand: &Expr, test: impl Fn(Decimal<'_>) -> bool) -> bool {
+    match operand {
+        Expr::Number { value, .. } => Decimal::parse(value).is_some_and(test),
+        _ => false,
+    }
+}
+
+/// Returns the message and help for a literal that looks like a mistake.
+fn literal(value: &str) -> Option<(String, String)> {
+    let digits = value.trim_start_matches('-');
+    let canonical = canonical_number(value);
+    if digits.len() > 1 && digits.starts_with('0') && !digits.starts_with("0.") {
+        return Some((
+            format!("Literal '{}' has leading zeros", value),
+            format!("write {}", canonical),
+        ));
+    }
+    if value.starts_with('-') && Decimal::parse(value).is_some_and(|value| value.is_zero()) {
+        return Some((
+            format!("Literal '{}' is a negative zero", value),
+            format!("write {}", canonical.trim_start_matches('-')),
+        ));
+    }
+    let significant = digits
+        .replace('.', "")
+        .trim_start_matches('0')
+        .trim_end_matches('0')
+        .len();
+    if significant > SIGNIFICANT_DIGITS {
+        return Some((
+            format!(
+                "Literal '{}' has {} significant digits; evaluation keeps {}",
+                value, significant, SIGNIFICANT_DIGITS
+            ),
+            "round it, or evaluate with --exact".to_string(),
+        ));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn lint(source: &str) -> Vec<(Rule, String)> {
+        lint_with(&Linter::new(), source)
+    }
+
+    fn lint_with(linter: &Linter, source: &str) -> Vec<(Rule, String)> {
+        let tokens = Lexer::new(source)
+            .with_identifiers(true)
+            .tokenize()
+            .unwrap();
+        linter
+            .lint(&Parser::new(tokens).parse().unwrap())
+            .into_iter()
+            .map(|lint| (lint.rule, lint.diagnostic.message().to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_clean_input() {
+        assert!(lint("x 2 * 3 +").is_empty());
+        // A wholly constant expression is the exercise, not a lint
+        assert!(lint("5 3 + 2 *").is_empty());
+    }
+
+    #[test]
+    fn test_redundant_operations() {
+        let count = |source| lint(source).len();
+        assert_eq!(count("x 1 *"), 1);
+        assert_eq!(count("1 x *"), 1);
+        assert_eq!(count("x 1 /"), 1);
+        assert_eq!(count("1 x /"), 0);
+        assert_eq!(count("x 0 -"), 1);
+        assert_eq!(count("0 x -"), 0);
+        assert_eq!(
+            lint("0 x +"),
+            [(
+                Rule::RedundantOperation,
+                "Adding 0 changes nothing".to_string()
+            )]
+        );
+        assert_eq!(lint("x 0.0 *")[0].1, "Multiplying by 0 always gives 0");
+    }
+
+    #[cfg(feature = "exact")]
+    #[test]
+    fn test_largest_constant_part_is_reported() {
+        assert_eq!(
+            lint("x 1 2 + 4 * +"),
+            [(
+                Rule::ConstantExpression,
+                "'1 2 + 4 *' could be folded to 12".to_string()
+            )]
+        );
+        // Parts without a terminating decimal value are left alone
+        assert!(lint("x 1 3 / +").is_empty());
+    }
+
+    #[test]
+    fn test_nesting() {
+        let linter = Linter::new().with_max_nesting(2);
+        assert!(lint_with(&linter, "x 1 + 2 *").is_empty());
+        assert_eq!(
+            lint_with(&linter, "x 1 + 2 * 3 -"),
+            [(
+                Rule::DeepNesting,
+                "Operations are nested 3 deep, more than 2".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_suspicious_literals() {
+        assert_eq!(
+            literal("007"),
+            Some((
+                "Literal '007' has leading zeros".to_string(),
+                "write 7".to_string()
+            ))
+        );
+        assert!(literal("0.07").is_none());
+        assert_eq!(literal("-0.0").unwrap().1, "write 0.0");
+        assert!(literal("3.14159265358979323").is_some());
+        assert!(literal("100000000000000000000").is_none());
+        assert_eq!(
+            lint("x 0 /"),
+            [(Rule::SuspiciousLiteral, "Division by zero".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_lints_in_source_order() {
+        let tokens = Lexer::new("x 1 *  y 00 +  -")
+            .with_identifiers(true)
+            .tokenize()
+            .unwrap();
+        let ast = Parser::new(tokens).parse().unwrap();
+        let columns: Vec<_> = Linter::new()
+            .lint(&ast)
+            .iter()
+            .map(|lint| (lint.rule, lint.diagnostic.column()))
+            .collect();
+        assert_eq!(
+            columns,
+            [
+                (Rule::RedundantOperation, 5),
+                (Rule::SuspiciousLiteral, 10),
+                (Rule::RedundantOperation, 13)
+            ]
+        );
+    }
+}
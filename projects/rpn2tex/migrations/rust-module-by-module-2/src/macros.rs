@@ -0,0 +1,424 @@
+//! RPN macros: named token sequences expanded before parsing.
+//!
+//! RPN calculator programs name short operation sequences and use them as
+//! new operations: `macro sq = dup *` makes `5 sq` mean `5 dup *`, which is
+//! `5 5 *`. A [`MacroTable`] holds such definitions, read from a prelude or
+//! from `macro` statements in the input, and the parser expands them when
+//! given one with [`Parser::with_macros`](crate::parser::Parser::with_macros).
+//!
+//! Expansion also understands the stack words `dup` (copy the top value),
+//! `swap` (exchange the top two) and `drop` (discard the top), so macros can
+//! rearrange their operands. They work on whole expressions: after
+//! `5 3 + dup`, the top two values are both `5 3 +`.
+
+use crate::batch::split_expressions;
+use crate::error::Rpn2TexError;
+use crate::error_code::ErrorCode;
+use crate::limits::LimitExceeded;
+use crate::parser::ParserError;
+use crate::plugin::OperatorTable;
+use crate::program::{definition, starts_with_keyword, statement_span, BindingError};
+use crate::tokens::{OwnedToken, Token, TokenType};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Words that rearrange the stack during expansion, which cannot be
+/// redefined.
+const STACK_WORDS: [&str; 3] = ["dup", "swap", "drop"];
+
+/// How deeply macros may expand into other macros, so a macro that uses
+/// itself is reported instead of expanding forever.
+const MAX_EXPANSION_DEPTH: usize = 64;
+
+/// Macro definitions, by name.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::latex::LaTeXGenerator;
+/// use rpn2tex::lexer::Lexer;
+/// use rpn2tex::macros::MacroTable;
+/// use rpn2tex::parser::Parser;
+///
+/// let macros = MacroTable::parse_prelude("macro sq = dup *\nmacro half = 2 /").unwrap();
+/// let tokens = Lexer::new("5 sq half").with_identifiers(true).tokenize().unwrap();
+/// let ast = Parser::new(tokens).with_macros(macros).parse().unwrap();
+/// assert_eq!(LaTeXGenerator::new().generate(&ast), "$5 \\times 5 \\div 2$");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MacroTable {
+    /// Each macro's body, without an EOF token
+    macros: HashMap<String, Vec<OwnedToken>>,
+}
+
+impl MacroTable {
+    /// Creates an empty table.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns this table with `name` defined as `body`, replacing any
+    /// earlier definition.
+    #[must_use]
+    pub fn with_macro(mut self, name: impl Into<String>, body: Vec<OwnedToken>) -> Self {
+        self.macros.insert(name.into(), body);
+        self
+    }
+
+    /// Reads a prelude: an input holding only `macro name = body`
+    /// statements, one per line or separated by `;`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`Rpn2TexError`] if a body fails to lex, or a statement is
+    /// not a well-formed macro definition.
+    pub fn parse_prelude(source: &str) -> Result<Self, Rpn2TexError> {
+        if let Some(segment) = split_expressions(source)
+            .iter()
+            .find(|segment| !starts_with_keyword(segment.text, "macro"))
+        {
+            return Err(
+                BindingError::new("Expected a macro definition", statement_span(segment)).into(),
+            );
+        }
+        Self::new().with_definitions(source)
+    }
+
+    /// Returns this table with the `macro` statements of `source` defined,
+    /// in order. Other statements are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`Rpn2TexError`] if a body fails to lex, or a definition
+    /// is malformed or redefines a stack word.
+    pub fn with_definitions(mut self, source: &str) -> Result<Self, Rpn2TexError> {
+        for segment in split_expressions(source) {
+            let Some((name, span, body)) = definition(&segment, "macro")? else {
+                continue;
+            };
+            if STACK_WORDS.contains(&name) {
+                return Err(BindingError::new(
+                    format!("Cannot redefine stack word '{}'", name),
+                    span,
+                )
+                .into());
+            }
+            let tokens = body.lexer().with_identifiers(true).tokenize()?;
+            let body = tokens
+                .into_iter()
+                .filter(|token| token.token_type != TokenType::Eof)
+                .map(Token::into_owned)
+                .collect();
+            self = self.with_macro(name, body);
+        }
+        Ok(self)
+    }
+
+    /// Returns the body of the macro called `name`, if defined.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&[OwnedToken]> {
+        self.macros.get(name).map(Vec::as_slice)
+    }
+
+    /// Returns the number of macros defined.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.macros.len()
+    }
+
+    /// Returns `true` if no macros are defined.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.macros.is_empty()
+    }
+
+    /// Replaces every macro name in `tokens` by its body and applies the
+    /// stack words. Expanded tokens take the position of the macro name, so
+    /// errors in them point at where the macro is used.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ParserError`] if a stack word lacks values or a macro
+    /// expands recursively. Missing operands of operators are left for the
+    /// parser to report.
+    pub fn expand<'a>(&self, tokens: Vec<Token<'a>>) -> Result<Vec<Token<'a>>, ParserError> {
+        self.expand_with(tokens, &OperatorTable::new(), None)
+    }
+
+    /// Expands like [`expand`](Self::expand), taking the number of values
+    /// each custom operator consumes from `operators`.
+    ///
+    /// Expansion can grow the input exponentially, so with `max_tokens` it
+    /// stops with a [`LimitExceeded::Tokens`] error as soon as the expanded
+    /// tokens, not counting EOF, would outnumber it.
+    pub(crate) fn expand_with<'a>(
+        &self,
+        tokens: Vec<Token<'a>>,
+        operators: &OperatorTable,
+        max_tokens: Option<usize>,
+    ) -> Result<Vec<Token<'a>>, ParserError> {
+        let mut expanded = Vec::with_capacity(tokens.len());
+        let mut starts = Vec::new();
+        for token in tokens {
+            self.expand_token(token, operators, &mut expanded, &mut starts, 0, max_tokens)?;
+        }
+        Ok(expanded)
+    }
+
+    /// Appends the expansion of `token` to `expanded`. `starts` holds where
+    /// each value on the stack begins in `expanded`; the top value runs to
+    /// its end.
+    fn expand_token<'a>(
+        &self,
+        token: Token<'a>,
+        operators: &OperatorTable,
+        expanded: &mut Vec<Token<'a>>,
+        starts: &mut Vec<usize>,
+        depth: usize,
+        max_tokens: Option<usize>,
+    ) -> Result<(), ParserError> {
+        match token.token_type {
+            TokenType::Identifier => match token.value.as_ref() {
+                "dup" => {
+                    let start = *starts.last().ok_or_else(|| {
+                        ParserError::new("'dup' needs a value", token.clone())
+                            .with_code(ErrorCode::Macro)
+                    })?;
+                    check_size(2 * expanded.len() - start, max_tokens, &token)?;
+                    starts.push(expanded.len());
+                    expanded.extend_from_within(start..);
+                }
+                "swap" => {
+                    if starts.len() < 2 {
+                        return Err(ParserError::new("'swap' needs two values", token)
+                            .with_code(ErrorCode::Macro));
+                    }
+                    let (below, top) = (starts[starts.len() - 2], starts[starts.len() - 1]);
+                    expanded[below..].rotate_left(top - below);
+                    *starts.last_mut().unwrap() = expanded.len() - (top - below);
+                }
+                "drop" => {
+                    let start = starts.pop().ok_or_else(|| {
+                        ParserError::new("'drop' needs a value", token).with_code(ErrorCode::Macro)
+                    })?;
+                    expanded.truncate(start);
+                }
+                name => match self.get(name) {
+                    Some(_) if depth == MAX_EXPANSION_DEPTH => {
+                        return Err(ParserError::new(
+                            format!("Macro '{}' expands recursively", name),
+                            token,
+                        )
+                        .with_code(ErrorCode::Macro));
+                    }
+                    Some(body) => {
+                        for body_token in body {
+                            let body_token = Token::new(
+                                body_token.token_type,
+                                body_token.value.clone(),
+                                token.span,
+                            );
+                            self.expand_token(
+                                body_token,
+                                operators,
+                                expanded,
+                                starts,
+                                depth + 1,
+                                max_tokens,
+                            )?;
+                        }
+                    }
+                    None => {
+                        starts.push(expanded.len());
+                        push(expanded, token, max_tokens)?;
+                    }
+                },
+            },
+            TokenType::Number => {
+                starts.push(expanded.len());
+                push(expanded, token, max_tokens)?;
+            }
+            TokenType::Plus | TokenType::Minus | TokenType::Mult | TokenType::Div => {
+                // The result starts where the left operand did
+                if starts.len() >= 2 {
+                    starts.pop();
+                } else {
+                    starts.clear();
+                }
+                push(expanded, token, max_tokens)?;
+            }
+            TokenType::Operator => {
+                // The result starts where the first operand did; an unknown
+                // operator is left for the parser to report
+                let arity = operators
+                    .get(&token.value)
+                    .map_or(1, |plugin| plugin.arity());
+                if starts.len() >= arity {
+                    starts.truncate(starts.len() + 1 - arity);
+                } else {
+                    starts.clear();
+                }
+                push(expanded, token, max_tokens)?;
+            }
+            TokenType::LeftParen
+            | TokenType::RightParen
+            | TokenType::Whitespace
+            | TokenType::Comment => push(expanded, token, max_tokens)?,
+            TokenType::Eof => expanded.push(token),
+        }
+        Ok(())
+    }
+}
+
+/// Appends `token` to `expanded`, unless that takes it past `max_tokens`.
+fn push<'a>(
+    expanded: &mut Vec<Token<'a>>,
+    token: Token<'a>,
+    max_tokens: Option<usize>,
+) -> Result<(), ParserError> {
+    check_size(expanded.len() + 1, max_tokens, &token)?;
+    expanded.push(token);
+    Ok(())
+}
+
+/// Fails at `token` if `len` expanded tokens are more than `max_tokens`.
+fn check_size(len: usize, max_tokens: Option<usize>, token: &Token<'_>) -> Result<(), ParserError> {
+    match max_tokens.filter(|&max| len > max) {
+        Some(max) => Err(ParserError::from_limit(
+            LimitExceeded::Tokens(max),
+            token.clone(),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Returns `true` if `source` holds a `macro` statement.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::macros::has_macros;
+///
+/// assert!(has_macros("macro sq = dup *; 5 sq"));
+/// assert!(!has_macros("5 5 *"));
+/// ```
+#[must_use]
+pub fn has_macros(source: &str) -> bool {
+    split_expressions(source)
+        .iter()
+        .any(|segment| starts_with_keyword(segment.text, "macro"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::limits::Limits;
+    use crate::notation::{Notation, NotationGenerator};
+    use crate::parser::Parser;
+
+    /// Expands and parses `source`, returning it in postfix notation.
+    fn expand(macros: &MacroTable, source: &str) -> Result<String, ParserError> {
+        let tokens = Lexer::new(source)
+            .with_identifiers(true)
+            .tokenize()
+            .unwrap();
+        let ast = Parser::new(tokens).with_macros(macros.clone()).parse()?;
+        Ok(NotationGenerator::new(Notation::Postfix).generate(&ast))
+    }
+
+    #[test]
+    fn test_stack_words() {
+        let macros = MacroTable::new();
+        assert_eq!(expand(&macros, "5 3 + dup *").unwrap(), "5 3 + 5 3 + *");
+        assert_eq!(expand(&macros, "1 2 3 + swap -").unwrap(), "2 3 + 1 -");
+        assert_eq!(expand(&macros, "1 2 drop 4 *").unwrap(), "1 4 *");
+    }
+
+    #[test]
+    fn test_stack_word_errors() {
+        let macros = MacroTable::new();
+        let error = expand(&macros, "dup").unwrap_err();
+        assert_eq!(error.message(), "'dup' needs a value");
+        let error = expand(&macros, "1 swap").unwrap_err();
+        assert_eq!(error.message(), "'swap' needs two values");
+        assert_eq!(error.token().span.column, 3);
+    }
+
+    #[test]
+    fn test_nested_macros() {
+        let macros = MacroTable::parse_prelude("macro sq = dup *\nmacro quad = sq sq").unwrap();
+        assert_eq!(macros.len(), 2);
+        assert_eq!(expand(&macros, "x quad").unwrap(), "x x * x x * *");
+    }
+
+    #[test]
+    fn test_expanded_positions() {
+        let macros = MacroTable::parse_prelude("macro inc = 1 +").unwrap();
+        let tokens = Lexer::new("5 inc")
+            .with_identifiers(true)
+            .tokenize()
+            .unwrap();
+        let expanded = macros.expand(tokens).unwrap();
+        let columns: Vec<u32> = expanded.iter().map(|token| token.span.column).collect();
+        assert_eq!(columns, [1, 3, 3, 6]);
+    }
+
+    #[test]
+    fn test_recursive_macro() {
+        let macros = MacroTable::parse_prelude("macro loop = 1 loop +").unwrap();
+        let error = expand(&macros, "loop").unwrap_err();
+        assert_eq!(error.message(), "Macro 'loop' expands recursively");
+    }
+
+    #[test]
+    fn test_expansion_limit() {
+        // Each `d` doubles the input, so 30 of them would need billions of
+        // tokens
+        let macros = MacroTable::parse_prelude("macro d = dup +").unwrap();
+        let source = format!("1{}", " d".repeat(30));
+        let tokens = Lexer::new(&source)
+            .with_identifiers(true)
+            .tokenize()
+            .unwrap();
+        let error = Parser::new(tokens)
+            .with_limits(Limits::new().with_max_tokens(1000).with_max_depth(50))
+            .with_macros(macros)
+            .parse()
+            .unwrap_err();
+        assert_eq!(error.limit_exceeded(), Some(LimitExceeded::Tokens(1000)));
+        assert_eq!(error.message(), "Input exceeds the limit of 1000 tokens");
+    }
+
+    #[test]
+    fn test_redefinition() {
+        let macros = MacroTable::parse_prelude("macro k = 1; macro k = 2").unwrap();
+        assert_eq!(expand(&macros, "k").unwrap(), "2");
+    }
+
+    #[test]
+    fn test_prelude_errors() {
+        let error = MacroTable::parse_prelude("macro sq = dup *\n5 sq").unwrap_err();
+        assert_eq!(error.message(), "Expected a macro definition");
+        assert_eq!((error.line(), error.column()), (2, 1));
+        let error = MacroTable::parse_prelude("macro dup = 1").unwrap_err();
+        assert_eq!(error.message(), "Cannot redefine stack word 'dup'");
+        let error = MacroTable::parse_prelude("macro sq dup *").unwrap_err();
+        assert_eq!(error.message(), "Expected '=' after 'macro sq'");
+        let error = MacroTable::parse_prelude("macro bad = 1 @").unwrap_err();
+        assert!(matches!(error, Rpn2TexError::Lexer(_)));
+    }
+
+    #[test]
+    fn test_with_definitions_skips_other_statements() {
+        let macros = MacroTable::new()
+            .with_definitions("let a = 1; macro sq = dup *; a sq")
+            .unwrap();
+        assert_eq!(macros.len(), 1);
+        assert!(macros.get("sq").is_some());
+        assert!(macros.get("a").is_none());
+    }
+}
@@ -2,7 +2,9 @@
 //!
 //! This module provides a CLI that orchestrates the entire RPN to LaTeX conversion
 //! pipeline. It handles argument parsing, file I/O, error reporting, and pipeline
-//! orchestration.
+//! orchestration. Each of those lives in a module under `cli`, from reading
+//! the arguments in `cli::args` to converting one input in `cli::convert`;
+//! this file only runs them in order.
 //!
 //! # Pipeline
 //!
@@ -11,23 +13,55 @@
 //! 2. Read input from file or stdin
 //! 3. Tokenize input (Lexer)
 //! 4. Parse tokens into AST (Parser)
-//! 5. Generate LaTeX from AST (LaTeX Generator)
+//! 5. Generate LaTeX from AST (LaTeX Generator), or another artifact selected
+//!    with `--emit`
 //! 6. Write output to file or stdout
 //!
+//! With `-d <dir>`, every file named by the inputs (paths, directories and
+//! globs such as `src/**/*.rpn`) is converted into the directory instead,
+//! mirroring the input tree, and a summary lists each file's outcome.
+//!
+//! The `worksheet` subcommand, given before the input, generates a document
+//! of numbered problems from the input's expressions instead. The `fmt`
+//! subcommand rewrites RPN sources in canonical form, and `lint` reports
+//! input that is redundant or probably a mistake. `convert`, the default,
+//! may be named explicitly, as in `convert --from infix --to rpn`. `repl`,
+//! built with the `readline` feature, converts expressions as they are typed
+//! at an editing prompt with history.
+//!
 //! # Exit Codes
 //!
 //! - 0: Success
 //! - 1: Error (file I/O, lexing, or parsing error)
 
-use rpn2tex::error::ErrorFormatter;
-use rpn2tex::latex::LaTeXGenerator;
-use rpn2tex::lexer::{Lexer, LexerError};
-use rpn2tex::parser::{Parser, ParserError};
+mod cli;
+
+use crate::cli::args::print_usage;
+use crate::cli::batch::convert_files;
+use crate::cli::commands::{format_files, lint_files};
+use crate::cli::config::{config_settings, env_settings, merge_settings, parse_args_with_settings};
+use crate::cli::convert::{
+    answer_key, blank_out, convert_rows, converts_expression, emit_expression, evaluate_ast,
+    generate_worksheet, parse_problems, parse_rpn,
+};
+#[cfg(feature = "clipboard")]
+use crate::cli::files::copy_to_clipboard;
+use crate::cli::files::{
+    decode_input, fill_template, load_prelude, load_template, read_input, write_output,
+};
+use crate::cli::logger::STDERR_LOGGER;
+use crate::cli::options::Command;
+#[cfg(feature = "pdf")]
+use crate::cli::pdf::compile_pdf;
+#[cfg(feature = "pdf")]
+use crate::cli::preview::show_preview;
+use crate::cli::records::convert_records;
+#[cfg(feature = "readline")]
+use crate::cli::repl::run_repl;
+use log::{debug, info, warn};
 use std::env;
-use std::fs;
-use std::io::{self, Read, Write};
-use std::path::PathBuf;
 use std::process;
+use std::time::Instant;
 
 /// Main entry point for the rpn2tex CLI.
 ///
@@ -44,460 +78,246 @@ fn run() -> i32 {
     let args: Vec<String> = env::args().collect();
 
     if args.len() < 2 {
-        eprintln!("Usage: {} <input-file> [-o <output-file>]", args[0]);
-        eprintln!();
-        eprintln!("Arguments:");
-        eprintln!("  <input-file>    Input RPN file (use '-' for stdin)");
-        eprintln!("  -o <output>     Output LaTeX file (default: stdout)");
+        print_usage(&args[0]);
         return 1;
     }
 
-    let input_path = &args[1];
-    let mut output_path: Option<PathBuf> = None;
-
-    // Parse optional output file argument
-    let mut i = 2;
-    while i < args.len() {
-        match args[i].as_str() {
-            "-o" | "--output" => {
-                if i + 1 >= args.len() {
-                    eprintln!("Error: -o flag requires an argument");
-                    return 1;
-                }
-                output_path = Some(PathBuf::from(&args[i + 1]));
-                i += 2;
-            }
-            _ => {
-                eprintln!("Error: Unknown argument '{}'", args[i]);
-                return 1;
-            }
-        }
-    }
-
-    // Read input
-    let source = match read_input(input_path) {
-        Ok(content) => content,
+    let args = args[1..].to_vec();
+    let no_config = args.iter().any(|arg| arg == "--no-config");
+    let vars = env::vars_os()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)));
+    let settings = if no_config {
+        Ok(Vec::new())
+    } else {
+        config_settings()
+    }
+    .and_then(|mut settings| {
+        merge_settings(&mut settings, env_settings(vars)?);
+        Ok(settings)
+    });
+    let settings = match settings {
+        Ok(settings) => settings,
         Err(e) => {
-            eprintln!("Error reading input: {}", e);
+            eprintln!("{}", e);
             return 1;
         }
     };
 
-    // Run the conversion pipeline
-    let latex_output = match convert_rpn_to_latex(&source) {
-        Ok(output) => output,
+    let mut options = match parse_args_with_settings(&args, &settings) {
+        Ok(options) => options,
         Err(e) => {
             eprintln!("{}", e);
             return 1;
         }
     };
-
-    // Write output
-    if let Err(e) = write_output(&latex_output, output_path.as_ref()) {
-        eprintln!("Error writing output: {}", e);
-        return 1;
-    }
-
-    // Print success message to stderr
-    if let Some(path) = output_path {
-        eprintln!("Generated: {}", path.display());
-    }
-
-    0
-}
-
-/// Reads input from a file or stdin.
-///
-/// # Arguments
-///
-/// * `path` - Path to input file, or "-" for stdin
-///
-/// # Errors
-///
-/// Returns an error if the file cannot be read or if there's an I/O error.
-fn read_input(path: &str) -> io::Result<String> {
-    if path == "-" {
-        // Read from stdin
-        let mut buffer = String::new();
-        io::stdin().read_to_string(&mut buffer)?;
-        Ok(buffer)
-    } else {
-        // Read from file with better error messages
-        fs::read_to_string(path).map_err(|e| match e.kind() {
-            io::ErrorKind::NotFound => io::Error::new(
-                io::ErrorKind::NotFound,
-                format!("Input file not found: {}", path),
-            ),
-            io::ErrorKind::PermissionDenied => io::Error::new(
-                io::ErrorKind::PermissionDenied,
-                format!("Permission denied: {}", path),
-            ),
-            _ => {
-                if PathBuf::from(path).is_dir() {
-                    io::Error::other(format!("Expected a file, got directory: {}", path))
-                } else {
-                    e
-                }
+    options.color = options.color.resolve();
+    if log::set_logger(&STDERR_LOGGER).is_ok() {
+        log::set_max_level(options.log_level());
+    }
+    if let Some(path) = &options.prelude_path {
+        options.prelude = match load_prelude(path, &options) {
+            Ok(prelude) => prelude,
+            Err(e) => {
+                eprintln!("{}", e);
+                return 1;
             }
-        })
+        };
     }
-}
-
-/// Writes output to a file or stdout.
-///
-/// # Arguments
-///
-/// * `content` - The LaTeX content to write
-/// * `path` - Optional output file path. If None, writes to stdout
-///
-/// # Errors
-///
-/// Returns an error if the file cannot be written or if there's an I/O error.
-fn write_output(content: &str, path: Option<&PathBuf>) -> io::Result<()> {
-    if let Some(output_path) = path {
-        // Write to file with better error messages
-        fs::write(output_path, content).map_err(|e| match e.kind() {
-            io::ErrorKind::PermissionDenied => io::Error::new(
-                io::ErrorKind::PermissionDenied,
-                format!("Permission denied: {}", output_path.display()),
-            ),
-            _ => {
-                if output_path.is_dir() {
-                    io::Error::other(format!(
-                        "Cannot write to directory: {}",
-                        output_path.display()
-                    ))
-                } else {
-                    e
-                }
+    if let Some(path) = &options.template_path {
+        options.template = match load_template(path, &options) {
+            Ok(template) => Some(template),
+            Err(e) => {
+                eprintln!("{}", e);
+                return 1;
             }
-        })
-    } else {
-        // Write to stdout
-        print!("{}", content);
-        io::stdout().flush()
-    }
-}
-
-/// Converts RPN input to LaTeX output through the complete pipeline.
-///
-/// This function orchestrates the lexer, parser, and LaTeX generator to
-/// perform the full conversion. It formats errors with source context when
-/// they occur.
-///
-/// # Arguments
-///
-/// * `source` - The RPN source text
-///
-/// # Returns
-///
-/// The LaTeX output string on success, or a formatted error message on failure.
-///
-/// # Errors
-///
-/// Returns a formatted error string if:
-/// - Lexing fails (invalid characters, malformed tokens)
-/// - Parsing fails (invalid RPN structure, missing operands)
-fn convert_rpn_to_latex(source: &str) -> Result<String, String> {
-    let formatter = ErrorFormatter::new(source);
-
-    // Tokenize
-    let lexer = Lexer::new(source);
-    let tokens = lexer
-        .tokenize()
-        .map_err(|e| format_lexer_error(&formatter, &e))?;
-
-    // Parse
-    let mut parser = Parser::new(tokens);
-    let ast = parser
-        .parse()
-        .map_err(|e| format_parser_error(&formatter, &e))?;
-
-    // Generate LaTeX
-    let generator = LaTeXGenerator::new();
-    Ok(generator.generate(&ast))
-}
-
-/// Formats a lexer error with source context.
-///
-/// # Arguments
-///
-/// * `formatter` - The error formatter with source context
-/// * `error` - The lexer error to format
-///
-/// # Returns
-///
-/// A formatted error message with source context, ready to display to the user.
-fn format_lexer_error(formatter: &ErrorFormatter, error: &LexerError) -> String {
-    formatter.format_error(error.message(), error.line(), error.column(), 1)
-}
-
-/// Formats a parser error with source context.
-///
-/// # Arguments
-///
-/// * `formatter` - The error formatter with source context
-/// * `error` - The parser error to format
-///
-/// # Returns
-///
-/// A formatted error message with source context, ready to display to the user.
-fn format_parser_error(formatter: &ErrorFormatter, error: &ParserError) -> String {
-    formatter.format_error(error.message(), error.token().line, error.token().column, 1)
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_convert_simple_addition() {
-        let result = convert_rpn_to_latex("5 3 +").expect("should succeed");
-        assert_eq!(result, "$5 + 3$");
+        };
     }
-
-    #[test]
-    fn test_convert_simple_multiplication() {
-        let result = convert_rpn_to_latex("4 7 *").expect("should succeed");
-        assert_eq!(result, r"$4 \times 7$");
+    if options.command == Command::Fmt {
+        return format_files(&options);
     }
-
-    #[test]
-    fn test_convert_with_precedence() {
-        let result = convert_rpn_to_latex("5 3 + 2 *").expect("should succeed");
-        assert_eq!(result, r"$( 5 + 3 ) \times 2$");
+    if options.command == Command::Lint {
+        return lint_files(&options);
     }
-
-    #[test]
-    fn test_convert_lexer_error() {
-        let result = convert_rpn_to_latex("2 3 ^");
-        assert!(result.is_err());
-        let error = result.unwrap_err();
-        assert!(error.contains("Line 1, column 5"));
-        assert!(error.contains("Unexpected character"));
+    #[cfg(feature = "readline")]
+    if options.command == Command::Repl {
+        return run_repl(&options);
     }
-
-    #[test]
-    fn test_convert_parser_error_too_few_operands() {
-        let result = convert_rpn_to_latex("5 +");
-        assert!(result.is_err());
-        let error = result.unwrap_err();
-        assert!(error.contains("Too few operands"));
-    }
-
-    #[test]
-    fn test_convert_parser_error_empty() {
-        let result = convert_rpn_to_latex("");
-        assert!(result.is_err());
-        let error = result.unwrap_err();
-        assert!(error.contains("Empty expression"));
-    }
-
-    #[test]
-    fn test_convert_parser_error_missing_operator() {
-        let result = convert_rpn_to_latex("5 3");
-        assert!(result.is_err());
-        let error = result.unwrap_err();
-        assert!(error.contains("Too many values"));
+    if options.output_dir.is_some() || options.in_place {
+        return convert_files(&options);
     }
+    let input_path = &options.input_path;
+    let output_path = options.output_path.clone();
 
-    #[test]
-    fn test_convert_floating_point() {
-        let result = convert_rpn_to_latex("3.14 2 *").expect("should succeed");
-        assert_eq!(result, r"$3.14 \times 2$");
+    for warning in options.latex_generator().katex_warnings() {
+        warn!("{}", warning);
     }
-
-    #[test]
-    fn test_convert_negative_number() {
-        let result = convert_rpn_to_latex("-5 3 +").expect("should succeed");
-        assert_eq!(result, "$-5 + 3$");
-    }
-
-    #[test]
-    fn test_convert_complex_expression() {
-        let result = convert_rpn_to_latex("1 2 + 3 4 + *").expect("should succeed");
-        assert_eq!(result, r"$( 1 + 2 ) \times ( 3 + 4 )$");
-    }
-
-    #[test]
-    fn test_format_lexer_error() {
-        let source = "2 3 ^";
-        let formatter = ErrorFormatter::new(source);
-        let error = LexerError::new("Unexpected character '^'", 1, 5);
-
-        let formatted = format_lexer_error(&formatter, &error);
-        assert!(formatted.contains("Line 1, column 5"));
-        assert!(formatted.contains("Unexpected character"));
-        assert!(formatted.contains("2 3 ^"));
-        assert!(formatted.contains("^")); // Should have caret pointer
-    }
-
-    #[test]
-    fn test_format_parser_error() {
-        use rpn2tex::tokens::{Token, TokenType};
-
-        let source = "5 +";
-        let formatter = ErrorFormatter::new(source);
-        let token = Token::new(TokenType::Plus, "+".to_string(), 1, 3);
-        let error = ParserError::new("Too few operands", token);
-
-        let formatted = format_parser_error(&formatter, &error);
-        assert!(formatted.contains("Line 1, column 3"));
-        assert!(formatted.contains("Too few operands"));
+    if options.null || options.jupyter {
+        return convert_records(&options);
     }
 
-    #[test]
-    fn test_read_input_stdin() {
-        // Can't easily test stdin reading without mocking
-        // This is a placeholder for manual testing
-    }
-
-    #[test]
-    fn test_write_output_stdout() {
-        // Can't easily test stdout writing without capturing
-        // This is a placeholder for manual testing
-    }
-
-    // Test all I/O contract cases through the convert function
-    #[test]
-    fn test_io_contract_case_01() {
-        assert_eq!(convert_rpn_to_latex("5 3 +").unwrap(), "$5 + 3$");
-    }
-
-    #[test]
-    fn test_io_contract_case_02() {
-        assert_eq!(convert_rpn_to_latex("5 3 -").unwrap(), "$5 - 3$");
-    }
-
-    #[test]
-    fn test_io_contract_case_03() {
-        assert_eq!(convert_rpn_to_latex("4 7 *").unwrap(), r"$4 \times 7$");
-    }
-
-    #[test]
-    fn test_io_contract_case_04() {
-        assert_eq!(convert_rpn_to_latex("10 2 /").unwrap(), r"$10 \div 2$");
-    }
-
-    #[test]
-    fn test_io_contract_case_05() {
-        assert_eq!(
-            convert_rpn_to_latex("5 3 + 2 *").unwrap(),
-            r"$( 5 + 3 ) \times 2$"
-        );
-    }
-
-    #[test]
-    fn test_io_contract_case_06() {
-        assert_eq!(
-            convert_rpn_to_latex("5 3 * 2 +").unwrap(),
-            r"$5 \times 3 + 2$"
-        );
-    }
-
-    #[test]
-    fn test_io_contract_case_07() {
-        assert_eq!(
-            convert_rpn_to_latex("10 2 / 5 *").unwrap(),
-            r"$10 \div 2 \times 5$"
-        );
-    }
-
-    #[test]
-    fn test_io_contract_case_08() {
-        assert_eq!(convert_rpn_to_latex("5 3 - 2 -").unwrap(), "$5 - 3 - 2$");
-    }
-
-    #[test]
-    fn test_io_contract_case_09() {
-        assert_eq!(
-            convert_rpn_to_latex("100 10 / 5 / 2 /").unwrap(),
-            r"$100 \div 10 \div 5 \div 2$"
-        );
-    }
+    // Read input
+    let start = Instant::now();
+    let bytes = match read_input(input_path) {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!("Error reading input: {}", e);
+            return 1;
+        }
+    };
+    let source = match decode_input(&bytes, &options) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
 
-    #[test]
-    fn test_io_contract_case_10() {
-        assert_eq!(
-            convert_rpn_to_latex("1 2 + 3 + 4 +").unwrap(),
-            "$1 + 2 + 3 + 4$"
-        );
-    }
+    debug!("Read {} bytes in {:.2?}", bytes.len(), start.elapsed());
 
-    #[test]
-    fn test_io_contract_case_11() {
-        assert_eq!(
-            convert_rpn_to_latex("2 3 4 * +").unwrap(),
-            r"$2 + 3 \times 4$"
-        );
-    }
+    // Run the conversion pipeline. What --eval, --blank and the answer key
+    // read is parsed once, and the output built from it where it can be
+    let start = Instant::now();
+    let ast = match (options.eval || options.blank.is_some())
+        .then(|| parse_rpn(&source, &options))
+        .transpose()
+    {
+        Ok(ast) => ast,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+    let problems = match options
+        .answer_key_path
+        .is_some()
+        .then(|| parse_problems(&source, &options))
+        .transpose()
+    {
+        Ok(problems) => problems,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+    let converted = match (&ast, &problems) {
+        (Some(ast), _) if converts_expression(&source, &options) => {
+            emit_expression(&source, ast, &options).map(|output| (output, Vec::new()))
+        }
+        (_, Some(problems)) => {
+            generate_worksheet(&source, problems, &options).map(|output| (output, Vec::new()))
+        }
+        _ => convert_rows(&source, &options),
+    };
+    let (latex_output, failures) = match converted {
+        Ok((output, failures)) => (fill_template(output, &options), failures),
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+    info!("Converted {} in {:.2?}", input_path, start.elapsed());
+    // Failing CSV rows are written with empty cells, and fail the run after
+    // the whole output
+    for failure in &failures {
+        eprintln!("{}", failure);
+    }
+
+    // Evaluate before writing, so a failed check leaves no output behind
+    let value = match &ast {
+        Some(ast) if options.eval => match evaluate_ast(&source, ast, &options) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                eprintln!("{}", e);
+                return 1;
+            }
+        },
+        _ => None,
+    };
+    // Reported apart from the output, which only shows the blank
+    let hidden = match (&ast, options.blank) {
+        (Some(ast), Some(token)) => match blank_out(ast, token, &options) {
+            Ok(cloze) => Some(cloze.hidden),
+            Err(e) => {
+                eprintln!("{}", e);
+                return 1;
+            }
+        },
+        _ => None,
+    };
 
-    #[test]
-    fn test_io_contract_case_12() {
-        assert_eq!(
-            convert_rpn_to_latex("2 3 + 4 *").unwrap(),
-            r"$( 2 + 3 ) \times 4$"
-        );
+    #[cfg(feature = "pdf")]
+    if let Some(path) = &options.pdf_path {
+        let start = Instant::now();
+        if let Err(e) = compile_pdf(&latex_output, path) {
+            eprintln!("{}", e);
+            return 1;
+        }
+        info!("Compiled {} in {:.2?}", path.display(), start.elapsed());
+        if !options.quiet {
+            eprintln!("Generated: {}", path.display());
+        }
     }
 
-    #[test]
-    fn test_io_contract_case_13() {
-        assert_eq!(
-            convert_rpn_to_latex("2 3 4 + *").unwrap(),
-            r"$2 \times ( 3 + 4 )$"
-        );
+    #[cfg(feature = "pdf")]
+    if options.preview {
+        if let Err(e) = show_preview(&latex_output, &options) {
+            eprintln!("{}", e);
+            return 1;
+        }
     }
 
-    #[test]
-    fn test_io_contract_case_14() {
-        assert_eq!(
-            convert_rpn_to_latex("2 3 * 4 +").unwrap(),
-            r"$2 \times 3 + 4$"
-        );
-    }
+    // Generated first too, so a failed evaluation leaves no output behind
+    let key = match (&options.answer_key_path, &problems) {
+        (Some(path), Some(problems)) => match answer_key(&source, problems, &options) {
+            Ok(key) => Some((path, key)),
+            Err(e) => {
+                eprintln!("{}", e);
+                return 1;
+            }
+        },
+        _ => None,
+    };
 
-    #[test]
-    fn test_io_contract_case_15() {
-        assert_eq!(
-            convert_rpn_to_latex("3.14 2 *").unwrap(),
-            r"$3.14 \times 2$"
-        );
+    // Write output; with --pdf the LaTeX is only written to a named file
+    if options.pdf_path.is_none() || output_path.is_some() {
+        if let Err(e) = write_output(&latex_output, output_path.as_ref()) {
+            eprintln!("Error writing output: {}", e);
+            return 1;
+        }
     }
 
-    #[test]
-    fn test_io_contract_case_16() {
-        assert_eq!(convert_rpn_to_latex("1.5 0.5 +").unwrap(), "$1.5 + 0.5$");
+    if let Some(value) = value {
+        eprintln!("Value: {}", value);
     }
-
-    #[test]
-    fn test_io_contract_case_17() {
-        assert_eq!(
-            convert_rpn_to_latex("1 2 + 3 4 + *").unwrap(),
-            r"$( 1 + 2 ) \times ( 3 + 4 )$"
-        );
+    if let Some(hidden) = hidden {
+        eprintln!("Blank: {}", hidden);
     }
 
-    #[test]
-    fn test_io_contract_case_18() {
-        assert_eq!(
-            convert_rpn_to_latex("10 2 / 3 + 4 *").unwrap(),
-            r"$( 10 \div 2 + 3 ) \times 4$"
-        );
+    if let Some((path, key)) = key {
+        if let Err(e) = write_output(&key, Some(path)) {
+            eprintln!("Error writing output: {}", e);
+            return 1;
+        }
+        if !options.quiet {
+            eprintln!("Generated: {}", path.display());
+        }
     }
 
-    // Error test cases
-    #[test]
-    fn test_io_contract_error_01() {
-        assert!(convert_rpn_to_latex("2 3 ^").is_err());
+    #[cfg(feature = "clipboard")]
+    if options.copy {
+        if let Err(e) = copy_to_clipboard(&latex_output) {
+            eprintln!("{}", e);
+            return 1;
+        }
+        if !options.quiet {
+            eprintln!("Copied to clipboard");
+        }
     }
 
-    #[test]
-    fn test_io_contract_error_02() {
-        assert!(convert_rpn_to_latex("2 3 ^ 4 *").is_err());
+    // Print success message to stderr
+    if let Some(path) = output_path.filter(|_| !options.quiet) {
+        eprintln!("Generated: {}", path.display());
     }
 
-    #[test]
-    fn test_io_contract_error_03() {
-        assert!(convert_rpn_to_latex("2 3 4 ^ ^").is_err());
-    }
+    i32::from(!failures.is_empty())
 }
@@ -0,0 +1,150 @@
+//! Markdown math snippets generated from Abstract Syntax Trees.
+//!
+//! Markdown renderers disagree on how math is delimited, so the snippet is
+//! shaped for a specific [`MarkdownFlavor`] and can be pasted into notes as-is.
+//! The math body itself is rendered by the LaTeX backend.
+
+use crate::ast::Expr;
+use crate::codegen::CodeGenerator;
+use crate::latex::LaTeXGenerator;
+
+/// Markdown dialect whose math syntax the output targets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum MarkdownFlavor {
+    /// GitHub-flavored Markdown: a ```` ```math ```` fenced block (the default)
+    #[default]
+    GitHub,
+    /// Pandoc Markdown: inline `$...$`
+    Pandoc,
+    /// Obsidian notes: inline `$...$`
+    Obsidian,
+}
+
+impl MarkdownFlavor {
+    /// Returns whether this flavor wraps math in a fenced code block rather
+    /// than dollar delimiters.
+    #[must_use]
+    pub const fn uses_fence(self) -> bool {
+        matches!(self, Self::GitHub)
+    }
+}
+
+/// Converts AST expressions to Markdown math snippets.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::ast::Expr;
+/// use rpn2tex::markdown::{MarkdownFlavor, MarkdownGenerator};
+/// use rpn2tex::span::Span;
+///
+/// let expr = Expr::BinaryOp {
+///     span: Span::new(4, 5, 1, 5),
+///     operator: "/".to_string(),
+///     left: Box::new(Expr::Number {
+///         span: Span::new(0, 1, 1, 1),
+///         value: "1".to_string(),
+///     }),
+///     right: Box::new(Expr::Number {
+///         span: Span::new(2, 3, 1, 3),
+///         value: "2".to_string(),
+///     }),
+/// };
+///
+/// let github = MarkdownGenerator::new(MarkdownFlavor::GitHub);
+/// assert_eq!(github.generate(&expr), "```math\n1 \\div 2\n```");
+///
+/// let pandoc = MarkdownGenerator::new(MarkdownFlavor::Pandoc);
+/// assert_eq!(pandoc.generate(&expr), r"$1 \div 2$");
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MarkdownGenerator {
+    /// Renders the math body shared with LaTeX
+    math: LaTeXGenerator,
+    /// Dialect the delimiters are chosen for
+    flavor: MarkdownFlavor,
+}
+
+impl MarkdownGenerator {
+    /// Creates a generator targeting the given Markdown flavor.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::markdown::{MarkdownFlavor, MarkdownGenerator};
+    ///
+    /// let generator = MarkdownGenerator::new(MarkdownFlavor::Obsidian);
+    /// assert_eq!(generator.flavor(), MarkdownFlavor::Obsidian);
+    /// ```
+    #[must_use]
+    pub const fn new(flavor: MarkdownFlavor) -> Self {
+        Self {
+            math: LaTeXGenerator::new(),
+            flavor,
+        }
+    }
+
+    /// Returns the Markdown flavor this generator targets.
+    #[must_use]
+    pub const fn flavor(&self) -> MarkdownFlavor {
+        self.flavor
+    }
+
+    /// Generates a Markdown math snippet from an AST expression.
+    ///
+    /// # Arguments
+    ///
+    /// * `ast` - The root expression node to convert
+    #[must_use]
+    pub fn generate(&self, ast: &Expr) -> String {
+        let inner = self.math.render_math(ast);
+        if self.flavor.uses_fence() {
+            format!("```math\n{}\n```", inner)
+        } else {
+            format!("${}$", inner)
+        }
+    }
+}
+
+impl CodeGenerator for MarkdownGenerator {
+    fn generate(&self, expr: &Expr) -> String {
+        MarkdownGenerator::generate(self, expr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn generate(input: &str, flavor: MarkdownFlavor) -> String {
+        let tokens = Lexer::new(input).tokenize().expect("tokenize failed");
+        let ast = Parser::new(tokens).parse().expect("parse failed");
+        MarkdownGenerator::new(flavor).generate(&ast)
+    }
+
+    #[test]
+    fn test_default_flavor_is_github() {
+        assert_eq!(
+            MarkdownGenerator::default().flavor(),
+            MarkdownFlavor::GitHub
+        );
+    }
+
+    #[test]
+    fn test_github_fence() {
+        assert_eq!(
+            generate("5 3 + 2 *", MarkdownFlavor::GitHub),
+            "```math\n( 5 + 3 ) \\times 2\n```"
+        );
+    }
+
+    #[test]
+    fn test_dollar_flavors() {
+        for flavor in [MarkdownFlavor::Pandoc, MarkdownFlavor::Obsidian] {
+            assert!(!flavor.uses_fence());
+            assert_eq!(generate("5 3 -", flavor), "$5 - 3$");
+        }
+    }
+}
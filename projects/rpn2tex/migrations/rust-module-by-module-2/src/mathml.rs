@@ -0,0 +1,227 @@
+//! Content MathML generation from Abstract Syntax Trees.
+//!
+//! Content MathML encodes the meaning of an expression (`<apply><plus/>...`)
+//! rather than its visual layout, so computer algebra systems can import the
+//! parsed expression without guessing at operator precedence.
+
+use crate::ast::Expr;
+use crate::codegen::CodeGenerator;
+
+/// MathML namespace declared on the root `<math>` element.
+const MATHML_NAMESPACE: &str = "http://www.w3.org/1998/Math/MathML";
+
+/// Converts AST expressions to Content MathML.
+///
+/// Each binary operation becomes an `<apply>` element whose first child is
+/// the operator (`<plus/>`, `<minus/>`, `<times/>`, `<divide/>`). Numbers are
+/// emitted as `<cn>` elements typed `integer` or `real` depending on whether
+/// the literal contains a decimal point, preserving the source spelling.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::ast::Expr;
+/// use rpn2tex::mathml::ContentMathMLGenerator;
+/// use rpn2tex::span::Span;
+///
+/// let expr = Expr::BinaryOp {
+///     span: Span::new(4, 5, 1, 5),
+///     operator: "+".to_string(),
+///     left: Box::new(Expr::Number {
+///         span: Span::new(0, 1, 1, 1),
+///         value: "5".to_string(),
+///     }),
+///     right: Box::new(Expr::Number {
+///         span: Span::new(2, 3, 1, 3),
+///         value: "3".to_string(),
+///     }),
+/// };
+///
+/// let mathml = ContentMathMLGenerator::new().generate(&expr);
+/// assert_eq!(
+///     mathml,
+///     "<math xmlns=\"http://www.w3.org/1998/Math/MathML\">\
+///      <apply><plus/><cn type=\"integer\">5</cn><cn type=\"integer\">3</cn></apply>\
+///      </math>"
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ContentMathMLGenerator;
+
+impl ContentMathMLGenerator {
+    /// Creates a new Content MathML generator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::mathml::ContentMathMLGenerator;
+    ///
+    /// let generator = ContentMathMLGenerator::new();
+    /// ```
+    #[must_use]
+    pub const fn new() -> Self {
+        Self
+    }
+
+    /// Generates a `<math>` element containing the Content MathML encoding
+    /// of an AST expression.
+    ///
+    /// # Arguments
+    ///
+    /// * `ast` - The root expression node to convert
+    #[must_use]
+    pub fn generate(&self, ast: &Expr) -> String {
+        format!(
+            "<math xmlns=\"{}\">{}</math>",
+            MATHML_NAMESPACE,
+            self.visit(ast)
+        )
+    }
+
+    /// Visits an expression node and generates its Content MathML encoding.
+    fn visit(&self, node: &Expr) -> String {
+        match node {
+            Expr::Number { value, .. } => {
+                let kind = if value.contains('.') {
+                    "real"
+                } else {
+                    "integer"
+                };
+                format!("<cn type=\"{}\">{}</cn>", kind, value)
+            }
+            Expr::Variable { name, .. } => format!("<ci>{}</ci>", name),
+            Expr::BinaryOp {
+                operator,
+                left,
+                right,
+                ..
+            } => format!(
+                "<apply><{}/>{}{}</apply>",
+                Self::operator_element(operator),
+                self.visit(left),
+                self.visit(right)
+            ),
+            // A custom operator is a symbol the reader must define
+            Expr::Apply {
+                operator, operands, ..
+            } => format!(
+                "<apply><csymbol>{}</csymbol>{}</apply>",
+                operator
+                    .replace('&', "&amp;")
+                    .replace('<', "&lt;")
+                    .replace('>', "&gt;"),
+                operands
+                    .iter()
+                    .map(|operand| self.visit(operand))
+                    .collect::<String>()
+            ),
+        }
+    }
+
+    /// Returns the Content MathML element name for an operator symbol.
+    ///
+    /// # Mappings
+    ///
+    /// * `+` → `plus`
+    /// * `-` → `minus`
+    /// * `*` → `times`
+    /// * `/` → `divide`
+    fn operator_element(operator: &str) -> &'static str {
+        match operator {
+            "+" => "plus",
+            "-" => "minus",
+            "*" => "times",
+            "/" => "divide",
+            _ => "csymbol",
+        }
+    }
+}
+
+impl CodeGenerator for ContentMathMLGenerator {
+    fn generate(&self, expr: &Expr) -> String {
+        ContentMathMLGenerator::generate(self, expr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::span::Span;
+
+    fn generate(input: &str) -> String {
+        let tokens = Lexer::new(input).tokenize().expect("tokenize failed");
+        let ast = Parser::new(tokens).parse().expect("parse failed");
+        ContentMathMLGenerator::new().generate(&ast)
+    }
+
+    /// Strips the `<math>` wrapper to keep expected strings readable.
+    fn body(input: &str) -> String {
+        let mathml = generate(input);
+        let open = format!("<math xmlns=\"{}\">", MATHML_NAMESPACE);
+        mathml
+            .strip_prefix(&open)
+            .and_then(|rest| rest.strip_suffix("</math>"))
+            .expect("missing <math> wrapper")
+            .to_string()
+    }
+
+    #[test]
+    fn test_number_types() {
+        assert_eq!(body("42"), r#"<cn type="integer">42</cn>"#);
+        assert_eq!(body("3.14"), r#"<cn type="real">3.14</cn>"#);
+        assert_eq!(body("-5"), r#"<cn type="integer">-5</cn>"#);
+    }
+
+    #[test]
+    fn test_variable() {
+        let variable = Expr::Variable {
+            span: Span::new(0, 1, 1, 1),
+            name: "x".to_string(),
+        };
+        assert_eq!(
+            ContentMathMLGenerator::new().generate(&variable),
+            format!("<math xmlns=\"{}\"><ci>x</ci></math>", MATHML_NAMESPACE)
+        );
+    }
+
+    #[test]
+    fn test_all_operators() {
+        for (input, element) in [
+            ("1 2 +", "plus"),
+            ("1 2 -", "minus"),
+            ("1 2 *", "times"),
+            ("1 2 /", "divide"),
+        ] {
+            assert_eq!(
+                body(input),
+                format!(
+                    r#"<apply><{}/><cn type="integer">1</cn><cn type="integer">2</cn></apply>"#,
+                    element
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn test_nesting_follows_tree_not_precedence() {
+        // (5 + 3) * 2 needs no parentheses in content markup
+        assert_eq!(
+            body("5 3 + 2 *"),
+            concat!(
+                "<apply><times/>",
+                r#"<apply><plus/><cn type="integer">5</cn><cn type="integer">3</cn></apply>"#,
+                r#"<cn type="integer">2</cn>"#,
+                "</apply>"
+            )
+        );
+    }
+
+    #[test]
+    fn test_math_wrapper() {
+        let mathml = generate("1");
+        assert!(mathml.starts_with("<math xmlns=\"http://www.w3.org/1998/Math/MathML\">"));
+        assert!(mathml.ends_with("</math>"));
+    }
+}
@@ -0,0 +1,187 @@
+//! Operator-position notations (prefix and postfix) generated from ASTs.
+//!
+//! Prefix (Polish) and postfix (Reverse Polish) notation need no parentheses:
+//! the tree shape is recovered entirely from operator positions. Both are
+//! produced by the same traversal, which only differs in whether an operator
+//! is written before or after its operands.
+
+use crate::ast::Expr;
+use crate::codegen::CodeGenerator;
+
+/// Where operators are placed relative to their operands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Notation {
+    /// Polish notation: operator first (`+ 5 3`)
+    Prefix,
+    /// Reverse Polish notation: operator last (`5 3 +`)
+    Postfix,
+}
+
+/// Converts AST expressions to prefix or postfix notation.
+///
+/// Tokens are separated by single spaces and numbers are written exactly as
+/// they appeared in the source.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::ast::Expr;
+/// use rpn2tex::notation::{Notation, NotationGenerator};
+/// use rpn2tex::span::Span;
+///
+/// // 5 + 3 * 2
+/// let expr = Expr::BinaryOp {
+///     span: Span::new(6, 7, 1, 7),
+///     operator: "+".to_string(),
+///     left: Box::new(Expr::Number {
+///         span: Span::new(0, 1, 1, 1),
+///         value: "5".to_string(),
+///     }),
+///     right: Box::new(Expr::BinaryOp {
+///         span: Span::new(4, 5, 1, 5),
+///         operator: "*".to_string(),
+///         left: Box::new(Expr::Number {
+///             span: Span::new(2, 3, 1, 3),
+///             value: "3".to_string(),
+///         }),
+///         right: Box::new(Expr::Number {
+///             span: Span::new(4, 5, 1, 5),
+///             value: "2".to_string(),
+///         }),
+///     }),
+/// };
+///
+/// let prefix = NotationGenerator::new(Notation::Prefix);
+/// assert_eq!(prefix.generate(&expr), "+ 5 * 3 2");
+///
+/// let rpn = NotationGenerator::new(Notation::Postfix);
+/// assert_eq!(rpn.generate(&expr), "5 3 2 * +");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NotationGenerator {
+    /// Operator placement used for every node
+    notation: Notation,
+}
+
+impl NotationGenerator {
+    /// Creates a generator for the given notation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::notation::{Notation, NotationGenerator};
+    ///
+    /// let generator = NotationGenerator::new(Notation::Prefix);
+    /// assert_eq!(generator.notation(), Notation::Prefix);
+    /// ```
+    #[must_use]
+    pub const fn new(notation: Notation) -> Self {
+        Self { notation }
+    }
+
+    /// Returns the notation this generator emits.
+    #[must_use]
+    pub const fn notation(&self) -> Notation {
+        self.notation
+    }
+
+    /// Generates the expression in this generator's notation.
+    ///
+    /// # Arguments
+    ///
+    /// * `ast` - The root expression node to convert
+    ///
+    /// # Returns
+    ///
+    /// The space-separated token sequence, without delimiters.
+    #[must_use]
+    pub fn generate(&self, ast: &Expr) -> String {
+        let mut tokens = Vec::new();
+        self.visit(ast, &mut tokens);
+        tokens.join(" ")
+    }
+
+    /// Appends the tokens of `node` and its subtree to `tokens`.
+    fn visit<'a>(&self, node: &'a Expr, tokens: &mut Vec<&'a str>) {
+        match node {
+            Expr::Number { value, .. } => tokens.push(value),
+            Expr::Variable { name, .. } => tokens.push(name),
+            Expr::BinaryOp {
+                operator,
+                left,
+                right,
+                ..
+            } => {
+                if self.notation == Notation::Prefix {
+                    tokens.push(operator);
+                }
+                self.visit(left, tokens);
+                self.visit(right, tokens);
+                if self.notation == Notation::Postfix {
+                    tokens.push(operator);
+                }
+            }
+            Expr::Apply {
+                operator, operands, ..
+            } => {
+                if self.notation == Notation::Prefix {
+                    tokens.push(operator);
+                }
+                for operand in operands {
+                    self.visit(operand, tokens);
+                }
+                if self.notation == Notation::Postfix {
+                    tokens.push(operator);
+                }
+            }
+        }
+    }
+}
+
+impl CodeGenerator for NotationGenerator {
+    fn generate(&self, expr: &Expr) -> String {
+        NotationGenerator::generate(self, expr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::parse;
+
+    #[test]
+    fn test_prefix_single_number() {
+        let generator = NotationGenerator::new(Notation::Prefix);
+        assert_eq!(generator.generate(&parse("42")), "42");
+    }
+
+    #[test]
+    fn test_prefix_nested() {
+        let generator = NotationGenerator::new(Notation::Prefix);
+        assert_eq!(generator.generate(&parse("5 3 + 2 *")), "* + 5 3 2");
+        assert_eq!(generator.generate(&parse("5 3 2 * +")), "+ 5 * 3 2");
+        assert_eq!(generator.generate(&parse("1 2 + 3 4 + *")), "* + 1 2 + 3 4");
+    }
+
+    #[test]
+    fn test_prefix_keeps_number_spelling() {
+        let generator = NotationGenerator::new(Notation::Prefix);
+        assert_eq!(generator.generate(&parse("-3.14 2 /")), "/ -3.14 2");
+    }
+
+    #[test]
+    fn test_postfix_normalizes_spacing() {
+        let generator = NotationGenerator::new(Notation::Postfix);
+        assert_eq!(generator.generate(&parse("  5   3+\n2 *")), "5 3 + 2 *");
+    }
+
+    #[test]
+    fn test_postfix_round_trip() {
+        let generator = NotationGenerator::new(Notation::Postfix);
+        for input in ["5 3 -", "10 2 / 3 + 4 *", "100 10 / 5 / 2 /"] {
+            let ast = parse(input);
+            assert_eq!(generator.generate(&ast), input);
+            assert_eq!(parse(&generator.generate(&ast)), ast);
+        }
+    }
+}
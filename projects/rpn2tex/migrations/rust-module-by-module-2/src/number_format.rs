@@ -0,0 +1,405 @@
+//! Typesetting of number literals in generated math.
+//!
+//! Formatting only changes how a literal is written in the output; the parsed
+//! value stored in the AST is never modified.
+
+/// Separator inserted between groups of three integer digits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ThousandsSeparator {
+    /// Digits are written unbroken: `1000000` (the default)
+    #[default]
+    None,
+    /// A thin space, as recommended by SI: `1\,000\,000`
+    ThinSpace,
+    /// A comma braced so TeX does not add punctuation space: `1{,}000{,}000`
+    Comma,
+}
+
+impl ThousandsSeparator {
+    /// Returns the TeX written between digit groups, or `None` when digits
+    /// are not grouped.
+    #[must_use]
+    pub const fn tex(self) -> Option<&'static str> {
+        match self {
+            Self::None => None,
+            Self::ThinSpace => Some(r"\,"),
+            Self::Comma => Some("{,}"),
+        }
+    }
+}
+
+/// Mark written between the integer and fractional digits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum DecimalSeparator {
+    /// A decimal point: `3.14` (the default)
+    #[default]
+    Point,
+    /// A decimal comma, as used in most European documents: `3{,}14`
+    Comma,
+}
+
+impl DecimalSeparator {
+    /// Returns the TeX written between integer and fractional digits.
+    ///
+    /// The comma is braced so TeX does not treat it as punctuation and add
+    /// space after it.
+    #[must_use]
+    pub const fn tex(self) -> &'static str {
+        match self {
+            Self::Point => ".",
+            Self::Comma => "{,}",
+        }
+    }
+}
+
+/// How many digits of a literal are kept in the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Precision {
+    /// Every digit is written as parsed (the default)
+    #[default]
+    Exact,
+    /// Rounded or zero-padded to this many digits after the decimal mark
+    DecimalPlaces(u8),
+    /// Rounded or zero-padded to this many significant figures (at least one)
+    SignificantFigures(u8),
+}
+
+/// Options controlling how number literals are written.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::number_format::{DecimalSeparator, NumberFormat, Precision, ThousandsSeparator};
+///
+/// let format = NumberFormat::new().with_thousands(ThousandsSeparator::ThinSpace);
+/// assert_eq!(format.format("1000000"), r"1\,000\,000");
+/// assert_eq!(format.format("-12345.678"), r"-12\,345.678");
+/// assert_eq!(NumberFormat::new().format("1000000"), "1000000");
+///
+/// let european = NumberFormat::new()
+///     .with_thousands(ThousandsSeparator::ThinSpace)
+///     .with_decimal(DecimalSeparator::Comma);
+/// assert_eq!(european.format("1234.5"), r"1\,234{,}5");
+///
+/// let rounded = NumberFormat::new().with_precision(Precision::DecimalPlaces(2));
+/// assert_eq!(rounded.format("3.14159"), "3.14");
+/// assert_eq!(rounded.format("2"), "2.00");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NumberFormat {
+    /// Separator between groups of integer digits
+    thousands: ThousandsSeparator,
+    /// Separator between integer and fractional digits
+    decimal: DecimalSeparator,
+    /// Digits kept when writing a literal
+    precision: Precision,
+    /// Whether a zero is written before a bare decimal mark, as in `0.5`
+    leading_zero: bool,
+}
+
+impl NumberFormat {
+    /// Creates a format that writes literals exactly as they were parsed.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            thousands: ThousandsSeparator::None,
+            decimal: DecimalSeparator::Point,
+            precision: Precision::Exact,
+            leading_zero: false,
+        }
+    }
+
+    /// Returns this format configured to group integer digits with
+    /// `thousands`.
+    #[must_use]
+    pub const fn with_thousands(mut self, thousands: ThousandsSeparator) -> Self {
+        self.thousands = thousands;
+        self
+    }
+
+    /// Returns the separator placed between groups of integer digits.
+    #[must_use]
+    pub const fn thousands(&self) -> ThousandsSeparator {
+        self.thousands
+    }
+
+    /// Returns this format configured to separate fractional digits with
+    /// `decimal`.
+    ///
+    /// Combining a decimal comma with [`ThousandsSeparator::Comma`] makes
+    /// numbers ambiguous to readers; prefer a thin space for grouping then.
+    #[must_use]
+    pub const fn with_decimal(mut self, decimal: DecimalSeparator) -> Self {
+        self.decimal = decimal;
+        self
+    }
+
+    /// Returns the separator placed between integer and fractional digits.
+    #[must_use]
+    pub const fn decimal(&self) -> DecimalSeparator {
+        self.decimal
+    }
+
+    /// Returns this format configured to round or pad literals to
+    /// `precision`.
+    ///
+    /// Rounding is performed on the decimal digits, rounding half away from
+    /// zero, so no binary floating-point error is introduced.
+    #[must_use]
+    pub const fn with_precision(mut self, precision: Precision) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Returns the precision literals are written with.
+    #[must_use]
+    pub const fn precision(&self) -> Precision {
+        self.precision
+    }
+
+    /// Returns this format configured to write literals such as `.5` with a
+    /// leading zero, as `0.5`.
+    ///
+    /// Rounded literals always get a leading zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::number_format::NumberFormat;
+    ///
+    /// assert_eq!(NumberFormat::new().format("-.5"), "-.5");
+    /// assert_eq!(NumberFormat::new().with_leading_zero(true).format("-.5"), "-0.5");
+    /// ```
+    #[must_use]
+    pub const fn with_leading_zero(mut self, leading_zero: bool) -> Self {
+        self.leading_zero = leading_zero;
+        self
+    }
+
+    /// Returns whether a zero is written before a bare decimal mark.
+    #[must_use]
+    pub const fn leading_zero(&self) -> bool {
+        self.leading_zero
+    }
+
+    /// Writes a number literal according to this format.
+    ///
+    /// The literal is first rounded to the configured precision. Only the
+    /// integer part is then grouped; digits after the decimal mark are left
+    /// ungrouped.
+    ///
+    /// # Arguments
+    ///
+    /// * `literal` - The literal as stored in the AST, e.g. `-1234.5`
+    #[must_use]
+    pub fn format(&self, literal: &str) -> String {
+        let (sign, unsigned) = match literal.strip_prefix('-') {
+            Some(rest) => ("-", rest),
+            None => ("", literal),
+        };
+        let (integer, fraction) = match unsigned.split_once('.') {
+            Some((integer, fraction)) => (integer, Some(fraction)),
+            None => (unsigned, None),
+        };
+
+        let (integer, fraction) = match self.precision {
+            Precision::Exact if integer.is_empty() && self.leading_zero => {
+                (String::from("0"), fraction.map(str::to_string))
+            }
+            Precision::Exact => (integer.to_string(), fraction.map(str::to_string)),
+            _ => {
+                let (integer, fraction) = self.round(integer, fraction.unwrap_or(""));
+                (integer, Some(fraction).filter(|f| !f.is_empty()))
+            }
+        };
+
+        // Rounding can turn "-0.001" into zero, which has no sign
+        let is_zero = integer
+            .chars()
+            .chain(fraction.iter().flat_map(|f| f.chars()))
+            .all(|c| c == '0');
+        let sign = if self.precision != Precision::Exact && is_zero {
+            ""
+        } else {
+            sign
+        };
+
+        let mut output = String::from(sign);
+        output.push_str(&self.group_integer(&integer));
+        if let Some(fraction) = fraction {
+            output.push_str(self.decimal.tex());
+            output.push_str(&fraction);
+        }
+        output
+    }
+
+    /// Rounds the digits of an unsigned literal to the configured precision,
+    /// returning the new integer and fractional digits.
+    fn round(&self, integer: &str, fraction: &str) -> (String, String) {
+        let mut digits: Vec<u8> = integer
+            .bytes()
+            .chain(fraction.bytes())
+            .map(|b| b - b'0')
+            .collect();
+        let mut point = integer.len();
+
+        // Number of leading digits to keep
+        let mut keep = match self.precision {
+            Precision::Exact => digits.len(),
+            Precision::DecimalPlaces(places) => point + usize::from(places),
+            Precision::SignificantFigures(figures) => {
+                let figures = usize::from(figures.max(1));
+                match digits.iter().position(|&d| d != 0) {
+                    Some(first) => first + figures,
+                    // Zero has no significant digits; pad it like "0.00"
+                    None => point + figures - 1,
+                }
+            }
+        };
+
+        if keep < digits.len() {
+            let round_up = digits[keep] >= 5;
+            digits.truncate(keep);
+            if round_up {
+                let mut carried = true;
+                for digit in digits.iter_mut().rev() {
+                    if *digit == 9 {
+                        *digit = 0;
+                    } else {
+                        *digit += 1;
+                        carried = false;
+                        break;
+                    }
+                }
+                if carried {
+                    // 9.99 -> 10.0: the number gained an integer digit
+                    digits.insert(0, 1);
+                    point += 1;
+                    if matches!(self.precision, Precision::DecimalPlaces(_)) {
+                        keep += 1;
+                    }
+                    digits.truncate(keep.max(point));
+                }
+            }
+        }
+        digits.resize(keep.max(point), 0);
+
+        let to_text =
+            |digits: &[u8]| -> String { digits.iter().map(|&d| char::from(b'0' + d)).collect() };
+        let mut integer = to_text(&digits[..point]);
+        if integer.is_empty() {
+            integer.push('0');
+        }
+        (integer, to_text(&digits[point..]))
+    }
+
+    /// Inserts the thousands separator between groups of three digits,
+    /// counting from the right.
+    fn group_integer(&self, digits: &str) -> String {
+        let Some(separator) = self.thousands.tex() else {
+            return digits.to_string();
+        };
+
+        let mut grouped = String::new();
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                grouped.push_str(separator);
+            }
+            grouped.push(digit);
+        }
+        grouped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_leaves_literals_unchanged() {
+        let format = NumberFormat::default();
+        for literal in ["0", "42", "-7", "3.14", "1000000", "-0.5"] {
+            assert_eq!(format.format(literal), literal);
+        }
+    }
+
+    #[test]
+    fn test_leading_zero() {
+        let format = NumberFormat::new().with_leading_zero(true);
+        assert_eq!(format.format(".5"), "0.5");
+        assert_eq!(format.format("-.25"), "-0.25");
+        assert_eq!(format.format("3.5"), "3.5");
+        assert_eq!(NumberFormat::new().format(".5"), ".5");
+
+        // Rounding pads the integer part regardless
+        let rounded = NumberFormat::new().with_precision(Precision::DecimalPlaces(1));
+        assert_eq!(rounded.format(".25"), "0.3");
+    }
+
+    #[test]
+    fn test_thin_space_grouping() {
+        let format = NumberFormat::new().with_thousands(ThousandsSeparator::ThinSpace);
+        assert_eq!(format.format("999"), "999");
+        assert_eq!(format.format("1000"), r"1\,000");
+        assert_eq!(format.format("123456"), r"123\,456");
+        assert_eq!(format.format("1234567"), r"1\,234\,567");
+        assert_eq!(format.format("-1000"), r"-1\,000");
+    }
+
+    #[test]
+    fn test_comma_grouping_leaves_fraction() {
+        let format = NumberFormat::new().with_thousands(ThousandsSeparator::Comma);
+        assert_eq!(format.format("1000000"), "1{,}000{,}000");
+        assert_eq!(format.format("1234.56789"), "1{,}234.56789");
+    }
+
+    #[test]
+    fn test_decimal_places() {
+        let format = NumberFormat::new().with_precision(Precision::DecimalPlaces(2));
+        assert_eq!(format.format("3.14159"), "3.14");
+        assert_eq!(format.format("2.675"), "2.68");
+        assert_eq!(format.format("9.999"), "10.00");
+        assert_eq!(format.format("-1.005"), "-1.01");
+        assert_eq!(format.format("-0.001"), "0.00");
+        assert_eq!(format.format("7"), "7.00");
+        assert_eq!(format.format("0.1"), "0.10");
+    }
+
+    #[test]
+    fn test_zero_decimal_places() {
+        let format = NumberFormat::new().with_precision(Precision::DecimalPlaces(0));
+        assert_eq!(format.format("3.5"), "4");
+        assert_eq!(format.format("99.9"), "100");
+        assert_eq!(format.format("12"), "12");
+    }
+
+    #[test]
+    fn test_significant_figures() {
+        let format = NumberFormat::new().with_precision(Precision::SignificantFigures(3));
+        assert_eq!(format.format("3.14159"), "3.14");
+        assert_eq!(format.format("0.0012345"), "0.00123");
+        assert_eq!(format.format("12345"), "12300");
+        assert_eq!(format.format("9.996"), "10.0");
+        assert_eq!(format.format("999.7"), "1000");
+        assert_eq!(format.format("2"), "2.00");
+        assert_eq!(format.format("0"), "0.00");
+    }
+
+    #[test]
+    fn test_precision_combines_with_separators() {
+        let format = NumberFormat::new()
+            .with_precision(Precision::DecimalPlaces(1))
+            .with_thousands(ThousandsSeparator::ThinSpace)
+            .with_decimal(DecimalSeparator::Comma);
+        assert_eq!(format.format("1234.56"), r"1\,234{,}6");
+    }
+
+    #[test]
+    fn test_decimal_comma() {
+        let format = NumberFormat::new().with_decimal(DecimalSeparator::Comma);
+        assert_eq!(format.format("3.14"), "3{,}14");
+        assert_eq!(format.format("-0.5"), "-0{,}5");
+        assert_eq!(format.format("42"), "42");
+        assert_eq!(format.decimal(), DecimalSeparator::Comma);
+    }
+}
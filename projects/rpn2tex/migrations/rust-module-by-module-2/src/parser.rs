@@ -14,18 +14,24 @@
 //!    - If EOF: stop processing
 //! 3. Verify the stack has exactly one element (the root expression)
 //!
+//! Prefix (Polish) input is parsed by [`Parser::parse_prefix`] with the same
+//! algorithm, reading the tokens from right to left. Infix input is parsed
+//! by [`Parser::parse_infix`], a recursive descent over the precedence
+//! levels.
+//!
 //! # Examples
 //!
 //! ```
 //! use rpn2tex::parser::Parser;
+//! use rpn2tex::span::Span;
 //! use rpn2tex::tokens::{Token, TokenType};
 //! use rpn2tex::ast::Expr;
 //!
 //! let tokens = vec![
-//!     Token::new(TokenType::Number, "5".to_string(), 1, 1),
-//!     Token::new(TokenType::Number, "3".to_string(), 1, 3),
-//!     Token::new(TokenType::Plus, "+".to_string(), 1, 5),
-//!     Token::new(TokenType::Eof, String::new(), 1, 6),
+//!     Token::new(TokenType::Number, "5", Span::new(0, 1, 1, 1)),
+//!     Token::new(TokenType::Number, "3", Span::new(2, 3, 1, 3)),
+//!     Token::new(TokenType::Plus, "+", Span::new(4, 5, 1, 5)),
+//!     Token::new(TokenType::Eof, "", Span::new(5, 5, 1, 6)),
 //! ];
 //!
 //! let mut parser = Parser::new(tokens);
@@ -39,7 +45,17 @@
 //! ```
 
 use crate::ast::Expr;
-use crate::tokens::{Token, TokenType};
+use crate::diagnostic::Diagnostic;
+use crate::error_code::ErrorCode;
+use crate::limits::{LimitExceeded, Limits};
+use crate::macros::MacroTable;
+use crate::notation::Notation;
+use crate::plugin::OperatorTable;
+use crate::span::Span;
+use crate::tokens::{OwnedToken, Token, TokenType};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 use std::error::Error;
 use std::fmt;
 
@@ -54,20 +70,34 @@ use std::fmt;
 ///
 /// ```
 /// use rpn2tex::parser::ParserError;
+/// use rpn2tex::span::Span;
 /// use rpn2tex::tokens::{Token, TokenType};
 ///
-/// let token = Token::new(TokenType::Plus, "+".to_string(), 1, 5);
+/// let token = Token::new(TokenType::Plus, "+", Span::new(4, 5, 1, 5));
 /// let error = ParserError::new("Too few operands for operator", token);
 ///
-/// assert_eq!(error.token().line, 1);
-/// assert_eq!(error.token().column, 5);
+/// assert_eq!(error.token().span.line, 1);
+/// assert_eq!(error.token().span.column, 5);
 /// ```
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ParserError {
     /// Error message describing what went wrong
     message: String,
     /// The token where the error occurred
-    token: Token,
+    token: OwnedToken,
+    /// The limit the expression exceeded, if that is the error
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    limit_exceeded: Option<LimitExceeded>,
+    /// Stable code identifying the kind of error
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    code: Option<ErrorCode>,
 }
 
 impl ParserError {
@@ -76,34 +106,57 @@ impl ParserError {
     /// # Arguments
     ///
     /// * `message` - Description of the error
-    /// * `token` - The token where the error occurred
+    /// * `token` - The token where the error occurred, copied if it borrows
+    ///   from the source
     ///
     /// # Examples
     ///
     /// ```
     /// use rpn2tex::parser::ParserError;
+    /// use rpn2tex::span::Span;
     /// use rpn2tex::tokens::{Token, TokenType};
     ///
-    /// let token = Token::new(TokenType::Eof, String::new(), 1, 10);
+    /// let token = Token::new(TokenType::Eof, "", Span::new(9, 9, 1, 10));
     /// let error = ParserError::new("Empty expression", token);
     /// ```
     #[must_use]
-    pub fn new(message: impl Into<String>, token: Token) -> Self {
+    pub fn new(message: impl Into<String>, token: Token<'_>) -> Self {
         Self {
             message: message.into(),
-            token,
+            token: token.into_owned(),
+            limit_exceeded: None,
+            code: None,
+        }
+    }
+
+    /// Creates an error for an expression that exceeded one of the
+    /// parser's [`Limits`], at the token where it was detected.
+    #[must_use]
+    pub fn from_limit(limit: LimitExceeded, token: Token<'_>) -> Self {
+        Self {
+            limit_exceeded: Some(limit),
+            code: Some(limit.code()),
+            ..Self::new(limit.to_string(), token)
         }
     }
 
+    /// Attaches the stable code identifying the kind of error.
+    #[must_use]
+    pub fn with_code(mut self, code: ErrorCode) -> Self {
+        self.code = Some(code);
+        self
+    }
+
     /// Returns a reference to the error message.
     ///
     /// # Examples
     ///
     /// ```
     /// use rpn2tex::parser::ParserError;
+    /// use rpn2tex::span::Span;
     /// use rpn2tex::tokens::{Token, TokenType};
     ///
-    /// let token = Token::new(TokenType::Eof, String::new(), 1, 1);
+    /// let token = Token::new(TokenType::Eof, "", Span::new(0, 0, 1, 1));
     /// let error = ParserError::new("Test error", token);
     /// assert_eq!(error.message(), "Test error");
     /// ```
@@ -118,17 +171,32 @@ impl ParserError {
     ///
     /// ```
     /// use rpn2tex::parser::ParserError;
+    /// use rpn2tex::span::Span;
     /// use rpn2tex::tokens::{Token, TokenType};
     ///
-    /// let token = Token::new(TokenType::Plus, "+".to_string(), 2, 5);
+    /// let token = Token::new(TokenType::Plus, "+", Span::new(10, 11, 2, 5));
     /// let error = ParserError::new("Error", token);
-    /// assert_eq!(error.token().line, 2);
-    /// assert_eq!(error.token().column, 5);
+    /// assert_eq!(error.token().span.line, 2);
+    /// assert_eq!(error.token().span.column, 5);
     /// ```
     #[must_use]
-    pub fn token(&self) -> &Token {
+    pub fn token(&self) -> &OwnedToken {
         &self.token
     }
+
+    /// Returns the limit the expression exceeded, or `None` for a syntax
+    /// error.
+    #[must_use]
+    pub fn limit_exceeded(&self) -> Option<LimitExceeded> {
+        self.limit_exceeded
+    }
+
+    /// Returns the stable code identifying the kind of error, if it has
+    /// one.
+    #[must_use]
+    pub fn code(&self) -> Option<ErrorCode> {
+        self.code
+    }
 }
 
 impl fmt::Display for ParserError {
@@ -136,13 +204,77 @@ impl fmt::Display for ParserError {
         write!(
             f,
             "Line {}, column {}: {}",
-            self.token.line, self.token.column, self.message
+            self.token.span.line, self.token.span.column, self.message
         )
     }
 }
 
 impl Error for ParserError {}
 
+/// A parse that failed, with the expressions understood before the error.
+///
+/// Returned by [`Parser::parse_partial`]. The stack holds the complete
+/// subexpressions built so far, bottom first, so tooling such as an editor
+/// preview can still render them.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::lexer::Lexer;
+/// use rpn2tex::parser::Parser;
+///
+/// let tokens = Lexer::new("5 3 + 2").tokenize().unwrap();
+/// let partial = Parser::new(tokens).parse_partial().unwrap_err();
+/// assert_eq!(partial.error().message(), "Too many values on stack (missing operators)");
+/// assert_eq!(partial.stack().len(), 2);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartialParse {
+    /// The error that stopped parsing, boxed to keep results small
+    error: Box<ParserError>,
+    /// Expressions on the operand stack when parsing stopped, bottom first
+    stack: Vec<Expr>,
+}
+
+impl PartialParse {
+    /// Returns the error that stopped parsing.
+    #[must_use]
+    pub fn error(&self) -> &ParserError {
+        &self.error
+    }
+
+    /// Returns the expressions on the operand stack when parsing stopped,
+    /// bottom first.
+    #[must_use]
+    pub fn stack(&self) -> &[Expr] {
+        &self.stack
+    }
+
+    /// Returns the error, discarding the partial stack.
+    #[must_use]
+    pub fn into_error(self) -> ParserError {
+        *self.error
+    }
+
+    /// Returns the partial stack, discarding the error.
+    #[must_use]
+    pub fn into_stack(self) -> Vec<Expr> {
+        self.stack
+    }
+}
+
+impl fmt::Display for PartialParse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl Error for PartialParse {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&*self.error)
+    }
+}
+
 /// Parser for RPN expressions.
 ///
 /// The parser takes a token stream from the lexer and constructs an Abstract
@@ -153,46 +285,185 @@ impl Error for ParserError {}
 ///
 /// ```
 /// use rpn2tex::parser::Parser;
+/// use rpn2tex::span::Span;
 /// use rpn2tex::tokens::{Token, TokenType};
 ///
 /// // Parse "5 3 +"
 /// let tokens = vec![
-///     Token::new(TokenType::Number, "5".to_string(), 1, 1),
-///     Token::new(TokenType::Number, "3".to_string(), 1, 3),
-///     Token::new(TokenType::Plus, "+".to_string(), 1, 5),
-///     Token::new(TokenType::Eof, String::new(), 1, 6),
+///     Token::new(TokenType::Number, "5", Span::new(0, 1, 1, 1)),
+///     Token::new(TokenType::Number, "3", Span::new(2, 3, 1, 3)),
+///     Token::new(TokenType::Plus, "+", Span::new(4, 5, 1, 5)),
+///     Token::new(TokenType::Eof, "", Span::new(5, 5, 1, 6)),
 /// ];
 ///
 /// let mut parser = Parser::new(tokens);
 /// let ast = parser.parse().expect("parse failed");
 /// ```
-#[derive(Debug)]
-pub struct Parser {
-    /// Token stream from the lexer
-    tokens: Vec<Token>,
+pub struct Parser<'a> {
+    /// Tokens read from the source and not yet consumed by a postfix parse
+    tokens: VecDeque<Token<'a>>,
+    /// Tokens not yet read, until the source runs out
+    source: Option<Box<dyn Iterator<Item = Token<'a>> + 'a>>,
     /// Current position in the token list (0-based)
     pos: usize,
+    /// Cap on expression depth; other limits are enforced by the lexer
+    limits: Limits,
+    /// Macros still to be expanded in the remaining tokens
+    macros: Option<MacroTable>,
+    /// Custom operators, with the number of operands each takes
+    operators: OperatorTable,
+}
+
+impl fmt::Debug for Parser<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Parser")
+            .field("tokens", &self.tokens)
+            .field("pos", &self.pos)
+            .field("limits", &self.limits)
+            .field("macros", &self.macros)
+            .field("operators", &self.operators)
+            .finish_non_exhaustive()
+    }
 }
 
-impl Parser {
+impl<'a> Parser<'a> {
     /// Creates a new parser with the given token stream.
     ///
+    /// Tokens are read only as the parse needs them. A postfix parse
+    /// without macros reads one token at a time, drops it once applied and
+    /// stops at the EOF token, so lexing and parsing can run in a single
+    /// pass holding one token at a time; prefix and infix parses, and macro
+    /// expansion, read every token first. A stream without an EOF token
+    /// ends after its last token.
+    ///
     /// # Arguments
     ///
-    /// * `tokens` - The token stream produced by the lexer
+    /// * `tokens` - The token stream produced by the lexer, as a `Vec` or
+    ///   any other iterable
     ///
     /// # Examples
     ///
     /// ```
     /// use rpn2tex::parser::Parser;
+    /// use rpn2tex::span::Span;
     /// use rpn2tex::tokens::{Token, TokenType};
     ///
-    /// let tokens = vec![Token::new(TokenType::Eof, String::new(), 1, 1)];
+    /// let tokens = vec![Token::new(TokenType::Eof, "", Span::new(0, 0, 1, 1))];
     /// let parser = Parser::new(tokens);
     /// ```
+    ///
+    /// Tokens can come straight from [`Lexer::iter`](crate::lexer::Lexer::iter);
+    /// [`parse_stream`](Self::parse_stream) also reports lexer errors.
+    ///
+    /// ```
+    /// use rpn2tex::lexer::Lexer;
+    /// use rpn2tex::parser::Parser;
+    ///
+    /// let tokens = Lexer::new("5 3 + 2 *").iter().map_while(Result::ok);
+    /// let ast = Parser::new(tokens).parse().unwrap();
+    /// assert_eq!(ast.column(), 9);
+    /// ```
+    #[must_use]
+    pub fn new<I>(tokens: I) -> Self
+    where
+        I: IntoIterator<Item = Token<'a>>,
+        I::IntoIter: 'a,
+    {
+        Self {
+            tokens: VecDeque::new(),
+            source: Some(Box::new(tokens.into_iter())),
+            pos: 0,
+            limits: Limits::new(),
+            macros: None,
+            operators: OperatorTable::new(),
+        }
+    }
+
+    /// Sets the limits to enforce. Only the depth limit applies to the
+    /// parser; the lexer enforces the others.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::lexer::Lexer;
+    /// use rpn2tex::limits::{LimitExceeded, Limits};
+    /// use rpn2tex::parser::Parser;
+    ///
+    /// // (1 + 2) + 3 is three levels deep
+    /// let tokens = Lexer::new("1 2 + 3 +").tokenize().unwrap();
+    /// let error = Parser::new(tokens)
+    ///     .with_limits(Limits::new().with_max_depth(2))
+    ///     .parse()
+    ///     .unwrap_err();
+    /// assert_eq!(error.limit_exceeded(), Some(LimitExceeded::Depth(2)));
+    /// assert_eq!(error.token().span.column, 9);
+    /// ```
+    #[must_use]
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Sets the macros to expand, along with the stack words `dup`, `swap`
+    /// and `drop`, before building the tree. Only postfix parses expand
+    /// them; [`parse_prefix`](Self::parse_prefix) ignores them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::lexer::Lexer;
+    /// use rpn2tex::macros::MacroTable;
+    /// use rpn2tex::parser::Parser;
+    ///
+    /// let macros = MacroTable::parse_prelude("macro sq = dup *").unwrap();
+    /// let tokens = Lexer::new("3 sq").with_identifiers(true).tokenize().unwrap();
+    /// assert!(Parser::new(tokens.clone()).parse().is_err());
+    /// assert!(Parser::new(tokens).with_macros(macros).parse().is_ok());
+    /// ```
+    #[must_use]
+    pub fn with_macros(mut self, macros: MacroTable) -> Self {
+        self.macros = Some(macros);
+        self
+    }
+
+    /// Sets the custom operators that
+    /// [`Operator`](TokenType::Operator) tokens are read as, each building
+    /// an [`Expr::Apply`] from as many operands as it takes. Only postfix
+    /// and prefix parses read them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::ast::Expr;
+    /// use rpn2tex::lexer::Lexer;
+    /// use rpn2tex::parser::Parser;
+    /// use rpn2tex::plugin::{OperatorPlugin, OperatorTable};
+    ///
+    /// struct Sqrt;
+    ///
+    /// impl OperatorPlugin for Sqrt {
+    ///     fn spelling(&self) -> &str {
+    ///         "sqrt"
+    ///     }
+    ///
+    ///     fn arity(&self) -> usize {
+    ///         1
+    ///     }
+    ///
+    ///     fn latex(&self, operands: &[String]) -> String {
+    ///         format!(r"\sqrt{{{}}}", operands[0])
+    ///     }
+    /// }
+    ///
+    /// let operators = OperatorTable::new().with_operator(Sqrt);
+    /// let tokens = Lexer::new("2 sqrt").with_operators(operators.clone()).tokenize().unwrap();
+    /// let ast = Parser::new(tokens).with_operators(operators).parse().unwrap();
+    /// assert!(matches!(ast, Expr::Apply { operands, .. } if operands.len() == 1));
+    /// ```
     #[must_use]
-    pub fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, pos: 0 }
+    pub fn with_operators(mut self, operators: OperatorTable) -> Self {
+        self.operators = operators;
+        self
     }
 
     /// Parses the token stream into an Abstract Syntax Tree.
@@ -211,91 +482,627 @@ impl Parser {
     ///
     /// ```
     /// use rpn2tex::parser::Parser;
+    /// use rpn2tex::span::Span;
     /// use rpn2tex::tokens::{Token, TokenType};
     ///
     /// let tokens = vec![
-    ///     Token::new(TokenType::Number, "42".to_string(), 1, 1),
-    ///     Token::new(TokenType::Eof, String::new(), 1, 4),
+    ///     Token::new(TokenType::Number, "42", Span::new(0, 2, 1, 1)),
+    ///     Token::new(TokenType::Eof, "", Span::new(3, 3, 1, 4)),
     /// ];
     ///
     /// let mut parser = Parser::new(tokens);
     /// let ast = parser.parse().expect("parse failed");
     /// ```
     pub fn parse(&mut self) -> Result<Expr, ParserError> {
-        let mut stack: Vec<Expr> = Vec::new();
+        self.parse_partial().map_err(PartialParse::into_error)
+    }
+
+    /// Parses the token stream like [`parse`](Self::parse), but on failure
+    /// also returns the expressions built before the error.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`PartialParse`] under the same conditions as
+    /// [`parse`](Self::parse). When an operator lacks operands, the stack
+    /// holds the operands it did find; when values are left over, it holds
+    /// all of them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::lexer::Lexer;
+    /// use rpn2tex::parser::Parser;
+    ///
+    /// let tokens = Lexer::new("5 3 + 2 * 4 + +").tokenize().unwrap();
+    /// let partial = Parser::new(tokens).parse_partial().unwrap_err();
+    /// assert_eq!(partial.error().token().span.column, 15);
+    ///
+    /// // (5 + 3) * 2 + 4 was understood before the last `+`
+    /// assert_eq!(partial.stack().len(), 1);
+    /// assert_eq!(partial.stack()[0].column(), 13);
+    /// ```
+    pub fn parse_partial(&mut self) -> Result<Expr, PartialParse> {
+        self.expand_macros().map_err(|error| PartialParse {
+            error: Box::new(error),
+            stack: Vec::new(),
+        })?;
+        let mut stack = Vec::new();
+        let max_depth = self.limits.max_depth();
+
+        let result = loop {
+            if self.at_end() {
+                break Self::finish(&mut stack, self.current());
+            }
+            if let Err(error) = Self::push_token(
+                &mut stack,
+                self.current(),
+                &self.operators,
+                Notation::Postfix,
+                max_depth,
+            ) {
+                break Err(error);
+            }
+            self.consume();
+        };
+
+        result.map_err(|error| PartialParse {
+            error: Box::new(error),
+            stack: stack.into_iter().map(|(expr, _)| expr).collect(),
+        })
+    }
+
+    /// Parses the token stream like [`parse`](Self::parse), but treats each
+    /// value left on the stack as a separate expression instead of an error.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ParserError`] if the input is empty, an operator lacks
+    /// operands, or a limit is exceeded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::latex::LaTeXGenerator;
+    /// use rpn2tex::lexer::Lexer;
+    /// use rpn2tex::parser::Parser;
+    ///
+    /// let tokens = Lexer::new("5 3 + 2 4 *").tokenize().unwrap();
+    /// let exprs = Parser::new(tokens).parse_all().unwrap();
+    /// let latex: Vec<String> = exprs.iter().map(|e| LaTeXGenerator::new().generate(e)).collect();
+    /// assert_eq!(latex, ["$5 + 3$", "$2 \\times 4$"]);
+    /// ```
+    pub fn parse_all(&mut self) -> Result<Vec<Expr>, ParserError> {
+        self.expand_macros()?;
+        let mut stack = Vec::new();
+        let max_depth = self.limits.max_depth();
+
+        while !self.at_end() {
+            Self::push_token(
+                &mut stack,
+                self.current(),
+                &self.operators,
+                Notation::Postfix,
+                max_depth,
+            )?;
+            self.consume();
+        }
+        if stack.is_empty() {
+            return Err(ParserError::new("Empty expression", self.current().clone())
+                .with_code(ErrorCode::EmptyExpression));
+        }
+        Ok(stack.into_iter().map(|(expr, _)| expr).collect())
+    }
 
+    /// Parses the token stream onto `stack`, the values left by earlier
+    /// input, leaving every value on it instead of requiring exactly one.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ParserError`] if an operator lacks operands or a limit is
+    /// exceeded. The stack is then left as it was before the call.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::lexer::Lexer;
+    /// use rpn2tex::parser::Parser;
+    ///
+    /// let mut stack = Vec::new();
+    /// Parser::new(Lexer::new("5 3").tokenize().unwrap()).parse_onto(&mut stack).unwrap();
+    /// Parser::new(Lexer::new("+ 2").tokenize().unwrap()).parse_onto(&mut stack).unwrap();
+    /// assert_eq!(stack.len(), 2);
+    ///
+    /// assert!(Parser::new(Lexer::new("* *").tokenize().unwrap()).parse_onto(&mut stack).is_err());
+    /// assert_eq!(stack.len(), 2);
+    /// ```
+    pub fn parse_onto(&mut self, stack: &mut Vec<Expr>) -> Result<(), ParserError> {
+        self.expand_macros()?;
+        let max_depth = self.limits.max_depth();
+        // The values are moved rather than copied; depths only matter
+        // against a limit
+        let mut entries: Vec<(Expr, usize)> = stack
+            .drain(..)
+            .map(|expr| {
+                let depth = max_depth.map_or(0, |_| tree_depth(&expr));
+                (expr, depth)
+            })
+            .collect();
+
+        // Every token applied pushed one value, built from those it popped
+        let mut applied = 0;
+        let mut result = Ok(());
         while !self.at_end() {
             let token = self.current();
+            result = Self::push_token(
+                &mut entries,
+                token,
+                &self.operators,
+                Notation::Postfix,
+                max_depth,
+            );
+            if result.is_err() {
+                break;
+            }
+            if !token.token_type.is_trivia() && token.token_type != TokenType::Eof {
+                applied += 1;
+            }
+            self.consume();
+        }
+        if result.is_err() {
+            // A failing token changes nothing, so taking the applied ones
+            // apart from the top down restores the stack
+            for _ in 0..applied {
+                match entries.pop().map(|(expr, _)| expr) {
+                    Some(Expr::BinaryOp { left, right, .. }) => {
+                        entries.extend([(*left, 0), (*right, 0)]);
+                    }
+                    Some(Expr::Apply { operands, .. }) => {
+                        entries.extend(operands.into_iter().map(|operand| (operand, 0)));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        stack.extend(entries.into_iter().map(|(expr, _)| expr));
+        result
+    }
+
+    /// Parses the token stream as a prefix (Polish) expression, with each
+    /// operator before its operands: `* + 5 3 2` is `(5 + 3) * 2`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ParserError`] under the same conditions as
+    /// [`parse`](Self::parse). Tokens are read from right to left, so when
+    /// several operators lack operands the rightmost is reported.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::lexer::Lexer;
+    /// use rpn2tex::notation::{Notation, NotationGenerator};
+    /// use rpn2tex::parser::Parser;
+    ///
+    /// let tokens = Lexer::new("* + 5 3 2").tokenize().unwrap();
+    /// let ast = Parser::new(tokens).parse_prefix().unwrap();
+    /// assert_eq!(NotationGenerator::new(Notation::Postfix).generate(&ast), "5 3 + 2 *");
+    /// ```
+    pub fn parse_prefix(&mut self) -> Result<Expr, ParserError> {
+        self.read_all();
+        let end = self
+            .tokens
+            .iter()
+            .skip(self.pos)
+            .position(|token| token.token_type == TokenType::Eof)
+            .map_or(self.tokens.len(), |offset| self.pos + offset);
+        let mut stack = Vec::new();
+        let max_depth = self.limits.max_depth();
+
+        for token in self.tokens.range(self.pos..end).rev() {
+            Self::push_token(
+                &mut stack,
+                token,
+                &self.operators,
+                Notation::Prefix,
+                max_depth,
+            )?;
+        }
+        self.pos = end;
+
+        Self::finish(&mut stack, self.current())
+    }
+
+    /// Parses the token stream as an infix expression: `*` and `/` bind
+    /// tighter than `+` and `-`, operators of equal precedence group from
+    /// the left, and parentheses group explicitly. The tokens should come
+    /// from a lexer [`with_infix`](crate::lexer::Lexer::with_infix) set.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ParserError`] if the expression is empty, an operand or
+    /// closing parenthesis is missing, two operands are not joined by an
+    /// operator, or the depth limit is exceeded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::lexer::Lexer;
+    /// use rpn2tex::notation::{Notation, NotationGenerator};
+    /// use rpn2tex::parser::Parser;
+    ///
+    /// let tokens = Lexer::new("(5 + 3) * 2 - 10/4").with_infix(true).tokenize().unwrap();
+    /// let ast = Parser::new(tokens).parse_infix().unwrap();
+    /// assert_eq!(
+    ///     NotationGenerator::new(Notation::Postfix).generate(&ast),
+    ///     "5 3 + 2 * 10 4 / -"
+    /// );
+    ///
+    /// let tokens = Lexer::new("5 3").with_infix(true).tokenize().unwrap();
+    /// let error = Parser::new(tokens).parse_infix().unwrap_err();
+    /// assert_eq!(error.message(), "Expected an operator");
+    /// ```
+    pub fn parse_infix(&mut self) -> Result<Expr, ParserError> {
+        self.read_all();
+        self.skip_trivia();
+        if self.at_end() {
+            return Err(ParserError::new("Empty expression", self.current().clone())
+                .with_code(ErrorCode::EmptyExpression));
+        }
+        let (ast, _) = self.infix_level(1, 0)?;
+        if !self.at_end() {
+            let (message, code) = match self.current().token_type {
+                TokenType::RightParen => ("Unmatched ')'", ErrorCode::Parenthesis),
+                _ => ("Expected an operator", ErrorCode::ExpectedOperator),
+            };
+            return Err(ParserError::new(message, self.current().clone()).with_code(code));
+        }
+        Ok(ast)
+    }
+
+    /// Parses the token stream, skipping operators that lack operands
+    /// instead of stopping at the first error.
+    ///
+    /// # Errors
+    ///
+    /// Returns a diagnostic for every skipped operator, followed by one for
+    /// an empty expression or missing operators, if there were any errors.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::lexer::Lexer;
+    /// use rpn2tex::parser::Parser;
+    ///
+    /// let tokens = Lexer::new("+ 5 * 3 4").tokenize().unwrap();
+    /// let diagnostics = Parser::new(tokens).parse_recovering().unwrap_err();
+    /// assert_eq!(diagnostics.len(), 3);
+    /// assert_eq!(diagnostics[2].message(), "Too many values on stack (missing operators)");
+    /// ```
+    pub fn parse_recovering(&mut self) -> Result<Expr, Vec<Diagnostic>> {
+        self.expand_macros().map_err(|error| vec![error.into()])?;
+        let mut stack = Vec::new();
+        let mut diagnostics = Vec::new();
+        let max_depth = self.limits.max_depth();
 
-            match token.token_type {
-                TokenType::Number => {
-                    // Push number onto stack
-                    let num = Expr::Number {
-                        line: token.line,
-                        column: token.column,
-                        value: token.value.clone(),
-                    };
-                    stack.push(num);
-                    self.advance();
+        while !self.at_end() {
+            let token = self.current();
+            let pushed = Self::push_token(
+                &mut stack,
+                token,
+                &self.operators,
+                Notation::Postfix,
+                max_depth,
+            );
+            if let Err(error) = pushed {
+                // Past a limit the rest of the input is not parsed
+                let over_limit = error.limit_exceeded().is_some();
+                diagnostics.push(error.into());
+                if over_limit {
+                    return Err(diagnostics);
                 }
-                TokenType::Plus | TokenType::Minus | TokenType::Mult | TokenType::Div => {
-                    // Check we have enough operands
-                    if stack.len() < 2 {
-                        return Err(ParserError::new(
-                            "Too few operands for operator",
-                            token.clone(),
-                        ));
-                    }
+            }
+            self.consume();
+        }
 
-                    // Pop right operand, then left operand
-                    let right = stack.pop().unwrap();
-                    let left = stack.pop().unwrap();
-
-                    // Get operator symbol
-                    let operator = match token.token_type {
-                        TokenType::Plus => "+",
-                        TokenType::Minus => "-",
-                        TokenType::Mult => "*",
-                        TokenType::Div => "/",
-                        _ => unreachable!(),
-                    };
-
-                    // Create binary operation node
-                    let binary_op = Expr::BinaryOp {
-                        line: token.line,
-                        column: token.column,
-                        operator: operator.to_string(),
-                        left: Box::new(left),
-                        right: Box::new(right),
-                    };
-                    stack.push(binary_op);
-                    self.advance();
+        match Self::finish(&mut stack, self.current()) {
+            Ok(ast) if diagnostics.is_empty() => Ok(ast),
+            Ok(_) => Err(diagnostics),
+            Err(error) => {
+                diagnostics.push(error.into());
+                Err(diagnostics)
+            }
+        }
+    }
+
+    /// Parses tokens as they are produced, without collecting them first.
+    ///
+    /// Parsing stops at the first EOF token or error, so the input is only
+    /// scanned as far as needed. Errors from `tokens` are converted into `E`,
+    /// which must also represent parser errors, such as
+    /// [`Rpn2TexError`](crate::error::Rpn2TexError).
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error yielded by `tokens`, or a [`ParserError`]
+    /// under the same conditions as [`parse`](Self::parse).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::error::Rpn2TexError;
+    /// use rpn2tex::lexer::Lexer;
+    /// use rpn2tex::parser::Parser;
+    ///
+    /// let tokens = Lexer::new("5 3 +").iter();
+    /// let ast = Parser::parse_stream::<_, _, Rpn2TexError>(tokens).unwrap();
+    /// assert_eq!(ast.line(), 1);
+    ///
+    /// let error = Parser::parse_stream::<_, _, Rpn2TexError>(Lexer::new("5 +").iter());
+    /// assert_eq!(error.unwrap_err().message(), "Too few operands for operator");
+    /// ```
+    pub fn parse_stream<I, L, E>(tokens: I) -> Result<Expr, E>
+    where
+        I: IntoIterator<Item = Result<Token<'a>, L>>,
+        E: From<L> + From<ParserError>,
+    {
+        // The stream ends at its first error, which outranks whatever the
+        // parse then makes of the tokens before it
+        let mut error = None;
+        let tokens = tokens
+            .into_iter()
+            .map_while(|token| token.map_err(|e| error = Some(e)).ok());
+        let ast = Parser::new(tokens).parse();
+        match error {
+            Some(error) => Err(error.into()),
+            None => Ok(ast?),
+        }
+    }
+
+    /// Applies one non-EOF token to the operand stack.
+    ///
+    /// Postfix tokens are read left to right, so an operator's right operand
+    /// is on top of the stack; prefix tokens are read right to left, so its
+    /// left operand is. Each stack entry holds the depth of its tree, so
+    /// operators deeper than `max_depth` are rejected.
+    fn push_token(
+        stack: &mut Vec<(Expr, usize)>,
+        token: &Token,
+        operators: &OperatorTable,
+        notation: Notation,
+        max_depth: Option<usize>,
+    ) -> Result<(), ParserError> {
+        match token.token_type {
+            TokenType::Number => {
+                // Push number onto stack
+                let number = Expr::Number {
+                    span: token.span,
+                    value: token.value.to_string(),
+                };
+                stack.push((number, 1));
+            }
+            TokenType::Identifier => {
+                let variable = Expr::Variable {
+                    span: token.span,
+                    name: token.value.to_string(),
+                };
+                stack.push((variable, 1));
+            }
+            TokenType::Plus | TokenType::Minus | TokenType::Mult | TokenType::Div => {
+                let (operands, depth) = Self::pop_operands(stack, 2, token, notation, max_depth)?;
+                let [left, right]: [Expr; 2] = operands.try_into().unwrap();
+
+                // Get operator symbol
+                let operator = match token.token_type {
+                    TokenType::Plus => "+",
+                    TokenType::Minus => "-",
+                    TokenType::Mult => "*",
+                    TokenType::Div => "/",
+                    _ => unreachable!(),
+                };
+
+                // Create binary operation node
+                let operation = Expr::BinaryOp {
+                    span: token.span,
+                    operator: operator.to_string(),
+                    left: Box::new(left),
+                    right: Box::new(right),
+                };
+                stack.push((operation, depth));
+            }
+            TokenType::Operator => {
+                let Some(plugin) = operators.get(&token.value) else {
+                    return Err(ParserError::new(
+                        format!("Unknown operator '{}'", token.value),
+                        token.clone(),
+                    )
+                    .with_code(ErrorCode::UnknownOperator));
+                };
+                let (operands, depth) =
+                    Self::pop_operands(stack, plugin.arity(), token, notation, max_depth)?;
+                let application = Expr::Apply {
+                    span: token.span,
+                    operator: token.value.to_string(),
+                    operands,
+                };
+                stack.push((application, depth));
+            }
+            TokenType::LeftParen | TokenType::RightParen => {
+                return Err(ParserError::new(
+                    "Parentheses are only read in infix input",
+                    token.clone(),
+                )
+                .with_code(ErrorCode::Parenthesis));
+            }
+            TokenType::Whitespace | TokenType::Comment | TokenType::Eof => {}
+        }
+        Ok(())
+    }
+
+    /// Takes the `count` operands of `token`'s operator off the stack, in
+    /// the order they are written in infix, with the depth of the tree the
+    /// operator will build.
+    fn pop_operands(
+        stack: &mut Vec<(Expr, usize)>,
+        count: usize,
+        token: &Token,
+        notation: Notation,
+        max_depth: Option<usize>,
+    ) -> Result<(Vec<Expr>, usize), ParserError> {
+        // Check we have enough operands
+        if stack.len() < count {
+            return Err(
+                ParserError::new("Too few operands for operator", token.clone())
+                    .with_code(ErrorCode::TooFewOperands),
+            );
+        }
+
+        let first = stack.len() - count;
+        let depth = 1 + stack[first..]
+            .iter()
+            .map(|&(_, depth)| depth)
+            .max()
+            .unwrap();
+        if let Some(max) = max_depth.filter(|&max| depth > max) {
+            return Err(ParserError::from_limit(
+                LimitExceeded::Depth(max),
+                token.clone(),
+            ));
+        }
+
+        let mut operands: Vec<Expr> = stack.drain(first..).map(|(expr, _)| expr).collect();
+        if notation == Notation::Prefix {
+            operands.reverse();
+        }
+        Ok((operands, depth))
+    }
+
+    /// Parses the operations of one precedence level of infix input, sums
+    /// at level 1 and products at level 2, grouping them from the left.
+    /// `parens` is the number of parentheses open.
+    fn infix_level(&mut self, level: u8, parens: usize) -> Result<(Expr, usize), ParserError> {
+        let operand = |parser: &mut Self| match level {
+            1 => parser.infix_level(2, parens),
+            _ => parser.infix_operand(parens),
+        };
+        let max_depth = self.limits.max_depth();
+
+        let (mut left, mut left_depth) = operand(self)?;
+        loop {
+            let token = self.current().clone();
+            let operator = match (level, token.token_type) {
+                (1, TokenType::Plus) => "+",
+                (1, TokenType::Minus) => "-",
+                (2, TokenType::Mult) => "*",
+                (2, TokenType::Div) => "/",
+                _ => return Ok((left, left_depth)),
+            };
+            self.advance();
+            self.skip_trivia();
+            let (right, right_depth) = operand(self)?;
+
+            let depth = 1 + left_depth.max(right_depth);
+            if let Some(max) = max_depth.filter(|&max| depth > max) {
+                return Err(ParserError::from_limit(LimitExceeded::Depth(max), token));
+            }
+            left = Expr::BinaryOp {
+                span: token.span,
+                operator: operator.to_string(),
+                left: Box::new(left),
+                right: Box::new(right),
+            };
+            left_depth = depth;
+        }
+    }
+
+    /// Parses one operand of infix input: a number, a variable or a
+    /// parenthesized expression.
+    fn infix_operand(&mut self, parens: usize) -> Result<(Expr, usize), ParserError> {
+        let token = self.current().clone();
+        let operand = match token.token_type {
+            TokenType::Number => {
+                let number = Expr::Number {
+                    span: token.span,
+                    value: token.value.to_string(),
+                };
+                (number, 1)
+            }
+            TokenType::Identifier => {
+                let variable = Expr::Variable {
+                    span: token.span,
+                    name: token.value.to_string(),
+                };
+                (variable, 1)
+            }
+            TokenType::LeftParen => {
+                // Parentheses nest no deeper than the tree may, so deep
+                // input cannot exhaust the stack
+                if let Some(max) = self.limits.max_depth().filter(|&max| parens >= max) {
+                    return Err(ParserError::from_limit(LimitExceeded::Depth(max), token));
                 }
-                TokenType::Eof => {
-                    break;
+                self.advance();
+                self.skip_trivia();
+                let inner = self.infix_level(1, parens + 1)?;
+                if self.current().token_type != TokenType::RightParen {
+                    return Err(ParserError::new("Expected ')'", self.current().clone())
+                        .with_code(ErrorCode::Parenthesis));
                 }
+                inner
             }
+            _ => {
+                return Err(ParserError::new("Expected an operand", token)
+                    .with_code(ErrorCode::ExpectedOperand))
+            }
+        };
+        self.advance();
+        self.skip_trivia();
+        Ok(operand)
+    }
+
+    /// Moves past whitespace and comment tokens.
+    fn skip_trivia(&mut self) {
+        self.read_all();
+        while self.pos < self.tokens.len() && self.current().token_type.is_trivia() {
+            self.pos += 1;
         }
+    }
+
+    /// Expands the macros in the remaining tokens, once.
+    fn expand_macros(&mut self) -> Result<(), ParserError> {
+        let Some(macros) = self.macros.take() else {
+            return Ok(());
+        };
+        self.read_all();
+        let tokens = self.tokens.split_off(self.pos);
+        self.tokens.extend(macros.expand_with(
+            Vec::from(tokens),
+            &self.operators,
+            self.limits.max_tokens(),
+        )?);
+        Ok(())
+    }
 
-        // Validate final stack state
+    /// Validates the final stack state, reporting errors at the EOF token.
+    ///
+    /// On success the root expression is taken off the stack; on error the
+    /// stack is left as it was.
+    fn finish(stack: &mut Vec<(Expr, usize)>, eof_token: &Token) -> Result<Expr, ParserError> {
         if stack.is_empty() {
             // No expression at all
-            let eof_token = self.current();
-            return Err(ParserError::new("Empty expression", eof_token.clone()));
+            return Err(ParserError::new("Empty expression", eof_token.clone())
+                .with_code(ErrorCode::EmptyExpression));
         }
 
         if stack.len() > 1 {
             // Too many values on stack - missing operators
-            // Report error at the EOF token
-            let eof_token = self.current();
             return Err(ParserError::new(
                 "Too many values on stack (missing operators)",
                 eof_token.clone(),
-            ));
+            )
+            .with_code(ErrorCode::MissingOperator));
         }
 
         // Return the single expression on the stack
-        Ok(stack.pop().unwrap())
+        Ok(stack.pop().unwrap().0)
     }
 
     /// Returns the current token without advancing.
@@ -304,22 +1111,65 @@ impl Parser {
     ///
     /// Panics if called when position is beyond the token list.
     /// Use `at_end()` to check before calling.
-    fn current(&self) -> &Token {
+    fn current(&self) -> &Token<'a> {
         &self.tokens[self.pos]
     }
 
-    /// Checks if the parser is at the end of the token stream.
+    /// Checks if the parser is at the end of the token stream, reading the
+    /// current token from the source if needed.
     ///
     /// Returns true if the current token is EOF or position is at/beyond
     /// the end of the token list.
-    fn at_end(&self) -> bool {
+    fn at_end(&mut self) -> bool {
+        if self.pos == self.tokens.len() {
+            self.read_next();
+        }
         self.pos >= self.tokens.len() || self.current().token_type == TokenType::Eof
     }
 
+    /// Reads one more token from the source into the token list.
+    fn read_next(&mut self) {
+        match self.source.as_mut().and_then(Iterator::next) {
+            Some(token) => self.tokens.push_back(token),
+            None => self.end_source(),
+        }
+    }
+
+    /// Reads every remaining token from the source into the token list.
+    fn read_all(&mut self) {
+        if let Some(source) = self.source.as_mut() {
+            self.tokens.extend(source);
+            self.end_source();
+        }
+    }
+
+    /// Drops the exhausted source, ending the token list with an EOF token
+    /// if it has none.
+    fn end_source(&mut self) {
+        if self.source.take().is_none() {
+            return;
+        }
+        let last = self.tokens.back();
+        if last.is_none_or(|token| token.token_type != TokenType::Eof) {
+            let span = last.map_or(Span::new(0, 0, 1, 1), |token| token.span);
+            self.tokens.push_back(Token::new(TokenType::Eof, "", span));
+        }
+    }
+
+    /// Consumes the current token of a postfix parse, dropping those before
+    /// it: postfix input is never looked at again once applied. The current
+    /// token is kept until the next, so a stream without an EOF token can
+    /// still end at its last token.
+    fn consume(&mut self) {
+        self.tokens.drain(..self.pos);
+        self.pos = 0;
+        self.advance();
+    }
+
     /// Consumes the current token and advances to the next one.
     ///
     /// Returns a reference to the token that was current before advancing.
-    fn advance(&mut self) -> &Token {
+    fn advance(&mut self) -> &Token<'a> {
         let current_pos = self.pos;
         if self.pos < self.tokens.len() {
             self.pos += 1;
@@ -328,21 +1178,39 @@ impl Parser {
     }
 }
 
+/// Returns how many levels deep `expr` is, counting a leaf as 1.
+fn tree_depth(expr: &Expr) -> usize {
+    match expr {
+        Expr::Number { .. } | Expr::Variable { .. } => 1,
+        Expr::BinaryOp { left, right, .. } => 1 + tree_depth(left).max(tree_depth(right)),
+        Expr::Apply { operands, .. } => 1 + operands.iter().map(tree_depth).max().unwrap_or(0),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::lexer::Lexer;
+    use crate::notation::NotationGenerator;
+
+    // Span of `value` at `line` and `col`, with offsets as if every line
+    // were the first
+    fn span(value: &str, line: u32, col: u32) -> Span {
+        let start = col as usize - 1;
+        Span::new(start, start + value.len(), line, col)
+    }
 
     // Helper function to create a simple token
-    fn num(value: &str, line: u32, col: u32) -> Token {
-        Token::new(TokenType::Number, value.to_string(), line, col)
+    fn num(value: &str, line: u32, col: u32) -> Token<'_> {
+        Token::new(TokenType::Number, value, span(value, line, col))
     }
 
-    fn op(op_type: TokenType, value: &str, line: u32, col: u32) -> Token {
-        Token::new(op_type, value.to_string(), line, col)
+    fn op(op_type: TokenType, value: &str, line: u32, col: u32) -> Token<'_> {
+        Token::new(op_type, value, span(value, line, col))
     }
 
-    fn eof(line: u32, col: u32) -> Token {
-        Token::new(TokenType::Eof, String::new(), line, col)
+    fn eof(line: u32, col: u32) -> Token<'static> {
+        Token::new(TokenType::Eof, "", span("", line, col))
     }
 
     #[test]
@@ -357,6 +1225,28 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_variable() {
+        let tokens = vec![
+            op(TokenType::Identifier, "x", 1, 1),
+            num("2", 1, 3),
+            op(TokenType::Mult, "*", 1, 5),
+            eof(1, 6),
+        ];
+        let ast = Parser::new(tokens).parse().expect("parse failed");
+
+        match ast {
+            Expr::BinaryOp { left, .. } => assert_eq!(
+                *left,
+                Expr::Variable {
+                    span: Span::new(0, 1, 1, 1),
+                    name: "x".to_string(),
+                }
+            ),
+            _ => panic!("Expected BinaryOp"),
+        }
+    }
+
     #[test]
     fn test_parse_simple_addition() {
         // "5 3 +"
@@ -597,6 +1487,241 @@ mod tests {
         assert_eq!(err.token().token_type, TokenType::Plus);
     }
 
+    #[test]
+    fn test_parse_prefix() {
+        // "* + 5 3 2" is (5 + 3) * 2
+        let tokens = vec![
+            op(TokenType::Mult, "*", 1, 1),
+            op(TokenType::Plus, "+", 1, 3),
+            num("5", 1, 5),
+            num("3", 1, 7),
+            num("2", 1, 9),
+            eof(1, 10),
+        ];
+        let ast = Parser::new(tokens).parse_prefix().expect("parse failed");
+        let Expr::BinaryOp {
+            operator,
+            left,
+            right,
+            span,
+        } = ast
+        else {
+            panic!("Expected BinaryOp");
+        };
+        assert_eq!(operator, "*");
+        assert_eq!(span.column, 1);
+        assert!(matches!(*right, Expr::Number { ref value, .. } if value == "2"));
+        let Expr::BinaryOp { left, right, .. } = *left else {
+            panic!("Expected BinaryOp");
+        };
+        assert!(matches!(*left, Expr::Number { ref value, .. } if value == "5"));
+        assert!(matches!(*right, Expr::Number { ref value, .. } if value == "3"));
+    }
+
+    #[test]
+    fn test_parse_prefix_keeps_operand_order() {
+        // "- 10 4" is 10 - 4, not 4 - 10
+        let tokens = vec![
+            op(TokenType::Minus, "-", 1, 1),
+            num("10", 1, 3),
+            num("4", 1, 6),
+            eof(1, 7),
+        ];
+        let ast = Parser::new(tokens).parse_prefix().expect("parse failed");
+        let Expr::BinaryOp { left, .. } = ast else {
+            panic!("Expected BinaryOp");
+        };
+        assert!(matches!(*left, Expr::Number { ref value, .. } if value == "10"));
+    }
+
+    #[test]
+    fn test_parse_prefix_errors() {
+        let tokens = vec![op(TokenType::Plus, "+", 1, 1), num("5", 1, 3), eof(1, 4)];
+        let err = Parser::new(tokens).parse_prefix().unwrap_err();
+        assert_eq!(err.message(), "Too few operands for operator");
+        assert_eq!(err.token().span.column, 1);
+
+        // RPN order read as prefix leaves two values
+        let tokens = vec![
+            num("5", 1, 1),
+            num("3", 1, 3),
+            op(TokenType::Plus, "+", 1, 5),
+            eof(1, 6),
+        ];
+        let err = Parser::new(tokens).parse_prefix().unwrap_err();
+        assert_eq!(err.message(), "Too few operands for operator");
+
+        let err = Parser::new(vec![eof(1, 1)]).parse_prefix().unwrap_err();
+        assert_eq!(err.message(), "Empty expression");
+    }
+
+    fn parse_infix(input: &str) -> Result<String, ParserError> {
+        let tokens = Lexer::new(input)
+            .with_identifiers(true)
+            .with_infix(true)
+            .tokenize()
+            .expect("tokenize failed");
+        let ast = Parser::new(tokens).parse_infix()?;
+        Ok(NotationGenerator::new(Notation::Postfix).generate(&ast))
+    }
+
+    #[test]
+    fn test_parse_infix() {
+        assert_eq!(parse_infix("1 + 2 * 3").unwrap(), "1 2 3 * +");
+        assert_eq!(parse_infix("10 - 4 - 3").unwrap(), "10 4 - 3 -");
+        assert_eq!(parse_infix("10 - (4 - 3)").unwrap(), "10 4 3 - -");
+        assert_eq!(parse_infix("x/2/y").unwrap(), "x 2 / y /");
+        assert_eq!(parse_infix("((7)) * -2 # note").unwrap(), "7 -2 *");
+        assert_eq!(parse_infix("3-2").unwrap(), "3 2 -");
+    }
+
+    #[test]
+    fn test_parse_infix_errors() {
+        let error = |input| {
+            let err = parse_infix(input).unwrap_err();
+            (err.message().to_string(), err.token().span.column)
+        };
+        assert_eq!(error(""), ("Empty expression".to_string(), 1));
+        assert_eq!(error("5 +"), ("Expected an operand".to_string(), 4));
+        assert_eq!(error("5 3"), ("Expected an operator".to_string(), 3));
+        assert_eq!(error("(5 + 3"), ("Expected ')'".to_string(), 7));
+        assert_eq!(error("5 + 3)"), ("Unmatched ')'".to_string(), 6));
+        assert_eq!(error("* 5"), ("Expected an operand".to_string(), 1));
+
+        // Parentheses count toward the depth limit
+        let tokens = Lexer::new("((1))").with_infix(true).tokenize().unwrap();
+        let err = Parser::new(tokens)
+            .with_limits(Limits::new().with_max_depth(1))
+            .parse_infix()
+            .unwrap_err();
+        assert_eq!(err.limit_exceeded(), Some(LimitExceeded::Depth(1)));
+        assert_eq!(err.token().span.column, 2);
+    }
+
+    #[test]
+    fn test_depth_limit() {
+        // "1 2 3 + *" is 1 * (2 + 3), three levels deep
+        let tokens = || {
+            vec![
+                num("1", 1, 1),
+                num("2", 1, 3),
+                num("3", 1, 5),
+                op(TokenType::Plus, "+", 1, 7),
+                op(TokenType::Mult, "*", 1, 9),
+                eof(1, 10),
+            ]
+        };
+        let limited =
+            |depth| Parser::new(tokens()).with_limits(Limits::new().with_max_depth(depth));
+        assert!(limited(3).parse().is_ok());
+
+        let err = limited(2).parse().unwrap_err();
+        assert_eq!(err.limit_exceeded(), Some(LimitExceeded::Depth(2)));
+        assert_eq!(err.token().span.column, 9);
+
+        let err = limited(1).parse().unwrap_err();
+        assert_eq!(err.token().span.column, 7);
+
+        // Syntax errors are not limit errors
+        let err = Parser::new(vec![eof(1, 1)]).parse().unwrap_err();
+        assert_eq!(err.limit_exceeded(), None);
+    }
+
+    #[test]
+    fn test_depth_limit_prefix() {
+        // "+ + 1 2 3" is (1 + 2) + 3
+        let tokens = vec![
+            op(TokenType::Plus, "+", 1, 1),
+            op(TokenType::Plus, "+", 1, 3),
+            num("1", 1, 5),
+            num("2", 1, 7),
+            num("3", 1, 9),
+            eof(1, 10),
+        ];
+        let err = Parser::new(tokens)
+            .with_limits(Limits::new().with_max_depth(2))
+            .parse_prefix()
+            .unwrap_err();
+        assert_eq!(err.token().span.column, 1);
+    }
+
+    #[test]
+    fn test_recovering_stops_at_depth_limit() {
+        let tokens = vec![
+            num("1", 1, 1),
+            num("2", 1, 3),
+            op(TokenType::Plus, "+", 1, 5),
+            num("3", 1, 7),
+            op(TokenType::Plus, "+", 1, 9),
+            op(TokenType::Plus, "+", 1, 11),
+            eof(1, 12),
+        ];
+        let diagnostics = Parser::new(tokens)
+            .with_limits(Limits::new().with_max_depth(2))
+            .parse_recovering()
+            .unwrap_err();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].column(), 9);
+    }
+
+    #[test]
+    fn test_parse_partial_too_few_operands() {
+        // "5 3 + 2 * +": the last operator finds only one operand
+        let tokens = vec![
+            num("5", 1, 1),
+            num("3", 1, 3),
+            op(TokenType::Plus, "+", 1, 5),
+            num("2", 1, 7),
+            op(TokenType::Mult, "*", 1, 9),
+            op(TokenType::Plus, "+", 1, 11),
+            eof(1, 12),
+        ];
+        let partial = Parser::new(tokens).parse_partial().unwrap_err();
+        assert_eq!(partial.error().message(), "Too few operands for operator");
+        assert_eq!(partial.to_string(), partial.error().to_string());
+        let stack = partial.into_stack();
+        assert_eq!(stack.len(), 1);
+        assert!(matches!(stack[0], Expr::BinaryOp { ref operator, .. } if operator == "*"));
+    }
+
+    #[test]
+    fn test_parse_partial_leftover_values() {
+        let tokens = vec![num("5", 1, 1), num("3", 1, 3), eof(1, 4)];
+        let partial = Parser::new(tokens).parse_partial().unwrap_err();
+        let values: Vec<u32> = partial.stack().iter().map(Expr::column).collect();
+        assert_eq!(values, [1, 3]);
+        assert!(partial.into_error().message().contains("Too many values"));
+
+        let partial = Parser::new(vec![eof(1, 1)]).parse_partial().unwrap_err();
+        assert!(partial.stack().is_empty());
+    }
+
+    #[test]
+    fn test_parse_all_leftover_values() {
+        let tokens = vec![
+            num("5", 1, 1),
+            num("3", 1, 3),
+            op(TokenType::Plus, "+", 1, 5),
+            num("2", 1, 7),
+            eof(1, 8),
+        ];
+        let exprs = Parser::new(tokens).parse_all().unwrap();
+        let columns: Vec<u32> = exprs.iter().map(Expr::column).collect();
+        assert_eq!(columns, [5, 7]);
+
+        let error = Parser::new(vec![eof(1, 1)]).parse_all().unwrap_err();
+        assert_eq!(error.message(), "Empty expression");
+        let tokens = vec![num("5", 1, 1), op(TokenType::Plus, "+", 1, 3), eof(1, 4)];
+        let error = Parser::new(tokens).parse_all().unwrap_err();
+        assert_eq!(error.message(), "Too few operands for operator");
+    }
+
+    #[test]
+    fn test_parse_partial_success() {
+        let tokens = vec![num("5", 1, 1), eof(1, 2)];
+        assert!(Parser::new(tokens).parse_partial().is_ok());
+    }
+
     #[test]
     fn test_parse_error_missing_operator() {
         // "5 3" - missing operator
@@ -623,7 +1748,7 @@ mod tests {
 
     #[test]
     fn test_parser_error_display() {
-        let token = Token::new(TokenType::Plus, "+".to_string(), 2, 5);
+        let token = op(TokenType::Plus, "+", 2, 5);
         let error = ParserError::new("Test error", token);
         let display = format!("{}", error);
 
@@ -633,13 +1758,26 @@ mod tests {
 
     #[test]
     fn test_parser_error_accessors() {
-        let token = Token::new(TokenType::Minus, "-".to_string(), 3, 7);
+        let token = op(TokenType::Minus, "-", 3, 7);
         let error = ParserError::new("Access test", token.clone());
 
         assert_eq!(error.message(), "Access test");
         assert_eq!(error.token().token_type, TokenType::Minus);
-        assert_eq!(error.token().line, 3);
-        assert_eq!(error.token().column, 7);
+        assert_eq!(error.token().span, span("-", 3, 7));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_parser_error_json_round_trip() {
+        let token = Token::new(TokenType::Plus, "+", Span::new(2, 3, 1, 3));
+        let error = ParserError::new("Too few operands for operator", token);
+        let json = serde_json::to_string(&error).expect("serialize failed");
+
+        assert!(json.contains(r#""message":"Too few operands for operator""#));
+        assert!(json.contains(r#""token_type":"Plus""#));
+
+        let back: ParserError = serde_json::from_str(&json).expect("deserialize failed");
+        assert_eq!(back, error);
     }
 
     #[test]
@@ -734,6 +1872,42 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_stream_matches_parse() {
+        let tokens = vec![
+            num("5", 1, 1),
+            num("3", 1, 3),
+            op(TokenType::Plus, "+", 1, 5),
+            eof(1, 6),
+        ];
+        let expected = Parser::new(tokens.clone()).parse();
+        let streamed =
+            Parser::parse_stream::<_, ParserError, ParserError>(tokens.into_iter().map(Ok));
+        assert_eq!(streamed, expected);
+    }
+
+    #[test]
+    fn test_parse_stream_stops_at_first_error() {
+        let tokens = vec![
+            Ok(num("5", 1, 1)),
+            Err(ParserError::new("Bad token", num("?", 1, 3))),
+            Ok(eof(1, 4)),
+        ];
+        let error = Parser::parse_stream::<_, _, ParserError>(tokens).unwrap_err();
+        assert_eq!(error.message(), "Bad token");
+    }
+
+    #[test]
+    fn test_parse_stream_without_eof() {
+        let tokens = vec![Ok(num("5", 1, 1)), Ok(num("3", 1, 3))];
+        let error = Parser::parse_stream::<_, ParserError, ParserError>(tokens).unwrap_err();
+        assert_eq!(
+            error.message(),
+            "Too many values on stack (missing operators)"
+        );
+        assert_eq!((error.token().span.line, error.token().span.column), (1, 3));
+    }
+
     #[test]
     fn test_parser_new() {
         let tokens = vec![eof(1, 1)];
@@ -741,6 +1915,51 @@ mod tests {
         assert_eq!(parser.pos, 0);
     }
 
+    #[test]
+    fn test_parse_reads_tokens_lazily() {
+        let read = std::cell::Cell::new(0);
+        let tokens = vec![num("5", 1, 1), num("3", 1, 3), eof(1, 4), num("9", 1, 6)];
+        let counted = tokens.into_iter().inspect(|_| read.set(read.get() + 1));
+        let error = Parser::new(counted).parse().unwrap_err();
+        assert_eq!(
+            error.message(),
+            "Too many values on stack (missing operators)"
+        );
+        // Nothing past the EOF token is read
+        assert_eq!(read.get(), 3);
+
+        // Applied tokens are dropped, but for the last one and the EOF token
+        let tokens = (1..=1000)
+            .map(|_| num("1", 1, 1))
+            .chain((1..1000).map(|_| op(TokenType::Plus, "+", 1, 1)));
+        let mut parser = Parser::new(tokens);
+        assert!(parser.parse().is_ok());
+        assert_eq!(parser.tokens.len(), 2);
+
+        let tokens = vec![num("5", 1, 1), op(TokenType::Plus, "+", 1, 3)];
+        let error = Parser::new(
+            tokens
+                .into_iter()
+                .chain(std::iter::from_fn(|| panic!("read past the error"))),
+        )
+        .parse()
+        .unwrap_err();
+        assert_eq!(error.message(), "Too few operands for operator");
+    }
+
+    #[test]
+    fn test_parse_without_eof() {
+        let error = Parser::new(vec![num("5", 1, 1), num("3", 1, 3)])
+            .parse()
+            .unwrap_err();
+        assert_eq!((error.token().span.line, error.token().span.column), (1, 3));
+        assert_eq!(
+            Parser::new(Vec::new()).parse().unwrap_err().message(),
+            "Empty expression"
+        );
+        assert!(Parser::new(vec![num("5", 1, 1)]).parse_prefix().is_ok());
+    }
+
     #[test]
     fn test_deeply_nested_expression() {
         // "1 2 + 3 + 4 +" -> ((1 + 2) + 3) + 4
@@ -0,0 +1,851 @@
+//! Whole-input conversion in one call.
+//!
+//! [`convert`] runs the lexer, the parser and a generator over one
+//! expression, [`compile`] does so with the default LaTeX generator, and
+//! [`convert_file`] first reads the expression from a file. Whichever stage
+//! fails, the error is an [`Rpn2TexError`].
+//!
+//! A [`Compiler`] holds the settings of every stage in one place, from the
+//! input extensions the lexer accepts to the output [`Format`], so callers
+//! need not build a lexer, parser and generator themselves. Its hooks run
+//! caller code between the stages, to inspect or rewrite the tokens after
+//! lexing and the AST after parsing and before generating. Custom operators
+//! registered with [`Compiler::operator`] are read by the lexer and parser
+//! and written by the LaTeX generator. The command-line tool converts
+//! through a `Compiler` too, so the two cannot read input differently.
+//!
+//! With the `tracing` feature, every stage runs in a span recording its
+//! duration, as described in the `trace` module.
+
+use crate::ast::Expr;
+use crate::batch::Segment;
+use crate::codegen::CodeGenerator;
+use crate::context::ConTeXtGenerator;
+use crate::encoding::decode;
+use crate::error::Rpn2TexError;
+use crate::infix::InfixGenerator;
+use crate::latex::{GeneratorConfig, LaTeXGenerator, MathEnvironment, MulStyle, SpacingStyle};
+use crate::lexer::Lexer;
+use crate::limits::Limits;
+use crate::macros::MacroTable;
+use crate::markdown::{MarkdownFlavor, MarkdownGenerator};
+use crate::mathml::ContentMathMLGenerator;
+use crate::notation::{Notation, NotationGenerator};
+use crate::parser::{Parser, ParserError};
+use crate::plugin::{OperatorPlugin, OperatorTable};
+use crate::python::PythonGenerator;
+use crate::tokens::Token;
+use crate::trace::stage;
+use crate::wolfram::{WolframGenerator, WolframStyle};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+/// A hook run on the tokens of an expression, ending with the EOF token,
+/// before they are parsed.
+type TokenHook = dyn for<'a> Fn(Vec<Token<'a>>) -> Vec<Token<'a>> + Send + Sync;
+
+/// A hook run on the AST of an expression.
+type AstHook = dyn Fn(Expr) -> Expr + Send + Sync;
+
+/// The hooks registered with a [`Compiler`], each list in the order they
+/// run.
+#[derive(Clone, Default)]
+struct Hooks {
+    /// Run on the tokens before parsing
+    after_lex: Vec<Arc<TokenHook>>,
+    /// Run on the AST as parsed, before folding
+    after_parse: Vec<Arc<AstHook>>,
+    /// Run on the AST about to be generated, after folding
+    before_generate: Vec<Arc<AstHook>>,
+}
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Hooks")
+            .field("after_lex", &self.after_lex.len())
+            .field("after_parse", &self.after_parse.len())
+            .field("before_generate", &self.before_generate.len())
+            .finish()
+    }
+}
+
+/// Hooks are equal when they are the same closures, as in a cloned
+/// compiler.
+impl PartialEq for Hooks {
+    fn eq(&self, other: &Self) -> bool {
+        fn same<T: ?Sized>(left: &[Arc<T>], right: &[Arc<T>]) -> bool {
+            left.len() == right.len() && left.iter().zip(right).all(|(l, r)| Arc::ptr_eq(l, r))
+        }
+        same(&self.after_lex, &other.after_lex)
+            && same(&self.after_parse, &other.after_parse)
+            && same(&self.before_generate, &other.before_generate)
+    }
+}
+
+impl Eq for Hooks {}
+
+/// The notation a [`Compiler`] reads its input in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum InputNotation {
+    /// Reverse Polish (postfix) notation (the default)
+    #[default]
+    Rpn,
+    /// Polish (prefix) notation
+    Prefix,
+    /// Infix notation, with the usual precedence and parentheses
+    Infix,
+}
+
+/// The notation a [`Compiler`] writes its output in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Format {
+    /// LaTeX math, configured by the compiler's LaTeX options (the default)
+    #[default]
+    Latex,
+    /// Polish (prefix) notation
+    Prefix,
+    /// Reverse Polish (postfix) notation, normalized
+    Rpn,
+    /// Infix notation with minimal parentheses
+    Infix,
+    /// Content MathML markup
+    ContentMathml,
+    /// Wolfram Language `FullForm` expression
+    Wolfram,
+    /// Wolfram Language infix expression
+    WolframInfix,
+    /// Evaluable Python expression
+    Python,
+    /// ConTeXt inline math
+    Context,
+    /// Markdown math snippet, delimited for the flavor
+    Markdown(MarkdownFlavor),
+}
+
+/// Converts RPN expressions with every stage configured in one place.
+///
+/// Settings are chained from [`Compiler::new`]; each returns the updated
+/// compiler. The LaTeX options are those of [`GeneratorConfig`], set whole
+/// with [`latex`](Self::latex) or one at a time with the shorthands here.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::latex::MulStyle;
+/// use rpn2tex::pipeline::{Compiler, Format};
+///
+/// let compiler = Compiler::new()
+///     .format(Format::Latex)
+///     .frac(true)
+///     .mul_style(MulStyle::Cdot);
+/// assert_eq!(compiler.compile("5 3 + 2 /").unwrap(), r"$\frac{5 + 3}{2}$");
+///
+/// let python = Compiler::new().format(Format::Python).variables(true);
+/// assert_eq!(python.compile("x 3 *").unwrap(), "x * 3");
+/// ```
+///
+/// Hooks rewrite what passes between the stages:
+///
+/// ```
+/// use rpn2tex::ast::Expr;
+/// use rpn2tex::pipeline::Compiler;
+///
+/// // Write every variable in upper case
+/// fn shout(expr: Expr) -> Expr {
+///     match expr {
+///         Expr::Variable { span, name } => Expr::Variable {
+///             span,
+///             name: name.to_uppercase(),
+///         },
+///         Expr::BinaryOp { span, operator, left, right } => Expr::BinaryOp {
+///             span,
+///             operator,
+///             left: Box::new(shout(*left)),
+///             right: Box::new(shout(*right)),
+///         },
+///         number => number,
+///     }
+/// }
+///
+/// let compiler = Compiler::new().variables(true).after_parse(shout);
+/// assert_eq!(compiler.compile("x y +").unwrap(), "$X + Y$");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Compiler {
+    /// Notation the input is read in
+    input: InputNotation,
+    /// Notation the output is written in
+    format: Format,
+    /// Options of LaTeX output
+    latex: GeneratorConfig,
+    /// Accept variable names in the input
+    variables: bool,
+    /// Read digits of other scripts, such as `١٢`, as ASCII digits
+    unicode_digits: bool,
+    /// Accept `,` between groups of three digits, such as `1,000`
+    comma_groups: bool,
+    /// Read malformed literals such as `5.` as numbers
+    lenient_numbers: bool,
+    /// Caps on input size, token count and expression depth
+    limits: Limits,
+    /// Macros the input may use
+    macros: MacroTable,
+    /// Fold numeric subexpressions before generating output
+    #[cfg(feature = "exact")]
+    fold_constants: bool,
+    /// Custom operators the input may use
+    operators: OperatorTable,
+    /// Caller code run between the stages
+    hooks: Hooks,
+}
+
+impl Compiler {
+    /// Creates a compiler writing LaTeX with the default settings.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads input in `input` notation.
+    #[must_use]
+    pub fn input(mut self, input: InputNotation) -> Self {
+        self.input = input;
+        self
+    }
+
+    /// Writes output in `format`.
+    #[must_use]
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Sets every option of LaTeX output at once.
+    #[must_use]
+    pub fn latex(mut self, latex: GeneratorConfig) -> Self {
+        self.latex = latex;
+        self
+    }
+
+    /// Wraps LaTeX output in `environment`; see
+    /// [`GeneratorConfig::environment`].
+    #[must_use]
+    pub fn environment(mut self, environment: MathEnvironment) -> Self {
+        self.latex = self.latex.environment(environment);
+        self
+    }
+
+    /// Lays out LaTeX whitespace in `spacing`; see
+    /// [`GeneratorConfig::spacing`].
+    #[must_use]
+    pub fn spacing(mut self, spacing: SpacingStyle) -> Self {
+        self.latex = self.latex.spacing(spacing);
+        self
+    }
+
+    /// Writes LaTeX multiplication with `mul_style`; see
+    /// [`GeneratorConfig::mul_style`].
+    #[must_use]
+    pub fn mul_style(mut self, mul_style: MulStyle) -> Self {
+        self.latex = self.latex.mul_style(mul_style);
+        self
+    }
+
+    /// Writes LaTeX division as `\frac{...}{...}`; see
+    /// [`GeneratorConfig::frac`].
+    #[must_use]
+    pub fn frac(mut self, frac: bool) -> Self {
+        self.latex = self.latex.frac(frac);
+        self
+    }
+
+    /// Accepts variable names in the input.
+    #[must_use]
+    pub fn variables(mut self, variables: bool) -> Self {
+        self.variables = variables;
+        self
+    }
+
+    /// Reads digits of other scripts as ASCII digits; see
+    /// [`Lexer::with_unicode_digits`].
+    #[must_use]
+    pub fn unicode_digits(mut self, unicode_digits: bool) -> Self {
+        self.unicode_digits = unicode_digits;
+        self
+    }
+
+    /// Accepts `,` between groups of three digits; see
+    /// [`Lexer::with_comma_groups`].
+    #[must_use]
+    pub fn comma_groups(mut self, comma_groups: bool) -> Self {
+        self.comma_groups = comma_groups;
+        self
+    }
+
+    /// Reads malformed number literals as numbers; see
+    /// [`Lexer::with_lenient_numbers`].
+    #[must_use]
+    pub fn lenient_numbers(mut self, lenient_numbers: bool) -> Self {
+        self.lenient_numbers = lenient_numbers;
+        self
+    }
+
+    /// Rejects input over `limits`.
+    #[must_use]
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Expands the macros of `macros` in the input. Their names are read
+    /// like variable names, so this needs [`variables`](Self::variables).
+    #[must_use]
+    pub fn macros(mut self, macros: MacroTable) -> Self {
+        self.macros = macros;
+        self
+    }
+
+    /// Folds numeric subexpressions to their values before generating
+    /// output. This needs the `exact` feature.
+    #[cfg(feature = "exact")]
+    #[must_use]
+    pub fn fold_constants(mut self, fold_constants: bool) -> Self {
+        self.fold_constants = fold_constants;
+        self
+    }
+
+    /// Reads and writes the custom operator `plugin`, replacing any
+    /// operator spelled the same way. LaTeX output writes it with the
+    /// plugin; the other formats write it as a function call.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the plugin cannot be read from the input, as
+    /// [`OperatorTable::with_operator`] describes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::pipeline::{Compiler, Format};
+    /// use rpn2tex::plugin::OperatorPlugin;
+    ///
+    /// struct Choose;
+    ///
+    /// impl OperatorPlugin for Choose {
+    ///     fn spelling(&self) -> &str {
+    ///         "choose"
+    ///     }
+    ///
+    ///     fn latex(&self, operands: &[String]) -> String {
+    ///         format!(r"\binom{{{}}}{{{}}}", operands[0], operands[1])
+    ///     }
+    /// }
+    ///
+    /// let compiler = Compiler::new().operator(Choose);
+    /// assert_eq!(compiler.compile("5 2 choose 3 *").unwrap(), r"$\binom{5}{2} \times 3$");
+    /// let infix = compiler.format(Format::Infix);
+    /// assert_eq!(infix.compile("5 2 choose").unwrap(), "choose(5, 2)");
+    /// ```
+    #[must_use]
+    pub fn operator(mut self, plugin: impl OperatorPlugin + 'static) -> Self {
+        self.operators = self.operators.with_operator(plugin);
+        self
+    }
+
+    /// Runs `hook` on the tokens of each expression before they are parsed,
+    /// after any earlier `after_lex` hooks. The tokens end with the EOF
+    /// token, which the parser expects to remain last.
+    #[must_use]
+    pub fn after_lex<F>(mut self, hook: F) -> Self
+    where
+        F: for<'a> Fn(Vec<Token<'a>>) -> Vec<Token<'a>> + Send + Sync + 'static,
+    {
+        self.hooks.after_lex.push(Arc::new(hook));
+        self
+    }
+
+    /// Runs `hook` on the AST of each expression as parsed, before it is
+    /// folded, after any earlier `after_parse` hooks.
+    #[must_use]
+    pub fn after_parse<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(Expr) -> Expr + Send + Sync + 'static,
+    {
+        self.hooks.after_parse.push(Arc::new(hook));
+        self
+    }
+
+    /// Runs `hook` on the AST of each expression just before its output is
+    /// generated, after folding and any earlier `before_generate` hooks.
+    #[must_use]
+    pub fn before_generate<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(Expr) -> Expr + Send + Sync + 'static,
+    {
+        self.hooks.before_generate.push(Arc::new(hook));
+        self
+    }
+
+    /// Builds the generator for the configured format.
+    #[must_use]
+    pub fn generator(&self) -> Box<dyn CodeGenerator> {
+        match self.format {
+            Format::Latex if !self.operators.is_empty() => Box::new(OperatorLaTeX {
+                latex: self.latex.build(),
+                operators: self.operators.clone(),
+            }),
+            Format::Latex => Box::new(self.latex.build()),
+            Format::Prefix => Box::new(NotationGenerator::new(Notation::Prefix)),
+            Format::Rpn => Box::new(NotationGenerator::new(Notation::Postfix)),
+            Format::Infix => Box::new(InfixGenerator::new()),
+            Format::ContentMathml => Box::new(ContentMathMLGenerator::new()),
+            Format::Wolfram => Box::new(WolframGenerator::new(WolframStyle::FullForm)),
+            Format::WolframInfix => Box::new(WolframGenerator::new(WolframStyle::Infix)),
+            Format::Python => Box::new(PythonGenerator::new()),
+            Format::Context => Box::new(ConTeXtGenerator::new()),
+            Format::Markdown(flavor) => Box::new(MarkdownGenerator::new(flavor)),
+        }
+    }
+
+    /// Builds the lexer for `segment` with the input settings, its
+    /// positions referring to the whole source.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::batch::Segment;
+    /// use rpn2tex::pipeline::Compiler;
+    ///
+    /// let compiler = Compiler::new().comma_groups(true);
+    /// let tokens = compiler.lexer(&Segment::whole("1,000 2 +")).tokenize().unwrap();
+    /// assert_eq!(tokens[0].value, "1000");
+    /// ```
+    #[must_use]
+    pub fn lexer<'a>(&self, segment: &Segment<'a>) -> Lexer<'a> {
+        segment
+            .lexer()
+            .with_identifiers(self.variables)
+            .with_unicode_digits(self.unicode_digits)
+            .with_comma_groups(self.comma_groups)
+            .with_lenient_numbers(self.lenient_numbers)
+            .with_infix(self.input == InputNotation::Infix)
+            .with_limits(self.limits)
+            .with_operators(self.operators.clone())
+    }
+
+    /// Parses the tokens of one expression in the input notation, without
+    /// running hooks or folding.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ParserError`] if the tokens are not a single well-formed
+    /// expression within the limits.
+    pub fn parse_tokens(&self, tokens: Vec<Token<'_>>) -> Result<Expr, ParserError> {
+        let mut parser = Parser::new(tokens)
+            .with_limits(self.limits)
+            .with_macros(self.macros.clone())
+            .with_operators(self.operators.clone());
+        match self.input {
+            InputNotation::Rpn => parser.parse(),
+            InputNotation::Prefix => parser.parse_prefix(),
+            InputNotation::Infix => parser.parse_infix(),
+        }
+    }
+
+    /// Parses one expression, running the `after_lex` and `after_parse`
+    /// hooks, and folds it if configured.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Rpn2TexError::Lexer`] or [`Rpn2TexError::Parser`] if the
+    /// source is not a single well-formed expression within the limits.
+    pub fn parse(&self, source: &str) -> Result<Expr, Rpn2TexError> {
+        self.parse_segment(&Segment::whole(source))
+    }
+
+    /// Parses one expression of a larger source like [`parse`](Self::parse),
+    /// with error positions referring to the whole source.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`parse`](Self::parse).
+    pub fn parse_segment(&self, segment: &Segment<'_>) -> Result<Expr, Rpn2TexError> {
+        let tokens = stage!("lex", self.lexer(segment).tokenize())?;
+        let tokens = self
+            .hooks
+            .after_lex
+            .iter()
+            .fold(tokens, |tokens, hook| hook(tokens));
+        let ast = stage!("parse", self.parse_tokens(tokens))?;
+        let ast = self
+            .hooks
+            .after_parse
+            .iter()
+            .fold(ast, |ast, hook| hook(ast));
+        #[cfg(feature = "exact")]
+        let ast = if self.fold_constants {
+            ast.fold_constants()
+        } else {
+            ast
+        };
+        Ok(ast)
+    }
+
+    /// Converts one RPN expression to the configured format, running every
+    /// hook.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`parse`](Self::parse), or an
+    /// [`Rpn2TexError::Generation`] if the format cannot express the
+    /// expression.
+    pub fn compile(&self, source: &str) -> Result<String, Rpn2TexError> {
+        let ast = self
+            .hooks
+            .before_generate
+            .iter()
+            .fold(self.parse(source)?, |ast, hook| hook(ast));
+        Ok(stage!("generate", self.generator().try_generate(&ast))?)
+    }
+}
+
+/// The LaTeX generator writing custom operators with their plugins.
+struct OperatorLaTeX {
+    latex: LaTeXGenerator,
+    operators: OperatorTable,
+}
+
+impl CodeGenerator for OperatorLaTeX {
+    fn generate(&self, expr: &Expr) -> String {
+        self.latex.generate_with_operators(expr, &self.operators)
+    }
+}
+
+/// Converts one RPN expression with `generator`.
+///
+/// # Errors
+///
+/// Returns an [`Rpn2TexError::Lexer`] or [`Rpn2TexError::Parser`] if the
+/// source is not a single well-formed expression, or an
+/// [`Rpn2TexError::Generation`] if `generator` cannot write it.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::notation::{Notation, NotationGenerator};
+///
+/// let prefix = NotationGenerator::new(Notation::Prefix);
+/// assert_eq!(rpn2tex::convert("5 3 + 2 *", &prefix).unwrap(), "* + 5 3 2");
+/// ```
+pub fn convert(source: &str, generator: &dyn CodeGenerator) -> Result<String, Rpn2TexError> {
+    let tokens = stage!("lex", Lexer::new(source).tokenize())?;
+    let ast = stage!("parse", Parser::new(tokens).parse())?;
+    Ok(stage!("generate", generator.try_generate(&ast))?)
+}
+
+/// Converts one RPN expression to LaTeX with the default settings.
+///
+/// # Errors
+///
+/// Returns an [`Rpn2TexError::Lexer`] or [`Rpn2TexError::Parser`] if the
+/// source is not a single well-formed expression.
+///
+/// # Examples
+///
+/// ```
+/// assert_eq!(rpn2tex::compile("5 3 + 2 *").unwrap(), r"$( 5 + 3 ) \times 2$");
+/// assert!(rpn2tex::compile("5 @").is_err());
+/// ```
+pub fn compile(source: &str) -> Result<String, Rpn2TexError> {
+    Compiler::new().compile(source)
+}
+
+/// Reads one RPN expression from the file at `path`, without its byte
+/// order mark if it has one, and converts it with `generator`.
+///
+/// # Errors
+///
+/// Returns an [`Rpn2TexError::Io`] if the file cannot be read, an
+/// [`Rpn2TexError::Encoding`] if it is not valid UTF-8, or an error of
+/// [`convert`].
+pub fn convert_file(
+    path: impl AsRef<Path>,
+    generator: &dyn CodeGenerator,
+) -> Result<String, Rpn2TexError> {
+    let path = path.as_ref();
+    let bytes = fs::read(path).map_err(|error| Rpn2TexError::io(path, error))?;
+    convert(decode(&bytes)?, generator)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error_code::ErrorCode;
+    use crate::latex::LaTeXGenerator;
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn test_errors_by_stage() {
+        let error = compile("2 3 ^").unwrap_err();
+        assert!(matches!(error, Rpn2TexError::Lexer(_)));
+        assert_eq!(error.code(), Some(ErrorCode::UnexpectedCharacter));
+        assert_eq!(
+            error.to_string(),
+            "Line 1, column 5: Unexpected character '^'"
+        );
+
+        let error = compile("5 3").unwrap_err();
+        assert!(matches!(error, Rpn2TexError::Parser(_)));
+        assert_eq!(error.code(), Some(ErrorCode::MissingOperator));
+    }
+
+    #[test]
+    fn test_input_settings() {
+        let rpn = Compiler::new().format(Format::Rpn);
+        assert!(rpn.compile("1,000 2.5.1 + ٣ * +").is_err());
+        let lenient = rpn
+            .clone()
+            .unicode_digits(true)
+            .comma_groups(true)
+            .lenient_numbers(true);
+        assert_eq!(
+            lenient.compile("1,000 2.5.1 + ٣ * +").unwrap(),
+            "1000 2.5 .1 + 3 * +"
+        );
+
+        let prefix = rpn.clone().input(InputNotation::Prefix);
+        assert_eq!(prefix.compile("* + 5 3 2").unwrap(), "5 3 + 2 *");
+        let infix = rpn.input(InputNotation::Infix);
+        assert_eq!(infix.compile("(5 + 3) * 2").unwrap(), "5 3 + 2 *");
+
+        // Positions refer to the whole source
+        let source = "5 3 +\n2 @";
+        let segment = crate::batch::split_expressions(source)[1];
+        let error = Compiler::new().parse_segment(&segment).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Line 2, column 3: Unexpected character '@'"
+        );
+    }
+
+    #[cfg(feature = "exact")]
+    #[test]
+    fn test_compiler_settings() {
+        let compiler = Compiler::new()
+            .format(Format::Rpn)
+            .variables(true)
+            .fold_constants(true);
+        assert_eq!(compiler.compile("2 3 * x +").unwrap(), "6 x +");
+
+        let markdown = Compiler::new().format(Format::Markdown(MarkdownFlavor::GitHub));
+        assert_eq!(
+            markdown.compile("5 3 +").unwrap(),
+            MarkdownGenerator::new(MarkdownFlavor::GitHub)
+                .generate(&markdown.parse("5 3 +").unwrap())
+        );
+
+        let error = Compiler::new().compile("x 1 +").unwrap_err();
+        assert!(matches!(error, Rpn2TexError::Lexer(_)));
+        let limited = Compiler::new().limits(Limits::new().with_max_tokens(2));
+        assert!(limited.compile("1 2 +").is_err());
+
+        let macros = MacroTable::parse_prelude("macro sq = dup *").unwrap();
+        let compiler = Compiler::new()
+            .macros(macros)
+            .variables(true)
+            .environment(MathEnvironment::Bare);
+        assert_eq!(compiler.compile("3 sq").unwrap(), r"3 \times 3");
+    }
+
+    #[cfg(feature = "exact")]
+    #[test]
+    fn test_hooks_run_between_stages() {
+        use crate::tokens::TokenType;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        // Read `*` as `+`, seeing the tokens the lexer produced
+        let compiler = Compiler::new().format(Format::Rpn).after_lex(|tokens| {
+            tokens
+                .into_iter()
+                .map(|mut token| {
+                    if token.token_type == TokenType::Mult {
+                        token.token_type = TokenType::Plus;
+                        token.value = "+".into();
+                    }
+                    token
+                })
+                .collect()
+        });
+        assert_eq!(compiler.compile("2 3 *").unwrap(), "2 3 +");
+
+        // after_parse sees the AST before folding, before_generate after
+        static PARSED: AtomicUsize = AtomicUsize::new(0);
+        let count = |expr: &Expr| match expr {
+            Expr::BinaryOp { .. } => 1,
+            _ => 0,
+        };
+        let compiler = Compiler::new()
+            .format(Format::Rpn)
+            .fold_constants(true)
+            .after_parse(move |ast| {
+                PARSED.fetch_add(count(&ast), Ordering::Relaxed);
+                ast
+            })
+            .before_generate(|ast| match ast {
+                Expr::Number { span, value } => Expr::Number {
+                    span,
+                    value: format!("{}.0", value),
+                },
+                other => other,
+            });
+        assert_eq!(compiler.compile("2 3 *").unwrap(), "6.0");
+        assert_eq!(PARSED.load(Ordering::Relaxed), 1);
+
+        assert_eq!(compiler.clone(), compiler);
+        assert_ne!(compiler.clone().after_parse(|ast| ast), compiler);
+    }
+
+    struct Sqrt;
+
+    impl OperatorPlugin for Sqrt {
+        fn spelling(&self) -> &str {
+            "sqrt"
+        }
+
+        fn arity(&self) -> usize {
+            1
+        }
+
+        fn latex(&self, operands: &[String]) -> String {
+            format!("\\sqrt{{{}}}", operands[0])
+        }
+
+        fn evaluate(&self, operands: &[f64]) -> Option<f64> {
+            Some(operands[0].sqrt()).filter(|root| root.is_finite())
+        }
+    }
+
+    struct Modulo;
+
+    impl OperatorPlugin for Modulo {
+        fn spelling(&self) -> &str {
+            "%"
+        }
+
+        fn precedence(&self) -> Option<i32> {
+            Some(2)
+        }
+
+        fn latex(&self, operands: &[String]) -> String {
+            format!("{} \\bmod {}", operands[0], operands[1])
+        }
+    }
+
+    #[test]
+    fn test_custom_operators() {
+        use crate::eval::Evaluator;
+
+        let compiler = Compiler::new()
+            .operator(Sqrt)
+            .operator(Modulo)
+            .environment(MathEnvironment::Bare);
+        assert_eq!(
+            compiler.compile("9 sqrt 2 *").unwrap(),
+            r"\sqrt{9} \times 2"
+        );
+        assert_eq!(compiler.compile("1 2 + sqrt").unwrap(), r"\sqrt{1 + 2}");
+        // Grouped by precedence, on either side
+        assert_eq!(compiler.compile("7 1 + 3 %").unwrap(), r"( 7 + 1 ) \bmod 3");
+        assert_eq!(
+            compiler.compile("7 3 % 2 *").unwrap(),
+            r"7 \bmod 3 \times 2"
+        );
+        assert_eq!(
+            compiler.compile("2 7 3 % *").unwrap(),
+            r"2 \times ( 7 \bmod 3 )"
+        );
+        assert_eq!(
+            compiler.compile("7 3 2 * %").unwrap(),
+            r"7 \bmod ( 3 \times 2 )"
+        );
+
+        let rpn = compiler.clone().format(Format::Rpn);
+        #[cfg(feature = "exact")]
+        assert_eq!(
+            rpn.clone()
+                .fold_constants(true)
+                .compile("4 5 + sqrt 3 %")
+                .unwrap(),
+            "9 sqrt 3 %"
+        );
+        let python = compiler.clone().format(Format::Python);
+        assert_eq!(python.compile("2 sqrt 1 +").unwrap(), "sqrt(2) + 1");
+        // `%` cannot be called as a Python function
+        let error = python.compile("1 7 3 % +").unwrap_err();
+        assert!(matches!(error, Rpn2TexError::Generation(_)));
+        assert_eq!(
+            error.to_string(),
+            "Line 1, column 7: Operator '%' is not a Python function name"
+        );
+
+        let error = compiler.compile("%").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Line 1, column 1: Too few operands for operator"
+        );
+        let error = Compiler::new().compile("9 sqrt").unwrap_err();
+        assert_eq!(error.code(), Some(ErrorCode::UnexpectedCharacter));
+
+        let operators = OperatorTable::new().with_operator(Sqrt);
+        let evaluator = Evaluator::new().with_operators(operators.clone());
+        let ast = compiler.parse("16 sqrt 1 +").unwrap();
+        assert_eq!(evaluator.evaluate(&ast), Ok(5.0));
+        #[cfg(feature = "exact")]
+        assert_eq!(evaluator.evaluate_exact(&ast).unwrap().to_string(), "5");
+        let error = evaluator
+            .evaluate(&compiler.parse("-1 sqrt").unwrap())
+            .unwrap_err();
+        assert_eq!(error.message(), "Operator 'sqrt' has no value here");
+        let error = Evaluator::new().evaluate(&ast).unwrap_err();
+        assert_eq!(error.message(), "Unknown operator 'sqrt'");
+
+        // Prefix input reads operands in written order
+        let tokens = Lexer::new("% 7 3").with_operators(compiler.operators.clone());
+        let ast = Parser::new(tokens.tokenize().unwrap())
+            .with_operators(compiler.operators.clone())
+            .parse_prefix()
+            .unwrap();
+        assert_eq!(rpn.generator().generate(&ast), "7 3 %");
+        let error = Parser::new(
+            Lexer::new("2 sqrt")
+                .with_operators(operators)
+                .tokenize()
+                .unwrap(),
+        )
+        .parse()
+        .unwrap_err();
+        assert_eq!(error.code(), Some(ErrorCode::UnknownOperator));
+    }
+
+    #[test]
+    fn test_convert_file() {
+        let path = env::temp_dir().join(format!("rpn2tex-pipeline-{}.rpn", process::id()));
+        let generator = LaTeXGenerator::new();
+        fs::write(&path, b"\xef\xbb\xbf5 3 +").unwrap();
+        assert_eq!(convert_file(&path, &generator).unwrap(), "$5 + 3$");
+
+        fs::write(&path, b"5 \xff").unwrap();
+        let error = convert_file(&path, &generator).unwrap_err();
+        assert!(matches!(error, Rpn2TexError::Encoding(_)));
+        assert_eq!(error.span().map(|span| span.column), Some(3));
+
+        fs::remove_file(&path).unwrap();
+        let error = convert_file(&path, &generator).unwrap_err();
+        assert!(matches!(error, Rpn2TexError::Io { .. }));
+        assert_eq!(error.span(), None);
+        assert!(error.to_string().starts_with("Error reading "));
+    }
+}
@@ -0,0 +1,234 @@
+//! Operators supplied by the caller rather than built into the crate.
+//!
+//! An [`OperatorPlugin`] describes one domain-specific operator: how it is
+//! spelled in the input, how many operands it takes, how tightly it binds,
+//! how it is written in LaTeX and, optionally, how it is computed. Plugins
+//! are registered in an [`OperatorTable`], which the lexer, parser, LaTeX
+//! generator and evaluator each accept; a
+//! [`Compiler`](crate::pipeline::Compiler) passes its table to all of them.
+//!
+//! A plugin operator is parsed into an [`Expr::Apply`](crate::ast::Expr::Apply)
+//! node. Generators other than LaTeX write it as a function call, such as
+//! `mod(7, 3)` in infix output.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// A custom operator.
+///
+/// Only [`spelling`](Self::spelling) and [`latex`](Self::latex) must be
+/// written; by default an operator is binary, is written like a function
+/// and cannot be evaluated.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::plugin::OperatorPlugin;
+///
+/// struct Modulo;
+///
+/// impl OperatorPlugin for Modulo {
+///     fn spelling(&self) -> &str {
+///         "mod"
+///     }
+///
+///     fn precedence(&self) -> Option<i32> {
+///         Some(2)
+///     }
+///
+///     fn latex(&self, operands: &[String]) -> String {
+///         format!(r"{} \bmod {}", operands[0], operands[1])
+///     }
+///
+///     fn evaluate(&self, operands: &[f64]) -> Option<f64> {
+///         Some(operands[0].rem_euclid(operands[1]))
+///     }
+/// }
+///
+/// assert_eq!(Modulo.arity(), 2);
+/// assert_eq!(Modulo.evaluate(&[-7.0, 3.0]), Some(2.0));
+/// ```
+pub trait OperatorPlugin: Send + Sync {
+    /// Returns the operator as written in the input, such as `mod` or `^`.
+    fn spelling(&self) -> &str;
+
+    /// Returns how many operands the operator takes from the stack.
+    fn arity(&self) -> usize {
+        2
+    }
+
+    /// Returns how tightly the operator binds, for one written between or
+    /// beside its operands: `+` and `-` bind at 1, `*` and `/` at 2.
+    /// Operands binding more loosely are parenthesized, as is the operator
+    /// itself under a tighter one; so is an operand binding equally, other
+    /// than the first.
+    ///
+    /// `None`, the default, is for an operator written like a function,
+    /// as in `\sqrt{x}`, which groups its operands itself and is never
+    /// parenthesized.
+    fn precedence(&self) -> Option<i32> {
+        None
+    }
+
+    /// Writes the operator applied to `operands`, which are already in
+    /// LaTeX and parenthesized as the precedences require.
+    fn latex(&self, operands: &[String]) -> String;
+
+    /// Computes the operator applied to `operands`, or returns `None` if it
+    /// has no numeric value there.
+    fn evaluate(&self, _operands: &[f64]) -> Option<f64> {
+        None
+    }
+}
+
+/// The custom operators recognized, by spelling.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::lexer::Lexer;
+/// use rpn2tex::plugin::{OperatorPlugin, OperatorTable};
+///
+/// struct Choose;
+///
+/// impl OperatorPlugin for Choose {
+///     fn spelling(&self) -> &str {
+///         "choose"
+///     }
+///
+///     fn latex(&self, operands: &[String]) -> String {
+///         format!(r"\binom{{{}}}{{{}}}", operands[0], operands[1])
+///     }
+/// }
+///
+/// let operators = OperatorTable::new().with_operator(Choose);
+/// assert!(operators.get("choose").is_some());
+/// assert!(Lexer::new("5 2 choose").with_operators(operators).tokenize().is_ok());
+/// ```
+#[derive(Clone, Default)]
+pub struct OperatorTable {
+    operators: BTreeMap<String, Arc<dyn OperatorPlugin>>,
+}
+
+impl OperatorTable {
+    /// Creates a table with no operators.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `plugin`, replacing any operator spelled the same way.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the plugin's spelling is empty, starts with a digit or
+    /// holds whitespace, `#` or `;`, or if its arity is 0; such an operator
+    /// could not be read from the input.
+    #[must_use]
+    pub fn with_operator(mut self, plugin: impl OperatorPlugin + 'static) -> Self {
+        let spelling = plugin.spelling();
+        assert!(
+            !spelling.is_empty()
+                && !spelling.starts_with(char::is_numeric)
+                && !spelling
+                    .chars()
+                    .any(|c| c.is_whitespace() || matches!(c, '#' | ';')),
+            "operator spelling {:?} cannot be read from the input",
+            spelling
+        );
+        assert!(
+            plugin.arity() > 0,
+            "operator '{}' takes no operands",
+            spelling
+        );
+        self.operators
+            .insert(spelling.to_string(), Arc::new(plugin));
+        self
+    }
+
+    /// Returns the operator spelled `spelling`, if there is one.
+    #[must_use]
+    pub fn get(&self, spelling: &str) -> Option<&dyn OperatorPlugin> {
+        self.operators.get(spelling).map(|plugin| &**plugin)
+    }
+
+    /// Returns whether the table has no operators.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.operators.is_empty()
+    }
+
+    /// Returns the spelling of the longest operator `text` starts with.
+    ///
+    /// A spelling ending in a letter must not be followed by another
+    /// letter or digit, so `mod` is not found in `modulus`.
+    pub(crate) fn longest_prefix(&self, text: &str) -> Option<&str> {
+        self.operators
+            .keys()
+            .filter(|spelling| {
+                text.strip_prefix(spelling.as_str()).is_some_and(|rest| {
+                    !spelling.ends_with(char::is_alphanumeric)
+                        || !rest.starts_with(|c: char| c.is_alphanumeric() || c == '_')
+                })
+            })
+            .max_by_key(|spelling| spelling.len())
+            .map(String::as_str)
+    }
+}
+
+impl fmt::Debug for OperatorTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.operators.keys()).finish()
+    }
+}
+
+impl PartialEq for OperatorTable {
+    fn eq(&self, other: &Self) -> bool {
+        self.operators.len() == other.operators.len()
+            && self
+                .operators
+                .iter()
+                .zip(&other.operators)
+                .all(|((a, a_plugin), (b, b_plugin))| a == b && Arc::ptr_eq(a_plugin, b_plugin))
+    }
+}
+
+impl Eq for OperatorTable {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Named(&'static str);
+
+    impl OperatorPlugin for Named {
+        fn spelling(&self) -> &str {
+            self.0
+        }
+
+        fn latex(&self, operands: &[String]) -> String {
+            operands.join(self.0)
+        }
+    }
+
+    #[test]
+    fn test_longest_prefix() {
+        let operators = OperatorTable::new()
+            .with_operator(Named("mod"))
+            .with_operator(Named("*"))
+            .with_operator(Named("**"));
+        assert_eq!(operators.longest_prefix("mod 3"), Some("mod"));
+        assert_eq!(operators.longest_prefix("mod"), Some("mod"));
+        assert_eq!(operators.longest_prefix("modulus"), None);
+        assert_eq!(operators.longest_prefix("** 2"), Some("**"));
+        assert_eq!(operators.longest_prefix("*2"), Some("*"));
+        assert_eq!(format!("{:?}", operators), r#"{"*", "**", "mod"}"#);
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot be read")]
+    fn test_rejects_unreadable_spelling() {
+        let _ = OperatorTable::new().with_operator(Named("x y"));
+    }
+}
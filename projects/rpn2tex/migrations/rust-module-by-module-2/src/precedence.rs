@@ -0,0 +1,350 @@
+//! Operator precedence rules shared by the infix generators.
+//!
+//! Every backend that writes infix notation (LaTeX, Wolfram, ...) must decide
+//! when a subexpression needs grouping. Keeping the rules here guarantees the
+//! backends agree on the structure they render.
+//!
+//! The rules are driven by a small table of operator properties and produce
+//! minimal parenthesization: a pair of parentheses is written exactly when
+//! removing it would change the value of the expression as read with the
+//! usual left-to-right conventions.
+
+use crate::ast::Expr;
+
+/// Properties of a binary operator that decide where grouping is needed.
+///
+/// Commutativity is deliberately absent: generators never reorder operands,
+/// so it has no bearing on where parentheses go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct OperatorInfo {
+    /// Binding strength; higher binds tighter
+    precedence: i32,
+    /// Whether `a op (b op' c)` equals `a op b op' c` for every operator
+    /// `op'` of the same precedence, i.e. whether a right operand of equal
+    /// precedence can be written without parentheses
+    associative: bool,
+}
+
+/// Looks up the properties of an operator symbol.
+///
+/// # Operator table
+///
+/// | Operator | Precedence | Associative |
+/// |----------|------------|-------------|
+/// | `+`      | 1          | yes         |
+/// | `-`      | 1          | no          |
+/// | `*`      | 2          | yes         |
+/// | `/`      | 2          | no          |
+fn operator_info(operator: &str) -> OperatorInfo {
+    match operator {
+        "+" => OperatorInfo {
+            precedence: 1,
+            associative: true,
+        },
+        "-" => OperatorInfo {
+            precedence: 1,
+            associative: false,
+        },
+        "*" => OperatorInfo {
+            precedence: 2,
+            associative: true,
+        },
+        "/" => OperatorInfo {
+            precedence: 2,
+            associative: false,
+        },
+        _ => OperatorInfo {
+            precedence: 0,
+            associative: false,
+        },
+    }
+}
+
+/// Returns the precedence level for an operator.
+///
+/// Higher numbers indicate higher precedence (tighter binding).
+///
+/// # Precedence levels
+///
+/// * Level 1: Addition (+), Subtraction (-)
+/// * Level 2: Multiplication (*), Division (/)
+pub(crate) fn precedence(operator: &str) -> i32 {
+    operator_info(operator).precedence
+}
+
+/// Determines if a child expression needs parentheses.
+///
+/// Parentheses are needed when:
+/// 1. The child has lower precedence than the parent
+/// 2. The child has equal precedence, is on the right, and the parent is not
+///    associative (`5 - (3 + 2)`, `8 / (4 * 2)`)
+///
+/// An equal-precedence right operand of an associative parent regroups
+/// freely (`5 + (3 - 2)` is `5 + 3 - 2`), and left operands of equal
+/// precedence already read left to right.
+///
+/// # Arguments
+///
+/// * `child` - The child expression to check
+/// * `parent_operator` - The operator the child is an operand of
+/// * `is_right` - Whether the child is the right operand
+pub(crate) fn needs_parens(child: &Expr, parent_operator: &str, is_right: bool) -> bool {
+    match child {
+        // Custom operators are written as function calls, which group
+        // their operands themselves
+        Expr::Number { .. } | Expr::Variable { .. } | Expr::Apply { .. } => false,
+        Expr::BinaryOp { operator, .. } => {
+            let child_precedence = precedence(operator);
+            let parent_precedence = precedence(parent_operator);
+
+            if child_precedence < parent_precedence {
+                // Lower precedence always needs parens
+                true
+            } else if child_precedence == parent_precedence && is_right {
+                // Only associative parents let a right operand regroup
+                !operator_info(parent_operator).associative
+            } else {
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::span::Span;
+
+    /// Leaf values for enumerated trees; chosen so that distinct groupings
+    /// evaluate to distinct values.
+    const LEAVES: [i128; 4] = [7, 3, 11, 5];
+
+    /// An exact rational number, so regrouping is never masked by rounding.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    struct Ratio {
+        numerator: i128,
+        denominator: i128,
+    }
+
+    impl Ratio {
+        fn new(numerator: i128, denominator: i128) -> Option<Self> {
+            if denominator == 0 {
+                return None;
+            }
+            let divisor = gcd(numerator.abs(), denominator.abs()).max(1);
+            let sign = denominator.signum();
+            Some(Self {
+                numerator: sign * numerator / divisor,
+                denominator: sign * denominator / divisor,
+            })
+        }
+
+        fn apply(self, operator: &str, rhs: Self) -> Option<Self> {
+            let (a, b, c, d) = (
+                self.numerator,
+                self.denominator,
+                rhs.numerator,
+                rhs.denominator,
+            );
+            match operator {
+                "+" => Self::new(a * d + c * b, b * d),
+                "-" => Self::new(a * d - c * b, b * d),
+                "*" => Self::new(a * c, b * d),
+                "/" => Self::new(a * d, b * c),
+                _ => None,
+            }
+        }
+    }
+
+    fn gcd(a: i128, b: i128) -> i128 {
+        if b == 0 {
+            a
+        } else {
+            gcd(b, a % b)
+        }
+    }
+
+    /// Reference evaluation of an AST; `None` on division by zero.
+    fn evaluate(expr: &Expr) -> Option<Ratio> {
+        match expr {
+            Expr::Number { value, .. } => Ratio::new(value.parse().ok()?, 1),
+            Expr::Variable { .. } | Expr::Apply { .. } => None,
+            Expr::BinaryOp {
+                operator,
+                left,
+                right,
+                ..
+            } => evaluate(left)?.apply(operator, evaluate(right)?),
+        }
+    }
+
+    /// Renders infix text using the rules under test.
+    fn render(expr: &Expr) -> String {
+        match expr {
+            Expr::Number { value, .. } => value.clone(),
+            Expr::Variable { name, .. } => name.clone(),
+            Expr::Apply { .. } => unreachable!("the trees under test have no custom operators"),
+            Expr::BinaryOp {
+                operator,
+                left,
+                right,
+                ..
+            } => {
+                let mut left_text = render(left);
+                if needs_parens(left, operator, false) {
+                    left_text = format!("( {} )", left_text);
+                }
+                let mut right_text = render(right);
+                if needs_parens(right, operator, true) {
+                    right_text = format!("( {} )", right_text);
+                }
+                format!("{} {} {}", left_text, operator, right_text)
+            }
+        }
+    }
+
+    /// Evaluates space-separated infix text with conventional precedence
+    /// and left associativity, independently of the rules under test.
+    fn evaluate_infix(text: &str) -> Option<Ratio> {
+        fn expression(tokens: &[&str], pos: &mut usize, min_precedence: i32) -> Option<Ratio> {
+            let mut lhs = if tokens[*pos] == "(" {
+                *pos += 1;
+                let inner = expression(tokens, pos, 1)?;
+                *pos += 1; // ")"
+                inner
+            } else {
+                *pos += 1;
+                Ratio::new(tokens[*pos - 1].parse().ok()?, 1)?
+            };
+            while let Some(&operator) = tokens.get(*pos) {
+                let operator_precedence = precedence(operator);
+                if operator == ")" || operator_precedence < min_precedence {
+                    break;
+                }
+                *pos += 1;
+                let rhs = expression(tokens, pos, operator_precedence + 1)?;
+                lhs = lhs.apply(operator, rhs)?;
+            }
+            Some(lhs)
+        }
+
+        let tokens: Vec<&str> = text.split_whitespace().collect();
+        expression(&tokens, &mut 0, 1)
+    }
+
+    /// Builds every expression tree over `leaves`, in order, with every
+    /// combination of operators.
+    fn all_trees(leaves: &[i128]) -> Vec<Expr> {
+        if leaves.len() == 1 {
+            return vec![Expr::Number {
+                span: Span::new(0, 0, 1, 1),
+                value: leaves[0].to_string(),
+            }];
+        }
+
+        let mut trees = Vec::new();
+        for split in 1..leaves.len() {
+            for left in all_trees(&leaves[..split]) {
+                for right in all_trees(&leaves[split..]) {
+                    for operator in ["+", "-", "*", "/"] {
+                        trees.push(Expr::BinaryOp {
+                            span: Span::new(0, 0, 1, 1),
+                            operator: operator.to_string(),
+                            left: Box::new(left.clone()),
+                            right: Box::new(right.clone()),
+                        });
+                    }
+                }
+            }
+        }
+        trees
+    }
+
+    /// Returns `text` with the parenthesis pair opening at token `open`
+    /// removed.
+    fn without_group(tokens: &[&str], open: usize) -> String {
+        let mut depth = 0;
+        let mut close = open;
+        for (i, token) in tokens.iter().enumerate().skip(open) {
+            match *token {
+                "(" => depth += 1,
+                ")" => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                close = i;
+                break;
+            }
+        }
+        tokens
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| i != open && i != close)
+            .map(|(_, token)| *token)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn test_operator_table() {
+        assert_eq!(precedence("+"), precedence("-"));
+        assert_eq!(precedence("*"), precedence("/"));
+        assert!(precedence("*") > precedence("+"));
+        assert!(operator_info("+").associative);
+        assert!(operator_info("*").associative);
+        assert!(!operator_info("-").associative);
+        assert!(!operator_info("/").associative);
+    }
+
+    #[test]
+    fn test_right_operand_of_non_associative_parent() {
+        let sum = Expr::BinaryOp {
+            span: Span::new(0, 1, 1, 1),
+            operator: "+".to_string(),
+            left: Box::new(Expr::Number {
+                span: Span::new(0, 1, 1, 1),
+                value: "3".to_string(),
+            }),
+            right: Box::new(Expr::Number {
+                span: Span::new(2, 3, 1, 3),
+                value: "2".to_string(),
+            }),
+        };
+        // 5 - (3 + 2) is not 5 - 3 + 2
+        assert!(needs_parens(&sum, "-", true));
+        // 5 + (3 + 2) is 5 + 3 + 2
+        assert!(!needs_parens(&sum, "+", true));
+    }
+
+    #[test]
+    fn test_rendering_matches_reference_evaluator() {
+        for leaf_count in 1..=LEAVES.len() {
+            for tree in all_trees(&LEAVES[..leaf_count]) {
+                let text = render(&tree);
+                assert_eq!(evaluate_infix(&text), evaluate(&tree), "{}", text);
+            }
+        }
+    }
+
+    #[test]
+    fn test_every_parenthesis_pair_is_required() {
+        for leaf_count in 1..=LEAVES.len() {
+            for tree in all_trees(&LEAVES[..leaf_count]) {
+                let Some(expected) = evaluate(&tree) else {
+                    continue;
+                };
+                let text = render(&tree);
+                let tokens: Vec<&str> = text.split_whitespace().collect();
+                for (open, _) in tokens.iter().enumerate().filter(|(_, t)| **t == "(") {
+                    let reduced = without_group(&tokens, open);
+                    assert_ne!(
+                        evaluate_infix(&reduced),
+                        Some(expected),
+                        "parentheses in '{}' are redundant",
+                        text
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,537 @@
+//! Inputs that name subexpressions with `let` statements.
+//!
+//! A program is a sequence of statements, one per line or separated by `;`,
+//! as in [`batch`](crate::batch). Every statement but the last binds a name,
+//! `let a = 5 3 +`, and the last is the expression to convert, which may use
+//! the names: `let a = 5 3 + ; a 2 *`. A [`Program`] keeps the bindings apart
+//! from that expression, so generators can either show the names
+//! ([`body`](Program::body)) or substitute their definitions
+//! ([`inline`](Program::inline)).
+//!
+//! Statements may also define [`macros`](crate::macros) with `macro`, which
+//! the other statements can use.
+
+use crate::ast::Expr;
+use crate::batch::{split_expressions, Segment};
+use crate::error::Rpn2TexError;
+use crate::macros::MacroTable;
+use crate::parser::Parser;
+use crate::span::Span;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+
+/// Error type for malformed programs.
+///
+/// Raised for a `let` statement without a name or `=`, and when the
+/// statements do not end with exactly one expression.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::error::Rpn2TexError;
+/// use rpn2tex::program::Program;
+///
+/// let Err(Rpn2TexError::Binding(error)) = Program::parse("let a 5") else {
+///     panic!("expected a binding error");
+/// };
+/// assert_eq!(error.message(), "Expected '=' after 'let a'");
+/// assert_eq!(error.column(), 7);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BindingError {
+    /// Error description
+    message: String,
+    /// Where in the source the error occurred
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    span: Span,
+}
+
+impl BindingError {
+    /// Creates a new binding error.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - Description of the error
+    /// * `span` - Where in the source the error occurred
+    #[must_use]
+    pub fn new(message: impl Into<String>, span: Span) -> Self {
+        Self {
+            message: message.into(),
+            span,
+        }
+    }
+
+    /// Returns the error message.
+    #[must_use]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns where in the source the error occurred.
+    #[must_use]
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// Returns the line number where the error occurred (1-based).
+    #[must_use]
+    pub fn line(&self) -> u32 {
+        self.span.line
+    }
+
+    /// Returns the column number where the error occurred (1-based).
+    #[must_use]
+    pub fn column(&self) -> u32 {
+        self.span.column
+    }
+}
+
+impl fmt::Display for BindingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Line {}, column {}: {}",
+            self.span.line, self.span.column, self.message
+        )
+    }
+}
+
+impl Error for BindingError {}
+
+/// A name bound by a `let` statement.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Binding {
+    /// The bound name
+    name: String,
+    /// Where the name appears in its `let` statement
+    span: Span,
+    /// The expression the name stands for
+    value: Expr,
+}
+
+impl Binding {
+    /// Returns the bound name.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns where the name appears in its `let` statement.
+    #[must_use]
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// Returns the expression the name stands for, as written: names bound
+    /// earlier are not substituted.
+    #[must_use]
+    pub fn value(&self) -> &Expr {
+        &self.value
+    }
+}
+
+/// A parsed program: its `let` bindings and the expression that follows.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::latex::LaTeXGenerator;
+/// use rpn2tex::program::Program;
+///
+/// let program = Program::parse("let a = 5 3 + ; a 2 *").unwrap();
+/// assert_eq!(program.bindings()[0].name(), "a");
+///
+/// let generator = LaTeXGenerator::new();
+/// assert_eq!(generator.generate(program.body()), "$a \\times 2$");
+/// assert_eq!(generator.generate(&program.inline()), "$( 5 + 3 ) \\times 2$");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Program {
+    /// The bindings, in source order
+    bindings: Vec<Binding>,
+    /// The expression after the bindings
+    body: Expr,
+}
+
+impl Program {
+    /// Parses a program, with identifiers enabled so the bound names can be
+    /// used, expanding the macros it defines.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`Rpn2TexError`] if a statement fails to lex or parse, or
+    /// the statements are not bindings followed by one expression.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::notation::{Notation, NotationGenerator};
+    /// use rpn2tex::program::Program;
+    ///
+    /// let program = Program::parse("macro sq = dup *\nlet a = 2 sq; a 1 +").unwrap();
+    /// let rpn = NotationGenerator::new(Notation::Postfix).generate(&program.inline());
+    /// assert_eq!(rpn, "2 2 * 1 +");
+    /// ```
+    pub fn parse(source: &str) -> Result<Self, Rpn2TexError> {
+        let macros = MacroTable::new().with_definitions(source)?;
+        Self::parse_with(source, |segment| {
+            let tokens = segment.lexer().with_identifiers(true).tokenize()?;
+            Ok(Parser::new(tokens).with_macros(macros.clone()).parse()?)
+        })
+    }
+
+    /// Parses a program, parsing each expression, the values of the
+    /// bindings and the final expression, with `parse`. `macro` statements
+    /// are skipped; read them with
+    /// [`MacroTable::with_definitions`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error of `parse`, or a [`Rpn2TexError::Binding`] if
+    /// the statements are not bindings followed by one expression.
+    pub fn parse_with<'a, F>(source: &'a str, mut parse: F) -> Result<Self, Rpn2TexError>
+    where
+        F: FnMut(&Segment<'a>) -> Result<Expr, Rpn2TexError>,
+    {
+        let mut bindings = Vec::new();
+        let mut body = None;
+
+        for segment in split_expressions(source) {
+            if starts_with_keyword(segment.text, "macro") {
+                continue;
+            }
+            if body.is_some() {
+                return Err(BindingError::new(
+                    "Expected the expression to be the last statement",
+                    statement_span(&segment),
+                )
+                .into());
+            }
+            match definition(&segment, "let")? {
+                Some((name, span, value)) => bindings.push(Binding {
+                    name: name.to_string(),
+                    span,
+                    value: parse(&value)?,
+                }),
+                None => body = Some(parse(&segment)?),
+            }
+        }
+
+        let body = body.ok_or_else(|| {
+            BindingError::new(
+                "Expected an expression after the bindings",
+                end_span(source),
+            )
+        })?;
+        Ok(Self { bindings, body })
+    }
+
+    /// Returns the bindings, in source order.
+    #[must_use]
+    pub fn bindings(&self) -> &[Binding] {
+        &self.bindings
+    }
+
+    /// Returns the final expression, with bound names as variables.
+    #[must_use]
+    pub fn body(&self) -> &Expr {
+        &self.body
+    }
+
+    /// Returns the final expression with every bound name replaced by its
+    /// definition. A name rebound later is replaced by the definition in
+    /// effect where it is used.
+    #[must_use]
+    pub fn inline(&self) -> Expr {
+        let mut values = HashMap::new();
+        for binding in &self.bindings {
+            let value = substitute(&binding.value, &values);
+            values.insert(binding.name.as_str(), value);
+        }
+        substitute(&self.body, &values)
+    }
+
+    /// Returns the first variable used before any binding names it, with
+    /// where it is used.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::program::Program;
+    ///
+    /// let program = Program::parse("let a = x 2 *\na b +").unwrap();
+    /// let (name, span) = program.unbound_variable().unwrap();
+    /// assert_eq!((name, span.line, span.column), ("x", 1, 9));
+    /// ```
+    #[must_use]
+    pub fn unbound_variable(&self) -> Option<(&str, Span)> {
+        let mut bound = HashSet::new();
+        for binding in &self.bindings {
+            if let Some(unbound) = first_unbound(&binding.value, &bound) {
+                return Some(unbound);
+            }
+            bound.insert(binding.name.as_str());
+        }
+        first_unbound(&self.body, &bound)
+    }
+}
+
+/// Returns `true` if `source` holds a `let` statement, and so should be
+/// parsed as a [`Program`] rather than a single expression.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::program::has_bindings;
+///
+/// assert!(has_bindings("let a = 5 3 + ; a 2 *"));
+/// assert!(!has_bindings("5 3 +\n2 *"));
+/// ```
+#[must_use]
+pub fn has_bindings(source: &str) -> bool {
+    split_expressions(source)
+        .iter()
+        .any(|segment| starts_with_keyword(segment.text, "let"))
+}
+
+/// Returns `true` if `text` is a statement introduced by `keyword`.
+pub(crate) fn starts_with_keyword(text: &str, keyword: &str) -> bool {
+    text.trim_start()
+        .strip_prefix(keyword)
+        .is_some_and(|rest| rest.chars().next().is_none_or(char::is_whitespace))
+}
+
+/// Splits a `<keyword> name = value` statement into its name, the name's
+/// span and the segment holding the value. Returns `None` for statements
+/// introduced by another keyword or none.
+pub(crate) fn definition<'a>(
+    segment: &Segment<'a>,
+    keyword: &str,
+) -> Result<Option<(&'a str, Span, Segment<'a>)>, BindingError> {
+    let text = segment.text;
+    if !starts_with_keyword(text, keyword) {
+        return Ok(None);
+    }
+
+    let rest = &text.trim_start()[keyword.len()..];
+    let name_start = text.len() - rest.trim_start().len();
+    let name_len = text[name_start..]
+        .char_indices()
+        .find(|&(i, ch)| !(ch.is_ascii_alphabetic() || (i > 0 && ch.is_ascii_alphanumeric())))
+        .map_or(text.len() - name_start, |(i, _)| i);
+    if name_len == 0 {
+        return Err(BindingError::new(
+            format!("Expected a name after '{}'", keyword),
+            span_in(segment, name_start, name_start),
+        ));
+    }
+    let name_end = name_start + name_len;
+    let name = &text[name_start..name_end];
+
+    let equals = text.len() - text[name_end..].trim_start().len();
+    if !text[equals..].starts_with('=') {
+        return Err(BindingError::new(
+            format!("Expected '=' after '{} {}'", keyword, name),
+            span_in(segment, equals, equals),
+        ));
+    }
+
+    let value_span = span_in(segment, equals + 1, text.len());
+    let value = Segment {
+        text: &text[equals + 1..],
+        offset: value_span.start,
+        line: value_span.line,
+        column: value_span.column,
+    };
+    Ok(Some((name, span_in(segment, name_start, name_end), value)))
+}
+
+/// Returns the span of the byte range `start..end` of a segment's text,
+/// which lies on a single line.
+fn span_in(segment: &Segment, start: usize, end: usize) -> Span {
+    let column = segment.column + segment.text[..start].chars().count() as u32;
+    Span::new(
+        segment.offset + start,
+        segment.offset + end,
+        segment.line,
+        column,
+    )
+}
+
+/// Returns the span of a statement's text, without leading and trailing
+/// whitespace.
+pub(crate) fn statement_span(segment: &Segment) -> Span {
+    let text = segment.text;
+    let start = text.len() - text.trim_start().len();
+    span_in(segment, start, text.trim_end().len())
+}
+
+/// Returns the empty span at the end of `source`.
+fn end_span(source: &str) -> Span {
+    let line_start = source.rfind('\n').map_or(0, |i| i + 1);
+    let line = source.matches('\n').count() as u32 + 1;
+    let column = source[line_start..].chars().count() as u32 + 1;
+    Span::new(source.len(), source.len(), line, column)
+}
+
+/// Returns `expr` with every variable named in `values` replaced.
+fn substitute(expr: &Expr, values: &HashMap<&str, Expr>) -> Expr {
+    match expr {
+        Expr::Variable { name, .. } => values
+            .get(name.as_str())
+            .cloned()
+            .unwrap_or_else(|| expr.clone()),
+        Expr::Number { .. } => expr.clone(),
+        Expr::BinaryOp {
+            span,
+            operator,
+            left,
+            right,
+        } => Expr::BinaryOp {
+            span: *span,
+            operator: operator.clone(),
+            left: Box::new(substitute(left, values)),
+            right: Box::new(substitute(right, values)),
+        },
+        Expr::Apply {
+            span,
+            operator,
+            operands,
+        } => Expr::Apply {
+            span: *span,
+            operator: operator.clone(),
+            operands: operands
+                .iter()
+                .map(|operand| substitute(operand, values))
+                .collect(),
+        },
+    }
+}
+
+/// Returns the leftmost variable in `expr` not in `bound`.
+fn first_unbound<'e>(expr: &'e Expr, bound: &HashSet<&str>) -> Option<(&'e str, Span)> {
+    match expr {
+        Expr::Variable { span, name } if !bound.contains(name.as_str()) => {
+            Some((name.as_str(), *span))
+        }
+        Expr::Number { .. } | Expr::Variable { .. } => None,
+        Expr::BinaryOp { left, right, .. } => {
+            first_unbound(left, bound).or_else(|| first_unbound(right, bound))
+        }
+        Expr::Apply { operands, .. } => operands
+            .iter()
+            .find_map(|operand| first_unbound(operand, bound)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::{Notation, NotationGenerator};
+
+    fn rpn(expr: &Expr) -> String {
+        NotationGenerator::new(Notation::Postfix).generate(expr)
+    }
+
+    #[test]
+    fn test_parse_bindings() {
+        let program = Program::parse("let a = 5 3 +\nlet b2 = a a *; b2 1 -").unwrap();
+        let names: Vec<&str> = program.bindings().iter().map(Binding::name).collect();
+        assert_eq!(names, ["a", "b2"]);
+        assert_eq!(program.bindings()[1].span(), Span::new(18, 20, 2, 5));
+        assert_eq!(rpn(program.bindings()[1].value()), "a a *");
+        assert_eq!(rpn(program.body()), "b2 1 -");
+        assert_eq!(rpn(&program.inline()), "5 3 + 5 3 + * 1 -");
+    }
+
+    #[test]
+    fn test_inline_rebinding() {
+        let program = Program::parse("let a = 2; let b = a 1 +; let a = 10; a b *").unwrap();
+        assert_eq!(rpn(&program.inline()), "10 2 1 + *");
+    }
+
+    #[test]
+    fn test_value_positions() {
+        let program = Program::parse("let a = 5 3 +; a").unwrap();
+        let value = program.bindings()[0].value();
+        assert_eq!((value.line(), value.column()), (1, 13));
+    }
+
+    #[test]
+    fn test_no_bindings() {
+        let program = Program::parse("5 3 +").unwrap();
+        assert!(program.bindings().is_empty());
+        assert_eq!(rpn(program.body()), "5 3 +");
+    }
+
+    #[test]
+    fn test_binding_errors() {
+        let message = |source| match Program::parse(source).unwrap_err() {
+            Rpn2TexError::Binding(error) => (error.message().to_string(), error.column()),
+            other => panic!("unexpected error {:?}", other),
+        };
+        assert_eq!(
+            message("let = 5; 1"),
+            ("Expected a name after 'let'".to_string(), 5)
+        );
+        assert_eq!(
+            message("let a 5; a"),
+            ("Expected '=' after 'let a'".to_string(), 7)
+        );
+        assert_eq!(
+            message("let a = 5"),
+            ("Expected an expression after the bindings".to_string(), 10)
+        );
+        assert_eq!(
+            message("let a = 5; a; let b = 1"),
+            (
+                "Expected the expression to be the last statement".to_string(),
+                15
+            )
+        );
+    }
+
+    #[test]
+    fn test_value_errors() {
+        let error = Program::parse("let a = 5 +; a").unwrap_err();
+        assert_eq!(error.message(), "Too few operands for operator");
+        assert_eq!(error.column(), 11);
+    }
+
+    #[test]
+    fn test_unbound_variable() {
+        let program = Program::parse("let a = 1; a b +").unwrap();
+        assert_eq!(program.unbound_variable().map(|(name, _)| name), Some("b"));
+        // A name can only be used after its binding
+        let program = Program::parse("let a = b; let b = 1; a").unwrap();
+        assert_eq!(program.unbound_variable().map(|(name, _)| name), Some("b"));
+        let program = Program::parse("let a = 1; a a +").unwrap();
+        assert_eq!(program.unbound_variable(), None);
+    }
+
+    #[test]
+    fn test_has_bindings() {
+        assert!(has_bindings("  let x = 1\nx"));
+        assert!(!has_bindings("letter 1 +"));
+        assert!(!has_bindings("# let a = 1\n5"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let program = Program::parse("let a = 5 3 +; a 2 *").unwrap();
+        let json = serde_json::to_string(&program).unwrap();
+        assert_eq!(serde_json::from_str::<Program>(&json).unwrap(), program);
+
+        let error = Program::parse("let = 5").unwrap_err();
+        let json = serde_json::to_string(&error).unwrap();
+        assert!(json.starts_with(r#"{"Binding":{"message":"#));
+        assert_eq!(serde_json::from_str::<Rpn2TexError>(&json).unwrap(), error);
+    }
+}
@@ -0,0 +1,221 @@
+//! Python expression generation from Abstract Syntax Trees.
+//!
+//! The generated code is a single evaluable Python expression (`(5 + 3) * 2`)
+//! that computes the same value as the RPN input, suitable for pasting into
+//! a script or notebook to check the typeset result.
+
+use crate::ast::Expr;
+use crate::codegen::{CodeGenerator, GenerationError};
+use crate::precedence;
+
+/// Converts AST expressions to Python source code.
+///
+/// Python's arithmetic operators share the precedence and associativity of
+/// the RPN operators, so the output uses the same minimal parenthesization
+/// as the LaTeX backend. Division is written as `/` (true division).
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::ast::Expr;
+/// use rpn2tex::python::PythonGenerator;
+/// use rpn2tex::span::Span;
+///
+/// // (5 + 3) * 2
+/// let expr = Expr::BinaryOp {
+///     span: Span::new(6, 7, 1, 7),
+///     operator: "*".to_string(),
+///     left: Box::new(Expr::BinaryOp {
+///         span: Span::new(4, 5, 1, 5),
+///         operator: "+".to_string(),
+///         left: Box::new(Expr::Number {
+///             span: Span::new(0, 1, 1, 1),
+///             value: "5".to_string(),
+///         }),
+///         right: Box::new(Expr::Number {
+///             span: Span::new(2, 3, 1, 3),
+///             value: "3".to_string(),
+///         }),
+///     }),
+///     right: Box::new(Expr::Number {
+///         span: Span::new(8, 9, 1, 9),
+///         value: "2".to_string(),
+///     }),
+/// };
+///
+/// assert_eq!(PythonGenerator::new().generate(&expr), "(5 + 3) * 2");
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PythonGenerator;
+
+impl PythonGenerator {
+    /// Creates a new Python generator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::python::PythonGenerator;
+    ///
+    /// let generator = PythonGenerator::new();
+    /// ```
+    #[must_use]
+    pub const fn new() -> Self {
+        Self
+    }
+
+    /// Generates a Python expression from an AST expression.
+    ///
+    /// # Arguments
+    ///
+    /// * `ast` - The root expression node to convert
+    #[must_use]
+    pub fn generate(&self, ast: &Expr) -> String {
+        self.visit(ast)
+    }
+
+    /// Visits an expression node and generates its Python representation.
+    fn visit(&self, node: &Expr) -> String {
+        match node {
+            Expr::Number { value, .. } => Self::number_literal(value),
+            Expr::Variable { name, .. } => name.clone(),
+            Expr::BinaryOp {
+                operator,
+                left,
+                right,
+                ..
+            } => {
+                let mut left_text = self.visit(left);
+                if precedence::needs_parens(left, operator, false) {
+                    left_text = format!("({})", left_text);
+                }
+
+                let mut right_text = self.visit(right);
+                if precedence::needs_parens(right, operator, true) {
+                    right_text = format!("({})", right_text);
+                }
+
+                format!("{} {} {}", left_text, operator, right_text)
+            }
+            // A custom operator becomes a call of a function the caller
+            // must define
+            Expr::Apply {
+                operator, operands, ..
+            } => {
+                let operands: Vec<String> = operands.iter().map(|o| self.visit(o)).collect();
+                format!("{}({})", operator, operands.join(", "))
+            }
+        }
+    }
+
+    /// Converts a number literal to valid Python syntax.
+    ///
+    /// Python 3 rejects integer literals with leading zeros (`007`), so those
+    /// are stripped from integers. Decimal literals are valid as written.
+    fn number_literal(value: &str) -> String {
+        if value.contains('.') {
+            return value.to_string();
+        }
+
+        let (sign, digits) = match value.strip_prefix('-') {
+            Some(digits) => ("-", digits),
+            None => ("", value),
+        };
+        let trimmed = digits.trim_start_matches('0');
+        if trimmed.is_empty() {
+            format!("{}0", sign)
+        } else {
+            format!("{}{}", sign, trimmed)
+        }
+    }
+}
+
+impl CodeGenerator for PythonGenerator {
+    fn generate(&self, expr: &Expr) -> String {
+        PythonGenerator::generate(self, expr)
+    }
+
+    /// Rejects custom operators that are not Python identifiers, since they
+    /// are written as calls of functions named after them.
+    fn try_generate(&self, expr: &Expr) -> Result<String, GenerationError> {
+        check_calls(expr)?;
+        Ok(PythonGenerator::generate(self, expr))
+    }
+}
+
+/// Checks that every custom operator in `expr` can be called by name.
+fn check_calls(expr: &Expr) -> Result<(), GenerationError> {
+    match expr {
+        Expr::Number { .. } | Expr::Variable { .. } => Ok(()),
+        Expr::BinaryOp { left, right, .. } => {
+            check_calls(left)?;
+            check_calls(right)
+        }
+        Expr::Apply {
+            span,
+            operator,
+            operands,
+        } => {
+            let mut chars = operator.chars();
+            let identifier = chars
+                .next()
+                .is_some_and(|first| first == '_' || first.is_alphabetic())
+                && chars.all(|ch| ch == '_' || ch.is_alphanumeric());
+            if !identifier {
+                return Err(GenerationError::new(
+                    format!("Operator '{}' is not a Python function name", operator),
+                    *span,
+                ));
+            }
+            operands.iter().try_for_each(check_calls)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn generate(input: &str) -> String {
+        let tokens = Lexer::new(input).tokenize().expect("tokenize failed");
+        let ast = Parser::new(tokens).parse().expect("parse failed");
+        PythonGenerator::new().generate(&ast)
+    }
+
+    #[test]
+    fn test_basic_operators() {
+        assert_eq!(generate("5 3 +"), "5 + 3");
+        assert_eq!(generate("5 3 -"), "5 - 3");
+        assert_eq!(generate("4 7 *"), "4 * 7");
+        assert_eq!(generate("10 2 /"), "10 / 2");
+    }
+
+    #[test]
+    fn test_precedence() {
+        assert_eq!(generate("5 3 + 2 *"), "(5 + 3) * 2");
+        assert_eq!(generate("2 3 4 * +"), "2 + 3 * 4");
+        assert_eq!(generate("1 2 + 3 4 + *"), "(1 + 2) * (3 + 4)");
+        assert_eq!(generate("10 2 / 3 + 4 *"), "(10 / 2 + 3) * 4");
+    }
+
+    #[test]
+    fn test_right_associativity_grouping() {
+        assert_eq!(generate("5 3 2 - -"), "5 - (3 - 2)");
+        assert_eq!(generate("8 4 2 / /"), "8 / (4 / 2)");
+    }
+
+    #[test]
+    fn test_negative_operand() {
+        assert_eq!(generate("10 -5 +"), "10 + -5");
+    }
+
+    #[test]
+    fn test_number_literal_leading_zeros() {
+        assert_eq!(PythonGenerator::number_literal("007"), "7");
+        assert_eq!(PythonGenerator::number_literal("-007"), "-7");
+        assert_eq!(PythonGenerator::number_literal("000"), "0");
+        assert_eq!(PythonGenerator::number_literal("00.5"), "00.5");
+        assert_eq!(PythonGenerator::number_literal("3.14"), "3.14");
+    }
+}
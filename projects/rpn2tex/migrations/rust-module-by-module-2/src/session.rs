@@ -0,0 +1,190 @@
+//! Line-by-line conversion with a persistent stack, like an RPN calculator.
+//!
+//! A [`Session`] keeps the values left by each line on its stack, so input
+//! can be entered one line at a time: `5 3` then `+` then `2 *` builds
+//! `(5 + 3) * 2`. After every line it renders the value on top of the stack.
+
+use crate::ast::Expr;
+use crate::error::Rpn2TexError;
+use crate::latex::LaTeXGenerator;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+
+/// A conversion session holding a value stack across lines.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::session::Session;
+///
+/// let mut session = Session::new();
+/// assert_eq!(session.push_line("5 3").unwrap(), Some("$3$".to_string()));
+/// assert_eq!(session.push_line("+").unwrap(), Some("$5 + 3$".to_string()));
+/// assert_eq!(session.push_line("2 *").unwrap(), Some("$( 5 + 3 ) \\times 2$".to_string()));
+///
+/// // A failed line leaves the stack as it was
+/// let error = session.push_line("-").unwrap_err();
+/// assert_eq!((error.line(), error.column()), (4, 1));
+/// assert_eq!(session.stack().len(), 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Session {
+    /// Values left by the lines so far, the top last
+    stack: Vec<Expr>,
+    /// Renders the top of the stack
+    generator: LaTeXGenerator,
+    /// Whether lines may contain variable names
+    identifiers: bool,
+    /// Number of lines pushed so far
+    lines: u32,
+    /// Byte offset of the next line, counting a newline after each line
+    offset: usize,
+}
+
+impl Session {
+    /// Creates a session with an empty stack and the default LaTeX output.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            stack: Vec::new(),
+            generator: LaTeXGenerator::new(),
+            identifiers: false,
+            lines: 0,
+            offset: 0,
+        }
+    }
+
+    /// Returns this session rendering with `generator`.
+    #[must_use]
+    pub fn with_generator(mut self, generator: LaTeXGenerator) -> Self {
+        self.generator = generator;
+        self
+    }
+
+    /// Returns this session accepting variable names (`x`, `rate2`) in its
+    /// lines.
+    #[must_use]
+    pub fn with_identifiers(mut self, identifiers: bool) -> Self {
+        self.identifiers = identifiers;
+        self
+    }
+
+    /// Applies one line of input to the stack and returns the LaTeX for the
+    /// value now on top, or `None` if the stack is empty.
+    ///
+    /// Errors are positioned as if the lines pushed so far formed one
+    /// input, one per line.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`Rpn2TexError`] if the line fails to lex, or an operator
+    /// lacks operands. The stack is then left as it was before the line.
+    pub fn push_line(&mut self, line: &str) -> Result<Option<String>, Rpn2TexError> {
+        self.lines += 1;
+        let start = self.offset;
+        self.offset += line.len() + 1;
+
+        let tokens = Lexer::new(line)
+            .with_start(start, self.lines, 1)
+            .with_identifiers(self.identifiers)
+            .tokenize()?;
+        Parser::new(tokens).parse_onto(&mut self.stack)?;
+        Ok(self.top().map(|expr| self.generator.generate(expr)))
+    }
+
+    /// Returns the value on top of the stack, if any.
+    #[must_use]
+    pub fn top(&self) -> Option<&Expr> {
+        self.stack.last()
+    }
+
+    /// Returns the values on the stack, the top last.
+    #[must_use]
+    pub fn stack(&self) -> &[Expr] {
+        &self.stack
+    }
+
+    /// Returns the number of lines pushed so far, including failed ones.
+    #[must_use]
+    pub fn lines(&self) -> u32 {
+        self.lines
+    }
+
+    /// Empties the stack. Line numbering continues.
+    pub fn clear(&mut self) {
+        self.stack.clear();
+    }
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::latex::MulStyle;
+
+    #[test]
+    fn test_stack_persists_across_lines() {
+        let mut session = Session::new();
+        assert_eq!(session.push_line("").unwrap(), None);
+        session.push_line("1 2").unwrap();
+        session.push_line("3").unwrap();
+        assert_eq!(session.stack().len(), 3);
+        assert_eq!(
+            session.push_line("+ +").unwrap(),
+            Some("$1 + 2 + 3$".to_string())
+        );
+        assert_eq!(session.stack().len(), 1);
+    }
+
+    #[test]
+    fn test_error_positions() {
+        let mut session = Session::new();
+        session.push_line("5 3").unwrap();
+        let error = session.push_line("+ @").unwrap_err();
+        assert!(matches!(error, Rpn2TexError::Lexer(_)));
+        assert_eq!((error.line(), error.column()), (2, 3));
+        // Nothing from the failed line was applied
+        assert_eq!(session.stack().len(), 2);
+        assert_eq!(session.lines(), 2);
+    }
+
+    #[test]
+    fn test_failed_line_is_undone() {
+        let mut session = Session::new().with_identifiers(true);
+        session.push_line("x 5 3").unwrap();
+        let before = session.stack().to_vec();
+        // Every value is combined into one before `-` lacks an operand
+        let error = session.push_line("+ 2 * * -").unwrap_err();
+        assert_eq!(error.message(), "Too few operands for operator");
+        assert_eq!(session.stack(), before);
+        assert_eq!(session.push_line("+").unwrap(), Some("$5 + 3$".to_string()));
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut session = Session::new();
+        session.push_line("5 3").unwrap();
+        session.clear();
+        assert!(session.top().is_none());
+        let error = session.push_line("+").unwrap_err();
+        assert_eq!(error.message(), "Too few operands for operator");
+        assert_eq!(error.line(), 2);
+    }
+
+    #[test]
+    fn test_configuration() {
+        let generator = LaTeXGenerator::builder().mul_style(MulStyle::Cdot).build();
+        let mut session = Session::new()
+            .with_generator(generator)
+            .with_identifiers(true);
+        assert_eq!(
+            session.push_line("x 2 *").unwrap(),
+            Some("$x \\cdot 2$".to_string())
+        );
+    }
+}
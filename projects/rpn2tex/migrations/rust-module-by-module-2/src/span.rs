@@ -0,0 +1,86 @@
+//! Source locations of tokens and expression nodes.
+//!
+//! A [`Span`] records both the byte range a token occupies in the source and
+//! the 1-based line and column where it starts, so errors can underline the
+//! whole token and output can be mapped back to the input.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The location of a piece of source text.
+///
+/// `start` and `end` are byte offsets into the whole source, with `end`
+/// exclusive; `line` and `column` are the 1-based position of `start`.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::span::Span;
+///
+/// let source = "5 3.14 *";
+/// let span = Span::new(2, 6, 1, 3);
+/// assert_eq!(span.text(source), "3.14");
+/// assert_eq!(span.len(), 4);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Span {
+    /// Byte offset of the first character
+    pub start: usize,
+    /// Byte offset just past the last character
+    pub end: usize,
+    /// 1-based line number of the first character
+    pub line: u32,
+    /// 1-based column number of the first character
+    pub column: u32,
+}
+
+impl Span {
+    /// Creates a span covering `start..end`, beginning at `line` and
+    /// `column`.
+    #[must_use]
+    pub const fn new(start: usize, end: usize, line: u32, column: u32) -> Self {
+        Self {
+            start,
+            end,
+            line,
+            column,
+        }
+    }
+
+    /// Returns the length of the span in bytes.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.end.saturating_sub(self.start)
+    }
+
+    /// Returns `true` if the span covers no text, as for the EOF token.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the text the span covers in `source`, or an empty string if
+    /// the span lies outside it.
+    #[must_use]
+    pub fn text<'a>(&self, source: &'a str) -> &'a str {
+        source.get(self.start..self.end).unwrap_or("")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_span() {
+        let eof = Span::new(5, 5, 1, 6);
+        assert!(eof.is_empty());
+        assert_eq!(eof.text("5 3 +"), "");
+    }
+
+    #[test]
+    fn test_text_outside_source() {
+        assert_eq!(Span::new(3, 9, 1, 4).text("5 3"), "");
+    }
+}
@@ -0,0 +1,18 @@
+//! Helpers shared by the unit tests of several modules.
+
+use crate::ast::Expr;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+
+/// Parses one RPN expression, with variables allowed.
+///
+/// # Panics
+///
+/// Panics if `input` fails to lex or parse.
+pub(crate) fn parse(input: &str) -> Expr {
+    let tokens = Lexer::new(input)
+        .with_identifiers(true)
+        .tokenize()
+        .expect("tokenize failed");
+    Parser::new(tokens).parse().expect("parse failed")
+}
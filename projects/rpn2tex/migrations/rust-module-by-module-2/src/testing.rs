@@ -0,0 +1,161 @@
+//! Random expressions for property tests and fuzzing, behind the `testing`
+//! feature.
+//!
+//! [`Expr`] implements [`arbitrary::Arbitrary`], for fuzzers such as
+//! `cargo fuzz`, and [`exprs`] is the equivalent [`proptest`] strategy.
+//! [`Rpn`] and [`rpn_strings`] give the same trees written as RPN, which
+//! always parse when the lexer reads identifiers.
+//!
+//! Trees are at most [`MAX_DEPTH`] levels deep and use only the built-in
+//! operators. Numbers are non-negative decimals, such as `42` or `3.14`,
+//! and variables are single lowercase letters.
+//!
+//! # Examples
+//!
+//! ```
+//! use proptest::prelude::*;
+//! use rpn2tex::lexer::Lexer;
+//! use rpn2tex::parser::Parser;
+//! use rpn2tex::testing::rpn_strings;
+//!
+//! proptest!(|(input in rpn_strings())| {
+//!     let tokens = Lexer::new(&input).with_identifiers(true).tokenize().unwrap();
+//!     prop_assert!(Parser::new(tokens).parse().is_ok());
+//! });
+//! ```
+
+use crate::ast::Expr;
+use crate::notation::{Notation, NotationGenerator};
+use arbitrary::{Arbitrary, Unstructured};
+use proptest::prelude::*;
+
+/// The deepest tree generated, counting the leaves as one level.
+pub const MAX_DEPTH: u32 = 6;
+
+/// The letters variables are named by.
+const LETTERS: [char; 26] = [
+    'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 's',
+    't', 'u', 'v', 'w', 'x', 'y', 'z',
+];
+
+/// An RPN expression that parses when identifiers are read, generated by
+/// writing an arbitrary [`Expr`] in postfix notation.
+///
+/// # Examples
+///
+/// ```
+/// use arbitrary::{Arbitrary, Unstructured};
+/// use rpn2tex::testing::Rpn;
+///
+/// let Rpn(input) = Rpn::arbitrary(&mut Unstructured::new(&[7, 1, 2, 3, 4])).unwrap();
+/// assert!(!input.is_empty());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rpn(pub String);
+
+impl<'a> Arbitrary<'a> for Expr {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        arbitrary_expr(u, MAX_DEPTH)
+    }
+}
+
+impl<'a> Arbitrary<'a> for Rpn {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Self(postfix(&Expr::arbitrary(u)?)))
+    }
+}
+
+/// Returns a strategy generating expressions as [`Arbitrary`] does, and
+/// shrinking them towards single leaves.
+pub fn exprs() -> impl Strategy<Value = Expr> {
+    let leaf = prop_oneof![
+        (0..1000u32, proptest::option::of(1..100u32))
+            .prop_map(|(integer, fraction)| number(integer, fraction)),
+        proptest::sample::select(&LETTERS[..]).prop_map(Expr::var),
+    ];
+    leaf.prop_recursive(MAX_DEPTH - 1, 64, 2, |operand| {
+        (0..4u8, operand.clone(), operand)
+            .prop_map(|(operator, left, right)| binary(operator, left, right))
+    })
+}
+
+/// Returns a strategy generating RPN expressions as [`Rpn`] does.
+pub fn rpn_strings() -> impl Strategy<Value = String> {
+    exprs().prop_map(|expr| postfix(&expr))
+}
+
+/// Generates a tree at most `depth` levels deep.
+fn arbitrary_expr(u: &mut Unstructured<'_>, depth: u32) -> arbitrary::Result<Expr> {
+    if depth > 1 && u.ratio(1, 2)? {
+        let operator = u.int_in_range(0..=3)?;
+        let left = arbitrary_expr(u, depth - 1)?;
+        let right = arbitrary_expr(u, depth - 1)?;
+        return Ok(binary(operator, left, right));
+    }
+    if u.ratio(1, 4)? {
+        return Ok(Expr::var(*u.choose(&LETTERS)?));
+    }
+    let integer = u.int_in_range(0..=999)?;
+    let fraction = if u.arbitrary()? {
+        Some(u.int_in_range(1..=99)?)
+    } else {
+        None
+    };
+    Ok(number(integer, fraction))
+}
+
+/// Builds a number node, with `fraction` as its digits after the point.
+fn number(integer: u32, fraction: Option<u32>) -> Expr {
+    match fraction {
+        Some(fraction) => Expr::num(format!("{}.{}", integer, fraction)),
+        None => Expr::num(integer),
+    }
+}
+
+/// Builds the operation numbered `operator` among `+`, `-`, `*` and `/`.
+fn binary(operator: u8, left: Expr, right: Expr) -> Expr {
+    match operator {
+        0 => left + right,
+        1 => left - right,
+        2 => left * right,
+        _ => left / right,
+    }
+}
+
+/// Writes `expr` as RPN.
+fn postfix(expr: &Expr) -> String {
+    NotationGenerator::new(Notation::Postfix).generate(expr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::parse;
+
+    fn depth(expr: &Expr) -> u32 {
+        match expr {
+            Expr::BinaryOp { left, right, .. } => 1 + depth(left).max(depth(right)),
+            _ => 1,
+        }
+    }
+
+    #[test]
+    fn test_arbitrary_exprs_parse_back() {
+        let data: Vec<u8> = (0..4096u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+            .collect();
+        let mut u = Unstructured::new(&data);
+        while !u.is_empty() {
+            let expr = Expr::arbitrary(&mut u).unwrap();
+            assert!(depth(&expr) <= MAX_DEPTH);
+            assert!(parse(&postfix(&expr)).structurally_eq(&expr));
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_rpn_strings_parse_back(input in rpn_strings()) {
+            prop_assert_eq!(postfix(&parse(&input)), input);
+        }
+    }
+}
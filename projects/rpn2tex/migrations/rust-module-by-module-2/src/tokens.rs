@@ -3,6 +3,10 @@
 //! This module defines the token types and token data structure used by the lexer
 //! to represent individual lexical units in RPN expressions.
 
+use crate::span::Span;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::fmt;
 
 /// Represents the different types of tokens in RPN expressions.
@@ -10,15 +14,24 @@ use std::fmt;
 /// # Variants
 ///
 /// * `Number` - Numeric literals (integers and decimals)
+/// * `Identifier` - Variable names (`x`, `rate2`)
 /// * `Plus` - Addition operator (+)
 /// * `Minus` - Subtraction operator (-)
 /// * `Mult` - Multiplication operator (*)
 /// * `Div` - Division operator (/)
+/// * `Operator` - A custom operator registered with the lexer
+/// * `LeftParen`, `RightParen` - Parentheses, only produced for infix input
+/// * `Whitespace` - A run of whitespace, only produced in trivia mode
+/// * `Comment` - A comment from `#` to the end of the line, only produced in
+///   trivia mode
 /// * `Eof` - End of file marker
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum TokenType {
     /// Numeric literals (integers and decimals)
     Number,
+    /// Variable names: an ASCII letter followed by letters and digits
+    Identifier,
     /// Addition operator (+)
     Plus,
     /// Subtraction operator (-)
@@ -27,81 +40,137 @@ pub enum TokenType {
     Mult,
     /// Division operator (/)
     Div,
+    /// A custom operator, read by a lexer
+    /// [`with_operators`](crate::lexer::Lexer::with_operators) set; the
+    /// value is its spelling
+    Operator,
+    /// Opening parenthesis, only read by a lexer
+    /// [`with_infix`](crate::lexer::Lexer::with_infix) set
+    LeftParen,
+    /// Closing parenthesis, only read by a lexer
+    /// [`with_infix`](crate::lexer::Lexer::with_infix) set
+    RightParen,
+    /// Spaces, tabs and line breaks, kept by
+    /// [`Lexer::with_trivia`](crate::lexer::Lexer::with_trivia)
+    Whitespace,
+    /// A comment, `#` up to but not including the `\n` or `\r\n`, kept by
+    /// [`Lexer::with_trivia`](crate::lexer::Lexer::with_trivia)
+    Comment,
     /// End of file marker
     Eof,
 }
 
+impl TokenType {
+    /// Returns `true` for whitespace and comments, which carry no meaning
+    /// and are skipped by the parser.
+    #[must_use]
+    pub const fn is_trivia(self) -> bool {
+        matches!(self, Self::Whitespace | Self::Comment)
+    }
+}
+
 /// Represents a single lexical token with position information.
 ///
 /// Tokens are immutable and contain the token type, string value,
-/// and position information (line and column) for error reporting.
+/// and the [`Span`] of source text they were read from.
+///
+/// The value borrows from the source text when the lexer produces the
+/// token, so tokenizing allocates nothing per token. Use
+/// [`into_owned`](Self::into_owned) to detach a token from its source, e.g.
+/// to keep it in an error or pass it across an FFI boundary.
 ///
 /// # Fields
 ///
 /// * `token_type` - The type of the token
 /// * `value` - The string value of the token
-/// * `line` - 1-based line number where the token appears
-/// * `column` - 1-based column number where the token starts
+/// * `span` - Where the token appears in the source
 ///
 /// # Examples
 ///
 /// ```
+/// use rpn2tex::span::Span;
 /// use rpn2tex::tokens::{Token, TokenType};
 ///
-/// let token = Token::new(TokenType::Number, "42".to_string(), 1, 1);
+/// let token = Token::new(TokenType::Number, "42", Span::new(0, 2, 1, 1));
 /// assert_eq!(token.token_type, TokenType::Number);
 /// assert_eq!(token.value, "42");
-/// assert_eq!(token.line, 1);
-/// assert_eq!(token.column, 1);
+/// assert_eq!(token.span.line, 1);
+/// assert_eq!(token.span.column, 1);
 /// ```
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Token {
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Token<'a> {
     /// The type of the token
     pub token_type: TokenType,
     /// The string value of the token
-    pub value: String,
-    /// 1-based line number where the token appears
-    pub line: u32,
-    /// 1-based column number where the token starts
-    pub column: u32,
+    pub value: Cow<'a, str>,
+    /// Where the token appears in the source
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub span: Span,
 }
 
-impl Token {
+/// A token that owns its value and does not borrow any source text.
+pub type OwnedToken = Token<'static>;
+
+impl<'a> Token<'a> {
     /// Creates a new token with the specified type, value, and position.
     ///
     /// # Arguments
     ///
     /// * `token_type` - The type of the token
     /// * `value` - The string value of the token
-    /// * `line` - 1-based line number (must be >= 1)
-    /// * `column` - 1-based column number (must be >= 1)
+    /// * `span` - Where the token appears in the source
     ///
     /// # Examples
     ///
     /// ```
+    /// use rpn2tex::span::Span;
     /// use rpn2tex::tokens::{Token, TokenType};
     ///
-    /// let token = Token::new(TokenType::Plus, "+".to_string(), 1, 5);
+    /// let token = Token::new(TokenType::Plus, "+", Span::new(4, 5, 1, 5));
     /// assert_eq!(token.token_type, TokenType::Plus);
     /// assert_eq!(token.value, "+");
     /// ```
     #[must_use]
-    pub fn new(token_type: TokenType, value: String, line: u32, column: u32) -> Self {
+    pub fn new(token_type: TokenType, value: impl Into<Cow<'a, str>>, span: Span) -> Self {
         Self {
             token_type,
-            value,
-            line,
-            column,
+            value: value.into(),
+            span,
+        }
+    }
+
+    /// Converts the token into one that owns its value, copying the value
+    /// if it is borrowed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::span::Span;
+    /// use rpn2tex::tokens::{OwnedToken, Token, TokenType};
+    ///
+    /// let source = String::from("42");
+    /// let span = Span::new(0, 2, 1, 1);
+    /// let token: OwnedToken = Token::new(TokenType::Number, source.as_str(), span).into_owned();
+    /// drop(source);
+    /// assert_eq!(token.value, "42");
+    /// ```
+    #[must_use]
+    pub fn into_owned(self) -> OwnedToken {
+        Token {
+            token_type: self.token_type,
+            value: Cow::Owned(self.value.into_owned()),
+            span: self.span,
         }
     }
 }
 
-impl fmt::Display for Token {
+impl fmt::Display for Token<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
             "Token({:?}, '{}', line={}, column={})",
-            self.token_type, self.value, self.line, self.column
+            self.token_type, self.value, self.span.line, self.span.column
         )
     }
 }
@@ -116,38 +185,46 @@ mod tests {
         assert_ne!(TokenType::Number, TokenType::Plus);
     }
 
+    #[test]
+    fn test_is_trivia() {
+        assert!(TokenType::Whitespace.is_trivia());
+        assert!(TokenType::Comment.is_trivia());
+        assert!(!TokenType::Number.is_trivia());
+        assert!(!TokenType::Eof.is_trivia());
+    }
+
     #[test]
     fn test_token_creation() {
-        let token = Token::new(TokenType::Number, "42".to_string(), 1, 1);
+        let token = Token::new(TokenType::Number, "42", Span::new(0, 2, 1, 1));
         assert_eq!(token.token_type, TokenType::Number);
         assert_eq!(token.value, "42");
-        assert_eq!(token.line, 1);
-        assert_eq!(token.column, 1);
+        assert_eq!(token.span.line, 1);
+        assert_eq!(token.span.column, 1);
     }
 
     #[test]
     fn test_token_with_operator() {
-        let token = Token::new(TokenType::Plus, "+".to_string(), 1, 5);
+        let token = Token::new(TokenType::Plus, "+", Span::new(4, 5, 1, 5));
         assert_eq!(token.token_type, TokenType::Plus);
         assert_eq!(token.value, "+");
-        assert_eq!(token.line, 1);
-        assert_eq!(token.column, 5);
+        assert_eq!(token.span.line, 1);
+        assert_eq!(token.span.column, 5);
     }
 
     #[test]
     fn test_token_with_decimal_number() {
-        let token = Token::new(TokenType::Number, "3.14".to_string(), 2, 10);
+        let token = Token::new(TokenType::Number, "3.14", Span::new(15, 19, 2, 10));
         assert_eq!(token.token_type, TokenType::Number);
         assert_eq!(token.value, "3.14");
-        assert_eq!(token.line, 2);
-        assert_eq!(token.column, 10);
+        assert_eq!(token.span.line, 2);
+        assert_eq!(token.span.column, 10);
     }
 
     #[test]
     fn test_token_equality() {
-        let token1 = Token::new(TokenType::Number, "42".to_string(), 1, 1);
-        let token2 = Token::new(TokenType::Number, "42".to_string(), 1, 1);
-        let token3 = Token::new(TokenType::Number, "43".to_string(), 1, 1);
+        let token1 = Token::new(TokenType::Number, "42", Span::new(0, 2, 1, 1));
+        let token2 = Token::new(TokenType::Number, "42", Span::new(0, 2, 1, 1));
+        let token3 = Token::new(TokenType::Number, "43", Span::new(0, 2, 1, 1));
 
         assert_eq!(token1, token2);
         assert_ne!(token1, token3);
@@ -155,7 +232,7 @@ mod tests {
 
     #[test]
     fn test_token_display() {
-        let token = Token::new(TokenType::Plus, "+".to_string(), 1, 5);
+        let token = Token::new(TokenType::Plus, "+", Span::new(4, 5, 1, 5));
         let display = format!("{}", token);
         assert!(display.contains("Plus"));
         assert!(display.contains("+"));
@@ -167,6 +244,7 @@ mod tests {
     fn test_all_token_types() {
         let types = [
             TokenType::Number,
+            TokenType::Identifier,
             TokenType::Plus,
             TokenType::Minus,
             TokenType::Mult,
@@ -181,16 +259,27 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_token_borrows_and_owns() {
+        let source = String::from("5 3 +");
+        let token = Token::new(TokenType::Number, &source[2..3], Span::new(2, 3, 1, 3));
+        assert!(matches!(token.value, Cow::Borrowed("3")));
+
+        let owned = token.clone().into_owned();
+        assert!(matches!(owned.value, Cow::Owned(_)));
+        assert_eq!(owned, token);
+    }
+
     #[test]
     fn test_eof_token() {
-        let token = Token::new(TokenType::Eof, String::new(), 1, 10);
+        let token = Token::new(TokenType::Eof, "", Span::new(9, 9, 1, 10));
         assert_eq!(token.token_type, TokenType::Eof);
         assert_eq!(token.value, "");
     }
 
     #[test]
     fn test_negative_number_token() {
-        let token = Token::new(TokenType::Number, "-42".to_string(), 1, 1);
+        let token = Token::new(TokenType::Number, "-42", Span::new(0, 3, 1, 1));
         assert_eq!(token.token_type, TokenType::Number);
         assert_eq!(token.value, "-42");
     }
@@ -198,30 +287,43 @@ mod tests {
     #[test]
     fn test_token_position_tracking() {
         // Test that position information is preserved correctly
-        let tokens = vec![
-            Token::new(TokenType::Number, "5".to_string(), 1, 1),
-            Token::new(TokenType::Number, "3".to_string(), 1, 3),
-            Token::new(TokenType::Plus, "+".to_string(), 1, 5),
+        let tokens = [
+            Token::new(TokenType::Number, "5", Span::new(0, 1, 1, 1)),
+            Token::new(TokenType::Number, "3", Span::new(2, 3, 1, 3)),
+            Token::new(TokenType::Plus, "+", Span::new(4, 5, 1, 5)),
         ];
 
-        assert_eq!(tokens[0].line, 1);
-        assert_eq!(tokens[0].column, 1);
-        assert_eq!(tokens[1].line, 1);
-        assert_eq!(tokens[1].column, 3);
-        assert_eq!(tokens[2].line, 1);
-        assert_eq!(tokens[2].column, 5);
+        assert_eq!(tokens[0].span.line, 1);
+        assert_eq!(tokens[0].span.column, 1);
+        assert_eq!(tokens[1].span.line, 1);
+        assert_eq!(tokens[1].span.column, 3);
+        assert_eq!(tokens[2].span.line, 1);
+        assert_eq!(tokens[2].span.column, 5);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_token_json_round_trip() {
+        let token = Token::new(TokenType::Number, "3.14", Span::new(12, 16, 2, 7));
+        let json = serde_json::to_string(&token).expect("serialize failed");
+        assert_eq!(
+            json,
+            r#"{"token_type":"Number","value":"3.14","start":12,"end":16,"line":2,"column":7}"#
+        );
+        let back: Token = serde_json::from_str(&json).expect("deserialize failed");
+        assert_eq!(back, token);
     }
 
     #[test]
     fn test_multiline_token_positions() {
-        let tokens = vec![
-            Token::new(TokenType::Number, "5".to_string(), 1, 1),
-            Token::new(TokenType::Number, "3".to_string(), 2, 1),
-            Token::new(TokenType::Plus, "+".to_string(), 3, 1),
+        let tokens = [
+            Token::new(TokenType::Number, "5", Span::new(0, 1, 1, 1)),
+            Token::new(TokenType::Number, "3", Span::new(2, 3, 2, 1)),
+            Token::new(TokenType::Plus, "+", Span::new(4, 5, 3, 1)),
         ];
 
-        assert_eq!(tokens[0].line, 1);
-        assert_eq!(tokens[1].line, 2);
-        assert_eq!(tokens[2].line, 3);
+        assert_eq!(tokens[0].span.line, 1);
+        assert_eq!(tokens[1].span.line, 2);
+        assert_eq!(tokens[2].span.line, 3);
     }
 }
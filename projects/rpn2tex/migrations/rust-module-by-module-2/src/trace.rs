@@ -0,0 +1,126 @@
+//! Instrumentation of the conversion stages.
+//!
+//! With the `tracing` feature, [`convert`](crate::convert) and
+//! [`Compiler`](crate::Compiler) run the lexer, the parser and the
+//! generator each inside an `INFO` span named `lex`, `parse` or `generate`.
+//! The span records the stage's duration in microseconds as `elapsed_us`,
+//! and a stage that fails also emits a `WARN` event holding the error.
+//! Without the feature the stages run as they are, at no cost.
+
+/// Runs `$run`, an expression giving a `Result`, as the stage `$name`.
+macro_rules! stage {
+    ($name:literal, $run:expr) => {{
+        #[cfg(feature = "tracing")]
+        let result = {
+            let span = tracing::info_span!($name, elapsed_us = tracing::field::Empty);
+            let _entered = span.enter();
+            let start = std::time::Instant::now();
+            let result = $run;
+            let elapsed_us = u64::try_from(start.elapsed().as_micros()).unwrap_or(u64::MAX);
+            span.record("elapsed_us", elapsed_us);
+            if let Err(error) = &result {
+                tracing::warn!(%error, elapsed_us, "{} failed", $name);
+            }
+            result
+        };
+        #[cfg(not(feature = "tracing"))]
+        let result = $run;
+        result
+    }};
+}
+
+pub(crate) use stage;
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    /// What the subscriber saw: span names, recorded fields and events.
+    #[derive(Default)]
+    struct Seen {
+        spans: Vec<&'static str>,
+        fields: Vec<String>,
+        events: Vec<String>,
+    }
+
+    /// Writes every field as `name=value`.
+    struct Fields<'a>(&'a mut Vec<String>);
+
+    impl Visit for Fields<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0.push(format!("{}={:?}", field.name(), value));
+        }
+    }
+
+    /// A subscriber keeping what it sees.
+    struct Recorder {
+        seen: Arc<Mutex<Seen>>,
+        next_id: AtomicU64,
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            self.seen.lock().unwrap().spans.push(span.metadata().name());
+            Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed) + 1)
+        }
+
+        fn record(&self, _: &Id, values: &Record<'_>) {
+            values.record(&mut Fields(&mut self.seen.lock().unwrap().fields));
+        }
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            event.record(&mut Fields(&mut self.seen.lock().unwrap().events));
+        }
+
+        fn enter(&self, _: &Id) {}
+
+        fn exit(&self, _: &Id) {}
+    }
+
+    fn record(run: impl FnOnce()) -> Seen {
+        let seen = Arc::new(Mutex::new(Seen::default()));
+        let recorder = Recorder {
+            seen: Arc::clone(&seen),
+            next_id: AtomicU64::new(0),
+        };
+        tracing::subscriber::with_default(recorder, run);
+        let seen = std::mem::take(&mut *seen.lock().unwrap());
+        seen
+    }
+
+    #[test]
+    fn test_stages_are_spans() {
+        let seen = record(|| {
+            crate::compile("5 3 +").unwrap();
+        });
+        assert_eq!(seen.spans, ["lex", "parse", "generate"]);
+        assert_eq!(seen.fields.len(), 3);
+        assert!(seen
+            .fields
+            .iter()
+            .all(|field| field.starts_with("elapsed_us=")));
+        assert!(seen.events.is_empty());
+    }
+
+    #[test]
+    fn test_failures_are_events() {
+        let seen = record(|| {
+            crate::compile("5 +").unwrap_err();
+        });
+        assert_eq!(seen.spans, ["lex", "parse"]);
+        assert!(seen
+            .events
+            .iter()
+            .any(|field| field == "error=Line 1, column 3: Too few operands for operator"));
+    }
+}
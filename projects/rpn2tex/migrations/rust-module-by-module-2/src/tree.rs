@@ -0,0 +1,128 @@
+//! Indented text dumps of Abstract Syntax Trees.
+//!
+//! This module renders an AST as a tree drawn with box characters, one node
+//! per line with its source position, so it can be read in a terminal to
+//! check how an input was parsed.
+
+use crate::ast::Expr;
+use crate::codegen::CodeGenerator;
+
+/// Converts AST expressions to an indented tree listing.
+///
+/// Each line names a node's kind, its operator, value or name, and the
+/// `line:column` of the token it was built from. Operands follow their
+/// operator, the left one first.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::lexer::Lexer;
+/// use rpn2tex::parser::Parser;
+/// use rpn2tex::tree::TreeGenerator;
+///
+/// let ast = Parser::new(Lexer::new("5 3 + 2 *").tokenize().unwrap()).parse().unwrap();
+/// assert_eq!(
+///     TreeGenerator::new().generate(&ast),
+///     "BinaryOp * at 1:9\n\
+///      ├── BinaryOp + at 1:5\n\
+///      │   ├── Number 5 at 1:1\n\
+///      │   └── Number 3 at 1:3\n\
+///      └── Number 2 at 1:7\n"
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TreeGenerator;
+
+impl TreeGenerator {
+    /// Creates a new tree generator.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self
+    }
+
+    /// Generates the tree listing of an AST expression, one line per node,
+    /// each terminated by a newline.
+    #[must_use]
+    pub fn generate(&self, ast: &Expr) -> String {
+        let mut out = String::new();
+        Self::visit(ast, "", "", &mut out);
+        out
+    }
+
+    /// Writes the line for `node` after `prefix`, then its operands with
+    /// `indent` before their branches.
+    fn visit(node: &Expr, prefix: &str, indent: &str, out: &mut String) {
+        let span = node.span();
+        let label = match node {
+            Expr::Number { value, .. } => format!("Number {}", value),
+            Expr::Variable { name, .. } => format!("Variable {}", name),
+            Expr::BinaryOp { operator, .. } => format!("BinaryOp {}", operator),
+            Expr::Apply { operator, .. } => format!("Apply {}", operator),
+        };
+        out.push_str(&format!(
+            "{}{} at {}:{}\n",
+            prefix, label, span.line, span.column
+        ));
+
+        let operands: Vec<&Expr> = match node {
+            Expr::Number { .. } | Expr::Variable { .. } => Vec::new(),
+            Expr::BinaryOp { left, right, .. } => vec![left, right],
+            Expr::Apply { operands, .. } => operands.iter().collect(),
+        };
+        for (index, operand) in operands.iter().enumerate() {
+            let (branch, below) = if index + 1 == operands.len() {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            Self::visit(
+                operand,
+                &format!("{}{}", indent, branch),
+                &format!("{}{}", indent, below),
+                out,
+            );
+        }
+    }
+}
+
+impl CodeGenerator for TreeGenerator {
+    fn generate(&self, expr: &Expr) -> String {
+        TreeGenerator::generate(self, expr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn tree(source: &str) -> String {
+        let tokens = Lexer::new(source)
+            .with_identifiers(true)
+            .tokenize()
+            .unwrap();
+        TreeGenerator::new().generate(&Parser::new(tokens).parse().unwrap())
+    }
+
+    #[test]
+    fn test_single_node() {
+        assert_eq!(tree("  -1.5"), "Number -1.5 at 1:3\n");
+        assert_eq!(tree("rate"), "Variable rate at 1:1\n");
+    }
+
+    #[test]
+    fn test_right_nested_indentation() {
+        // 1 - (2 / (x + 3)), written over two lines
+        assert_eq!(
+            tree("1 2 x 3\n+ / -"),
+            "BinaryOp - at 2:5\n\
+             ├── Number 1 at 1:1\n\
+             └── BinaryOp / at 2:3\n\
+             \x20   ├── Number 2 at 1:3\n\
+             \x20   └── BinaryOp + at 2:1\n\
+             \x20       ├── Variable x at 1:5\n\
+             \x20       └── Number 3 at 1:7\n"
+        );
+    }
+}
@@ -0,0 +1,233 @@
+//! Wolfram Language (Mathematica) expression generation from ASTs.
+//!
+//! Expressions can be written either in `FullForm`, where every operation is
+//! an explicit head (`Times[Plus[5, 3], 2]`), or as infix input (`(5 + 3)*2`)
+//! using the same parenthesization rules as the LaTeX backend.
+
+use crate::ast::Expr;
+use crate::codegen::CodeGenerator;
+use crate::precedence;
+
+/// Syntax used when writing Wolfram Language expressions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum WolframStyle {
+    /// Explicit heads: `Times[Plus[5, 3], 2]` (the default)
+    #[default]
+    FullForm,
+    /// Infix operators with minimal parentheses: `(5 + 3)*2`
+    Infix,
+}
+
+/// Converts AST expressions to Wolfram Language syntax.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::ast::Expr;
+/// use rpn2tex::span::Span;
+/// use rpn2tex::wolfram::{WolframGenerator, WolframStyle};
+///
+/// // (5 + 3) * 2
+/// let expr = Expr::BinaryOp {
+///     span: Span::new(6, 7, 1, 7),
+///     operator: "*".to_string(),
+///     left: Box::new(Expr::BinaryOp {
+///         span: Span::new(4, 5, 1, 5),
+///         operator: "+".to_string(),
+///         left: Box::new(Expr::Number {
+///             span: Span::new(0, 1, 1, 1),
+///             value: "5".to_string(),
+///         }),
+///         right: Box::new(Expr::Number {
+///             span: Span::new(2, 3, 1, 3),
+///             value: "3".to_string(),
+///         }),
+///     }),
+///     right: Box::new(Expr::Number {
+///         span: Span::new(8, 9, 1, 9),
+///         value: "2".to_string(),
+///     }),
+/// };
+///
+/// let full_form = WolframGenerator::new(WolframStyle::FullForm);
+/// assert_eq!(full_form.generate(&expr), "Times[Plus[5, 3], 2]");
+///
+/// let infix = WolframGenerator::new(WolframStyle::Infix);
+/// assert_eq!(infix.generate(&expr), "(5 + 3)*2");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct WolframGenerator {
+    /// Syntax used for every operation
+    style: WolframStyle,
+}
+
+impl WolframGenerator {
+    /// Creates a generator producing the given syntax.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::wolfram::{WolframGenerator, WolframStyle};
+    ///
+    /// let generator = WolframGenerator::new(WolframStyle::Infix);
+    /// assert_eq!(generator.style(), WolframStyle::Infix);
+    /// ```
+    #[must_use]
+    pub const fn new(style: WolframStyle) -> Self {
+        Self { style }
+    }
+
+    /// Returns the syntax this generator produces.
+    #[must_use]
+    pub const fn style(&self) -> WolframStyle {
+        self.style
+    }
+
+    /// Generates Wolfram Language source for an AST expression.
+    ///
+    /// # Arguments
+    ///
+    /// * `ast` - The root expression node to convert
+    #[must_use]
+    pub fn generate(&self, ast: &Expr) -> String {
+        match self.style {
+            WolframStyle::FullForm => Self::visit_full_form(ast),
+            WolframStyle::Infix => Self::visit_infix(ast),
+        }
+    }
+
+    /// Writes a node as nested `Head[left, right]` calls.
+    fn visit_full_form(node: &Expr) -> String {
+        match node {
+            Expr::Number { value, .. } => value.clone(),
+            Expr::Variable { name, .. } => name.clone(),
+            Expr::BinaryOp {
+                operator,
+                left,
+                right,
+                ..
+            } => format!(
+                "{}[{}, {}]",
+                Self::operator_head(operator),
+                Self::visit_full_form(left),
+                Self::visit_full_form(right)
+            ),
+            Expr::Apply {
+                operator, operands, ..
+            } => Self::apply(operator, operands, Self::visit_full_form),
+        }
+    }
+
+    /// Writes a node using infix operators, grouping only where required.
+    fn visit_infix(node: &Expr) -> String {
+        match node {
+            Expr::Number { value, .. } => value.clone(),
+            Expr::Variable { name, .. } => name.clone(),
+            Expr::BinaryOp {
+                operator,
+                left,
+                right,
+                ..
+            } => {
+                let mut left_text = Self::visit_infix(left);
+                if precedence::needs_parens(left, operator, false) {
+                    left_text = format!("({})", left_text);
+                }
+
+                let mut right_text = Self::visit_infix(right);
+                if precedence::needs_parens(right, operator, true) {
+                    right_text = format!("({})", right_text);
+                }
+
+                // Wolfram's own InputForm spaces additive operators only
+                match operator.as_str() {
+                    "+" | "-" => format!("{} {} {}", left_text, operator, right_text),
+                    _ => format!("{}{}{}", left_text, operator, right_text),
+                }
+            }
+            Expr::Apply {
+                operator, operands, ..
+            } => Self::apply(operator, operands, Self::visit_infix),
+        }
+    }
+
+    /// Writes a custom operator as a call `operator[operand, ...]` of a
+    /// function the caller must define.
+    fn apply(operator: &str, operands: &[Expr], visit: fn(&Expr) -> String) -> String {
+        let operands: Vec<String> = operands.iter().map(visit).collect();
+        format!("{}[{}]", operator, operands.join(", "))
+    }
+
+    /// Returns the Wolfram Language head for an operator symbol.
+    ///
+    /// # Mappings
+    ///
+    /// * `+` → `Plus`
+    /// * `-` → `Subtract`
+    /// * `*` → `Times`
+    /// * `/` → `Divide`
+    fn operator_head(operator: &str) -> &str {
+        match operator {
+            "+" => "Plus",
+            "-" => "Subtract",
+            "*" => "Times",
+            "/" => "Divide",
+            _ => operator,
+        }
+    }
+}
+
+impl CodeGenerator for WolframGenerator {
+    fn generate(&self, expr: &Expr) -> String {
+        WolframGenerator::generate(self, expr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn generate(input: &str, style: WolframStyle) -> String {
+        let tokens = Lexer::new(input).tokenize().expect("tokenize failed");
+        let ast = Parser::new(tokens).parse().expect("parse failed");
+        WolframGenerator::new(style).generate(&ast)
+    }
+
+    #[test]
+    fn test_default_style_is_full_form() {
+        assert_eq!(WolframGenerator::default().style(), WolframStyle::FullForm);
+    }
+
+    #[test]
+    fn test_full_form_heads() {
+        assert_eq!(generate("5 3 +", WolframStyle::FullForm), "Plus[5, 3]");
+        assert_eq!(generate("5 3 -", WolframStyle::FullForm), "Subtract[5, 3]");
+        assert_eq!(generate("4 7 *", WolframStyle::FullForm), "Times[4, 7]");
+        assert_eq!(generate("10 2 /", WolframStyle::FullForm), "Divide[10, 2]");
+    }
+
+    #[test]
+    fn test_full_form_nesting() {
+        assert_eq!(
+            generate("10 2 / 3 + 4 *", WolframStyle::FullForm),
+            "Times[Plus[Divide[10, 2], 3], 4]"
+        );
+    }
+
+    #[test]
+    fn test_full_form_numbers() {
+        assert_eq!(generate("-3.14", WolframStyle::FullForm), "-3.14");
+    }
+
+    #[test]
+    fn test_infix_precedence() {
+        assert_eq!(generate("5 3 + 2 *", WolframStyle::Infix), "(5 + 3)*2");
+        assert_eq!(generate("2 3 4 * +", WolframStyle::Infix), "2 + 3*4");
+        assert_eq!(generate("5 3 - 2 -", WolframStyle::Infix), "5 - 3 - 2");
+        assert_eq!(generate("5 3 2 - -", WolframStyle::Infix), "5 - (3 - 2)");
+        assert_eq!(generate("100 10 / 5 /", WolframStyle::Infix), "100/10/5");
+        assert_eq!(generate("2 3 4 / /", WolframStyle::Infix), "2/(3/4)");
+    }
+}
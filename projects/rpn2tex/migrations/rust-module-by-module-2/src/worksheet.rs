@@ -0,0 +1,311 @@
+//! Worksheet documents of numbered problems.
+//!
+//! A worksheet lists expressions as numbered exercises, each followed by
+//! blank space for a handwritten answer, in a complete LaTeX document that
+//! can be printed as is. The problems are laid out in one or more columns.
+//! An answer key lists each problem with its result, as a page after the
+//! problems or as a document of its own.
+
+use crate::ast::Expr;
+use crate::latex::LaTeXGenerator;
+
+/// Generates worksheet documents from AST expressions.
+///
+/// # Examples
+///
+/// ```
+/// use rpn2tex::lexer::Lexer;
+/// use rpn2tex::parser::Parser;
+/// use rpn2tex::worksheet::WorksheetGenerator;
+///
+/// let problems: Vec<_> = ["5 3 +", "6 2 /"]
+///     .iter()
+///     .map(|source| Parser::new(Lexer::new(source).tokenize().unwrap()).parse().unwrap())
+///     .collect();
+/// let worksheet = WorksheetGenerator::new()
+///     .with_columns(1)
+///     .with_answer_space("1cm")
+///     .generate(&problems);
+/// assert_eq!(
+///     worksheet,
+///     "\\documentclass{article}\n\
+///      \\begin{document}\n\
+///      \\begin{enumerate}\n\
+///      \\item $5 + 3 = $\n\\vspace{1cm}\n\
+///      \\item $6 \\div 2 = $\n\\vspace{1cm}\n\
+///      \\end{enumerate}\n\
+///      \\end{document}\n"
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct WorksheetGenerator {
+    /// Renders each problem's math
+    math: LaTeXGenerator,
+    /// Number of columns the problems are set in
+    columns: u8,
+    /// Vertical space left after each problem, as a LaTeX length
+    answer_space: String,
+    /// Heading above the problems, as LaTeX
+    title: Option<String>,
+}
+
+impl WorksheetGenerator {
+    /// Creates a generator for two-column worksheets with 2cm of answer
+    /// space after each problem and no title.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            math: LaTeXGenerator::new(),
+            columns: 2,
+            answer_space: "2cm".to_string(),
+            title: None,
+        }
+    }
+
+    /// Returns this generator rendering problems with `generator`. Its math
+    /// environment is ignored: problems are always inline math.
+    #[must_use]
+    pub fn with_generator(mut self, generator: LaTeXGenerator) -> Self {
+        self.math = generator;
+        self
+    }
+
+    /// Returns this generator setting problems in `columns` columns, at
+    /// least one.
+    #[must_use]
+    pub fn with_columns(mut self, columns: u8) -> Self {
+        self.columns = columns.max(1);
+        self
+    }
+
+    /// Returns this generator leaving `space`, a LaTeX length such as `3em`,
+    /// after each problem.
+    #[must_use]
+    pub fn with_answer_space(mut self, space: impl Into<String>) -> Self {
+        self.answer_space = space.into();
+        self
+    }
+
+    /// Returns this generator heading the worksheet with `title`, written as
+    /// given so it may hold LaTeX markup; plain text should be passed
+    /// through [`escape`](crate::latex::escape) first.
+    #[must_use]
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    /// Returns the number of columns.
+    #[must_use]
+    pub fn columns(&self) -> u8 {
+        self.columns
+    }
+
+    /// Returns the space left after each problem.
+    #[must_use]
+    pub fn answer_space(&self) -> &str {
+        &self.answer_space
+    }
+
+    /// Returns the title, if any.
+    #[must_use]
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    /// Generates a worksheet document with `problems` numbered in order.
+    #[must_use]
+    pub fn generate(&self, problems: &[Expr]) -> String {
+        let mut document = self.preamble(self.columns > 1);
+        document.push_str(&self.problems(problems));
+        document.push_str("\\end{document}\n");
+        document
+    }
+
+    /// Generates a worksheet document followed, on a new page, by its
+    /// answer key. `answers` holds the result of each problem, in order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rpn2tex::lexer::Lexer;
+    /// use rpn2tex::parser::Parser;
+    /// use rpn2tex::worksheet::WorksheetGenerator;
+    ///
+    /// let problem = Parser::new(Lexer::new("5 3 +").tokenize().unwrap()).parse().unwrap();
+    /// let worksheet = WorksheetGenerator::new()
+    ///     .with_columns(1)
+    ///     .generate_with_answers(&[problem], &["8".to_string()]);
+    /// assert!(worksheet.ends_with(
+    ///     "\\end{enumerate}\n\
+    ///      \\newpage\n\
+    ///      \\section*{Answers}\n\
+    ///      \\begin{enumerate}\n\
+    ///      \\item $5 + 3 = 8$\n\
+    ///      \\end{enumerate}\n\
+    ///      \\end{document}\n"
+    /// ));
+    /// ```
+    #[must_use]
+    pub fn generate_with_answers(&self, problems: &[Expr], answers: &[String]) -> String {
+        let mut document = self.preamble(self.columns > 1);
+        document.push_str(&self.problems(problems));
+        document.push_str("\\newpage\n");
+        document.push_str(&self.answers(problems, answers, "Answers"));
+        document.push_str("\\end{document}\n");
+        document
+    }
+
+    /// Generates the answer key of a worksheet as a document of its own,
+    /// headed with the worksheet's title. `answers` holds the result of
+    /// each problem, in order.
+    #[must_use]
+    pub fn generate_answer_key(&self, problems: &[Expr], answers: &[String]) -> String {
+        let heading = match &self.title {
+            Some(title) => format!("{}: Answers", title),
+            None => "Answers".to_string(),
+        };
+        let mut document = self.preamble(false);
+        document.push_str(&self.answers(problems, answers, &heading));
+        document.push_str("\\end{document}\n");
+        document
+    }
+
+    /// Returns the document class and packages, with `multicol` if asked,
+    /// and the start of the document.
+    fn preamble(&self, multicol: bool) -> String {
+        let mut preamble = String::from("\\documentclass{article}\n");
+        if multicol {
+            preamble.push_str("\\usepackage{multicol}\n");
+        }
+        for package in self.math.required_packages() {
+            preamble.push_str(&format!("\\usepackage{{{}}}\n", package));
+        }
+        preamble.push_str("\\begin{document}\n");
+        preamble
+    }
+
+    /// Returns the title and the numbered problems, in columns.
+    fn problems(&self, problems: &[Expr]) -> String {
+        let mut document = String::new();
+        if let Some(title) = &self.title {
+            document.push_str(&format!("\\section*{{{}}}\n", title));
+        }
+        if self.columns > 1 {
+            document.push_str(&format!("\\begin{{multicols}}{{{}}}\n", self.columns));
+        }
+        document.push_str("\\begin{enumerate}\n");
+        for problem in problems {
+            document.push_str(&format!(
+                "\\item ${} = $\n\\vspace{{{}}}\n",
+                self.math.render_math(problem),
+                self.answer_space
+            ));
+        }
+        document.push_str("\\end{enumerate}\n");
+        if self.columns > 1 {
+            document.push_str("\\end{multicols}\n");
+        }
+        document
+    }
+
+    /// Returns a section headed `heading` listing each problem with its
+    /// answer, numbered like the problems.
+    fn answers(&self, problems: &[Expr], answers: &[String], heading: &str) -> String {
+        let mut section = format!("\\section*{{{}}}\n\\begin{{enumerate}}\n", heading);
+        for (problem, answer) in problems.iter().zip(answers) {
+            section.push_str(&format!(
+                "\\item ${} = {}$\n",
+                self.math.render_math(problem),
+                answer
+            ));
+        }
+        section.push_str("\\end{enumerate}\n");
+        section
+    }
+}
+
+impl Default for WorksheetGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::latex::{MathEnvironment, MulStyle};
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn problems(sources: &[&str]) -> Vec<Expr> {
+        sources
+            .iter()
+            .map(|source| {
+                Parser::new(Lexer::new(source).tokenize().unwrap())
+                    .parse()
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_columns_and_title() {
+        let worksheet = WorksheetGenerator::new()
+            .with_title("Practice \\#1")
+            .with_columns(3)
+            .generate(&problems(&["2 3 *"]));
+        assert_eq!(
+            worksheet,
+            "\\documentclass{article}\n\
+             \\usepackage{multicol}\n\
+             \\begin{document}\n\
+             \\section*{Practice \\#1}\n\
+             \\begin{multicols}{3}\n\
+             \\begin{enumerate}\n\
+             \\item $2 \\times 3 = $\n\\vspace{2cm}\n\
+             \\end{enumerate}\n\
+             \\end{multicols}\n\
+             \\end{document}\n"
+        );
+    }
+
+    #[test]
+    fn test_generator_settings() {
+        // Problems stay inline whatever the environment; packages carry over
+        let generator = LaTeXGenerator::builder()
+            .environment(MathEnvironment::Gather)
+            .mul_style(MulStyle::Cdot)
+            .build();
+        let worksheet = WorksheetGenerator::new()
+            .with_generator(generator)
+            .with_columns(0)
+            .generate(&problems(&["2 3 *"]));
+        assert!(worksheet.contains("\\usepackage{amsmath}\n"));
+        assert!(worksheet.contains("\\item $2 \\cdot 3 = $\n"));
+        assert!(!worksheet.contains("multicol"));
+    }
+
+    #[test]
+    fn test_answer_key() {
+        let generator = WorksheetGenerator::new().with_title("Quiz");
+        let problems = problems(&["5 3 +", "1 3 /"]);
+        let answers = ["8".to_string(), "1/3".to_string()];
+        assert_eq!(
+            generator.generate_answer_key(&problems, &answers),
+            "\\documentclass{article}\n\
+             \\begin{document}\n\
+             \\section*{Quiz: Answers}\n\
+             \\begin{enumerate}\n\
+             \\item $5 + 3 = 8$\n\
+             \\item $1 \\div 3 = 1/3$\n\
+             \\end{enumerate}\n\
+             \\end{document}\n"
+        );
+
+        // The appendix follows the problems, still in their columns
+        let worksheet = generator.generate_with_answers(&problems, &answers);
+        assert!(worksheet.starts_with(&generator.preamble(true)));
+        assert!(worksheet.contains("\\end{multicols}\n\\newpage\n\\section*{Answers}\n"));
+    }
+}